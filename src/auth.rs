@@ -0,0 +1,124 @@
+//! Shared secrets for the HTTP API.
+//!
+//! The optional control password (set via `POST /setauth`) arrives as
+//! HTTP Basic credentials, base64-decoded here; the `POST /ota` token as
+//! a bearer token. Either is checked with [`tokens_match`]. The user name
+//! of Basic credentials is ignored: there is only one password.
+
+/// Longest control password accepted.
+pub const MAX_AUTH_PASSWORD_LEN: usize = 64;
+
+/// Longest `user:password` pair decoded from a Basic header.
+const MAX_USER_PASS_LEN: usize = 2 * MAX_AUTH_PASSWORD_LEN;
+
+/// The password of an `Authorization: Basic <base64 of user:password>`
+/// header value. `None` if it isn't Basic, the base64 or UTF-8 is
+/// malformed, there is no `:`, or the password is too long.
+pub fn basic_password(value: &str) -> Option<heapless::String<MAX_AUTH_PASSWORD_LEN>> {
+    let (scheme, encoded) = value.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = base64_decode::<MAX_USER_PASS_LEN>(encoded.trim())?;
+    let decoded = core::str::from_utf8(&decoded).ok()?;
+    let (_user, password) = decoded.split_once(':')?;
+    heapless::String::try_from(password).ok()
+}
+
+/// True when `given` equals `expected`. Takes as long for any `given` of
+/// the same length, so timing doesn't reveal how much of it matched.
+pub fn tokens_match(expected: &str, given: &str) -> bool {
+    let (expected, given) = (expected.as_bytes(), given.as_bytes());
+    let diff = expected
+        .iter()
+        .zip(given)
+        .fold(0, |diff, (a, b)| diff | (a ^ b));
+    !expected.is_empty() && expected.len() == given.len() && diff == 0
+}
+
+/// Decode standard, padded base64. `None` for a length that isn't a
+/// multiple of 4, a character outside the alphabet, misplaced padding or
+/// more than `N` bytes of output.
+fn base64_decode<const N: usize>(encoded: &str) -> Option<heapless::Vec<u8, N>> {
+    let encoded = encoded.as_bytes();
+    if !encoded.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut out = heapless::Vec::new();
+    let quads = encoded.as_chunks::<4>().0;
+    for (i, quad) in quads.iter().enumerate() {
+        let padding = quad.iter().rev().take_while(|&&c| c == b'=').count();
+        if padding > 2 || (padding > 0 && i + 1 < quads.len()) {
+            return None;
+        }
+        let mut n = 0u32;
+        for &c in &quad[..4 - padding] {
+            n = n << 6 | u32::from(base64_value(c)?);
+        }
+        n <<= 6 * padding;
+        for &byte in &n.to_be_bytes()[1..4 - padding] {
+            out.push(byte).ok()?;
+        }
+    }
+    Some(out)
+}
+
+/// The 6-bit value of a base64 character.
+fn base64_value(c: u8) -> Option<u8> {
+    match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_basic_credentials() {
+        // RFC 7617's example: "Aladdin:open sesame".
+        let password = basic_password("Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==").unwrap();
+        assert_eq!(password.as_str(), "open sesame");
+        // Empty user name, and a password with a colon in it.
+        let password = basic_password("basic OmE6Yg==").unwrap();
+        assert_eq!(password.as_str(), "a:b");
+    }
+
+    #[test]
+    fn rejects_malformed_credentials() {
+        for value in [
+            "Bearer QWxhZGRpbjpvcGVuIHNlc2FtZQ==",
+            "Basic",
+            "Basic QWxhZGRpbg==",               // no colon
+            "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ", // unpadded
+            "Basic QW=hZGRp",
+            "Basic QWxh*GRp",
+        ] {
+            assert_eq!(basic_password(value), None, "{value}");
+        }
+    }
+
+    #[test]
+    fn base64_handles_every_padding() {
+        assert_eq!(base64_decode::<8>("").unwrap().as_slice(), b"");
+        assert_eq!(base64_decode::<8>("Zg==").unwrap().as_slice(), b"f");
+        assert_eq!(base64_decode::<8>("Zm8=").unwrap().as_slice(), b"fo");
+        assert_eq!(base64_decode::<8>("Zm9v").unwrap().as_slice(), b"foo");
+        assert_eq!(base64_decode::<8>("Zm9vYg==").unwrap().as_slice(), b"foob");
+        assert_eq!(base64_decode::<2>("Zm9v"), None);
+    }
+
+    #[test]
+    fn tokens_must_match_exactly() {
+        assert!(tokens_match("s3cret", "s3cret"));
+        assert!(!tokens_match("s3cret", "s3creT"));
+        assert!(!tokens_match("s3cret", "s3cre"));
+        assert!(!tokens_match("s3cret", "s3crets"));
+        assert!(!tokens_match("", ""));
+    }
+}
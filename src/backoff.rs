@@ -0,0 +1,88 @@
+//! Exponential backoff between reconnection attempts.
+//!
+//! Each failed attempt doubles the wait, up to a cap, so a device whose
+//! router is gone for hours doesn't hammer the radio, yet a brief drop is
+//! retried quickly. [`Backoff::reset`] starts over once connected again,
+//! and [`Backoff::failures`] counts the attempts in between.
+
+/// Retry delays that double after every attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    initial_ms: u32,
+    max_ms: u32,
+    next_ms: u32,
+    failures: u32,
+}
+
+impl Backoff {
+    /// Start at `initial_ms` and never wait longer than `max_ms`.
+    pub const fn new(initial_ms: u32, max_ms: u32) -> Self {
+        Self {
+            initial_ms,
+            max_ms,
+            next_ms: initial_ms,
+            failures: 0,
+        }
+    }
+
+    /// How long to wait before the next attempt, in milliseconds. Each
+    /// call counts as one failed attempt.
+    pub fn next_delay_ms(&mut self) -> u32 {
+        let delay = self.next_ms.min(self.max_ms);
+        self.next_ms = delay.saturating_mul(2);
+        self.failures = self.failures.saturating_add(1);
+        delay
+    }
+
+    /// Failed attempts since creation or the last [`Self::reset`].
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Go back to the initial delay, e.g. after a successful attempt.
+    pub fn reset(&mut self) {
+        self.next_ms = self.initial_ms;
+        self.failures = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays_double_up_to_the_cap() {
+        let mut backoff = Backoff::new(1000, 10_000);
+        let delays: [u32; 6] = core::array::from_fn(|_| backoff.next_delay_ms());
+        assert_eq!(delays, [1000, 2000, 4000, 8000, 10_000, 10_000]);
+    }
+
+    #[test]
+    fn reset_starts_over() {
+        let mut backoff = Backoff::new(500, 60_000);
+        backoff.next_delay_ms();
+        backoff.next_delay_ms();
+        backoff.reset();
+        assert_eq!(backoff.next_delay_ms(), 500);
+    }
+
+    #[test]
+    fn failures_count_delays_since_reset() {
+        let mut backoff = Backoff::new(1000, 10_000);
+        assert_eq!(backoff.failures(), 0);
+        for _ in 0..6 {
+            backoff.next_delay_ms();
+        }
+        assert_eq!(backoff.failures(), 6);
+        backoff.reset();
+        assert_eq!(backoff.failures(), 0);
+    }
+
+    #[test]
+    fn never_overflows() {
+        let mut backoff = Backoff::new(u32::MAX / 2 + 1, u32::MAX);
+        assert_eq!(backoff.next_delay_ms(), u32::MAX / 2 + 1);
+        assert_eq!(backoff.next_delay_ms(), u32::MAX);
+        assert_eq!(backoff.next_delay_ms(), u32::MAX);
+    }
+}
@@ -13,6 +13,10 @@
 //!
 //! Extracting this from the GPIO-talking code means the bit patterns can
 //! be unit-tested on the host without any ESP32 hardware.
+//!
+//! [`delay_cycles`] converts the DCLK pulse width into CPU cycles for the
+//! driver's busy-wait, and [`ChainBit::gpio_masks`] packs a bit into
+//! output-register masks so all lines change with one write each.
 
 use crate::chain_mapper::{CHAIN_LEN, ICS_PER_CHAIN};
 
@@ -26,6 +30,16 @@ pub const CONFIG_TOTAL_DCLKS: usize = PWM_BITS * ICS_PER_CHAIN;
 /// `CONFIG_WRITE_PULSES` DCLKs of the config shift).
 pub const CONFIG_WRITE_PULSES: usize = 4;
 
+/// Shortest DCLK high or low phase the driver ICs accept, in
+/// nanoseconds. The driver's default pulse width.
+pub const DCLK_MIN_PULSE_NS: u32 = 10;
+
+/// CPU cycles spanning at least `ns` nanoseconds at `cpu_mhz`, rounded
+/// up so a wait is never shorter than asked.
+pub const fn delay_cycles(ns: u32, cpu_mhz: u32) -> u32 {
+    (ns as u64 * cpu_mhz as u64).div_ceil(1000) as u32
+}
+
 /// One DCLK cycle worth of pin states.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ChainBit {
@@ -52,6 +66,31 @@ impl ChainBit {
             le_high,
         }
     }
+
+    /// Masks for the GPIO set and clear registers (`OUT_W1TS` /
+    /// `OUT_W1TC`): `(high, low)`. `data_pins` are the GPIO numbers of
+    /// the data lines in field order (R1, G1, B1, R2, G2, B2). `le_pin`
+    /// is only ever set here, never cleared — the driver drops LE itself
+    /// once the chain is shifted.
+    pub const fn gpio_masks(&self, data_pins: &[u8; 6], le_pin: u8) -> (u32, u32) {
+        let lines = [self.r1, self.g1, self.b1, self.r2, self.g2, self.b2];
+        let mut high = 0;
+        let mut low = 0;
+        let mut i = 0;
+        while i < lines.len() {
+            let mask = 1 << data_pins[i];
+            if lines[i] {
+                high |= mask;
+            } else {
+                low |= mask;
+            }
+            i += 1;
+        }
+        if self.le_high {
+            high |= 1 << le_pin;
+        }
+        (high, low)
+    }
 }
 
 /// Yield the 22 × 16 = 352 DCLKs of bit-pattern for sending one
@@ -153,6 +192,46 @@ mod tests {
         }
     }
 
+    /// The README's wiring: DR1, DG1, DB1, DR2, DG2, DB2 on GPIO7-10, 20
+    /// and 21, LE on GPIO2.
+    const DATA_PINS: [u8; 6] = [7, 8, 9, 10, 20, 21];
+    const LE_PIN: u8 = 2;
+
+    #[test]
+    fn gpio_masks_split_data_lines_into_set_and_clear() {
+        let bit = ChainBit {
+            r1: true,
+            g1: false,
+            b1: false,
+            r2: false,
+            g2: true,
+            b2: false,
+            le_high: false,
+        };
+        let (high, low) = bit.gpio_masks(&DATA_PINS, LE_PIN);
+        assert_eq!(high, 1 << 7 | 1 << 20);
+        assert_eq!(low, 1 << 8 | 1 << 9 | 1 << 10 | 1 << 21);
+    }
+
+    #[test]
+    fn gpio_masks_set_le_but_never_clear_it() {
+        let (high, low) = ChainBit::broadcast(true, true).gpio_masks(&DATA_PINS, LE_PIN);
+        assert_eq!(high, 0b11 << 20 | 0b1111 << 7 | 1 << LE_PIN);
+        assert_eq!(low, 0);
+        let (high, low) = ChainBit::broadcast(false, false).gpio_masks(&DATA_PINS, LE_PIN);
+        assert_eq!(high, 0);
+        assert_eq!(low & 1 << LE_PIN, 0);
+    }
+
+    #[test]
+    fn delay_cycles_round_up() {
+        // 10 ns at 160 MHz is 1.6 cycles.
+        assert_eq!(delay_cycles(DCLK_MIN_PULSE_NS, 160), 2);
+        assert_eq!(delay_cycles(1000, 160), 160);
+        assert_eq!(delay_cycles(0, 160), 0);
+        assert_eq!(delay_cycles(u32::MAX, 160), 687_194_768);
+    }
+
     #[test]
     fn leds_per_ic_constant_matches_chain_data_block_size() {
         assert_eq!(LEDS_PER_IC, PWM_BITS);
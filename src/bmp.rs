@@ -0,0 +1,342 @@
+//! Decoder for BMP images uploaded to `/image`.
+//!
+//! Handles what paint programs save for an RGB picture: an uncompressed
+//! 24-bit BMP with a `BITMAPINFOHEADER`, or one of its longer V4/V5
+//! successors, whose extra fields are ignored. Pixels are stored blue,
+//! green, red; rows bottom-up (top-down for a negative height), each
+//! padded to a multiple of 4 bytes.
+//!
+//! [`BmpDecoder`] takes the file in pieces as it arrives, so it never has
+//! to fit in memory: each pixel goes straight into an RGB565 image the
+//! size of the display, with smaller pictures centred on black.
+
+use crate::{MATRIX_HEIGHT, MATRIX_WIDTH};
+
+/// File header (14 bytes) plus `BITMAPINFOHEADER` (40 bytes).
+pub const BMP_HEADER_LEN: usize = 54;
+
+/// Bytes of the file header, before the info header.
+const FILE_HEADER_LEN: usize = 14;
+
+/// Longest info header accepted: `BITMAPV5HEADER`.
+const MAX_INFO_HEADER_LEN: usize = 124;
+
+/// Largest BMP `/image` accepts: a full-screen picture after the longest
+/// header.
+pub const MAX_BMP_LEN: usize =
+    FILE_HEADER_LEN + MAX_INFO_HEADER_LEN + row_stride(MATRIX_WIDTH) * MATRIX_HEIGHT;
+
+/// An RGB565 image the size of the display, row by row.
+pub type Image = [u16; MATRIX_WIDTH * MATRIX_HEIGHT];
+
+/// Why a BMP was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BmpError {
+    /// No `BM` signature, or a header this decoder doesn't know.
+    NotBmp,
+    /// Run-length encoded or bit-field pixels.
+    Compressed,
+    /// Not 24 bits per pixel.
+    BitDepth,
+    /// Wider or taller than the display.
+    TooLarge,
+    /// The file ends before its last row.
+    Truncated,
+}
+
+impl BmpError {
+    /// What to tell the uploader.
+    pub fn message(self) -> &'static str {
+        match self {
+            Self::NotBmp => "Not a BMP image",
+            Self::Compressed => "Compressed BMP images are not supported, save it uncompressed",
+            Self::BitDepth => "Only 24-bit BMP images are supported",
+            Self::TooLarge => "BMP image is larger than the display",
+            Self::Truncated => "BMP image ends early",
+        }
+    }
+}
+
+/// Bytes in one stored row of a `width`-pixel image, padding included.
+const fn row_stride(width: usize) -> usize {
+    (width * 3).div_ceil(4) * 4
+}
+
+/// The fields of a BMP header the decoder needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BmpHeader {
+    pub width: usize,
+    pub height: usize,
+    /// Rows stored top row first, instead of the usual bottom row first.
+    pub top_down: bool,
+    /// Where the pixel rows start in the file.
+    pub data_offset: usize,
+}
+
+impl BmpHeader {
+    /// Parse the first [`BMP_HEADER_LEN`] bytes of a file and check it is
+    /// an image the decoder can show.
+    pub fn parse(bytes: &[u8]) -> Result<Self, BmpError> {
+        if bytes.len() < BMP_HEADER_LEN || !bytes.starts_with(b"BM") {
+            return Err(BmpError::NotBmp);
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+        let u32_at =
+            |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let data_offset = u32_at(10) as usize;
+        let info_len = u32_at(14) as usize;
+        if !(BMP_HEADER_LEN - FILE_HEADER_LEN..=MAX_INFO_HEADER_LEN).contains(&info_len)
+            || data_offset < FILE_HEADER_LEN + info_len
+            || u16_at(26) != 1
+        {
+            return Err(BmpError::NotBmp);
+        }
+        if u32_at(30) != 0 {
+            return Err(BmpError::Compressed);
+        }
+        if u16_at(28) != 24 {
+            return Err(BmpError::BitDepth);
+        }
+        let (width, height) = (u32_at(18) as i32, u32_at(22) as i32);
+        if width <= 0 || height == 0 {
+            return Err(BmpError::NotBmp);
+        }
+        let header = Self {
+            width: width as usize,
+            height: height.unsigned_abs() as usize,
+            top_down: height < 0,
+            data_offset,
+        };
+        if header.width > MATRIX_WIDTH || header.height > MATRIX_HEIGHT {
+            return Err(BmpError::TooLarge);
+        }
+        Ok(header)
+    }
+
+    /// Offset just past the last pixel row.
+    pub fn pixels_end(&self) -> usize {
+        self.data_offset + row_stride(self.width) * self.height
+    }
+}
+
+/// Decodes a BMP fed to it in pieces. See the module docs.
+pub struct BmpDecoder {
+    /// The header, until it is complete and parsed.
+    start: [u8; BMP_HEADER_LEN],
+    header: Option<BmpHeader>,
+    /// Bytes of the file fed so far.
+    pos: usize,
+    /// Blue, green and red of the pixel being read.
+    bgr: [u8; 3],
+}
+
+impl Default for BmpDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BmpDecoder {
+    pub const fn new() -> Self {
+        Self {
+            start: [0; BMP_HEADER_LEN],
+            header: None,
+            pos: 0,
+            bgr: [0; 3],
+        }
+    }
+
+    /// Decode the next `bytes` of the file into `image`. Once the header
+    /// is in, `image` is cleared to black and every pixel lands as soon
+    /// as its last byte arrives.
+    pub fn feed(&mut self, bytes: &[u8], image: &mut Image) -> Result<(), BmpError> {
+        for &byte in bytes {
+            self.take(byte, image)?;
+        }
+        Ok(())
+    }
+
+    /// Check the whole picture arrived, after the last [`Self::feed`].
+    pub fn finish(&self) -> Result<BmpHeader, BmpError> {
+        match self.header {
+            Some(header) if self.pos >= header.pixels_end() => Ok(header),
+            Some(_) => Err(BmpError::Truncated),
+            None => Err(BmpError::NotBmp),
+        }
+    }
+
+    fn take(&mut self, byte: u8, image: &mut Image) -> Result<(), BmpError> {
+        let pos = self.pos;
+        self.pos += 1;
+        let Some(header) = self.header else {
+            self.start[pos] = byte;
+            if self.pos == BMP_HEADER_LEN {
+                self.header = Some(BmpHeader::parse(&self.start)?);
+                image.fill(0);
+            }
+            return Ok(());
+        };
+        if pos < header.data_offset || pos >= header.pixels_end() {
+            return Ok(());
+        }
+        let offset = pos - header.data_offset;
+        let stride = row_stride(header.width);
+        let (row, byte_in_row) = (offset / stride, offset % stride);
+        if byte_in_row >= header.width * 3 {
+            // Row padding.
+            return Ok(());
+        }
+        self.bgr[byte_in_row % 3] = byte;
+        if byte_in_row % 3 == 2 {
+            let row = if header.top_down {
+                row
+            } else {
+                header.height - 1 - row
+            };
+            let y = (MATRIX_HEIGHT - header.height) / 2 + row;
+            let x = (MATRIX_WIDTH - header.width) / 2 + byte_in_row / 3;
+            let [b, g, r] = self.bgr.map(u16::from);
+            image[y * MATRIX_WIDTH + x] = (r >> 3) << 11 | (g >> 2) << 5 | b >> 3;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `width` x `height` BMP with `info_len` bytes of info header,
+    /// pixel `(x, y)` (top-left origin) colored `[x, y, 0xFF]` as RGB.
+    fn bmp(width: i32, height: i32, info_len: usize) -> heapless::Vec<u8, MAX_BMP_LEN> {
+        let rows = height.unsigned_abs() as usize;
+        let stride = row_stride(width as usize);
+        let offset = FILE_HEADER_LEN + info_len;
+        let mut out = heapless::Vec::new();
+        out.resize(offset + stride * rows, 0).unwrap();
+        out[..2].copy_from_slice(b"BM");
+        let len = out.len() as u32;
+        out[2..6].copy_from_slice(&len.to_le_bytes());
+        out[10..14].copy_from_slice(&(offset as u32).to_le_bytes());
+        out[14..18].copy_from_slice(&(info_len as u32).to_le_bytes());
+        out[18..22].copy_from_slice(&width.to_le_bytes());
+        out[22..26].copy_from_slice(&height.to_le_bytes());
+        out[26..28].copy_from_slice(&1u16.to_le_bytes());
+        out[28..30].copy_from_slice(&24u16.to_le_bytes());
+        for stored in 0..rows {
+            let y = if height < 0 {
+                stored
+            } else {
+                rows - 1 - stored
+            };
+            for x in 0..width as usize {
+                let at = offset + stored * stride + x * 3;
+                out[at..at + 3].copy_from_slice(&[0xFF, y as u8, x as u8]);
+            }
+        }
+        out
+    }
+
+    fn decode(file: &[u8]) -> Result<Image, BmpError> {
+        let mut image = [0x1234; MATRIX_WIDTH * MATRIX_HEIGHT];
+        let mut decoder = BmpDecoder::new();
+        decoder.feed(file, &mut image)?;
+        decoder.finish()?;
+        Ok(image)
+    }
+
+    /// RGB565 of the test pattern's pixel `(x, y)`.
+    fn expected(x: usize, y: usize) -> u16 {
+        (x as u16 >> 3) << 11 | (y as u16 >> 2) << 5 | 0x1F
+    }
+
+    #[test]
+    fn full_screen_image_is_flipped_to_top_down() {
+        let image = decode(&bmp(MATRIX_WIDTH as i32, MATRIX_HEIGHT as i32, 40)).unwrap();
+        for y in 0..MATRIX_HEIGHT {
+            for x in 0..MATRIX_WIDTH {
+                assert_eq!(image[y * MATRIX_WIDTH + x], expected(x, y), "({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn small_image_is_centred_on_black() {
+        // 3 pixels wide: 9 bytes of each row, then 3 of padding.
+        let image = decode(&bmp(3, 2, 40)).unwrap();
+        let (x0, y0) = ((MATRIX_WIDTH - 3) / 2, (MATRIX_HEIGHT - 2) / 2);
+        for y in 0..MATRIX_HEIGHT {
+            for x in 0..MATRIX_WIDTH {
+                let inside = (x0..x0 + 3).contains(&x) && (y0..y0 + 2).contains(&y);
+                let color = if inside { expected(x - x0, y - y0) } else { 0 };
+                assert_eq!(image[y * MATRIX_WIDTH + x], color, "({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn top_down_rows_and_longer_headers_decode_the_same() {
+        let bottom_up = decode(&bmp(5, 4, 40)).unwrap();
+        assert_eq!(decode(&bmp(5, -4, 40)).unwrap(), bottom_up);
+        assert_eq!(decode(&bmp(5, 4, 124)).unwrap(), bottom_up);
+    }
+
+    #[test]
+    fn pieces_decode_like_the_whole_file() {
+        let file = bmp(7, 3, 108);
+        let mut image = [0; MATRIX_WIDTH * MATRIX_HEIGHT];
+        let mut decoder = BmpDecoder::new();
+        for piece in file.chunks(5) {
+            decoder.feed(piece, &mut image).unwrap();
+        }
+        let header = decoder.finish().unwrap();
+        assert_eq!(
+            (header.width, header.height, header.top_down),
+            (7, 3, false)
+        );
+        assert_eq!(image, decode(&file).unwrap());
+    }
+
+    #[test]
+    fn unsupported_files_are_rejected() {
+        let mut compressed = bmp(4, 4, 40);
+        compressed[30] = 1;
+        let mut eight_bit = bmp(4, 4, 40);
+        eight_bit[28] = 8;
+        let mut not_bmp = bmp(4, 4, 40);
+        not_bmp[0] = b'P';
+        let mut unknown_header = bmp(4, 4, 40);
+        unknown_header[14] = 12;
+        for (file, error) in [
+            (&compressed[..], BmpError::Compressed),
+            (&eight_bit, BmpError::BitDepth),
+            (&not_bmp, BmpError::NotBmp),
+            (&unknown_header, BmpError::NotBmp),
+            (&bmp(MATRIX_WIDTH as i32 + 1, 1, 40), BmpError::TooLarge),
+            (&bmp(1, -(MATRIX_HEIGHT as i32) - 1, 40), BmpError::TooLarge),
+            (&bmp(0, 4, 40), BmpError::NotBmp),
+            (&bmp(4, 4, 40)[..BMP_HEADER_LEN - 1], BmpError::NotBmp),
+        ] {
+            assert_eq!(decode(file), Err(error));
+        }
+    }
+
+    #[test]
+    fn file_ending_before_the_last_row_is_truncated() {
+        let file = bmp(4, 4, 40);
+        assert_eq!(decode(&file[..file.len() - 1]), Err(BmpError::Truncated));
+    }
+
+    #[test]
+    fn no_bmp_has_the_length_of_a_raw_image() {
+        // `/image` tells the two apart by length first.
+        for info_len in [40, 108, 124] {
+            for height in 1..=MATRIX_HEIGHT {
+                for width in 1..=MATRIX_WIDTH {
+                    let len = FILE_HEADER_LEN + info_len + row_stride(width) * height;
+                    assert_ne!(len, MATRIX_WIDTH * MATRIX_HEIGHT * 2);
+                }
+            }
+        }
+    }
+}
@@ -7,70 +7,160 @@
 //! - `data[0..10]` and `data[11..21]` are chain 1's two row groups
 //! - `data[22..32]` and `data[33..43]` are chain 2's two row groups
 //!
+//! Each IC drives a block of 8 columns in two rows, so the 22 ICs x 16
+//! LEDs of a chain cover 4 rows of all 88 columns per scanline, not 352
+//! columns. For scanline `s`, `data[i]` is the IC drawing:
+//!
+//! ```text
+//!   rows     | cols 0-7    | 8-15 |     | 80-87|
+//!            +-------------+------+ ... +------+
+//!   s, s+11  |  data[33]   | [34] |     | [43] |  \
+//!            +-------------+------+ ... +------+   } chain 2
+//!  s+22,s+33 |  data[22]   | [23] |     | [32] |  /
+//!            +-------------+------+ ... +------+
+//!  s+44,s+55 |  data[11]   | [12] |     | [21] |  \
+//!            +-------------+------+ ... +------+   } chain 1
+//!  s+66,s+77 |  data[0]    | [1]  |     | [10] |  /
+//!            +-------------+------+ ... +------+
+//! ```
+//!
+//! In each block, leds 8..15 light the upper row's columns left to right
+//! and leds 0..7 the lower row's right to left.
+//!
+//! With the `two-panels` feature, a second panel to the right takes its
+//! data from the first one's outputs, so each chain is 44 ICs long and
+//! the vector 88 slots. The right panel's ICs, reached last, are the
+//! first 22 of each chain: `data[0..22]` and `data[44..66]`, laid out as
+//! above, and the left panel's follow.
+//!
 //! The translation between the linear 88x88 bitmap and this 44-element
 //! ordering is non-obvious — it's a direct port of the `getChainData`
 //! function from the wiki's reference implementation. See
-//! [`compute_chain_data`] for the mapping.
+//! [`HacklabMapper`] for the mapping. A panel wired another way can
+//! bring its own [`PixelMapper`] and stream with [`map_chain_data`].
 //!
 //! This module is pure data transformation; it has no GPIO dependencies
 //! and is fully unit-testable on the host.
 
-use crate::frame_buffer::Pixel;
+use crate::frame_buffer::{StoredPixel, load_pixel};
+use crate::{MATRIX_HEIGHT, MATRIX_WIDTH, PANEL_WIDTH, PANELS};
 
 /// 11 scanlines (multiplexing factor).
 pub const SCANLINES: usize = 11;
 
-/// Number of ICs per chain.
-pub const ICS_PER_CHAIN: usize = 22;
+/// ICs of one panel on each chain.
+pub const PANEL_ICS: usize = 22;
+
+/// Number of ICs per chain, across all [`PANELS`].
+pub const ICS_PER_CHAIN: usize = PANEL_ICS * PANELS;
 
 /// LEDs driven by each IC.
 pub const LEDS_PER_IC: usize = 16;
 
 /// Length of the per-cycle chain vector.
-pub const CHAIN_LEN: usize = 44;
+pub const CHAIN_LEN: usize = 2 * ICS_PER_CHAIN;
 
-/// Translate the 88x88 frame buffer into the 44-pixel ordering the chain
-/// hardware expects for one `(scanline, led)` cycle.
+/// Where each chain slot's pixel is on a panel: given a `(scanline,
+/// led)` cycle and an IC position in one of the two chains, the
+/// frame-buffer pixel `(x, y)` that LED lights.
 ///
-/// Per call: fills `data[0..10]` and `data[11..21]` with chain 1's two row
-/// groups, and `data[22..32]` and `data[33..43]` with chain 2's two row
-/// groups for this `(scanline, led)`. The frame buffer is indexed as
+/// The driver streams frames in hardware order, so this is the
+/// direction it needs. [`HacklabMapper`], the default, is the Hacklab
+/// panel's wiring; a panel wired differently (serpentine rows, ICs in
+/// another order) can bring its own, as a type or as a closure
+/// `Fn(scanline, led, chain, ic) -> (x, y)`. Over the
+/// [`SCANLINES`] x [`LEDS_PER_IC`] cycles of a frame, every pixel must
+/// come out exactly once, and always within the frame.
+pub trait PixelMapper {
+    /// The pixel `(x, y)` for IC `ic` (`0..ICS_PER_CHAIN`, in shift
+    /// order) of `chain` in the `(scanline, led)` cycle. `chain` is 0 for
+    /// chain 1 (`data[..ICS_PER_CHAIN]`) and 1 for chain 2.
+    fn pixel(&self, scanline: usize, led: usize, chain: usize, ic: usize) -> (usize, usize);
+}
+
+impl<F: Fn(usize, usize, usize, usize) -> (usize, usize)> PixelMapper for F {
+    fn pixel(&self, scanline: usize, led: usize, chain: usize, ic: usize) -> (usize, usize) {
+        self(scanline, led, chain, ic)
+    }
+}
+
+/// The Hacklab panel's wiring, as in the module docs: a direct port of
+/// the wiki's `getChainData`.
+///
+/// Scanline `s` addresses the eight rows `s + 11 * k`: leds 8..15 take
+/// the even `k` and leds 0..7 the odd ones, so over a frame the 11
+/// scanlines reach all 88 rows. Chain 2 (`data[ICS_PER_CHAIN..]`)
+/// carries the top half, rows 0..44, and chain 1 the bottom half. Each
+/// 11-IC group spans the width, every 8th column, so the 16 leds of a
+/// scanline reach all 88 columns of its rows. Chained panels repeat
+/// this per panel, the rightmost first.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HacklabMapper;
+
+impl PixelMapper for HacklabMapper {
+    #[inline]
+    fn pixel(&self, scanline: usize, led: usize, chain: usize, ic: usize) -> (usize, usize) {
+        // led 0..7 picks one row-group of the scanline; led 8..15 picks
+        // the other.
+        let led_row = if led < 8 { 11 } else { 0 };
+        let panel = PANELS - 1 - ic / PANEL_ICS;
+        let (group, i) = (ic % PANEL_ICS / 11, ic % 11);
+        // Four row blocks of 22 rows, top to bottom: chain 2's second
+        // group, its first, then chain 1's second and first.
+        let block = 3 - (2 * chain + group);
+        let x = PANEL_WIDTH * panel + 8 * i + led_column(led);
+        (x, scanline + led_row + 22 * block)
+    }
+}
+
+/// Translate the frame buffer into the ordering the chain hardware
+/// expects for one `(scanline, led)` cycle of the Hacklab panel: the
+/// chain vector for [`HacklabMapper`]. The frame buffer is indexed as
 /// `pixels[y][x]`.
 pub fn compute_chain_data(
     scanline: usize,
     led: usize,
-    pixels: &[[Pixel; 88]; 88],
+    pixels: &[[StoredPixel; MATRIX_WIDTH]; MATRIX_HEIGHT],
     data: &mut [[u16; 3]; CHAIN_LEN],
 ) {
-    // led 0..7 picks one row-group of the scanline; led 8..15 picks the
-    // other. ledColumn is the per-group column offset, reversed for the
-    // first group to match the physical wiring.
-    let led_row: usize = if led < 8 { 11 } else { 0 };
-    let mut led_column: usize = led % 8;
-    let mut row: usize = scanline + led_row;
-    if led_row == 11 {
-        led_column = 7 - led_column;
-    }
+    map_chain_data(&HacklabMapper, scanline, led, pixels, data);
+}
 
-    // Four row blocks, each contributing 11 columns of one row. The start
-    // index descends (33, 22, 11, 0) because the loop writes the highest
-    // row block first.
-    const STARTS: [usize; 4] = [33, 22, 11, 0];
-    for &start in &STARTS {
-        for i in 0..11usize {
-            let col = 8 * i + led_column;
-            data[start + i] = pixels[row][col];
-        }
-        row += 22;
+/// [`compute_chain_data`] for a panel wired as `mapper` says.
+pub fn map_chain_data<M: PixelMapper + ?Sized>(
+    mapper: &M,
+    scanline: usize,
+    led: usize,
+    pixels: &[[StoredPixel; MATRIX_WIDTH]; MATRIX_HEIGHT],
+    data: &mut [[u16; 3]; CHAIN_LEN],
+) {
+    for (slot, value) in data.iter_mut().enumerate() {
+        let (x, y) = mapper.pixel(scanline, led, slot / ICS_PER_CHAIN, slot % ICS_PER_CHAIN);
+        *value = load_pixel(pixels[y][x]);
     }
 }
 
+/// Frame-buffer column that `data[slot]` comes from in a `led` cycle
+/// of [`compute_chain_data`] (the same for every scanline). Lets callers
+/// treat chain data by column, e.g. for a left-to-right wipe.
+pub fn chain_column(led: usize, slot: usize) -> usize {
+    HacklabMapper
+        .pixel(0, led, slot / ICS_PER_CHAIN, slot % ICS_PER_CHAIN)
+        .0
+}
+
+/// Per-group column offset of `led`, reversed for the first row group
+/// (leds 0..7) to match the physical wiring.
+fn led_column(led: usize) -> usize {
+    if led < 8 { 7 - led } else { led % 8 }
+}
+
 /// Build a complete frame's worth of (scanline, led) → chain-data pairs.
 ///
 /// Returns 11 × 16 = 176 cycles, each with its associated 44-pixel vector.
 /// The caller typically streams these directly to the shift registers.
 pub fn compute_full_frame(
-    pixels: &[[Pixel; 88]; 88],
+    pixels: &[[StoredPixel; MATRIX_WIDTH]; MATRIX_HEIGHT],
 ) -> [[[u16; 3]; CHAIN_LEN]; SCANLINES * LEDS_PER_IC] {
     let mut out = [[[0u16; 3]; CHAIN_LEN]; SCANLINES * LEDS_PER_IC];
     for scanline in 0..SCANLINES {
@@ -85,34 +175,49 @@ pub fn compute_full_frame(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::frame_buffer::store_pixel;
+
+    /// Index within each chain of the first IC of `panel` (counted from
+    /// the left). The rightmost panel is farthest along the chain, so its
+    /// ICs come first.
+    const fn panel_first_ic(panel: usize) -> usize {
+        (PANELS - 1 - panel) * PANEL_ICS
+    }
+
+    /// Where the left panel's ICs start in each chain (0 for one panel).
+    const LEFT: usize = panel_first_ic(0);
+
+    /// Slot of the IC drawing the top-left block: `data[33]` on a single
+    /// panel.
+    const TOP_LEFT: usize = ICS_PER_CHAIN + 11 + LEFT;
 
     /// Build a frame buffer where every pixel has the same color — easy
     /// to assert against.
-    fn uniform_pixels(r: u16, g: u16, b: u16) -> [[Pixel; 88]; 88] {
-        [[r, g, b]; 88].map(|row| [row; 88])
+    fn uniform_pixels(r: u16, g: u16, b: u16) -> [[StoredPixel; MATRIX_WIDTH]; MATRIX_HEIGHT] {
+        [[store_pixel([r, g, b]); MATRIX_WIDTH]; MATRIX_HEIGHT]
     }
 
     #[test]
     fn uniform_buffer_round_trips_through_mapper() {
-        let px = uniform_pixels(7, 11, 13);
+        let px = uniform_pixels(0x707, 0xB0B, 0xD0D);
         let mut data = [[0u16; 3]; CHAIN_LEN];
         compute_chain_data(0, 0, &px, &mut data);
 
         // Every chain pixel should be the same as the source uniform color.
         for entry in data.iter() {
-            assert_eq!(*entry, [7, 11, 13]);
+            assert_eq!(*entry, [0x707, 0xB0B, 0xD0D]);
         }
     }
 
     #[test]
     fn full_frame_has_expected_cycle_count() {
-        let px = uniform_pixels(1, 2, 3);
+        let px = uniform_pixels(0x101, 0x202, 0x303);
         let frame = compute_full_frame(&px);
         // SCANLINES * LEDS_PER_IC = 11 * 16 = 176 cycles
         assert_eq!(frame.len(), SCANLINES * LEDS_PER_IC);
         for cycle in frame.iter() {
             for entry in cycle.iter() {
-                assert_eq!(*entry, [1, 2, 3]);
+                assert_eq!(*entry, [0x101, 0x202, 0x303]);
             }
         }
     }
@@ -140,10 +245,10 @@ mod tests {
 
         // Now try (scanline=0, led=8): led_row=0 → row=0. led_column = 0
         // (since led%8 = 0). The first row block reads row 0, starting at
-        // data[33]. data[33] = pixels[0][0] = [42, 43, 44].
+        // data[TOP_LEFT]. data[TOP_LEFT] = pixels[0][0] = [42, 43, 44].
         let mut data = [[0u16; 3]; CHAIN_LEN];
         compute_chain_data(0, 8, &px, &mut data);
-        assert_eq!(data[33], [42, 43, 44]);
+        assert_eq!(data[TOP_LEFT], load_pixel([42, 43, 44]));
     }
 
     #[test]
@@ -153,19 +258,86 @@ mod tests {
         //   - led_row=11 (led<8)
         //   - led_column starts at led%8=0, then is flipped to 7-0=7
         //   - row = scanline+11 = 11
-        //   - First row block starts at data[33], reading pixels[11][7]
+        //   - First row block starts at data[TOP_LEFT], reading pixels[11][7]
         px[11][7] = [9, 9, 9];
         let mut data = [[0u16; 3]; CHAIN_LEN];
         compute_chain_data(0, 0, &px, &mut data);
-        assert_eq!(data[33], [9, 9, 9]);
+        assert_eq!(data[TOP_LEFT], load_pixel([9, 9, 9]));
 
         // For (scanline=0, led=7):
         //   - led_row=11, led_column=7, flipped to 7-7=0
         //   - First row block reads pixels[11][0]
         let mut data = [[0u16; 3]; CHAIN_LEN];
         compute_chain_data(0, 7, &px, &mut data);
-        // data[33] = pixels[11][0] which is still zero (only col 7 was set)
-        assert_eq!(data[33], [0, 0, 0]);
+        // data[TOP_LEFT] = pixels[11][0] which is still zero (only col 7 was set)
+        assert_eq!(data[TOP_LEFT], [0, 0, 0]);
+    }
+
+    #[test]
+    fn chain_column_matches_mapping() {
+        // Each pixel holds its own column, so chain data shows where it
+        // was read from.
+        let px = [[0; 3]; MATRIX_HEIGHT].map(|_| core::array::from_fn(|x| [x as _, 0, 0]));
+        for led in 0..LEDS_PER_IC {
+            let mut data = [[0u16; 3]; CHAIN_LEN];
+            compute_chain_data(3, led, &px, &mut data);
+            for (slot, &entry) in data.iter().enumerate() {
+                assert_eq!(store_pixel(entry)[0] as usize, chain_column(led, slot));
+            }
+        }
+    }
+
+    #[test]
+    fn every_pixel_is_sent_exactly_once_per_frame() {
+        // Each pixel holds its own coordinates.
+        let mut px = [[[0; 3]; MATRIX_WIDTH]; MATRIX_HEIGHT];
+        for (y, row) in px.iter_mut().enumerate() {
+            for (x, entry) in row.iter_mut().enumerate() {
+                *entry = [x as _, y as _, 0];
+            }
+        }
+        let mut sent = [[0u8; MATRIX_WIDTH]; MATRIX_HEIGHT];
+        for (cycle, data) in compute_full_frame(&px).iter().enumerate() {
+            let scanline = cycle / LEDS_PER_IC;
+            for (slot, &entry) in data.iter().enumerate() {
+                let [x, y, _] = store_pixel(entry).map(usize::from);
+                assert_eq!(y % SCANLINES, scanline, "scanline {scanline} sent row {y}");
+                let chain = if slot < ICS_PER_CHAIN { 1 } else { 2 };
+                assert_eq!(
+                    chain,
+                    if y < 44 { 2 } else { 1 },
+                    "row {y} on chain {chain}"
+                );
+                sent[y][x] += 1;
+            }
+        }
+        assert!(sent.iter().flatten().all(|&n| n == 1));
+    }
+
+    #[test]
+    fn each_ic_drives_eight_columns_of_two_rows() {
+        for slot in 0..CHAIN_LEN {
+            let mut lit = [0u8; MATRIX_WIDTH];
+            for led in 0..LEDS_PER_IC {
+                lit[chain_column(led, slot)] += 1;
+            }
+            let ic = slot % ICS_PER_CHAIN;
+            let panel = PANELS - 1 - ic / PANEL_ICS;
+            let first = PANEL_WIDTH * panel + 8 * (ic % 11);
+            for (col, &n) in lit.iter().enumerate() {
+                let expected = if (first..first + 8).contains(&col) {
+                    2
+                } else {
+                    0
+                };
+                assert_eq!(n, expected, "slot {slot}, column {col}");
+            }
+        }
+        // Leds 8..15 run left to right, leds 0..7 right to left.
+        assert_eq!(chain_column(8, LEFT + 12), 8);
+        assert_eq!(chain_column(15, LEFT + 12), 15);
+        assert_eq!(chain_column(0, LEFT + 12), 15);
+        assert_eq!(chain_column(7, LEFT + 12), 8);
     }
 
     #[test]
@@ -178,6 +350,6 @@ mod tests {
         px[0][0] = [1, 2, 3];
         let mut data = [[0u16; 3]; CHAIN_LEN];
         compute_chain_data(0, 8, &px, &mut data);
-        assert_eq!(data[33], [1, 2, 3]);
+        assert_eq!(data[TOP_LEFT], load_pixel([1, 2, 3]));
     }
 }
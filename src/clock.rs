@@ -0,0 +1,149 @@
+//! Wall clock for the clock display mode.
+//!
+//! Once the station is online an SNTP task asks [`NTP_SERVER`] for the
+//! time, and again every [`RESYNC_INTERVAL_SECS`]. In between, time is
+//! extrapolated from the embassy tick counter. The time zone is a POSIX
+//! TZ string saved in flash (see [`esp32_led_matrix::timezone`]); until
+//! the first reply the clock face shows dashes.
+
+use embassy_executor::Spawner;
+use embassy_net::dns::DnsQueryType;
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_net::{IpEndpoint, Stack};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::{Duration, Instant, Timer, with_timeout};
+use esp32_led_matrix::mode;
+use esp32_led_matrix::sntp;
+use esp32_led_matrix::timezone::{DEFAULT_TZ, MAX_TZ_LEN, TimeZone};
+use log::{info, warn};
+
+use crate::nvs::{self, Slot};
+use crate::wifi;
+
+/// NTP server pool queried for the time.
+const NTP_SERVER: &str = "pool.ntp.org";
+
+/// Seconds between successful syncs.
+const RESYNC_INTERVAL_SECS: u64 = 3600;
+
+/// Seconds before retrying a failed sync.
+const RETRY_INTERVAL_SECS: u64 = 30;
+
+/// How long to wait for a server reply.
+const REPLY_TIMEOUT_SECS: u64 = 5;
+
+/// Unix time at the last sync and the tick count it was taken at.
+/// `None` until the first SNTP reply.
+static SYNCED: Mutex<CriticalSectionRawMutex, Option<(i64, Instant)>> = Mutex::new(None);
+
+/// Time zone used by [`clock_text`].
+static TIMEZONE: Mutex<CriticalSectionRawMutex, TimeZone> = Mutex::new(TimeZone::UTC);
+
+/// Restore the time zone saved by [`set_timezone`]. Call once at boot.
+pub async fn init_timezone() {
+    let saved: Option<heapless::String<MAX_TZ_LEN>> = nvs::load(Slot::Timezone).await;
+    let tz = saved.as_deref().unwrap_or(DEFAULT_TZ);
+    match TimeZone::parse(tz) {
+        Some(zone) => {
+            *TIMEZONE.lock().await = zone;
+            info!("Time zone: {}", tz);
+        }
+        None => warn!("Ignoring invalid saved time zone {}", tz),
+    }
+}
+
+/// Switch to the POSIX TZ string `tz` and save it. Invalid strings are
+/// logged and ignored.
+pub async fn set_timezone(tz: &str) {
+    let Some(zone) = TimeZone::parse(tz) else {
+        warn!("Ignoring invalid time zone {}", tz);
+        return;
+    };
+    *TIMEZONE.lock().await = zone;
+    nvs::save(Slot::Timezone, tz).await;
+    info!("Time zone set to {}", tz);
+}
+
+/// Start keeping time over SNTP on the station `stack`.
+pub fn start_clock(spawner: Spawner, stack: &'static Stack<'static>) {
+    spawner.spawn(sntp_task(stack)).ok();
+}
+
+/// Current Unix time, or `None` before the first sync.
+pub async fn now_unix() -> Option<i64> {
+    let (unix, at) = (*SYNCED.lock().await)?;
+    Some(unix + at.elapsed().as_secs() as i64)
+}
+
+/// Clock face text for the current local time. See
+/// [`mode::clock_text`].
+pub async fn clock_text(seconds: bool) -> heapless::String<8> {
+    let tz = *TIMEZONE.lock().await;
+    mode::clock_text(now_unix().await, &tz, seconds)
+}
+
+/// Sync the clock whenever the station is online.
+#[embassy_executor::task]
+async fn sntp_task(stack: &'static Stack<'static>) {
+    loop {
+        wifi::wait_for_connection(stack).await;
+        let delay = match query_time(stack).await {
+            Some(unix) => {
+                *SYNCED.lock().await = Some((unix, Instant::now()));
+                info!("Clock synced: Unix time {}", unix);
+                RESYNC_INTERVAL_SECS
+            }
+            None => RETRY_INTERVAL_SECS,
+        };
+        Timer::after(Duration::from_secs(delay)).await;
+    }
+}
+
+/// Ask [`NTP_SERVER`] for the time. `None` (after logging) on DNS,
+/// socket or protocol errors, or if no reply arrives in time.
+async fn query_time(stack: &'static Stack<'static>) -> Option<i64> {
+    let addrs = match stack.dns_query(NTP_SERVER, DnsQueryType::A).await {
+        Ok(addrs) => addrs,
+        Err(e) => {
+            warn!("NTP server lookup failed: {:?}", e);
+            return None;
+        }
+    };
+    let server = IpEndpoint::new(*addrs.first()?, sntp::NTP_PORT);
+
+    let mut rx_meta = [PacketMetadata::EMPTY; 2];
+    let mut rx_buffer = [0u8; 128];
+    let mut tx_meta = [PacketMetadata::EMPTY; 2];
+    let mut tx_buffer = [0u8; 128];
+    let mut socket = UdpSocket::new(
+        *stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    if let Err(e) = socket.bind(0) {
+        warn!("SNTP bind error: {:?}", e);
+        return None;
+    }
+
+    let nonce = Instant::now().as_ticks();
+    if let Err(e) = socket.send_to(&sntp::request(nonce), server).await {
+        warn!("SNTP request failed: {:?}", e);
+        return None;
+    }
+    let mut reply = [0u8; sntp::PACKET_LEN];
+    let timeout = Duration::from_secs(REPLY_TIMEOUT_SECS);
+    loop {
+        let Ok(Ok((len, _))) = with_timeout(timeout, socket.recv_from(&mut reply)).await else {
+            warn!("No SNTP reply from {}", NTP_SERVER);
+            return None;
+        };
+        // Stray packets (e.g. a late reply to an earlier query) are
+        // skipped.
+        if let Some(unix) = sntp::parse_reply(&reply[..len], nonce) {
+            return Some(unix);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Drawing commands sent from the network layer to the display loop.
+//!
+//! The HTTP server doesn't own the [`FrameBuffer`] — the refresh loop
+//! does. Requests that draw into the frame (as opposed to replacing the
+//! display text) are turned into a [`DrawCommand`] by
+//! [`crate::http_request::dispatch`], queued by the server, and applied by
+//! the refresh loop between frames via [`DrawCommand::apply`].
+
+use crate::frame_buffer::{FrameBuffer, Pixel};
+use crate::gradient::GradientDir;
+use crate::http_request::MAX_MESSAGE_LEN;
+
+/// One drawing operation on the frame buffer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DrawCommand {
+    /// Set the pixel at `(x, y)` to `color`.
+    Pixel { x: i32, y: i32, color: Pixel },
+    /// Set every pixel to `color`.
+    Fill { color: Pixel },
+    /// Fill with a gradient from `from` to `to`, running `dir`.
+    Gradient {
+        from: Pixel,
+        to: Pixel,
+        dir: GradientDir,
+    },
+    /// A line from `(x0, y0)` to `(x1, y1)` inclusive.
+    Line {
+        x0: i32,
+        y0: i32,
+        x1: i32,
+        y1: i32,
+        color: Pixel,
+    },
+    /// A `width` x `height` rectangle with its top-left corner at
+    /// `(x, y)`: filled, or just its outline.
+    Rect {
+        x: i32,
+        y: i32,
+        width: usize,
+        height: usize,
+        color: Pixel,
+        fill: bool,
+    },
+    /// `text` with its top-left corner at `(x, y)`, over the current
+    /// picture.
+    Text {
+        x: i32,
+        y: i32,
+        text: heapless::String<MAX_MESSAGE_LEN>,
+        color: Pixel,
+    },
+}
+
+impl DrawCommand {
+    /// Apply this command to `fb`.
+    pub fn apply(&self, fb: &mut FrameBuffer) {
+        match *self {
+            DrawCommand::Pixel { x, y, color } => {
+                let [r, g, b] = color;
+                fb.set_pixel_16(x, y, r, g, b);
+            }
+            DrawCommand::Fill { color } => {
+                let [r, g, b] = color;
+                fb.fill(r, g, b);
+            }
+            DrawCommand::Gradient { from, to, dir } => fb.fill_gradient(from, to, dir),
+            DrawCommand::Line {
+                x0,
+                y0,
+                x1,
+                y1,
+                color: [r, g, b],
+            } => fb.draw_line(x0, y0, x1, y1, r, g, b),
+            DrawCommand::Rect {
+                x,
+                y,
+                width,
+                height,
+                color: [r, g, b],
+                fill,
+            } => {
+                if fill {
+                    fb.fill_rect(x, y, width, height, r, g, b);
+                } else {
+                    fb.draw_rect(x, y, width, height, r, g, b);
+                }
+            }
+            DrawCommand::Text {
+                x,
+                y,
+                ref text,
+                color: [r, g, b],
+            } => fb.draw_text(text, x, y, r, g, b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixel_command_sets_pixel() {
+        let mut fb = FrameBuffer::new();
+        DrawCommand::Pixel {
+            x: 5,
+            y: 6,
+            color: [0x101, 0x202, 0x303],
+        }
+        .apply(&mut fb);
+        assert_eq!(fb.get_pixel(5, 6), [0x101, 0x202, 0x303]);
+    }
+
+    #[test]
+    fn fill_command_fills_buffer() {
+        let mut fb = FrameBuffer::new();
+        DrawCommand::Fill {
+            color: [0x404, 0x505, 0x606],
+        }
+        .apply(&mut fb);
+        assert_eq!(fb.get_pixel(0, 0), [0x404, 0x505, 0x606]);
+        assert_eq!(fb.get_pixel(87, 87), [0x404, 0x505, 0x606]);
+    }
+
+    #[test]
+    fn rect_command_fills_or_outlines() {
+        let mut fb = FrameBuffer::new();
+        let rect = |fill| DrawCommand::Rect {
+            x: 2,
+            y: 2,
+            width: 3,
+            height: 3,
+            color: [0x909; 3],
+            fill,
+        };
+        rect(false).apply(&mut fb);
+        assert_eq!(fb.get_pixel(2, 2), [0x909; 3]);
+        assert_eq!(fb.get_pixel(3, 3), [0, 0, 0]);
+        rect(true).apply(&mut fb);
+        assert_eq!(fb.get_pixel(3, 3), [0x909; 3]);
+    }
+
+    #[test]
+    fn line_and_text_commands_draw() {
+        let mut fb = FrameBuffer::new();
+        DrawCommand::Line {
+            x0: 0,
+            y0: 0,
+            x1: 3,
+            y1: 3,
+            color: [0x101; 3],
+        }
+        .apply(&mut fb);
+        assert_eq!(fb.get_pixel(2, 2), [0x101; 3]);
+        DrawCommand::Text {
+            x: 40,
+            y: 40,
+            text: heapless::String::try_from("I").unwrap(),
+            color: [0x202; 3],
+        }
+        .apply(&mut fb);
+        assert_eq!(fb.get_pixel(42, 41), [0x202; 3]);
+    }
+}
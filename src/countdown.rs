@@ -0,0 +1,141 @@
+//! The `/timer` countdown.
+//!
+//! A [`Countdown`] keeps the time left and, while it runs, when it was
+//! last started, all on a caller-supplied millisecond clock (any
+//! monotonic one, e.g. time since boot). The time left is worked out
+//! from the clock on every call, so it stays accurate however unevenly
+//! the refresh loop asks.
+
+/// Longest countdown: `99:59`, the most `MM:SS` can show.
+pub const MAX_TIMER_SECONDS: u32 = 99 * 60 + 59;
+
+/// How long "DONE" is shown, and then blanked, while it flashes.
+pub const DONE_FLASH_MS: u64 = 500;
+
+/// What a `/timer` request does to the countdown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerCommand {
+    /// Start counting down from this many seconds, replacing any
+    /// countdown there was.
+    Start(u32),
+    /// Pause a running countdown, or resume a paused one.
+    Pause,
+}
+
+/// A countdown to zero. See the module docs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Countdown {
+    /// Time left as of `running_since`, or for good while paused.
+    left_ms: u64,
+    /// When the countdown was last started or resumed; `None` while
+    /// paused.
+    running_since: Option<u64>,
+}
+
+impl Countdown {
+    /// A countdown that has already run out.
+    pub const fn new() -> Self {
+        Self {
+            left_ms: 0,
+            running_since: None,
+        }
+    }
+
+    /// Apply `command` at `now_ms`.
+    pub fn apply(&mut self, command: TimerCommand, now_ms: u64) {
+        match command {
+            TimerCommand::Start(seconds) => {
+                self.left_ms = u64::from(seconds) * 1000;
+                self.running_since = Some(now_ms);
+            }
+            TimerCommand::Pause => match self.running_since {
+                Some(_) => {
+                    self.left_ms = self.left_ms(now_ms);
+                    self.running_since = None;
+                }
+                None => self.running_since = Some(now_ms),
+            },
+        }
+    }
+
+    /// Milliseconds left at `now_ms`.
+    pub fn left_ms(&self, now_ms: u64) -> u64 {
+        match self.running_since {
+            Some(since) => self.left_ms.saturating_sub(now_ms.saturating_sub(since)),
+            None => self.left_ms,
+        }
+    }
+
+    /// True once the countdown has reached zero.
+    pub fn is_done(&self, now_ms: u64) -> bool {
+        self.left_ms(now_ms) == 0
+    }
+
+    /// What to show at `now_ms`: the time left as `MM:SS`, rounded up so
+    /// `00:00` never shows, then "DONE", blinking every
+    /// [`DONE_FLASH_MS`] (empty while it is off).
+    pub fn text(&self, now_ms: u64) -> heapless::String<5> {
+        let mut text = heapless::String::new();
+        let left_ms = self.left_ms(now_ms);
+        if left_ms == 0 {
+            let done_for = match self.running_since {
+                Some(since) => now_ms.saturating_sub(since + self.left_ms),
+                None => 0,
+            };
+            if (done_for / DONE_FLASH_MS).is_multiple_of(2) {
+                let _ = text.push_str("DONE");
+            }
+            return text;
+        }
+        let seconds = left_ms.div_ceil(1000).min(MAX_TIMER_SECONDS.into());
+        for (i, value) in [seconds / 60, seconds % 60].into_iter().enumerate() {
+            if i > 0 {
+                let _ = text.push(':');
+            }
+            let _ = text.push((b'0' + (value / 10) as u8) as char);
+            let _ = text.push((b'0' + (value % 10) as u8) as char);
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_down_by_the_clock() {
+        let mut countdown = Countdown::new();
+        countdown.apply(TimerCommand::Start(300), 10_000);
+        assert_eq!(countdown.text(10_000), "05:00");
+        assert_eq!(countdown.text(10_001), "05:00");
+        assert_eq!(countdown.text(11_000), "04:59");
+        assert_eq!(countdown.text(309_999), "00:01");
+        assert!(!countdown.is_done(309_999));
+        assert!(countdown.is_done(310_000));
+    }
+
+    #[test]
+    fn pause_holds_the_time_left() {
+        let mut countdown = Countdown::new();
+        countdown.apply(TimerCommand::Start(60), 0);
+        countdown.apply(TimerCommand::Pause, 15_500);
+        assert_eq!(countdown.text(100_000), "00:45");
+        countdown.apply(TimerCommand::Pause, 100_000);
+        assert_eq!(countdown.text(100_500), "00:44");
+        assert!(countdown.is_done(144_500));
+    }
+
+    #[test]
+    fn done_flashes() {
+        let mut countdown = Countdown::new();
+        countdown.apply(TimerCommand::Start(1), 0);
+        assert_eq!(countdown.text(1_000), "DONE");
+        assert_eq!(countdown.text(1_000 + DONE_FLASH_MS), "");
+        assert_eq!(countdown.text(1_000 + 2 * DONE_FLASH_MS), "DONE");
+        // Paused at zero, it stays on.
+        countdown.apply(TimerCommand::Pause, 5_000);
+        assert_eq!(countdown.text(5_700), "DONE");
+        assert_eq!(Countdown::new().text(0), "DONE");
+    }
+}
@@ -0,0 +1,69 @@
+//! What the panel shows after a panic, until the chip resets.
+//!
+//! A panicking firmware can't trust the frame buffers or whatever held
+//! them, so its panic hook starts the panel afresh and sends
+//! [`crash_pixel`] with [`Panel::send_pattern`](crate::panel::Panel::send_pattern),
+//! which needs no frame buffer: a red X on black inside a red border
+//! that blinks every [`CRASH_BLINK_MS`]. Stale content never looks like
+//! that, so a unit nobody can attach a serial console to still shows it
+//! crashed.
+
+use crate::frame_buffer::Pixel;
+use crate::{MATRIX_HEIGHT, MATRIX_WIDTH};
+
+/// How long the border stays lit, then dark.
+pub const CRASH_BLINK_MS: u64 = 500;
+
+/// Width of the border, in pixels.
+pub const CRASH_BORDER: usize = 2;
+
+/// Color of the X and the border.
+pub const CRASH_COLOR: Pixel = [0xFFFF, 0, 0];
+
+/// The crash screen's pixel at `(x, y)`, `elapsed_ms` after it first
+/// showed. The X runs corner to corner, three pixels thick, and spans
+/// every panel of the display.
+pub fn crash_pixel(x: usize, y: usize, elapsed_ms: u64) -> Pixel {
+    let border = x < CRASH_BORDER
+        || y < CRASH_BORDER
+        || x >= MATRIX_WIDTH - CRASH_BORDER
+        || y >= MATRIX_HEIGHT - CRASH_BORDER;
+    let border_lit = (elapsed_ms / CRASH_BLINK_MS).is_multiple_of(2);
+    // Column `x` on a square display as tall as this one.
+    let u = x * MATRIX_HEIGHT / MATRIX_WIDTH;
+    let cross = u.abs_diff(y) <= 1 || (u + y).abs_diff(MATRIX_HEIGHT - 1) <= 1;
+    if cross || (border && border_lit) {
+        CRASH_COLOR
+    } else {
+        [0, 0, 0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_x_joins_the_corners() {
+        let (right, bottom) = (MATRIX_WIDTH - 1, MATRIX_HEIGHT - 1);
+        for (x, y) in [(0, 0), (right, 0), (0, bottom), (right, bottom)] {
+            assert_eq!(crash_pixel(x, y, CRASH_BLINK_MS), CRASH_COLOR);
+        }
+        assert_eq!(
+            crash_pixel(MATRIX_WIDTH / 2, MATRIX_HEIGHT / 2, 0),
+            CRASH_COLOR
+        );
+        assert_eq!(crash_pixel(MATRIX_WIDTH / 2, 20, 0), [0, 0, 0]);
+    }
+
+    #[test]
+    fn the_border_blinks() {
+        let (x, y) = (MATRIX_WIDTH / 2, 1);
+        assert_eq!(crash_pixel(x, y, 0), CRASH_COLOR);
+        assert_eq!(crash_pixel(x, y, CRASH_BLINK_MS - 1), CRASH_COLOR);
+        assert_eq!(crash_pixel(x, y, CRASH_BLINK_MS), [0, 0, 0]);
+        assert_eq!(crash_pixel(x, y, 2 * CRASH_BLINK_MS), CRASH_COLOR);
+        // Just inside the border stays dark.
+        assert_eq!(crash_pixel(x, CRASH_BORDER, 0), [0, 0, 0]);
+    }
+}
@@ -0,0 +1,34 @@
+//! CRC-32, as used by the ESP bootloader's `otadata` entries and by PNG
+//! chunks.
+
+/// Reflected CRC-32 (polynomial `0xEDB88320`) of `data`, starting from
+/// register value `crc`, without the final inversion. Seed with `!0` and
+/// invert the result for the usual CRC-32; calls chain to cover data in
+/// pieces.
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    data.iter().fold(crc, |crc, &byte| {
+        (0..8).fold(crc ^ u32::from(byte), |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc_is_standard_crc32() {
+        assert_eq!(!crc32_update(!0, b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn updates_chain() {
+        let whole = crc32_update(!0, b"123456789");
+        assert_eq!(crc32_update(crc32_update(!0, b"1234"), b"56789"), whole);
+    }
+}
@@ -0,0 +1,72 @@
+//! WiFi station credentials.
+//!
+//! Entered on the `/setup` page, saved to flash by the firmware's `nvs`
+//! module and read back at boot, so moving the display to another
+//! network no longer needs a reflash. Validation lives here so the
+//! HTTP parser and the flash loader agree on what a usable pair is.
+
+/// Longest SSID allowed by 802.11.
+pub const MAX_SSID_LEN: usize = 32;
+
+/// Longest WPA2 passphrase.
+pub const MAX_PASSWORD_LEN: usize = 64;
+
+/// Shortest WPA2 passphrase. An empty password means an open network.
+const MIN_PASSWORD_LEN: usize = 8;
+
+/// SSID and password for station mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub ssid: heapless::String<MAX_SSID_LEN>,
+    pub password: heapless::String<MAX_PASSWORD_LEN>,
+}
+
+impl Credentials {
+    /// Validate and copy a credential pair. Returns `None` for an empty or
+    /// over-long SSID, or a password that is neither empty (open network)
+    /// nor a valid WPA2 passphrase length.
+    pub fn new(ssid: &str, password: &str) -> Option<Self> {
+        if ssid.is_empty() {
+            return None;
+        }
+        if !password.is_empty() && password.len() < MIN_PASSWORD_LEN {
+            return None;
+        }
+        Some(Self {
+            ssid: heapless::String::try_from(ssid).ok()?,
+            password: heapless::String::try_from(password).ok()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_wpa2_and_open_networks() {
+        let creds = Credentials::new("hacklab", "correct horse").unwrap();
+        assert_eq!(creds.ssid.as_str(), "hacklab");
+        assert_eq!(creds.password.as_str(), "correct horse");
+        assert!(Credentials::new("cafe", "").is_some());
+    }
+
+    #[test]
+    fn rejects_empty_ssid() {
+        assert!(Credentials::new("", "password").is_none());
+    }
+
+    #[test]
+    fn rejects_short_password() {
+        assert!(Credentials::new("hacklab", "1234567").is_none());
+    }
+
+    #[test]
+    fn rejects_over_long_values() {
+        let long = [b'a'; MAX_PASSWORD_LEN + 1];
+        let long = core::str::from_utf8(&long).unwrap();
+        assert!(Credentials::new(&long[..MAX_SSID_LEN + 1], "password").is_none());
+        assert!(Credentials::new("hacklab", long).is_none());
+        assert!(Credentials::new(&long[..MAX_SSID_LEN], &long[..MAX_PASSWORD_LEN]).is_some());
+    }
+}
@@ -0,0 +1,361 @@
+//! Minimal DHCP server for the setup access point.
+//!
+//! embassy-net only ships a DHCP *client*, so phones joining the setup AP
+//! would otherwise never get an address. This covers just enough of
+//! RFC 2131 for that: DISCOVER → OFFER and REQUEST → ACK, or NAK for an
+//! address this server didn't hand out. Leases never expire — the pool
+//! is tiny and the AP only lives until WiFi credentials are saved.
+//!
+//! Clients get consecutive addresses after the server's own, in the
+//! order they first show up. The server advertises itself as router and
+//! DNS server.
+
+/// UDP port the server listens on.
+pub const SERVER_PORT: u16 = 67;
+
+/// UDP port replies are sent to.
+pub const CLIENT_PORT: u16 = 68;
+
+/// Most clients that get an address at once.
+pub const MAX_LEASES: usize = 8;
+
+/// Size of the reply buffer callers must provide. BOOTP packets are at
+/// least 300 bytes.
+pub const REPLY_LEN: usize = 300;
+
+/// Advertised lease time. Clients renew well before it runs out, and
+/// renewals are always granted.
+const LEASE_SECS: u32 = 3600;
+
+/// Start of the options field (fixed BOOTP header + magic cookie).
+const OPTIONS_OFFSET: usize = 240;
+
+const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+
+const BOOTREQUEST: u8 = 1;
+const BOOTREPLY: u8 = 2;
+
+const OPT_PAD: u8 = 0;
+const OPT_SUBNET_MASK: u8 = 1;
+const OPT_ROUTER: u8 = 3;
+const OPT_DNS: u8 = 6;
+const OPT_REQUESTED_IP: u8 = 50;
+const OPT_LEASE_TIME: u8 = 51;
+const OPT_MESSAGE_TYPE: u8 = 53;
+const OPT_SERVER_ID: u8 = 54;
+const OPT_END: u8 = 255;
+
+const DHCPDISCOVER: u8 = 1;
+const DHCPOFFER: u8 = 2;
+const DHCPREQUEST: u8 = 3;
+const DHCPACK: u8 = 5;
+const DHCPNAK: u8 = 6;
+
+/// Hands out addresses on a /24 shared with the server.
+pub struct DhcpServer {
+    server_ip: [u8; 4],
+    /// Client hardware addresses; lease `i` is `server_ip + 1 + i`.
+    leases: heapless::Vec<[u8; 6], MAX_LEASES>,
+}
+
+impl DhcpServer {
+    /// Create a server for `server_ip`, which must leave room for
+    /// [`MAX_LEASES`] addresses after it in the same /24.
+    pub const fn new(server_ip: [u8; 4]) -> Self {
+        Self {
+            server_ip,
+            leases: heapless::Vec::new(),
+        }
+    }
+
+    /// Handle one request packet. Writes the reply into `reply` and
+    /// returns its length, or `None` when there is nothing to send
+    /// (malformed packet, a request for another server, or a full pool).
+    pub fn handle(&mut self, request: &[u8], reply: &mut [u8; REPLY_LEN]) -> Option<usize> {
+        if request.len() < OPTIONS_OFFSET
+            || request[0] != BOOTREQUEST
+            || request[236..240] != MAGIC_COOKIE
+        {
+            return None;
+        }
+        let options = &request[OPTIONS_OFFSET..];
+        let mut mac = [0u8; 6];
+        mac.copy_from_slice(&request[28..34]);
+
+        let (message_type, yiaddr) = match find_option(options, OPT_MESSAGE_TYPE)? {
+            [DHCPDISCOVER] => (DHCPOFFER, self.lease_for(mac)?),
+            [DHCPREQUEST] => {
+                if let Some(server_id) = find_option(options, OPT_SERVER_ID)
+                    && server_id != self.server_ip
+                {
+                    // The client picked another server's offer.
+                    return None;
+                }
+                let requested = match find_option(options, OPT_REQUESTED_IP) {
+                    Some(&[a, b, c, d]) => [a, b, c, d],
+                    // Renewals carry the address in `ciaddr` instead.
+                    _ => [request[12], request[13], request[14], request[15]],
+                };
+                match self.lease_for(mac) {
+                    Some(ip) if ip == requested => (DHCPACK, ip),
+                    _ => (DHCPNAK, [0; 4]),
+                }
+            }
+            _ => return None,
+        };
+
+        Some(self.build_reply(request, message_type, yiaddr, reply))
+    }
+
+    /// Address leased to `mac`, allocating one on first sight. `None`
+    /// when the pool is full.
+    fn lease_for(&mut self, mac: [u8; 6]) -> Option<[u8; 4]> {
+        let index = match self.leases.iter().position(|m| *m == mac) {
+            Some(index) => index,
+            None => {
+                self.leases.push(mac).ok()?;
+                self.leases.len() - 1
+            }
+        };
+        let [a, b, c, d] = self.server_ip;
+        Some([a, b, c, d.wrapping_add(1 + index as u8)])
+    }
+
+    fn build_reply(
+        &self,
+        request: &[u8],
+        message_type: u8,
+        yiaddr: [u8; 4],
+        reply: &mut [u8; REPLY_LEN],
+    ) -> usize {
+        reply.fill(0);
+        reply[0] = BOOTREPLY;
+        // htype, hlen, hops, xid, secs and flags echo the request.
+        reply[1..12].copy_from_slice(&request[1..12]);
+        reply[3] = 0;
+        reply[16..20].copy_from_slice(&yiaddr);
+        reply[20..24].copy_from_slice(&self.server_ip);
+        // giaddr + chaddr.
+        reply[24..44].copy_from_slice(&request[24..44]);
+        reply[236..240].copy_from_slice(&MAGIC_COOKIE);
+
+        let mut options = OptionWriter {
+            buf: &mut reply[..],
+            pos: OPTIONS_OFFSET,
+        };
+        options.put(OPT_MESSAGE_TYPE, &[message_type]);
+        options.put(OPT_SERVER_ID, &self.server_ip);
+        if message_type != DHCPNAK {
+            options.put(OPT_LEASE_TIME, &LEASE_SECS.to_be_bytes());
+            options.put(OPT_SUBNET_MASK, &[255, 255, 255, 0]);
+            options.put(OPT_ROUTER, &self.server_ip);
+            options.put(OPT_DNS, &self.server_ip);
+        }
+        options.buf[options.pos] = OPT_END;
+        REPLY_LEN
+    }
+}
+
+/// Appends `code, len, data` option triples.
+struct OptionWriter<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl OptionWriter<'_> {
+    fn put(&mut self, code: u8, data: &[u8]) {
+        self.buf[self.pos] = code;
+        self.buf[self.pos + 1] = data.len() as u8;
+        self.buf[self.pos + 2..self.pos + 2 + data.len()].copy_from_slice(data);
+        self.pos += 2 + data.len();
+    }
+}
+
+/// Value of option `code`, if present and well-formed.
+fn find_option(mut options: &[u8], code: u8) -> Option<&[u8]> {
+    loop {
+        match *options.first()? {
+            OPT_END => return None,
+            OPT_PAD => options = &options[1..],
+            current => {
+                let len = *options.get(1)? as usize;
+                let value = options.get(2..2 + len)?;
+                if current == code {
+                    return Some(value);
+                }
+                options = &options[2 + len..];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERVER: [u8; 4] = [192, 168, 4, 1];
+    const MAC_A: [u8; 6] = [2, 0, 0, 0, 0, 0xA];
+    const MAC_B: [u8; 6] = [2, 0, 0, 0, 0, 0xB];
+
+    /// Build a client packet with the given options (without the end
+    /// marker).
+    fn request(mac: [u8; 6], options: &[u8]) -> heapless::Vec<u8, 300> {
+        let mut packet = heapless::Vec::new();
+        packet.resize(OPTIONS_OFFSET, 0).unwrap();
+        packet[0] = BOOTREQUEST;
+        packet[1] = 1;
+        packet[2] = 6;
+        packet[4..8].copy_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF]);
+        packet[28..34].copy_from_slice(&mac);
+        packet[236..240].copy_from_slice(&MAGIC_COOKIE);
+        packet.extend_from_slice(options).unwrap();
+        packet.push(OPT_END).unwrap();
+        packet
+    }
+
+    fn reply_type(reply: &[u8]) -> u8 {
+        find_option(&reply[OPTIONS_OFFSET..], OPT_MESSAGE_TYPE).unwrap()[0]
+    }
+
+    #[test]
+    fn discover_gets_offer_with_next_address() {
+        let mut server = DhcpServer::new(SERVER);
+        let mut reply = [0u8; REPLY_LEN];
+        let len = server
+            .handle(
+                &request(MAC_A, &[OPT_MESSAGE_TYPE, 1, DHCPDISCOVER]),
+                &mut reply,
+            )
+            .unwrap();
+        assert_eq!(len, REPLY_LEN);
+        assert_eq!(reply[0], BOOTREPLY);
+        assert_eq!(reply[4..8], [0xDE, 0xAD, 0xBE, 0xEF]);
+        assert_eq!(reply[16..20], [192, 168, 4, 2]);
+        assert_eq!(reply[28..34], MAC_A);
+        assert_eq!(reply_type(&reply), DHCPOFFER);
+        let options = &reply[OPTIONS_OFFSET..];
+        assert_eq!(find_option(options, OPT_SERVER_ID), Some(&SERVER[..]));
+        assert_eq!(find_option(options, OPT_DNS), Some(&SERVER[..]));
+    }
+
+    #[test]
+    fn clients_get_distinct_stable_addresses() {
+        let mut server = DhcpServer::new(SERVER);
+        let mut reply = [0u8; REPLY_LEN];
+        let discover = [OPT_MESSAGE_TYPE, 1, DHCPDISCOVER];
+        server.handle(&request(MAC_A, &discover), &mut reply);
+        server.handle(&request(MAC_B, &discover), &mut reply);
+        assert_eq!(reply[16..20], [192, 168, 4, 3]);
+        server.handle(&request(MAC_A, &discover), &mut reply);
+        assert_eq!(reply[16..20], [192, 168, 4, 2]);
+    }
+
+    #[test]
+    fn request_for_offered_address_is_acked() {
+        let mut server = DhcpServer::new(SERVER);
+        let mut reply = [0u8; REPLY_LEN];
+        server.handle(
+            &request(MAC_A, &[OPT_MESSAGE_TYPE, 1, DHCPDISCOVER]),
+            &mut reply,
+        );
+        let req = request(
+            MAC_A,
+            &[
+                OPT_MESSAGE_TYPE,
+                1,
+                DHCPREQUEST,
+                OPT_REQUESTED_IP,
+                4,
+                192,
+                168,
+                4,
+                2,
+                OPT_SERVER_ID,
+                4,
+                192,
+                168,
+                4,
+                1,
+            ],
+        );
+        server.handle(&req, &mut reply).unwrap();
+        assert_eq!(reply_type(&reply), DHCPACK);
+        assert_eq!(reply[16..20], [192, 168, 4, 2]);
+    }
+
+    #[test]
+    fn request_for_foreign_address_is_naked() {
+        let mut server = DhcpServer::new(SERVER);
+        let mut reply = [0u8; REPLY_LEN];
+        let req = request(
+            MAC_A,
+            &[
+                OPT_MESSAGE_TYPE,
+                1,
+                DHCPREQUEST,
+                OPT_REQUESTED_IP,
+                4,
+                10,
+                0,
+                0,
+                7,
+            ],
+        );
+        server.handle(&req, &mut reply).unwrap();
+        assert_eq!(reply_type(&reply), DHCPNAK);
+        assert_eq!(reply[16..20], [0; 4]);
+    }
+
+    #[test]
+    fn request_for_other_server_is_ignored() {
+        let mut server = DhcpServer::new(SERVER);
+        let mut reply = [0u8; REPLY_LEN];
+        let req = request(
+            MAC_A,
+            &[
+                OPT_MESSAGE_TYPE,
+                1,
+                DHCPREQUEST,
+                OPT_SERVER_ID,
+                4,
+                192,
+                168,
+                4,
+                99,
+            ],
+        );
+        assert!(server.handle(&req, &mut reply).is_none());
+    }
+
+    #[test]
+    fn full_pool_stops_offering() {
+        let mut server = DhcpServer::new(SERVER);
+        let mut reply = [0u8; REPLY_LEN];
+        let discover = [OPT_MESSAGE_TYPE, 1, DHCPDISCOVER];
+        for i in 0..MAX_LEASES as u8 {
+            assert!(
+                server
+                    .handle(&request([2, 0, 0, 0, 1, i], &discover), &mut reply)
+                    .is_some()
+            );
+        }
+        assert!(
+            server
+                .handle(&request(MAC_A, &discover), &mut reply)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn malformed_packets_are_ignored() {
+        let mut server = DhcpServer::new(SERVER);
+        let mut reply = [0u8; REPLY_LEN];
+        assert!(server.handle(&[0u8; 10], &mut reply).is_none());
+        let mut no_cookie = request(MAC_A, &[OPT_MESSAGE_TYPE, 1, DHCPDISCOVER]);
+        no_cookie[236] = 0;
+        assert!(server.handle(&no_cookie, &mut reply).is_none());
+        // Truncated option.
+        let truncated = request(MAC_A, &[OPT_MESSAGE_TYPE, 9, DHCPDISCOVER]);
+        assert!(server.handle(&truncated, &mut reply).is_none());
+    }
+}
@@ -0,0 +1,114 @@
+//! Which part of a frame changed since it was last shown.
+//!
+//! The [`FrameBuffer`](crate::frame_buffer::FrameBuffer) grows a
+//! [`DirtyRect`] around everything drawn into it, in panel (physical)
+//! coordinates, and `led_matrix` uses it to skip frames that didn't
+//! change.
+//!
+//! ## What the hardware can skip
+//! Only whole frames. Without new data the driver just keeps clocking
+//! GCLK ([`Panel::hold_frame`]) and the panel repeats the frame it has.
+//! Anything smaller still costs a full [`Panel::send_frame`]:
+//! - Data latches carry no address. The driver ICs count them from the
+//!   last VSYNC, so every `(scanline, led)` cycle has to be shifted, in
+//!   order, for any of them to land in the right place.
+//! - VSYNC swaps the whole display buffer, so the unchanged scanlines
+//!   would have to be in the back buffer too.
+//! - A brightness or gamma change affects every pixel, as does each
+//!   step of a transition.
+//!
+//! So the rectangle says *whether* to send; its extent is there for
+//! callers that only care about part of the panel.
+//!
+//! [`Panel::hold_frame`]: crate::panel::Panel::hold_frame
+//! [`Panel::send_frame`]: crate::panel::Panel::send_frame
+
+use core::ops::Range;
+
+use crate::{MATRIX_HEIGHT, MATRIX_WIDTH};
+
+/// A non-empty rectangle of the panel, in physical coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirtyRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl DirtyRect {
+    /// The whole panel.
+    pub const FULL: Self = Self {
+        x: 0,
+        y: 0,
+        width: MATRIX_WIDTH,
+        height: MATRIX_HEIGHT,
+    };
+
+    /// The part of `width` x `height` at `(x, y)` that is on the panel,
+    /// or `None` if none of it is.
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Option<Self> {
+        let right = x.saturating_add(width).min(MATRIX_WIDTH);
+        let bottom = y.saturating_add(height).min(MATRIX_HEIGHT);
+        // Lazily: past the panel, `right - x` would underflow.
+        (x < right && y < bottom).then(|| Self {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        })
+    }
+
+    /// The smallest rectangle covering both.
+    pub fn union(self, other: Self) -> Self {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = self.columns().end.max(other.columns().end);
+        let bottom = self.rows().end.max(other.rows().end);
+        Self {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+
+    /// Columns covered.
+    pub fn columns(&self) -> Range<usize> {
+        self.x..self.x + self.width
+    }
+
+    /// Rows covered.
+    pub fn rows(&self) -> Range<usize> {
+        self.y..self.y + self.height
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_clips_to_the_panel() {
+        let rect = DirtyRect::new(MATRIX_WIDTH - 2, 5, 10, 3).unwrap();
+        assert_eq!(rect.columns(), MATRIX_WIDTH - 2..MATRIX_WIDTH);
+        assert_eq!(rect.rows(), 5..8);
+        assert_eq!(DirtyRect::new(MATRIX_WIDTH, 0, 1, 1), None);
+        assert_eq!(DirtyRect::new(MATRIX_WIDTH + 1, 0, 1, 1), None);
+        assert_eq!(DirtyRect::new(0, MATRIX_HEIGHT + 1, 1, 1), None);
+        assert_eq!(DirtyRect::new(0, 0, 0, 1), None);
+        assert_eq!(
+            DirtyRect::new(0, 0, usize::MAX, usize::MAX),
+            Some(DirtyRect::FULL)
+        );
+    }
+
+    #[test]
+    fn union_covers_both() {
+        let a = DirtyRect::new(2, 10, 1, 1).unwrap();
+        let b = DirtyRect::new(5, 3, 2, 2).unwrap();
+        let both = a.union(b);
+        assert_eq!((both.columns(), both.rows()), (2..7, 3..11));
+        assert_eq!(both.union(a), both);
+    }
+}
@@ -0,0 +1,192 @@
+//! The display mode and text color, saved across reboots.
+//!
+//! A [`DisplayState`] goes to flash as a query string shaped like the
+//! requests that set it, e.g. `m=marquee&speed=30&r=65535&g=0&b=0`, and
+//! is read back with [`QueryParams`]; the text has a flash slot of its
+//! own. Modes whose content only lives in RAM (the countdown, the graph,
+//! an uploaded animation, the regions, Snake and the test patterns) are
+//! saved as `text`, so after a reboot the saved text shows instead of an
+//! empty screen.
+
+use core::fmt::Write;
+
+use crate::frame_buffer::{DEFAULT_TEXT_COLOR, Pixel};
+use crate::http_request::QueryParams;
+use crate::mode::{DisplayMode, Effect, MAX_EFFECT_SPEED, MAX_MARQUEE_SPEED};
+
+/// Capacity of [`DisplayState::to_record`]'s output; fits the longest
+/// record, a Game of Life with every color channel at its maximum.
+pub const DISPLAY_STATE_LEN: usize = 80;
+
+/// What the display shows, apart from the text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayState {
+    pub mode: DisplayMode,
+    pub color: Pixel,
+}
+
+impl Default for DisplayState {
+    /// Plain text in [`DEFAULT_TEXT_COLOR`], as on first boot.
+    fn default() -> Self {
+        Self {
+            mode: DisplayMode::Text,
+            color: DEFAULT_TEXT_COLOR,
+        }
+    }
+}
+
+impl DisplayState {
+    /// The state as a record for flash. See the module docs.
+    pub fn to_record(&self) -> heapless::String<DISPLAY_STATE_LEN> {
+        let mut record = heapless::String::new();
+        // DISPLAY_STATE_LEN covers the longest possible record.
+        let _ = match self.mode {
+            DisplayMode::Marquee { speed } => write!(record, "m=marquee&speed={speed}"),
+            DisplayMode::VScroll { speed } => write!(record, "m=vscroll&speed={speed}"),
+            DisplayMode::Paragraph => write!(record, "m=paragraph"),
+            DisplayMode::Clock { seconds } => {
+                write!(record, "m=clock&seconds={}", u8::from(seconds))
+            }
+            DisplayMode::Effect { effect, speed } => {
+                let _ = write!(record, "m=effect&name={}&speed={speed}", effect.name());
+                match effect {
+                    Effect::Life { wrap, from_screen } => write!(
+                        record,
+                        "&wrap={}&screen={}",
+                        u8::from(wrap),
+                        u8::from(from_screen)
+                    ),
+                    _ => Ok(()),
+                }
+            }
+            _ => write!(record, "m=text"),
+        };
+        let [r, g, b] = self.color;
+        let _ = write!(record, "&r={r}&g={g}&b={b}");
+        record
+    }
+
+    /// Read back a record written by [`DisplayState::to_record`]. `None`
+    /// if a field is missing or out of range.
+    pub fn from_record(record: &str) -> Option<Self> {
+        let params = QueryParams::from_form(record);
+        let speed = |max: u16| {
+            params
+                .parse::<u16>("speed")
+                .filter(|speed| (1..=max).contains(speed))
+        };
+        let flag = |name: &str| match params.get(name)? {
+            "0" => Some(false),
+            "1" => Some(true),
+            _ => None,
+        };
+        let mode = match params.get("m")? {
+            "text" => DisplayMode::Text,
+            "marquee" => DisplayMode::Marquee {
+                speed: speed(MAX_MARQUEE_SPEED)?,
+            },
+            "vscroll" => DisplayMode::VScroll {
+                speed: speed(MAX_MARQUEE_SPEED)?,
+            },
+            "paragraph" => DisplayMode::Paragraph,
+            "clock" => DisplayMode::Clock {
+                seconds: flag("seconds")?,
+            },
+            "effect" => {
+                let mut effect = Effect::from_name(params.get("name")?)?;
+                if let Effect::Life { wrap, from_screen } = &mut effect {
+                    *wrap = flag("wrap")?;
+                    *from_screen = flag("screen")?;
+                }
+                DisplayMode::Effect {
+                    effect,
+                    speed: speed(MAX_EFFECT_SPEED)?,
+                }
+            }
+            _ => return None,
+        };
+        let color = [params.parse("r")?, params.parse("g")?, params.parse("b")?];
+        Some(Self { mode, color })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::TestPattern;
+
+    #[test]
+    fn saved_modes_read_back() {
+        for mode in [
+            DisplayMode::Text,
+            DisplayMode::Marquee { speed: 30 },
+            DisplayMode::VScroll { speed: 500 },
+            DisplayMode::Paragraph,
+            DisplayMode::Clock { seconds: true },
+            DisplayMode::Effect {
+                effect: Effect::Plasma,
+                speed: 1,
+            },
+            DisplayMode::Effect {
+                effect: Effect::Life {
+                    wrap: false,
+                    from_screen: true,
+                },
+                speed: MAX_EFFECT_SPEED,
+            },
+        ] {
+            let state = DisplayState {
+                mode,
+                color: [0xFFFF, 0xFFFF, 0xFFFF],
+            };
+            assert_eq!(DisplayState::from_record(&state.to_record()), Some(state));
+        }
+        let state = DisplayState {
+            mode: DisplayMode::Marquee { speed: 30 },
+            color: [65535, 0, 0],
+        };
+        assert_eq!(
+            state.to_record().as_str(),
+            "m=marquee&speed=30&r=65535&g=0&b=0"
+        );
+    }
+
+    #[test]
+    fn modes_kept_only_in_ram_are_saved_as_text() {
+        for mode in [
+            DisplayMode::Timer,
+            DisplayMode::Graph,
+            DisplayMode::Animation,
+            DisplayMode::Regions,
+            DisplayMode::Snake,
+            DisplayMode::Test(TestPattern::Bars),
+        ] {
+            let state = DisplayState {
+                mode,
+                color: [1, 2, 3],
+            };
+            let restored = DisplayState::from_record(&state.to_record()).unwrap();
+            assert_eq!(restored.mode, DisplayMode::Text);
+            assert_eq!(restored.color, [1, 2, 3]);
+        }
+    }
+
+    #[test]
+    fn corrupt_records_are_refused() {
+        for record in [
+            "",
+            "m=text",
+            "m=text&r=1&g=2",
+            "m=text&r=1&g=2&b=65536",
+            "m=marquee&r=0&g=0&b=0",
+            "m=marquee&speed=0&r=0&g=0&b=0",
+            "m=vscroll&speed=501&r=0&g=0&b=0",
+            "m=clock&seconds=yes&r=0&g=0&b=0",
+            "m=effect&name=sparkle&speed=100&r=0&g=0&b=0",
+            "m=effect&name=life&speed=100&r=0&g=0&b=0",
+            "m=snake&r=0&g=0&b=0",
+        ] {
+            assert_eq!(DisplayState::from_record(record), None, "{record}");
+        }
+    }
+}
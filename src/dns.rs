@@ -0,0 +1,158 @@
+//! Catch-all DNS responder for the setup captive portal.
+//!
+//! Every `A` query is answered with the device's own address, whatever
+//! the name. Phones and laptops probe a well-known URL right after
+//! joining a network; resolving it to us lets the HTTP server redirect
+//! the probe to `/setup`, which makes the OS pop the setup page up on
+//! its own.
+//!
+//! Other query types get an empty answer rather than an error, so
+//! clients asking for `AAAA` first fall back to `A` quickly.
+
+/// UDP port the responder listens on.
+pub const DNS_PORT: u16 = 53;
+
+/// Largest query handled; anything longer is ignored. Also the size of
+/// the reply buffer callers must provide.
+pub const MAX_PACKET_LEN: usize = 512;
+
+/// TTL of the answers, in seconds. Short, so clients don't keep the
+/// fake address once they're on the real network.
+const TTL_SECS: u32 = 60;
+
+const HEADER_LEN: usize = 12;
+
+/// Length of the answer record: name pointer, type, class, TTL,
+/// length and a 4-byte address.
+const ANSWER_LEN: usize = 16;
+
+const TYPE_A: u16 = 1;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+
+/// Build the reply to `query` in `reply`, answering with `ip`. Returns
+/// the reply length, or `None` for anything that isn't a single-question
+/// standard query.
+pub fn answer(query: &[u8], ip: [u8; 4], reply: &mut [u8; MAX_PACKET_LEN]) -> Option<usize> {
+    if query.len() < HEADER_LEN || query.len() > MAX_PACKET_LEN {
+        return None;
+    }
+    let flags = u16::from_be_bytes([query[2], query[3]]);
+    let is_response = flags & 0x8000 != 0;
+    let opcode = (flags >> 11) & 0xF;
+    let qdcount = u16::from_be_bytes([query[4], query[5]]);
+    if is_response || opcode != 0 || qdcount != 1 {
+        return None;
+    }
+
+    let question_end = question_end(query)?;
+    let qtype = u16::from_be_bytes([query[question_end - 4], query[question_end - 3]]);
+    let qclass = u16::from_be_bytes([query[question_end - 2], query[question_end - 1]]);
+    let answers = (qtype == TYPE_A || qtype == TYPE_ANY) && qclass == CLASS_IN;
+
+    // Header: same id, QR + AA set, RD echoed, one question, no
+    // authority or additional records.
+    reply[0..2].copy_from_slice(&query[0..2]);
+    let reply_flags = 0x8400 | (flags & 0x0100);
+    reply[2..4].copy_from_slice(&reply_flags.to_be_bytes());
+    reply[4..6].copy_from_slice(&1u16.to_be_bytes());
+    reply[6..8].copy_from_slice(&(answers as u16).to_be_bytes());
+    reply[8..12].fill(0);
+    reply[HEADER_LEN..question_end].copy_from_slice(&query[HEADER_LEN..question_end]);
+    if !answers {
+        return Some(question_end);
+    }
+
+    let answer = &mut reply[question_end..question_end + ANSWER_LEN];
+    // Compressed name pointing back at the question.
+    answer[0..2].copy_from_slice(&0xC00Cu16.to_be_bytes());
+    answer[2..4].copy_from_slice(&TYPE_A.to_be_bytes());
+    answer[4..6].copy_from_slice(&CLASS_IN.to_be_bytes());
+    answer[6..10].copy_from_slice(&TTL_SECS.to_be_bytes());
+    answer[10..12].copy_from_slice(&4u16.to_be_bytes());
+    answer[12..16].copy_from_slice(&ip);
+    Some(question_end + ANSWER_LEN)
+}
+
+/// End offset of the (single) question: the name's labels, its
+/// terminating zero, then type and class. `None` if truncated or the
+/// name uses compression, which queries don't.
+fn question_end(query: &[u8]) -> Option<usize> {
+    let mut pos = HEADER_LEN;
+    loop {
+        let len = *query.get(pos)? as usize;
+        if len == 0 {
+            break;
+        }
+        if len & 0xC0 != 0 {
+            return None;
+        }
+        pos += 1 + len;
+    }
+    let end = pos + 1 + 4;
+    (end <= query.len()).then_some(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IP: [u8; 4] = [192, 168, 4, 1];
+
+    /// Standard query with RD set for `name` (dot-separated).
+    fn query(name: &str, qtype: u16) -> heapless::Vec<u8, MAX_PACKET_LEN> {
+        let mut packet = heapless::Vec::new();
+        packet
+            .extend_from_slice(&[0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0])
+            .unwrap();
+        for label in name.split('.') {
+            packet.push(label.len() as u8).unwrap();
+            packet.extend_from_slice(label.as_bytes()).unwrap();
+        }
+        packet.push(0).unwrap();
+        packet.extend_from_slice(&qtype.to_be_bytes()).unwrap();
+        packet.extend_from_slice(&CLASS_IN.to_be_bytes()).unwrap();
+        packet
+    }
+
+    #[test]
+    fn a_query_is_answered_with_own_address() {
+        let q = query("connectivitycheck.gstatic.com", TYPE_A);
+        let mut reply = [0u8; MAX_PACKET_LEN];
+        let len = answer(&q, IP, &mut reply).unwrap();
+        assert_eq!(len, q.len() + ANSWER_LEN);
+        assert_eq!(reply[0..2], [0x12, 0x34]);
+        assert_eq!(reply[2..4], [0x85, 0x00]);
+        assert_eq!(reply[6..8], [0, 1]);
+        assert_eq!(reply[HEADER_LEN..q.len()], q[HEADER_LEN..]);
+        assert_eq!(reply[len - 4..len], IP);
+    }
+
+    #[test]
+    fn aaaa_query_gets_empty_answer() {
+        let q = query("example.com", 28);
+        let mut reply = [0u8; MAX_PACKET_LEN];
+        let len = answer(&q, IP, &mut reply).unwrap();
+        assert_eq!(len, q.len());
+        assert_eq!(reply[6..8], [0, 0]);
+    }
+
+    #[test]
+    fn non_queries_are_ignored() {
+        let mut reply = [0u8; MAX_PACKET_LEN];
+        let mut response = query("example.com", TYPE_A);
+        response[2] |= 0x80;
+        assert!(answer(&response, IP, &mut reply).is_none());
+        let mut two_questions = query("example.com", TYPE_A);
+        two_questions[5] = 2;
+        assert!(answer(&two_questions, IP, &mut reply).is_none());
+    }
+
+    #[test]
+    fn truncated_query_is_ignored() {
+        let q = query("example.com", TYPE_A);
+        let mut reply = [0u8; MAX_PACKET_LEN];
+        assert!(answer(&q[..q.len() - 1], IP, &mut reply).is_none());
+        assert!(answer(&q[..8], IP, &mut reply).is_none());
+    }
+}
@@ -0,0 +1,333 @@
+//! `POST /api/draw`: a JSON array of drawing operations.
+//!
+//! ```json
+//! [{"op":"clear"},
+//!  {"op":"rect","x":0,"y":0,"w":88,"h":12,"rgb":[0,0,32768],"fill":true},
+//!  {"op":"text","msg":"HELLO","x":4,"y":3},
+//!  {"op":"line","x0":0,"y0":87,"x1":87,"y1":13,"rgb":[65535,0,0]}]
+//! ```
+//!
+//! [`parse_ops`] turns the body into [`DrawCommand`]s, all or nothing: one
+//! bad op rejects the whole batch with an [`ApiError`] naming it, so a
+//! script never leaves half a picture behind.
+
+use core::fmt::Write;
+
+use crate::command::DrawCommand;
+use crate::frame_buffer::{DEFAULT_TEXT_COLOR, Pixel};
+use crate::http_request::MAX_MESSAGE_LEN;
+use crate::json::Reader;
+
+/// Most operations accepted in one request.
+pub const MAX_DRAW_OPS: usize = 16;
+
+/// Body of a successful reply.
+pub const OK_JSON: &str = "{\"ok\":true}";
+
+/// Capacity of [`ApiError::to_json`]'s output.
+pub const ERROR_JSON_LEN: usize = 64;
+
+/// Integer fields, all coordinates or sizes in pixels.
+const COORD_FIELDS: [&str; 8] = ["x", "y", "x0", "y0", "x1", "y1", "w", "h"];
+
+/// The [`COORD_FIELDS`] that are sizes, and can't be negative.
+const SIZE_FIELDS: [&str; 2] = ["w", "h"];
+
+/// Why a request was rejected. Ops are numbered from 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiError {
+    /// The body isn't a JSON array of objects.
+    Json,
+    /// The body didn't fit in the request buffer.
+    TooLarge,
+    /// More than [`MAX_DRAW_OPS`] ops.
+    TooManyOps,
+    /// Op `n` has an `op` name that isn't known.
+    UnknownOp(usize),
+    /// Op `n` lacks a field it needs, or has one of the wrong type or
+    /// out of range.
+    BadField(usize, &'static str),
+}
+
+impl ApiError {
+    /// The error as a JSON reply, e.g.
+    /// `{"ok":false,"error":"op 2: missing or invalid x0"}`.
+    pub fn to_json(&self) -> heapless::String<ERROR_JSON_LEN> {
+        let mut json = heapless::String::new();
+        // ERROR_JSON_LEN covers the longest message.
+        let _ = json.push_str("{\"ok\":false,\"error\":\"");
+        let _ = match self {
+            Self::Json => write!(json, "invalid JSON"),
+            Self::TooLarge => write!(json, "body too large"),
+            Self::TooManyOps => write!(json, "at most {MAX_DRAW_OPS} ops"),
+            Self::UnknownOp(n) => write!(json, "op {n}: unknown op"),
+            Self::BadField(n, field) => write!(json, "op {n}: missing or invalid {field}"),
+        };
+        let _ = json.push_str("\"}");
+        json
+    }
+}
+
+/// Parse a `POST /api/draw` body. Ops:
+///
+/// - `pixel`: `x`, `y`
+/// - `line`: `x0`, `y0`, `x1`, `y1`
+/// - `rect`: `x`, `y`, `w`, `h`, and `fill` (default `false`, outline)
+/// - `text`: `msg`, `x`, `y` (top-left corner of the first glyph)
+/// - `clear`: fills with `rgb`, black by default
+///
+/// `rgb` is `[r, g, b]` with 16-bit channels, white when left out (except
+/// for `clear`). Coordinates are `-32768..=32767` and sizes `0..=65535`;
+/// anything off the panel is clipped. Unknown fields are ignored.
+pub fn parse_ops(body: &[u8]) -> Result<heapless::Vec<DrawCommand, MAX_DRAW_OPS>, ApiError> {
+    let mut json = Reader::new(body);
+    json.expect(b'[').ok_or(ApiError::Json)?;
+    let mut ops = heapless::Vec::new();
+    if !json.eat(b']') {
+        loop {
+            let op = parse_op(&mut json, ops.len())?;
+            ops.push(op).map_err(|_| ApiError::TooManyOps)?;
+            if json.eat(b']') {
+                break;
+            }
+            json.expect(b',').ok_or(ApiError::Json)?;
+        }
+    }
+    if !json.at_end() {
+        return Err(ApiError::Json);
+    }
+    Ok(ops)
+}
+
+/// Parse op number `index`, a JSON object.
+fn parse_op(json: &mut Reader, index: usize) -> Result<DrawCommand, ApiError> {
+    let bad = |field: &'static str| ApiError::BadField(index, field);
+    let mut op: Option<heapless::String<8>> = None;
+    let mut coords = [None; COORD_FIELDS.len()];
+    let mut rgb = None;
+    let mut msg: Option<heapless::String<MAX_MESSAGE_LEN>> = None;
+    let mut fill = None;
+
+    json.expect(b'{').ok_or(ApiError::Json)?;
+    if !json.eat(b'}') {
+        loop {
+            let key: heapless::String<16> = json.string().ok_or(ApiError::Json)?;
+            json.expect(b':').ok_or(ApiError::Json)?;
+            match key.as_str() {
+                "op" => op = Some(json.string().ok_or(bad("op"))?),
+                "rgb" => rgb = Some(parse_color(json).ok_or(bad("rgb"))?),
+                "msg" => msg = Some(json.string().ok_or(bad("msg"))?),
+                "fill" => fill = Some(json.boolean().ok_or(bad("fill"))?),
+                key => match COORD_FIELDS.iter().position(|&field| field == key) {
+                    Some(i) => {
+                        let value = json
+                            .integer()
+                            .and_then(|v| match SIZE_FIELDS.contains(&key) {
+                                true => u16::try_from(v).ok().map(i32::from),
+                                false => i16::try_from(v).ok().map(i32::from),
+                            });
+                        coords[i] = Some(value.ok_or(bad(COORD_FIELDS[i]))?);
+                    }
+                    None => json.skip_value().ok_or(ApiError::Json)?,
+                },
+            }
+            if json.eat(b'}') {
+                break;
+            }
+            json.expect(b',').ok_or(ApiError::Json)?;
+        }
+    }
+
+    let coord = |field: &'static str| {
+        let i = COORD_FIELDS.iter().position(|&f| f == field);
+        i.and_then(|i| coords[i]).ok_or(bad(field))
+    };
+    let color = rgb.unwrap_or(DEFAULT_TEXT_COLOR);
+    match op.ok_or(bad("op"))?.as_str() {
+        "pixel" => Ok(DrawCommand::Pixel {
+            x: coord("x")?,
+            y: coord("y")?,
+            color,
+        }),
+        "line" => Ok(DrawCommand::Line {
+            x0: coord("x0")?,
+            y0: coord("y0")?,
+            x1: coord("x1")?,
+            y1: coord("y1")?,
+            color,
+        }),
+        "rect" => Ok(DrawCommand::Rect {
+            x: coord("x")?,
+            y: coord("y")?,
+            // Sizes were checked to be non-negative.
+            width: coord("w")? as usize,
+            height: coord("h")? as usize,
+            color,
+            fill: fill.unwrap_or(false),
+        }),
+        "text" => Ok(DrawCommand::Text {
+            x: coord("x")?,
+            y: coord("y")?,
+            text: msg.ok_or(bad("msg"))?,
+            color,
+        }),
+        "clear" => Ok(DrawCommand::Fill {
+            color: rgb.unwrap_or([0, 0, 0]),
+        }),
+        _ => Err(ApiError::UnknownOp(index)),
+    }
+}
+
+/// `[r, g, b]`, each `0..=65535`.
+fn parse_color(json: &mut Reader) -> Option<Pixel> {
+    json.expect(b'[')?;
+    let mut color = [0u16; 3];
+    for (i, channel) in color.iter_mut().enumerate() {
+        if i > 0 {
+            json.expect(b',')?;
+        }
+        *channel = u16::try_from(json.integer()?).ok()?;
+    }
+    json.expect(b']')?;
+    Some(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_op() {
+        let ops = parse_ops(
+            br#"[
+                {"op":"clear"},
+                {"op":"pixel","x":1,"y":2,"rgb":[1,2,3]},
+                {"op":"line","x0":0,"y0":1,"x1":87,"y1":86},
+                {"op":"rect","x":4,"y":5,"w":6,"h":7,"fill":true,"rgb":[0,0,65535]},
+                {"op":"text","msg":"HI","x":8,"y":9}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            ops.as_slice(),
+            [
+                DrawCommand::Fill { color: [0, 0, 0] },
+                DrawCommand::Pixel {
+                    x: 1,
+                    y: 2,
+                    color: [1, 2, 3],
+                },
+                DrawCommand::Line {
+                    x0: 0,
+                    y0: 1,
+                    x1: 87,
+                    y1: 86,
+                    color: DEFAULT_TEXT_COLOR,
+                },
+                DrawCommand::Rect {
+                    x: 4,
+                    y: 5,
+                    width: 6,
+                    height: 7,
+                    color: [0, 0, 0xFFFF],
+                    fill: true,
+                },
+                DrawCommand::Text {
+                    x: 8,
+                    y: 9,
+                    text: heapless::String::try_from("HI").unwrap(),
+                    color: DEFAULT_TEXT_COLOR,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn field_order_and_unknown_fields_do_not_matter() {
+        let ops = parse_ops(br#"[{"y":2,"note":{"a":[1]},"x":1,"op":"pixel"}]"#).unwrap();
+        assert_eq!(
+            ops.as_slice(),
+            [DrawCommand::Pixel {
+                x: 1,
+                y: 2,
+                color: DEFAULT_TEXT_COLOR,
+            }]
+        );
+        assert!(parse_ops(b" [ ] ").unwrap().is_empty());
+        // Positions may start off the panel.
+        let ops = parse_ops(br#"[{"op":"text","msg":"A","x":-2,"y":-32768}]"#).unwrap();
+        assert!(matches!(
+            ops[0],
+            DrawCommand::Text {
+                x: -2,
+                y: -32768,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn errors_name_the_op_and_field() {
+        let cases: [(&[u8], ApiError); 10] = [
+            (b"{}", ApiError::Json),
+            (br#"[{"op":"clear"}"#, ApiError::Json),
+            (br#"[{"op":"clear"}] x"#, ApiError::Json),
+            (br#"[{"op":"clear"},{"op":"spin"}]"#, ApiError::UnknownOp(1)),
+            (br#"[{"x":1}]"#, ApiError::BadField(0, "op")),
+            (
+                br#"[{"op":"line","x0":0,"y0":0,"x1":5}]"#,
+                ApiError::BadField(0, "y1"),
+            ),
+            (
+                br#"[{"op":"pixel","x":40000,"y":0}]"#,
+                ApiError::BadField(0, "x"),
+            ),
+            (
+                br#"[{"op":"rect","x":-1,"y":0,"w":-4,"h":2}]"#,
+                ApiError::BadField(0, "w"),
+            ),
+            (
+                br#"[{"op":"pixel","x":1,"y":1,"rgb":[1,2]}]"#,
+                ApiError::BadField(0, "rgb"),
+            ),
+            (
+                br#"[{"op":"text","x":1,"y":1}]"#,
+                ApiError::BadField(0, "msg"),
+            ),
+        ];
+        for (body, error) in cases {
+            assert_eq!(
+                parse_ops(body),
+                Err(error),
+                "{}",
+                core::str::from_utf8(body).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_too_many_ops() {
+        let mut body: heapless::String<512> = heapless::String::new();
+        body.push('[').unwrap();
+        for i in 0..=MAX_DRAW_OPS {
+            if i > 0 {
+                body.push(',').unwrap();
+            }
+            body.push_str(r#"{"op":"clear"}"#).unwrap();
+        }
+        body.push(']').unwrap();
+        assert_eq!(parse_ops(body.as_bytes()), Err(ApiError::TooManyOps));
+    }
+
+    #[test]
+    fn error_json_names_the_problem() {
+        assert_eq!(
+            ApiError::BadField(12, "x0").to_json().as_str(),
+            r#"{"ok":false,"error":"op 12: missing or invalid x0"}"#
+        );
+        assert_eq!(
+            ApiError::TooManyOps.to_json().as_str(),
+            r#"{"ok":false,"error":"at most 16 ops"}"#
+        );
+    }
+}
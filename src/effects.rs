@@ -0,0 +1,581 @@
+//! Full-screen animations for `/effect`.
+//!
+//! Each effect implements [`Animation`]: given how long it has been
+//! running, it paints the whole frame buffer. [`ActiveEffect`] holds the
+//! state of whichever [`Effect`] is selected, and the refresh loop steps
+//! it once per frame.
+//!
+//! The ESP32-C3 has no FPU, so the per-pixel work is integer arithmetic
+//! and table lookups; floats are only used for a few palette entries per
+//! frame. State is kept small too: the largest, the fire's heat map, is
+//! at half resolution (under 2 KB), and the Game of Life keeps a bit
+//! per cell.
+
+use crate::frame_buffer::{FrameBuffer, Pixel, hsv_to_pixel};
+use crate::mode::Effect;
+use crate::{MATRIX_HEIGHT, MATRIX_WIDTH};
+
+/// How fast the rainbow cycles, in degrees of hue per second.
+pub const RAINBOW_DEGREES_PER_SEC: u64 = 90;
+
+/// Colors in the plasma palette, rebuilt every frame as its hues drift.
+const PLASMA_PALETTE_LEN: usize = 64;
+
+/// How fast the plasma palette's hues drift, in degrees per second.
+const PLASMA_HUE_DEGREES_PER_SEC: u64 = 20;
+
+/// Fire heat map size: one cell per 2x2 pixel block.
+const FIRE_WIDTH: usize = MATRIX_WIDTH / 2;
+const FIRE_HEIGHT: usize = MATRIX_HEIGHT / 2;
+
+/// Fire simulation steps per second: how fast the flames rise.
+const FIRE_STEPS_PER_SEC: u64 = 30;
+
+/// Stars in the starfield.
+const STAR_COUNT: usize = 48;
+
+/// Depth at which a new star appears, in the same units as its `x` and
+/// `y` (which range over `-STAR_DEPTH / 2..STAR_DEPTH / 2`).
+const STAR_DEPTH: u32 = 1024;
+
+/// Nearest a star gets before it is replaced.
+const STAR_MIN_DEPTH: u32 = 16;
+
+/// How fast stars approach, in depth units per second.
+const STAR_SPEED: u32 = 400;
+
+/// Game of Life generations per second at normal speed.
+const LIFE_STEPS_PER_SEC: u64 = 10;
+
+/// Most generations [`Life`] catches up on in one frame after a stall
+/// (or when sped up), so a frame never takes long.
+const LIFE_MAX_STEPS_PER_FRAME: u64 = 8;
+
+/// One in this many cells starts alive in a random seed.
+const LIFE_SEED_ONE_IN: u32 = 4;
+
+/// Color of live cells.
+const LIFE_COLOR: Pixel = [0, 0xFFFF, 0x4000];
+
+/// Bytes in one row of a [`Life`] grid: a bit per cell.
+const LIFE_ROW_BYTES: usize = MATRIX_WIDTH.div_ceil(8);
+
+/// Something that paints frames over time.
+pub trait Animation {
+    /// Paint the frame `t_ms` milliseconds after the animation started.
+    /// `t_ms` never decreases between calls.
+    fn step(&mut self, fb: &mut FrameBuffer, t_ms: u64);
+}
+
+/// The diagonal rainbow of [`FrameBuffer::rainbow_fill`], cycling
+/// through every hue.
+pub struct Rainbow;
+
+impl Animation for Rainbow {
+    fn step(&mut self, fb: &mut FrameBuffer, t_ms: u64) {
+        // Whole cycles are dropped before converting to degrees so the
+        // phase stays precise however long it runs.
+        let millidegrees = t_ms * RAINBOW_DEGREES_PER_SEC % 360_000;
+        fb.rainbow_fill(millidegrees as f32 / 1000.0);
+    }
+}
+
+/// Overlapping sine waves mapped onto a drifting rainbow palette.
+pub struct Plasma {
+    /// One period of a sine wave, scaled to `-127..=127`.
+    sine: [i8; 256],
+}
+
+impl Plasma {
+    pub fn new() -> Self {
+        Self {
+            sine: core::array::from_fn(|i| {
+                (libm::sinf(i as f32 * core::f32::consts::TAU / 256.0) * 127.0) as i8
+            }),
+        }
+    }
+
+    fn sin(&self, angle: usize) -> i32 {
+        i32::from(self.sine[angle % 256])
+    }
+}
+
+impl Default for Plasma {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Animation for Plasma {
+    fn step(&mut self, fb: &mut FrameBuffer, t_ms: u64) {
+        let hue = (t_ms * PLASMA_HUE_DEGREES_PER_SEC % 360_000) as f32 / 1000.0;
+        let palette: [Pixel; PLASMA_PALETTE_LEN] = core::array::from_fn(|i| {
+            hsv_to_pixel(hue + i as f32 * 360.0 / PLASMA_PALETTE_LEN as f32, 1.0, 1.0)
+        });
+        // Each wave moves at its own speed; 256 is a full period.
+        let phase = (t_ms / 8 % 256_000) as usize;
+        for y in 0..MATRIX_HEIGHT {
+            for x in 0..MATRIX_WIDTH {
+                let v = self.sin(x * 6 + phase)
+                    + self.sin(y * 5 + phase * 3 / 2)
+                    + self.sin((x + y) * 3 + 256_000 - phase / 2)
+                    + self.sin((x * y) / 16 + phase / 3);
+                // `v` is in -508..=508.
+                let i = (v + 508) as usize * (PLASMA_PALETTE_LEN - 1) / 1016;
+                let [r, g, b] = palette[i];
+                fb.set_pixel_16(x as i32, y as i32, r, g, b);
+            }
+        }
+    }
+}
+
+/// Flames rising from the bottom edge: heat spreads upwards, drifting
+/// sideways and cooling as it goes.
+pub struct Fire {
+    /// Heat of each 2x2 block, 0 (cold) to 255.
+    heat: [[u8; FIRE_WIDTH]; FIRE_HEIGHT],
+    /// Simulation steps taken so far.
+    steps: u64,
+    rng: Rng,
+}
+
+impl Fire {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            heat: [[0; FIRE_WIDTH]; FIRE_HEIGHT],
+            steps: 0,
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// Advance the flames by one step.
+    fn spread(&mut self) {
+        for cell in self.heat[FIRE_HEIGHT - 1].iter_mut() {
+            *cell = 160 + (self.rng.next() % 96) as u8;
+        }
+        for y in 0..FIRE_HEIGHT - 1 {
+            for x in 0..FIRE_WIDTH {
+                let r = self.rng.next();
+                let from = (x + (r % 3) as usize).saturating_sub(1).min(FIRE_WIDTH - 1);
+                let cooling = (r >> 8) % 20;
+                self.heat[y][x] = self.heat[y + 1][from].saturating_sub(cooling as u8);
+            }
+        }
+    }
+}
+
+impl Animation for Fire {
+    fn step(&mut self, fb: &mut FrameBuffer, t_ms: u64) {
+        let target = t_ms * FIRE_STEPS_PER_SEC / 1000;
+        // After a long stall, a screen's worth of steps rebuilds the
+        // flames; more would change nothing.
+        let due = (target - self.steps).min(FIRE_HEIGHT as u64);
+        self.steps = target;
+        for _ in 0..due {
+            self.spread();
+        }
+        for y in 0..MATRIX_HEIGHT {
+            for x in 0..MATRIX_WIDTH {
+                let [r, g, b] = fire_color(self.heat[y / 2][x / 2]);
+                fb.set_pixel_16(x as i32, y as i32, r, g, b);
+            }
+        }
+    }
+}
+
+/// Flame palette: black through red, orange and yellow to white.
+fn fire_color(heat: u8) -> Pixel {
+    let ramp = |start: u8| (u32::from(heat.saturating_sub(start)).min(85) * 0xFFFF / 85) as u16;
+    [ramp(0), ramp(85), ramp(170)]
+}
+
+/// A star: its position across the view and how far away it is.
+#[derive(Clone, Copy)]
+struct Star {
+    x: i32,
+    y: i32,
+    z: u32,
+}
+
+/// Stars flying towards the viewer, brightening as they approach.
+pub struct Starfield {
+    stars: [Star; STAR_COUNT],
+    /// `t_ms` of the previous step.
+    last_ms: u64,
+    rng: Rng,
+}
+
+impl Starfield {
+    pub fn new(seed: u32) -> Self {
+        let mut rng = Rng::new(seed);
+        let mut stars = [Star { x: 0, y: 0, z: 0 }; STAR_COUNT];
+        for star in &mut stars {
+            *star = Self::new_star(&mut rng);
+            // Spread the first stars out in depth too.
+            star.z = STAR_MIN_DEPTH + rng.next() % (STAR_DEPTH - STAR_MIN_DEPTH);
+        }
+        Self {
+            stars,
+            last_ms: 0,
+            rng,
+        }
+    }
+
+    fn new_star(rng: &mut Rng) -> Star {
+        let half = STAR_DEPTH as i32 / 2;
+        let x = (rng.next() % STAR_DEPTH) as i32 - half;
+        let y = (rng.next() % STAR_DEPTH) as i32 - half;
+        Star {
+            x,
+            // No taller than the display, which may be wider than it is tall.
+            y: y * MATRIX_HEIGHT as i32 / MATRIX_WIDTH as i32,
+            z: STAR_DEPTH,
+        }
+    }
+
+    /// Where `star` shows up on the panel, if it does. At full depth the
+    /// view spans the whole panel.
+    fn project(star: &Star) -> Option<(usize, usize)> {
+        let focal = MATRIX_WIDTH as i32;
+        let x = MATRIX_WIDTH as i32 / 2 + star.x * focal / star.z as i32;
+        let y = MATRIX_HEIGHT as i32 / 2 + star.y * focal / star.z as i32;
+        let on_panel =
+            (0..MATRIX_WIDTH as i32).contains(&x) && (0..MATRIX_HEIGHT as i32).contains(&y);
+        on_panel.then_some((x as usize, y as usize))
+    }
+}
+
+impl Animation for Starfield {
+    fn step(&mut self, fb: &mut FrameBuffer, t_ms: u64) {
+        let elapsed_ms = (t_ms - self.last_ms).min(1000) as u32;
+        self.last_ms = t_ms;
+        fb.clear();
+        for star in &mut self.stars {
+            star.z = star.z.saturating_sub(STAR_SPEED * elapsed_ms / 1000);
+            if star.z < STAR_MIN_DEPTH || Self::project(star).is_none() {
+                *star = Self::new_star(&mut self.rng);
+            }
+            if let Some((x, y)) = Self::project(star) {
+                let level = (0xFFFF * (STAR_DEPTH - star.z) / STAR_DEPTH) as u16;
+                fb.set_pixel_16(x as i32, y as i32, level, level, level);
+            }
+        }
+    }
+}
+
+/// One cell per pixel, a bit each.
+type LifeGrid = [[u8; LIFE_ROW_BYTES]; MATRIX_HEIGHT];
+
+/// Conway's Game of Life, a cell per pixel. It starts from a random
+/// pattern or from whatever the panel showed, and starts over from a
+/// random one once every cell has died or nothing changes any more.
+pub struct Life {
+    cells: LifeGrid,
+    /// Scratch space for the next generation, so a step never reads
+    /// cells it has already overwritten.
+    next: LifeGrid,
+    /// Cells past an edge are the ones on the opposite edge, rather than
+    /// always dead.
+    wrap: bool,
+    /// Seed from the lit pixels of the first frame given to
+    /// [`Animation::step`] instead of at random.
+    from_screen: bool,
+    /// False until the first step has seeded the grid.
+    seeded: bool,
+    /// Generations so far.
+    steps: u64,
+    rng: Rng,
+}
+
+impl Life {
+    pub fn new(wrap: bool, from_screen: bool, seed: u32) -> Self {
+        Self {
+            cells: [[0; LIFE_ROW_BYTES]; MATRIX_HEIGHT],
+            next: [[0; LIFE_ROW_BYTES]; MATRIX_HEIGHT],
+            wrap,
+            from_screen,
+            seeded: false,
+            steps: 0,
+            rng: Rng::new(seed),
+        }
+    }
+
+    fn is_alive(&self, x: usize, y: usize) -> bool {
+        self.cells[y][x / 8] & (1 << (x % 8)) != 0
+    }
+
+    fn set(grid: &mut LifeGrid, x: usize, y: usize, alive: bool) {
+        if alive {
+            grid[y][x / 8] |= 1 << (x % 8);
+        } else {
+            grid[y][x / 8] &= !(1 << (x % 8));
+        }
+    }
+
+    fn seed_randomly(&mut self) {
+        for y in 0..MATRIX_HEIGHT {
+            for x in 0..MATRIX_WIDTH {
+                let alive = self.rng.next().is_multiple_of(LIFE_SEED_ONE_IN);
+                Self::set(&mut self.cells, x, y, alive);
+            }
+        }
+    }
+
+    /// Live neighbours of `(x, y)`.
+    fn neighbours(&self, x: usize, y: usize) -> usize {
+        let (width, height) = (MATRIX_WIDTH as isize, MATRIX_HEIGHT as isize);
+        let mut count = 0;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let (mut nx, mut ny) = (x as isize + dx, y as isize + dy);
+                if self.wrap {
+                    (nx, ny) = (nx.rem_euclid(width), ny.rem_euclid(height));
+                } else if !(0..width).contains(&nx) || !(0..height).contains(&ny) {
+                    continue;
+                }
+                if (dx, dy) != (0, 0) && self.is_alive(nx as usize, ny as usize) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    /// Advance one generation. Returns false if nothing changed.
+    fn generation(&mut self) -> bool {
+        for y in 0..MATRIX_HEIGHT {
+            for x in 0..MATRIX_WIDTH {
+                let alive = matches!(
+                    (self.is_alive(x, y), self.neighbours(x, y)),
+                    (true, 2 | 3) | (false, 3)
+                );
+                Self::set(&mut self.next, x, y, alive);
+            }
+        }
+        let changed = self.next != self.cells;
+        core::mem::swap(&mut self.cells, &mut self.next);
+        changed
+    }
+}
+
+impl Animation for Life {
+    fn step(&mut self, fb: &mut FrameBuffer, t_ms: u64) {
+        if !self.seeded {
+            self.seeded = true;
+            if self.from_screen {
+                for y in 0..MATRIX_HEIGHT {
+                    for x in 0..MATRIX_WIDTH {
+                        Self::set(&mut self.cells, x, y, fb.get_pixel(x, y) != [0; 3]);
+                    }
+                }
+            } else {
+                self.seed_randomly();
+            }
+        }
+        let target = t_ms * LIFE_STEPS_PER_SEC / 1000;
+        let due = (target - self.steps).min(LIFE_MAX_STEPS_PER_FRAME);
+        self.steps = target;
+        for _ in 0..due {
+            if !self.generation() {
+                // Dead or frozen: nothing more to watch.
+                self.seed_randomly();
+            }
+        }
+        let [r, g, b] = LIFE_COLOR;
+        fb.clear();
+        for y in 0..MATRIX_HEIGHT {
+            for x in 0..MATRIX_WIDTH {
+                if self.is_alive(x, y) {
+                    fb.set_pixel_16(x as i32, y as i32, r, g, b);
+                }
+            }
+        }
+    }
+}
+
+/// The running effect and its state. Only one runs at a time, so the
+/// enum's size, the largest effect's, is all the memory effects take.
+#[allow(clippy::large_enum_variant)]
+pub enum ActiveEffect {
+    Rainbow(Rainbow),
+    Plasma(Plasma),
+    Fire(Fire),
+    Starfield(Starfield),
+    Life(Life),
+}
+
+impl ActiveEffect {
+    /// Start `effect` from scratch. `seed` varies the random effects.
+    pub fn new(effect: Effect, seed: u32) -> Self {
+        match effect {
+            Effect::Rainbow => Self::Rainbow(Rainbow),
+            Effect::Plasma => Self::Plasma(Plasma::new()),
+            Effect::Fire => Self::Fire(Fire::new(seed)),
+            Effect::Starfield => Self::Starfield(Starfield::new(seed)),
+            Effect::Life { wrap, from_screen } => Self::Life(Life::new(wrap, from_screen, seed)),
+        }
+    }
+}
+
+impl Animation for ActiveEffect {
+    /// Paint the next frame of the effect. Cancels any active scroll.
+    fn step(&mut self, fb: &mut FrameBuffer, t_ms: u64) {
+        fb.stop_scroll();
+        match self {
+            Self::Rainbow(effect) => effect.step(fb, t_ms),
+            Self::Plasma(effect) => effect.step(fb, t_ms),
+            Self::Fire(effect) => effect.step(fb, t_ms),
+            Self::Starfield(effect) => effect.step(fb, t_ms),
+            Self::Life(effect) => effect.step(fb, t_ms),
+        }
+    }
+}
+
+/// xorshift32: cheap pseudo-random numbers, plenty for flickering.
+pub(crate) struct Rng(u32);
+
+impl Rng {
+    pub(crate) fn new(seed: u32) -> Self {
+        // Zero would stay zero forever.
+        Self(seed.max(1))
+    }
+
+    pub(crate) fn next(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame_buffer::tests::lit_pixels;
+    use crate::frame_buffer::{load_pixel, store_pixel};
+
+    /// [`LIFE_COLOR`] as the frame buffer gives it back.
+    const LIFE_SHOWN: Pixel = load_pixel(store_pixel(LIFE_COLOR));
+
+    #[test]
+    fn rainbow_matches_rainbow_fill() {
+        let mut fb = FrameBuffer::new();
+        let mut expected = FrameBuffer::new();
+        // A third of a cycle in, then many cycles later.
+        for t_ms in [4_000 / 3 + 1, 4_000 * 1_000_000] {
+            Rainbow.step(&mut fb, t_ms);
+            let millidegrees = t_ms * RAINBOW_DEGREES_PER_SEC % 360_000;
+            expected.rainbow_fill(millidegrees as f32 / 1000.0);
+            assert_eq!(fb.as_pixels(), expected.as_pixels());
+        }
+    }
+
+    #[test]
+    fn plasma_fills_the_panel_and_moves() {
+        let mut plasma = Plasma::new();
+        let mut fb = FrameBuffer::new();
+        plasma.step(&mut fb, 0);
+        assert_eq!(lit_pixels(&fb).len(), MATRIX_WIDTH * MATRIX_HEIGHT);
+        let first = *fb.as_pixels();
+        plasma.step(&mut fb, 500);
+        assert_ne!(*fb.as_pixels(), first);
+    }
+
+    #[test]
+    fn fire_burns_from_the_bottom() {
+        let mut fire = Fire::new(1);
+        let mut fb = FrameBuffer::new();
+        fire.step(&mut fb, 0);
+        assert_eq!(lit_pixels(&fb).len(), 0);
+        fire.step(&mut fb, 2_000);
+        // The bottom rows burn bright; the top is all but dark.
+        let red = |y| {
+            (0..MATRIX_WIDTH)
+                .map(|x| u32::from(fb.get_pixel(x, y)[0]))
+                .sum::<u32>()
+        };
+        assert!(fb.get_pixel(40, MATRIX_HEIGHT - 1)[0] > 0x8000);
+        assert!(red(0) < red(MATRIX_HEIGHT - 1) / 8);
+    }
+
+    #[test]
+    fn fire_palette_runs_from_black_to_white() {
+        assert_eq!(fire_color(0), [0, 0, 0]);
+        assert_eq!(fire_color(85), [0xFFFF, 0, 0]);
+        assert_eq!(fire_color(170), [0xFFFF, 0xFFFF, 0]);
+        assert_eq!(fire_color(255), [0xFFFF, 0xFFFF, 0xFFFF]);
+    }
+
+    #[test]
+    fn stars_fly_outwards() {
+        let mut stars = Starfield::new(7);
+        let mut fb = FrameBuffer::new();
+        stars.step(&mut fb, 0);
+        let count = lit_pixels(&fb).len();
+        assert!(count > 0 && count <= STAR_COUNT);
+        let before = stars.stars.map(|star| star.z);
+        stars.step(&mut fb, 100);
+        // All but the few that flew past (and were replaced far away)
+        // came closer.
+        let closer = stars
+            .stars
+            .iter()
+            .zip(before)
+            .filter(|(star, z)| star.z < *z);
+        assert!(closer.count() > STAR_COUNT / 2);
+    }
+
+    /// A Life grid with exactly the cells in `alive` lit, stepped from
+    /// the screen.
+    fn life_from(alive: &[(i32, i32)], wrap: bool) -> (Life, FrameBuffer) {
+        let mut fb = FrameBuffer::new();
+        for &(x, y) in alive {
+            fb.set_pixel_16(x, y, 0xFFFF, 0xFFFF, 0xFFFF);
+        }
+        let mut life = Life::new(wrap, true, 1);
+        life.step(&mut fb, 0);
+        (life, fb)
+    }
+
+    #[test]
+    fn life_blinker_oscillates() {
+        let (mut life, mut fb) = life_from(&[(10, 9), (10, 10), (10, 11)], false);
+        assert_eq!(fb.get_pixel(10, 9), LIFE_SHOWN);
+        life.step(&mut fb, 1000 / LIFE_STEPS_PER_SEC);
+        assert_eq!(lit_pixels(&fb).len(), 3);
+        assert_eq!(fb.get_pixel(9, 10), LIFE_SHOWN);
+        assert_eq!(fb.get_pixel(10, 9), [0; 3]);
+        life.step(&mut fb, 2 * 1000 / LIFE_STEPS_PER_SEC);
+        assert_eq!(fb.get_pixel(10, 9), LIFE_SHOWN);
+    }
+
+    #[test]
+    fn life_edges_wrap_only_when_asked() {
+        // A blinker lying across the left and right edges.
+        let across = [(MATRIX_WIDTH as i32 - 1, 5), (0, 5), (1, 5)];
+        let (mut life, mut fb) = life_from(&across, true);
+        life.step(&mut fb, 1000 / LIFE_STEPS_PER_SEC);
+        assert_eq!(lit_pixels(&fb).len(), 3);
+        assert_eq!(fb.get_pixel(0, 4), LIFE_SHOWN);
+
+        // Without wrapping, the ends are two separate pieces, too small
+        // to live. Once nothing changes, a random pattern takes over.
+        let (mut life, mut fb) = life_from(&across, false);
+        life.step(&mut fb, 1000 / LIFE_STEPS_PER_SEC);
+        assert_eq!(lit_pixels(&fb).len(), 0);
+        life.step(&mut fb, 2 * 1000 / LIFE_STEPS_PER_SEC);
+        assert!(
+            lit_pixels(&fb).len() > MATRIX_WIDTH * MATRIX_HEIGHT / LIFE_SEED_ONE_IN as usize / 4
+        );
+    }
+
+    #[test]
+    fn effects_cancel_scrolling() {
+        let mut fb = FrameBuffer::new();
+        fb.set_scroll_text("THIS MESSAGE IS FAR TOO LONG TO FIT", 1);
+        ActiveEffect::new(Effect::Fire, 3).step(&mut fb, 0);
+        assert!(!fb.is_scrolling());
+    }
+}
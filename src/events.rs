@@ -0,0 +1,173 @@
+//! Display-state notifications for `/events`, a Server-Sent Events
+//! stream.
+//!
+//! Whatever changes the display, an HTTP request or a serial command,
+//! goes through the network layer's `apply_changes`. It turns the
+//! [`Response`] into [`DisplayEvent`]s with
+//! [`DisplayEvent::from_response`] and hands them to every connected
+//! `/events` client, each as one line of JSON ([`DisplayEvent::to_sse`]):
+//!
+//! ```text
+//! data: {"type":"text","text":"Hello"}
+//! data: {"type":"clear"}
+//! data: {"type":"fill","color":"#0000ff"}
+//! data: {"type":"mode","mode":"marquee"}
+//! data: {"type":"brightness","level":128}
+//! ```
+//!
+//! A client that goes quiet is only noticed when a write to it fails,
+//! so the stream also carries a [`SSE_KEEPALIVE`] comment every
+//! [`KEEPALIVE_SECS`]; browsers ignore it.
+
+use core::fmt::Write;
+
+use crate::command::DrawCommand;
+use crate::frame_buffer::Pixel;
+use crate::http_request::{MAX_MESSAGE_LEN, Response};
+use crate::stats::push_json_string;
+
+/// `/events` clients connected at once. Each has an HTTP server task of
+/// its own while it stays connected.
+pub const MAX_EVENT_CLIENTS: usize = 2;
+
+/// Events a client can fall behind by before it misses some.
+pub const EVENT_QUEUE_LEN: usize = 8;
+
+/// Most events one change makes: new text or a fill, a mode and a
+/// brightness.
+pub const MAX_EVENTS_PER_CHANGE: usize = 3;
+
+/// Capacity of [`DisplayEvent::to_sse`]'s output: text of
+/// [`MAX_MESSAGE_LEN`] characters, every one escaped as `\u00XX`.
+pub const EVENT_LINE_LEN: usize = 40 + MAX_MESSAGE_LEN * 6;
+
+/// Seconds between keepalives on an idle stream.
+pub const KEEPALIVE_SECS: u64 = 15;
+
+/// SSE comment sent to an idle stream, so a client that is gone is
+/// noticed and its slot freed.
+pub const SSE_KEEPALIVE: &str = ": keepalive\n\n";
+
+/// One change to what the display shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisplayEvent {
+    /// New text.
+    Text(heapless::String<MAX_MESSAGE_LEN>),
+    /// Cleared to black.
+    Clear,
+    /// Filled with one color.
+    Fill(Pixel),
+    /// Switched to the mode named.
+    Mode(&'static str),
+    /// Global brightness changed.
+    Brightness(u8),
+}
+
+impl DisplayEvent {
+    /// The events `response` makes once applied: text (or the fill that
+    /// goes with `/clear` and `/color`), then the mode, then the
+    /// brightness. Read before the changes are taken out of it.
+    pub fn from_response(response: &Response) -> heapless::Vec<Self, MAX_EVENTS_PER_CHANGE> {
+        let mut events = heapless::Vec::new();
+        // Three pushes at most, one per field.
+        let _ = match (&response.display_text, &response.draw) {
+            (_, Some(DrawCommand::Fill { color: [0, 0, 0] })) => events.push(Self::Clear),
+            (_, Some(DrawCommand::Fill { color })) => events.push(Self::Fill(*color)),
+            (Some(text), _) if !text.is_empty() => events.push(Self::Text(text.clone())),
+            _ => Ok(()),
+        };
+        if let Some(mode) = &response.mode {
+            let _ = events.push(Self::Mode(mode.name()));
+        }
+        if let Some(level) = response.brightness {
+            let _ = events.push(Self::Brightness(level));
+        }
+        events
+    }
+
+    /// The event as an SSE message: a `data:` line of JSON and a blank
+    /// line. See the module docs.
+    pub fn to_sse(&self) -> heapless::String<EVENT_LINE_LEN> {
+        let mut line = heapless::String::new();
+        // EVENT_LINE_LEN covers the longest possible output.
+        let _ = line.push_str("data: {\"type\":");
+        let _ = match self {
+            Self::Text(text) => {
+                let _ = line.push_str("\"text\",\"text\":");
+                push_json_string(&mut line, Some(text));
+                Ok(())
+            }
+            Self::Clear => line.push_str("\"clear\"").map_err(|_| core::fmt::Error),
+            Self::Fill([r, g, b]) => write!(
+                line,
+                "\"fill\",\"color\":\"#{:02x}{:02x}{:02x}\"",
+                r >> 8,
+                g >> 8,
+                b >> 8
+            ),
+            Self::Mode(mode) => write!(line, "\"mode\",\"mode\":\"{mode}\""),
+            Self::Brightness(level) => write!(line, "\"brightness\",\"level\":{level}"),
+        };
+        let _ = line.push_str("}\n\n");
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_request::dispatch;
+
+    fn events(request: &[u8]) -> heapless::Vec<DisplayEvent, MAX_EVENTS_PER_CHANGE> {
+        DisplayEvent::from_response(&dispatch(request))
+    }
+
+    #[test]
+    fn changes_become_events() {
+        assert_eq!(
+            events(b"GET /text?msg=hi HTTP/1.1"),
+            [DisplayEvent::Text("hi".try_into().unwrap())]
+        );
+        assert_eq!(events(b"GET /clear HTTP/1.1"), [DisplayEvent::Clear]);
+        assert_eq!(
+            events(b"GET /color?hex=0000ff HTTP/1.1"),
+            [DisplayEvent::Fill([0, 0, 0xFFFF])]
+        );
+        assert_eq!(
+            events(b"GET /brightness?level=9 HTTP/1.1"),
+            [DisplayEvent::Brightness(9)]
+        );
+        assert_eq!(
+            events(b"GET /marquee?msg=go HTTP/1.1"),
+            [
+                DisplayEvent::Text("go".try_into().unwrap()),
+                DisplayEvent::Mode("marquee")
+            ]
+        );
+        assert!(events(b"GET /stats HTTP/1.1").is_empty());
+        assert!(events(b"GET /pixel?x=1&y=1&r=9 HTTP/1.1").is_empty());
+    }
+
+    #[test]
+    fn events_are_one_json_line_each() {
+        let text = DisplayEvent::Text("say \"hi\"\n".try_into().unwrap());
+        assert_eq!(
+            text.to_sse().as_str(),
+            "data: {\"type\":\"text\",\"text\":\"say \\\"hi\\\"\\u000a\"}\n\n"
+        );
+        assert_eq!(
+            DisplayEvent::Fill([0xFFFF, 0x8000, 0]).to_sse().as_str(),
+            "data: {\"type\":\"fill\",\"color\":\"#ff8000\"}\n\n"
+        );
+        assert_eq!(
+            DisplayEvent::Brightness(128).to_sse().as_str(),
+            "data: {\"type\":\"brightness\",\"level\":128}\n\n"
+        );
+
+        // The longest text still fits, escaped.
+        let mut long = heapless::String::new();
+        while long.push('\u{1}').is_ok() {}
+        let sse = DisplayEvent::Text(long).to_sse();
+        assert!(sse.ends_with("\\u0001\"}\n\n"), "{sse}");
+    }
+}
@@ -7,10 +7,20 @@
 //! The lookup table replaces what was a 60+-arm `match` statement and
 //! makes adding or auditing glyphs easier: every printable ASCII code
 //! appears exactly once in the table.
+//!
+//! A font can also be uploaded at run time, see [`Font::from_bytes`].
+//! Characters it has no glyph for are drawn with the built-in font.
+
+use crate::{MATRIX_HEIGHT, MATRIX_WIDTH};
+
+/// Built-in font dimensions
+pub const FONT_WIDTH: usize = 5;
+pub const FONT_HEIGHT: usize = 7;
 
-/// Font dimensions
-const FONT_WIDTH: usize = 5;
-const FONT_HEIGHT: usize = 7;
+/// Font pixels between adjacent glyphs, unless
+/// [`FrameBuffer::set_char_spacing`](crate::frame_buffer::FrameBuffer::set_char_spacing)
+/// says otherwise.
+pub const DEFAULT_CHAR_SPACING: usize = 1;
 
 /// A single character glyph (5x7 bitmap)
 pub type Glyph = [[u8; FONT_WIDTH]; FONT_HEIGHT];
@@ -21,11 +31,62 @@ const ASCII_OFFSET: usize = 32;
 /// Number of slots in the lookup table (covers ASCII 32..=126 inclusive).
 const ASCII_COUNT: usize = 95;
 
+/// Largest font file [`Font::from_bytes`] accepts, header included.
+pub const MAX_FONT_LEN: usize = 4096;
+
+/// Bytes before the first glyph of a font file: width, height and the
+/// glyph count (`u16`, little-endian).
+const FONT_HEADER_LEN: usize = 4;
+
+/// Why [`Font::from_bytes`] rejected a font file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontError {
+    /// Shorter than its header says.
+    Truncated,
+    /// Longer than its header says.
+    TooLong,
+    /// More than [`MAX_FONT_LEN`] bytes.
+    TooLarge,
+    /// Width or height is zero or larger than the panel.
+    BadSize,
+}
+
 /// Font struct containing character glyphs.
 pub struct Font {
-    // The actual glyph data lives in [`GLYPH_TABLE`] below — this struct
-    // is a thin wrapper that lets the rest of the code talk to fonts via
-    // methods rather than free functions.
+    /// An uploaded font file, already validated by [`Font::from_bytes`].
+    /// Empty for the built-in font, whose glyphs live in [`GLYPH_TABLE`]
+    /// below.
+    custom: heapless::Vec<u8, MAX_FONT_LEN>,
+}
+
+/// One character's bitmap, from either the built-in table or an uploaded
+/// font.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlyphRef<'a> {
+    /// A built-in 5x7 glyph.
+    Builtin(&'static Glyph),
+    /// Rows of an uploaded glyph, `stride` bytes each, leftmost pixel in
+    /// the top bit.
+    Packed { rows: &'a [u8], stride: usize },
+}
+
+impl GlyphRef<'_> {
+    /// True if pixel `(x, y)` of the glyph is lit. Anything outside the
+    /// glyph is dark, so a built-in glyph can be drawn in a larger cell.
+    pub fn is_lit(&self, x: usize, y: usize) -> bool {
+        match *self {
+            Self::Builtin(glyph) => glyph
+                .get(y)
+                .and_then(|row| row.get(x))
+                .is_some_and(|&pixel| pixel != 0),
+            Self::Packed { rows, stride } => {
+                x / 8 < stride
+                    && rows
+                        .get(y * stride + x / 8)
+                        .is_some_and(|&byte| byte & (0x80 >> (x % 8)) != 0)
+            }
+        }
+    }
 }
 
 impl Default for Font {
@@ -37,32 +98,119 @@ impl Default for Font {
 impl Font {
     /// Create a new font with built-in ASCII characters.
     pub const fn new() -> Self {
-        Self {}
+        Self {
+            custom: heapless::Vec::new(),
+        }
     }
 
-    /// Get a glyph for a character, returns `None` if no glyph is defined
-    /// for it. Lowercase letters fall back to their uppercase glyph.
-    pub fn get_glyph(&self, ch: char) -> Option<&'static Glyph> {
-        let code = ch as u32;
+    /// Load an uploaded font. The format is a 4-byte header (width and
+    /// height in pixels, then the glyph count as a little-endian `u16`)
+    /// followed by the glyphs for consecutive characters from `' '`
+    /// (U+0020) on. Each glyph is `height` rows of `ceil(width / 8)`
+    /// bytes, leftmost pixel in the top bit of the first byte. Glyphs
+    /// may be at most as large as the panel.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, FontError> {
+        let Some(&[width, height, count_lo, count_hi]) = data.first_chunk::<FONT_HEADER_LEN>()
+        else {
+            return Err(FontError::Truncated);
+        };
+        let (width, height) = (usize::from(width), usize::from(height));
+        if !(1..=MATRIX_WIDTH).contains(&width) || !(1..=MATRIX_HEIGHT).contains(&height) {
+            return Err(FontError::BadSize);
+        }
+        let count = usize::from(u16::from_le_bytes([count_lo, count_hi]));
+        let len = FONT_HEADER_LEN + count * width.div_ceil(8) * height;
+        if len > MAX_FONT_LEN {
+            return Err(FontError::TooLarge);
+        }
+        match data.len().cmp(&len) {
+            core::cmp::Ordering::Less => Err(FontError::Truncated),
+            core::cmp::Ordering::Greater => Err(FontError::TooLong),
+            core::cmp::Ordering::Equal => Ok(Self {
+                // Fits: `len` was checked against MAX_FONT_LEN above.
+                custom: heapless::Vec::from_slice(data).map_err(|_| FontError::TooLarge)?,
+            }),
+        }
+    }
+
+    /// True for an uploaded font, false for the built-in one.
+    pub fn is_custom(&self) -> bool {
+        !self.custom.is_empty()
+    }
 
-        // Map lowercase to uppercase, then recurse.
-        if (code as u8).is_ascii_lowercase() {
-            return self.get_glyph((code - 32) as u8 as char);
+    /// Get a built-in glyph for a character, returns `None` for control
+    /// characters. Lowercase letters (including the Latin-1 ones, e.g.
+    /// `ä`) fall back to their uppercase glyph, and any other character
+    /// without a glyph gets a box.
+    pub fn get_glyph(&self, ch: char) -> Option<&'static Glyph> {
+        if ch.is_control() {
+            return None;
         }
+        let code = to_upper(ch) as usize;
+        let slot = match code {
+            ASCII_OFFSET..LATIN1_OFFSET => GLYPH_TABLE.get(code - ASCII_OFFSET).copied().flatten(),
+            LATIN1_OFFSET.. => LATIN1_TABLE.get(code - LATIN1_OFFSET).copied().flatten(),
+            _ => None,
+        };
+        Some(slot.unwrap_or(&BOX))
+    }
 
-        let idx = (code as usize).checked_sub(ASCII_OFFSET)?;
-        let slot = GLYPH_TABLE.get(idx)?;
-        *slot
+    /// The glyph to draw for `ch`: the uploaded font's (trying the
+    /// uppercase letter if it has no lowercase one), else the built-in
+    /// one from [`Self::get_glyph`].
+    pub fn glyph(&self, ch: char) -> Option<GlyphRef<'_>> {
+        self.custom_glyph(ch)
+            .or_else(|| self.custom_glyph(to_upper(ch)))
+            .or_else(|| self.get_glyph(ch).map(GlyphRef::Builtin))
+    }
+
+    /// `ch`'s glyph in the uploaded font, if there is one.
+    fn custom_glyph(&self, ch: char) -> Option<GlyphRef<'_>> {
+        let (&[width, height, count_lo, count_hi], glyphs) =
+            self.custom.split_first_chunk::<FONT_HEADER_LEN>()?;
+        let index = (ch as usize).checked_sub(ASCII_OFFSET)?;
+        if index >= usize::from(u16::from_le_bytes([count_lo, count_hi])) {
+            return None;
+        }
+        let stride = usize::from(width).div_ceil(8);
+        let glyph_len = stride * usize::from(height);
+        let rows = glyphs.get(index * glyph_len..(index + 1) * glyph_len)?;
+        Some(GlyphRef::Packed { rows, stride })
     }
 
     /// Get font width in pixels
-    pub const fn width(&self) -> usize {
-        FONT_WIDTH
+    pub fn width(&self) -> usize {
+        self.custom.first().map_or(FONT_WIDTH, |&w| usize::from(w))
     }
 
     /// Get font height in pixels
-    pub const fn height(&self) -> usize {
-        FONT_HEIGHT
+    pub fn height(&self) -> usize {
+        self.custom.get(1).map_or(FONT_HEIGHT, |&h| usize::from(h))
+    }
+
+    /// Rendered width of `text` in pixels: one glyph width per character
+    /// plus `spacing` pixels between adjacent characters (none after the
+    /// last one). Characters without a glyph still take up a cell, to
+    /// match how [`crate::frame_buffer::FrameBuffer::display_text`] lays
+    /// them out.
+    pub fn text_width(&self, text: &str, spacing: usize) -> usize {
+        let count = text.chars().count();
+        if count == 0 {
+            return 0;
+        }
+        count * self.width() + (count - 1) * spacing
+    }
+}
+
+/// The uppercase letter for a lowercase ASCII or Latin-1 letter, which
+/// sits 32 code points below it (`÷` and `ÿ` are the exceptions); any
+/// other character unchanged.
+fn to_upper(ch: char) -> char {
+    match ch {
+        'a'..='z' | '\u{e0}'..='\u{fe}' if ch != '\u{f7}' => {
+            char::from_u32(ch as u32 - 32).unwrap_or(ch)
+        }
+        _ => ch,
     }
 }
 
@@ -166,6 +314,113 @@ const GLYPH_TABLE: [Option<&'static Glyph>; ASCII_COUNT] = [
     None,               // 126 (0x7e) '~' (no glyph)
 ];
 
+/// First code point of the Latin-1 supplement block.
+const LATIN1_OFFSET: usize = 0xA0;
+
+/// Number of slots in the Latin-1 table (covers U+00A0..=U+00FF).
+const LATIN1_COUNT: usize = 96;
+
+/// Latin-1 supplement lookup table: index = (code_point - LATIN1_OFFSET).
+/// Mostly the Nordic and German letters; the rest draw [`BOX`].
+const LATIN1_TABLE: [Option<&'static Glyph>; LATIN1_COUNT] = [
+    Some(&SPACE),             // 160 (0xa0) 'NBSP'
+    None,                     // 161 (0xa1) '¡' (no glyph)
+    None,                     // 162 (0xa2) '¢' (no glyph)
+    None,                     // 163 (0xa3) '£' (no glyph)
+    None,                     // 164 (0xa4) '¤' (no glyph)
+    None,                     // 165 (0xa5) '¥' (no glyph)
+    None,                     // 166 (0xa6) '¦' (no glyph)
+    None,                     // 167 (0xa7) '§' (no glyph)
+    None,                     // 168 (0xa8) '¨' (no glyph)
+    None,                     // 169 (0xa9) '©' (no glyph)
+    None,                     // 170 (0xaa) 'ª' (no glyph)
+    None,                     // 171 (0xab) '«' (no glyph)
+    None,                     // 172 (0xac) '¬' (no glyph)
+    None,                     // 173 (0xad) '­' (no glyph)
+    None,                     // 174 (0xae) '®' (no glyph)
+    None,                     // 175 (0xaf) '¯' (no glyph)
+    Some(&DEGREE),            // 176 (0xb0) '°'
+    None,                     // 177 (0xb1) '±' (no glyph)
+    None,                     // 178 (0xb2) '²' (no glyph)
+    None,                     // 179 (0xb3) '³' (no glyph)
+    None,                     // 180 (0xb4) '´' (no glyph)
+    None,                     // 181 (0xb5) 'µ' (no glyph)
+    None,                     // 182 (0xb6) '¶' (no glyph)
+    None,                     // 183 (0xb7) '·' (no glyph)
+    None,                     // 184 (0xb8) '¸' (no glyph)
+    None,                     // 185 (0xb9) '¹' (no glyph)
+    None,                     // 186 (0xba) 'º' (no glyph)
+    None,                     // 187 (0xbb) '»' (no glyph)
+    None,                     // 188 (0xbc) '¼' (no glyph)
+    None,                     // 189 (0xbd) '½' (no glyph)
+    None,                     // 190 (0xbe) '¾' (no glyph)
+    None,                     // 191 (0xbf) '¿' (no glyph)
+    None,                     // 192 (0xc0) 'À' (no glyph)
+    None,                     // 193 (0xc1) 'Á' (no glyph)
+    None,                     // 194 (0xc2) 'Â' (no glyph)
+    None,                     // 195 (0xc3) 'Ã' (no glyph)
+    Some(&UPPER_A_DIAERESIS), // 196 (0xc4) 'Ä'
+    Some(&UPPER_A_RING),      // 197 (0xc5) 'Å'
+    Some(&UPPER_AE),          // 198 (0xc6) 'Æ'
+    None,                     // 199 (0xc7) 'Ç' (no glyph)
+    None,                     // 200 (0xc8) 'È' (no glyph)
+    Some(&UPPER_E_ACUTE),     // 201 (0xc9) 'É'
+    None,                     // 202 (0xca) 'Ê' (no glyph)
+    None,                     // 203 (0xcb) 'Ë' (no glyph)
+    None,                     // 204 (0xcc) 'Ì' (no glyph)
+    None,                     // 205 (0xcd) 'Í' (no glyph)
+    None,                     // 206 (0xce) 'Î' (no glyph)
+    None,                     // 207 (0xcf) 'Ï' (no glyph)
+    None,                     // 208 (0xd0) 'Ð' (no glyph)
+    None,                     // 209 (0xd1) 'Ñ' (no glyph)
+    None,                     // 210 (0xd2) 'Ò' (no glyph)
+    None,                     // 211 (0xd3) 'Ó' (no glyph)
+    None,                     // 212 (0xd4) 'Ô' (no glyph)
+    None,                     // 213 (0xd5) 'Õ' (no glyph)
+    Some(&UPPER_O_DIAERESIS), // 214 (0xd6) 'Ö'
+    None,                     // 215 (0xd7) '×' (no glyph)
+    Some(&UPPER_O_STROKE),    // 216 (0xd8) 'Ø'
+    None,                     // 217 (0xd9) 'Ù' (no glyph)
+    None,                     // 218 (0xda) 'Ú' (no glyph)
+    None,                     // 219 (0xdb) 'Û' (no glyph)
+    Some(&UPPER_U_DIAERESIS), // 220 (0xdc) 'Ü'
+    None,                     // 221 (0xdd) 'Ý' (no glyph)
+    None,                     // 222 (0xde) 'Þ' (no glyph)
+    Some(&SHARP_S),           // 223 (0xdf) 'ß'
+    None,                     // 224 (0xe0) 'à' (no glyph)
+    None,                     // 225 (0xe1) 'á' (no glyph)
+    None,                     // 226 (0xe2) 'â' (no glyph)
+    None,                     // 227 (0xe3) 'ã' (no glyph)
+    None,                     // 228 (0xe4) 'ä' (no glyph)
+    None,                     // 229 (0xe5) 'å' (no glyph)
+    None,                     // 230 (0xe6) 'æ' (no glyph)
+    None,                     // 231 (0xe7) 'ç' (no glyph)
+    None,                     // 232 (0xe8) 'è' (no glyph)
+    None,                     // 233 (0xe9) 'é' (no glyph)
+    None,                     // 234 (0xea) 'ê' (no glyph)
+    None,                     // 235 (0xeb) 'ë' (no glyph)
+    None,                     // 236 (0xec) 'ì' (no glyph)
+    None,                     // 237 (0xed) 'í' (no glyph)
+    None,                     // 238 (0xee) 'î' (no glyph)
+    None,                     // 239 (0xef) 'ï' (no glyph)
+    None,                     // 240 (0xf0) 'ð' (no glyph)
+    None,                     // 241 (0xf1) 'ñ' (no glyph)
+    None,                     // 242 (0xf2) 'ò' (no glyph)
+    None,                     // 243 (0xf3) 'ó' (no glyph)
+    None,                     // 244 (0xf4) 'ô' (no glyph)
+    None,                     // 245 (0xf5) 'õ' (no glyph)
+    None,                     // 246 (0xf6) 'ö' (no glyph)
+    None,                     // 247 (0xf7) '÷' (no glyph)
+    None,                     // 248 (0xf8) 'ø' (no glyph)
+    None,                     // 249 (0xf9) 'ù' (no glyph)
+    None,                     // 250 (0xfa) 'ú' (no glyph)
+    None,                     // 251 (0xfb) 'û' (no glyph)
+    None,                     // 252 (0xfc) 'ü' (no glyph)
+    None,                     // 253 (0xfd) 'ý' (no glyph)
+    None,                     // 254 (0xfe) 'þ' (no glyph)
+    None,                     // 255 (0xff) 'ÿ' (no glyph)
+];
+
 // --- Glyph definitions -------------------------------------------------------
 
 const SPACE: Glyph = [
@@ -758,6 +1013,110 @@ const UPPER_Z: Glyph = [
     [1, 1, 1, 1, 1],
 ];
 
+// --- Latin-1 glyphs: the diacritic takes the top row or two ------------------
+
+const DEGREE: Glyph = [
+    [0, 1, 1, 0, 0],
+    [1, 0, 0, 1, 0],
+    [1, 0, 0, 1, 0],
+    [0, 1, 1, 0, 0],
+    [0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0],
+];
+
+const UPPER_A_DIAERESIS: Glyph = [
+    [0, 1, 0, 1, 0],
+    [0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 0],
+    [1, 0, 0, 0, 1],
+    [1, 1, 1, 1, 1],
+    [1, 0, 0, 0, 1],
+    [1, 0, 0, 0, 1],
+];
+
+const UPPER_A_RING: Glyph = [
+    [0, 0, 1, 0, 0],
+    [0, 1, 0, 1, 0],
+    [0, 0, 1, 0, 0],
+    [0, 1, 0, 1, 0],
+    [1, 0, 0, 0, 1],
+    [1, 1, 1, 1, 1],
+    [1, 0, 0, 0, 1],
+];
+
+const UPPER_AE: Glyph = [
+    [0, 1, 1, 1, 1],
+    [1, 0, 1, 0, 0],
+    [1, 0, 1, 0, 0],
+    [1, 1, 1, 1, 1],
+    [1, 0, 1, 0, 0],
+    [1, 0, 1, 0, 0],
+    [1, 0, 1, 1, 1],
+];
+
+const UPPER_E_ACUTE: Glyph = [
+    [0, 0, 0, 1, 0],
+    [0, 0, 1, 0, 0],
+    [1, 1, 1, 1, 1],
+    [1, 0, 0, 0, 0],
+    [1, 1, 1, 1, 0],
+    [1, 0, 0, 0, 0],
+    [1, 1, 1, 1, 1],
+];
+
+const UPPER_O_DIAERESIS: Glyph = [
+    [0, 1, 0, 1, 0],
+    [0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 0],
+    [1, 0, 0, 0, 1],
+    [1, 0, 0, 0, 1],
+    [1, 0, 0, 0, 1],
+    [0, 1, 1, 1, 0],
+];
+
+const UPPER_O_STROKE: Glyph = [
+    [0, 1, 1, 1, 0],
+    [1, 0, 0, 1, 1],
+    [1, 0, 1, 0, 1],
+    [1, 0, 1, 0, 1],
+    [1, 0, 1, 0, 1],
+    [1, 1, 0, 0, 1],
+    [0, 1, 1, 1, 0],
+];
+
+const UPPER_U_DIAERESIS: Glyph = [
+    [0, 1, 0, 1, 0],
+    [0, 0, 0, 0, 0],
+    [1, 0, 0, 0, 1],
+    [1, 0, 0, 0, 1],
+    [1, 0, 0, 0, 1],
+    [1, 0, 0, 0, 1],
+    [0, 1, 1, 1, 0],
+];
+
+const SHARP_S: Glyph = [
+    [0, 1, 1, 0, 0],
+    [1, 0, 0, 1, 0],
+    [1, 0, 1, 0, 0],
+    [1, 0, 1, 1, 0],
+    [1, 0, 0, 0, 1],
+    [1, 0, 0, 0, 1],
+    [1, 0, 1, 1, 0],
+];
+
+/// Drawn for printable characters without a glyph, so they show up as
+/// something rather than a gap.
+const BOX: Glyph = [
+    [1, 1, 1, 1, 1],
+    [1, 0, 0, 0, 1],
+    [1, 0, 0, 0, 1],
+    [1, 0, 0, 0, 1],
+    [1, 0, 0, 0, 1],
+    [1, 0, 0, 0, 1],
+    [1, 1, 1, 1, 1],
+];
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -900,6 +1259,15 @@ mod tests {
         assert_eq!(exclamation[6][2], 1); // Dot at bottom
     }
 
+    #[test]
+    fn test_text_width() {
+        let font = Font::new();
+        assert_eq!(font.text_width("", 1), 0);
+        assert_eq!(font.text_width("A", 1), FONT_WIDTH);
+        assert_eq!(font.text_width("AB", 1), 2 * FONT_WIDTH + 1);
+        assert_eq!(font.text_width("AB", 0), 2 * FONT_WIDTH);
+    }
+
     #[test]
     fn test_glyph_immutability() {
         let font = Font::new();
@@ -909,4 +1277,128 @@ mod tests {
         let glyph2 = font.get_glyph('X').unwrap();
         assert_eq!(glyph1 as *const _, glyph2 as *const _);
     }
+
+    #[test]
+    fn test_latin1_letters() {
+        let font = Font::new();
+        for (lower, upper, glyph) in [
+            ('\u{e5}', '\u{c5}', &UPPER_A_RING),
+            ('\u{e4}', '\u{c4}', &UPPER_A_DIAERESIS),
+            ('\u{f6}', '\u{d6}', &UPPER_O_DIAERESIS),
+        ] {
+            assert_eq!(font.get_glyph(upper), Some(glyph));
+            assert_eq!(
+                font.get_glyph(lower).unwrap() as *const _,
+                font.get_glyph(upper).unwrap() as *const _,
+                "{lower} should map to {upper}"
+            );
+        }
+        assert_eq!(font.get_glyph('\u{a0}'), Some(&SPACE));
+        assert_eq!(font.get_glyph('\u{b0}'), Some(&DEGREE));
+        // Not letters: no case mapping.
+        assert_eq!(font.get_glyph('\u{f7}'), Some(&BOX));
+        assert_eq!(font.get_glyph('\u{ff}'), Some(&BOX));
+    }
+
+    #[test]
+    fn test_unmapped_characters_get_a_box() {
+        let font = Font::new();
+        // U+0161 used to be truncated to 0x61 ('a') and drawn as 'A'.
+        for ch in ['\u{161}', '\u{20ac}', '\u{1f600}', '[', '~', '\u{a3}'] {
+            assert_eq!(font.get_glyph(ch), Some(&BOX), "{ch}");
+        }
+        // Control characters, including C1 ones, still draw nothing.
+        assert!(font.get_glyph('\u{85}').is_none());
+        assert!(font.get_glyph('\u{9f}').is_none());
+    }
+
+    /// A 10x2 font with glyphs for `' '` (blank) and `'!'` (a frame:
+    /// the top row lit, then only the outer columns).
+    fn two_glyph_font() -> [u8; 12] {
+        [
+            10, 2, 2, 0, // header
+            0x00, 0x00, 0x00, 0x00, // ' '
+            0xFF, 0xC0, 0x80, 0x40, // '!'
+        ]
+    }
+
+    #[test]
+    fn test_custom_font_from_bytes() {
+        let font = Font::from_bytes(&two_glyph_font()).unwrap();
+        assert!(font.is_custom());
+        assert!(!Font::new().is_custom());
+        assert_eq!(font.width(), 10);
+        assert_eq!(font.height(), 2);
+        assert_eq!(font.text_width("!!", 1), 21);
+
+        let glyph = font.glyph('!').unwrap();
+        assert!((0..10).all(|x| glyph.is_lit(x, 0)));
+        assert!(glyph.is_lit(0, 1) && glyph.is_lit(9, 1));
+        assert!((1..9).all(|x| !glyph.is_lit(x, 1)));
+        // Padding bits and anything past the glyph stay dark.
+        assert!(!glyph.is_lit(10, 0) && !glyph.is_lit(0, 2));
+        assert!((0..10).all(|x| !font.glyph(' ').unwrap().is_lit(x, 0)));
+    }
+
+    #[test]
+    fn test_custom_font_falls_back_to_builtin() {
+        let font = Font::from_bytes(&two_glyph_font()).unwrap();
+        assert_eq!(font.glyph('A'), Some(GlyphRef::Builtin(&UPPER_A)));
+        assert_eq!(font.glyph('a'), Some(GlyphRef::Builtin(&UPPER_A)));
+        assert_eq!(font.glyph('\n'), None);
+        assert_eq!(
+            Font::new().glyph('!'),
+            Some(GlyphRef::Builtin(&EXCLAMATION))
+        );
+    }
+
+    #[test]
+    fn test_custom_font_lowercase_uses_its_uppercase() {
+        // 1x1 glyphs for ' ' through 'A', only 'A' lit.
+        let mut data = [0u8; FONT_HEADER_LEN + 34];
+        data[..FONT_HEADER_LEN].copy_from_slice(&[1, 1, 34, 0]);
+        data[FONT_HEADER_LEN + 33] = 0x80;
+        let font = Font::from_bytes(&data).unwrap();
+        let a = font.glyph('a').unwrap();
+        assert_eq!(Some(a), font.glyph('A'));
+        assert!(a.is_lit(0, 0));
+    }
+
+    #[test]
+    fn test_custom_font_rejects_bad_files() {
+        let font = two_glyph_font();
+        assert_eq!(
+            Font::from_bytes(&font[..3]).err(),
+            Some(FontError::Truncated)
+        );
+        assert_eq!(
+            Font::from_bytes(&font[..11]).err(),
+            Some(FontError::Truncated)
+        );
+        let mut long = [0u8; 13];
+        long[..12].copy_from_slice(&font);
+        assert_eq!(Font::from_bytes(&long).err(), Some(FontError::TooLong));
+        assert_eq!(
+            Font::from_bytes(&[0, 7, 0, 0]).err(),
+            Some(FontError::BadSize)
+        );
+        assert_eq!(
+            Font::from_bytes(&[5, 0, 0, 0]).err(),
+            Some(FontError::BadSize)
+        );
+        assert_eq!(
+            Font::from_bytes(&[5, MATRIX_HEIGHT as u8 + 1, 0, 0]).err(),
+            Some(FontError::BadSize)
+        );
+        assert_eq!(
+            Font::from_bytes(&[MATRIX_WIDTH as u8 + 1, 7, 0, 0]).err(),
+            Some(FontError::BadSize)
+        );
+        // 88x88 is fine, but 95 such glyphs aren't.
+        assert_eq!(
+            Font::from_bytes(&[88, 88, 95, 0]).err(),
+            Some(FontError::TooLarge)
+        );
+        assert!(Font::from_bytes(&[88, 88, 0, 0]).is_ok());
+    }
 }
@@ -3,21 +3,292 @@
 //! Pure data type that owns the 88x88 RGB pixel array plus text rendering
 //! logic. No GPIO dependencies, so it can be unit-tested on the host.
 
-use crate::font::Font;
+use core::ops::Range;
+
+use crate::command::DrawCommand;
+use crate::dirty::DirtyRect;
+use crate::font::{DEFAULT_CHAR_SPACING, FONT_HEIGHT, Font};
+use crate::gradient::{self, GradientDir, GradientMode};
+use crate::mode::TestPattern;
+use crate::scene::Scene;
+use crate::scroll::{RowBits, ScrollText, VerticalScroll};
+use crate::sprite::Sprite;
 use crate::{MATRIX_HEIGHT, MATRIX_WIDTH};
 
 /// RGB color for a single pixel (16-bit per channel).
 pub type Pixel = [u16; 3];
 
+/// A pixel as a [`FrameBuffer`] keeps it: a [`Pixel`], or with the
+/// `pixel8` feature 8 bits per channel, halving the buffer. Drawing
+/// other than [`FrameBuffer::set_pixel`] still takes 16-bit channels;
+/// see [`store_pixel`] and [`load_pixel`].
+pub type StoredPixel = [Channel; 3];
+
+/// One channel as a [`FrameBuffer`] stores it, and as
+/// [`FrameBuffer::set_pixel`] takes it: `u16`, or `u8` with `pixel8`.
+#[cfg(not(feature = "pixel8"))]
+pub type Channel = u16;
+#[cfg(feature = "pixel8")]
+pub type Channel = u8;
+
+/// Reduce `px` to how a [`FrameBuffer`] stores it: unchanged, or with
+/// `pixel8` each channel rounded to the nearest of 256 levels.
+pub const fn store_pixel(px: Pixel) -> StoredPixel {
+    #[cfg(feature = "pixel8")]
+    {
+        const fn channel(value: u16) -> u8 {
+            ((value as u32 * 0xFF + 0x7FFF) / 0xFFFF) as u8
+        }
+        [channel(px[0]), channel(px[1]), channel(px[2])]
+    }
+    #[cfg(not(feature = "pixel8"))]
+    px
+}
+
+/// Like [`store_pixel`], but with `pixel8` each channel is rounded up or
+/// down by [`dither_channel`] for physical position `(x, y)`.
+pub const fn store_pixel_dithered(px: Pixel, x: usize, y: usize) -> StoredPixel {
+    #[cfg(feature = "pixel8")]
+    {
+        [
+            dither_channel(px[0], x, y),
+            dither_channel(px[1], x, y),
+            dither_channel(px[2], x, y),
+        ]
+    }
+    #[cfg(not(feature = "pixel8"))]
+    {
+        let _ = (x, y);
+        px
+    }
+}
+
+/// Order in which the cells of a 4x4 block round up as a channel climbs
+/// from one 8-bit level to the next.
+const BAYER_4X4: [[u32; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Reduce a 16-bit channel to 8 bits with an ordered (Bayer) dither:
+/// whether it rounds up depends on `(x, y)`, so an area between two
+/// levels shows a mix of both in proportion instead of a band edge.
+/// Values exactly on a level are kept, and the pattern depends only on
+/// the position, so it stands still from frame to frame.
+pub const fn dither_channel(value: u16, x: usize, y: usize) -> u8 {
+    let threshold = (BAYER_4X4[y % 4][x % 4] * 2 + 1) * 0xFFFF / 32;
+    ((value as u32 * 0xFF + threshold) / 0xFFFF) as u8
+}
+
+/// Expand a stored pixel back to 16 bits per channel. With `pixel8`
+/// each byte is repeated (`0xAB` to `0xABAB`), so `0xFF` is full on.
+pub const fn load_pixel(px: StoredPixel) -> Pixel {
+    #[cfg(feature = "pixel8")]
+    {
+        [
+            px[0] as u16 * 0x101,
+            px[1] as u16 * 0x101,
+            px[2] as u16 * 0x101,
+        ]
+    }
+    #[cfg(not(feature = "pixel8"))]
+    px
+}
+
+/// Default text color: full-brightness white.
+pub const DEFAULT_TEXT_COLOR: Pixel = [0xFFFF, 0xFFFF, 0xFFFF];
+
+/// Expand a packed RGB565 color into a 16-bit-per-channel [`Pixel`].
+///
+/// Each field is scaled linearly to the full `0..=0xFFFF` range:
+/// `channel16 = round(field * 0xFFFF / field_max)` where `field_max` is
+/// 31 for the 5-bit red and blue fields and 63 for the 6-bit green field.
+/// So 0 maps to 0, the field maximum maps to exactly `0xFFFF`, and each
+/// 5-bit step is ≈2114 (6-bit step ≈1040) counts.
+pub const fn rgb565_to_pixel(color: u16) -> Pixel {
+    const fn expand(field: u16, max: u32) -> u16 {
+        ((field as u32 * 0xFFFF + max / 2) / max) as u16
+    }
+    let r = (color >> 11) & 0x1F;
+    let g = (color >> 5) & 0x3F;
+    let b = color & 0x1F;
+    [expand(r, 31), expand(g, 63), expand(b, 31)]
+}
+
+/// Mix `src` over `dst` with opacity `alpha`: 0 keeps `dst`, 255 gives
+/// `src`. Each 16-bit channel is `(src * alpha + dst * (255 - alpha)) /
+/// 255`, rounded to nearest.
+pub const fn blend(dst: Pixel, src: Pixel, alpha: u8) -> Pixel {
+    const fn channel(dst: u16, src: u16, alpha: u32) -> u16 {
+        ((src as u32 * alpha + dst as u32 * (255 - alpha) + 127) / 255) as u16
+    }
+    let alpha = alpha as u32;
+    [
+        channel(dst[0], src[0], alpha),
+        channel(dst[1], src[1], alpha),
+        channel(dst[2], src[2], alpha),
+    ]
+}
+
+/// Convert an HSV color to a 16-bit-per-channel [`Pixel`].
+///
+/// `h` is the hue in degrees and wraps, so `360.0` and `-360.0` are both
+/// red. `s` (saturation) and `v` (value) are clamped to `0.0..=1.0`. At
+/// full saturation and value the six 60° sector boundaries are pure
+/// colors: red, yellow, green, cyan, blue and magenta.
+pub fn hsv_to_pixel(h: f32, s: f32, v: f32) -> Pixel {
+    fn channel(level: f32) -> u16 {
+        (level * 65535.0 + 0.5) as u16
+    }
+    let s = s.clamp(0.0, 1.0);
+    let v = v.clamp(0.0, 1.0);
+    let mut h = h % 360.0;
+    if h < 0.0 {
+        h += 360.0;
+    }
+    // A tiny negative hue can round up to exactly 360.0 above.
+    if h >= 360.0 {
+        h = 0.0;
+    }
+    let sector = (h / 60.0) as u32;
+    let f = h / 60.0 - sector as f32;
+    let p = v * (1.0 - s);
+    let q = v * (1.0 - s * f);
+    let t = v * (1.0 - s * (1.0 - f));
+    let (r, g, b) = match sector {
+        0 => (v, t, p),
+        1 => (q, v, p),
+        2 => (p, v, t),
+        3 => (p, q, v),
+        4 => (t, p, v),
+        _ => (v, p, q),
+    };
+    [channel(r), channel(g), channel(b)]
+}
+
+/// How the panel is mounted. Logical coordinates passed to
+/// [`FrameBuffer::set_pixel`] are mapped to physical ones with
+/// [`Self::map`], so every drawing primitive follows the setting.
+///
+/// Rotations are clockwise. A single panel is square, so the logical
+/// coordinate space stays 88x88 in every orientation. Quarter turns
+/// would swap the axes of chained panels, so
+/// [`FrameBuffer::set_orientation`] ignores them there.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    #[default]
+    Normal,
+    Rot90,
+    Rot180,
+    Rot270,
+    /// Mirrored left-to-right.
+    FlipH,
+    /// Mirrored top-to-bottom.
+    FlipV,
+}
+
+/// Rotating by 90/270 swaps the axes, which only maps the display onto
+/// itself when it is square.
+const SQUARE: bool = MATRIX_WIDTH == MATRIX_HEIGHT;
+
+impl Orientation {
+    /// Physical `(x, y)` of logical pixel `(x, y)`. Both must be on the
+    /// panel.
+    pub const fn map(self, x: usize, y: usize) -> (usize, usize) {
+        const MAX_X: usize = MATRIX_WIDTH - 1;
+        const MAX_Y: usize = MATRIX_HEIGHT - 1;
+        match self {
+            Self::Normal => (x, y),
+            Self::Rot90 => (MAX_Y - y, x),
+            Self::Rot180 => (MAX_X - x, MAX_Y - y),
+            Self::Rot270 => (y, MAX_X - x),
+            Self::FlipH => (MAX_X - x, y),
+            Self::FlipV => (x, MAX_Y - y),
+        }
+    }
+}
+
+/// Horizontal placement of each line of text. See
+/// [`FrameBuffer::set_text_align`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    /// Start at the left margin.
+    #[default]
+    Left,
+    /// Centre the rendered width on the panel.
+    Center,
+    /// End at the right margin.
+    Right,
+}
+
+/// Left margin of [`FrameBuffer::display_text`], in pixels. Right-aligned
+/// text keeps the same margin on the other side.
+const TEXT_START_X: usize = 4;
+
+/// Largest [`FrameBuffer::set_text_scale`] factor with the built-in font:
+/// one glyph as tall as the panel allows.
+pub const MAX_TEXT_SCALE: usize = MATRIX_HEIGHT / FONT_HEIGHT;
+
+/// Most lines [`FrameBuffer::display_text_lines`] can stack with at least
+/// one blank row between them. Further lines are dropped.
+pub const MAX_TEXT_LINES: usize = MATRIX_HEIGHT / (FONT_HEIGHT + 1);
+
 /// 88x88 RGB frame buffer.
 ///
 /// `pixels[row][col]` is `[r, g, b]` with each channel stored as `u16` to
-/// match the LED panel's 16-bit PWM depth. Indexing is `pixels[y][x]`
+/// match the LED panel's 16-bit PWM depth (`u8` with `pixel8`, see
+/// [`StoredPixel`]). Indexing is `pixels[y][x]`
 /// (row-major), matching the natural "row, column" coordinate system used
 /// by the rest of the driver.
 pub struct FrameBuffer {
-    pixels: [[Pixel; MATRIX_WIDTH]; MATRIX_HEIGHT],
+    pixels: [[StoredPixel; MATRIX_WIDTH]; MATRIX_HEIGHT],
     font: Font,
+    /// Color used by [`Self::display_text`].
+    text_color: Pixel,
+    /// Colors for scrolling text instead of `text_color`. See
+    /// [`Self::set_scroll_gradient`].
+    scroll_gradient: Option<GradientMode>,
+    /// Active scrolling message, if any. See [`Self::set_scroll_text`]
+    /// and [`Self::set_vscroll_lines`].
+    scroll: Option<Scroll>,
+    /// Logical-to-physical coordinate transform. See [`Orientation`].
+    orientation: Orientation,
+    /// Glyph pixel size used by text rendering. See [`Self::set_text_scale`].
+    text_scale: usize,
+    /// Horizontal placement of text. See [`Self::set_text_align`].
+    text_align: Align,
+    /// Font pixels between characters. See [`Self::set_char_spacing`].
+    char_spacing: usize,
+    /// Font pixels between stacked lines, `None` to spread them out.
+    /// See [`Self::set_line_spacing`].
+    line_spacing: Option<usize>,
+    /// Retained drawing that text is rendered over. See
+    /// [`Self::push_command`].
+    scene: Scene,
+    /// Physical area drawn since [`Self::take_dirty`]. See
+    /// [`crate::dirty`].
+    dirty: Option<DirtyRect>,
+    /// Dither channels as they are stored. See [`Self::set_dithering`].
+    dithering: bool,
+}
+
+/// A scrolling message: a marquee or a vertical ticker.
+enum Scroll {
+    Horizontal(ScrollText),
+    Vertical(VerticalScroll),
+}
+
+impl Scroll {
+    fn speed_px(&self) -> usize {
+        match self {
+            Self::Horizontal(scroll) => scroll.speed_px(),
+            Self::Vertical(scroll) => scroll.speed_px(),
+        }
+    }
+
+    fn advance_by(&mut self, px: usize) {
+        match self {
+            Self::Horizontal(scroll) => scroll.advance_by(px),
+            Self::Vertical(scroll) => scroll.advance_by(px),
+        }
+    }
 }
 
 impl Default for FrameBuffer {
@@ -30,165 +301,2625 @@ impl FrameBuffer {
     /// Create a fresh, blank frame buffer with the built-in font.
     pub const fn new() -> Self {
         Self {
-            pixels: [[[0u16; 3]; MATRIX_WIDTH]; MATRIX_HEIGHT],
+            pixels: [[[0; 3]; MATRIX_WIDTH]; MATRIX_HEIGHT],
             font: Font::new(),
+            text_color: DEFAULT_TEXT_COLOR,
+            scroll_gradient: None,
+            scroll: None,
+            orientation: Orientation::Normal,
+            text_scale: 1,
+            text_align: Align::Left,
+            char_spacing: DEFAULT_CHAR_SPACING,
+            line_spacing: None,
+            scene: Scene::new(),
+            dirty: None,
+            dithering: false,
         }
     }
 
     /// Reset every pixel to black (`[0, 0, 0]`).
     pub fn clear(&mut self) {
-        for row in self.pixels.iter_mut() {
-            for px in row.iter_mut() {
-                *px = [0, 0, 0];
+        self.fill(0, 0, 0);
+    }
+
+    /// Draw `cmd` and keep it in the scene, so text rendered later
+    /// (a new message, the next scroll step) goes over it instead of
+    /// wiping it. Returns false if the scene is full: `cmd` is drawn, but
+    /// only until the next text redraw. See [`Scene::push`].
+    pub fn push_command(&mut self, cmd: DrawCommand) -> bool {
+        cmd.apply(self);
+        self.scene.push(cmd)
+    }
+
+    /// Forget the scene kept by [`Self::push_command`]. The pixels stay
+    /// until the next text redraw, which starts from black again.
+    pub fn clear_scene(&mut self) {
+        self.scene.clear();
+    }
+
+    /// The commands text is rendered over.
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    /// Record that the `width` x `height` block at `(x, y)`, in physical
+    /// coordinates, changed. Drawing calls do this themselves; call it
+    /// to have an area sent to the panel again anyway. Parts off the
+    /// panel are ignored.
+    pub fn mark_dirty(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        if let Some(rect) = DirtyRect::new(x, y, width, height) {
+            self.dirty = Some(self.dirty.map_or(rect, |dirty| dirty.union(rect)));
+        }
+    }
+
+    /// True if anything was drawn since [`Self::take_dirty`].
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.is_some()
+    }
+
+    /// The physical area drawn since [`Self::take_dirty`], if any.
+    pub fn dirty_rect(&self) -> Option<DirtyRect> {
+        self.dirty
+    }
+
+    /// Return the area drawn so far and start tracking afresh, e.g.
+    /// once the frame has been shown.
+    pub fn take_dirty(&mut self) -> Option<DirtyRect> {
+        self.dirty.take()
+    }
+
+    /// Start a text redraw: black, then the scene.
+    pub(crate) fn clear_to_scene(&mut self) {
+        self.clear();
+        let scene = core::mem::take(&mut self.scene);
+        scene.render(self);
+        self.scene = scene;
+    }
+
+    /// Set every pixel to the same RGB color.
+    pub fn fill(&mut self, r: u16, g: u16, b: u16) {
+        self.pixels = [[store_pixel([r, g, b]); MATRIX_WIDTH]; MATRIX_HEIGHT];
+        if self.dithering {
+            for (y, row) in self.pixels.iter_mut().enumerate() {
+                for (x, px) in row.iter_mut().enumerate() {
+                    *px = store_pixel_dithered([r, g, b], x, y);
+                }
             }
         }
+        self.dirty = Some(DirtyRect::FULL);
     }
 
-    /// Set a single pixel's RGB color. Out-of-bounds writes are silently
-    /// dropped to mirror the behaviour of the original driver.
-    pub fn set_pixel(&mut self, x: usize, y: usize, r: u16, g: u16, b: u16) {
-        if x < MATRIX_WIDTH && y < MATRIX_HEIGHT {
-            self.pixels[y][x] = [r, g, b];
+    /// Fill the panel with a gradient from `start` to `end`, running
+    /// `direction` in logical coordinates: the first column, row or
+    /// corner is exactly `start` and the last exactly `end`. With
+    /// [`Self::set_dithering`] on, the steps in between don't band.
+    pub fn fill_gradient(&mut self, start: Pixel, end: Pixel, direction: GradientDir) {
+        for y in 0..MATRIX_HEIGHT {
+            for x in 0..MATRIX_WIDTH {
+                let (step, steps) = match direction {
+                    GradientDir::Horizontal => (x, MATRIX_WIDTH - 1),
+                    GradientDir::Vertical => (y, MATRIX_HEIGHT - 1),
+                    GradientDir::Diagonal => (x + y, MATRIX_WIDTH + MATRIX_HEIGHT - 2),
+                };
+                let (px, py) = self.orientation.map(x, y);
+                self.pixels[py][px] = self.store(gradient::lerp(start, end, step, steps), px, py);
+            }
         }
+        self.dirty = Some(DirtyRect::FULL);
     }
 
-    /// Read a pixel's RGB color. Returns black for out-of-bounds reads.
-    pub fn get_pixel(&self, x: usize, y: usize) -> Pixel {
-        if x < MATRIX_WIDTH && y < MATRIX_HEIGHT {
-            self.pixels[y][x]
+    /// Dither colors into the `pixel8` buffer's 256 levels per channel
+    /// (see [`dither_channel`]) instead of rounding them, so slow
+    /// gradients don't band. Only affects later drawing. Without
+    /// `pixel8` nothing is rounded and this changes nothing.
+    pub fn set_dithering(&mut self, on: bool) {
+        self.dithering = on;
+    }
+
+    /// Whether [`Self::set_dithering`] is on.
+    pub fn dithering(&self) -> bool {
+        self.dithering
+    }
+
+    /// `px` as stored at physical `(x, y)`.
+    #[inline]
+    fn store(&self, px: Pixel, x: usize, y: usize) -> StoredPixel {
+        if self.dithering {
+            store_pixel_dithered(px, x, y)
         } else {
-            [0, 0, 0]
+            store_pixel(px)
+        }
+    }
+
+    /// Switch how logical coordinates map onto the panel. Only affects
+    /// later drawing; what is already in the buffer stays put. Quarter
+    /// turns are ignored unless the display is square.
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        if SQUARE || !matches!(orientation, Orientation::Rot90 | Orientation::Rot270) {
+            self.orientation = orientation;
         }
     }
 
-    /// Render `text` to the buffer, clearing it first.
+    /// The current mounting orientation.
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    /// Set a single pixel's RGB color. Out-of-bounds writes, negative
+    /// coordinates included, are silently dropped to mirror the behaviour
+    /// of the original driver.
     ///
-    /// Glyphs are drawn left-to-right starting at `x = 4` and centred
-    /// vertically. Characters that don't fit are dropped.
-    pub fn display_text(&mut self, text: &str) {
-        self.clear();
-        if text.is_empty() {
+    /// `(x, y)` are logical coordinates: they are clipped first, then
+    /// mapped through the current [`Orientation`]. Every drawing call
+    /// takes signed coordinates like this one, so shapes can hang off any
+    /// edge of the panel.
+    ///
+    /// The channels are stored as given, so with `pixel8` they are `u8`s.
+    /// [`Self::set_pixel_16`] takes 16-bit ones in every build.
+    pub fn set_pixel(&mut self, x: i32, y: i32, r: Channel, g: Channel, b: Channel) {
+        let [r, g, b] = load_pixel([r, g, b]);
+        self.plot(x.into(), y.into(), r, g, b);
+    }
+
+    /// [`Self::set_pixel`] with 16-bit channels, like the rest of the
+    /// drawing calls. With `pixel8` they are rounded when stored.
+    pub fn set_pixel_16(&mut self, x: i32, y: i32, r: u16, g: u16, b: u16) {
+        self.plot(x.into(), y.into(), r, g, b);
+    }
+
+    /// Store `px` at logical `(x, y)`, which must be on the panel.
+    #[inline]
+    fn put(&mut self, x: usize, y: usize, px: Pixel) {
+        let (px_x, px_y) = self.orientation.map(x, y);
+        self.pixels[px_y][px_x] = self.store(px, px_x, px_y);
+        self.mark_dirty(px_x, px_y, 1, 1);
+    }
+
+    /// Set a batch of `(x, y, r, g, b)` pixels, as [`Self::set_pixel_16`]
+    /// does one at a time. Out-of-bounds entries are dropped.
+    pub fn set_pixels(&mut self, pixels: &[(i32, i32, u16, u16, u16)]) {
+        for &(x, y, r, g, b) in pixels {
+            self.set_pixel_16(x, y, r, g, b);
+        }
+    }
+
+    /// Replace every pixel with `frame`, indexed `frame[y][x]` in logical
+    /// coordinates. In the default orientation that is a single copy.
+    pub fn set_frame(&mut self, frame: &[[Pixel; MATRIX_WIDTH]; MATRIX_HEIGHT]) {
+        self.dirty = Some(DirtyRect::FULL);
+        #[cfg(not(feature = "pixel8"))]
+        if self.orientation == Orientation::Normal {
+            self.pixels = *frame;
             return;
         }
+        for (y, row) in frame.iter().enumerate() {
+            for (x, &px) in row.iter().enumerate() {
+                let (px_x, px_y) = self.orientation.map(x, y);
+                self.pixels[px_y][px_x] = self.store(px, px_x, px_y);
+            }
+        }
+    }
 
-        let start_y = (MATRIX_HEIGHT - self.font.height()) / 2;
-        let mut x = 4;
-        let max_x = MATRIX_WIDTH.saturating_sub(self.font.width());
+    /// Set a single pixel from a packed RGB565 color. See
+    /// [`rgb565_to_pixel`] for how each field is expanded.
+    pub fn set_pixel_565(&mut self, x: i32, y: i32, color: u16) {
+        let [r, g, b] = rgb565_to_pixel(color);
+        self.set_pixel_16(x, y, r, g, b);
+    }
 
-        for ch in text.chars() {
-            if x >= max_x {
-                break;
+    /// Set a single pixel from an HSV color. See [`hsv_to_pixel`] for the
+    /// ranges.
+    pub fn set_pixel_hsv(&mut self, x: i32, y: i32, h: f32, s: f32, v: f32) {
+        let [r, g, b] = hsv_to_pixel(h, s, v);
+        self.set_pixel_16(x, y, r, g, b);
+    }
+
+    /// Paint a full-brightness rainbow running diagonally from the
+    /// top-left corner to the bottom-right one, one full hue cycle across
+    /// the panel. `phase` (degrees) shifts every hue, so increasing it
+    /// over time animates the gradient. Cancels any active scroll.
+    pub fn rainbow_fill(&mut self, phase: f32) {
+        self.scroll = None;
+        // Every pixel on a diagonal shares a hue, so convert each only once.
+        const DIAGONALS: usize = MATRIX_WIDTH + MATRIX_HEIGHT - 1;
+        let mut colors = [[0u16; 3]; DIAGONALS];
+        for (d, color) in colors.iter_mut().enumerate() {
+            *color = hsv_to_pixel(phase + d as f32 * 360.0 / DIAGONALS as f32, 1.0, 1.0);
+        }
+        for y in 0..MATRIX_HEIGHT {
+            for x in 0..MATRIX_WIDTH {
+                self.put(x, y, colors[x + y]);
+            }
+        }
+    }
+
+    /// Paint a bring-up [`TestPattern`]. `step` is how far the
+    /// [`TestPattern::Sweep`] pixel has moved, in pixels from the
+    /// top-left corner, and wraps; the other patterns ignore it. Drawn
+    /// in panel coordinates, whatever the [`Orientation`], so what shows
+    /// up where says something about the wiring. Cancels any active
+    /// scroll.
+    pub fn test_pattern(&mut self, pattern: TestPattern, step: usize) {
+        const WHITE: StoredPixel = store_pixel([0xFFFF; 3]);
+        self.scroll = None;
+        self.dirty = Some(DirtyRect::FULL);
+        match pattern {
+            TestPattern::Red => self.fill(0xFFFF, 0, 0),
+            TestPattern::Green => self.fill(0, 0xFFFF, 0),
+            TestPattern::Blue => self.fill(0, 0, 0xFFFF),
+            TestPattern::Border => {
+                self.clear();
+                for y in 0..MATRIX_HEIGHT {
+                    self.pixels[y][0] = WHITE;
+                    self.pixels[y][MATRIX_WIDTH - 1] = WHITE;
+                }
+                self.pixels[0] = [WHITE; MATRIX_WIDTH];
+                self.pixels[MATRIX_HEIGHT - 1] = [WHITE; MATRIX_WIDTH];
+            }
+            TestPattern::Sweep => {
+                self.clear();
+                let step = step % (MATRIX_WIDTH * MATRIX_HEIGHT);
+                self.pixels[step / MATRIX_WIDTH][step % MATRIX_WIDTH] = WHITE;
+            }
+            TestPattern::Bars => {
+                // Green, red and blue are bits 2, 1 and 0 of the bar
+                // number counted down from 7: the usual color bar order.
+                for row in self.pixels.iter_mut() {
+                    for (x, px) in row.iter_mut().enumerate() {
+                        let bits = 7 - x * 8 / MATRIX_WIDTH;
+                        let bars = [bits & 0b010, bits & 0b100, bits & 0b001];
+                        *px = store_pixel(bars.map(|on| if on != 0 { 0xFFFF } else { 0 }));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Mix a color into the pixel at `(x, y)` with opacity `alpha` (0 =
+    /// keep what is there, 255 = overwrite). See [`blend`]. Out-of-bounds
+    /// pixels are ignored.
+    pub fn blend_pixel(&mut self, x: i32, y: i32, r: u16, g: u16, b: u16, alpha: u8) {
+        if let Some((x, y)) = on_panel(x.into(), y.into()) {
+            self.put(x, y, blend(self.get_pixel(x, y), [r, g, b], alpha));
+        }
+    }
+
+    /// Blend a `width` x `height` rectangle with its top-left corner at
+    /// `(x, y)`, e.g. a dimmed status bar over the current picture. Parts
+    /// past any edge are clipped.
+    #[allow(clippy::too_many_arguments)]
+    pub fn blend_rect(
+        &mut self,
+        x: i32,
+        y: i32,
+        width: usize,
+        height: usize,
+        r: u16,
+        g: u16,
+        b: u16,
+        alpha: u8,
+    ) {
+        for py in clip(y, height, MATRIX_HEIGHT) {
+            for px in clip(x, width, MATRIX_WIDTH) {
+                self.put(px, py, blend(self.get_pixel(px, py), [r, g, b], alpha));
+            }
+        }
+    }
+
+    /// Fill a `width` x `height` rectangle with its top-left corner at
+    /// `(x, y)`. Parts past any edge are clipped, so `(-5, -5)` with a
+    /// size of 10 fills the 5x5 block in the top-left corner.
+    #[allow(clippy::too_many_arguments)]
+    pub fn fill_rect(
+        &mut self,
+        x: i32,
+        y: i32,
+        width: usize,
+        height: usize,
+        r: u16,
+        g: u16,
+        b: u16,
+    ) {
+        for py in clip(y, height, MATRIX_HEIGHT) {
+            for px in clip(x, width, MATRIX_WIDTH) {
+                self.put(px, py, [r, g, b]);
             }
-            self.draw_char(ch, x, start_y, 0xFFFF, 0xFFFF, 0xFFFF);
-            x += self.font.width() + 1;
         }
     }
 
-    /// Draw one character glyph at `(x, y)` using the supplied color.
-    pub fn draw_char(&mut self, ch: char, x: usize, y: usize, r: u16, g: u16, b: u16) {
-        let Some(glyph) = self.font.get_glyph(ch) else {
+    /// Draw the one-pixel outline of a `width` x `height` rectangle with
+    /// its top-left corner at `(x, y)`, clipped like [`Self::fill_rect`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_rect(
+        &mut self,
+        x: i32,
+        y: i32,
+        width: usize,
+        height: usize,
+        r: u16,
+        g: u16,
+        b: u16,
+    ) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let right = offset(x, width - 1);
+        let bottom = offset(y, height - 1);
+        self.fill_rect(x, y, width, 1, r, g, b);
+        self.fill_rect(x, bottom, width, 1, r, g, b);
+        self.fill_rect(x, y, 1, height, r, g, b);
+        self.fill_rect(right, y, 1, height, r, g, b);
+    }
+
+    /// Read a pixel's RGB color at logical `(x, y)`. Returns black for
+    /// out-of-bounds reads; see [`Self::pixel`] to tell them apart.
+    pub fn get_pixel(&self, x: usize, y: usize) -> Pixel {
+        self.pixel(x, y).unwrap_or([0, 0, 0])
+    }
+
+    /// The pixel at logical `(x, y)` as stored, or `None` out of bounds.
+    /// Effects that build on the previous frame read it back with this.
+    pub fn pixel(&self, x: usize, y: usize) -> Option<Pixel> {
+        if x < MATRIX_WIDTH && y < MATRIX_HEIGHT {
+            let (px, py) = self.orientation.map(x, y);
+            Some(load_pixel(self.pixels[py][px]))
+        } else {
+            None
+        }
+    }
+
+    /// Draw a straight line from `(x0, y0)` to `(x1, y1)` inclusive using
+    /// Bresenham's algorithm.
+    ///
+    /// Works for any slope. The endpoints are normalised so swapping them
+    /// lights exactly the same pixels. Endpoints may lie outside the panel
+    /// — the line is clipped to the panel first, so only its on-panel
+    /// steps are walked, lighting the pixels the whole line would.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, r: u16, g: u16, b: u16) {
+        // Always walk from the lexicographically smaller endpoint so the
+        // error term rounds the same way regardless of argument order.
+        let ((x0, y0), (x1, y1)) = if (x0, y0) <= (x1, y1) {
+            ((x0, y0), (x1, y1))
+        } else {
+            ((x1, y1), (x0, y0))
+        };
+        let (x0, y0) = (i64::from(x0), i64::from(y0));
+        let (x1, y1) = (i64::from(x1), i64::from(y1));
+        let Some((first, last)) = clip_line(x0, y0, x1, y1) else {
             return;
         };
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let sx = if x0 < x1 { 1 } else { -1 };
+        let sy = if y0 < y1 { 1 } else { -1 };
+        // Start at step `first`, with the error term the walk from
+        // `(x0, y0)` would have there: `dx + dy` plus `dy` per x step and
+        // `dx` per y step taken.
+        let major = dx.max(-dy);
+        let (nx, ny) = (
+            step_offset(first, dx, major),
+            step_offset(first, -dy, major),
+        );
+        let (mut x, mut y) = (x0 + sx * nx, y0 + sy * ny);
+        let err =
+            i128::from(dx + dy) + i128::from(nx) * i128::from(dy) + i128::from(ny) * i128::from(dx);
+        let mut err = err as i64;
 
-        for (gy, row) in glyph.iter().enumerate() {
-            for (gx, &pixel) in row.iter().enumerate() {
-                if pixel != 0 {
-                    self.set_pixel(x + gx, y + gy, r, g, b);
-                }
+        for _ in first..=last {
+            self.plot(x, y, r, g, b);
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
             }
         }
     }
 
-    /// Borrow the raw 88x88 RGB array.
+    /// Draw the outline of a circle centred on `(cx, cy)` using the
+    /// midpoint circle algorithm.
     ///
-    /// Required by [`crate::chain_mapper::compute_chain_data`] when running
-    /// in `no_std` mode — there is no `AsRef` blanket that would let it
-    /// dereference through to the inner field.
-    pub fn as_pixels(&self) -> &[[Pixel; MATRIX_WIDTH]; MATRIX_HEIGHT] {
-        &self.pixels
+    /// A `radius` of 0 lights only the centre pixel. Parts of the circle
+    /// that fall off the panel are clipped.
+    pub fn draw_circle(&mut self, cx: i32, cy: i32, radius: usize, r: u16, g: u16, b: u16) {
+        let (cx, cy) = (i64::from(cx), i64::from(cy));
+        for_each_octant_point(radius, |x, y| {
+            self.plot(cx + x, cy + y, r, g, b);
+            self.plot(cx - x, cy + y, r, g, b);
+            self.plot(cx + x, cy - y, r, g, b);
+            self.plot(cx - x, cy - y, r, g, b);
+            self.plot(cx + y, cy + x, r, g, b);
+            self.plot(cx - y, cy + x, r, g, b);
+            self.plot(cx + y, cy - x, r, g, b);
+            self.plot(cx - y, cy - x, r, g, b);
+        });
     }
 
-    /// Mutable access to the raw pixel array.
-    pub fn as_pixels_mut(&mut self) -> &mut [[Pixel; MATRIX_WIDTH]; MATRIX_HEIGHT] {
-        &mut self.pixels
+    /// Draw a filled circle centred on `(cx, cy)`.
+    ///
+    /// Uses the same midpoint walk as [`Self::draw_circle`] and fills the
+    /// horizontal span between each pair of mirrored outline points, so
+    /// the filled disc covers exactly the outline plus its interior.
+    pub fn fill_circle(&mut self, cx: i32, cy: i32, radius: usize, r: u16, g: u16, b: u16) {
+        let (cx, cy) = (i64::from(cx), i64::from(cy));
+        for_each_octant_point(radius, |x, y| {
+            self.hline(cx - x, cx + x, cy + y, r, g, b);
+            self.hline(cx - x, cx + x, cy - y, r, g, b);
+            self.hline(cx - y, cx + y, cy + x, r, g, b);
+            self.hline(cx - y, cx + y, cy - x, r, g, b);
+        });
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Draw the outline of the triangle with corners `(x0, y0)`,
+    /// `(x1, y1)` and `(x2, y2)`: three [`Self::draw_line`]s. Collinear
+    /// corners give the line that spans them. Parts off the panel are
+    /// clipped.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_triangle(
+        &mut self,
+        x0: i32,
+        y0: i32,
+        x1: i32,
+        y1: i32,
+        x2: i32,
+        y2: i32,
+        r: u16,
+        g: u16,
+        b: u16,
+    ) {
+        self.draw_line(x0, y0, x1, y1, r, g, b);
+        self.draw_line(x1, y1, x2, y2, r, g, b);
+        self.draw_line(x2, y2, x0, y0, r, g, b);
+    }
 
-    #[test]
-    fn new_is_blank() {
-        let fb = FrameBuffer::new();
-        for row in fb.as_pixels().iter() {
-            for px in row.iter() {
-                assert_eq!(*px, [0, 0, 0]);
-            }
+    /// Join consecutive `points` with [`Self::draw_line`]s, e.g. to plot
+    /// a series of readings. A single point lights one pixel; no points
+    /// draw nothing. Parts off the panel are clipped.
+    pub fn draw_polyline(&mut self, points: &[(i32, i32)], r: u16, g: u16, b: u16) {
+        if let [(x, y)] = points {
+            self.set_pixel_16(*x, *y, r, g, b);
+        }
+        for pair in points.windows(2) {
+            let [(x0, y0), (x1, y1)] = [pair[0], pair[1]];
+            self.draw_line(x0, y0, x1, y1, r, g, b);
         }
     }
 
-    #[test]
-    fn default_matches_new() {
-        let fb = FrameBuffer::default();
-        assert_eq!(fb.get_pixel(0, 0), [0, 0, 0]);
+    /// Draw the closed outline through `points`: [`Self::draw_polyline`]
+    /// plus a line from the last point back to the first.
+    pub fn draw_polygon(&mut self, points: &[(i32, i32)], r: u16, g: u16, b: u16) {
+        self.draw_polyline(points, r, g, b);
+        if let [(x0, y0), .., (x1, y1)] = points {
+            self.draw_line(*x1, *y1, *x0, *y0, r, g, b);
+        }
     }
 
-    #[test]
-    fn set_and_get_pixel() {
-        let mut fb = FrameBuffer::new();
-        fb.set_pixel(10, 20, 100, 200, 300);
-        assert_eq!(fb.get_pixel(10, 20), [100, 200, 300]);
+    /// Draw a filled triangle with corners `(x0, y0)`, `(x1, y1)` and
+    /// `(x2, y2)`.
+    ///
+    /// Walks the edges one row at a time, from the top corner down, and
+    /// fills the span between the long edge (top to bottom corner) and
+    /// whichever short edge covers the row. The outline from
+    /// [`Self::draw_triangle`] is drawn too, so the filled triangle covers
+    /// exactly it plus its interior; collinear corners give a line.
+    #[allow(clippy::too_many_arguments)]
+    pub fn fill_triangle(
+        &mut self,
+        x0: i32,
+        y0: i32,
+        x1: i32,
+        y1: i32,
+        x2: i32,
+        y2: i32,
+        r: u16,
+        g: u16,
+        b: u16,
+    ) {
+        self.draw_triangle(x0, y0, x1, y1, x2, y2, r, g, b);
+        let mut corners = [(x0, y0), (x1, y1), (x2, y2)].map(|(x, y)| (i64::from(x), i64::from(y)));
+        corners.sort_unstable_by_key(|&(_, y)| y);
+        let [top, middle, bottom] = corners;
+        // Rows off the panel would be clipped anyway.
+        let first = top.1.max(0);
+        let last = bottom.1.min(MATRIX_HEIGHT as i64 - 1);
+        for y in first..=last {
+            let long = edge_x(top, bottom, y);
+            let short = if y < middle.1 {
+                edge_x(top, middle, y)
+            } else {
+                edge_x(middle, bottom, y)
+            };
+            self.hline(long.min(short), long.max(short), y, r, g, b);
+        }
     }
 
-    #[test]
-    fn out_of_bounds_set_is_noop() {
-        let mut fb = FrameBuffer::new();
-        fb.set_pixel(MATRIX_WIDTH, 0, 1, 2, 3);
-        fb.set_pixel(0, MATRIX_HEIGHT, 1, 2, 3);
-        // buffer still zeroed
-        assert_eq!(fb.get_pixel(0, 0), [0, 0, 0]);
+    /// Render `text` to the buffer, over the scene (see
+    /// [`Self::push_command`]; black without one).
+    ///
+    /// Glyphs are drawn left-to-right starting at `x = 4` and centred
+    /// vertically in the current text color (see [`Self::set_text_color`]).
+    /// Characters that don't fit are dropped. Cancels any active scroll.
+    pub fn display_text(&mut self, text: &str) {
+        self.display_text_lines(&[text]);
     }
 
-    #[test]
-    fn out_of_bounds_get_returns_black() {
-        let fb = FrameBuffer::new();
-        assert_eq!(fb.get_pixel(MATRIX_WIDTH, 0), [0, 0, 0]);
-        assert_eq!(fb.get_pixel(0, MATRIX_HEIGHT), [0, 0, 0]);
+    /// Render several lines stacked vertically, over the scene like
+    /// [`Self::display_text`].
+    ///
+    /// The rows are spread evenly: the gaps above the first line, between
+    /// lines and below the last are equal (give or take a pixel), so one
+    /// line is centred exactly like [`Self::display_text`]. With a
+    /// [`Self::set_line_spacing`] the lines are that far apart instead,
+    /// centred as a block. Each line starts at `x = 4` and is truncated
+    /// the same way. At most [`MAX_TEXT_LINES`] lines are shown, fewer
+    /// when a scaled font leaves no room for a blank row (or the line
+    /// spacing) between them. Cancels any active scroll.
+    pub fn display_text_lines(&mut self, lines: &[&str]) {
+        self.scroll = None;
+        self.clear_to_scene();
+        self.draw_text_band(lines, self.text_color, 0, MATRIX_HEIGHT);
     }
 
-    #[test]
-    fn clear_resets_all_pixels() {
-        let mut fb = FrameBuffer::new();
-        fb.set_pixel(1, 1, 50, 60, 70);
-        fb.set_pixel(87, 87, 80, 90, 100);
-        fb.clear();
-        assert_eq!(fb.get_pixel(1, 1), [0, 0, 0]);
-        assert_eq!(fb.get_pixel(87, 87), [0, 0, 0]);
+    /// Lay out `lines` in `color` as [`Self::display_text_lines`] does,
+    /// but within the `height` rows starting at `top`, over whatever is
+    /// there. Lines that don't fit the band are dropped, so nothing is
+    /// drawn outside it. The scroll is left alone.
+    pub fn draw_text_band(&mut self, lines: &[&str], color: Pixel, top: usize, height: usize) {
+        let line_height = self.font.height() * self.text_scale;
+        let spacing = self.line_spacing.map(|px| px * self.text_scale);
+        let max_lines = match spacing {
+            Some(gap) => (height + gap) / (line_height + gap),
+            None => height / (line_height + 1),
+        };
+        // One line always shows if it fits, blank row or not.
+        let max_lines = max_lines.max(usize::from(line_height <= height));
+        let lines = &lines[..lines.len().min(max_lines).min(MAX_TEXT_LINES)];
+        if lines.is_empty() {
+            return;
+        }
+        let (first, gap) = match spacing {
+            Some(gap) => {
+                let block = lines.len() * (line_height + gap) - gap;
+                ((height - block) / 2, gap)
+            }
+            None => {
+                let gap = (height - lines.len() * line_height) / (lines.len() + 1);
+                (gap, gap)
+            }
+        };
+        for (i, line) in lines.iter().enumerate() {
+            self.draw_spans(&[(line, color)], top + first + i * (line_height + gap));
+        }
     }
 
-    #[test]
-    fn empty_text_clears_buffer() {
-        let mut fb = FrameBuffer::new();
-        fb.set_pixel(5, 5, 1, 2, 3);
-        fb.display_text("");
-        assert_eq!(fb.get_pixel(5, 5), [0, 0, 0]);
+    /// Render `text` as a paragraph, word-wrapped over as many lines as
+    /// fit, over the scene like [`Self::display_text`] and in the current
+    /// text color. Cancels any active scroll.
+    pub fn display_text_wrapped(&mut self, text: &str) {
+        self.scroll = None;
+        self.clear_to_scene();
+        let [r, g, b] = self.text_color;
+        self.draw_text_wrapped(text, r, g, b);
     }
 
-    #[test]
-    fn draw_char_writes_only_set_pixels() {
-        let mut fb = FrameBuffer::new();
-        fb.draw_char('!', 0, 0, 0xFFFF, 0xFFFF, 0xFFFF);
-        // The '!' glyph has at least one set pixel
-        let mut any_set = false;
-        for row in fb.as_pixels().iter() {
-            for px in row.iter() {
-                if *px != [0, 0, 0] {
-                    any_set = true;
+    /// Word-wrap `text` to the panel width and lay the lines out as
+    /// [`Self::display_text_lines`] does, over whatever is there. Lines
+    /// break at spaces, and words too long for a line of their own at the
+    /// last character that fits; lines past the bottom are dropped. The
+    /// scroll is left alone.
+    pub fn draw_text_wrapped(&mut self, text: &str, r: u16, g: u16, b: u16) {
+        let lines = self.wrap_text(text);
+        self.draw_text_band(&lines, [r, g, b], 0, MATRIX_HEIGHT);
+    }
+
+    /// Split `text` into lines no wider than [`Self::draw_spans`] draws
+    /// whole, breaking at spaces where it can and always at a `'\n'`. At
+    /// most [`MAX_TEXT_LINES`] lines; the rest of the text is dropped.
+    fn wrap_text<'a>(&self, text: &'a str) -> heapless::Vec<&'a str, MAX_TEXT_LINES> {
+        let advance = (self.font.width() + self.char_spacing) * self.text_scale;
+        let room = MATRIX_WIDTH - TEXT_START_X + self.char_spacing * self.text_scale;
+        let max_chars = (room.saturating_sub(1) / advance).max(1);
+
+        let mut lines = heapless::Vec::new();
+        for paragraph in text.split('\n') {
+            let mut rest = paragraph.trim_start_matches(' ');
+            // An empty paragraph still takes a line.
+            loop {
+                let end = match rest.char_indices().nth(max_chars) {
+                    None => rest.len(),
+                    Some((cut, ' ')) => cut,
+                    Some((cut, _)) => rest[..cut].rfind(' ').filter(|&i| i > 0).unwrap_or(cut),
+                };
+                if lines.push(rest[..end].trim_end_matches(' ')).is_err() {
+                    return lines;
+                }
+                rest = rest[end..].trim_start_matches(' ');
+                if rest.is_empty() {
                     break;
                 }
             }
         }
-        assert!(any_set, "expected some pixels lit after drawing '!'");
+        lines
+    }
+
+    /// Render `segments` one after another on a single line, each
+    /// `(text, color)` in its own color: [`Self::display_text`] of their
+    /// concatenation, otherwise. Characters past the right edge are
+    /// dropped. Cancels any active scroll.
+    pub fn display_colored_segments(&mut self, segments: &[(&str, Pixel)]) {
+        self.scroll = None;
+        self.clear_to_scene();
+
+        let height = self.font.height() * self.text_scale;
+        self.draw_spans(segments, (MATRIX_HEIGHT - height) / 2);
+    }
+
+    /// Draw `spans` left-to-right, each `(text, color)` in its own color,
+    /// on the row band starting at `y`, at the current scale and
+    /// alignment. Characters that don't fit are dropped.
+    fn draw_spans(&mut self, spans: &[(&str, Pixel)], y: usize) {
+        let chars = spans.iter().map(|(text, _)| text.chars().count()).sum();
+        let mut x = self.line_start_x(chars);
+        let glyph_width = self.font.width() * self.text_scale;
+        let max_x = MATRIX_WIDTH.saturating_sub(glyph_width);
+
+        for &(text, [r, g, b]) in spans {
+            for ch in text.chars() {
+                if x >= max_x {
+                    return;
+                }
+                self.draw_char(ch, x as i32, y as i32, r, g, b);
+                // The gap between glyphs scales with them.
+                x += glyph_width + self.char_spacing * self.text_scale;
+            }
+        }
+    }
+
+    /// Where a line of `chars` characters starts for the current
+    /// alignment. Text too wide to fit is left-aligned, so it truncates
+    /// the same way in every mode.
+    fn line_start_x(&self, chars: usize) -> usize {
+        let gaps = chars.saturating_sub(1) * self.char_spacing;
+        let width = (chars * self.font.width() + gaps) * self.text_scale;
+        if TEXT_START_X + width > MATRIX_WIDTH {
+            return TEXT_START_X;
+        }
+        match self.text_align {
+            Align::Left => TEXT_START_X,
+            Align::Center => (MATRIX_WIDTH - width) / 2,
+            Align::Right => MATRIX_WIDTH - TEXT_START_X - width,
+        }
+    }
+
+    /// True when [`Self::display_text`] can show all of `text` without
+    /// truncating it, at the current text scale.
+    pub fn text_fits(&self, text: &str) -> bool {
+        let width = self.font.text_width(text, self.char_spacing);
+        TEXT_START_X + width * self.text_scale <= MATRIX_WIDTH
+    }
+
+    /// Start scrolling `text` right-to-left across the panel, moving
+    /// `speed_px` pixels per [`Self::advance_scroll`]. Unlike
+    /// [`Self::display_text`] the whole message is shown, not just the
+    /// first ~14 characters. The text enters from the right edge.
+    pub fn set_scroll_text(&mut self, text: &str, speed_px: usize) {
+        let scroll = ScrollText::new_spaced(&self.font, text, speed_px, self.char_spacing)
+            .with_scale(self.text_scale);
+        self.scroll = Some(Scroll::Horizontal(scroll));
+        self.render_scroll();
+    }
+
+    /// Start scrolling `lines` upwards like a news ticker, moving
+    /// `speed_px` pixels per [`Self::advance_scroll`]. The lines are
+    /// stacked with a line-high gap between them (or the
+    /// [`Self::set_line_spacing`]), each placed and
+    /// truncated like [`Self::display_text_lines`] does, enter at the
+    /// bottom edge and start over once the last has left at the top.
+    /// Lines beyond [`MAX_VSCROLL_ROWS`](crate::scroll::MAX_VSCROLL_ROWS)
+    /// are dropped; no lines at all blank the display.
+    pub fn set_vscroll_lines(&mut self, lines: &[&str], speed_px: usize) {
+        let mut scroll =
+            VerticalScroll::new(speed_px).with_spacing(self.char_spacing, self.line_spacing);
+        for line in lines {
+            let x = self.line_start_x(line.chars().count());
+            if !scroll.push_line(&self.font, line, x, self.text_scale) {
+                break;
+            }
+        }
+        self.scroll = Some(Scroll::Vertical(scroll));
+        self.render_scroll();
+    }
+
+    /// Stop the active scroll (if any), leaving the picture as it is.
+    pub fn stop_scroll(&mut self) {
+        self.scroll = None;
+    }
+
+    /// True while a scrolling message is active.
+    pub fn is_scrolling(&self) -> bool {
+        self.scroll.is_some()
+    }
+
+    /// Step the active scroll (if any) by its speed and redraw it. Called
+    /// once per refresh by the driver. Returns true if the text moved.
+    pub fn advance_scroll(&mut self) -> bool {
+        let speed_px = self.scroll.as_ref().map_or(0, Scroll::speed_px);
+        self.scroll_by(speed_px)
+    }
+
+    /// Move the active scroll (if any) on by `px` and redraw it, for
+    /// scrolls timed by the caller (started with a speed of 0 so
+    /// [`Self::advance_scroll`] leaves them alone). Returns true if the
+    /// text moved.
+    pub fn scroll_by(&mut self, px: usize) -> bool {
+        match self.scroll.as_mut() {
+            Some(scroll) if px > 0 => {
+                scroll.advance_by(px);
+                self.render_scroll();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Redraw the buffer from the current scroll window.
+    fn render_scroll(&mut self) {
+        if self.scroll.is_none() {
+            return;
+        }
+        self.clear_to_scene();
+        let (text_color, gradient) = (self.text_color, self.scroll_gradient);
+        let color_at = |position| gradient.map_or(text_color, |g| g.color_at(position));
+        let scroll = match self.scroll.as_ref() {
+            Some(Scroll::Horizontal(scroll)) => scroll,
+            Some(Scroll::Vertical(scroll)) => {
+                let mut rows = [RowBits::EMPTY; MATRIX_HEIGHT];
+                for (y, bits) in rows.iter_mut().enumerate() {
+                    *bits = scroll.row_at(y);
+                }
+                for (y, bits) in rows.into_iter().enumerate() {
+                    for x in 0..MATRIX_WIDTH {
+                        if bits.is_lit(x) {
+                            self.put(x, y, color_at(x));
+                        }
+                    }
+                }
+                return;
+            }
+            None => return,
+        };
+        let scale = self.text_scale;
+        let start_y = (MATRIX_HEIGHT - self.font.height() * scale) / 2;
+        let mut columns = [0u128; MATRIX_WIDTH];
+        for (x, bits) in columns.iter_mut().enumerate() {
+            *bits = scroll.column_at(x);
+        }
+        let offset = scroll.offset();
+        for (x, bits) in columns.into_iter().enumerate() {
+            let color = color_at(x + offset);
+            for gy in 0..self.font.height() {
+                if bits & (1 << gy) != 0 {
+                    for dy in 0..scale {
+                        self.put(x, start_y + gy * scale + dy, color);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Set the color used by [`Self::display_text`]. Defaults to white.
+    pub fn set_text_color(&mut self, r: u16, g: u16, b: u16) {
+        self.text_color = [r, g, b];
+    }
+
+    /// The color currently used by [`Self::display_text`].
+    pub fn text_color(&self) -> Pixel {
+        self.text_color
+    }
+
+    /// Color scrolling text column by column with `gradient`, or in the
+    /// text color again with `None`. Text that fits and stands still
+    /// keeps the text color. Takes effect at the next scroll step.
+    pub fn set_scroll_gradient(&mut self, gradient: Option<GradientMode>) {
+        self.scroll_gradient = gradient;
+    }
+
+    /// The gradient set by [`Self::set_scroll_gradient`].
+    pub fn scroll_gradient(&self) -> Option<GradientMode> {
+        self.scroll_gradient
+    }
+
+    /// Copy a `width` x `height` block of row-major `pixels` with its
+    /// top-left corner at `(x, y)`. Parts past any edge are clipped; if
+    /// `pixels` is shorter than `width * height` only the rows it covers
+    /// are drawn.
+    pub fn draw_bitmap(&mut self, x: i32, y: i32, width: usize, height: usize, pixels: &[Pixel]) {
+        if width == 0 {
+            return;
+        }
+        for (dy, row) in pixels.chunks(width).take(height).enumerate() {
+            for (dx, &[r, g, b]) in row.iter().enumerate() {
+                self.plot(i64::from(x) + dx as i64, i64::from(y) + dy as i64, r, g, b);
+            }
+        }
+    }
+
+    /// Stamp `sprite` with its top-left corner at `(x, y)`, over what is
+    /// already drawn: its transparent pixels leave the buffer as it was.
+    /// Parts off the panel are clipped.
+    pub fn draw_sprite(&mut self, sprite: &Sprite, x: i32, y: i32) {
+        for (dx, dy, [r, g, b]) in sprite.opaque_pixels() {
+            self.plot(i64::from(x) + dx as i64, i64::from(y) + dy as i64, r, g, b);
+        }
+    }
+
+    /// Set the text scale: each glyph pixel becomes a `scale` x `scale`
+    /// block, so at 2 the 5x7 font renders 10x14. Clamped so a glyph
+    /// still fits the panel height ([`MAX_TEXT_SCALE`] for the built-in
+    /// font). Applies to later text, including scrolls.
+    pub fn set_text_scale(&mut self, scale: usize) {
+        self.text_scale = scale.clamp(1, MATRIX_HEIGHT / self.font.height());
+    }
+
+    /// Use `font` for later text; what is already drawn stays. The text
+    /// scale shrinks if its glyphs would no longer fit the panel.
+    pub fn set_font(&mut self, font: Font) {
+        self.font = font;
+        self.set_text_scale(self.text_scale);
+    }
+
+    /// The font text is drawn with.
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
+    /// Set how each line of text is placed horizontally. Defaults to
+    /// [`Align::Left`]. Scrolling text is not affected.
+    pub fn set_text_align(&mut self, align: Align) {
+        self.text_align = align;
+    }
+
+    /// The current text alignment.
+    pub fn text_align(&self) -> Align {
+        self.text_align
+    }
+
+    /// The current text scale.
+    pub fn text_scale(&self) -> usize {
+        self.text_scale
+    }
+
+    /// Set the gap between adjacent characters, in font pixels: scaled
+    /// with the text, like the glyphs. Defaults to
+    /// [`DEFAULT_CHAR_SPACING`]; 0 draws the glyphs side by side. Applies
+    /// to later text, including scrolls.
+    pub fn set_char_spacing(&mut self, px: usize) {
+        self.char_spacing = px;
+    }
+
+    /// The current character spacing.
+    pub fn char_spacing(&self) -> usize {
+        self.char_spacing
+    }
+
+    /// Set the gap between stacked lines, in font pixels like
+    /// [`Self::set_char_spacing`], or `None` for the default:
+    /// [`Self::display_text_lines`] spreads its lines evenly and
+    /// [`Self::set_vscroll_lines`] leaves a line's height between them.
+    /// Applies to later text.
+    pub fn set_line_spacing(&mut self, px: Option<usize>) {
+        self.line_spacing = px;
+    }
+
+    /// The current line spacing.
+    pub fn line_spacing(&self) -> Option<usize> {
+        self.line_spacing
+    }
+
+    /// Draw one character glyph at `(x, y)` using the supplied color, at
+    /// the current text scale. See [`Self::draw_char_at`].
+    pub fn draw_char(&mut self, ch: char, x: i32, y: i32, r: u16, g: u16, b: u16) {
+        self.draw_char_at(ch, x, y, r, g, b);
+    }
+
+    /// Draw one character with its top-left corner at `(x, y)`, over
+    /// whatever is there, clipped at the panel edge. Returns how far to
+    /// move right for the next character (glyph width plus the character
+    /// spacing, both at the current text scale), so callers can lay out
+    /// their own strings.
+    pub fn draw_char_at(&mut self, ch: char, x: i32, y: i32, r: u16, g: u16, b: u16) -> usize {
+        let (width, height) = (self.font.width(), self.font.height());
+        let scale = self.text_scale;
+        let advance = (width + self.char_spacing) * scale;
+        let (x, y) = (i64::from(x), i64::from(y));
+        for gy in 0..height {
+            // One row at a time, so the font isn't borrowed while drawing.
+            let Some(glyph) = self.font.glyph(ch) else {
+                return advance;
+            };
+            let mut row = RowBits::EMPTY;
+            (0..width)
+                .filter(|&gx| glyph.is_lit(gx, gy))
+                .for_each(|gx| row.set(gx));
+            for gx in 0..width {
+                if row.is_lit(gx) {
+                    for dy in 0..scale {
+                        for dx in 0..scale {
+                            let (dx, dy) = ((gx * scale + dx) as i64, (gy * scale + dy) as i64);
+                            self.plot(x + dx, y + dy, r, g, b);
+                        }
+                    }
+                }
+            }
+        }
+        advance
+    }
+
+    /// Draw `text` with its top-left corner at `(x, y)`, over whatever is
+    /// there, at the current text scale. Unlike [`Self::display_text`]
+    /// nothing is cleared, and glyphs are clipped at the panel edge
+    /// instead of dropped.
+    pub fn draw_text(&mut self, text: &str, x: i32, y: i32, r: u16, g: u16, b: u16) {
+        let mut x = x;
+        for ch in text.chars() {
+            if x >= MATRIX_WIDTH as i32 {
+                break;
+            }
+            x = offset(x, self.draw_char_at(ch, x, y, r, g, b));
+        }
+    }
+
+    /// [`Self::set_pixel`] for the wider coordinates the shape routines
+    /// compute in. Anything off the panel is dropped.
+    #[inline]
+    fn plot(&mut self, x: i64, y: i64, r: u16, g: u16, b: u16) {
+        if let Some((x, y)) = on_panel(x, y) {
+            self.put(x, y, [r, g, b]);
+        }
+    }
+
+    /// Fill the horizontal span `x0..=x1` on row `y`, clipped to the panel.
+    fn hline(&mut self, x0: i64, x1: i64, y: i64, r: u16, g: u16, b: u16) {
+        let Ok(y) = usize::try_from(y) else {
+            return;
+        };
+        if y >= MATRIX_HEIGHT || x1 < x0 {
+            return;
+        }
+        let width = usize::try_from(x1 - x0 + 1).unwrap_or(usize::MAX);
+        for x in clip_wide(x0, width, MATRIX_WIDTH) {
+            self.put(x, y, [r, g, b]);
+        }
+    }
+
+    /// Borrow the raw 88x88 RGB array, in physical (post-[`Orientation`])
+    /// coordinates and as stored (see [`StoredPixel`]).
+    ///
+    /// Required by [`crate::chain_mapper::compute_chain_data`] when running
+    /// in `no_std` mode — there is no `AsRef` blanket that would let it
+    /// dereference through to the inner field.
+    pub fn as_pixels(&self) -> &[[StoredPixel; MATRIX_WIDTH]; MATRIX_HEIGHT] {
+        &self.pixels
+    }
+
+    /// Mutable access to the raw pixel array. Counts as drawing over the
+    /// whole panel (see [`Self::is_dirty`]).
+    pub fn as_pixels_mut(&mut self) -> &mut [[StoredPixel; MATRIX_WIDTH]; MATRIX_HEIGHT] {
+        self.dirty = Some(DirtyRect::FULL);
+        &mut self.pixels
+    }
+
+    /// Replace every pixel with `other`'s, cancelling any active scroll.
+    /// Text settings (color, scale, alignment, orientation) are kept.
+    pub fn copy_from(&mut self, other: &FrameBuffer) {
+        self.scroll = None;
+        self.pixels = other.pixels;
+        self.dirty = Some(DirtyRect::FULL);
+    }
+}
+
+/// How far along one axis step `k` of a Bresenham line is, for a line
+/// that moves `delta` along that axis in `major` steps (the larger of its
+/// two deltas): `k * delta / major`, rounded half up, as
+/// [`FrameBuffer::draw_line`]'s error term rounds it.
+fn step_offset(k: i64, delta: i64, major: i64) -> i64 {
+    if major == 0 {
+        return 0;
+    }
+    let (k, delta, major) = (i128::from(k), i128::from(delta), i128::from(major));
+    ((2 * delta * k + major) / (2 * major)) as i64
+}
+
+/// The first step at which [`step_offset`] reaches `offset`, or
+/// `i64::MAX` if it never does.
+fn first_step_at(offset: i64, delta: i64, major: i64) -> i64 {
+    if offset <= 0 {
+        return 0;
+    }
+    if delta == 0 {
+        return i64::MAX;
+    }
+    let (offset, delta, major) = (i128::from(offset), i128::from(delta), i128::from(major));
+    // The smallest k with 2 * delta * k + major >= 2 * major * offset.
+    let k = ((2 * offset - 1) * major + 2 * delta - 1) / (2 * delta);
+    i64::try_from(k).unwrap_or(i64::MAX)
+}
+
+/// The first and last steps of the Bresenham line from `(x0, y0)` to
+/// `(x1, y1)` that land on the panel, or `None` if none do.
+///
+/// Liang–Barsky with the step number as the parameter: each coordinate
+/// only moves one way along the line, so each panel edge cuts off a run
+/// of steps at one end, found from [`step_offset`] without walking them.
+fn clip_line(x0: i64, y0: i64, x1: i64, y1: i64) -> Option<(i64, i64)> {
+    let (dx, dy) = ((x1 - x0).abs(), (y1 - y0).abs());
+    let major = dx.max(dy);
+    let (mut first, mut last) = (0, major);
+    for (from, to, delta, size) in [(x0, x1, dx, MATRIX_WIDTH), (y0, y1, dy, MATRIX_HEIGHT)] {
+        let size = size as i64;
+        // Offsets from `from`, towards `to`, of the panel's near and far
+        // edges.
+        let (near, far) = if from <= to {
+            (-from, size - 1 - from)
+        } else {
+            (from - (size - 1), from)
+        };
+        first = first.max(first_step_at(near, delta, major));
+        last = last.min(first_step_at(far.saturating_add(1), delta, major).saturating_sub(1));
+    }
+    (first <= last).then_some((first, last))
+}
+
+/// Logical `(x, y)` as buffer indices, or `None` off the panel.
+fn on_panel(x: i64, y: i64) -> Option<(usize, usize)> {
+    let x = usize::try_from(x).ok().filter(|&x| x < MATRIX_WIDTH)?;
+    let y = usize::try_from(y).ok().filter(|&y| y < MATRIX_HEIGHT)?;
+    Some((x, y))
+}
+
+/// The on-panel part of the `len` pixels from `start` along an axis of
+/// `limit` pixels.
+fn clip(start: i32, len: usize, limit: usize) -> Range<usize> {
+    clip_wide(start.into(), len, limit)
+}
+
+/// [`clip`] from an `i64` start.
+fn clip_wide(start: i64, len: usize, limit: usize) -> Range<usize> {
+    let end = start.saturating_add(i64::try_from(len).unwrap_or(i64::MAX));
+    let bound = |at: i64| at.clamp(0, limit as i64) as usize;
+    bound(start)..bound(end)
+}
+
+/// `start` moved on by `by` pixels, saturating rather than wrapping.
+fn offset(start: i32, by: usize) -> i32 {
+    start.saturating_add(i32::try_from(by).unwrap_or(i32::MAX))
+}
+
+/// Column where the edge from `a` to `b` crosses row `y`, rounded to
+/// the nearest pixel. A horizontal edge gives its far end, `b`.
+fn edge_x(a: (i64, i64), b: (i64, i64), y: i64) -> i64 {
+    let dy = b.1 - a.1;
+    if dy == 0 {
+        return b.0;
+    }
+    let num = (b.0 - a.0) * (y - a.1);
+    // Round half away from zero; `dy` is positive (rows are sorted).
+    a.0 + (2 * num + num.signum() * dy) / (2 * dy)
+}
+
+/// Walk one octant of a midpoint circle of the given radius, calling
+/// `plot(x, y)` with offsets from the centre where `x >= y >= 0`. Callers
+/// mirror each point into the other seven octants.
+fn for_each_octant_point(radius: usize, mut plot: impl FnMut(i64, i64)) {
+    let mut x = radius as i64;
+    let mut y = 0i64;
+    let mut err = 1 - x;
+    while x >= y {
+        plot(x, y);
+        y += 1;
+        if err < 0 {
+            err += 2 * y + 1;
+        } else {
+            x -= 1;
+            err += 2 * (y - x) + 1;
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::font::FONT_WIDTH;
+
+    /// A dim channel value that `pixel8` stores exactly: its lowest
+    /// level.
+    const DIM: u16 = 0x101;
+
+    /// Longer than a line of text, even across two panels.
+    const LONG: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+    /// Characters of the built-in font that fit on a line at `scale`.
+    fn line_chars(scale: usize) -> usize {
+        let cell = (FONT_WIDTH + DEFAULT_CHAR_SPACING) * scale;
+        (MATRIX_WIDTH - TEXT_START_X + DEFAULT_CHAR_SPACING * scale) / cell
+    }
+
+    /// The panel size as drawing coordinates.
+    const W: i32 = MATRIX_WIDTH as i32;
+    const H: i32 = MATRIX_HEIGHT as i32;
+
+    #[test]
+    fn new_is_blank() {
+        let fb = FrameBuffer::new();
+        for row in fb.as_pixels().iter() {
+            for px in row.iter() {
+                assert_eq!(*px, [0, 0, 0]);
+            }
+        }
+    }
+
+    #[test]
+    fn default_matches_new() {
+        let fb = FrameBuffer::default();
+        assert_eq!(fb.get_pixel(0, 0), [0, 0, 0]);
+    }
+
+    #[test]
+    fn set_and_get_pixel() {
+        let mut fb = FrameBuffer::new();
+        fb.set_pixel(10, 20, 100, 200, 250);
+        assert_eq!(fb.get_pixel(10, 20), load_pixel([100, 200, 250]));
+    }
+
+    #[test]
+    fn stored_pixels_keep_the_extremes_and_round_the_rest() {
+        for px in [[0, 0x8000, 0xFFFF], [0x1234, 0xABCD, 0x7F7F]] {
+            let loaded = load_pixel(store_pixel(px));
+            assert_eq!(store_pixel(loaded), store_pixel(px));
+            for (a, b) in loaded.into_iter().zip(px) {
+                assert!(a.abs_diff(b) <= 0x80, "{px:x?} came back as {loaded:x?}");
+            }
+        }
+        assert_eq!(load_pixel(store_pixel([0, 0, 0xFFFF])), [0, 0, 0xFFFF]);
+    }
+
+    #[test]
+    fn dithering_keeps_exact_levels() {
+        for level in [0u8, 1, 0x7F, 0xFE, 0xFF] {
+            for (x, y) in [(0, 0), (1, 2), (3, 3), (63, 31)] {
+                assert_eq!(dither_channel(u16::from(level) * 0x101, x, y), level);
+            }
+        }
+        // A quarter of the way to the next level rounds up in a quarter
+        // of each 4x4 block, and the block repeats across the panel.
+        let quarter = 10 * 0x101 + 0x101 / 4;
+        let up = |x, y| dither_channel(quarter, x, y) == 11;
+        let count = (0..16).filter(|i| up(i % 4, i / 4)).count();
+        assert_eq!(count, 4);
+        assert!((0..16).all(|i| up(i % 4, i / 4) == up(i % 4 + 8, i / 4 + 4)));
+    }
+
+    #[test]
+    fn dithered_ramp_follows_the_gradient() {
+        // A 16-column ramp from level 10 to level 11, four rows high.
+        let ramp = |x: usize| (10 * 0x101 + 0x101 * x / 16) as u16;
+        let round = |v: u16| ((u32::from(v) * 0xFF + 0x7FFF) / 0xFFFF) as u8;
+        let mut rounded_up = [0; 4];
+        let mut dithered_up = [0; 4];
+        for y in 0..4 {
+            for x in 0..16 {
+                rounded_up[x / 4] += usize::from(round(ramp(x)) == 11);
+                dithered_up[x / 4] += usize::from(dither_channel(ramp(x), x, y) == 11);
+            }
+        }
+        // Plain rounding jumps from 10 to 11 at one column: a band edge.
+        assert_eq!(rounded_up, [0, 0, 12, 16]);
+        // Dithered, each block is a mix that tracks the ramp's mean there.
+        for (block, &up) in dithered_up.iter().enumerate() {
+            let expected = (block * 4) as f32 + 1.5;
+            assert!((up as f32 - expected).abs() <= 1.5, "{dithered_up:?}");
+        }
+        assert!(dithered_up.windows(2).all(|pair| pair[0] < pair[1]));
+
+        // With 16-bit storage there is nothing to round away.
+        let mut fb = FrameBuffer::new();
+        fb.set_dithering(true);
+        fb.set_pixel_16(5, 5, ramp(7), 0, 0xFFFF);
+        #[cfg(not(feature = "pixel8"))]
+        assert_eq!(fb.get_pixel(5, 5), [ramp(7), 0, 0xFFFF]);
+    }
+
+    #[test]
+    fn gradient_corners_are_the_end_colors() {
+        let (start, end) = ([0xFFFF, 0x1212, 0], [0, 0x3434, 0xFFFF]);
+        let (right, bottom) = (MATRIX_WIDTH - 1, MATRIX_HEIGHT - 1);
+        let mut fb = FrameBuffer::new();
+        for dithering in [false, true] {
+            fb.set_dithering(dithering);
+            fb.fill_gradient(start, end, GradientDir::Horizontal);
+            assert_eq!(fb.get_pixel(0, bottom), start);
+            assert_eq!(fb.get_pixel(right, 0), end);
+            fb.fill_gradient(start, end, GradientDir::Vertical);
+            assert_eq!(fb.get_pixel(right, 0), start);
+            assert_eq!(fb.get_pixel(0, bottom), end);
+            fb.fill_gradient(start, end, GradientDir::Diagonal);
+            assert_eq!(fb.get_pixel(0, 0), start);
+            assert_eq!(fb.get_pixel(right, bottom), end);
+        }
+        // Halfway across, halfway between.
+        fb.set_dithering(false);
+        fb.fill_gradient([0; 3], [0xFFFF; 3], GradientDir::Vertical);
+        let [r, _, _] = fb.get_pixel(0, MATRIX_HEIGHT / 2);
+        assert!(r.abs_diff(0x8000) < 0x400, "{r:#x}");
+    }
+
+    #[test]
+    fn rgb565_primaries_expand_to_full_range() {
+        assert_eq!(rgb565_to_pixel(0xF800), [0xFFFF, 0, 0]);
+        assert_eq!(rgb565_to_pixel(0x07E0), [0, 0xFFFF, 0]);
+        assert_eq!(rgb565_to_pixel(0x001F), [0, 0, 0xFFFF]);
+        assert_eq!(rgb565_to_pixel(0xFFFF), [0xFFFF, 0xFFFF, 0xFFFF]);
+        assert_eq!(rgb565_to_pixel(0x0000), [0, 0, 0]);
+    }
+
+    #[test]
+    fn rgb565_lowest_step_is_scaled() {
+        // One 5-bit red step = 0xFFFF / 31 ≈ 2114.
+        assert_eq!(rgb565_to_pixel(0x0800), [2114, 0, 0]);
+        // One 6-bit green step = 0xFFFF / 63 ≈ 1040.
+        assert_eq!(rgb565_to_pixel(0x0020), [0, 1040, 0]);
+    }
+
+    #[test]
+    fn hsv_sector_boundaries_are_pure_colors() {
+        const FULL: u16 = 0xFFFF;
+        for (h, color) in [
+            (0.0, [FULL, 0, 0]),
+            (60.0, [FULL, FULL, 0]),
+            (120.0, [0, FULL, 0]),
+            (180.0, [0, FULL, FULL]),
+            (240.0, [0, 0, FULL]),
+            (300.0, [FULL, 0, FULL]),
+        ] {
+            assert_eq!(hsv_to_pixel(h, 1.0, 1.0), color, "hue {h}");
+        }
+    }
+
+    #[test]
+    fn hsv_hue_wraps_and_levels_clamp() {
+        assert_eq!(hsv_to_pixel(360.0, 1.0, 1.0), [0xFFFF, 0, 0]);
+        assert_eq!(hsv_to_pixel(-120.0, 1.0, 1.0), [0, 0, 0xFFFF]);
+        assert_eq!(hsv_to_pixel(720.0 + 120.0, 2.0, 1.5), [0, 0xFFFF, 0]);
+        // No saturation is grey, no value is black.
+        assert_eq!(hsv_to_pixel(200.0, 0.0, 0.5), [32768, 32768, 32768]);
+        assert_eq!(hsv_to_pixel(200.0, 1.0, -1.0), [0, 0, 0]);
+        // Halfway between red and yellow.
+        assert_eq!(hsv_to_pixel(30.0, 1.0, 1.0), [0xFFFF, 32768, 0]);
+    }
+
+    #[test]
+    fn set_pixel_hsv_writes_converted_color() {
+        let mut fb = FrameBuffer::new();
+        fb.set_pixel_hsv(5, 6, 240.0, 1.0, 1.0);
+        fb.set_pixel_hsv(W, 0, 0.0, 1.0, 1.0);
+        assert_eq!(fb.get_pixel(5, 6), [0, 0, 0xFFFF]);
+        assert_eq!(fb.get_pixel(0, 0), [0, 0, 0]);
+    }
+
+    #[test]
+    fn rainbow_fill_is_a_diagonal_gradient() {
+        let mut fb = FrameBuffer::new();
+        fb.set_scroll_text("THIS MESSAGE IS FAR TOO LONG TO FIT", 2);
+        fb.rainbow_fill(0.0);
+        assert!(!fb.is_scrolling());
+        assert_eq!(fb.get_pixel(0, 0), [0xFFFF, 0, 0]);
+        // Pixels on one diagonal match; neighbouring diagonals differ.
+        assert_eq!(fb.get_pixel(10, 3), fb.get_pixel(3, 10));
+        assert_ne!(fb.get_pixel(10, 3), fb.get_pixel(11, 3));
+
+        // The phase shifts every hue.
+        fb.rainbow_fill(120.0);
+        assert_eq!(fb.get_pixel(0, 0), [0, 0xFFFF, 0]);
+    }
+
+    #[test]
+    fn test_patterns_light_the_expected_pixels() {
+        let mut fb = FrameBuffer::new();
+        fb.set_orientation(Orientation::Rot180);
+        fb.test_pattern(TestPattern::Green, 0);
+        let green = store_pixel([0, 0xFFFF, 0]);
+        assert!(fb.pixels.iter().flatten().all(|&px| px == green));
+
+        fb.test_pattern(TestPattern::Border, 0);
+        assert_eq!(
+            lit_pixels(&fb).len(),
+            2 * (MATRIX_WIDTH + MATRIX_HEIGHT) - 4
+        );
+        let white = store_pixel([0xFFFF; 3]);
+        assert_eq!(fb.pixels[0][0], white);
+        assert_eq!(fb.pixels[1][1], [0; 3]);
+
+        // Panel coordinates, wrapping after the last pixel.
+        fb.test_pattern(TestPattern::Sweep, MATRIX_WIDTH + 2);
+        assert_eq!(fb.pixels[1][2], white);
+        fb.test_pattern(TestPattern::Sweep, MATRIX_WIDTH * MATRIX_HEIGHT + 1);
+        assert_eq!(fb.pixels[0][1], white);
+        assert_eq!(fb.pixels[1][2], [0; 3]);
+    }
+
+    #[test]
+    fn color_bars_cover_every_chain_mix() {
+        let mut fb = FrameBuffer::new();
+        fb.test_pattern(TestPattern::Bars, 0);
+        let bar = |i: usize| fb.pixels[MATRIX_HEIGHT - 1][i * MATRIX_WIDTH / 8];
+        let [full, _, _] = store_pixel([0xFFFF; 3]);
+        assert_eq!(bar(0), [full, full, full]);
+        assert_eq!(bar(1), [full, full, 0]);
+        assert_eq!(bar(2), [0, full, full]);
+        assert_eq!(bar(5), [full, 0, 0]);
+        assert_eq!(bar(7), [0, 0, 0]);
+        assert_eq!(fb.pixels[0][MATRIX_WIDTH / 8 - 1], [full; 3]);
+    }
+
+    #[test]
+    fn set_pixels_applies_a_batch() {
+        let mut fb = FrameBuffer::new();
+        fb.set_pixels(&[
+            (1, 2, 0x303, 0x404, 0x505),
+            (W, 0, 9, 9, 9),
+            (W - 1, H - 1, 0x606, 0x707, 0x808),
+        ]);
+        assert_eq!(fb.get_pixel(1, 2), [0x303, 0x404, 0x505]);
+        assert_eq!(
+            fb.get_pixel(MATRIX_WIDTH - 1, MATRIX_HEIGHT - 1),
+            [0x606, 0x707, 0x808]
+        );
+        assert_eq!(lit_pixels(&fb).len(), 2);
+    }
+
+    #[test]
+    fn set_frame_follows_the_orientation() {
+        let mut frame = [[[0; 3]; MATRIX_WIDTH]; MATRIX_HEIGHT];
+        frame[0][1] = [0x101, 0x202, 0x303];
+        let mut fb = FrameBuffer::new();
+        fb.set_frame(&frame);
+        assert_eq!(fb.pixels, frame.map(|row| row.map(store_pixel)));
+
+        fb.set_orientation(Orientation::Rot180);
+        fb.set_frame(&frame);
+        assert_eq!(fb.get_pixel(1, 0), [0x101, 0x202, 0x303]);
+        assert_eq!(
+            fb.pixels[MATRIX_HEIGHT - 1][MATRIX_WIDTH - 2],
+            store_pixel([0x101, 0x202, 0x303])
+        );
+        assert_eq!(fb.pixels[0][1], [0; 3]);
+    }
+
+    #[test]
+    fn fill_rect_is_clipped() {
+        let mut fb = FrameBuffer::new();
+        fb.fill_rect(86, 2, 5, 2, 0x101, 0x202, 0x303);
+        assert_eq!(fb.get_pixel(86, 2), [0x101, 0x202, 0x303]);
+        assert_eq!(fb.get_pixel(87, 3), [0x101, 0x202, 0x303]);
+        assert_eq!(fb.get_pixel(85, 2), [0, 0, 0]);
+        assert_eq!(fb.get_pixel(86, 4), [0, 0, 0]);
+    }
+
+    #[test]
+    fn negative_origins_draw_the_visible_part() {
+        let mut fb = FrameBuffer::new();
+        fb.set_pixel(-3, 5, 1, 2, 3);
+        assert!(!fb.is_dirty());
+        assert_eq!(fb.get_pixel(0, 5), [0, 0, 0]);
+
+        fb.fill_rect(-5, -5, 10, 10, 0x101, 0x202, 0x303);
+        let lit = lit_pixels(&fb);
+        assert_eq!(lit.len(), 25);
+        assert!(lit.iter().all(|&(x, y)| x < 5 && y < 5));
+
+        // A glyph hanging off the left edge shows its right-hand columns.
+        fb.clear();
+        fb.draw_char_at('H', -4, 0, 0x101, 0x202, 0x303);
+        assert_eq!(fb.get_pixel(0, 3), [0x101, 0x202, 0x303]);
+        assert_eq!(fb.get_pixel(0, 2), [0x101, 0x202, 0x303]);
+        fb.draw_line(-100, 10, 200, 10, 0x101, 0x202, 0x303);
+        assert_eq!(fb.get_pixel(0, 10), [0x101, 0x202, 0x303]);
+        assert_eq!(fb.get_pixel(87, 10), [0x101, 0x202, 0x303]);
+    }
+
+    #[test]
+    fn draw_rect_outlines_only() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_rect(10, 10, 4, 3, 0x707, 0x707, 0x707);
+        for (x, y) in [(10, 10), (13, 10), (10, 12), (13, 12), (11, 10), (10, 11)] {
+            assert_eq!(fb.get_pixel(x, y), [0x707; 3], "({x}, {y})");
+        }
+        assert_eq!(fb.get_pixel(11, 11), [0, 0, 0]);
+        assert_eq!(fb.get_pixel(14, 10), [0, 0, 0]);
+    }
+
+    #[test]
+    fn draw_rect_lights_exactly_the_perimeter() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_rect(20, 30, 10, 10, 0x505, 0x505, 0x505);
+        for y in 0..MATRIX_HEIGHT {
+            for x in 0..MATRIX_WIDTH {
+                let edge = (x == 20 || x == 29) && (30..40).contains(&y)
+                    || (y == 30 || y == 39) && (20..30).contains(&x);
+                let expected = if edge { [0x505; 3] } else { [0, 0, 0] };
+                assert_eq!(fb.get_pixel(x, y), expected, "({x}, {y})");
+            }
+        }
+        assert_eq!(lit_pixels(&fb).len(), 36);
+    }
+
+    #[test]
+    fn draw_rect_handles_degenerate_sizes() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_rect(3, 4, 1, 1, DIM, DIM, DIM);
+        fb.draw_rect(10, 0, 1, 5, DIM, DIM, DIM);
+        fb.draw_rect(0, 20, 6, 1, DIM, DIM, DIM);
+        fb.draw_rect(50, 50, 0, 9, DIM, DIM, DIM);
+        assert_eq!(lit_pixels(&fb).len(), 1 + 5 + 6);
+        assert_eq!(fb.get_pixel(3, 4), [DIM; 3]);
+        assert_eq!(fb.get_pixel(10, 4), [DIM; 3]);
+        assert_eq!(fb.get_pixel(5, 20), [DIM; 3]);
+    }
+
+    #[test]
+    fn draw_rect_clips_edges_past_the_panel() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_rect(80, 84, 20, 20, 0x202, 0x202, 0x202);
+        // Only the top and left edges are on the panel.
+        assert_eq!(fb.get_pixel(87, 84), [0x202; 3]);
+        assert_eq!(fb.get_pixel(80, 87), [0x202; 3]);
+        assert_eq!(fb.get_pixel(87, 87), [0, 0, 0]);
+        fb.draw_rect(i32::MAX, i32::MAX, 2, 2, 2, 2, 2);
+    }
+
+    #[test]
+    fn draw_text_keeps_background() {
+        let mut fb = FrameBuffer::new();
+        fb.fill(0, 0, 0x909);
+        fb.draw_text("I", 20, 30, 0xFFFF, 0, 0);
+        // `I` has a three-pixel bar on top, one pixel in.
+        assert_eq!(fb.get_pixel(21, 30), [0xFFFF, 0, 0]);
+        assert_eq!(fb.get_pixel(20, 30), [0, 0, 0x909]);
+        // Partly off the right edge: clipped, not dropped.
+        fb.draw_text("I", 85, 0, DIM, DIM, DIM);
+        assert_eq!(fb.get_pixel(86, 0), [DIM; 3]);
+    }
+
+    #[test]
+    fn copy_from_takes_pixels_and_stops_scrolling() {
+        let mut other = FrameBuffer::new();
+        other.set_pixel(4, 5, 1, 2, 3);
+        let mut fb = FrameBuffer::new();
+        fb.set_text_color(9, 9, 9);
+        fb.set_scroll_text("THIS MESSAGE IS FAR TOO LONG TO FIT", 2);
+        fb.copy_from(&other);
+        assert!(!fb.is_scrolling());
+        assert_eq!(fb.as_pixels(), other.as_pixels());
+        assert_eq!(fb.text_color(), [9, 9, 9]);
+    }
+
+    #[test]
+    fn blend_alpha_zero_keeps_existing() {
+        let mut fb = FrameBuffer::new();
+        fb.set_pixel(1, 1, 100, 200, 250);
+        fb.blend_pixel(1, 1, 0xFFFF, 0xFFFF, 0xFFFF, 0);
+        assert_eq!(fb.get_pixel(1, 1), load_pixel([100, 200, 250]));
+    }
+
+    #[test]
+    fn blend_alpha_full_overwrites() {
+        let mut fb = FrameBuffer::new();
+        fb.set_pixel(1, 1, 100, 200, 250);
+        fb.blend_pixel(1, 1, 0xFFFF, 0x707, 0, 255);
+        assert_eq!(fb.get_pixel(1, 1), [0xFFFF, 0x707, 0]);
+    }
+
+    #[test]
+    fn blend_alpha_half_mixes_and_rounds() {
+        // Red at 128/255 over green: 65535 * 128/255 red and
+        // 65535 * 127/255 green, both exact.
+        assert_eq!(
+            blend([0, 0xFFFF, 0], [0xFFFF, 0, 0], 128),
+            [32896, 32639, 0]
+        );
+        // 1 * 128/255 ≈ 0.502 rounds up; 1 * 127/255 ≈ 0.498 rounds down.
+        assert_eq!(blend([0; 3], [1, 1, 1], 128), [1, 1, 1]);
+        assert_eq!(blend([0; 3], [1, 1, 1], 127), [0, 0, 0]);
+    }
+
+    #[test]
+    fn blend_rect_is_clipped_to_the_panel() {
+        let mut fb = FrameBuffer::new();
+        fb.fill(0xFFFF, 0xFFFF, 0xFFFF);
+        fb.blend_rect(W - 2, 10, 5, 3, 0, 0, 0, 255);
+        assert_eq!(fb.get_pixel(MATRIX_WIDTH - 1, 12), [0, 0, 0]);
+        assert_eq!(fb.get_pixel(MATRIX_WIDTH - 3, 12), [0xFFFF; 3]);
+        assert_eq!(fb.get_pixel(MATRIX_WIDTH - 1, 13), [0xFFFF; 3]);
+        // Fully off-panel does nothing (and doesn't panic).
+        fb.blend_rect(W, H, usize::MAX, usize::MAX, 0, 0, 0, 255);
+        fb.blend_pixel(W, 0, 0, 0, 0, 255);
+        fb.blend_pixel(-1, 0, 0, 0, 0, 255);
+    }
+
+    #[test]
+    fn set_pixel_565_writes_expanded_color() {
+        let mut fb = FrameBuffer::new();
+        fb.set_pixel_565(3, 4, 0xF800);
+        fb.set_pixel_565(W, 0, 0xFFFF);
+        assert_eq!(fb.get_pixel(3, 4), [0xFFFF, 0, 0]);
+        assert_eq!(fb.get_pixel(0, 0), [0, 0, 0]);
+    }
+
+    #[test]
+    fn out_of_bounds_set_is_noop() {
+        let mut fb = FrameBuffer::new();
+        fb.set_pixel(W, 0, 1, 2, 3);
+        fb.set_pixel(0, H, 1, 2, 3);
+        // buffer still zeroed
+        assert_eq!(fb.get_pixel(0, 0), [0, 0, 0]);
+    }
+
+    #[test]
+    fn out_of_bounds_get_returns_black() {
+        let fb = FrameBuffer::new();
+        assert_eq!(fb.get_pixel(MATRIX_WIDTH, 0), [0, 0, 0]);
+        assert_eq!(fb.get_pixel(0, MATRIX_HEIGHT), [0, 0, 0]);
+    }
+
+    #[test]
+    fn pixel_round_trips_up_to_the_edges() {
+        let mut fb = FrameBuffer::new();
+        let (right, bottom) = (MATRIX_WIDTH - 1, MATRIX_HEIGHT - 1);
+        for (i, (x, y)) in [(0, 0), (right, 0), (0, bottom), (right, bottom)]
+            .into_iter()
+            .enumerate()
+        {
+            let color = [i as u16 * 0x1111, 0xFFFF, 0];
+            fb.set_pixel_16(x as i32, y as i32, color[0], color[1], color[2]);
+            assert_eq!(fb.pixel(x, y), Some(load_pixel(store_pixel(color))));
+        }
+        for (x, y) in [(MATRIX_WIDTH, 0), (0, MATRIX_HEIGHT), (usize::MAX, 0)] {
+            fb.set_pixel(x as i32, y as i32, 1, 2, 3);
+            assert_eq!(fb.pixel(x, y), None);
+        }
+        assert_eq!(fb.pixel(1, 1), Some([0, 0, 0]));
+    }
+
+    #[test]
+    fn clear_resets_all_pixels() {
+        let mut fb = FrameBuffer::new();
+        fb.set_pixel(1, 1, 50, 60, 70);
+        fb.set_pixel(87, 87, 80, 90, 100);
+        fb.clear();
+        assert_eq!(fb.get_pixel(1, 1), [0, 0, 0]);
+        assert_eq!(fb.get_pixel(87, 87), [0, 0, 0]);
+    }
+
+    #[test]
+    fn fill_sets_every_pixel() {
+        let mut fb = FrameBuffer::new();
+        fb.fill(0x101, 0x202, 0x303);
+        assert!(
+            fb.as_pixels()
+                .iter()
+                .flatten()
+                .all(|px| *px == store_pixel([0x101, 0x202, 0x303]))
+        );
+    }
+
+    #[test]
+    fn drawing_grows_the_dirty_rect() {
+        let mut fb = FrameBuffer::new();
+        assert!(!fb.is_dirty());
+        fb.set_pixel(3, 4, 1, 1, 1);
+        fb.draw_line(10, 2, 12, 2, 1, 1, 1);
+        let rect = fb.take_dirty().unwrap();
+        assert_eq!((rect.columns(), rect.rows()), (3..13, 2..5));
+        assert!(!fb.is_dirty());
+
+        fb.set_orientation(Orientation::Rot180);
+        fb.set_pixel(0, 0, 1, 1, 1);
+        let corner = DirtyRect::new(MATRIX_WIDTH - 1, MATRIX_HEIGHT - 1, 1, 1);
+        assert_eq!(fb.dirty_rect(), corner);
+
+        fb.take_dirty();
+        fb.clear();
+        assert_eq!(fb.take_dirty(), Some(DirtyRect::FULL));
+        fb.mark_dirty(MATRIX_WIDTH, 0, 5, 5);
+        fb.mark_dirty(MATRIX_WIDTH + 12, 0, 5, 5);
+        fb.mark_dirty(0, MATRIX_HEIGHT + 12, 5, 5);
+        assert!(!fb.is_dirty());
+    }
+
+    #[test]
+    fn empty_text_clears_buffer() {
+        let mut fb = FrameBuffer::new();
+        fb.set_pixel(5, 5, 1, 2, 3);
+        fb.display_text("");
+        assert_eq!(fb.get_pixel(5, 5), [0, 0, 0]);
+    }
+
+    /// Collect the coordinates of every lit pixel, row-major.
+    pub(crate) fn lit_pixels(
+        fb: &FrameBuffer,
+    ) -> heapless::Vec<(usize, usize), { MATRIX_WIDTH * MATRIX_HEIGHT }> {
+        let mut out = heapless::Vec::new();
+        for (y, row) in fb.as_pixels().iter().enumerate() {
+            for (x, px) in row.iter().enumerate() {
+                if *px != [0, 0, 0] {
+                    out.push((x, y)).unwrap();
+                }
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn horizontal_line_lights_exact_pixels() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_line(2, 5, 6, 5, DIM, DIM, DIM);
+        assert_eq!(lit_pixels(&fb), [(2, 5), (3, 5), (4, 5), (5, 5), (6, 5)]);
+    }
+
+    #[test]
+    fn vertical_line_lights_exact_pixels() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_line(3, 1, 3, 4, DIM, DIM, DIM);
+        assert_eq!(lit_pixels(&fb), [(3, 1), (3, 2), (3, 3), (3, 4)]);
+    }
+
+    #[test]
+    fn diagonal_line_lights_exact_pixels() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_line(0, 0, 3, 3, DIM, DIM, DIM);
+        assert_eq!(lit_pixels(&fb), [(0, 0), (1, 1), (2, 2), (3, 3)]);
+    }
+
+    #[test]
+    fn reversed_endpoints_draw_same_line() {
+        let mut forward = FrameBuffer::new();
+        forward.draw_line(1, 2, 9, 5, DIM, DIM, DIM);
+        let mut backward = FrameBuffer::new();
+        backward.draw_line(9, 5, 1, 2, DIM, DIM, DIM);
+        assert_eq!(lit_pixels(&forward), lit_pixels(&backward));
+    }
+
+    #[test]
+    fn steep_line_has_one_pixel_per_row() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_line(4, 0, 6, 10, DIM, DIM, DIM);
+        let lit = lit_pixels(&fb);
+        assert_eq!(lit.len(), 11);
+        for (i, &(_, y)) in lit.iter().enumerate() {
+            assert_eq!(y, i);
+        }
+    }
+
+    #[test]
+    fn line_past_edge_is_clipped() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_line(80, 10, W + 20, 10, DIM, DIM, DIM);
+        assert_eq!(lit_pixels(&fb).len(), MATRIX_WIDTH - 80);
+    }
+
+    /// The on-panel pixels of the line from `(x0, y0)` to `(x1, y1)`,
+    /// found by walking all of it.
+    fn walked_line(x0: i64, y0: i64, x1: i64, y1: i64) -> heapless::Vec<(usize, usize), 256> {
+        let ((x0, y0), (x1, y1)) = if (x0, y0) <= (x1, y1) {
+            ((x0, y0), (x1, y1))
+        } else {
+            ((x1, y1), (x0, y0))
+        };
+        let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+        let (sx, sy) = (if x0 < x1 { 1 } else { -1 }, if y0 < y1 { 1 } else { -1 });
+        let (mut x, mut y, mut err) = (x0, y0, dx + dy);
+        let mut out = heapless::Vec::new();
+        loop {
+            if (0..MATRIX_WIDTH as i64).contains(&x) && (0..MATRIX_HEIGHT as i64).contains(&y) {
+                out.push((x as usize, y as usize)).unwrap();
+            }
+            if (x, y) == (x1, y1) {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+        out.sort_unstable_by_key(|&(x, y)| (y, x));
+        out
+    }
+
+    #[test]
+    fn clipped_lines_light_the_walked_pixels() {
+        let xs = [-3 * W, -1, 0, W / 2, W - 1, W, W + 7];
+        let ys = [-2 * H, -5, 0, H / 3, H - 1, H, 2 * H + 1];
+        for (x0, y0) in xs.into_iter().flat_map(|x| ys.map(|y| (x, y))) {
+            for (x1, y1) in xs.into_iter().flat_map(|x| ys.map(|y| (x, y))) {
+                let mut fb = FrameBuffer::new();
+                fb.draw_line(x0, y0, x1, y1, DIM, DIM, DIM);
+                let walked = walked_line(x0.into(), y0.into(), x1.into(), y1.into());
+                assert_eq!(lit_pixels(&fb), walked, "({x0}, {y0}) to ({x1}, {y1})");
+            }
+        }
+    }
+
+    #[test]
+    fn far_off_endpoints_are_clipped_without_walking_them() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_line(i32::MIN, 10, i32::MAX, 10, DIM, DIM, DIM);
+        assert_eq!(lit_pixels(&fb).len(), MATRIX_WIDTH);
+        fb.clear();
+        // Only reaches row 0 some 24 million columns left of the panel.
+        fb.draw_line(W - 1, -1, -i32::MAX, H / 2 - 1, DIM, DIM, DIM);
+        assert!(lit_pixels(&fb).is_empty());
+        fb.clear();
+        // Steep enough to leave through the bottom edge.
+        fb.draw_line(5, 0, 1_000_000, i32::MAX, DIM, DIM, DIM);
+        let lit = lit_pixels(&fb);
+        assert_eq!(lit.len(), MATRIX_HEIGHT);
+        assert_eq!(
+            (lit[0], lit[MATRIX_HEIGHT - 1]),
+            ((5, 0), (5, MATRIX_HEIGHT - 1))
+        );
+    }
+
+    #[test]
+    fn zero_radius_circle_lights_centre_only() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_circle(10, 10, 0, DIM, DIM, DIM);
+        assert_eq!(lit_pixels(&fb), [(10, 10)]);
+
+        let mut fb = FrameBuffer::new();
+        fb.fill_circle(10, 10, 0, DIM, DIM, DIM);
+        assert_eq!(lit_pixels(&fb), [(10, 10)]);
+    }
+
+    #[test]
+    fn small_circle_outlines_have_known_pixel_counts() {
+        for (radius, expected) in [(1, 4), (2, 12), (3, 16), (5, 28)] {
+            let mut fb = FrameBuffer::new();
+            fb.draw_circle(40, 40, radius, DIM, DIM, DIM);
+            assert_eq!(lit_pixels(&fb).len(), expected, "radius {}", radius);
+        }
+    }
+
+    #[test]
+    fn small_filled_circles_have_known_pixel_counts() {
+        for (radius, expected) in [(1, 5), (2, 21), (3, 37), (5, 97)] {
+            let mut fb = FrameBuffer::new();
+            fb.fill_circle(40, 40, radius, DIM, DIM, DIM);
+            assert_eq!(lit_pixels(&fb).len(), expected, "radius {}", radius);
+        }
+    }
+
+    #[test]
+    fn filled_circle_contains_its_outline() {
+        let mut outline = FrameBuffer::new();
+        outline.draw_circle(20, 20, 6, DIM, DIM, DIM);
+        let mut filled = FrameBuffer::new();
+        filled.fill_circle(20, 20, 6, DIM, DIM, DIM);
+        for (x, y) in lit_pixels(&outline) {
+            assert_ne!(filled.get_pixel(x, y), [0, 0, 0], "({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn circle_past_edge_is_clipped() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_circle(0, 0, 3, DIM, DIM, DIM);
+        fb.fill_circle(W - 1, H - 1, 10, DIM, DIM, DIM);
+        // Only the quadrants that overlap the panel are drawn.
+        assert_ne!(fb.get_pixel(3, 0), [0, 0, 0]);
+        assert_ne!(fb.get_pixel(0, 3), [0, 0, 0]);
+        assert_ne!(
+            fb.get_pixel(MATRIX_WIDTH - 1, MATRIX_HEIGHT - 11),
+            [0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn filled_right_triangle_lights_its_half_square() {
+        let mut fb = FrameBuffer::new();
+        fb.fill_triangle(0, 0, 9, 0, 0, 9, DIM, DIM, DIM);
+        // Row y covers x = 0..=9 - y: 10 + 9 + ... + 1 pixels.
+        assert_eq!(lit_pixels(&fb).len(), 55);
+        for (x, y) in lit_pixels(&fb) {
+            assert!(x + y <= 9, "({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn filled_upward_arrow_head_is_symmetric() {
+        let mut fb = FrameBuffer::new();
+        fb.fill_triangle(44, 10, 34, 20, 54, 20, DIM, DIM, DIM);
+        // Row 10 + k spans 44 - k..=44 + k.
+        assert_eq!(
+            lit_pixels(&fb).len(),
+            (0..=10).map(|k| 2 * k + 1).sum::<usize>()
+        );
+        for k in 0..=10 {
+            assert_ne!(fb.get_pixel(44 - k, 10 + k), [0, 0, 0]);
+            assert_ne!(fb.get_pixel(44 + k, 10 + k), [0, 0, 0]);
+            assert_eq!(fb.get_pixel(44 + k + 1, 10 + k), [0, 0, 0]);
+        }
+        // Corner order doesn't matter.
+        let mut swapped = FrameBuffer::new();
+        swapped.fill_triangle(54, 20, 44, 10, 34, 20, DIM, DIM, DIM);
+        assert_eq!(fb.as_pixels(), swapped.as_pixels());
+    }
+
+    #[test]
+    fn triangle_outline_is_three_lines() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_triangle(0, 0, 9, 0, 0, 9, DIM, DIM, DIM);
+        // 10 + 10 + 10 pixels, each corner shared by two edges.
+        assert_eq!(lit_pixels(&fb).len(), 27);
+        assert_eq!(fb.get_pixel(1, 1), [0, 0, 0]);
+    }
+
+    #[test]
+    fn filled_triangle_contains_its_outline() {
+        let mut outline = FrameBuffer::new();
+        outline.draw_triangle(5, 3, 30, 17, 12, 40, DIM, DIM, DIM);
+        let mut filled = FrameBuffer::new();
+        filled.fill_triangle(5, 3, 30, 17, 12, 40, DIM, DIM, DIM);
+        for (x, y) in lit_pixels(&outline) {
+            assert_ne!(filled.get_pixel(x, y), [0, 0, 0], "({x}, {y})");
+        }
+        assert!(lit_pixels(&filled).len() > lit_pixels(&outline).len());
+    }
+
+    #[test]
+    fn collinear_triangle_is_a_line() {
+        let mut fb = FrameBuffer::new();
+        fb.fill_triangle(10, 10, 20, 20, 15, 15, DIM, DIM, DIM);
+        let mut line = FrameBuffer::new();
+        line.draw_line(10, 10, 20, 20, DIM, DIM, DIM);
+        assert_eq!(fb.as_pixels(), line.as_pixels());
+
+        let mut flat = FrameBuffer::new();
+        flat.fill_triangle(30, 5, 40, 5, 35, 5, DIM, DIM, DIM);
+        assert_eq!(lit_pixels(&flat).len(), 11);
+    }
+
+    #[test]
+    fn triangle_past_edge_is_clipped() {
+        let mut fb = FrameBuffer::new();
+        fb.fill_triangle(W - 8, H - 8, W + 32, H - 8, W - 8, H + 32, DIM, DIM, DIM);
+        // The on-panel corner of the triangle is fully lit.
+        for y in MATRIX_HEIGHT - 8..MATRIX_HEIGHT {
+            for x in MATRIX_WIDTH - 8..MATRIX_WIDTH {
+                assert_ne!(fb.get_pixel(x, y), [0, 0, 0], "({x}, {y})");
+            }
+        }
+        fb.fill_triangle(-1000, 0, 0, -1000, 5, 5, DIM, DIM, DIM);
+    }
+
+    #[test]
+    fn closed_polygon_matches_the_triangle_outline() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_polygon(&[(10, 10), (30, 12), (15, 40)], DIM, DIM, DIM);
+        let mut outline = FrameBuffer::new();
+        outline.draw_triangle(10, 10, 30, 12, 15, 40, DIM, DIM, DIM);
+        assert_eq!(fb.as_pixels(), outline.as_pixels());
+
+        // Without the closing edge the (10, 10) - (15, 40) side is missing.
+        let mut open = FrameBuffer::new();
+        open.draw_polyline(&[(10, 10), (30, 12), (15, 40)], DIM, DIM, DIM);
+        assert_eq!(open.get_pixel(12, 22), [0, 0, 0]);
+        assert_ne!(fb.get_pixel(12, 22), [0, 0, 0]);
+    }
+
+    #[test]
+    fn short_polylines_light_at_most_a_point() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_polyline(&[], DIM, DIM, DIM);
+        fb.draw_polygon(&[], DIM, DIM, DIM);
+        assert!(!fb.is_dirty());
+        fb.draw_polygon(&[(5, 6)], DIM, DIM, DIM);
+        assert_eq!(lit_pixels(&fb), [(5, 6)]);
+        fb.draw_polyline(&[(0, 0), (200, 0)], DIM, DIM, DIM);
+        assert_eq!(lit_pixels(&fb).len(), MATRIX_WIDTH + 1);
+    }
+
+    #[test]
+    fn text_is_rendered_over_the_scene() {
+        let mut fb = FrameBuffer::new();
+        assert!(fb.push_command(DrawCommand::Fill {
+            color: [0, 0, 0x909]
+        }));
+        assert!(fb.push_command(DrawCommand::Pixel {
+            x: 1,
+            y: 1,
+            color: [0x707, 0, 0],
+        }));
+        fb.display_text("I");
+        assert_eq!(fb.get_pixel(1, 1), [0x707, 0, 0]);
+        assert_eq!(fb.get_pixel(87, 87), [0, 0, 0x909]);
+        let mut plain = FrameBuffer::new();
+        plain.display_text("I");
+        let glyph = lit_pixels(&plain);
+        assert!(!glyph.is_empty());
+        for &(x, y) in &glyph {
+            assert_eq!(fb.get_pixel(x, y), DEFAULT_TEXT_COLOR);
+        }
+        // Scroll steps redraw over it too.
+        fb.set_scroll_text("HELLO WORLD, SCROLLING", 0);
+        fb.scroll_by(5);
+        assert_eq!(fb.get_pixel(87, 87), [0, 0, 0x909]);
+    }
+
+    #[test]
+    fn clear_scene_goes_back_to_black_at_the_next_redraw() {
+        let mut fb = FrameBuffer::new();
+        fb.push_command(DrawCommand::Fill {
+            color: [0, 0, 0x909],
+        });
+        fb.clear_scene();
+        assert!(fb.scene().is_empty());
+        assert_eq!(fb.get_pixel(0, 0), [0, 0, 0x909]);
+        fb.display_text("");
+        assert_eq!(fb.get_pixel(0, 0), [0, 0, 0]);
+        // Immediate drawing isn't kept.
+        DrawCommand::Fill { color: [0x303; 3] }.apply(&mut fb);
+        fb.display_text("");
+        assert_eq!(fb.get_pixel(0, 0), [0, 0, 0]);
+    }
+
+    #[test]
+    fn text_color_defaults_to_white() {
+        let fb = FrameBuffer::new();
+        assert_eq!(fb.text_color(), DEFAULT_TEXT_COLOR);
+    }
+
+    #[test]
+    fn display_text_uses_text_color() {
+        let mut fb = FrameBuffer::new();
+        fb.set_text_color(0xFFFF, 0, 0);
+        fb.display_text("I");
+        let lit = lit_pixels(&fb);
+        assert!(!lit.is_empty());
+        for (x, y) in lit {
+            assert_eq!(fb.get_pixel(x, y), [0xFFFF, 0, 0]);
+        }
+    }
+
+    #[test]
+    fn text_fits_matches_display_text_truncation() {
+        let fb = FrameBuffer::new();
+        assert!(fb.text_fits(&LONG[..line_chars(1)]));
+        assert!(!fb.text_fits(&LONG[..line_chars(1) + 1]));
+    }
+
+    #[test]
+    fn colored_segments_draw_each_span_in_its_color() {
+        let (red, green) = ([0xFFFF, 0, 0], [0, 0xFFFF, 0]);
+        let mut fb = FrameBuffer::new();
+        fb.display_colored_segments(&[("AB", red), ("C", green)]);
+        let lit = lit_pixels(&fb);
+
+        let mut plain = FrameBuffer::new();
+        plain.display_text("ABC");
+        assert_eq!(lit, lit_pixels(&plain));
+
+        // "C" is the third glyph: everything from its cell on is green.
+        let c_start = TEXT_START_X + 2 * (plain.font.width() + 1);
+        for &(x, y) in &lit {
+            let expected = if x >= c_start { green } else { red };
+            assert_eq!(fb.get_pixel(x, y), expected, "({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn char_spacing_zero_puts_glyphs_side_by_side() {
+        let mut fb = FrameBuffer::new();
+        fb.set_char_spacing(0);
+        // "H" lights its first and last columns.
+        fb.display_text("HH");
+        let lit_at = |x| lit_pixels(&fb).iter().any(|&(px, _)| px == x);
+        let end = TEXT_START_X + fb.font.width();
+        assert!(lit_at(end - 1) && lit_at(end));
+        assert!(fb.text_fits("ABCDEFGHIJKLMNOP"));
+        assert_eq!(fb.draw_char_at('A', 0, 0, 1, 1, 1), fb.font.width());
+
+        fb.set_char_spacing(3);
+        fb.display_text("HH");
+        let lit_at = |x| lit_pixels(&fb).iter().any(|&(px, _)| px == x);
+        assert!(!lit_at(end) && lit_at(end + 3));
+    }
+
+    #[test]
+    fn line_spacing_centres_the_lines_as_a_block() {
+        let mut fb = FrameBuffer::new();
+        fb.set_line_spacing(Some(3));
+        fb.display_text_lines(&["H", "H"]);
+        let lit = lit_pixels(&fb);
+        let rows = lit.iter().map(|&(_, y)| y);
+        let top = (MATRIX_HEIGHT - (2 * FONT_HEIGHT + 3)) / 2;
+        assert_eq!(rows.clone().min(), Some(top));
+        assert_eq!(rows.max(), Some(top + 2 * FONT_HEIGHT + 3 - 1));
+        // With no spacing the lines touch.
+        fb.set_line_spacing(Some(0));
+        fb.display_text_lines(&["I"; MAX_TEXT_LINES]);
+        let block = MAX_TEXT_LINES * FONT_HEIGHT;
+        let bottom = lit_pixels(&fb).iter().map(|&(_, y)| y).max();
+        assert_eq!(bottom, Some((MATRIX_HEIGHT - block) / 2 + block - 1));
+    }
+
+    #[test]
+    fn scroll_text_advances_and_display_text_cancels_it() {
+        let mut fb = FrameBuffer::new();
+        fb.set_scroll_text("THIS MESSAGE IS FAR TOO LONG TO FIT", 2);
+        assert!(fb.is_scrolling());
+        // Starts off-screen, so the panel is blank.
+        assert!(lit_pixels(&fb).is_empty());
+        for _ in 0..10 {
+            fb.advance_scroll();
+        }
+        assert!(!lit_pixels(&fb).is_empty());
+
+        fb.display_text("HI");
+        assert!(!fb.is_scrolling());
+    }
+
+    #[test]
+    fn vscroll_lines_rise_from_the_bottom() {
+        let mut fb = FrameBuffer::new();
+        fb.set_vscroll_lines(&["NEWS", "MORE NEWS"], 1);
+        assert!(fb.is_scrolling());
+        assert!(lit_pixels(&fb).is_empty());
+        for _ in 0..4 {
+            fb.advance_scroll();
+        }
+        let lit = lit_pixels(&fb);
+        assert!(!lit.is_empty());
+        assert!(lit.iter().all(|&(_, y)| y >= MATRIX_HEIGHT - 4));
+        // Lines start where `display_text` puts them.
+        assert_eq!(lit.iter().map(|&(x, _)| x).min(), Some(TEXT_START_X));
+
+        fb.set_vscroll_lines(&[], 1);
+        for _ in 0..MATRIX_HEIGHT {
+            fb.advance_scroll();
+            assert!(lit_pixels(&fb).is_empty());
+        }
+    }
+
+    #[cfg(feature = "two-panels")]
+    #[test]
+    fn vertical_scroll_reaches_the_second_panel() {
+        let line = "IIIIIIIIIIIIIIIIIIIIIIIII";
+        let mut fb = FrameBuffer::new();
+        fb.set_text_align(Align::Right);
+        fb.set_vscroll_lines(&[line], 1);
+        fb.advance_scroll();
+        // Wider than a `u128`; each 'I' lights the middle three columns
+        // of its top row.
+        let width = fb.font().text_width(line, DEFAULT_CHAR_SPACING);
+        assert!(width > 128);
+        let start = MATRIX_WIDTH - TEXT_START_X - width;
+        let step = FONT_WIDTH + DEFAULT_CHAR_SPACING;
+        let lit = (0..line.len()).flat_map(|i| (1..=3).map(move |dx| start + i * step + dx));
+        let y = MATRIX_HEIGHT - 1;
+        assert!(lit_pixels(&fb).into_iter().eq(lit.map(|x| (x, y))));
+    }
+
+    #[test]
+    fn zero_speed_scroll_moves_only_by_scroll_by() {
+        let mut fb = FrameBuffer::new();
+        fb.set_scroll_text("MARQUEE", 0);
+        assert!(!fb.advance_scroll());
+        assert!(lit_pixels(&fb).is_empty());
+        assert!(!fb.scroll_by(0));
+        assert!(fb.scroll_by(20));
+        assert!(!lit_pixels(&fb).is_empty());
+
+        // Restarting the scroll puts the new text back off-screen.
+        fb.set_scroll_text("OTHER", 0);
+        assert!(lit_pixels(&fb).is_empty());
+    }
+
+    #[test]
+    fn scroll_gradient_travels_with_the_text() {
+        let mut fb = FrameBuffer::new();
+        fb.set_scroll_gradient(Some(GradientMode::Rainbow));
+        fb.set_scroll_text("MARQUEE", 0);
+        fb.scroll_by(30);
+        let &(x, y) = lit_pixels(&fb).first().unwrap();
+        let color = fb.get_pixel(x, y);
+        assert_ne!(color, fb.text_color());
+        fb.scroll_by(1);
+        assert_eq!(fb.get_pixel(x - 1, y), color);
+        assert_ne!(fb.get_pixel(x + 5, y), color);
+
+        fb.set_scroll_gradient(None);
+        fb.scroll_by(1);
+        let &(x, y) = lit_pixels(&fb).first().unwrap();
+        assert_eq!(fb.get_pixel(x, y), fb.text_color());
+    }
+
+    #[test]
+    fn draw_char_writes_only_set_pixels() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_char('!', 0, 0, 0xFFFF, 0xFFFF, 0xFFFF);
+        // The '!' glyph has at least one set pixel
+        let mut any_set = false;
+        for row in fb.as_pixels().iter() {
+            for px in row.iter() {
+                if *px != [0, 0, 0] {
+                    any_set = true;
+                    break;
+                }
+            }
+        }
+        assert!(any_set, "expected some pixels lit after drawing '!'");
+    }
+
+    /// Physical position of logical pixel `(x, y)` after drawing it in
+    /// `orientation`.
+    #[cfg(not(feature = "two-panels"))]
+    fn physical_position(orientation: Orientation, x: usize, y: usize) -> (usize, usize) {
+        let mut fb = FrameBuffer::new();
+        fb.set_orientation(orientation);
+        fb.set_pixel(x as i32, y as i32, 1, 2, 3);
+        let lit = lit_pixels(&fb);
+        assert_eq!(lit.len(), 1);
+        lit[0]
+    }
+
+    #[test]
+    fn quarter_turns_need_a_square_display() {
+        let mut fb = FrameBuffer::new();
+        fb.set_orientation(Orientation::Rot270);
+        let expected = if SQUARE {
+            Orientation::Rot270
+        } else {
+            Orientation::Normal
+        };
+        assert_eq!(fb.orientation(), expected);
+        fb.set_orientation(Orientation::FlipH);
+        assert_eq!(fb.orientation(), Orientation::FlipH);
+    }
+
+    // Quarter turns only apply to a square display.
+    #[cfg(not(feature = "two-panels"))]
+    #[test]
+    fn corner_pixel_lands_at_transformed_location() {
+        let max = MATRIX_WIDTH - 1;
+        let cases = [
+            (Orientation::Normal, (0, 0)),
+            (Orientation::Rot90, (max, 0)),
+            (Orientation::Rot180, (max, max)),
+            (Orientation::Rot270, (0, max)),
+            (Orientation::FlipH, (max, 0)),
+            (Orientation::FlipV, (0, max)),
+        ];
+        for (orientation, expected) in cases {
+            assert_eq!(
+                physical_position(orientation, 0, 0),
+                expected,
+                "{orientation:?}"
+            );
+        }
+    }
+
+    // Quarter turns only apply to a square display.
+    #[cfg(not(feature = "two-panels"))]
+    #[test]
+    fn rotations_move_off_diagonal_pixel_clockwise() {
+        // Logical top edge, one pixel in from the left corner.
+        assert_eq!(physical_position(Orientation::Rot90, 1, 0), (87, 1));
+        assert_eq!(physical_position(Orientation::Rot180, 1, 0), (86, 87));
+        assert_eq!(physical_position(Orientation::Rot270, 1, 0), (0, 86));
+    }
+
+    // Quarter turns only apply to a square display.
+    #[cfg(not(feature = "two-panels"))]
+    #[test]
+    fn get_pixel_reads_logical_coordinates() {
+        let mut fb = FrameBuffer::new();
+        fb.set_orientation(Orientation::Rot270);
+        fb.set_pixel(5, 9, 1, 2, 3);
+        assert_eq!(fb.get_pixel(5, 9), load_pixel([1, 2, 3]));
+        assert_eq!(fb.as_pixels()[MATRIX_WIDTH - 1 - 5][9], [1, 2, 3]);
+    }
+
+    // Quarter turns only apply to a square display.
+    #[cfg(not(feature = "two-panels"))]
+    #[test]
+    fn rotated_drawing_clips_without_panicking() {
+        let mut fb = FrameBuffer::new();
+        fb.set_orientation(Orientation::Rot90);
+        fb.set_pixel(W, 0, 1, 2, 3);
+        fb.fill_circle(0, 0, 200, 1, 2, 3);
+        fb.display_text("ABCDEFGHIJKLMNOPQRSTUVWXYZ");
+        assert!(!lit_pixels(&fb).is_empty());
+    }
+
+    // Quarter turns only apply to a square display.
+    #[cfg(not(feature = "two-panels"))]
+    #[test]
+    fn rotated_text_is_the_transposed_normal_text() {
+        let mut normal = FrameBuffer::new();
+        normal.display_text("HI");
+        let mut rotated = FrameBuffer::new();
+        rotated.set_orientation(Orientation::Rot90);
+        rotated.display_text("HI");
+        for y in 0..MATRIX_HEIGHT {
+            for x in 0..MATRIX_WIDTH {
+                assert_eq!(rotated.get_pixel(x, y), normal.get_pixel(x, y));
+            }
+        }
+        assert_ne!(rotated.as_pixels(), normal.as_pixels());
+    }
+
+    #[test]
+    fn draw_bitmap_copies_block_at_offset() {
+        let mut fb = FrameBuffer::new();
+        let block = [
+            [0x101, 0, 0],
+            [0x202, 0, 0],
+            [0x303, 0, 0],
+            [0x404, 0, 0],
+            [0x505, 0, 0],
+            [0x606, 0, 0],
+        ];
+        fb.draw_bitmap(10, 20, 3, 2, &block);
+        assert_eq!(fb.get_pixel(10, 20), [0x101, 0, 0]);
+        assert_eq!(fb.get_pixel(12, 20), [0x303, 0, 0]);
+        assert_eq!(fb.get_pixel(10, 21), [0x404, 0, 0]);
+        assert_eq!(fb.get_pixel(12, 21), [0x606, 0, 0]);
+        assert_eq!(lit_pixels(&fb).len(), block.len());
+    }
+
+    #[test]
+    fn draw_bitmap_clips_at_edges() {
+        let mut fb = FrameBuffer::new();
+        let block = [[0x707; 3]; 16];
+        fb.draw_bitmap(W - 2, H - 1, 4, 4, &block);
+        assert_eq!(
+            lit_pixels(&fb),
+            [
+                (MATRIX_WIDTH - 2, MATRIX_HEIGHT - 1),
+                (MATRIX_WIDTH - 1, MATRIX_HEIGHT - 1)
+            ]
+        );
+        fb.draw_bitmap(i32::MAX, 0, 4, 4, &block);
+        assert_eq!(lit_pixels(&fb).len(), 2);
+        // Hanging off the top-left corner, only the last pixel shows.
+        fb.draw_bitmap(-3, -3, 4, 4, &block);
+        assert_eq!(lit_pixels(&fb).len(), 3);
+        assert_eq!(fb.get_pixel(0, 0), [0x707; 3]);
+    }
+
+    #[test]
+    fn draw_bitmap_full_frame() {
+        let mut fb = FrameBuffer::new();
+        let frame = [[0x909, 0x808, 0x707]; MATRIX_WIDTH * MATRIX_HEIGHT];
+        fb.draw_bitmap(0, 0, MATRIX_WIDTH, MATRIX_HEIGHT, &frame);
+        assert!(
+            fb.as_pixels()
+                .iter()
+                .flatten()
+                .all(|px| *px == store_pixel([0x909, 0x808, 0x707]))
+        );
+    }
+
+    #[test]
+    fn sprite_leaves_transparent_pixels_untouched() {
+        let key = [0, 0xFFFF, 0];
+        let pixels = [[0x101; 3], [0x202; 3], [0x303; 3], key];
+        let sprite = Sprite::new(2, 2, &pixels).with_transparent(key);
+        let mut fb = FrameBuffer::new();
+        fb.fill(0x505, 0x505, 0x505);
+        fb.draw_sprite(&sprite, 40, 30);
+        assert_eq!(fb.get_pixel(40, 30), [0x101; 3]);
+        assert_eq!(fb.get_pixel(41, 30), [0x202; 3]);
+        assert_eq!(fb.get_pixel(40, 31), [0x303; 3]);
+        assert_eq!(fb.get_pixel(41, 31), [0x505; 3]);
+
+        fb.clear();
+        fb.draw_sprite(&sprite, W - 1, H - 1);
+        assert_eq!(lit_pixels(&fb), [(MATRIX_WIDTH - 1, MATRIX_HEIGHT - 1)]);
+    }
+
+    #[test]
+    fn single_line_matches_display_text() {
+        let mut single = FrameBuffer::new();
+        single.display_text("HI");
+        let mut lines = FrameBuffer::new();
+        lines.display_text_lines(&["HI"]);
+        assert_eq!(single.as_pixels(), lines.as_pixels());
+    }
+
+    #[test]
+    fn lines_are_spread_evenly() {
+        let mut fb = FrameBuffer::new();
+        // 'I' lights every row of its glyph.
+        fb.display_text_lines(&["I", "I", "I"]);
+        let lit = lit_pixels(&fb);
+        let rows: heapless::Vec<usize, MATRIX_HEIGHT> = lit
+            .chunk_by(|a, b| a.1 == b.1)
+            .map(|row| row[0].1)
+            .collect();
+        // (88 - 3 * 7) / 4 = 16 blank rows above, between and below.
+        let expected: heapless::Vec<usize, 21> =
+            [16, 39, 62].iter().flat_map(|&y| y..y + 7).collect();
+        assert_eq!(rows, expected);
+    }
+
+    #[test]
+    fn each_line_truncates_on_its_own() {
+        let mut fb = FrameBuffer::new();
+        fb.display_text_lines(&[LONG, "A"]);
+        let mut truncated = FrameBuffer::new();
+        truncated.display_text_lines(&[&LONG[..line_chars(1)], "A"]);
+        assert_eq!(fb.as_pixels(), truncated.as_pixels());
+    }
+
+    #[test]
+    fn extra_lines_are_dropped() {
+        let many = ["I"; MAX_TEXT_LINES + 3];
+        let mut fb = FrameBuffer::new();
+        fb.display_text_lines(&many);
+        let mut capped = FrameBuffer::new();
+        capped.display_text_lines(&many[..MAX_TEXT_LINES]);
+        assert_eq!(fb.as_pixels(), capped.as_pixels());
+        let rows = lit_pixels(&fb).chunk_by(|a, b| a.1 == b.1).count();
+        assert_eq!(rows, MAX_TEXT_LINES * 7);
+    }
+
+    #[test]
+    fn words_wrap_onto_two_lines() {
+        let mut fb = FrameBuffer::new();
+        let word = &LONG[..line_chars(1)];
+        let mut text = heapless::String::<64>::new();
+        text.push_str("HELLO ").unwrap();
+        text.push_str(word).unwrap();
+        fb.display_text_wrapped(&text);
+        let mut lines = FrameBuffer::new();
+        lines.display_text_lines(&["HELLO", word]);
+        assert_eq!(fb.as_pixels(), lines.as_pixels());
+        assert!(!fb.is_scrolling());
+    }
+
+    #[test]
+    fn wrapping_breaks_long_words_and_drops_extra_lines() {
+        let fb = FrameBuffer::new();
+        let (first, rest) = LONG.split_at(line_chars(1));
+        let mut second = heapless::String::<16>::new();
+        second.push_str(&rest[..4]).unwrap();
+        second.push_str("  STU").unwrap();
+        let mut text = heapless::String::<64>::new();
+        text.push_str(first).unwrap();
+        text.push_str(&second).unwrap();
+        assert_eq!(fb.wrap_text(&text), [first, &second]);
+        assert_eq!(fb.wrap_text(" A\n\nB "), ["A", "", "B"]);
+        let many = "I I I I I I I I I I I I I I I I I I I I I I I I I I I I I I I I \
+                    I I I I I I I I I I I I I I I I I I I I I I I I I I I I I I I I";
+        let mut fb = FrameBuffer::new();
+        fb.set_text_scale(4);
+        // "I I" per line on one panel, three 28-row lines in 88 rows.
+        assert_eq!(fb.wrap_text(many)[0], &many[..line_chars(4)]);
+        assert_eq!(fb.wrap_text(many).len(), MAX_TEXT_LINES);
+        fb.display_text_wrapped(many);
+        let rows = lit_pixels(&fb).chunk_by(|a, b| a.1 == b.1).count();
+        assert_eq!(rows, 3 * 7 * 4);
+    }
+
+    #[test]
+    fn scale_two_glyph_lights_four_times_the_pixels() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_char('A', 0, 0, 1, 1, 1);
+        let plain = lit_pixels(&fb).len();
+        fb.clear();
+        fb.set_text_scale(2);
+        fb.draw_char('A', 0, 0, 1, 1, 1);
+        assert_eq!(lit_pixels(&fb).len(), 4 * plain);
+    }
+
+    #[test]
+    fn text_scale_is_clamped() {
+        let mut fb = FrameBuffer::new();
+        fb.set_text_scale(0);
+        assert_eq!(fb.text_scale(), 1);
+        fb.set_text_scale(100);
+        assert_eq!(fb.text_scale(), MAX_TEXT_SCALE);
+    }
+
+    #[test]
+    fn multibyte_characters_take_one_cell_each() {
+        let mut latin1 = FrameBuffer::new();
+        latin1.display_text("\u{c4}\u{e4}\u{d6}");
+        let mut glyphs = FrameBuffer::new();
+        for (i, ch) in ['\u{c4}', '\u{c4}', '\u{d6}'].into_iter().enumerate() {
+            glyphs.draw_char(
+                ch,
+                (TEXT_START_X + i * 6) as i32,
+                40,
+                0xFFFF,
+                0xFFFF,
+                0xFFFF,
+            );
+        }
+        assert_eq!(latin1.as_pixels(), glyphs.as_pixels());
+    }
+
+    #[test]
+    fn draw_char_at_keeps_the_frame_and_returns_the_advance() {
+        let mut fb = FrameBuffer::new();
+        fb.set_pixel(87, 87, 1, 2, 3);
+        let x = fb.draw_char_at('H', 0, 0, 0xFFFF, 0, 0);
+        assert_eq!(x, 6);
+        assert_eq!(fb.draw_char_at('I', x as i32, 0, 0, 0xFFFF, 0), 6);
+        assert_eq!(fb.get_pixel(87, 87), load_pixel([1, 2, 3]));
+        // 'H' lights its left column, 'I' its middle one.
+        assert_eq!(fb.get_pixel(0, 3), [0xFFFF, 0, 0]);
+        assert_eq!(fb.get_pixel(8, 3), [0, 0xFFFF, 0]);
+
+        let mut direct = FrameBuffer::new();
+        direct.set_pixel(87, 87, 1, 2, 3);
+        direct.draw_text("H", 0, 0, 0xFFFF, 0, 0);
+        direct.draw_text("I", 6, 0, 0, 0xFFFF, 0);
+        assert_eq!(fb.as_pixels(), direct.as_pixels());
+
+        // Scaled, and off-panel: nothing drawn but the advance still holds.
+        fb.set_text_scale(3);
+        assert_eq!(fb.draw_char_at('\u{c4}', 200, 0, DIM, DIM, DIM), 18);
+    }
+
+    /// A 2x40 uploaded font whose only glyph, `' '`, is a full block.
+    fn tall_font() -> Font {
+        let mut data = [0xFFu8; 4 + 40];
+        data[..4].copy_from_slice(&[2, 40, 1, 0]);
+        Font::from_bytes(&data).unwrap()
+    }
+
+    #[test]
+    fn uploaded_font_draws_its_glyphs_and_falls_back() {
+        let mut fb = FrameBuffer::new();
+        fb.set_font(tall_font());
+        fb.draw_char(' ', 3, 1, DIM, DIM, DIM);
+        assert_eq!(lit_pixels(&fb).len(), 2 * 40);
+        assert_eq!(fb.get_pixel(4, 40), [DIM; 3]);
+        assert_eq!(fb.get_pixel(5, 40), [0, 0, 0]);
+
+        // 'I' isn't in the font: the built-in glyph is clipped to 2 wide.
+        fb.clear();
+        fb.draw_char('I', 0, 0, DIM, DIM, DIM);
+        let mut builtin = FrameBuffer::new();
+        builtin.draw_char('I', 0, 0, DIM, DIM, DIM);
+        let clipped = lit_pixels(&builtin).iter().filter(|&&(x, _)| x < 2).count();
+        assert_eq!(lit_pixels(&fb).len(), clipped);
+    }
+
+    #[test]
+    fn uploaded_font_limits_the_text_scale() {
+        let mut fb = FrameBuffer::new();
+        fb.set_text_scale(5);
+        fb.set_font(tall_font());
+        assert_eq!(fb.text_scale(), 2);
+        fb.set_text_scale(100);
+        assert_eq!(fb.text_scale(), 2);
+        fb.set_font(Font::new());
+        fb.set_text_scale(100);
+        assert_eq!(fb.text_scale(), MAX_TEXT_SCALE);
+    }
+
+    #[test]
+    fn scaled_text_is_centred_and_truncated() {
+        let mut fb = FrameBuffer::new();
+        fb.set_text_scale(2);
+        let fit = line_chars(2);
+        assert!(fb.text_fits(&LONG[..fit]));
+        assert!(!fb.text_fits(&LONG[..fit + 1]));
+        let many = "IIIIIIIIIIIIIIIIIIII";
+        fb.display_text(many);
+        let lit = lit_pixels(&fb);
+        // 14 rows tall, centred: (88 - 14) / 2 = 37.
+        assert_eq!(lit.first().map(|&(_, y)| y), Some(37));
+        assert_eq!(lit.last().map(|&(_, y)| y), Some(50));
+        // Seven 10-pixel glyphs with 2-pixel gaps from x = 4 on one
+        // panel; the rest is dropped.
+        let mut fitting = FrameBuffer::new();
+        fitting.set_text_scale(2);
+        fitting.display_text(&many[..fit]);
+        assert_eq!(fb.as_pixels(), fitting.as_pixels());
+    }
+
+    #[test]
+    fn scaled_scroll_uses_scaled_height() {
+        let mut fb = FrameBuffer::new();
+        fb.set_text_scale(3);
+        fb.set_scroll_text("IIIIIIIIIIIIIIIIIIIIIIIIIIIIIIII", 5);
+        for _ in 0..20 {
+            fb.advance_scroll();
+        }
+        let rows = lit_pixels(&fb).chunk_by(|a, b| a.1 == b.1).count();
+        assert_eq!(rows, 7 * 3);
+    }
+
+    /// First and last lit column.
+    #[test]
+    fn left_align_is_default() {
+        let fb = FrameBuffer::new();
+        assert_eq!(fb.text_align(), Align::Left);
+    }
+
+    #[test]
+    fn centered_text_has_equal_margins() {
+        let mut fb = FrameBuffer::new();
+        fb.set_text_align(Align::Center);
+        // "HH" is 11 px wide and both glyphs light their edge columns.
+        fb.display_text("HH");
+        let lit = lit_pixels(&fb);
+        let columns = lit.iter().map(|&(x, _)| x);
+        let left = (MATRIX_WIDTH - 11) / 2;
+        assert_eq!(
+            (columns.clone().min(), columns.max()),
+            (Some(left), Some(left + 10))
+        );
+    }
+
+    #[test]
+    fn right_aligned_text_ends_at_right_margin() {
+        let mut fb = FrameBuffer::new();
+        fb.set_text_align(Align::Right);
+        fb.display_text("HH");
+        let lit = lit_pixels(&fb);
+        let columns = lit.iter().map(|&(x, _)| x);
+        assert_eq!(
+            (columns.clone().min(), columns.max()),
+            (Some(MATRIX_WIDTH - 4 - 11), Some(MATRIX_WIDTH - 4 - 1))
+        );
+    }
+
+    #[test]
+    fn alignment_uses_scaled_width() {
+        let mut fb = FrameBuffer::new();
+        fb.set_text_scale(2);
+        fb.set_text_align(Align::Right);
+        fb.display_text("HH");
+        let lit = lit_pixels(&fb);
+        let columns = lit.iter().map(|&(x, _)| x);
+        assert_eq!(
+            (columns.clone().min(), columns.max()),
+            (Some(MATRIX_WIDTH - 4 - 22), Some(MATRIX_WIDTH - 4 - 1))
+        );
+        fb.set_text_align(Align::Center);
+        fb.display_text("HH");
+        let lit = lit_pixels(&fb);
+        let columns = lit.iter().map(|&(x, _)| x);
+        let left = (MATRIX_WIDTH - 22) / 2;
+        assert_eq!(
+            (columns.clone().min(), columns.max()),
+            (Some(left), Some(left + 21))
+        );
+    }
+
+    #[test]
+    fn too_wide_text_truncates_like_left_aligned() {
+        let mut left = FrameBuffer::new();
+        left.display_text(LONG);
+        for align in [Align::Center, Align::Right] {
+            let mut fb = FrameBuffer::new();
+            fb.set_text_align(align);
+            fb.display_text(LONG);
+            assert_eq!(fb.as_pixels(), left.as_pixels());
+        }
     }
 }
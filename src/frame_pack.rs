@@ -0,0 +1,281 @@
+//! Looping animations uploaded to `/anim`, as a "frame pack".
+//!
+//! GIF would need an LZW decoder and a palette per frame; a frame pack is
+//! simply the frames the panel shows, already scaled to 88x88:
+//!
+//! | Bytes             | Content                                        |
+//! |-------------------|------------------------------------------------|
+//! | 4                 | `LEDA`                                         |
+//! | 2                 | Frame 1 delay, milliseconds, little-endian     |
+//! | [`FRAME_LEN`]     | Frame 1 pixels, RGB565 as for `POST /image`    |
+//! | ...               | The same for each further frame                |
+//!
+//! The frame count follows from the length, up to [`MAX_PACK_FRAMES`].
+//! Frames are kept in RAM, [`FRAME_LEN`] bytes each, so the pack is
+//! small: [`FramePackDecoder`] stores them as they arrive and
+//! [`FramePack::frame_at`] picks the one to show.
+
+use crate::bmp::Image;
+use crate::{MATRIX_HEIGHT, MATRIX_WIDTH};
+
+/// First four bytes of every frame pack.
+pub const FRAME_PACK_MAGIC: &[u8; 4] = b"LEDA";
+
+/// Most frames a pack may hold: about 60 KB of RAM.
+pub const MAX_PACK_FRAMES: usize = 4;
+
+/// Bytes of one frame's pixels.
+pub const FRAME_LEN: usize = MATRIX_WIDTH * MATRIX_HEIGHT * 2;
+
+/// Bytes per frame in the pack: its delay, then its pixels.
+const RECORD_LEN: usize = 2 + FRAME_LEN;
+
+/// Shortest frame delay, in milliseconds. Shorter ones (including 0)
+/// are stretched to it: the panel can't refresh faster anyway.
+pub const MIN_FRAME_DELAY_MS: u16 = 20;
+
+/// Why a frame pack was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramePackError {
+    /// The pack doesn't start with [`FRAME_PACK_MAGIC`].
+    NotPack,
+    /// The pack ends before its last frame.
+    Truncated,
+}
+
+impl FramePackError {
+    /// What to tell the uploader.
+    pub fn message(self) -> &'static str {
+        match self {
+            Self::NotPack => "Not a frame pack",
+            Self::Truncated => "Frame pack ends early",
+        }
+    }
+}
+
+/// Frames in a `len`-byte pack: `None` unless `len` is the magic plus
+/// one to [`MAX_PACK_FRAMES`] whole frames.
+pub fn frame_count(len: usize) -> Option<usize> {
+    let records = len.checked_sub(FRAME_PACK_MAGIC.len())?;
+    let count = records / RECORD_LEN;
+    (records % RECORD_LEN == 0 && (1..=MAX_PACK_FRAMES).contains(&count)).then_some(count)
+}
+
+/// The frames of an uploaded pack and how long each one shows.
+pub struct FramePack {
+    frames: [Image; MAX_PACK_FRAMES],
+    delays_ms: [u16; MAX_PACK_FRAMES],
+    len: usize,
+}
+
+impl Default for FramePack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FramePack {
+    /// An empty pack: nothing to show.
+    pub const fn new() -> Self {
+        Self {
+            frames: [[0; MATRIX_WIDTH * MATRIX_HEIGHT]; MAX_PACK_FRAMES],
+            delays_ms: [0; MAX_PACK_FRAMES],
+            len: 0,
+        }
+    }
+
+    /// Forget every frame, e.g. after a failed upload.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Number of frames.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Frame `index`, as RGB565 rows.
+    pub fn frame(&self, index: usize) -> Option<&Image> {
+        self.frames[..self.len].get(index)
+    }
+
+    /// How long frame `index` shows, at least [`MIN_FRAME_DELAY_MS`].
+    pub fn delay_ms(&self, index: usize) -> u16 {
+        self.delays_ms[index].max(MIN_FRAME_DELAY_MS)
+    }
+
+    /// The frame to show `elapsed_ms` after playback started, looping
+    /// forever. `None` for an empty pack.
+    pub fn frame_at(&self, elapsed_ms: u64) -> Option<usize> {
+        let delays = (0..self.len).map(|i| u64::from(self.delay_ms(i)));
+        let total: u64 = delays.clone().sum();
+        if total == 0 {
+            return None;
+        }
+        let mut t = elapsed_ms % total;
+        for (index, delay) in delays.enumerate() {
+            if t < delay {
+                return Some(index);
+            }
+            t -= delay;
+        }
+        None
+    }
+}
+
+/// Decodes a frame pack fed to it in pieces, straight into a
+/// [`FramePack`].
+pub struct FramePackDecoder {
+    /// Bytes of the pack fed so far.
+    pos: usize,
+    /// The first byte of a little-endian value still waiting for the
+    /// second.
+    low: u8,
+}
+
+impl Default for FramePackDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FramePackDecoder {
+    pub const fn new() -> Self {
+        Self { pos: 0, low: 0 }
+    }
+
+    /// Decode the next `bytes` of the pack into `pack`. The pack is
+    /// emptied first, and each frame counts once its last byte is in.
+    pub fn feed(&mut self, bytes: &[u8], pack: &mut FramePack) -> Result<(), FramePackError> {
+        for &byte in bytes {
+            self.take(byte, pack)?;
+        }
+        Ok(())
+    }
+
+    /// Check every frame arrived, after the last [`Self::feed`]. Returns
+    /// the frame count.
+    pub fn finish(&self, pack: &FramePack) -> Result<usize, FramePackError> {
+        match self.pos.checked_sub(FRAME_PACK_MAGIC.len()) {
+            None => Err(FramePackError::NotPack),
+            Some(records) if records % RECORD_LEN != 0 || pack.is_empty() => {
+                Err(FramePackError::Truncated)
+            }
+            Some(_) => Ok(pack.len),
+        }
+    }
+
+    fn take(&mut self, byte: u8, pack: &mut FramePack) -> Result<(), FramePackError> {
+        let pos = self.pos;
+        self.pos += 1;
+        if pos < FRAME_PACK_MAGIC.len() {
+            if byte != FRAME_PACK_MAGIC[pos] {
+                return Err(FramePackError::NotPack);
+            }
+            pack.len = 0;
+            return Ok(());
+        }
+        let offset = pos - FRAME_PACK_MAGIC.len();
+        let (index, at) = (offset / RECORD_LEN, offset % RECORD_LEN);
+        if index >= MAX_PACK_FRAMES {
+            // Past the largest pack; `frame_count` keeps these out.
+            return Ok(());
+        }
+        if at % 2 == 0 {
+            self.low = byte;
+            return Ok(());
+        }
+        let value = u16::from_le_bytes([self.low, byte]);
+        if at == 1 {
+            pack.delays_ms[index] = value;
+        } else {
+            pack.frames[index][(at - 2) / 2] = value;
+            if at == RECORD_LEN - 1 {
+                pack.len = index + 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pack of `delays.len()` frames, frame `i` filled with `i + 1`.
+    fn pack_bytes(delays: &[u16]) -> heapless::Vec<u8, { 4 + MAX_PACK_FRAMES * RECORD_LEN }> {
+        let mut out = heapless::Vec::new();
+        out.extend_from_slice(FRAME_PACK_MAGIC).unwrap();
+        for (i, delay) in delays.iter().enumerate() {
+            out.extend_from_slice(&delay.to_le_bytes()).unwrap();
+            for _ in 0..FRAME_LEN / 2 {
+                out.extend_from_slice(&(i as u16 + 1).to_le_bytes())
+                    .unwrap();
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn frame_count_needs_whole_frames() {
+        assert_eq!(frame_count(4 + RECORD_LEN), Some(1));
+        assert_eq!(
+            frame_count(4 + MAX_PACK_FRAMES * RECORD_LEN),
+            Some(MAX_PACK_FRAMES)
+        );
+        assert_eq!(frame_count(4), None);
+        assert_eq!(frame_count(4 + RECORD_LEN + 1), None);
+        assert_eq!(frame_count(4 + (MAX_PACK_FRAMES + 1) * RECORD_LEN), None);
+    }
+
+    #[test]
+    fn decodes_a_pack_fed_in_pieces() {
+        let bytes = pack_bytes(&[100, 250, 0]);
+        let mut pack = FramePack::new();
+        let mut decoder = FramePackDecoder::new();
+        // Odd-sized pieces split the little-endian values.
+        for piece in bytes.chunks(777) {
+            decoder.feed(piece, &mut pack).unwrap();
+        }
+        assert_eq!(decoder.finish(&pack), Ok(3));
+        assert_eq!(pack.frame(1).unwrap()[FRAME_LEN / 2 - 1], 2);
+        assert_eq!(pack.delay_ms(1), 250);
+        assert_eq!(pack.delay_ms(2), MIN_FRAME_DELAY_MS);
+        assert!(pack.frame(3).is_none());
+    }
+
+    #[test]
+    fn rejects_bad_and_short_packs() {
+        let mut pack = FramePack::new();
+        let mut decoder = FramePackDecoder::new();
+        assert_eq!(
+            decoder.feed(b"GIF89a", &mut pack),
+            Err(FramePackError::NotPack)
+        );
+
+        let bytes = pack_bytes(&[100, 100]);
+        let mut decoder = FramePackDecoder::new();
+        decoder.feed(&bytes[..bytes.len() - 1], &mut pack).unwrap();
+        assert_eq!(decoder.finish(&pack), Err(FramePackError::Truncated));
+        assert_eq!(pack.len(), 1);
+    }
+
+    #[test]
+    fn frames_loop_by_their_delays() {
+        let mut pack = FramePack::new();
+        assert_eq!(pack.frame_at(0), None);
+        FramePackDecoder::new()
+            .feed(&pack_bytes(&[100, 50]), &mut pack)
+            .unwrap();
+        assert_eq!(pack.frame_at(0), Some(0));
+        assert_eq!(pack.frame_at(99), Some(0));
+        assert_eq!(pack.frame_at(100), Some(1));
+        assert_eq!(pack.frame_at(149), Some(1));
+        assert_eq!(pack.frame_at(150), Some(0));
+        assert_eq!(pack.frame_at(1_000_170), Some(1));
+    }
+}
@@ -0,0 +1,392 @@
+//! Games played on the panel: Snake, for `/snake`.
+//!
+//! A game is an [`Animation`]: the refresh loop steps it every frame like
+//! an effect, and it moves at its own fixed tick rate however fast the
+//! frames come. Input arrives in between, through [`Snake::steer`], from
+//! `/input?dir=` or a `/ws` text message.
+
+use core::fmt::Write;
+
+use crate::effects::{Animation, Rng};
+use crate::frame_buffer::{FrameBuffer, Pixel};
+use crate::{MATRIX_HEIGHT, MATRIX_WIDTH};
+
+/// Pixels along each side of a Snake cell.
+pub const SNAKE_CELL: usize = 4;
+
+/// Snake board size, in cells.
+pub const SNAKE_WIDTH: usize = MATRIX_WIDTH / SNAKE_CELL;
+pub const SNAKE_HEIGHT: usize = MATRIX_HEIGHT / SNAKE_CELL;
+
+/// Cells the snake moves per second.
+pub const SNAKE_STEPS_PER_SEC: u64 = 8;
+
+/// Most moves caught up on in one frame after a stall, so the snake
+/// never jumps far past a turn.
+const SNAKE_MAX_STEPS_PER_FRAME: u64 = 2;
+
+/// Length of a new snake.
+const SNAKE_START_LEN: usize = 3;
+
+/// Turns kept until the snake gets to make them, so a quick double turn
+/// isn't lost between two moves.
+const MAX_QUEUED_TURNS: usize = 2;
+
+/// How long the panel flashes after a crash, before the score shows.
+/// Input is ignored until then.
+pub const GAME_OVER_FLASH_MS: u64 = 1_500;
+
+/// How long each flash, and each gap between them, lasts.
+const FLASH_MS: u64 = 250;
+
+const HEAD_COLOR: Pixel = [0x8000, 0xFFFF, 0x8000];
+const BODY_COLOR: Pixel = [0, 0xA000, 0];
+const FOOD_COLOR: Pixel = [0xFFFF, 0x2000, 0];
+const CRASH_COLOR: Pixel = [0xFFFF, 0, 0];
+
+/// Which way to steer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    /// Direction for an `/input?dir=` name: `up`, `down`, `left` or
+    /// `right`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "up" => Some(Self::Up),
+            "down" => Some(Self::Down),
+            "left" => Some(Self::Left),
+            "right" => Some(Self::Right),
+            _ => None,
+        }
+    }
+
+    fn opposite(self) -> Self {
+        match self {
+            Self::Up => Self::Down,
+            Self::Down => Self::Up,
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+        }
+    }
+
+    /// One cell's move as `(dx, dy)`.
+    fn offset(self) -> (isize, isize) {
+        match self {
+            Self::Up => (0, -1),
+            Self::Down => (0, 1),
+            Self::Left => (-1, 0),
+            Self::Right => (1, 0),
+        }
+    }
+}
+
+/// What `/snake`, `/input` and `/ws` text messages do to the game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameInput {
+    /// Start a new game.
+    NewGame,
+    /// [`Snake::steer`].
+    Steer(Direction),
+}
+
+/// A board cell, `(x, y)`.
+type Cell = (u8, u8);
+
+/// A game of Snake: steer the snake to the food, growing a cell with
+/// each bite, without running into the edge or itself.
+///
+/// After a crash the panel flashes for [`GAME_OVER_FLASH_MS`], then
+/// shows the score until the next [`Self::steer`] starts a new game.
+pub struct Snake {
+    /// Head first.
+    body: heapless::Deque<Cell, { SNAKE_WIDTH * SNAKE_HEIGHT }>,
+    heading: Direction,
+    turns: heapless::Deque<Direction, MAX_QUEUED_TURNS>,
+    /// `None` once the snake fills the board.
+    food: Option<Cell>,
+    score: u32,
+    /// When the game started; `None` until the first step.
+    started_ms: Option<u64>,
+    /// Moves made since then.
+    steps: u64,
+    /// When the snake crashed; `None` while playing.
+    over_ms: Option<u64>,
+    /// Time of the last step.
+    now_ms: u64,
+    /// Set by a steer once the score shows: start over.
+    restart: bool,
+    rng: Rng,
+}
+
+impl Snake {
+    /// A new game. `seed` varies where the food appears.
+    pub fn new(seed: u32) -> Self {
+        let mut snake = Self {
+            body: heapless::Deque::new(),
+            heading: Direction::Right,
+            turns: heapless::Deque::new(),
+            food: None,
+            score: 0,
+            started_ms: None,
+            steps: 0,
+            over_ms: None,
+            now_ms: 0,
+            restart: false,
+            rng: Rng::new(seed),
+        };
+        snake.reset();
+        snake
+    }
+
+    /// Food eaten so far.
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+
+    /// True once the snake has crashed.
+    pub fn is_over(&self) -> bool {
+        self.over_ms.is_some()
+    }
+
+    /// Turn towards `direction` at the next move. Turning back on
+    /// itself, or the way it already goes, does nothing. Once a crashed
+    /// game shows its score, any direction starts a new one.
+    pub fn steer(&mut self, direction: Direction) {
+        if let Some(over) = self.over_ms {
+            self.restart |= self.now_ms >= over + GAME_OVER_FLASH_MS;
+            return;
+        }
+        let last = self.turns.back().copied().unwrap_or(self.heading);
+        if direction != last && direction != last.opposite() {
+            let _ = self.turns.push_back(direction);
+        }
+    }
+
+    /// A snake in the middle of the board heading right, and new food.
+    fn reset(&mut self) {
+        self.body.clear();
+        let y = (SNAKE_HEIGHT / 2) as u8;
+        for i in 0..SNAKE_START_LEN {
+            let _ = self.body.push_back(((SNAKE_WIDTH / 2 - i) as u8, y));
+        }
+        self.heading = Direction::Right;
+        self.turns.clear();
+        self.score = 0;
+        self.started_ms = None;
+        self.steps = 0;
+        self.over_ms = None;
+        self.restart = false;
+        self.place_food();
+    }
+
+    /// Put the food on a random free cell.
+    fn place_food(&mut self) {
+        let free = SNAKE_WIDTH * SNAKE_HEIGHT - self.body.len();
+        if free == 0 {
+            self.food = None;
+            return;
+        }
+        let pick = self.rng.next() as usize % free;
+        let body = &self.body;
+        self.food = (0..SNAKE_HEIGHT)
+            .flat_map(|y| (0..SNAKE_WIDTH).map(move |x| (x as u8, y as u8)))
+            .filter(|cell| !body.iter().any(|c| c == cell))
+            .nth(pick);
+    }
+
+    /// Move one cell. `false` if the snake crashed instead.
+    fn advance(&mut self) -> bool {
+        if let Some(turn) = self.turns.pop_front() {
+            self.heading = turn;
+        }
+        let Some(&(x, y)) = self.body.front() else {
+            return false;
+        };
+        let (dx, dy) = self.heading.offset();
+        let (x, y) = (x as isize + dx, y as isize + dy);
+        if !(0..SNAKE_WIDTH as isize).contains(&x) || !(0..SNAKE_HEIGHT as isize).contains(&y) {
+            return false;
+        }
+        let next = (x as u8, y as u8);
+        let grows = self.food == Some(next);
+        // The tail moves out of the way, unless the snake grows.
+        let kept = self.body.len() - usize::from(!grows);
+        if self.body.iter().take(kept).any(|&cell| cell == next) {
+            return false;
+        }
+        if !grows {
+            self.body.pop_back();
+        }
+        let _ = self.body.push_front(next);
+        if grows {
+            self.score += 1;
+            self.place_food();
+        }
+        true
+    }
+
+    fn draw_cell(fb: &mut FrameBuffer, (x, y): Cell, [r, g, b]: Pixel) {
+        let (x, y) = (
+            i32::from(x) * SNAKE_CELL as i32,
+            i32::from(y) * SNAKE_CELL as i32,
+        );
+        fb.fill_rect(x, y, SNAKE_CELL, SNAKE_CELL, r, g, b);
+    }
+
+    fn draw_board(&self, fb: &mut FrameBuffer) {
+        fb.clear();
+        if let Some(food) = self.food {
+            Self::draw_cell(fb, food, FOOD_COLOR);
+        }
+        for (i, &cell) in self.body.iter().enumerate() {
+            Self::draw_cell(fb, cell, if i == 0 { HEAD_COLOR } else { BODY_COLOR });
+        }
+    }
+
+    /// The crash flash, `since_ms` after it, then the score.
+    fn draw_game_over(&self, fb: &mut FrameBuffer, since_ms: u64) {
+        if since_ms >= GAME_OVER_FLASH_MS {
+            let mut score: heapless::String<16> = heapless::String::new();
+            let _ = write!(score, "SCORE {}", self.score);
+            fb.display_text_lines(&["GAME OVER", &score]);
+        } else if (since_ms / FLASH_MS).is_multiple_of(2) {
+            let [r, g, b] = CRASH_COLOR;
+            fb.fill(r, g, b);
+        } else {
+            self.draw_board(fb);
+        }
+    }
+}
+
+impl Animation for Snake {
+    /// Make the moves due by `t_ms` and paint the board. Cancels any
+    /// active scroll.
+    fn step(&mut self, fb: &mut FrameBuffer, t_ms: u64) {
+        fb.stop_scroll();
+        self.now_ms = t_ms;
+        if self.restart {
+            self.reset();
+        }
+        if let Some(over) = self.over_ms {
+            self.draw_game_over(fb, t_ms - over);
+            return;
+        }
+        let started = *self.started_ms.get_or_insert(t_ms);
+        let target = (t_ms - started) * SNAKE_STEPS_PER_SEC / 1000;
+        let due = (target - self.steps).min(SNAKE_MAX_STEPS_PER_FRAME);
+        self.steps = target;
+        for _ in 0..due {
+            if !self.advance() {
+                self.over_ms = Some(t_ms);
+                self.draw_game_over(fb, 0);
+                return;
+            }
+        }
+        self.draw_board(fb);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame_buffer::{load_pixel, store_pixel};
+
+    /// Time of move `n`, counting from a first step at 0.
+    fn move_ms(n: u64) -> u64 {
+        n * 1000 / SNAKE_STEPS_PER_SEC
+    }
+
+    #[test]
+    fn moves_at_the_tick_rate_and_turns() {
+        let mut fb = FrameBuffer::new();
+        let mut snake = Snake::new(1);
+        snake.food = None;
+        snake.step(&mut fb, 0);
+        let (x, y) = *snake.body.front().unwrap();
+        snake.step(&mut fb, move_ms(1) - 1);
+        assert_eq!(snake.body.front(), Some(&(x, y)));
+        snake.step(&mut fb, move_ms(1));
+        assert_eq!(snake.body.front(), Some(&(x + 1, y)));
+        // Backwards is ignored; down then left both happen.
+        snake.steer(Direction::Left);
+        snake.steer(Direction::Down);
+        snake.steer(Direction::Left);
+        snake.step(&mut fb, move_ms(3));
+        assert_eq!(snake.body.front(), Some(&(x, y + 1)));
+        assert_eq!(snake.body.len(), SNAKE_START_LEN);
+        assert_eq!(
+            fb.get_pixel(
+                usize::from(x) * SNAKE_CELL,
+                (usize::from(y) + 1) * SNAKE_CELL
+            ),
+            load_pixel(store_pixel(HEAD_COLOR))
+        );
+    }
+
+    #[test]
+    fn eating_grows_and_scores() {
+        let mut fb = FrameBuffer::new();
+        let mut snake = Snake::new(7);
+        let (x, y) = *snake.body.front().unwrap();
+        snake.food = Some((x + 1, y));
+        snake.step(&mut fb, 0);
+        snake.step(&mut fb, move_ms(1));
+        assert_eq!(snake.score(), 1);
+        assert_eq!(snake.body.len(), SNAKE_START_LEN + 1);
+        let food = snake.food.unwrap();
+        assert!(!snake.body.iter().any(|&cell| cell == food));
+    }
+
+    #[test]
+    fn hitting_the_edge_ends_the_game_until_the_score_shows() {
+        let mut fb = FrameBuffer::new();
+        let mut snake = Snake::new(3);
+        snake.food = None;
+        let mut t = 0;
+        while !snake.is_over() {
+            t += move_ms(1);
+            snake.step(&mut fb, t);
+        }
+        assert_eq!(snake.body.front().unwrap().0 as usize, SNAKE_WIDTH - 1);
+        assert_eq!(fb.get_pixel(0, 0), CRASH_COLOR);
+        // Too early: still flashing, the steer is ignored.
+        snake.steer(Direction::Up);
+        snake.step(&mut fb, t + FLASH_MS);
+        assert!(snake.is_over());
+        assert_eq!(fb.get_pixel(0, 0), [0, 0, 0]);
+
+        snake.step(&mut fb, t + GAME_OVER_FLASH_MS);
+        snake.steer(Direction::Up);
+        snake.step(&mut fb, t + GAME_OVER_FLASH_MS + 1);
+        assert!(!snake.is_over());
+        assert_eq!(snake.score(), 0);
+        assert_eq!(snake.body.len(), SNAKE_START_LEN);
+    }
+
+    #[test]
+    fn running_into_itself_ends_the_game() {
+        let mut fb = FrameBuffer::new();
+        let mut snake = Snake::new(5);
+        snake.food = None;
+        let (x, y) = *snake.body.front().unwrap();
+        // A five-cell snake curled round onto its own body.
+        for cell in [(x + 1, y), (x + 2, y)] {
+            let _ = snake.body.push_front(cell);
+        }
+        snake.step(&mut fb, 0);
+        for (i, direction) in [Direction::Down, Direction::Left, Direction::Up]
+            .into_iter()
+            .enumerate()
+        {
+            snake.steer(direction);
+            snake.step(&mut fb, move_ms(i as u64 + 1));
+        }
+        assert!(snake.is_over());
+    }
+}
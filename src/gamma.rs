@@ -0,0 +1,162 @@
+//! Output corrections for the 16-bit PWM channels: gamma and brightness.
+//!
+//! LED brightness is linear in PWM duty cycle but perceived brightness is
+//! not, so writing linear values straight to the panel crushes the low
+//! end of every gradient. [`GammaTable`] maps a stored channel value to
+//! the duty cycle that *looks* proportionally bright.
+//!
+//! A full 65536-entry table would cost 128 KB, so the curve is sampled at
+//! 257 points and linearly interpolated in between. The frame buffer keeps
+//! the uncorrected values — correction happens on the way out to the
+//! shift registers, so `set_pixel` callers are unaffected.
+//!
+//! Global brightness ([`scale_chain`]) is applied after gamma, as a plain
+//! multiplier on the duty cycle.
+
+use crate::chain_mapper::CHAIN_LEN;
+
+/// Default gamma exponent (the usual sRGB-ish approximation).
+pub const DEFAULT_GAMMA: f32 = 2.2;
+
+/// Number of interpolation segments in the table.
+const SEGMENTS: usize = 256;
+
+/// Sampled gamma curve: `lut[i]` is the corrected value for input
+/// `i * 65535 / 256`.
+pub struct GammaTable {
+    lut: [u16; SEGMENTS + 1],
+}
+
+impl Default for GammaTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_GAMMA)
+    }
+}
+
+impl GammaTable {
+    /// Build the table for `gamma`. Non-finite or non-positive exponents
+    /// fall back to 1.0 (no correction).
+    pub fn new(gamma: f32) -> Self {
+        let gamma = if gamma.is_finite() && gamma > 0.0 {
+            gamma
+        } else {
+            1.0
+        };
+        let mut lut = [0u16; SEGMENTS + 1];
+        for (i, slot) in lut.iter_mut().enumerate() {
+            let x = i as f32 / SEGMENTS as f32;
+            let y = libm::powf(x, gamma) * u16::MAX as f32;
+            *slot = libm::roundf(y) as u16;
+        }
+        Self { lut }
+    }
+
+    /// Map one stored channel value to its corrected PWM value.
+    #[inline]
+    pub fn correct(&self, value: u16) -> u16 {
+        let pos = value as u32 * SEGMENTS as u32;
+        let seg = (pos / u16::MAX as u32) as usize;
+        let frac = pos % u16::MAX as u32;
+        if seg >= SEGMENTS {
+            return self.lut[SEGMENTS];
+        }
+        let lo = self.lut[seg] as u32;
+        let hi = self.lut[seg + 1] as u32;
+        (lo + (hi - lo) * frac / u16::MAX as u32) as u16
+    }
+
+    /// Correct every channel of one `(scanline, led)` chain vector in place.
+    pub fn correct_chain(&self, data: &mut [[u16; 3]; CHAIN_LEN]) {
+        for px in data.iter_mut() {
+            for channel in px.iter_mut() {
+                *channel = self.correct(*channel);
+            }
+        }
+    }
+}
+
+/// Full brightness — [`scale`] leaves values unchanged.
+pub const MAX_BRIGHTNESS: u8 = u8::MAX;
+
+/// Scale a channel value by `level / 255`, rounding to nearest. The
+/// product fits comfortably in `u32`, so there is no overflow.
+#[inline]
+pub const fn scale(value: u16, level: u8) -> u16 {
+    ((value as u32 * level as u32 + 127) / 255) as u16
+}
+
+/// Scale every channel of one chain vector by `level / 255` in place.
+/// A no-op at [`MAX_BRIGHTNESS`], so the common case costs nothing.
+pub fn scale_chain(data: &mut [[u16; 3]; CHAIN_LEN], level: u8) {
+    if level == MAX_BRIGHTNESS {
+        return;
+    }
+    for px in data.iter_mut() {
+        for channel in px.iter_mut() {
+            *channel = scale(*channel, level);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endpoints_are_preserved() {
+        let table = GammaTable::default();
+        assert_eq!(table.correct(0), 0);
+        assert_eq!(table.correct(u16::MAX), u16::MAX);
+    }
+
+    #[test]
+    fn midpoint_is_dimmer_under_default_gamma() {
+        let table = GammaTable::new(DEFAULT_GAMMA);
+        let corrected = table.correct(0x8000);
+        assert!(corrected < 0x8000);
+        // 0.5^2.2 ≈ 0.2176 → ≈ 14263 of 65535.
+        assert!((14_000..14_600).contains(&corrected), "got {}", corrected);
+    }
+
+    #[test]
+    fn gamma_one_is_identity() {
+        let table = GammaTable::new(1.0);
+        for v in [0u16, 1, 255, 0x1234, 0x8000, 0xFFFE, 0xFFFF] {
+            let out = table.correct(v);
+            assert!(out.abs_diff(v) <= 1, "{} -> {}", v, out);
+        }
+    }
+
+    #[test]
+    fn invalid_gamma_falls_back_to_linear() {
+        let table = GammaTable::new(-3.0);
+        assert!(table.correct(0x8000).abs_diff(0x8000) <= 1);
+    }
+
+    #[test]
+    fn brightness_extremes() {
+        assert_eq!(scale(u16::MAX, MAX_BRIGHTNESS), u16::MAX);
+        assert_eq!(scale(0x1234, MAX_BRIGHTNESS), 0x1234);
+        assert_eq!(scale(u16::MAX, 0), 0);
+    }
+
+    #[test]
+    fn brightness_halves_at_midpoint() {
+        // 128/255 of full scale, rounded.
+        assert_eq!(scale(u16::MAX, 128), 32_896);
+        let mut data = [[u16::MAX; 3]; CHAIN_LEN];
+        scale_chain(&mut data, 128);
+        assert!(data.iter().all(|px| *px == [32_896; 3]));
+    }
+
+    #[test]
+    fn correction_is_monotonic() {
+        let table = GammaTable::default();
+        let mut prev = 0;
+        for v in (0..=u16::MAX).step_by(97) {
+            let out = table.correct(v);
+            assert!(out >= prev);
+            prev = out;
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Colors that run along scrolling text, or across the panel.
+//!
+//! By default a scroll is drawn in the text color. With a
+//! [`GradientMode`] set (see
+//! [`FrameBuffer::set_scroll_gradient`](crate::frame_buffer::FrameBuffer::set_scroll_gradient))
+//! each column gets its own color instead, picked by its position in the
+//! scroll: the panel column plus the scroll offset. So the colors belong
+//! to the letters and travel with them across the panel.
+//!
+//! A [`GradientDir`] says which way a background gradient runs, for
+//! [`FrameBuffer::fill_gradient`](crate::frame_buffer::FrameBuffer::fill_gradient).
+
+use crate::MATRIX_WIDTH;
+use crate::frame_buffer::{Pixel, hsv_to_pixel};
+
+/// Pixels over which [`GradientMode::Rainbow`] goes through every hue,
+/// and [`GradientMode::TwoColor`] from one color to the other and back.
+pub const GRADIENT_PERIOD: usize = MATRIX_WIDTH;
+
+/// How scrolling text is colored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GradientMode {
+    /// One color throughout.
+    Solid(Pixel),
+    /// Every hue at full brightness.
+    Rainbow,
+    /// From the first color to the second and back again, so the
+    /// gradient repeats without a seam.
+    TwoColor(Pixel, Pixel),
+}
+
+impl GradientMode {
+    /// Color at `position` along the scroll.
+    pub fn color_at(&self, position: usize) -> Pixel {
+        let phase = position % GRADIENT_PERIOD;
+        match *self {
+            Self::Solid(color) => color,
+            Self::Rainbow => hsv_to_pixel(phase as f32 * 360.0 / GRADIENT_PERIOD as f32, 1.0, 1.0),
+            Self::TwoColor(from, to) => {
+                // Out in the first half of the period, back in the second.
+                let half = GRADIENT_PERIOD / 2;
+                let step = if phase < half {
+                    phase
+                } else {
+                    GRADIENT_PERIOD - phase
+                };
+                lerp(from, to, step, half)
+            }
+        }
+    }
+}
+
+/// Which way [`FrameBuffer::fill_gradient`](crate::frame_buffer::FrameBuffer::fill_gradient)
+/// runs, from its start color to its end color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GradientDir {
+    /// Left to right.
+    Horizontal,
+    /// Top to bottom.
+    Vertical,
+    /// Top-left corner to bottom-right.
+    Diagonal,
+}
+
+impl GradientDir {
+    /// The direction named `horizontal`, `vertical` or `diagonal`, as
+    /// in `/gradient?dir=`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "horizontal" => Some(Self::Horizontal),
+            "vertical" => Some(Self::Vertical),
+            "diagonal" => Some(Self::Diagonal),
+            _ => None,
+        }
+    }
+}
+
+/// The color `step` steps of `steps` from `from` to `to`, each channel
+/// interpolated over its full 16 bits: `from` at 0, `to` at `steps`.
+pub fn lerp(from: Pixel, to: Pixel, step: usize, steps: usize) -> Pixel {
+    let mut color = from;
+    for (channel, (&a, &b)) in color.iter_mut().zip(from.iter().zip(&to)) {
+        let (a, b) = (i64::from(a), i64::from(b));
+        *channel = (a + (b - a) * step as i64 / steps.max(1) as i64) as u16;
+    }
+    color
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_color_goes_there_and_back() {
+        let gradient = GradientMode::TwoColor([0, 0, 0xFFFF], [0xFFFF, 0, 0]);
+        let half = GRADIENT_PERIOD / 2;
+        assert_eq!(gradient.color_at(0), [0, 0, 0xFFFF]);
+        assert_eq!(gradient.color_at(half), [0xFFFF, 0, 0]);
+        assert_eq!(
+            gradient.color_at(half / 2),
+            gradient.color_at(GRADIENT_PERIOD - half / 2)
+        );
+        assert_eq!(gradient.color_at(GRADIENT_PERIOD), gradient.color_at(0));
+    }
+
+    #[test]
+    fn rainbow_cycles_the_hues() {
+        assert_eq!(GradientMode::Rainbow.color_at(0), [0xFFFF, 0, 0]);
+        let third = GradientMode::Rainbow.color_at(GRADIENT_PERIOD / 3);
+        assert!(third[1] > third[0] && third[1] > third[2], "{third:?}");
+        assert_eq!(
+            GradientMode::Rainbow.color_at(GRADIENT_PERIOD),
+            [0xFFFF, 0, 0]
+        );
+        assert_eq!(GradientMode::Solid([1, 2, 3]).color_at(40), [1, 2, 3]);
+    }
+}
@@ -0,0 +1,127 @@
+//! A scrolling line chart of recent readings, fed by `/graph/push?v=`.
+//!
+//! [`Graph`] keeps the last [`GRAPH_LEN`] samples, one per column, in a
+//! fixed-size ring: each new one goes in on the right and the oldest
+//! drops off the left, so the chart scrolls left as readings arrive.
+//! Samples are normalized: 0 is the bottom row, 1 the top one.
+
+use crate::frame_buffer::FrameBuffer;
+use crate::{MATRIX_HEIGHT, MATRIX_WIDTH};
+
+/// Samples a [`Graph`] keeps: one per column.
+pub const GRAPH_LEN: usize = MATRIX_WIDTH;
+
+/// The most recent samples, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    samples: heapless::Deque<f32, GRAPH_LEN>,
+}
+
+impl Graph {
+    /// An empty graph.
+    pub const fn new() -> Self {
+        Self {
+            samples: heapless::Deque::new(),
+        }
+    }
+
+    /// Add `value` on the right, dropping the oldest sample once
+    /// [`GRAPH_LEN`] are kept. Values outside 0..=1 are clamped; NaN
+    /// counts as 0.
+    pub fn push(&mut self, value: f32) {
+        if self.samples.is_full() {
+            self.samples.pop_front();
+        }
+        let value = if value.is_nan() {
+            0.0
+        } else {
+            value.clamp(0.0, 1.0)
+        };
+        let _ = self.samples.push_back(value);
+    }
+
+    /// Forget every sample.
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Number of samples kept.
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// True before the first sample.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Clear `fb` and draw the samples as a line in its text color, the
+    /// newest in the rightmost column. Cancels any active scroll.
+    pub fn render(&self, fb: &mut FrameBuffer) {
+        fb.stop_scroll();
+        fb.clear();
+        let first_x = GRAPH_LEN - self.samples.len();
+        let points: heapless::Vec<(i32, i32), GRAPH_LEN> = self
+            .samples
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| ((first_x + i) as i32, row(value) as i32))
+            .collect();
+        let [r, g, b] = fb.text_color();
+        fb.draw_polyline(&points, r, g, b);
+    }
+}
+
+/// Row for a sample: 1 at the top, 0 at the bottom.
+fn row(value: f32) -> usize {
+    let top = (MATRIX_HEIGHT - 1) as f32;
+    MATRIX_HEIGHT - 1 - libm::roundf(value * top) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame_buffer::tests::lit_pixels;
+
+    #[test]
+    fn push_keeps_the_latest_clamped() {
+        let mut graph = Graph::new();
+        for i in 0..GRAPH_LEN + 5 {
+            graph.push(i as f32);
+        }
+        assert_eq!(graph.len(), GRAPH_LEN);
+        graph.push(-3.0);
+        graph.push(f32::NAN);
+        assert_eq!(graph.samples.back(), Some(&0.0));
+        assert_eq!(graph.samples.front(), Some(&1.0));
+        graph.clear();
+        assert!(graph.is_empty());
+    }
+
+    #[test]
+    fn newest_sample_is_on_the_right() {
+        let mut graph = Graph::new();
+        graph.push(0.0);
+        graph.push(1.0);
+        let mut fb = FrameBuffer::new();
+        fb.set_text_color(0x909, 0x909, 0x909);
+        graph.render(&mut fb);
+        assert_eq!(
+            fb.get_pixel(MATRIX_WIDTH - 2, MATRIX_HEIGHT - 1),
+            [0x909; 3]
+        );
+        assert_eq!(fb.get_pixel(MATRIX_WIDTH - 1, 0), [0x909; 3]);
+        // The two samples are joined by a line: one pixel per row.
+        assert_eq!(lit_pixels(&fb).len(), MATRIX_HEIGHT);
+    }
+
+    #[test]
+    fn render_clears_the_previous_chart() {
+        let mut graph = Graph::new();
+        graph.push(0.5);
+        let mut fb = FrameBuffer::new();
+        fb.fill(1, 1, 1);
+        graph.render(&mut fb);
+        assert_eq!(lit_pixels(&fb).len(), 1);
+    }
+}
@@ -0,0 +1,97 @@
+//! [`embedded-graphics`] support, built with the `embedded-graphics`
+//! feature.
+//!
+//! [`FrameBuffer`] is a [`DrawTarget`] for [`Rgb565`] colors, so that
+//! crate's fonts, primitives and image loaders draw straight into it,
+//! over whatever is there like [`FrameBuffer::draw_text`]. Each color is
+//! widened as [`FrameBuffer::set_pixel_565`] does, and points off the
+//! panel are dropped.
+//!
+//! [`embedded-graphics`]: https://docs.rs/embedded-graphics
+
+use core::convert::Infallible;
+
+use embedded_graphics_core::pixelcolor::Rgb565;
+use embedded_graphics_core::prelude::*;
+use embedded_graphics_core::primitives::Rectangle;
+
+use crate::frame_buffer::{FrameBuffer, rgb565_to_pixel};
+use crate::{MATRIX_HEIGHT, MATRIX_WIDTH};
+
+impl OriginDimensions for FrameBuffer {
+    fn size(&self) -> Size {
+        Size::new(MATRIX_WIDTH as u32, MATRIX_HEIGHT as u32)
+    }
+}
+
+impl DrawTarget for FrameBuffer {
+    type Color = Rgb565;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            self.set_pixel_565(point.x, point.y, color.into_storage());
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let [r, g, b] = rgb565_to_pixel(color.into_storage());
+        self.fill_rect(
+            area.top_left.x,
+            area.top_left.y,
+            area.size.width as usize,
+            area.size.height as usize,
+            r,
+            g,
+            b,
+        );
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        let [r, g, b] = rgb565_to_pixel(color.into_storage());
+        self.fill(r, g, b);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame_buffer::tests::lit_pixels;
+
+    #[test]
+    fn pixels_are_widened_and_clipped() {
+        let mut fb = FrameBuffer::new();
+        fb.draw_iter([
+            Pixel(Point::new(3, 4), Rgb565::RED),
+            Pixel(Point::new(-1, 0), Rgb565::WHITE),
+            Pixel(Point::new(0, MATRIX_HEIGHT as i32), Rgb565::WHITE),
+        ])
+        .unwrap();
+        assert_eq!(fb.get_pixel(3, 4), [0xFFFF, 0, 0]);
+        assert_eq!(lit_pixels(&fb).len(), 1);
+        assert_eq!(
+            fb.size(),
+            Size::new(MATRIX_WIDTH as u32, MATRIX_HEIGHT as u32)
+        );
+    }
+
+    #[test]
+    fn fill_solid_is_clipped_to_the_panel() {
+        let mut fb = FrameBuffer::new();
+        let area = Rectangle::new(Point::new(-2, MATRIX_HEIGHT as i32 - 2), Size::new(4, 4));
+        fb.fill_solid(&area, Rgb565::BLUE).unwrap();
+        let mut expected = FrameBuffer::new();
+        expected.fill_rect(0, MATRIX_HEIGHT as i32 - 2, 2, 2, 0, 0, 0xFFFF);
+        assert_eq!(fb.as_pixels(), expected.as_pixels());
+
+        // The inherent `FrameBuffer::clear` takes no color.
+        DrawTarget::clear(&mut fb, Rgb565::GREEN).unwrap();
+        assert_eq!(fb.get_pixel(MATRIX_WIDTH - 1, 0), [0, 0xFFFF, 0]);
+    }
+}
@@ -0,0 +1,85 @@
+//! Liveness: reboot when the refresh loop stops.
+//!
+//! The refresh loop pokes a [`HealthMonitor`] once per iteration, which
+//! feeds the TIMG1 watchdog. If the loop hangs — a lock that is never
+//! released, a driver stuck in a busy-wait — the display freezes, and
+//! after [`WATCHDOG_TIMEOUT_MS`] without a poke the watchdog resets the
+//! chip. The next boot logs that it was the watchdog.
+//!
+//! A panic doesn't wait for the watchdog: once `esp-backtrace` has
+//! printed it, [`custom_halt`] disables the watchdog, shows the crash
+//! screen for [`CRASH_SCREEN_MS`] and resets.
+//!
+//! There is no heartbeat LED: the SuperMini's onboard LED is on GPIO8,
+//! which drives the panel's DG1 line, and no other GPIO is free.
+
+use esp_hal::peripherals::TIMG1;
+use esp_hal::rtc_cntl::SocResetReason;
+use esp_hal::time::Duration;
+use esp_hal::timer::timg::{MwdtStage, TimerGroup, Wdt};
+use log::{info, warn};
+
+use crate::led_matrix;
+
+/// How long the refresh loop may go without poking the monitor. A
+/// refresh takes tens of milliseconds; this leaves room for the longest
+/// flash write.
+const WATCHDOG_TIMEOUT_MS: u64 = 5000;
+
+/// How long a panic's crash screen shows before the reset. The watchdog
+/// is disabled first, so this isn't bounded by [`WATCHDOG_TIMEOUT_MS`].
+const CRASH_SCREEN_MS: u64 = 4000;
+
+/// Resets the chip unless [`Self::poke`] is called often enough.
+pub struct HealthMonitor {
+    wdt: Wdt<TIMG1<'static>>,
+}
+
+impl HealthMonitor {
+    /// Start the watchdog. Poke the monitor from now on.
+    pub fn start(timg1: TIMG1<'static>) -> Self {
+        let mut wdt = TimerGroup::new(timg1).wdt;
+        wdt.set_timeout(
+            MwdtStage::Stage0,
+            Duration::from_millis(WATCHDOG_TIMEOUT_MS),
+        );
+        wdt.enable();
+        info!("Watchdog armed ({} ms)", WATCHDOG_TIMEOUT_MS);
+        Self { wdt }
+    }
+
+    /// The refresh loop is still running: restart the countdown.
+    pub fn poke(&mut self) {
+        self.wdt.feed();
+    }
+}
+
+/// Log why the chip last reset, calling out a watchdog reset: the
+/// refresh loop hung before it.
+pub fn log_reset_reason() {
+    match esp_hal::system::reset_reason() {
+        Some(SocResetReason::CoreMwdt1 | SocResetReason::Cpu0Mwdt1) => {
+            warn!("Reset by the watchdog: the refresh loop had stopped");
+        }
+        reason => info!("Reset reason: {:?}", reason),
+    }
+}
+
+/// Where `esp-backtrace` (built with `custom-halt`) ends up after
+/// printing a panic or exception, instead of spinning forever with the
+/// last frame frozen on the panel: show the crash screen (see
+/// [`esp32_led_matrix::crash_screen`]), then reset.
+#[unsafe(no_mangle)]
+fn custom_halt() -> ! {
+    // The last poke may have been most of a timeout ago, and nothing
+    // feeds the watchdog from here on: stop it so it can't reset the
+    // chip partway through the crash screen.
+    // SAFETY: the panicked code never runs again, so the `HealthMonitor`
+    // that owns TIMG1 can't use it meanwhile.
+    TimerGroup::new(unsafe { TIMG1::steal() }).wdt.disable();
+    // No task or interrupt may touch the pins meanwhile.
+    critical_section::with(|_| {
+        led_matrix::show_crash_screen(crate::DCLK_DELAY_NS, CRASH_SCREEN_MS);
+    });
+    esp_hal::system::software_reset()
+}
@@ -5,21 +5,131 @@
 //! [`dispatch`] returns a [`Response`] describing what the HTTP layer
 //! should send back to the client and whether to update the display text.
 
+use core::fmt::Write;
+
+use crate::auth::{self, MAX_AUTH_PASSWORD_LEN};
+use crate::bmp::{BMP_HEADER_LEN, MAX_BMP_LEN};
+use crate::command::DrawCommand;
+use crate::countdown::{MAX_TIMER_SECONDS, TimerCommand};
+use crate::credentials::{Credentials, MAX_PASSWORD_LEN, MAX_SSID_LEN};
+use crate::draw_api::{self, ApiError, MAX_DRAW_OPS};
+use crate::font::MAX_FONT_LEN;
+use crate::frame_buffer::Pixel;
+use crate::frame_pack;
+use crate::games::{Direction, GameInput};
+use crate::gradient::{GradientDir, GradientMode};
+use crate::mdns_packet::{self, MAX_HOSTNAME_LEN};
+use crate::mode::{
+    DEFAULT_EFFECT_SPEED, DEFAULT_MARQUEE_SPEED, DisplayMode, Effect, MAX_EFFECT_SPEED,
+    MAX_MARQUEE_SPEED, TestPattern,
+};
+use crate::notice::{MAX_NOTICE_SECONDS, Notice, TimedNotice};
+use crate::ota_image::{IMAGE_HEADER_LEN, MAX_TOKEN_LEN, OTA_SLOT_LEN};
+use crate::rate_limit::RateLimiter;
+use crate::regions::{Region, RegionUpdate};
+use crate::rich_text::{MAX_SEGMENTS, RichText};
+use crate::screensaver::MAX_IDLE_TIMEOUT_SECS;
+use crate::screenshot;
+use crate::stats::MAX_TARGET_FPS;
+use crate::timezone::{MAX_TZ_LEN, TimeZone};
+use crate::transition::{DEFAULT_TRANSITION_MS, MAX_TRANSITION_MS, Transition, TransitionKind};
+use crate::websocket::{self, ACCEPT_KEY_LEN, MAX_KEY_LEN};
+use crate::{MATRIX_HEIGHT, MATRIX_WIDTH};
+
 /// Maximum length of a decoded display message.
 pub const MAX_MESSAGE_LEN: usize = 32;
 
+/// Body length of `POST /image`: one full frame of little-endian RGB565.
+pub const IMAGE_LEN: usize = MATRIX_WIDTH * MATRIX_HEIGHT * 2;
+
 /// HTML body for the controller's main page. Kept here so the parser
 /// can return it from [`dispatch`] without depending on the network layer.
 const HTML_PAGE: &str = include_str!("http_page.html");
 
+/// WiFi credentials form served at `/setup`.
+const SETUP_PAGE: &str = include_str!("setup_page.html");
+
+/// Shown after `/setup` accepts new credentials.
+const SETUP_SAVED_PAGE: &str = "<!DOCTYPE html><html><body><p>Saved. Reconnecting to the new network&hellip;</p></body></html>";
+
 /// Full 200 OK response (headers + HTML page).
 const OK_HTML_RESPONSE: &[u8] =
     b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nConnection: close\r\n\r\n";
 
+/// Full 204 response, for input sent many times a second (`/input`)
+/// that has nothing to show.
+const NO_CONTENT_RESPONSE: &[u8] = b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n";
+
+/// 200 OK headers for a JSON body, which the HTTP layer appends.
+pub const JSON_OK_RESPONSE: &[u8] =
+    b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nConnection: close\r\n\r\n";
+
+/// 400 headers for a JSON error body, which the HTTP layer appends.
+pub const JSON_BAD_REQUEST_RESPONSE: &[u8] =
+    b"HTTP/1.1 400 Bad Request\r\nContent-Type: application/json\r\nConnection: close\r\n\r\n";
+
+/// Start of the `101 Switching Protocols` answer to a WebSocket upgrade.
+/// The HTTP layer appends the accept key; see [`websocket::handshake_end`].
+pub const WEBSOCKET_UPGRADE_RESPONSE: &[u8] = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: ";
+
+/// Headers that open a `/events` stream; the HTTP layer then writes
+/// [`DisplayEvent::to_sse`](crate::events::DisplayEvent::to_sse)
+/// messages to it until the client goes.
+pub const EVENTS_RESPONSE: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n";
+
+/// Full 503 response for a `/events` client past
+/// [`MAX_EVENT_CLIENTS`](crate::events::MAX_EVENT_CLIENTS).
+pub const EVENTS_FULL_RESPONSE: &[u8] = b"HTTP/1.1 503 Service Unavailable\r\nRetry-After: 10\r\nContent-Type: text/plain\r\nConnection: close\r\n\r\nToo Many Event Clients";
+
 /// Full 404 Not Found response.
 pub const NOT_FOUND_RESPONSE: &[u8] =
     b"HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\nConnection: close\r\n\r\nNot Found";
 
+/// Captive-portal redirect to the setup page. Relative, so it works
+/// whatever host name the client's probe used.
+pub const PORTAL_REDIRECT_RESPONSE: &[u8] =
+    b"HTTP/1.1 302 Found\r\nLocation: /setup\r\nContent-Type: text/plain\r\nConnection: close\r\n\r\nSee /setup";
+
+/// Full 400 Bad Request response, for known routes with malformed params.
+pub const BAD_REQUEST_RESPONSE: &[u8] =
+    b"HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nConnection: close\r\n\r\nBad Request";
+
+/// Headers of a 400 response whose plain-text reason the HTTP layer
+/// appends, e.g. [`BmpError::message`](crate::bmp::BmpError::message).
+pub const TEXT_BAD_REQUEST_RESPONSE: &[u8] =
+    b"HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nConnection: close\r\n\r\n";
+
+/// Full 401 response for a `POST /ota` without a token.
+const UNAUTHORIZED_RESPONSE: &[u8] =
+    b"HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Bearer\r\nContent-Type: text/plain\r\nConnection: close\r\n\r\nUnauthorized";
+
+/// Full 401 response for a control request without the password, once
+/// one is set via `POST /setauth`. Browsers answer it with a login prompt.
+const BASIC_UNAUTHORIZED_RESPONSE: &[u8] = b"HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Basic realm=\"LED Matrix\"\r\nContent-Type: text/plain\r\nConnection: close\r\n\r\nUnauthorized";
+
+/// Full 403 response, sent by the HTTP layer when a `POST /ota` token
+/// doesn't match the stored one.
+pub const FORBIDDEN_RESPONSE: &[u8] =
+    b"HTTP/1.1 403 Forbidden\r\nContent-Type: text/plain\r\nConnection: close\r\n\r\nForbidden";
+
+/// Full 429 response for display changes past the
+/// [`RateLimiter`] budget.
+pub const TOO_MANY_REQUESTS_RESPONSE: &[u8] = b"HTTP/1.1 429 Too Many Requests\r\nRetry-After: 1\r\nContent-Type: text/plain\r\nConnection: close\r\n\r\nToo Many Requests";
+
+/// Full 503 response for drawing commands that don't fit in the refresh
+/// loop's queue.
+pub const QUEUE_FULL_RESPONSE: &[u8] = b"HTTP/1.1 503 Service Unavailable\r\nRetry-After: 1\r\nContent-Type: text/plain\r\nConnection: close\r\n\r\nDraw Queue Full";
+
+/// A `POST /ota` firmware upload, not yet authorized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OtaUpload {
+    /// Body length: the size of the firmware image.
+    pub len: usize,
+    /// Bearer token from the `Authorization` header, for the HTTP layer
+    /// to check against the one saved in flash.
+    pub token: heapless::String<MAX_TOKEN_LEN>,
+}
+
 /// What the HTTP layer should send back and what (if anything) to put on
 /// the display.
 pub struct Response {
@@ -28,6 +138,118 @@ pub struct Response {
     /// When `Some(text)`, the HTTP handler updates the display buffer
     /// with this text. `None` means "no change".
     pub display_text: Option<heapless::String<MAX_MESSAGE_LEN>>,
+    /// When `Some(color)`, the HTTP handler switches the text color.
+    /// Only set by `/text` requests that carry `r`, `g` or `b` params.
+    pub text_color: Option<Pixel>,
+    /// When `Some(level)`, the HTTP handler changes the global brightness
+    /// (0 = blank, 255 = full).
+    pub brightness: Option<u8>,
+    /// When `Some(cmd)`, the HTTP handler queues `cmd` for the refresh
+    /// loop to draw into the frame buffer.
+    pub draw: Option<DrawCommand>,
+    /// Commands from a valid `POST /api/draw`, queued in order after
+    /// `draw`.
+    pub draw_batch: heapless::Vec<DrawCommand, MAX_DRAW_OPS>,
+    /// Set for a rejected `POST /api/draw`: the HTTP handler appends its
+    /// [`ApiError::to_json`] after `body`.
+    pub api_error: Option<ApiError>,
+    /// When `Some(creds)`, the HTTP handler saves them and reconnects
+    /// WiFi. Only set by a valid `POST /setup`.
+    pub credentials: Option<Credentials>,
+    /// When `Some(token)`, the HTTP handler saves it as the `POST /ota`
+    /// token, unless one is saved already. Only set by a valid
+    /// `POST /setup` with a non-empty `ota_token` field.
+    pub ota_token: Option<heapless::String<MAX_TOKEN_LEN>>,
+    /// When `Some`, the HTTP handler checks the token, streams the body
+    /// (starting at [`body_start`]) into the idle OTA partition and
+    /// reboots into it once the reply is out.
+    pub ota_upload: Option<OtaUpload>,
+    /// When `Some(password)`, the HTTP handler saves it as the control
+    /// password; an empty one turns authentication off. Only set by a
+    /// valid `POST /setauth`.
+    pub auth_password: Option<heapless::String<MAX_AUTH_PASSWORD_LEN>>,
+    /// When `Some(name)`, the HTTP handler saves it as the mDNS hostname,
+    /// used from the next boot. Only set by a valid `/hostname` request.
+    pub hostname: Option<heapless::String<MAX_HOSTNAME_LEN>>,
+    /// Set for a valid `POST /image`: the HTTP handler reads the
+    /// [`IMAGE_LEN`]-byte body (starting at [`body_start`] in the buffer
+    /// it already has) and shows it full screen.
+    pub image_upload: bool,
+    /// When `Some(len)`, a `POST /image` with a `len`-byte BMP body: the
+    /// HTTP handler decodes it (starting at [`body_start`]) with a
+    /// [`BmpDecoder`](crate::bmp::BmpDecoder) and shows it.
+    pub bmp_upload: Option<usize>,
+    /// When `Some(len)`, a `POST /font` with a `len`-byte body: the HTTP
+    /// handler reads it (starting at [`body_start`]) and switches to that
+    /// font, or back to the built-in one when `len` is 0.
+    pub font_upload: Option<usize>,
+    /// When `Some(len)`, a `POST /anim` with a `len`-byte frame pack: the
+    /// HTTP handler decodes it (starting at [`body_start`]) with a
+    /// [`FramePackDecoder`](crate::frame_pack::FramePackDecoder) and
+    /// plays it.
+    pub anim_upload: Option<usize>,
+    /// Set for `GET /stats`: the HTTP handler appends the current
+    /// [`Stats`](crate::stats::Stats) as JSON after `body`.
+    pub stats: bool,
+    /// Set for `GET /status`: the HTTP handler appends the current
+    /// [`Status`](crate::stats::Status) as JSON after `body`.
+    pub status: bool,
+    /// Set for `GET /events`: after `body` the HTTP handler keeps the
+    /// connection open and streams
+    /// [`DisplayEvent`](crate::events::DisplayEvent)s to it.
+    pub events: bool,
+    /// When `Some(format)`, a `GET /screenshot`: the HTTP handler sends
+    /// [`screenshot::Format::headers`] and the frame on screen encoded
+    /// as `format`, instead of `body`.
+    pub screenshot: Option<screenshot::Format>,
+    /// When `Some(mode)`, the HTTP handler switches the display mode.
+    pub mode: Option<DisplayMode>,
+    /// When `Some(v)`, the HTTP handler adds `v` to the
+    /// [`Graph`](crate::graph::Graph). Only set by a valid `/graph/push`,
+    /// together with [`DisplayMode::Graph`].
+    pub graph_sample: Option<f32>,
+    /// When `Some(command)`, the HTTP handler starts or pauses the
+    /// [`Countdown`](crate::countdown::Countdown). A start comes with
+    /// [`DisplayMode::Timer`].
+    pub timer: Option<TimerCommand>,
+    /// When `Some(tz)`, the HTTP handler switches the clock's time zone
+    /// and saves it. Always a valid POSIX TZ string.
+    pub timezone: Option<heapless::String<MAX_TZ_LEN>>,
+    /// When `Some(t)`, the HTTP handler animates the new `display_text`
+    /// in with `t` instead of switching at once.
+    pub transition: Option<Transition>,
+    /// When `Some(g)`, the HTTP handler colors the new `display_text`
+    /// with `g` while it scrolls. `None` with new text goes back to the
+    /// text color.
+    pub gradient: Option<GradientMode>,
+    /// When `Some(rich)`, the HTTP handler draws the new `display_text`
+    /// (the same as `rich.text()`) with a color per segment. `None` with
+    /// new text goes back to the text color.
+    pub rich_text: Option<RichText>,
+    /// When `Some(update)`, the HTTP handler sets that region's text (and
+    /// color). Always together with [`DisplayMode::Regions`].
+    pub region: Option<RegionUpdate>,
+    /// When `Some(seconds)`, the HTTP handler sets the
+    /// [`Screensaver`](crate::screensaver::Screensaver) timeout and saves
+    /// it. 0 turns the screensaver off.
+    pub idle_timeout: Option<u32>,
+    /// When `Some(fps)`, the HTTP handler sets the refresh loop's
+    /// [`FramePacer`](crate::stats::FramePacer) target.
+    pub target_fps: Option<u32>,
+    /// When `Some(notice)`, the HTTP handler queues it in the
+    /// [`Notices`](crate::notice::Notices).
+    pub notice: Option<TimedNotice>,
+    /// When `Some(input)`, the HTTP handler passes it on to the
+    /// [`Snake`](crate::games::Snake) game. A new game comes with
+    /// [`DisplayMode::Snake`].
+    pub game_input: Option<GameInput>,
+    /// When `Some(accept)`, a valid `GET /ws` upgrade: the HTTP handler
+    /// finishes the handshake with this `Sec-WebSocket-Accept` value and
+    /// then reads frames from the connection instead of closing it.
+    pub websocket: Option<heapless::String<ACCEPT_KEY_LEN>>,
+    /// HTML appended after `body`. Empty when `body` is already the
+    /// complete response.
+    pub page: &'static [u8],
 }
 
 impl Response {
@@ -35,22 +257,62 @@ impl Response {
         Self {
             body,
             display_text: None,
+            text_color: None,
+            brightness: None,
+            draw: None,
+            draw_batch: heapless::Vec::new(),
+            api_error: None,
+            credentials: None,
+            ota_token: None,
+            ota_upload: None,
+            auth_password: None,
+            hostname: None,
+            image_upload: false,
+            bmp_upload: None,
+            font_upload: None,
+            anim_upload: None,
+            stats: false,
+            status: false,
+            events: false,
+            screenshot: None,
+            mode: None,
+            graph_sample: None,
+            timer: None,
+            timezone: None,
+            transition: None,
+            gradient: None,
+            rich_text: None,
+            region: None,
+            idle_timeout: None,
+            target_fps: None,
+            notice: None,
+            game_input: None,
+            websocket: None,
+            page: HTML_PAGE.as_bytes(),
         }
     }
 
     fn html_with_text(body: &'static [u8], text: heapless::String<MAX_MESSAGE_LEN>) -> Self {
         Self {
-            body,
             display_text: Some(text),
+            ..Self::html(body)
         }
     }
 
-    const fn not_found() -> Self {
+    fn complete(body: &'static [u8]) -> Self {
         Self {
-            body: NOT_FOUND_RESPONSE,
-            display_text: None,
+            page: b"",
+            ..Self::html(body)
         }
     }
+
+    fn not_found() -> Self {
+        Self::complete(NOT_FOUND_RESPONSE)
+    }
+
+    fn bad_request() -> Self {
+        Self::complete(BAD_REQUEST_RESPONSE)
+    }
 }
 
 /// Dispatch an HTTP request and return the response the network layer
@@ -59,187 +321,2667 @@ impl Response {
 /// The response body is `OK_HTML_RESPONSE ++ HTML_PAGE` for a controller
 /// page, or `NOT_FOUND_RESPONSE` for an unknown route.
 pub fn dispatch(request: &[u8]) -> Response {
+    // Checked on the raw bytes: the binary body is rarely valid UTF-8.
+    if is_image_upload(request) {
+        // No uncompressed 24-bit BMP is exactly `IMAGE_LEN` bytes, so
+        // that length always means a raw frame.
+        let body = &request[body_start(request).unwrap_or(request.len())..];
+        return match content_length(request) {
+            Some(IMAGE_LEN) => Response {
+                image_upload: true,
+                ..Response::html(OK_HTML_RESPONSE)
+            },
+            Some(len)
+                if body.starts_with(b"BM") && (BMP_HEADER_LEN..=MAX_BMP_LEN).contains(&len) =>
+            {
+                Response {
+                    bmp_upload: Some(len),
+                    ..Response::html(OK_HTML_RESPONSE)
+                }
+            }
+            _ => Response::bad_request(),
+        };
+    }
+
+    if is_font_upload(request) {
+        return match content_length(request) {
+            Some(len) if len <= MAX_FONT_LEN => Response {
+                font_upload: Some(len),
+                ..Response::html(OK_HTML_RESPONSE)
+            },
+            _ => Response::bad_request(),
+        };
+    }
+
+    if is_anim_upload(request) {
+        return match content_length(request).filter(|&len| frame_pack::frame_count(len).is_some()) {
+            Some(len) => Response {
+                anim_upload: Some(len),
+                ..Response::html(OK_HTML_RESPONSE)
+            },
+            None => Response::bad_request(),
+        };
+    }
+
+    if is_ota_upload(request) {
+        let Some(token) = bearer_token(request) else {
+            return Response::complete(UNAUTHORIZED_RESPONSE);
+        };
+        return match content_length(request) {
+            Some(len) if (IMAGE_HEADER_LEN..=OTA_SLOT_LEN).contains(&len) => Response {
+                ota_upload: Some(OtaUpload { len, token }),
+                ..Response::html(OK_HTML_RESPONSE)
+            },
+            _ => Response::bad_request(),
+        };
+    }
+
+    if is_api_draw(request) {
+        return match api_draw_body(request).and_then(draw_api::parse_ops) {
+            Ok(ops) => Response {
+                draw_batch: ops,
+                page: draw_api::OK_JSON.as_bytes(),
+                ..Response::html(JSON_OK_RESPONSE)
+            },
+            Err(error) => Response {
+                api_error: Some(error),
+                ..Response::complete(JSON_BAD_REQUEST_RESPONSE)
+            },
+        };
+    }
+
     let request_str = core::str::from_utf8(request).unwrap_or("");
+    let params = request_params(request_str);
+
+    if is_websocket_request(request_str) {
+        return match websocket_key(request_str) {
+            Some(key) => Response {
+                websocket: Some(websocket::accept_key(key)),
+                ..Response::complete(WEBSOCKET_UPGRADE_RESPONSE)
+            },
+            None => Response::bad_request(),
+        };
+    }
 
     if is_root_request(request_str) {
         return Response::html(OK_HTML_RESPONSE);
     }
+    if is_setup_submit(request_str) {
+        return match parse_credentials(request_str) {
+            Some(creds) => Response {
+                credentials: Some(creds),
+                ota_token: parse_ota_token(request_str),
+                page: SETUP_SAVED_PAGE.as_bytes(),
+                ..Response::html(OK_HTML_RESPONSE)
+            },
+            None => Response::bad_request(),
+        };
+    }
+    if is_setauth_request(request_str) {
+        return match parse_auth_password(request_str) {
+            Some(password) => Response {
+                auth_password: Some(password),
+                ..Response::html(OK_HTML_RESPONSE)
+            },
+            None => Response::bad_request(),
+        };
+    }
+    if is_setup_request(request_str) {
+        return Response {
+            page: SETUP_PAGE.as_bytes(),
+            ..Response::html(OK_HTML_RESPONSE)
+        };
+    }
     if is_clear_request(request_str) {
-        return Response::html_with_text(OK_HTML_RESPONSE, heapless::String::new());
+        // The fill also empties the scene under the text; `/clear?r=&g=&b=`
+        // fills with a solid color instead of black.
+        let color = extract_query_color(&params).unwrap_or([0, 0, 0]);
+        let mut response = Response::html_with_text(OK_HTML_RESPONSE, heapless::String::new());
+        response.draw = Some(DrawCommand::Fill { color });
+        return response;
+    }
+    if is_color_request(request_str) {
+        return match params.decoded("hex").as_deref().and_then(parse_hex_color) {
+            Some(color) => Response {
+                draw: Some(DrawCommand::Fill { color }),
+                ..Response::html_with_text(OK_HTML_RESPONSE, heapless::String::new())
+            },
+            None => Response::bad_request(),
+        };
+    }
+    if is_gradient_request(request_str) {
+        let dir = match params.get("dir") {
+            Some(name) => GradientDir::from_name(name),
+            None => Some(GradientDir::Horizontal),
+        };
+        let color = |name| params.decoded(name).as_deref().and_then(parse_hex_color);
+        return match (color("from"), color("to"), dir) {
+            (Some(from), Some(to), Some(dir)) => Response {
+                draw: Some(DrawCommand::Gradient { from, to, dir }),
+                ..Response::html_with_text(OK_HTML_RESPONSE, heapless::String::new())
+            },
+            _ => Response::bad_request(),
+        };
     }
-    if is_text_update_request(request_str)
+    if (is_text_update_request(request_str) || is_text_submit(request_str))
         && let Some(decoded) = extract_query_message(request_str)
     {
-        return Response::html_with_text(OK_HTML_RESPONSE, decoded);
+        let (Some(transition), Some(gradient)) =
+            (parse_transition(&params), parse_gradient(&params))
+        else {
+            return Response::bad_request();
+        };
+        let text = join_text_lines(&decoded, &params);
+        let mut response = Response::html_with_text(OK_HTML_RESPONSE, text);
+        response.text_color = extract_query_color(&params);
+        response.transition = transition;
+        response.gradient = gradient;
+        return response;
+    }
+    if is_brightness_request(request_str) {
+        return match params.parse("level") {
+            Some(level) => Response {
+                brightness: Some(level),
+                ..Response::html(OK_HTML_RESPONSE)
+            },
+            None => Response::bad_request(),
+        };
     }
 
-    Response::not_found()
-}
+    if is_pixel_request(request_str) {
+        return match parse_pixel(&params) {
+            Some(cmd) => Response {
+                draw: Some(cmd),
+                ..Response::html(OK_HTML_RESPONSE)
+            },
+            None => Response::bad_request(),
+        };
+    }
 
-/// Borrow the HTML page body (used by the network layer to append it
-/// after [`OK_HTML_RESPONSE`]).
-pub fn html_page() -> &'static [u8] {
-    HTML_PAGE.as_bytes()
-}
+    // `/marquee/stop` first: `/marquee` would match it too.
+    if is_marquee_stop(request_str) || is_anim_stop(request_str) {
+        return Response {
+            mode: Some(DisplayMode::Text),
+            ..Response::html(OK_HTML_RESPONSE)
+        };
+    }
+    if is_marquee_request(request_str) {
+        return match (parse_marquee(&params), parse_gradient(&params)) {
+            (Some((text, speed)), Some(gradient)) => Response {
+                mode: Some(DisplayMode::Marquee { speed }),
+                text_color: extract_query_color(&params),
+                gradient,
+                ..Response::html_with_text(OK_HTML_RESPONSE, text)
+            },
+            _ => Response::bad_request(),
+        };
+    }
 
-/// True for `GET /` or `GET / HTTP/1.x` (root page).
-fn is_root_request(request: &str) -> bool {
-    request.starts_with("GET / ") || request.starts_with("GET / HTTP")
-}
+    if is_paragraph_request(request_str) {
+        return match params.decoded("msg") {
+            Some(msg) => Response {
+                mode: Some(DisplayMode::Paragraph),
+                text_color: extract_query_color(&params),
+                ..Response::html_with_text(OK_HTML_RESPONSE, join_text_lines(&msg, &params))
+            },
+            None => Response::bad_request(),
+        };
+    }
 
-/// True for `GET /clear`.
-fn is_clear_request(request: &str) -> bool {
-    request.contains("GET /clear")
-}
+    if is_rich_request(request_str) {
+        return match parse_rich(&params) {
+            Some(rich) => Response {
+                display_text: Some(rich.text().try_into().unwrap_or_default()),
+                rich_text: Some(rich),
+                ..Response::html(OK_HTML_RESPONSE)
+            },
+            None => Response::bad_request(),
+        };
+    }
 
-/// True for `GET /text?msg=...`.
-fn is_text_update_request(request: &str) -> bool {
-    request.contains("GET /text?msg=")
-}
+    if is_region_request(request_str) {
+        return match parse_region(&params) {
+            Some(update) => Response {
+                mode: Some(DisplayMode::Regions),
+                region: Some(update),
+                ..Response::html(OK_HTML_RESPONSE)
+            },
+            None => Response::bad_request(),
+        };
+    }
 
-/// Extract the URL-decoded `msg=` query parameter from a request, if any.
-fn extract_query_message(request: &str) -> Option<heapless::String<MAX_MESSAGE_LEN>> {
-    let start = request.find("msg=")?;
-    let value = &request[start + 4..];
-    let end = value.find([' ', '\r', '\n']).unwrap_or(value.len());
-    url_decode(&value[..end])
-}
+    if is_vscroll_request(request_str) {
+        return match (parse_vscroll(&params), parse_gradient(&params)) {
+            (Some((text, speed)), Some(gradient)) => Response {
+                mode: Some(DisplayMode::VScroll { speed }),
+                text_color: extract_query_color(&params),
+                gradient,
+                ..Response::html_with_text(OK_HTML_RESPONSE, text)
+            },
+            _ => Response::bad_request(),
+        };
+    }
 
-/// Percent-decode a URL-encoded string, capped at [`MAX_MESSAGE_LEN`]
-/// characters. Stops at the first character that would overflow.
-fn url_decode(encoded: &str) -> Option<heapless::String<MAX_MESSAGE_LEN>> {
-    let mut out = heapless::String::new();
-    let mut chars = encoded.chars().peekable();
+    if is_stats_request(request_str) {
+        return Response {
+            stats: true,
+            ..Response::complete(JSON_OK_RESPONSE)
+        };
+    }
 
-    while let Some(c) = chars.next() {
-        let decoded_char = match c {
-            '%' => decode_percent(&mut chars)?,
-            '+' => ' ',
-            other => other,
+    if is_status_request(request_str) {
+        return Response {
+            status: true,
+            ..Response::complete(JSON_OK_RESPONSE)
         };
-        if out.push(decoded_char).is_err() {
-            // Buffer full — stop here. Caller sees a truncated message
-            // rather than no message at all.
-            break;
-        }
     }
 
-    Some(out)
-}
+    if is_events_request(request_str) {
+        return Response {
+            events: true,
+            ..Response::complete(EVENTS_RESPONSE)
+        };
+    }
 
-/// Decode a `%XX` percent-escape and return the resulting character.
-fn decode_percent(chars: &mut core::iter::Peekable<core::str::Chars<'_>>) -> Option<char> {
-    let h = chars.next()?.to_digit(16)?;
-    let l = chars.next()?.to_digit(16)?;
-    char::from_u32(h * 16 + l)
-}
+    if is_screenshot_request(request_str) {
+        return match parse_screenshot_format(&params) {
+            Some(format) => Response {
+                screenshot: Some(format),
+                ..Response::complete(b"")
+            },
+            None => Response::bad_request(),
+        };
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    if is_effect_request(request_str) {
+        return match parse_effect(&params) {
+            Some((effect, speed)) => Response {
+                mode: Some(DisplayMode::Effect { effect, speed }),
+                ..Response::html(OK_HTML_RESPONSE)
+            },
+            None => Response::bad_request(),
+        };
+    }
 
-    #[test]
-    fn root_request_matches() {
-        assert!(is_root_request("GET / HTTP/1.1"));
-        assert!(is_root_request("GET / "));
-        assert!(!is_root_request("GET /text HTTP/1.1"));
+    // `/life/stop` first: `/life` would match it too.
+    if is_life_stop(request_str) {
+        return Response {
+            mode: Some(DisplayMode::Text),
+            ..Response::html(OK_HTML_RESPONSE)
+        };
+    }
+    if is_life_request(request_str) {
+        return match parse_life(&params) {
+            Some((effect, speed)) => Response {
+                mode: Some(DisplayMode::Effect { effect, speed }),
+                ..Response::html(OK_HTML_RESPONSE)
+            },
+            None => Response::bad_request(),
+        };
     }
 
-    #[test]
-    fn clear_request_matches() {
-        assert!(is_clear_request("GET /clear HTTP/1.1"));
-        assert!(!is_clear_request("GET / HTTP/1.1"));
+    // `/snake/stop` first: `/snake` would match it too.
+    if is_snake_stop(request_str) {
+        return Response {
+            mode: Some(DisplayMode::Text),
+            ..Response::html(OK_HTML_RESPONSE)
+        };
+    }
+    if is_snake_request(request_str) {
+        return Response {
+            mode: Some(DisplayMode::Snake),
+            game_input: Some(GameInput::NewGame),
+            ..Response::html(OK_HTML_RESPONSE)
+        };
+    }
+    if is_input_request(request_str) {
+        return match params.get("dir").and_then(Direction::from_name) {
+            Some(direction) => Response {
+                game_input: Some(GameInput::Steer(direction)),
+                ..Response::complete(NO_CONTENT_RESPONSE)
+            },
+            None => Response::bad_request(),
+        };
     }
 
-    #[test]
-    fn text_update_request_matches() {
-        assert!(is_text_update_request("GET /text?msg=hi HTTP/1.1"));
-        assert!(!is_text_update_request("GET / HTTP/1.1"));
+    if is_graph_push_request(request_str) {
+        return match params.parse::<f32>("v").filter(|v| v.is_finite()) {
+            Some(v) => Response {
+                mode: Some(DisplayMode::Graph),
+                graph_sample: Some(v),
+                ..Response::html(OK_HTML_RESPONSE)
+            },
+            None => Response::bad_request(),
+        };
     }
 
-    #[test]
-    fn url_decode_passes_plain_text() {
-        let decoded = url_decode("hello").unwrap();
-        assert_eq!(decoded.as_str(), "hello");
+    // `/timer/pause` and `/timer/stop` first: `/timer` would match them.
+    if is_timer_pause(request_str) {
+        return Response {
+            timer: Some(TimerCommand::Pause),
+            ..Response::html(OK_HTML_RESPONSE)
+        };
+    }
+    if is_timer_stop(request_str) {
+        return Response {
+            mode: Some(DisplayMode::Text),
+            ..Response::html(OK_HTML_RESPONSE)
+        };
+    }
+    if is_timer_request(request_str) {
+        return match params
+            .parse::<u32>("seconds")
+            .filter(|s| (1..=MAX_TIMER_SECONDS).contains(s))
+        {
+            Some(seconds) => Response {
+                mode: Some(DisplayMode::Timer),
+                timer: Some(TimerCommand::Start(seconds)),
+                ..Response::html(OK_HTML_RESPONSE)
+            },
+            None => Response::bad_request(),
+        };
     }
 
-    #[test]
-    fn url_decode_handles_plus_sign() {
-        let decoded = url_decode("hello+world").unwrap();
-        assert_eq!(decoded.as_str(), "hello world");
+    if is_notify_request(request_str) {
+        let notice = match params.get("pattern") {
+            Some(name) => TestPattern::from_name(name).map(Notice::Pattern),
+            None => params.decoded("msg").map(Notice::Text),
+        };
+        let seconds = params
+            .parse::<u32>("seconds")
+            .filter(|s| (1..=MAX_NOTICE_SECONDS).contains(s));
+        return match (notice, seconds) {
+            (Some(notice), Some(seconds)) => Response {
+                notice: Some(TimedNotice {
+                    notice,
+                    duration_ms: u64::from(seconds) * 1000,
+                }),
+                ..Response::html(OK_HTML_RESPONSE)
+            },
+            _ => Response::bad_request(),
+        };
     }
 
-    #[test]
-    fn url_decode_handles_percent_escapes() {
-        let decoded = url_decode("hello%20world").unwrap();
-        assert_eq!(decoded.as_str(), "hello world");
+    if is_test_pattern_request(request_str) {
+        return match params.get("pattern").and_then(TestPattern::from_name) {
+            Some(pattern) => Response {
+                mode: Some(DisplayMode::Test(pattern)),
+                ..Response::html(OK_HTML_RESPONSE)
+            },
+            None => Response::bad_request(),
+        };
     }
 
-    #[test]
-    fn url_decode_handles_uppercase_hex() {
-        let decoded = url_decode("%2A").unwrap();
-        assert_eq!(decoded.as_str(), "*");
+    if is_mode_request(request_str) {
+        return match parse_mode(&params) {
+            Some((mode, timezone)) => Response {
+                mode,
+                timezone,
+                ..Response::html(OK_HTML_RESPONSE)
+            },
+            None => Response::bad_request(),
+        };
     }
 
-    #[test]
-    fn url_decode_stops_on_truncated_escape() {
-        // '%2' is incomplete — decoder returns None.
-        assert!(url_decode("%2").is_none());
+    if is_idle_request(request_str) {
+        return match params
+            .parse::<u32>("seconds")
+            .filter(|&s| s <= MAX_IDLE_TIMEOUT_SECS)
+        {
+            Some(seconds) => Response {
+                idle_timeout: Some(seconds),
+                ..Response::html(OK_HTML_RESPONSE)
+            },
+            None => Response::bad_request(),
+        };
     }
 
-    #[test]
-    fn url_decode_handles_mixed_input() {
-        let decoded = url_decode("a%20b+c%21").unwrap();
-        assert_eq!(decoded.as_str(), "a b c!");
+    if is_fps_request(request_str) {
+        return match params
+            .parse::<u32>("target")
+            .filter(|fps| (1..=MAX_TARGET_FPS).contains(fps))
+        {
+            Some(fps) => Response {
+                target_fps: Some(fps),
+                ..Response::html(OK_HTML_RESPONSE)
+            },
+            None => Response::bad_request(),
+        };
     }
 
-    #[test]
-    fn extract_query_message_basic() {
-        let msg = extract_query_message("GET /text?msg=hi HTTP/1.1").unwrap();
-        assert_eq!(msg.as_str(), "hi");
+    if is_hostname_request(request_str) {
+        return match parse_hostname(&params) {
+            Some(name) => Response {
+                hostname: Some(name),
+                ..Response::html(OK_HTML_RESPONSE)
+            },
+            None => Response::bad_request(),
+        };
     }
 
-    #[test]
-    fn extract_query_message_decoded() {
-        let msg = extract_query_message("GET /text?msg=hello+world HTTP/1.1").unwrap();
-        assert_eq!(msg.as_str(), "hello world");
+    Response::not_found()
+}
+
+/// [`dispatch`] for the setup access point's captive portal: unknown
+/// paths (including the OS connectivity probes) redirect to `/setup`
+/// instead of returning 404. `/events` gets a 204, which tells a
+/// browser not to reconnect: the portal has a single server task, which
+/// a stream would keep to itself.
+pub fn dispatch_portal(request: &[u8]) -> Response {
+    let response = dispatch(request);
+    if response.body == NOT_FOUND_RESPONSE {
+        return Response::complete(PORTAL_REDIRECT_RESPONSE);
+    }
+    if response.events {
+        return Response::complete(NO_CONTENT_RESPONSE);
     }
+    response
+}
 
-    #[test]
-    fn extract_query_message_missing_returns_none() {
-        assert!(extract_query_message("GET / HTTP/1.1").is_none());
+/// Gate `response`, what [`dispatch`] made of `request`, behind HTTP
+/// Basic auth. With a control `password` set, anything but the root
+/// page, `/stats`, `/status`, the `/setup` form, `POST /ota` (which has its own
+/// token) and unknown paths needs it in the `Authorization` header, or
+/// is answered with a 401 challenge instead.
+pub fn authorize(request: &[u8], response: Response, password: Option<&str>) -> Response {
+    let Some(password) = password else {
+        return response;
+    };
+    let request_str = core::str::from_utf8(request).unwrap_or("");
+    let public = is_root_request(request_str)
+        || is_stats_request(request_str)
+        || is_status_request(request_str)
+        || is_setup_request(request_str)
+        || is_ota_upload(request)
+        || response.body == NOT_FOUND_RESPONSE
+        || response.body == PORTAL_REDIRECT_RESPONSE;
+    let given = body_start(request)
+        .and_then(|start| core::str::from_utf8(&request[..start]).ok())
+        .and_then(|headers| header(headers, "authorization"))
+        .and_then(auth::basic_password);
+    if public || given.is_some_and(|given| auth::tokens_match(password, &given)) {
+        response
+    } else {
+        Response::complete(BASIC_UNAUTHORIZED_RESPONSE)
     }
+}
 
-    #[test]
-    fn dispatch_root_returns_html_no_text() {
-        let resp = dispatch(b"GET / HTTP/1.1");
-        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
-        assert!(resp.display_text.is_none());
+/// Replace `response` with [`TOO_MANY_REQUESTS_RESPONSE`] if it changes
+/// the display (text, a draw command, a `/api/draw` batch, a graph
+/// sample or a region's text) and `limiter` has no budget left at `now_ms`. Everything else
+/// passes through without counting.
+pub fn throttle(response: Response, limiter: &mut RateLimiter, now_ms: u64) -> Response {
+    let draws = response.display_text.is_some()
+        || response.draw.is_some()
+        || !response.draw_batch.is_empty()
+        || response.graph_sample.is_some()
+        || response.region.is_some()
+        || response.notice.is_some();
+    if !draws || limiter.allow(now_ms) {
+        response
+    } else {
+        Response::complete(TOO_MANY_REQUESTS_RESPONSE)
     }
+}
 
-    #[test]
-    fn dispatch_clear_returns_html_and_empty_text() {
-        let resp = dispatch(b"GET /clear HTTP/1.1");
-        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
-        assert_eq!(resp.display_text.unwrap().as_str(), "");
+/// True if `response`, as [`authorize`] left it, answers someone using
+/// the display, which wakes it from the screensaver: anything but the
+/// read-only `/stats`, `/status`, `/events` and `/screenshot`, refused
+/// logins, captive portal probes and unknown paths.
+pub fn is_command(response: &Response) -> bool {
+    !(response.stats
+        || response.status
+        || response.events
+        || response.screenshot.is_some()
+        || response.body == NOT_FOUND_RESPONSE
+        || response.body == PORTAL_REDIRECT_RESPONSE
+        || response.body == BASIC_UNAUTHORIZED_RESPONSE)
+}
+
+/// Replace `response` with [`QUEUE_FULL_RESPONSE`] if its drawing
+/// commands (`draw` and the `/api/draw` batch) need more than `free`
+/// slots in the refresh loop's queue. All of a request's commands are
+/// queued or none are, so a batch is never drawn in part.
+pub fn backpressure(response: Response, free: usize) -> Response {
+    let commands = usize::from(response.draw.is_some()) + response.draw_batch.len();
+    if commands > free {
+        Response::complete(QUEUE_FULL_RESPONSE)
+    } else {
+        response
     }
+}
 
-    #[test]
-    fn dispatch_text_update_returns_html_and_decoded_text() {
-        let resp = dispatch(b"GET /text?msg=hi HTTP/1.1");
-        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
-        assert_eq!(resp.display_text.unwrap().as_str(), "hi");
+/// Size of the buffer the network layer reads a request into. Big enough
+/// for a browser's headers plus a `/setup` form body, or a script's
+/// headers plus a full `/api/draw` batch.
+pub const MAX_REQUEST_LEN: usize = 2048;
+
+/// True once `request` holds the full header block and as many body
+/// bytes as its `Content-Length` announces (none if absent). Lets the
+/// network layer keep reading when a POST body arrives in a later
+/// segment than its headers.
+pub fn is_complete(request: &[u8]) -> bool {
+    match (body_start(request), content_length(request)) {
+        (Some(start), Some(len)) => request.len() - start >= len,
+        _ => false,
     }
+}
 
-    #[test]
-    fn dispatch_unknown_path_returns_404() {
-        let resp = dispatch(b"GET /nope HTTP/1.1");
-        assert!(resp.body.starts_with(b"HTTP/1.1 404"));
-        assert!(resp.display_text.is_none());
+/// Offset of the body: just past the blank line ending the headers.
+/// `None` until the whole header block is in `request`.
+pub fn body_start(request: &[u8]) -> Option<usize> {
+    let header_end = request.windows(4).position(|w| w == b"\r\n\r\n")?;
+    Some(header_end + 4)
+}
+
+/// The `Content-Length` header's value, `0` if absent. `None` until the
+/// whole header block is in `request`.
+fn content_length(request: &[u8]) -> Option<usize> {
+    let headers = &request[..body_start(request)?];
+    let headers = core::str::from_utf8(headers).unwrap_or("");
+    let len = header(headers, "content-length")
+        .and_then(|value| value.parse::<usize>().ok())
+        .unwrap_or(0);
+    Some(len)
+}
+
+/// Split response `headers` so a `Content-Length` for a `body_len`-byte
+/// body can go in before their closing blank line. Returns the headers
+/// without that blank line, then the `Content-Length` line plus the
+/// blank line. Headers that don't end in a blank line come back whole,
+/// with nothing to add.
+pub fn content_length_head(headers: &[u8], body_len: usize) -> (&[u8], heapless::String<32>) {
+    let mut length = heapless::String::new();
+    let Some(head) = headers.strip_suffix(b"\r\n") else {
+        return (headers, length);
+    };
+    if !head.ends_with(b"\r\n") {
+        return (headers, length);
     }
+    // "Content-Length: " plus at most 20 digits and two line breaks.
+    let _ = write!(length, "Content-Length: {body_len}\r\n\r\n");
+    (head, length)
+}
 
-    #[test]
-    fn html_page_is_non_empty() {
-        assert!(!html_page().is_empty());
+/// Value of the header `name` (case-insensitive), trimmed.
+fn header<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+    request
+        .split("\r\n")
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .find(|(header, _)| header.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+}
+
+/// Decode `POST /image` body bytes into RGB565 values, two bytes each,
+/// low byte first. A trailing odd byte is ignored.
+pub fn rgb565_pixels(body: &[u8]) -> impl Iterator<Item = u16> + '_ {
+    body.as_chunks::<2>()
+        .0
+        .iter()
+        .map(|&pair| u16::from_le_bytes(pair))
+}
+
+/// Borrow the HTML page body (used by the network layer to append it
+/// after [`OK_HTML_RESPONSE`]).
+pub fn html_page() -> &'static [u8] {
+    HTML_PAGE.as_bytes()
+}
+
+/// True for `GET /` or `GET / HTTP/1.x` (root page).
+fn is_root_request(request: &str) -> bool {
+    request.starts_with("GET / ") || request.starts_with("GET / HTTP")
+}
+
+/// True for `GET /setup` (the credentials form).
+fn is_setup_request(request: &str) -> bool {
+    request.starts_with("GET /setup")
+}
+
+/// True for `POST /image` (raw RGB565 frame upload).
+fn is_image_upload(request: &[u8]) -> bool {
+    request.starts_with(b"POST /image")
+}
+
+/// True for `POST /font` (bitmap font upload).
+fn is_font_upload(request: &[u8]) -> bool {
+    request.starts_with(b"POST /font")
+}
+
+/// True for `POST /anim` (frame pack upload).
+fn is_anim_upload(request: &[u8]) -> bool {
+    request.starts_with(b"POST /anim")
+}
+
+/// True for `POST /ota` (firmware upload).
+fn is_ota_upload(request: &[u8]) -> bool {
+    request.starts_with(b"POST /ota")
+}
+
+/// The token of an `Authorization: Bearer <token>` header. `None` if the
+/// header is missing, not a bearer token, empty or too long.
+fn bearer_token(request: &[u8]) -> Option<heapless::String<MAX_TOKEN_LEN>> {
+    let headers = &request[..body_start(request)?];
+    let headers = core::str::from_utf8(headers).ok()?;
+    let value = header(headers, "authorization")?;
+    let (scheme, token) = value.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") || token.trim().is_empty() {
+        return None;
+    }
+    heapless::String::try_from(token.trim()).ok()
+}
+
+/// True for `POST /api/draw`.
+fn is_api_draw(request: &[u8]) -> bool {
+    request.starts_with(b"POST /api/draw")
+}
+
+/// The body of a `POST /api/draw`, or [`ApiError::TooLarge`] if the
+/// request buffer filled up before all of it arrived.
+fn api_draw_body(request: &[u8]) -> Result<&[u8], ApiError> {
+    let start = body_start(request).ok_or(ApiError::TooLarge)?;
+    let len = content_length(request).ok_or(ApiError::TooLarge)?;
+    start
+        .checked_add(len)
+        .and_then(|end| request.get(start..end))
+        .ok_or(ApiError::TooLarge)
+}
+
+/// True for `POST /setup` (credentials form submission).
+fn is_setup_submit(request: &str) -> bool {
+    request.starts_with("POST /setup")
+}
+
+/// Parse the `ssid` and `password` fields of a form-encoded
+/// `POST /setup` body. `None` if either is malformed or fails
+/// [`Credentials::new`] validation.
+fn parse_credentials(request: &str) -> Option<Credentials> {
+    let (_, body) = request.split_once("\r\n\r\n")?;
+    let form = QueryParams::from_form(body.trim_end_matches('\0'));
+    // One spare byte so an over-long value is rejected, not truncated.
+    let ssid: heapless::String<{ MAX_SSID_LEN + 1 }> = form.decoded_to("ssid")?;
+    let password: heapless::String<{ MAX_PASSWORD_LEN + 1 }> =
+        form.decoded_to("password").unwrap_or_default();
+    Credentials::new(&ssid, &password)
+}
+
+/// The optional `ota_token` field of a `POST /setup` body. `None` when
+/// it's absent, empty or too long.
+fn parse_ota_token(request: &str) -> Option<heapless::String<MAX_TOKEN_LEN>> {
+    let (_, body) = request.split_once("\r\n\r\n")?;
+    let form = QueryParams::from_form(body.trim_end_matches('\0'));
+    // One spare byte so an over-long token is rejected, not truncated.
+    let token: heapless::String<{ MAX_TOKEN_LEN + 1 }> = form.decoded_to("ota_token")?;
+    if token.is_empty() {
+        return None;
+    }
+    heapless::String::try_from(token.as_str()).ok()
+}
+
+/// True for `POST /setauth` (control password change).
+fn is_setauth_request(request: &str) -> bool {
+    request.starts_with("POST /setauth")
+}
+
+/// The `password` field of a form-encoded `POST /setauth` body, possibly
+/// empty. `None` if it's missing, malformed or too long.
+fn parse_auth_password(request: &str) -> Option<heapless::String<MAX_AUTH_PASSWORD_LEN>> {
+    let (_, body) = request.split_once("\r\n\r\n")?;
+    let form = QueryParams::from_form(body.trim_end_matches('\0'));
+    // One spare byte so an over-long password is rejected, not truncated.
+    let password: heapless::String<{ MAX_AUTH_PASSWORD_LEN + 1 }> = form.decoded_to("password")?;
+    heapless::String::try_from(password.as_str()).ok()
+}
+
+/// True for `GET /clear`, with or without color params.
+fn is_clear_request(request: &str) -> bool {
+    request.contains("GET /clear")
+}
+
+/// True for `GET /color?hex=...`.
+fn is_color_request(request: &str) -> bool {
+    request.contains("GET /color")
+}
+
+/// True for `GET /gradient?from=...&to=...`.
+fn is_gradient_request(request: &str) -> bool {
+    request.contains("GET /gradient")
+}
+
+/// Parse a `RRGGBB` or `#RRGGBB` hex color, repeating each byte to fill
+/// the 16-bit channel (`0xAB` to `0xABAB`). `None` unless exactly six
+/// hex digits follow the optional `#`.
+pub fn parse_hex_color(hex: &str) -> Option<Pixel> {
+    let digits = hex.strip_prefix('#').unwrap_or(hex);
+    if digits.len() != 6 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).map(|c| c as u16 * 0x101);
+    Some([channel(0).ok()?, channel(2).ok()?, channel(4).ok()?])
+}
+
+/// True for `GET /text?...`. The `msg` parameter may appear anywhere in
+/// the query string.
+fn is_text_update_request(request: &str) -> bool {
+    request.contains("GET /text?")
+}
+
+/// True for `POST /text`: the web form, with the same fields as the
+/// `GET` query string in a form-encoded body.
+fn is_text_submit(request: &str) -> bool {
+    request.starts_with("POST /text")
+}
+
+/// True for `GET /brightness?level=...`.
+fn is_brightness_request(request: &str) -> bool {
+    request.contains("GET /brightness")
+}
+
+/// True for `GET /pixel?x=..&y=..`.
+fn is_pixel_request(request: &str) -> bool {
+    request.contains("GET /pixel")
+}
+
+/// Parse the `transition=&ms=` params of `/text`. `transition` is `fade`
+/// or `wipe`; `ms` is its length, `0..=`[`MAX_TRANSITION_MS`], and
+/// defaults to [`DEFAULT_TRANSITION_MS`]. `Some(None)` without
+/// `transition`, `None` if either is invalid.
+fn parse_transition(params: &QueryParams) -> Option<Option<Transition>> {
+    let Some(name) = params.get("transition") else {
+        return Some(None);
+    };
+    let kind = TransitionKind::from_name(name)?;
+    let duration_ms = params
+        .parse_optional::<u32>("ms")?
+        .unwrap_or(DEFAULT_TRANSITION_MS);
+    (duration_ms <= MAX_TRANSITION_MS).then_some(Some(Transition::new(kind, duration_ms)))
+}
+
+/// Parse the `gradient=` param of `/text`, `/marquee` and `/vscroll`:
+/// `rainbow`, one hex color, or two separated by a comma (`ff0000,0000ff`)
+/// to blend between. `Some(None)` without `gradient`, `None` if it is
+/// invalid.
+fn parse_gradient(params: &QueryParams) -> Option<Option<GradientMode>> {
+    let Some(value) = params.decoded("gradient") else {
+        return Some(None);
+    };
+    if value.eq_ignore_ascii_case("rainbow") {
+        return Some(Some(GradientMode::Rainbow));
+    }
+    let gradient = match value.split_once(',') {
+        Some((from, to)) => GradientMode::TwoColor(parse_hex_color(from)?, parse_hex_color(to)?),
+        None => GradientMode::Solid(parse_hex_color(&value)?),
+    };
+    Some(Some(gradient))
+}
+
+/// True for `GET /marquee/stop`.
+fn is_marquee_stop(request: &str) -> bool {
+    request.contains("GET /marquee/stop")
+}
+
+/// True for `GET /anim/stop`.
+fn is_anim_stop(request: &str) -> bool {
+    request.contains("GET /anim/stop")
+}
+
+/// True for `GET /marquee?msg=...`.
+fn is_marquee_request(request: &str) -> bool {
+    request.contains("GET /marquee")
+}
+
+/// Parse `/marquee?msg=&speed=`. `speed` is in pixels per second,
+/// `1..=`[`MAX_MARQUEE_SPEED`], and defaults to
+/// [`DEFAULT_MARQUEE_SPEED`]. `None` without `msg` or with a bad `speed`.
+fn parse_marquee(params: &QueryParams) -> Option<(heapless::String<MAX_MESSAGE_LEN>, u16)> {
+    let text = params.decoded("msg")?;
+    let speed = params
+        .parse_optional::<u16>("speed")?
+        .unwrap_or(DEFAULT_MARQUEE_SPEED);
+    (1..=MAX_MARQUEE_SPEED)
+        .contains(&speed)
+        .then_some((text, speed))
+}
+
+/// True for `GET /vscroll?msg=...`.
+fn is_vscroll_request(request: &str) -> bool {
+    request.starts_with("GET /vscroll")
+}
+
+/// Parse `/vscroll?msg=&line2=&line3=&speed=`: the lines as for `/text`
+/// and a `speed` like `/marquee`'s. An empty `msg` blanks the display.
+/// `None` without `msg` or with a bad `speed`.
+fn parse_vscroll(params: &QueryParams) -> Option<(heapless::String<MAX_MESSAGE_LEN>, u16)> {
+    let msg = params.decoded("msg")?;
+    let speed = params
+        .parse_optional::<u16>("speed")?
+        .unwrap_or(DEFAULT_MARQUEE_SPEED);
+    (1..=MAX_MARQUEE_SPEED)
+        .contains(&speed)
+        .then(|| (join_text_lines(&msg, params), speed))
+}
+
+/// True for `GET /paragraph?msg=...`.
+fn is_paragraph_request(request: &str) -> bool {
+    request.starts_with("GET /paragraph")
+}
+
+/// True for `GET /rich?s=...`.
+fn is_rich_request(request: &str) -> bool {
+    request.starts_with("GET /rich")
+}
+
+/// Longest decoded `s` for `/rich`: a full message with a `|RRGGBB;`
+/// for every segment.
+const MAX_RICH_LEN: usize = MAX_MESSAGE_LEN + MAX_SEGMENTS * 8;
+
+/// Parse `/rich?s=`: segments separated by `;`, each its text, a `|` and
+/// a hex color as for `gradient`, e.g. `Hello|FFFFFF;World|FF0000`.
+/// `None` without `s`, with a segment lacking its color, or with more
+/// text or segments than a [`RichText`] holds.
+fn parse_rich(params: &QueryParams) -> Option<RichText> {
+    let value = params.decoded_to::<MAX_RICH_LEN>("s")?;
+    let mut rich = RichText::new();
+    for segment in value.split(';').filter(|segment| !segment.is_empty()) {
+        let (text, color) = segment.rsplit_once('|')?;
+        if !rich.push(text, parse_hex_color(color)?) {
+            return None;
+        }
+    }
+    Some(rich)
+}
+
+/// True for `GET /region?id=...`.
+fn is_region_request(request: &str) -> bool {
+    request.starts_with("GET /region")
+}
+
+/// Parse `/region?id=&msg=&r=&g=&b=`. `id` is `top` or `bottom`; `msg`
+/// (which may be empty, to blank the region) takes `line2`, `line3`
+/// and `\n` like `/text`, and the optional color stays with the
+/// region. `None` without a known `id` or without `msg`.
+fn parse_region(params: &QueryParams) -> Option<RegionUpdate> {
+    let region = Region::from_name(params.get("id")?)?;
+    let msg = params.decoded("msg")?;
+    Some(RegionUpdate {
+        region,
+        text: join_text_lines(&msg, params),
+        color: extract_query_color(params),
+    })
+}
+
+/// True for `GET /snake/stop`.
+fn is_snake_stop(request: &str) -> bool {
+    request.starts_with("GET /snake/stop")
+}
+
+/// True for `GET /snake`.
+fn is_snake_request(request: &str) -> bool {
+    request.starts_with("GET /snake")
+}
+
+/// True for `GET /input?dir=...`.
+fn is_input_request(request: &str) -> bool {
+    request.starts_with("GET /input")
+}
+
+/// True for `GET /ws`.
+fn is_websocket_request(request: &str) -> bool {
+    request.starts_with("GET /ws ") || request.starts_with("GET /ws?")
+}
+
+/// The `Sec-WebSocket-Key` of a WebSocket upgrade request. `None`
+/// without `Upgrade: websocket` or a plausible key.
+fn websocket_key(request: &str) -> Option<&str> {
+    if !header(request, "upgrade")?.eq_ignore_ascii_case("websocket") {
+        return None;
+    }
+    let key = header(request, "sec-websocket-key")?;
+    (!key.is_empty() && key.len() <= MAX_KEY_LEN).then_some(key)
+}
+
+/// True for `GET /stats`.
+fn is_stats_request(request: &str) -> bool {
+    request.starts_with("GET /stats")
+}
+
+/// True for `GET /status`.
+fn is_status_request(request: &str) -> bool {
+    request.starts_with("GET /status")
+}
+
+/// True for `GET /events`.
+fn is_events_request(request: &str) -> bool {
+    request.starts_with("GET /events")
+}
+
+/// True for `GET /screenshot`.
+fn is_screenshot_request(request: &str) -> bool {
+    request.starts_with("GET /screenshot")
+}
+
+/// Parse `/screenshot?format=`: `png` (the default) or `ppm`. `None` for
+/// any other format.
+fn parse_screenshot_format(params: &QueryParams) -> Option<screenshot::Format> {
+    match params.get("format") {
+        Some(name) => screenshot::Format::from_name(name),
+        None => Some(screenshot::Format::default()),
+    }
+}
+
+/// True for `GET /effect?name=...`.
+fn is_effect_request(request: &str) -> bool {
+    request.contains("GET /effect")
+}
+
+/// True for `GET /graph/push?v=...`. `v` is a reading normalized to
+/// 0..=1; the graph clamps anything outside.
+fn is_graph_push_request(request: &str) -> bool {
+    request.contains("GET /graph/push")
+}
+
+/// True for `GET /timer/pause`.
+fn is_timer_pause(request: &str) -> bool {
+    request.contains("GET /timer/pause")
+}
+
+/// True for `GET /timer/stop`.
+fn is_timer_stop(request: &str) -> bool {
+    request.contains("GET /timer/stop")
+}
+
+/// True for `GET /timer?seconds=...`. `seconds` is
+/// `1..=`[`MAX_TIMER_SECONDS`].
+fn is_timer_request(request: &str) -> bool {
+    request.contains("GET /timer")
+}
+
+/// True for `GET /notify?msg=...&seconds=...` (or `pattern=` instead of
+/// `msg`).
+fn is_notify_request(request: &str) -> bool {
+    request.starts_with("GET /notify")
+}
+
+/// True for `GET /test?pattern=...`.
+fn is_test_pattern_request(request: &str) -> bool {
+    request.starts_with("GET /test?") || request.starts_with("GET /test ")
+}
+
+/// Parse `/effect?name=&speed=`. `speed` is a percentage of the normal
+/// speed, `1..=`[`MAX_EFFECT_SPEED`], and defaults to
+/// [`DEFAULT_EFFECT_SPEED`]. `None` for an unknown name or a bad `speed`.
+fn parse_effect(params: &QueryParams) -> Option<(Effect, u16)> {
+    let effect = Effect::from_name(params.get("name")?)?;
+    Some((effect, parse_effect_speed(params)?))
+}
+
+/// The `speed` of `/effect` and `/life`. `None` if it is bad.
+fn parse_effect_speed(params: &QueryParams) -> Option<u16> {
+    let speed = params
+        .parse_optional::<u16>("speed")?
+        .unwrap_or(DEFAULT_EFFECT_SPEED);
+    (1..=MAX_EFFECT_SPEED).contains(&speed).then_some(speed)
+}
+
+/// True for `GET /life/stop`.
+fn is_life_stop(request: &str) -> bool {
+    request.starts_with("GET /life/stop")
+}
+
+/// True for `GET /life?...`.
+fn is_life_request(request: &str) -> bool {
+    request.starts_with("GET /life")
+}
+
+/// Parse `/life?speed=&wrap=&seed=`: `speed` as for `/effect`, `wrap=0`
+/// for edges where cells die instead of wrapping around, and
+/// `seed=screen` to start from what is shown instead of at random.
+fn parse_life(params: &QueryParams) -> Option<(Effect, u16)> {
+    let effect = Effect::Life {
+        wrap: params.get("wrap") != Some("0"),
+        from_screen: params.get("seed") == Some("screen"),
+    };
+    Some((effect, parse_effect_speed(params)?))
+}
+
+/// True for `GET /mode?m=...`.
+fn is_mode_request(request: &str) -> bool {
+    request.contains("GET /mode")
+}
+
+/// Parse `/mode?m=&seconds=&tz=`. `m` is `text`, `clock` or `graph`
+/// (`seconds=1` adds seconds to the clock); `tz` is a POSIX TZ string. Either may be
+/// left out, but not both. `None` if anything is invalid.
+fn parse_mode(
+    params: &QueryParams,
+) -> Option<(Option<DisplayMode>, Option<heapless::String<MAX_TZ_LEN>>)> {
+    let seconds = params.get("seconds") == Some("1");
+    let mode = match params.get("m") {
+        Some(name) => Some(DisplayMode::from_name(name, seconds)?),
+        None => None,
+    };
+    let timezone = match params.decoded_to::<{ MAX_TZ_LEN + 1 }>("tz") {
+        Some(tz) => {
+            TimeZone::parse(&tz)?;
+            Some(heapless::String::try_from(tz.as_str()).ok()?)
+        }
+        None => None,
+    };
+    (mode.is_some() || timezone.is_some()).then_some((mode, timezone))
+}
+
+/// True for `GET /idle?seconds=...`.
+fn is_idle_request(request: &str) -> bool {
+    request.starts_with("GET /idle")
+}
+
+/// True for `GET /fps?target=...`.
+fn is_fps_request(request: &str) -> bool {
+    request.starts_with("GET /fps")
+}
+
+/// True for `GET /hostname?name=...`.
+fn is_hostname_request(request: &str) -> bool {
+    request.contains("GET /hostname")
+}
+
+/// Parse `/hostname?name=`. `None` unless `name` is a valid `.local`
+/// host label.
+fn parse_hostname(params: &QueryParams) -> Option<heapless::String<MAX_HOSTNAME_LEN>> {
+    let name = params.decoded_to::<{ MAX_HOSTNAME_LEN + 1 }>("name")?;
+    if !mdns_packet::is_valid_hostname(&name) {
+        return None;
+    }
+    heapless::String::try_from(name.as_str()).ok()
+}
+
+/// Parse `/pixel?x=&y=&r=&g=&b=`. `x` and `y` are required and must be on
+/// the panel; color channels are optional (default 0) but must be valid
+/// `u16`s when present. Returns `None` on any malformed input.
+fn parse_pixel(params: &QueryParams<'_>) -> Option<DrawCommand> {
+    let x: usize = params.parse("x")?;
+    let y: usize = params.parse("y")?;
+    if x >= MATRIX_WIDTH || y >= MATRIX_HEIGHT {
+        return None;
+    }
+    let r = params.parse_optional("r")?.unwrap_or(0);
+    let g = params.parse_optional("g")?.unwrap_or(0);
+    let b = params.parse_optional("b")?.unwrap_or(0);
+    Some(DrawCommand::Pixel {
+        x: x as i32,
+        y: y as i32,
+        color: [r, g, b],
+    })
+}
+
+/// The URL-decoded `msg` param of a request (its query string, or the
+/// body of a `POST /text`), if any.
+fn extract_query_message(request: &str) -> Option<heapless::String<MAX_MESSAGE_LEN>> {
+    request_params(request).decoded("msg")
+}
+
+/// The params of `request`: its query string, or the form-encoded body
+/// of a `POST /text`.
+fn request_params(request: &str) -> QueryParams<'_> {
+    match request.split_once("\r\n\r\n") {
+        Some((_, body)) if is_text_submit(request) => {
+            QueryParams::from_form(body.trim_end_matches('\0'))
+        }
+        _ => QueryParams::from_request(request),
+    }
+}
+
+/// Build the display text from `msg` plus the optional `line2` and
+/// `line3` params, one line per `'\n'`. A literal `\n` (backslash, n)
+/// in `msg` also starts a new line. Lines that would overflow
+/// [`MAX_MESSAGE_LEN`] are dropped.
+fn join_text_lines(msg: &str, params: &QueryParams<'_>) -> heapless::String<MAX_MESSAGE_LEN> {
+    let mut text = heapless::String::new();
+    for (i, line) in msg.split("\\n").enumerate() {
+        if i > 0 {
+            let _ = text.push('\n');
+        }
+        let _ = text.push_str(line);
+    }
+    for name in ["line2", "line3"] {
+        if let Some(line) = params.decoded(name)
+            && text.len() + 1 + line.len() <= MAX_MESSAGE_LEN
+        {
+            let _ = text.push('\n');
+            let _ = text.push_str(&line);
+        }
+    }
+    text
+}
+
+/// Extract an optional text color from the `r`, `g` and `b` query
+/// parameters. Returns `None` when none of them is present; a missing
+/// channel alongside a present one defaults to 0. Channels are 16-bit
+/// (0..=65535) to match the panel's PWM depth.
+fn extract_query_color(params: &QueryParams<'_>) -> Option<Pixel> {
+    let r = params.parse("r");
+    let g = params.parse("g");
+    let b = params.parse("b");
+    if r.is_none() && g.is_none() && b.is_none() {
+        return None;
+    }
+    Some([r.unwrap_or(0), g.unwrap_or(0), b.unwrap_or(0)])
+}
+
+/// Maximum number of query parameters kept per request. Extra ones are
+/// ignored.
+pub const MAX_QUERY_PARAMS: usize = 8;
+
+/// Query-string parameters of a request line, split on `&` and `=`.
+///
+/// Fixed capacity ([`MAX_QUERY_PARAMS`]) and borrowed from the request
+/// buffer, so parsing never allocates. Values are stored still
+/// URL-encoded; use [`Self::decoded`] for text and [`Self::parse`] for
+/// numbers. If a key repeats, the first occurrence wins. A parameter
+/// without `=` (e.g. `?flag`) has an empty value.
+pub struct QueryParams<'a> {
+    pairs: heapless::Vec<(&'a str, &'a str), MAX_QUERY_PARAMS>,
+}
+
+impl<'a> QueryParams<'a> {
+    /// Parse the query string out of the first line of `request`
+    /// (`GET /path?a=1&b=2 HTTP/1.1`). Requests without a `?` yield an
+    /// empty set.
+    pub fn from_request(request: &'a str) -> Self {
+        let query = request
+            .split(['\r', '\n'])
+            .next()
+            .and_then(|line| line.split(' ').nth(1))
+            .and_then(|target| target.split_once('?'))
+            .map_or("", |(_, query)| query);
+        Self::from_form(query)
+    }
+
+    /// Parse a bare `a=1&b=2` string, such as a query string or an
+    /// `application/x-www-form-urlencoded` POST body.
+    pub fn from_form(form: &'a str) -> Self {
+        let mut pairs = heapless::Vec::new();
+        for pair in form.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            if pairs.push((key, value)).is_err() {
+                break;
+            }
+        }
+        Self { pairs }
+    }
+
+    /// Raw (still URL-encoded) value of `name`, if present.
+    pub fn get(&self, name: &str) -> Option<&'a str> {
+        self.pairs
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| *value)
+    }
+
+    /// URL-decoded value of `name`, capped at [`MAX_MESSAGE_LEN`]
+    /// characters. `None` if absent or the encoding is malformed.
+    pub fn decoded(&self, name: &str) -> Option<heapless::String<MAX_MESSAGE_LEN>> {
+        url_decode(self.get(name)?)
+    }
+
+    /// Like [`Self::decoded`], capped at `N` characters instead.
+    pub fn decoded_to<const N: usize>(&self, name: &str) -> Option<heapless::String<N>> {
+        url_decode_to(self.get(name)?)
+    }
+
+    /// Value of `name` parsed as `T`. `None` if absent or malformed.
+    pub fn parse<T: core::str::FromStr>(&self, name: &str) -> Option<T> {
+        self.get(name)?.parse().ok()
+    }
+
+    /// Parse an optional parameter. The outer `Option` is `None` when the
+    /// parameter is present but malformed; the inner one is `None` when it
+    /// is absent.
+    pub fn parse_optional<T: core::str::FromStr>(&self, name: &str) -> Option<Option<T>> {
+        match self.get(name) {
+            Some(value) => value.parse().ok().map(Some),
+            None => Some(None),
+        }
+    }
+
+    /// Number of parameters parsed.
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    /// True when the request had no query parameters.
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+}
+
+/// Percent-decode a URL-encoded string, capped at [`MAX_MESSAGE_LEN`]
+/// characters. Stops at the first character that would overflow.
+fn url_decode(encoded: &str) -> Option<heapless::String<MAX_MESSAGE_LEN>> {
+    url_decode_to(encoded)
+}
+
+/// [`url_decode`] with a caller-chosen capacity of `N` bytes.
+/// Percent-escaped UTF-8 sequences decode to one character (`%C3%A4` is
+/// `ä`); an escaped byte that doesn't start one is taken as Latin-1.
+fn url_decode_to<const N: usize>(encoded: &str) -> Option<heapless::String<N>> {
+    let mut out = heapless::String::new();
+    let mut chars = encoded.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let decoded_char = match c {
+            '%' => {
+                let byte = decode_percent(&mut chars)?;
+                let mut ahead = chars.clone();
+                match decode_utf8_escapes(byte, &mut ahead) {
+                    Some(ch) => {
+                        chars = ahead;
+                        ch
+                    }
+                    None => char::from(byte),
+                }
+            }
+            '+' => ' ',
+            other => other,
+        };
+        if out.push(decoded_char).is_err() {
+            // Buffer full — stop here. Caller sees a truncated message
+            // rather than no message at all.
+            break;
+        }
+    }
+
+    Some(out)
+}
+
+/// Decode the two hex digits of a `%XX` percent-escape.
+fn decode_percent(chars: &mut core::iter::Peekable<core::str::Chars<'_>>) -> Option<u8> {
+    let h = chars.next()?.to_digit(16)?;
+    let l = chars.next()?.to_digit(16)?;
+    Some((h * 16 + l) as u8)
+}
+
+/// The character whose UTF-8 encoding starts with the escaped byte
+/// `lead` and continues with the next escapes in `chars`, or `None` if
+/// they don't form a valid sequence.
+fn decode_utf8_escapes(
+    lead: u8,
+    chars: &mut core::iter::Peekable<core::str::Chars<'_>>,
+) -> Option<char> {
+    let len = match lead {
+        0x00..=0x7F => 1,
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => return None,
+    };
+    let mut bytes = [lead, 0, 0, 0];
+    for byte in &mut bytes[1..len] {
+        if chars.next()? != '%' {
+            return None;
+        }
+        *byte = decode_percent(chars)?;
+    }
+    core::str::from_utf8(&bytes[..len]).ok()?.chars().next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A POST to `path` announcing a `len`-byte body, of which `body`
+    /// has arrived.
+    fn post(path: &str, len: usize, body: &str) -> heapless::String<128> {
+        let mut request = heapless::String::new();
+        write!(
+            request,
+            "POST {path} HTTP/1.1\r\nContent-Length: {len}\r\n\r\n{body}"
+        )
+        .unwrap();
+        request
+    }
+
+    #[test]
+    fn root_request_matches() {
+        assert!(is_root_request("GET / HTTP/1.1"));
+        assert!(is_root_request("GET / "));
+        assert!(!is_root_request("GET /text HTTP/1.1"));
+    }
+
+    #[test]
+    fn clear_request_matches() {
+        assert!(is_clear_request("GET /clear HTTP/1.1"));
+        assert!(!is_clear_request("GET / HTTP/1.1"));
+    }
+
+    #[test]
+    fn text_update_request_matches() {
+        assert!(is_text_update_request("GET /text?msg=hi HTTP/1.1"));
+        assert!(!is_text_update_request("GET / HTTP/1.1"));
+    }
+
+    #[test]
+    fn url_decode_passes_plain_text() {
+        let decoded = url_decode("hello").unwrap();
+        assert_eq!(decoded.as_str(), "hello");
+    }
+
+    #[test]
+    fn url_decode_handles_plus_sign() {
+        let decoded = url_decode("hello+world").unwrap();
+        assert_eq!(decoded.as_str(), "hello world");
+    }
+
+    #[test]
+    fn url_decode_handles_percent_escapes() {
+        let decoded = url_decode("hello%20world").unwrap();
+        assert_eq!(decoded.as_str(), "hello world");
+    }
+
+    #[test]
+    fn url_decode_handles_uppercase_hex() {
+        let decoded = url_decode("%2A").unwrap();
+        assert_eq!(decoded.as_str(), "*");
+    }
+
+    #[test]
+    fn url_decode_stops_on_truncated_escape() {
+        // '%2' is incomplete — decoder returns None.
+        assert!(url_decode("%2").is_none());
+    }
+
+    #[test]
+    fn url_decode_handles_utf8_escapes() {
+        let decoded = url_decode("h%C3%A4%C3%A4t+%C3%85bo+%E2%82%AC").unwrap();
+        assert_eq!(decoded.as_str(), "h\u{e4}\u{e4}t \u{c5}bo \u{20ac}");
+    }
+
+    #[test]
+    fn url_decode_takes_stray_bytes_as_latin1() {
+        // A Latin-1 form (`%E4` = ä) and a lead byte with no continuation.
+        let decoded = url_decode("%E4%C3x%C3").unwrap();
+        assert_eq!(decoded.as_str(), "\u{e4}\u{c3}x\u{c3}");
+    }
+
+    #[test]
+    fn url_decode_handles_mixed_input() {
+        let decoded = url_decode("a%20b+c%21").unwrap();
+        assert_eq!(decoded.as_str(), "a b c!");
+    }
+
+    #[test]
+    fn extract_query_message_basic() {
+        let msg = extract_query_message("GET /text?msg=hi HTTP/1.1").unwrap();
+        assert_eq!(msg.as_str(), "hi");
+    }
+
+    #[test]
+    fn extract_query_message_decoded() {
+        let msg = extract_query_message("GET /text?msg=hello+world HTTP/1.1").unwrap();
+        assert_eq!(msg.as_str(), "hello world");
+    }
+
+    #[test]
+    fn extract_query_message_missing_returns_none() {
+        assert!(extract_query_message("GET / HTTP/1.1").is_none());
+    }
+
+    #[test]
+    fn extract_query_message_stops_at_next_param() {
+        let msg = extract_query_message("GET /text?msg=hi&r=1 HTTP/1.1").unwrap();
+        assert_eq!(msg.as_str(), "hi");
+    }
+
+    #[test]
+    fn extract_query_message_not_first_param() {
+        let msg = extract_query_message("GET /text?foo=1&msg=hello HTTP/1.1").unwrap();
+        assert_eq!(msg.as_str(), "hello");
+        let msg = extract_query_message("GET /text?xmsg=no&msg=yes HTTP/1.1").unwrap();
+        assert_eq!(msg.as_str(), "yes");
+    }
+
+    #[test]
+    fn extract_query_color_missing_returns_none() {
+        let params = QueryParams::from_request("GET /text?msg=hi HTTP/1.1");
+        assert!(extract_query_color(&params).is_none());
+    }
+
+    #[test]
+    fn extract_query_color_defaults_missing_channels_to_zero() {
+        let params = QueryParams::from_request("GET /text?msg=hi&r=65535 HTTP/1.1");
+        assert_eq!(extract_query_color(&params), Some([0xFFFF, 0, 0]));
+    }
+
+    #[test]
+    fn extract_query_color_ignores_malformed_channel() {
+        let params = QueryParams::from_request("GET /text?msg=hi&r=red&g=7 HTTP/1.1");
+        assert_eq!(extract_query_color(&params), Some([0, 7, 0]));
+    }
+
+    #[test]
+    fn query_params_splits_all_pairs() {
+        let params =
+            QueryParams::from_request("GET /x?a=1&b=two&flag&c=%20 HTTP/1.1\r\nHost: h\r\n");
+        assert_eq!(params.len(), 4);
+        assert_eq!(params.get("a"), Some("1"));
+        assert_eq!(params.get("b"), Some("two"));
+        assert_eq!(params.get("flag"), Some(""));
+        assert_eq!(params.decoded("c").unwrap().as_str(), " ");
+        assert_eq!(params.parse::<u8>("a"), Some(1));
+        assert_eq!(params.get("missing"), None);
+    }
+
+    #[test]
+    fn query_params_without_query_is_empty() {
+        assert!(QueryParams::from_request("GET / HTTP/1.1").is_empty());
+        assert!(QueryParams::from_request("").is_empty());
+        // The query is only read from the request line, not the headers.
+        assert!(QueryParams::from_request("GET / HTTP/1.1\r\nReferer: /a?b=c\r\n").is_empty());
+    }
+
+    #[test]
+    fn query_params_first_duplicate_wins() {
+        let params = QueryParams::from_request("GET /x?a=1&a=2 HTTP/1.1");
+        assert_eq!(params.get("a"), Some("1"));
+    }
+
+    #[test]
+    fn query_params_caps_at_capacity() {
+        let params =
+            QueryParams::from_request("GET /x?a=1&b=2&c=3&d=4&e=5&f=6&g=7&h=8&i=9 HTTP/1.1");
+        assert_eq!(params.len(), MAX_QUERY_PARAMS);
+        assert!(params.get("i").is_none());
+    }
+
+    #[test]
+    fn query_params_parse_optional() {
+        let params = QueryParams::from_request("GET /x?a=1&b=z HTTP/1.1");
+        assert_eq!(params.parse_optional::<u8>("a"), Some(Some(1)));
+        assert_eq!(params.parse_optional::<u8>("b"), None);
+        assert_eq!(params.parse_optional::<u8>("c"), Some(None));
+    }
+
+    #[test]
+    fn dispatch_root_returns_html_no_text() {
+        let resp = dispatch(b"GET / HTTP/1.1");
+        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+        assert!(resp.display_text.is_none());
+    }
+
+    #[test]
+    fn dispatch_clear_returns_html_and_empty_text() {
+        let resp = dispatch(b"GET /clear HTTP/1.1");
+        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+        assert_eq!(resp.display_text.unwrap().as_str(), "");
+        assert_eq!(resp.draw, Some(DrawCommand::Fill { color: [0, 0, 0] }));
+    }
+
+    #[test]
+    fn dispatch_clear_with_color_queues_fill() {
+        let resp = dispatch(b"GET /clear?r=10&b=30 HTTP/1.1");
+        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+        assert_eq!(resp.display_text.unwrap().as_str(), "");
+        assert_eq!(resp.draw, Some(DrawCommand::Fill { color: [10, 0, 30] }));
+    }
+
+    #[test]
+    fn parse_hex_color_expands_each_byte() {
+        assert_eq!(parse_hex_color("00FF80"), Some([0, 0xFFFF, 0x8080]));
+        assert_eq!(parse_hex_color("#0a0B0c"), Some([0x0A0A, 0x0B0B, 0x0C0C]));
+        for bad in ["", "#", "FFF", "#FFFFFFF", "GG0000", "+12345", "##123456"] {
+            assert_eq!(parse_hex_color(bad), None, "{bad}");
+        }
+    }
+
+    #[test]
+    fn dispatch_color_fills_the_display() {
+        for req in [
+            &b"GET /color?hex=0000FF HTTP/1.1"[..],
+            b"GET /color?hex=%230000ff HTTP/1.1",
+        ] {
+            let resp = dispatch(req);
+            assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+            assert_eq!(resp.display_text.unwrap().as_str(), "");
+            assert_eq!(
+                resp.draw,
+                Some(DrawCommand::Fill {
+                    color: [0, 0, 0xFFFF]
+                })
+            );
+        }
+        for req in [
+            &b"GET /color HTTP/1.1"[..],
+            b"GET /color?hex=blue HTTP/1.1",
+            b"GET /color?hex=00F HTTP/1.1",
+        ] {
+            let resp = dispatch(req);
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert!(resp.draw.is_none());
+        }
+    }
+
+    #[test]
+    fn dispatch_gradient_fills_the_display() {
+        let resp = dispatch(b"GET /gradient?from=%23ff0000&to=0000ff&dir=diagonal HTTP/1.1");
+        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+        assert_eq!(resp.display_text.unwrap().as_str(), "");
+        assert_eq!(
+            resp.draw,
+            Some(DrawCommand::Gradient {
+                from: [0xFFFF, 0, 0],
+                to: [0, 0, 0xFFFF],
+                dir: GradientDir::Diagonal,
+            })
+        );
+        let resp = dispatch(b"GET /gradient?from=000000&to=ffffff HTTP/1.1");
+        assert!(matches!(
+            resp.draw,
+            Some(DrawCommand::Gradient {
+                dir: GradientDir::Horizontal,
+                ..
+            })
+        ));
+        for req in [
+            &b"GET /gradient?from=000000 HTTP/1.1"[..],
+            b"GET /gradient?from=000000&to=fff HTTP/1.1",
+            b"GET /gradient?from=000000&to=ffffff&dir=radial HTTP/1.1",
+        ] {
+            let resp = dispatch(req);
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert!(resp.draw.is_none());
+        }
+    }
+
+    #[test]
+    fn dispatch_text_update_returns_html_and_decoded_text() {
+        let resp = dispatch(b"GET /text?msg=hi HTTP/1.1");
+        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+        assert_eq!(resp.display_text.unwrap().as_str(), "hi");
+    }
+
+    #[test]
+    fn dispatch_text_update_carries_color() {
+        let resp = dispatch(b"GET /text?msg=ok&r=0&g=65535&b=0 HTTP/1.1");
+        assert_eq!(resp.display_text.unwrap().as_str(), "ok");
+        assert_eq!(resp.text_color, Some([0, 0xFFFF, 0]));
+    }
+
+    #[test]
+    fn dispatch_text_splits_lines() {
+        let resp = dispatch(b"GET /text?msg=CPU+42%25%0AMEM+7%25 HTTP/1.1");
+        assert_eq!(resp.display_text.unwrap().as_str(), "CPU 42%\nMEM 7%");
+        let resp = dispatch(b"GET /text?msg=ONE%5CnTWO HTTP/1.1");
+        assert_eq!(resp.display_text.unwrap().as_str(), "ONE\nTWO");
+    }
+
+    #[test]
+    fn dispatch_text_appends_line_params() {
+        let resp = dispatch(b"GET /text?msg=A&line3=C&line2=B HTTP/1.1");
+        assert_eq!(resp.display_text.unwrap().as_str(), "A\nB\nC");
+        // A line that would overflow the message buffer is dropped.
+        let resp =
+            dispatch(b"GET /text?msg=ABCDEFGHIJKLMNOPQRSTUVWXYZ&line2=TOOLONG&line3=OK HTTP/1.1");
+        assert_eq!(
+            resp.display_text.unwrap().as_str(),
+            "ABCDEFGHIJKLMNOPQRSTUVWXYZ\nOK"
+        );
+    }
+
+    #[test]
+    fn dispatch_text_accepts_a_form_body() {
+        let resp = dispatch(
+            b"POST /text HTTP/1.1\r\nContent-Type: application/x-www-form-urlencoded\r\n\
+              Content-Length: 26\r\n\r\nmsg=h%C3%A4+hi&line2=there",
+        );
+        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+        assert_eq!(resp.display_text.unwrap().as_str(), "h\u{e4} hi\nthere");
+        // The query string of a POST is not read.
+        let resp = dispatch(b"POST /text?msg=hi HTTP/1.1\r\nContent-Length: 0\r\n\r\n");
+        assert!(resp.display_text.is_none());
+    }
+
+    #[test]
+    fn dispatch_text_carries_transition() {
+        let resp = dispatch(b"GET /text?msg=hi HTTP/1.1");
+        assert_eq!(resp.transition, None);
+        let resp = dispatch(b"GET /text?msg=hi&transition=fade HTTP/1.1");
+        assert_eq!(
+            resp.transition,
+            Some(Transition::new(TransitionKind::Fade, DEFAULT_TRANSITION_MS))
+        );
+        let resp = dispatch(b"GET /text?msg=hi&transition=wipe&ms=2000 HTTP/1.1");
+        assert_eq!(resp.display_text.unwrap().as_str(), "hi");
+        assert_eq!(
+            resp.transition,
+            Some(Transition::new(TransitionKind::Wipe, 2000))
+        );
+    }
+
+    #[test]
+    fn dispatch_text_rejects_bad_transition() {
+        for req in [
+            &b"GET /text?msg=hi&transition=spin HTTP/1.1"[..],
+            b"GET /text?msg=hi&transition=fade&ms=slow HTTP/1.1",
+            b"GET /text?msg=hi&transition=fade&ms=10001 HTTP/1.1",
+        ] {
+            let resp = dispatch(req);
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert!(resp.display_text.is_none());
+        }
+    }
+
+    #[test]
+    fn dispatch_scroll_carries_gradient() {
+        let resp = dispatch(b"GET /text?msg=hi HTTP/1.1");
+        assert_eq!(resp.gradient, None);
+        let resp = dispatch(b"GET /text?msg=hi&gradient=rainbow HTTP/1.1");
+        assert_eq!(resp.gradient, Some(GradientMode::Rainbow));
+        let resp = dispatch(b"GET /marquee?msg=hi&gradient=%23ff0000%2C0000ff HTTP/1.1");
+        assert_eq!(
+            resp.gradient,
+            Some(GradientMode::TwoColor([0xFFFF, 0, 0], [0, 0, 0xFFFF]))
+        );
+        let resp = dispatch(b"GET /vscroll?msg=hi&gradient=00ff00 HTTP/1.1");
+        assert_eq!(resp.gradient, Some(GradientMode::Solid([0, 0xFFFF, 0])));
+        for req in [
+            &b"GET /text?msg=hi&gradient=plaid HTTP/1.1"[..],
+            b"GET /marquee?msg=hi&gradient=ff0000, HTTP/1.1",
+            b"GET /vscroll?msg=hi&gradient=ff0000,00ff00,0000ff HTTP/1.1",
+        ] {
+            let resp = dispatch(req);
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert!(resp.display_text.is_none());
+        }
+    }
+
+    #[test]
+    fn dispatch_rich_colors_each_segment() {
+        let resp = dispatch(b"GET /rich?s=Hello%20|FFFFFF;World|%23FF0000 HTTP/1.1");
+        assert_eq!(resp.display_text.as_deref(), Some("Hello World"));
+        let rich = resp.rich_text.unwrap();
+        assert_eq!(
+            rich.segments(),
+            [("Hello ", [0xFFFF; 3]), ("World", [0xFFFF, 0, 0])]
+        );
+        assert!(dispatch(b"GET /text?msg=hi HTTP/1.1").rich_text.is_none());
+        for req in [
+            &b"GET /rich HTTP/1.1"[..],
+            b"GET /rich?s=Hello HTTP/1.1",
+            b"GET /rich?s=Hello|red HTTP/1.1",
+            b"GET /rich?s=a|000000;b|000000;c|000000;d|000000;e|000000;f|000000;g|000000;h|000000;i|000000 HTTP/1.1",
+        ] {
+            let resp = dispatch(req);
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert!(resp.display_text.is_none());
+        }
+    }
+
+    #[test]
+    fn dispatch_region_updates_one_region() {
+        let resp = dispatch(b"GET /region?id=top&msg=12%3A00&r=65535 HTTP/1.1");
+        assert_eq!(resp.mode, Some(DisplayMode::Regions));
+        assert_eq!(
+            resp.region,
+            Some(RegionUpdate {
+                region: Region::Top,
+                text: "12:00".try_into().unwrap(),
+                color: Some([0xFFFF, 0, 0]),
+            })
+        );
+        assert!(resp.display_text.is_none());
+        let resp = dispatch(b"GET /region?id=bottom&msg=a&line2=b HTTP/1.1");
+        let update = resp.region.unwrap();
+        assert_eq!(
+            (update.region, update.text.as_str()),
+            (Region::Bottom, "a\nb")
+        );
+        assert_eq!(update.color, None);
+        for req in [
+            &b"GET /region?msg=hi HTTP/1.1"[..],
+            b"GET /region?id=middle&msg=hi HTTP/1.1",
+            b"GET /region?id=top HTTP/1.1",
+        ] {
+            let resp = dispatch(req);
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert!(resp.region.is_none() && resp.mode.is_none());
+        }
+    }
+
+    #[test]
+    fn dispatch_snake_starts_steers_and_stops() {
+        let resp = dispatch(b"GET /snake HTTP/1.1");
+        assert_eq!(resp.mode, Some(DisplayMode::Snake));
+        assert_eq!(resp.game_input, Some(GameInput::NewGame));
+        let resp = dispatch(b"GET /input?dir=left HTTP/1.1");
+        assert_eq!(resp.body, NO_CONTENT_RESPONSE);
+        assert_eq!(resp.game_input, Some(GameInput::Steer(Direction::Left)));
+        assert!(resp.mode.is_none() && resp.page.is_empty());
+        for req in [&b"GET /input HTTP/1.1"[..], b"GET /input?dir=back HTTP/1.1"] {
+            let resp = dispatch(req);
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert!(resp.game_input.is_none());
+        }
+        let resp = dispatch(b"GET /snake/stop HTTP/1.1");
+        assert_eq!(resp.mode, Some(DisplayMode::Text));
+        assert!(resp.game_input.is_none());
+    }
+
+    #[test]
+    fn dispatch_brightness_sets_level() {
+        let resp = dispatch(b"GET /brightness?level=64 HTTP/1.1");
+        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+        assert_eq!(resp.brightness, Some(64));
+        assert!(resp.display_text.is_none());
+    }
+
+    #[test]
+    fn dispatch_brightness_rejects_bad_level() {
+        for req in [
+            &b"GET /brightness HTTP/1.1"[..],
+            b"GET /brightness?level=256 HTTP/1.1",
+            b"GET /brightness?level=dim HTTP/1.1",
+        ] {
+            let resp = dispatch(req);
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert!(resp.brightness.is_none());
+        }
+    }
+
+    #[test]
+    fn dispatch_marquee_sets_text_and_speed() {
+        let resp = dispatch(b"GET /marquee?msg=WELCOME+TO+THE+LAB&speed=60 HTTP/1.1");
+        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+        assert_eq!(resp.display_text.unwrap().as_str(), "WELCOME TO THE LAB");
+        assert_eq!(resp.mode, Some(DisplayMode::Marquee { speed: 60 }));
+        assert!(resp.text_color.is_none());
+
+        let resp = dispatch(b"GET /marquee?msg=HI&g=65535 HTTP/1.1");
+        assert_eq!(
+            resp.mode,
+            Some(DisplayMode::Marquee {
+                speed: DEFAULT_MARQUEE_SPEED
+            })
+        );
+        assert_eq!(resp.text_color, Some([0, 0xFFFF, 0]));
+    }
+
+    #[test]
+    fn dispatch_marquee_rejects_bad_params() {
+        for req in [
+            &b"GET /marquee HTTP/1.1"[..],
+            b"GET /marquee?speed=10 HTTP/1.1",
+            b"GET /marquee?msg=HI&speed=0 HTTP/1.1",
+            b"GET /marquee?msg=HI&speed=501 HTTP/1.1",
+            b"GET /marquee?msg=HI&speed=fast HTTP/1.1",
+        ] {
+            let resp = dispatch(req);
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert!(resp.mode.is_none());
+            assert!(resp.display_text.is_none());
+        }
+    }
+
+    #[test]
+    fn dispatch_vscroll_sets_lines_and_speed() {
+        let resp = dispatch(b"GET /vscroll?msg=NEWS&line2=MORE&speed=20 HTTP/1.1");
+        assert_eq!(resp.mode, Some(DisplayMode::VScroll { speed: 20 }));
+        assert_eq!(resp.display_text.unwrap().as_str(), "NEWS\nMORE");
+
+        let resp = dispatch(b"GET /vscroll?msg= HTTP/1.1");
+        assert_eq!(
+            resp.mode,
+            Some(DisplayMode::VScroll {
+                speed: DEFAULT_MARQUEE_SPEED
+            })
+        );
+        assert_eq!(resp.display_text.unwrap().as_str(), "");
+
+        for req in [
+            &b"GET /vscroll HTTP/1.1"[..],
+            b"GET /vscroll?msg=HI&speed=0 HTTP/1.1",
+        ] {
+            assert_eq!(dispatch(req).body, BAD_REQUEST_RESPONSE);
+        }
+    }
+
+    #[test]
+    fn dispatch_paragraph_sets_text_and_mode() {
+        let resp = dispatch(b"GET /paragraph?msg=Back%20in%20five&line2=Desk%203&r=65535 HTTP/1.1");
+        assert_eq!(resp.mode, Some(DisplayMode::Paragraph));
+        assert_eq!(resp.display_text.unwrap().as_str(), "Back in five\nDesk 3");
+        assert_eq!(resp.text_color, Some([65535, 0, 0]));
+        assert_eq!(
+            dispatch(b"GET /paragraph HTTP/1.1").body,
+            BAD_REQUEST_RESPONSE
+        );
+    }
+
+    #[test]
+    fn dispatch_marquee_stop_returns_to_text() {
+        let resp = dispatch(b"GET /marquee/stop HTTP/1.1");
+        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+        assert_eq!(resp.mode, Some(DisplayMode::Text));
+        assert!(resp.display_text.is_none());
+    }
+
+    #[test]
+    fn dispatch_stats_asks_for_json() {
+        let resp = dispatch(b"GET /stats HTTP/1.1\r\nHost: x\r\n\r\n");
+        assert_eq!(resp.body, JSON_OK_RESPONSE);
+        assert!(resp.stats);
+        assert!(resp.page.is_empty());
+        assert!(!dispatch(b"GET / HTTP/1.1").stats);
+    }
+
+    #[test]
+    fn dispatch_status_asks_for_json() {
+        let resp = dispatch(b"GET /status HTTP/1.1\r\nHost: x\r\n\r\n");
+        assert_eq!(resp.body, JSON_OK_RESPONSE);
+        assert!(resp.status && !resp.stats);
+        assert!(!dispatch(b"GET /stats HTTP/1.1").status);
+    }
+
+    #[test]
+    fn dispatch_events_opens_a_stream() {
+        let resp = dispatch(b"GET /events HTTP/1.1\r\nAccept: text/event-stream\r\n\r\n");
+        assert_eq!(resp.body, EVENTS_RESPONSE);
+        assert!(resp.events && resp.page.is_empty());
+        assert!(!is_command(&resp));
+        assert!(!dispatch(b"GET /status HTTP/1.1").events);
+    }
+
+    #[test]
+    fn dispatch_screenshot_picks_the_format() {
+        let resp = dispatch(b"GET /screenshot HTTP/1.1\r\n\r\n");
+        assert_eq!(resp.screenshot, Some(screenshot::Format::Png));
+        assert!(resp.body.is_empty() && resp.page.is_empty());
+        let resp = dispatch(b"GET /screenshot?format=ppm HTTP/1.1\r\n\r\n");
+        assert_eq!(resp.screenshot, Some(screenshot::Format::Ppm));
+        let resp = dispatch(b"GET /screenshot?format=gif HTTP/1.1\r\n\r\n");
+        assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+        assert!(resp.screenshot.is_none());
+    }
+
+    #[test]
+    fn dispatch_websocket_upgrade_returns_accept_key() {
+        let resp = dispatch(
+            b"GET /ws HTTP/1.1\r\nHost: x\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+              Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        );
+        assert_eq!(resp.body, WEBSOCKET_UPGRADE_RESPONSE);
+        assert!(resp.page.is_empty());
+        assert_eq!(
+            resp.websocket.unwrap().as_str(),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn dispatch_websocket_rejects_plain_get() {
+        for req in [
+            &b"GET /ws HTTP/1.1\r\nHost: x\r\n\r\n"[..],
+            b"GET /ws HTTP/1.1\r\nUpgrade: websocket\r\n\r\n",
+            b"GET /ws HTTP/1.1\r\nUpgrade: h2c\r\nSec-WebSocket-Key: abc\r\n\r\n",
+        ] {
+            let resp = dispatch(req);
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert!(resp.websocket.is_none());
+        }
+    }
+
+    #[test]
+    fn dispatch_effect_starts_rainbow() {
+        let resp = dispatch(b"GET /effect?name=rainbow HTTP/1.1");
+        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+        assert_eq!(
+            resp.mode,
+            Some(DisplayMode::Effect {
+                effect: Effect::Rainbow,
+                speed: DEFAULT_EFFECT_SPEED
+            })
+        );
+    }
+
+    #[test]
+    fn dispatch_life_starts_and_stops() {
+        let resp = dispatch(b"GET /life HTTP/1.1");
+        assert_eq!(
+            resp.mode,
+            Some(DisplayMode::Effect {
+                effect: Effect::Life {
+                    wrap: true,
+                    from_screen: false
+                },
+                speed: DEFAULT_EFFECT_SPEED
+            })
+        );
+        let resp = dispatch(b"GET /life?speed=300&wrap=0&seed=screen HTTP/1.1");
+        assert_eq!(
+            resp.mode,
+            Some(DisplayMode::Effect {
+                effect: Effect::Life {
+                    wrap: false,
+                    from_screen: true
+                },
+                speed: 300
+            })
+        );
+        let resp = dispatch(b"GET /life/stop HTTP/1.1");
+        assert_eq!(resp.mode, Some(DisplayMode::Text));
+        let resp = dispatch(b"GET /life?speed=0 HTTP/1.1");
+        assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+    }
+
+    #[test]
+    fn dispatch_effect_takes_a_speed() {
+        let resp = dispatch(b"GET /effect?name=fire&speed=250 HTTP/1.1");
+        assert_eq!(
+            resp.mode,
+            Some(DisplayMode::Effect {
+                effect: Effect::Fire,
+                speed: 250
+            })
+        );
+    }
+
+    #[test]
+    fn dispatch_timer_starts_pauses_and_stops() {
+        let resp = dispatch(b"GET /timer?seconds=300 HTTP/1.1");
+        assert_eq!(resp.body, OK_HTML_RESPONSE);
+        assert_eq!(resp.mode, Some(DisplayMode::Timer));
+        assert_eq!(resp.timer, Some(TimerCommand::Start(300)));
+
+        let resp = dispatch(b"GET /timer/pause HTTP/1.1");
+        assert_eq!(resp.timer, Some(TimerCommand::Pause));
+        assert_eq!(resp.mode, None);
+        let resp = dispatch(b"GET /timer/stop HTTP/1.1");
+        assert_eq!(resp.timer, None);
+        assert_eq!(resp.mode, Some(DisplayMode::Text));
+
+        for req in [
+            &b"GET /timer HTTP/1.1"[..],
+            b"GET /timer?seconds=0 HTTP/1.1",
+            b"GET /timer?seconds=6000 HTTP/1.1",
+            b"GET /timer?seconds=5m HTTP/1.1",
+        ] {
+            let resp = dispatch(req);
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert_eq!(resp.timer, None);
+        }
+    }
+
+    #[test]
+    fn dispatch_graph_push_adds_a_sample() {
+        let resp = dispatch(b"GET /graph/push?v=0.25 HTTP/1.1");
+        assert_eq!(resp.body, OK_HTML_RESPONSE);
+        assert_eq!(resp.mode, Some(DisplayMode::Graph));
+        assert_eq!(resp.graph_sample, Some(0.25));
+        // Out of range is clamped later, not rejected.
+        assert_eq!(
+            dispatch(b"GET /graph/push?v=7 HTTP/1.1").graph_sample,
+            Some(7.0)
+        );
+        for req in [
+            &b"GET /graph/push HTTP/1.1"[..],
+            b"GET /graph/push?v=high HTTP/1.1",
+            b"GET /graph/push?v=NaN HTTP/1.1",
+            b"GET /graph/push?v=inf HTTP/1.1",
+        ] {
+            let resp = dispatch(req);
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert!(resp.mode.is_none());
+        }
+    }
+
+    #[test]
+    fn dispatch_test_pattern() {
+        let resp = dispatch(b"GET /test?pattern=bars HTTP/1.1");
+        assert_eq!(resp.body, OK_HTML_RESPONSE);
+        assert_eq!(resp.mode, Some(DisplayMode::Test(TestPattern::Bars)));
+        for req in [
+            &b"GET /test HTTP/1.1"[..],
+            b"GET /test?pattern=plaid HTTP/1.1",
+        ] {
+            assert_eq!(dispatch(req).body, BAD_REQUEST_RESPONSE);
+        }
+        // `/text` isn't mistaken for it.
+        assert!(dispatch(b"GET /text?msg=HI HTTP/1.1").mode.is_none());
+    }
+
+    #[test]
+    fn dispatch_effect_rejects_unknown_name() {
+        for req in [
+            &b"GET /effect HTTP/1.1"[..],
+            b"GET /effect?name=sparkle HTTP/1.1",
+            b"GET /effect?name=fire&speed=0 HTTP/1.1",
+            b"GET /effect?name=fire&speed=1001 HTTP/1.1",
+        ] {
+            let resp = dispatch(req);
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert!(resp.mode.is_none());
+        }
+    }
+
+    #[test]
+    fn dispatch_mode_switches_display_mode() {
+        let resp = dispatch(b"GET /mode?m=clock HTTP/1.1");
+        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+        assert_eq!(resp.mode, Some(DisplayMode::Clock { seconds: false }));
+        assert!(resp.timezone.is_none());
+        let resp = dispatch(b"GET /mode?m=clock&seconds=1 HTTP/1.1");
+        assert_eq!(resp.mode, Some(DisplayMode::Clock { seconds: true }));
+        let resp = dispatch(b"GET /mode?m=text HTTP/1.1");
+        assert_eq!(resp.mode, Some(DisplayMode::Text));
+    }
+
+    #[test]
+    fn dispatch_mode_sets_timezone() {
+        let resp = dispatch(b"GET /mode?tz=EET-2EEST%2CM3.5.0%2F3%2CM10.5.0%2F4 HTTP/1.1");
+        assert!(resp.mode.is_none());
+        assert_eq!(
+            resp.timezone.unwrap().as_str(),
+            "EET-2EEST,M3.5.0/3,M10.5.0/4"
+        );
+    }
+
+    #[test]
+    fn dispatch_mode_rejects_bad_params() {
+        for req in [
+            &b"GET /mode HTTP/1.1"[..],
+            b"GET /mode?m=disco HTTP/1.1",
+            b"GET /mode?m=clock&tz=nowhere HTTP/1.1",
+        ] {
+            let resp = dispatch(req);
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert!(resp.mode.is_none());
+        }
+    }
+
+    #[test]
+    fn dispatch_hostname_sets_name() {
+        let resp = dispatch(b"GET /hostname?name=lobby-sign HTTP/1.1");
+        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+        assert_eq!(resp.hostname.unwrap().as_str(), "lobby-sign");
+    }
+
+    #[test]
+    fn dispatch_hostname_rejects_invalid_name() {
+        for req in [
+            &b"GET /hostname HTTP/1.1"[..],
+            b"GET /hostname?name= HTTP/1.1",
+            b"GET /hostname?name=led%20matrix HTTP/1.1",
+            b"GET /hostname?name=abcdefghijklmnopqrstuvwxyz0123456 HTTP/1.1",
+        ] {
+            let resp = dispatch(req);
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert!(resp.hostname.is_none());
+        }
+    }
+
+    #[test]
+    fn dispatch_idle_sets_the_timeout() {
+        let resp = dispatch(b"GET /idle?seconds=300 HTTP/1.1");
+        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+        assert_eq!(resp.idle_timeout, Some(300));
+        assert_eq!(
+            dispatch(b"GET /idle?seconds=0 HTTP/1.1").idle_timeout,
+            Some(0)
+        );
+        for req in [
+            &b"GET /idle HTTP/1.1"[..],
+            b"GET /idle?seconds=-1 HTTP/1.1",
+            b"GET /idle?seconds=86401 HTTP/1.1",
+        ] {
+            let resp = dispatch(req);
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert!(resp.idle_timeout.is_none());
+        }
+    }
+
+    #[test]
+    fn dispatch_notify_queues_a_timed_notice() {
+        let resp = dispatch(b"GET /notify?msg=Door+bell&seconds=5 HTTP/1.1");
+        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+        assert_eq!(
+            resp.notice,
+            Some(TimedNotice {
+                notice: Notice::Text("Door bell".try_into().unwrap()),
+                duration_ms: 5_000,
+            })
+        );
+        assert_eq!(resp.display_text, None);
+        assert_eq!(resp.mode, None);
+        let resp = dispatch(b"GET /notify?pattern=red&seconds=1 HTTP/1.1");
+        let notice = resp.notice.unwrap().notice;
+        assert_eq!(notice, Notice::Pattern(TestPattern::Red));
+
+        for req in [
+            &b"GET /notify?msg=hi HTTP/1.1"[..],
+            b"GET /notify?seconds=5 HTTP/1.1",
+            b"GET /notify?msg=hi&seconds=0 HTTP/1.1",
+            b"GET /notify?msg=hi&seconds=3601 HTTP/1.1",
+            b"GET /notify?pattern=plaid&seconds=5 HTTP/1.1",
+        ] {
+            let resp = dispatch(req);
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert!(resp.notice.is_none());
+        }
+    }
+
+    #[test]
+    fn dispatch_fps_sets_the_target() {
+        let resp = dispatch(b"GET /fps?target=30 HTTP/1.1");
+        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+        assert_eq!(resp.target_fps, Some(30));
+        for req in [
+            &b"GET /fps HTTP/1.1"[..],
+            b"GET /fps?target=0 HTTP/1.1",
+            b"GET /fps?target=201 HTTP/1.1",
+        ] {
+            let resp = dispatch(req);
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert!(resp.target_fps.is_none());
+        }
+    }
+
+    #[test]
+    fn dispatch_pixel_queues_draw_command() {
+        let resp = dispatch(b"GET /pixel?x=3&y=87&r=65535&b=9 HTTP/1.1");
+        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+        assert_eq!(
+            resp.draw,
+            Some(DrawCommand::Pixel {
+                x: 3,
+                y: 87,
+                color: [0xFFFF, 0, 9],
+            })
+        );
+    }
+
+    #[test]
+    fn dispatch_pixel_rejects_malformed_input() {
+        let mut past_right = heapless::String::<32>::new();
+        write!(past_right, "GET /pixel?x={MATRIX_WIDTH}&y=0 HTTP/1.1").unwrap();
+        let mut past_bottom = heapless::String::<32>::new();
+        write!(past_bottom, "GET /pixel?x=0&y={MATRIX_HEIGHT} HTTP/1.1").unwrap();
+        for req in [
+            "GET /pixel HTTP/1.1",
+            "GET /pixel?x=1 HTTP/1.1",
+            &past_right,
+            &past_bottom,
+            "GET /pixel?x=-1&y=0 HTTP/1.1",
+            "GET /pixel?x=0&y=0&g=green HTTP/1.1",
+        ] {
+            let resp = dispatch(req.as_bytes());
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert!(resp.draw.is_none());
+        }
+    }
+
+    #[test]
+    fn dispatch_unknown_path_returns_404() {
+        let resp = dispatch(b"GET /nope HTTP/1.1");
+        assert!(resp.body.starts_with(b"HTTP/1.1 404"));
+        assert!(resp.display_text.is_none());
+    }
+
+    #[test]
+    fn throttle_limits_only_display_changes() {
+        let mut limiter = RateLimiter::new(2);
+        let pixel = b"GET /pixel?x=1&y=1 HTTP/1.1";
+        assert!(throttle(dispatch(pixel), &mut limiter, 0).draw.is_some());
+        let text = dispatch(b"GET /text?msg=hi HTTP/1.1");
+        assert!(throttle(text, &mut limiter, 10).display_text.is_some());
+        // The budget is spent, but non-drawing requests still go through.
+        let stats = throttle(dispatch(b"GET /stats HTTP/1.1"), &mut limiter, 20);
+        assert!(stats.stats);
+        let region = throttle(
+            dispatch(b"GET /region?id=top&msg=x HTTP/1.1"),
+            &mut limiter,
+            25,
+        );
+        assert_eq!(region.body, TOO_MANY_REQUESTS_RESPONSE);
+        let refused = throttle(dispatch(pixel), &mut limiter, 30);
+        assert_eq!(refused.body, TOO_MANY_REQUESTS_RESPONSE);
+        assert!(refused.draw.is_none());
+        assert!(throttle(dispatch(pixel), &mut limiter, 1000).draw.is_some());
+    }
+
+    #[test]
+    fn backpressure_refuses_what_does_not_fit() {
+        let body = br#"[{"op":"clear"},{"op":"pixel","x":1,"y":2}]"#;
+        let mut req: heapless::Vec<u8, 256> = heapless::Vec::new();
+        req.extend_from_slice(b"POST /api/draw HTTP/1.1\r\nContent-Length: 43\r\n\r\n")
+            .unwrap();
+        req.extend_from_slice(body).unwrap();
+        assert_eq!(backpressure(dispatch(&req), 2).draw_batch.len(), 2);
+        let refused = backpressure(dispatch(&req), 1);
+        assert_eq!(refused.body, QUEUE_FULL_RESPONSE);
+        assert!(refused.draw_batch.is_empty());
+
+        let pixel = b"GET /pixel?x=1&y=1 HTTP/1.1";
+        assert!(backpressure(dispatch(pixel), 1).draw.is_some());
+        assert!(backpressure(dispatch(pixel), 0).draw.is_none());
+        // Nothing to queue, nothing to refuse.
+        assert!(backpressure(dispatch(b"GET /stats HTTP/1.1"), 0).stats);
+    }
+
+    #[test]
+    fn content_length_goes_before_the_blank_line() {
+        let (head, length) = content_length_head(OK_HTML_RESPONSE, 6417);
+        let mut full = heapless::Vec::<u8, 128>::new();
+        full.extend_from_slice(head).unwrap();
+        full.extend_from_slice(length.as_bytes()).unwrap();
+        assert_eq!(
+            &full[..],
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nConnection: close\r\nContent-Length: 6417\r\n\r\n"
+        );
+        let (head, length) = content_length_head(BAD_REQUEST_RESPONSE, 0);
+        assert_eq!(head, BAD_REQUEST_RESPONSE);
+        assert!(length.is_empty());
+    }
+
+    #[test]
+    fn html_page_is_non_empty() {
+        assert!(!html_page().is_empty());
+    }
+
+    #[test]
+    fn html_page_controls_use_known_routes() {
+        let page = core::str::from_utf8(html_page()).unwrap();
+        assert!(page.contains("'/color?hex='") && page.contains("'/brightness?level='"));
+        let mut buttons = 0;
+        for chunk in page.split("data-path=\"").skip(1) {
+            let path = &chunk[..chunk.find('"').unwrap()];
+            let mut req: heapless::String<64> = heapless::String::new();
+            write!(req, "GET {path} HTTP/1.1").unwrap();
+            assert!(
+                dispatch(req.as_bytes()).body.starts_with(b"HTTP/1.1 200"),
+                "{path}"
+            );
+            buttons += 1;
+        }
+        assert_eq!(buttons, 14);
+        assert!(page.contains("'/input?dir='"));
+        assert!(page.contains("fetch('/image', { method: 'POST'"));
+        assert!(page.contains("EventSource('/events')"));
+        assert!(!page.contains("http://") && !page.contains("https://"));
+    }
+
+    #[test]
+    fn dispatch_api_draw_queues_ops() {
+        let body = br#"[{"op":"clear"},{"op":"pixel","x":1,"y":2}]"#;
+        let mut req: heapless::Vec<u8, 256> = heapless::Vec::new();
+        req.extend_from_slice(b"POST /api/draw HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: 43\r\n\r\n")
+            .unwrap();
+        req.extend_from_slice(body).unwrap();
+        let resp = dispatch(&req);
+        assert_eq!(resp.body, JSON_OK_RESPONSE);
+        assert_eq!(resp.page, b"{\"ok\":true}");
+        assert_eq!(
+            resp.draw_batch.as_slice(),
+            [
+                DrawCommand::Fill { color: [0, 0, 0] },
+                DrawCommand::Pixel {
+                    x: 1,
+                    y: 2,
+                    color: [0xFFFF; 3],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn dispatch_api_draw_reports_errors() {
+        let resp =
+            dispatch(b"POST /api/draw HTTP/1.1\r\nContent-Length: 15\r\n\r\n[{\"op\":\"spin\"}]");
+        assert_eq!(resp.body, JSON_BAD_REQUEST_RESPONSE);
+        assert_eq!(resp.api_error, Some(ApiError::UnknownOp(0)));
+        assert!(resp.draw_batch.is_empty());
+        // Body cut off by the request buffer.
+        let resp = dispatch(b"POST /api/draw HTTP/1.1\r\nContent-Length: 2000\r\n\r\n[{");
+        assert_eq!(resp.api_error, Some(ApiError::TooLarge));
+        // A length that would run past the end of memory.
+        let resp =
+            dispatch(b"POST /api/draw HTTP/1.1\r\nContent-Length: 18446744073709551615\r\n\r\n[]");
+        assert_eq!(resp.api_error, Some(ApiError::TooLarge));
+    }
+
+    #[test]
+    fn error_responses_carry_no_page() {
+        assert!(dispatch(b"GET /nope HTTP/1.1").page.is_empty());
+        assert!(dispatch(b"GET /brightness HTTP/1.1").page.is_empty());
+        assert_eq!(dispatch(b"GET / HTTP/1.1").page, html_page());
+    }
+
+    #[test]
+    fn query_params_from_form_body() {
+        let form = QueryParams::from_form("ssid=hack+lab&password=p%40ss");
+        assert_eq!(form.decoded("ssid").unwrap().as_str(), "hack lab");
+        assert_eq!(form.decoded("password").unwrap().as_str(), "p@ss");
+    }
+
+    #[test]
+    fn dispatch_portal_redirects_unknown_paths() {
+        let resp = dispatch_portal(b"GET /generate_204 HTTP/1.1\r\n\r\n");
+        assert_eq!(resp.body, PORTAL_REDIRECT_RESPONSE);
+        assert!(resp.page.is_empty());
+        let resp = dispatch_portal(b"GET /setup HTTP/1.1\r\n\r\n");
+        assert_eq!(resp.page, SETUP_PAGE.as_bytes());
+        let resp = dispatch_portal(b"GET /text?msg=hi HTTP/1.1\r\n\r\n");
+        assert_eq!(resp.display_text.unwrap().as_str(), "hi");
+        let resp = dispatch_portal(b"GET /events HTTP/1.1\r\n\r\n");
+        assert!(!resp.events);
+        assert_eq!(resp.body, NO_CONTENT_RESPONSE);
+    }
+
+    #[test]
+    fn is_complete_waits_for_headers_and_body() {
+        assert!(!is_complete(b"GET / HTTP/1.1\r\nHost: x\r\n"));
+        assert!(is_complete(b"GET / HTTP/1.1\r\nHost: x\r\n\r\n"));
+        let post = b"POST /setup HTTP/1.1\r\ncontent-length: 9\r\n\r\nssid=cafe";
+        assert!(!is_complete(&post[..post.len() - 1]));
+        assert!(is_complete(post));
+    }
+
+    #[test]
+    fn dispatch_image_accepts_full_frame() {
+        let mut request = [0xA5u8; 128];
+        let head = post("/image", IMAGE_LEN, "");
+        request[..head.len()].copy_from_slice(head.as_bytes());
+        let resp = dispatch(&request);
+        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+        assert!(resp.image_upload);
+        assert_eq!(body_start(&request), Some(head.len()));
+    }
+
+    #[test]
+    fn dispatch_image_rejects_wrong_length() {
+        for req in [
+            "POST /image HTTP/1.1\r\n\r\n",
+            &post("/image", IMAGE_LEN - 1, ""),
+            &post("/image", IMAGE_LEN * 2, ""),
+        ] {
+            let resp = dispatch(req.as_bytes());
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert!(!resp.image_upload);
+        }
+    }
+
+    #[test]
+    fn dispatch_image_takes_a_bmp_by_its_signature() {
+        let resp = dispatch(b"POST /image HTTP/1.1\r\nContent-Length: 23286\r\n\r\nBM6Z");
+        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+        assert_eq!(resp.bmp_upload, Some(23286));
+        assert!(!resp.image_upload);
+
+        for req in [
+            &b"POST /image HTTP/1.1\r\nContent-Length: 23286\r\n\r\nP6"[..],
+            b"POST /image HTTP/1.1\r\nContent-Length: 53\r\n\r\nBM",
+            post("/image", MAX_BMP_LEN + 1, "BM").as_bytes(),
+        ] {
+            let resp = dispatch(req);
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert_eq!(resp.bmp_upload, None);
+        }
+        // A raw frame may start with the same two bytes.
+        let resp = dispatch(post("/image", IMAGE_LEN, "BM").as_bytes());
+        assert!(resp.image_upload);
+        assert_eq!(resp.bmp_upload, None);
+    }
+
+    #[test]
+    fn dispatch_anim_takes_whole_frame_packs() {
+        // The magic, then a two-byte duration and a frame per record.
+        let pack_len = |frames| frame_pack::FRAME_PACK_MAGIC.len() + frames * (2 + IMAGE_LEN);
+        let resp = dispatch(post("/anim", pack_len(2), "LEDA").as_bytes());
+        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+        assert_eq!(resp.anim_upload, Some(pack_len(2)));
+
+        for req in [
+            "POST /anim HTTP/1.1\r\n\r\n",
+            &post("/anim", IMAGE_LEN, ""),
+            &post("/anim", pack_len(frame_pack::MAX_PACK_FRAMES + 1), ""),
+        ] {
+            let resp = dispatch(req.as_bytes());
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert_eq!(resp.anim_upload, None);
+        }
+
+        let resp = dispatch(b"GET /anim/stop HTTP/1.1");
+        assert_eq!(resp.mode, Some(DisplayMode::Text));
+    }
+
+    #[test]
+    fn dispatch_font_accepts_body_up_to_max_len() {
+        for (head, len) in [
+            (
+                &b"POST /font HTTP/1.1\r\nContent-Length: 12\r\n\r\n"[..],
+                12,
+            ),
+            (b"POST /font HTTP/1.1\r\nContent-Length: 4096\r\n\r\n", 4096),
+            (b"POST /font HTTP/1.1\r\n\r\n", 0),
+        ] {
+            let resp = dispatch(head);
+            assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+            assert_eq!(resp.font_upload, Some(len));
+        }
+        let resp = dispatch(b"POST /font HTTP/1.1\r\nContent-Length: 4097\r\n\r\n");
+        assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+        assert_eq!(resp.font_upload, None);
+    }
+
+    #[test]
+    fn rgb565_pixels_are_little_endian() {
+        let pixels: heapless::Vec<u16, 4> =
+            rgb565_pixels(&[0x00, 0xF8, 0x1F, 0x00, 0xFF]).collect();
+        assert_eq!(pixels, [0xF800, 0x001F]);
+    }
+
+    #[test]
+    fn dispatch_setup_serves_form() {
+        let resp = dispatch(b"GET /setup HTTP/1.1\r\n\r\n");
+        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+        assert_eq!(resp.page, SETUP_PAGE.as_bytes());
+        assert!(resp.credentials.is_none());
+    }
+
+    #[test]
+    fn dispatch_setup_post_returns_credentials() {
+        let mut buf = [0u8; 512];
+        let req = b"POST /setup HTTP/1.1\r\nContent-Type: application/x-www-form-urlencoded\r\n\r\nssid=hack+lab&password=correct+horse";
+        buf[..req.len()].copy_from_slice(req);
+        // The server hands over its whole zero-padded read buffer.
+        let resp = dispatch(&buf);
+        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+        let creds = resp.credentials.unwrap();
+        assert_eq!(creds.ssid.as_str(), "hack lab");
+        assert_eq!(creds.password.as_str(), "correct horse");
+    }
+
+    #[test]
+    fn dispatch_setup_post_open_network() {
+        let resp = dispatch(b"POST /setup HTTP/1.1\r\n\r\nssid=cafe");
+        assert_eq!(resp.credentials.unwrap().password.as_str(), "");
+    }
+
+    #[test]
+    fn dispatch_setup_post_rejects_invalid_credentials() {
+        for req in [
+            &b"POST /setup HTTP/1.1\r\n\r\npassword=correct+horse"[..],
+            b"POST /setup HTTP/1.1\r\n\r\nssid=x&password=short",
+            b"POST /setup HTTP/1.1\r\n\r\nssid=0123456789012345678901234567890123",
+            b"POST /setup HTTP/1.1\r\n",
+        ] {
+            let resp = dispatch(req);
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert!(resp.credentials.is_none());
+        }
+    }
+
+    #[test]
+    fn dispatch_setup_post_returns_ota_token() {
+        let resp = dispatch(b"POST /setup HTTP/1.1\r\n\r\nssid=cafe&ota_token=let+me+in%21");
+        assert!(resp.credentials.is_some());
+        assert_eq!(resp.ota_token.unwrap().as_str(), "let me in!");
+        for body in ["ssid=cafe", "ssid=cafe&ota_token="] {
+            let mut req: heapless::String<256> = heapless::String::new();
+            req.push_str("POST /setup HTTP/1.1\r\n\r\n").unwrap();
+            req.push_str(body).unwrap();
+            assert_eq!(dispatch(req.as_bytes()).ota_token, None);
+        }
+        let long = [b'x'; MAX_TOKEN_LEN + 1];
+        let mut req = [0u8; 256];
+        let head = b"POST /setup HTTP/1.1\r\n\r\nssid=cafe&ota_token=";
+        req[..head.len()].copy_from_slice(head);
+        req[head.len()..head.len() + long.len()].copy_from_slice(&long);
+        assert_eq!(dispatch(&req).ota_token, None);
+    }
+
+    #[test]
+    fn dispatch_setauth_returns_password() {
+        let resp = dispatch(b"POST /setauth HTTP/1.1\r\n\r\npassword=open+sesame");
+        assert_eq!(resp.auth_password.unwrap().as_str(), "open sesame");
+        // Empty turns authentication off.
+        let resp = dispatch(b"POST /setauth HTTP/1.1\r\n\r\npassword=");
+        assert_eq!(resp.auth_password.unwrap().as_str(), "");
+        let resp = dispatch(b"POST /setauth HTTP/1.1\r\n\r\npass=x");
+        assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+        let mut req = [0u8; 256];
+        let head = b"POST /setauth HTTP/1.1\r\n\r\npassword=";
+        req[..head.len()].copy_from_slice(head);
+        req[head.len()..head.len() + MAX_AUTH_PASSWORD_LEN + 1].fill(b'x');
+        assert_eq!(dispatch(&req).body, BAD_REQUEST_RESPONSE);
+    }
+
+    #[test]
+    fn authorize_passes_everything_without_a_password() {
+        let req = b"GET /text?msg=HI HTTP/1.1\r\n\r\n";
+        let resp = authorize(req, dispatch(req), None);
+        assert_eq!(resp.display_text.unwrap().as_str(), "HI");
+    }
+
+    #[test]
+    fn authorize_challenges_control_requests() {
+        for req in [
+            &b"GET /text?msg=HI HTTP/1.1\r\n\r\n"[..],
+            b"GET /clear HTTP/1.1\r\n\r\n",
+            b"POST /setauth HTTP/1.1\r\n\r\npassword=",
+            b"POST /setup HTTP/1.1\r\n\r\nssid=cafe",
+            // Wrong password ("user:wrong").
+            b"GET /clear HTTP/1.1\r\nAuthorization: Basic dXNlcjp3cm9uZw==\r\n\r\n",
+        ] {
+            let resp = authorize(req, dispatch(req), Some("s3cret"));
+            assert_eq!(resp.body, BASIC_UNAUTHORIZED_RESPONSE);
+            assert!(resp.display_text.is_none() && resp.auth_password.is_none());
+            assert!(resp.credentials.is_none());
+        }
+    }
+
+    #[test]
+    fn authorize_accepts_the_password() {
+        // "admin:s3cret"
+        let req = b"GET /text?msg=HI HTTP/1.1\r\nAuthorization: Basic YWRtaW46czNjcmV0\r\n\r\n";
+        let resp = authorize(req, dispatch(req), Some("s3cret"));
+        assert_eq!(resp.display_text.unwrap().as_str(), "HI");
+    }
+
+    #[test]
+    fn authorize_keeps_read_only_pages_public() {
+        for req in [
+            &b"GET / HTTP/1.1\r\n\r\n"[..],
+            b"GET /stats HTTP/1.1\r\n\r\n",
+            b"GET /status HTTP/1.1\r\n\r\n",
+            b"GET /setup HTTP/1.1\r\n\r\n",
+            b"GET /nope HTTP/1.1\r\n\r\n",
+        ] {
+            let resp = authorize(req, dispatch(req), Some("s3cret"));
+            assert_ne!(resp.body, BASIC_UNAUTHORIZED_RESPONSE);
+        }
+        let req = b"GET /generate_204 HTTP/1.1\r\n\r\n";
+        let resp = authorize(req, dispatch_portal(req), Some("s3cret"));
+        assert_eq!(resp.body, PORTAL_REDIRECT_RESPONSE);
+    }
+
+    #[test]
+    fn only_commands_wake_the_display() {
+        for req in [
+            &b"GET / HTTP/1.1
+
+"[..],
+            b"GET /text?msg=HI HTTP/1.1
+
+",
+            b"GET /brightness?level=9 HTTP/1.1
+
+",
+            b"GET /text?msg=HI&transition=bogus HTTP/1.1
+
+",
+        ] {
+            assert!(is_command(&dispatch(req)));
+        }
+        for req in [
+            &b"GET /stats HTTP/1.1
+
+"[..],
+            b"GET /status HTTP/1.1
+
+",
+            b"GET /screenshot HTTP/1.1
+
+",
+            b"GET /nope HTTP/1.1
+
+",
+        ] {
+            assert!(!is_command(&dispatch(req)));
+        }
+        let req = b"GET /clear HTTP/1.1
+
+";
+        assert!(!is_command(&authorize(req, dispatch(req), Some("s3cret"))));
+        let req = b"GET /generate_204 HTTP/1.1
+
+";
+        assert!(!is_command(&dispatch_portal(req)));
+    }
+
+    #[test]
+    fn dispatch_ota_returns_upload() {
+        let resp = dispatch(
+            b"POST /ota HTTP/1.1\r\nAuthorization: Bearer s3cret\r\nContent-Length: 900000\r\n\r\n",
+        );
+        assert!(resp.body.starts_with(b"HTTP/1.1 200"));
+        let upload = resp.ota_upload.unwrap();
+        assert_eq!(upload.len, 900_000);
+        assert_eq!(upload.token.as_str(), "s3cret");
+    }
+
+    #[test]
+    fn dispatch_ota_requires_a_token() {
+        for req in [
+            &b"POST /ota HTTP/1.1\r\nContent-Length: 900000\r\n\r\n"[..],
+            b"POST /ota HTTP/1.1\r\nAuthorization: Basic czNjcmV0\r\nContent-Length: 900000\r\n\r\n",
+            b"POST /ota HTTP/1.1\r\nAuthorization: Bearer \r\nContent-Length: 900000\r\n\r\n",
+        ] {
+            let resp = dispatch(req);
+            assert!(resp.body.starts_with(b"HTTP/1.1 401"));
+            assert!(resp.ota_upload.is_none());
+        }
+    }
+
+    #[test]
+    fn dispatch_ota_rejects_bad_lengths() {
+        for req in [
+            &b"POST /ota HTTP/1.1\r\nAuthorization: Bearer s3cret\r\n\r\n"[..],
+            b"POST /ota HTTP/1.1\r\nAuthorization: Bearer s3cret\r\nContent-Length: 23\r\n\r\n",
+            b"POST /ota HTTP/1.1\r\nAuthorization: Bearer s3cret\r\nContent-Length: 1966081\r\n\r\n",
+        ] {
+            let resp = dispatch(req);
+            assert_eq!(resp.body, BAD_REQUEST_RESPONSE);
+            assert!(resp.ota_upload.is_none());
+        }
     }
 }
@@ -3,21 +3,103 @@
 //! URL-decoding and response shaping lives in the host-testable
 //! `http_request` module.
 
-use crate::DISPLAY_TEXT;
+use crate::{
+    BRIGHTNESS, COUNTDOWN, DISPLAY_MODE, DISPLAY_TEXT, DRAW_QUEUE, EVENTS, FONT, FRAME_PACER,
+    FRAME_PACK, FRAME_PACK_READY, FRAME_TIME_US, GAME_INPUT, GRAPH, GRAPH_UPDATED, IMAGE,
+    IMAGE_READY, NOTICES, REGIONS, REGIONS_UPDATED, RICH_TEXT, SCREENSAVER, SCREENSHOT,
+    SCREENSHOT_READY, SCREENSHOT_REQUEST, SCROLL_GRADIENT, TEXT_COLOR, TRANSITION,
+};
+use crate::{clock, mdns, nvs, ota, wifi};
 use embassy_net::{Stack, tcp::TcpSocket};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::TrySendError;
+use embassy_sync::mutex::Mutex;
+use embassy_sync::pubsub::Subscriber;
+use embassy_time::{Duration, Instant, Timer, with_timeout};
+use esp32_led_matrix::auth::{self, MAX_AUTH_PASSWORD_LEN};
+use esp32_led_matrix::bmp::{BmpDecoder, BmpError};
+use esp32_led_matrix::command::DrawCommand;
+use esp32_led_matrix::countdown::TimerCommand;
+use esp32_led_matrix::credentials::Credentials;
+use esp32_led_matrix::display_state::DisplayState;
+use esp32_led_matrix::events::{
+    DisplayEvent, EVENT_QUEUE_LEN, KEEPALIVE_SECS, MAX_EVENT_CLIENTS, SSE_KEEPALIVE,
+};
+use esp32_led_matrix::font::{Font, MAX_FONT_LEN};
+use esp32_led_matrix::frame_buffer::Pixel;
+use esp32_led_matrix::frame_pack::{FramePack, FramePackDecoder, FramePackError};
+use esp32_led_matrix::games::{Direction, GameInput};
+use esp32_led_matrix::gradient::GradientMode;
 use esp32_led_matrix::http_request;
-use log::{debug, info};
+use esp32_led_matrix::mode::DisplayMode;
+use esp32_led_matrix::notice::TimedNotice;
+use esp32_led_matrix::ota_image::MAX_TOKEN_LEN;
+use esp32_led_matrix::rate_limit::{DEFAULT_REQUESTS_PER_SEC, RateLimiter};
+use esp32_led_matrix::regions::RegionUpdate;
+use esp32_led_matrix::rich_text::RichText;
+use esp32_led_matrix::screenshot;
+use esp32_led_matrix::stats::{STATUS_JSON_LEN, Stats, Status};
+use esp32_led_matrix::timezone::MAX_TZ_LEN;
+use esp32_led_matrix::transition::Transition;
+use esp32_led_matrix::websocket::{self, Decoder, Event, Opcode};
+use log::{debug, info, warn};
+
+/// Bytes read from the socket at a time while receiving `POST /image`.
+const IMAGE_CHUNK_LEN: usize = http_request::MAX_REQUEST_LEN;
+
+/// Bytes written to flash at a time during `POST /ota`: one sector.
+const OTA_CHUNK_LEN: usize = 4096;
+
+/// Bytes read from the socket at a time on a `/ws` connection.
+const WEBSOCKET_CHUNK_LEN: usize = 1024;
+
+/// Pause between answering `POST /setup` and rebooting.
+const REBOOT_DELAY_MS: u64 = 500;
+
+/// Display-changing requests (`/text`, `/pixel`, `/clear`, ...) served
+/// per second; the rest get a 429 so the refresh loop keeps its share
+/// of the CPU.
+const REQUESTS_PER_SEC: u32 = DEFAULT_REQUESTS_PER_SEC;
+
+/// Where [`serve_websocket`] assembles a frame before copying it to
+/// [`IMAGE`]: one buffer for all the server tasks rather than a frame on
+/// each task's stack. A `/ws` connection holds it until it closes.
+static WEBSOCKET_FRAME: Mutex<CriticalSectionRawMutex, [u8; http_request::IMAGE_LEN]> =
+    Mutex::new([0; http_request::IMAGE_LEN]);
+
+/// The [`REQUESTS_PER_SEC`] budget, shared by every server task.
+static LIMITER: Mutex<CriticalSectionRawMutex, RateLimiter> =
+    Mutex::new(RateLimiter::new(REQUESTS_PER_SEC));
+
+/// An `/events` client's place in [`EVENTS`].
+type EventSubscriber = Subscriber<
+    'static,
+    CriticalSectionRawMutex,
+    DisplayEvent,
+    EVENT_QUEUE_LEN,
+    MAX_EVENT_CLIENTS,
+    0,
+>;
 
 /// Run the HTTP server forever, accepting one connection at a time.
-#[embassy_executor::task]
-pub async fn http_server_task(stack: &'static Stack<'static>) {
+/// One instance for the setup access point (`portal`, where unknown
+/// paths redirect to `/setup`), and one for the station network plus
+/// one more per `/events` client.
+#[embassy_executor::task(pool_size = 2 + MAX_EVENT_CLIENTS)]
+pub async fn http_server_task(stack: &'static Stack<'static>, portal: bool) {
     info!("HTTP server task starting on port 80");
-    run_http_server(stack).await;
+    run_http_server(stack, portal).await;
 }
 
 /// Server loop. Each iteration accepts a connection, reads one request,
-/// writes one response, then closes the socket.
-pub async fn run_http_server(stack: &'static Stack<'static>) {
+/// writes one response, then closes the socket — or, for `/ws` and
+/// `/events`, keeps serving it until the client goes.
+pub async fn run_http_server(stack: &'static Stack<'static>, portal: bool) {
+    let dispatch = if portal {
+        http_request::dispatch_portal
+    } else {
+        http_request::dispatch
+    };
     let mut rx_buffer = [0u8; 2048];
     let mut tx_buffer = [0u8; 8192];
 
@@ -30,65 +112,804 @@ pub async fn run_http_server(stack: &'static Stack<'static>) {
         }
         info!("HTTP client connected");
 
-        let response = match read_request(&mut socket).await {
-            Some(buf) => http_request::dispatch(&buf),
-            None => continue,
+        let Some((buf, len)) = read_request(&mut socket).await else {
+            continue;
         };
+        let password = load_auth_password().await;
+        let mut response =
+            http_request::authorize(&buf[..len], dispatch(&buf[..len]), password.as_deref());
+        let response = http_request::throttle(
+            response,
+            &mut *LIMITER.lock().await,
+            Instant::now().as_millis(),
+        );
+        let response = http_request::backpressure(response, DRAW_QUEUE.free_capacity());
+        if http_request::is_command(&response) {
+            SCREENSAVER.lock().await.wake(Instant::now().as_millis());
+        }
 
         // Take ownership of the optional display text *before* the body
         // so the partial move of `Response` doesn't trouble the borrow
         // checker on the subsequent `write_response` call.
-        let body = response.body;
-        let text = response.display_text;
-        apply_text_update(text).await;
-        write_response(&mut socket, body).await;
+        let mut body = response.body;
+        let stats_json;
+        let status_json;
+        let handshake_end;
+        let api_error_json;
+        let mut page: &[u8] = response.page;
+        if response.stats {
+            stats_json = current_stats().await.to_json();
+            page = stats_json.as_bytes();
+        }
+        if response.status {
+            status_json = current_status(stack).await;
+            page = status_json.as_bytes();
+        }
+        if let Some(error) = &response.api_error {
+            api_error_json = error.to_json();
+            page = api_error_json.as_bytes();
+        }
+        if let Some(accept) = &response.websocket {
+            handshake_end = websocket::handshake_end(accept);
+            page = handshake_end.as_bytes();
+        }
+        // Subscribe before the headers go out, so no change in between
+        // is missed.
+        let mut events = None;
+        if response.events {
+            match EVENTS.subscriber() {
+                Ok(subscriber) => events = Some(subscriber),
+                Err(_) => body = http_request::EVENTS_FULL_RESPONSE,
+            }
+        }
+        if response.image_upload {
+            let start = http_request::body_start(&buf[..len]).unwrap_or(len);
+            if !receive_image(&mut socket, &buf[start..len]).await {
+                body = http_request::BAD_REQUEST_RESPONSE;
+                page = b"";
+            }
+        }
+        if let Some(bmp_len) = response.bmp_upload {
+            let start = http_request::body_start(&buf[..len]).unwrap_or(len);
+            if let Err(e) = receive_bmp(&mut socket, &buf[start..len], bmp_len).await {
+                body = http_request::TEXT_BAD_REQUEST_RESPONSE;
+                page = e.message().as_bytes();
+            }
+        }
+        if let Some(font_len) = response.font_upload {
+            let start = http_request::body_start(&buf[..len]).unwrap_or(len);
+            if !receive_font(&mut socket, &buf[start..len], font_len).await {
+                body = http_request::BAD_REQUEST_RESPONSE;
+                page = b"";
+            }
+        }
+        if let Some(pack_len) = response.anim_upload {
+            let start = http_request::body_start(&buf[..len]).unwrap_or(len);
+            if let Err(e) = receive_frame_pack(&mut socket, &buf[start..len], pack_len).await {
+                body = http_request::TEXT_BAD_REQUEST_RESPONSE;
+                page = e.message().as_bytes();
+            }
+        }
+        let mut ota_done = false;
+        if let Some(upload) = &response.ota_upload {
+            let start = http_request::body_start(&buf[..len]).unwrap_or(len);
+            match receive_ota(&mut socket, &buf[start..len], upload).await {
+                Ok(()) => ota_done = true,
+                Err(reply) => {
+                    body = reply;
+                    page = b"";
+                }
+            }
+        }
+        apply_changes(&mut response).await;
+        if let Some(format) = response.screenshot {
+            send_screenshot(&mut socket, format).await;
+        } else {
+            write_response(&mut socket, body, page).await;
+        }
+        if response.websocket.is_some() {
+            serve_websocket(&mut socket).await;
+        }
+        if let Some(subscriber) = events {
+            serve_events(&mut socket, subscriber).await;
+        }
         socket.close();
+        // Reboot only after the reply is out — it takes the setup access
+        // point (and this client) down.
+        apply_credentials(response.credentials).await;
+        if ota_done {
+            info!("Rebooting into the new firmware...");
+            reboot().await;
+        }
         info!("HTTP request handled");
     }
 }
 
-/// Read one HTTP request into a fixed buffer. Returns `None` on read
-/// errors so the caller can move on to the next connection.
-async fn read_request(socket: &mut TcpSocket<'_>) -> Option<[u8; 512]> {
-    let mut buf = [0u8; 512];
-    match socket.read(&mut buf).await {
-        Ok(_) => Some(buf),
+/// Hand everything `response` changes to the display, the settings and
+/// flash, taking it out of `response`. What is left — the reply, an
+/// upload, new WiFi credentials — is up to the caller. Shared with
+/// [`crate::serial`], so a serial command does what its HTTP request
+/// does.
+pub async fn apply_changes(response: &mut http_request::Response) {
+    let events = DisplayEvent::from_response(response);
+    let text = response.display_text.take();
+    let save_state = response.mode.is_some() || response.text_color.is_some();
+    apply_text_color(response.text_color.take()).await;
+    apply_brightness(response.brightness.take()).await;
+    // Text first: a text change re-renders the frame, which would
+    // wipe a draw command (e.g. the `/clear` fill) applied before it.
+    // Its transition, gradient and segment colors go before it, so
+    // the refresh loop never sees the new text without them.
+    if text.is_some() {
+        apply_transition(response.transition.take()).await;
+        apply_gradient(response.gradient.take()).await;
+        apply_rich_text(response.rich_text.take()).await;
+    }
+    apply_text_update(text).await;
+    queue_draw(response.draw.take());
+    for cmd in core::mem::take(&mut response.draw_batch) {
+        queue_draw(Some(cmd));
+    }
+    apply_ota_token(response.ota_token.take()).await;
+    apply_auth_password(response.auth_password.take()).await;
+    apply_hostname(response.hostname.take()).await;
+    apply_timezone(response.timezone.take()).await;
+    apply_graph_sample(response.graph_sample.take()).await;
+    apply_region(response.region.take()).await;
+    apply_idle_timeout(response.idle_timeout.take()).await;
+    apply_target_fps(response.target_fps.take()).await;
+    apply_notice(response.notice.take()).await;
+    queue_game_input(response.game_input.take());
+    apply_timer(response.timer.take()).await;
+    apply_mode(response.mode.take()).await;
+    if save_state {
+        save_display_state().await;
+    }
+    let publisher = EVENTS.immediate_publisher();
+    for event in events {
+        publisher.publish_immediate(event);
+    }
+}
+
+/// Snapshot for `/stats`: the refresh loop's frame time, free heap,
+/// uptime and WiFi health.
+async fn current_stats() -> Stats {
+    Stats {
+        frame_us: *FRAME_TIME_US.lock().await,
+        free_heap: esp_alloc::HEAP.free(),
+        uptime_secs: Instant::now().as_secs(),
+        wifi_failures: wifi::connect_failures().await,
+    }
+}
+
+/// `/status` as JSON: uptime, WiFi signal, free heap, the network and
+/// address `stack` is on, and the display mode.
+async fn current_status(stack: &Stack<'static>) -> heapless::String<STATUS_JSON_LEN> {
+    let ssid = wifi::joined_ssid().await;
+    let ip = wifi::get_ip_address(stack);
+    Status {
+        uptime_secs: Instant::now().as_secs(),
+        rssi: wifi::rssi().await,
+        free_heap: esp_alloc::HEAP.free(),
+        ssid: ssid.as_deref(),
+        ip: ip.as_deref(),
+        mode: DISPLAY_MODE.lock().await.name(),
+    }
+    .to_json()
+}
+
+/// Read one HTTP request into a fixed buffer, until
+/// [`http_request::is_complete`], the peer stops sending, or the buffer
+/// is full. Returns the buffer and how much of it was filled, or `None`
+/// on read errors so the caller can move on to the next connection.
+async fn read_request(
+    socket: &mut TcpSocket<'_>,
+) -> Option<([u8; http_request::MAX_REQUEST_LEN], usize)> {
+    let mut buf = [0u8; http_request::MAX_REQUEST_LEN];
+    let mut len = 0;
+    while len < buf.len() {
+        match socket.read(&mut buf[len..]).await {
+            Ok(0) => break,
+            Ok(n) => {
+                len += n;
+                if http_request::is_complete(&buf[..len]) {
+                    break;
+                }
+            }
+            Err(e) => {
+                debug!("Read error: {:?}", e);
+                return None;
+            }
+        }
+    }
+    Some((buf, len))
+}
+
+/// Receive the `POST /image` body into [`IMAGE`] and tell the refresh
+/// loop to show it. `received` is the part of the body that arrived with
+/// the headers; the rest is read from `socket` in chunks. Returns `false`
+/// if the peer stops sending early or the read fails.
+async fn receive_image(socket: &mut TcpSocket<'_>, received: &[u8]) -> bool {
+    let mut image = IMAGE.lock().await;
+    let mut chunk = [0u8; IMAGE_CHUNK_LEN];
+    let mut len = received.len().min(http_request::IMAGE_LEN);
+    chunk[..len].copy_from_slice(&received[..len]);
+    // Bytes already decoded into `image`. Always even; an odd trailing
+    // byte waits at the front of `chunk` for its partner.
+    let mut stored = 0;
+    loop {
+        let whole = len & !1;
+        let pixels = http_request::rgb565_pixels(&chunk[..whole]);
+        for (px, value) in image[stored / 2..].iter_mut().zip(pixels) {
+            *px = value;
+        }
+        stored += whole;
+        chunk.copy_within(whole..len, 0);
+        len -= whole;
+        if stored == http_request::IMAGE_LEN {
+            break;
+        }
+
+        let end = (len + http_request::IMAGE_LEN - stored).min(chunk.len());
+        match socket.read(&mut chunk[len..end]).await {
+            Ok(0) => {
+                warn!("Image upload ended after {} bytes", stored + len);
+                return false;
+            }
+            Ok(n) => len += n,
+            Err(e) => {
+                debug!("Read error: {:?}", e);
+                return false;
+            }
+        }
+    }
+    IMAGE_READY.signal(());
+    info!("Image received");
+    true
+}
+
+/// Receive a `len`-byte BMP `POST /image` body, decoding it into
+/// [`IMAGE`] as it arrives, and tell the refresh loop to show it.
+/// `received` is the part of the body that arrived with the headers.
+/// Stops at the first byte that makes the file unusable.
+async fn receive_bmp(
+    socket: &mut TcpSocket<'_>,
+    received: &[u8],
+    len: usize,
+) -> Result<(), BmpError> {
+    let mut image = IMAGE.lock().await;
+    let mut decoder = BmpDecoder::new();
+    let first = received.len().min(len);
+    decoder.feed(&received[..first], &mut image)?;
+    let mut remaining = len - first;
+    let mut chunk = [0u8; IMAGE_CHUNK_LEN];
+    while remaining > 0 {
+        let end = remaining.min(chunk.len());
+        match socket.read(&mut chunk[..end]).await {
+            Ok(0) => {
+                warn!("BMP upload ended {} bytes early", remaining);
+                return Err(BmpError::Truncated);
+            }
+            Ok(n) => {
+                decoder.feed(&chunk[..n], &mut image)?;
+                remaining -= n;
+            }
+            Err(e) => {
+                debug!("Read error: {:?}", e);
+                return Err(BmpError::Truncated);
+            }
+        }
+    }
+    let header = decoder.finish()?;
+    IMAGE_READY.signal(());
+    info!("BMP image received: {}x{}", header.width, header.height);
+    Ok(())
+}
+
+/// Receive a `len`-byte `POST /anim` frame pack into [`FRAME_PACK`] and
+/// play it. `received` is the part of the body that arrived with the
+/// headers. A pack that fails halfway is dropped, so no half-uploaded
+/// animation plays.
+async fn receive_frame_pack(
+    socket: &mut TcpSocket<'_>,
+    received: &[u8],
+    len: usize,
+) -> Result<(), FramePackError> {
+    let mut pack = FRAME_PACK.lock().await;
+    match decode_frame_pack(socket, received, len, &mut pack).await {
+        Ok(frames) => {
+            info!("Frame pack received: {} frames", frames);
+            *DISPLAY_MODE.lock().await = DisplayMode::Animation;
+            FRAME_PACK_READY.signal(());
+            Ok(())
+        }
         Err(e) => {
-            debug!("Read error: {:?}", e);
-            None
+            pack.clear();
+            Err(e)
+        }
+    }
+}
+
+/// Decode a frame pack into `pack` as it arrives. Returns its frame
+/// count.
+async fn decode_frame_pack(
+    socket: &mut TcpSocket<'_>,
+    received: &[u8],
+    len: usize,
+    pack: &mut FramePack,
+) -> Result<usize, FramePackError> {
+    let mut decoder = FramePackDecoder::new();
+    let first = received.len().min(len);
+    decoder.feed(&received[..first], pack)?;
+    let mut remaining = len - first;
+    let mut chunk = [0u8; IMAGE_CHUNK_LEN];
+    while remaining > 0 {
+        let end = remaining.min(chunk.len());
+        match socket.read(&mut chunk[..end]).await {
+            Ok(0) => {
+                warn!("Frame pack upload ended {} bytes early", remaining);
+                return Err(FramePackError::Truncated);
+            }
+            Ok(n) => {
+                decoder.feed(&chunk[..n], pack)?;
+                remaining -= n;
+            }
+            Err(e) => {
+                debug!("Read error: {:?}", e);
+                return Err(FramePackError::Truncated);
+            }
         }
     }
+    decoder.finish(pack)
+}
+
+/// Receive a `len`-byte `POST /font` body and hand the font to the
+/// refresh loop; an empty body restores the built-in font. `received` is
+/// the part that arrived with the headers. Returns `false` if the peer
+/// stops sending early, the read fails or the font is invalid.
+async fn receive_font(socket: &mut TcpSocket<'_>, received: &[u8], len: usize) -> bool {
+    let mut data = [0u8; MAX_FONT_LEN];
+    let mut stored = received.len().min(len);
+    data[..stored].copy_from_slice(&received[..stored]);
+    while stored < len {
+        match socket.read(&mut data[stored..len]).await {
+            Ok(0) => {
+                warn!("Font upload ended after {} bytes", stored);
+                return false;
+            }
+            Ok(n) => stored += n,
+            Err(e) => {
+                debug!("Read error: {:?}", e);
+                return false;
+            }
+        }
+    }
+    let font = if len == 0 {
+        Ok(Font::new())
+    } else {
+        Font::from_bytes(&data[..len])
+    };
+    match font {
+        Ok(font) => {
+            info!("Font received ({}x{})", font.width(), font.height());
+            FONT.signal(font);
+            true
+        }
+        Err(e) => {
+            warn!("Rejected font: {:?}", e);
+            false
+        }
+    }
+}
+
+/// Check a `POST /ota` upload's token, then stream its body into the idle
+/// app slot, one sector at a time, and select it for the next boot.
+/// `received` is the part of the body that arrived with the headers. On
+/// failure returns the reply to send instead; the running firmware stays
+/// selected.
+async fn receive_ota(
+    socket: &mut TcpSocket<'_>,
+    received: &[u8],
+    upload: &http_request::OtaUpload,
+) -> Result<(), &'static [u8]> {
+    let expected = nvs::load::<MAX_TOKEN_LEN>(nvs::Slot::OtaToken).await;
+    if !expected.is_some_and(|expected| auth::tokens_match(&expected, &upload.token)) {
+        warn!("OTA upload with a wrong token, or none saved");
+        return Err(http_request::FORBIDDEN_RESPONSE);
+    }
+    let failed = |e: ota::OtaError| {
+        warn!("OTA failed ({:?}), keeping the current firmware", e);
+        http_request::BAD_REQUEST_RESPONSE
+    };
+    let mut update = ota::Update::begin(upload.len).await.map_err(failed)?;
+    let mut chunk = [0u8; OTA_CHUNK_LEN];
+    let mut filled = received.len().min(upload.len).min(chunk.len());
+    chunk[..filled].copy_from_slice(&received[..filled]);
+    let mut received = &received[filled..];
+    let mut remaining = upload.len - filled;
+    loop {
+        // Fill the chunk, first from what arrived with the headers.
+        while filled < chunk.len() && remaining > 0 {
+            let end = (filled + remaining).min(chunk.len());
+            let n = if received.is_empty() {
+                match socket.read(&mut chunk[filled..end]).await {
+                    Ok(0) => return Err(failed(ota::OtaError::Incomplete)),
+                    Ok(n) => n,
+                    Err(e) => {
+                        debug!("Read error: {:?}", e);
+                        return Err(failed(ota::OtaError::Incomplete));
+                    }
+                }
+            } else {
+                let n = received.len().min(end - filled);
+                chunk[filled..filled + n].copy_from_slice(&received[..n]);
+                received = &received[n..];
+                n
+            };
+            filled += n;
+            remaining -= n;
+        }
+        update.write(&chunk[..filled]).await.map_err(failed)?;
+        if remaining == 0 {
+            break;
+        }
+        filled = 0;
+    }
+    update.finish().await.map_err(failed)
+}
+
+/// Show every image that arrives on an upgraded `/ws` connection, until
+/// the client closes it or the connection drops. Malformed messages are
+/// logged and skipped. Frames are assembled in [`WEBSOCKET_FRAME`], so
+/// a half-received one never reaches the panel. Other HTTP requests are
+/// served by the other server tasks meanwhile; a second `/ws` client
+/// waits until the first disconnects.
+async fn serve_websocket(socket: &mut TcpSocket<'_>) {
+    info!("WebSocket client connected");
+    let mut decoder = Decoder::new();
+    let mut frame = WEBSOCKET_FRAME.lock().await;
+    let mut chunk = [0u8; WEBSOCKET_CHUNK_LEN];
+    loop {
+        let len = match socket.read(&mut chunk).await {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) => {
+                debug!("Read error: {:?}", e);
+                break;
+            }
+        };
+        let mut pos = 0;
+        while pos < len {
+            let (used, event) = decoder.feed(&mut chunk[pos..len], &mut frame);
+            pos += used;
+            match event {
+                Some(Event::Frame) => {
+                    SCREENSAVER.lock().await.wake(Instant::now().as_millis());
+                    let mut image = IMAGE.lock().await;
+                    for (px, value) in image.iter_mut().zip(http_request::rgb565_pixels(&*frame)) {
+                        *px = value;
+                    }
+                    IMAGE_READY.signal(());
+                }
+                Some(Event::Text(text)) => {
+                    SCREENSAVER.lock().await.wake(Instant::now().as_millis());
+                    match Direction::from_name(text.trim()) {
+                        Some(direction) => queue_game_input(Some(GameInput::Steer(direction))),
+                        None => warn!("Unknown WebSocket command {}", text.as_str()),
+                    }
+                }
+                Some(Event::Ping(payload)) => {
+                    write_frame(socket, &websocket::control_frame(Opcode::Pong, &payload)).await;
+                }
+                Some(Event::Close) => {
+                    write_frame(socket, &websocket::control_frame(Opcode::Close, &[])).await;
+                    info!("WebSocket client disconnected");
+                    return;
+                }
+                Some(Event::Dropped(reason)) => {
+                    warn!("Dropped WebSocket message: {:?}", reason);
+                }
+                None => {}
+            }
+        }
+    }
+    info!("WebSocket connection lost");
+}
+
+/// Stream display changes to an `/events` client as they are published,
+/// with a keepalive when there are none, until a write fails. Dropping
+/// `subscriber` then frees the client's slot.
+async fn serve_events(socket: &mut TcpSocket<'_>, mut subscriber: EventSubscriber) {
+    info!("Event client connected");
+    loop {
+        let keepalive = Duration::from_secs(KEEPALIVE_SECS);
+        let written = match with_timeout(keepalive, subscriber.next_message_pure()).await {
+            Ok(event) => write_all(socket, event.to_sse().as_bytes()).await,
+            Err(_) => write_all(socket, SSE_KEEPALIVE.as_bytes()).await,
+        };
+        if let Err(e) = written {
+            debug!("Write error: {:?}", e);
+            break;
+        }
+    }
+    info!("Event client disconnected");
+}
+
+/// Answer `GET /screenshot`: have the refresh loop encode the frame on
+/// screen, then send it straight from [`SCREENSHOT`] to the socket.
+async fn send_screenshot(socket: &mut TcpSocket<'_>, format: screenshot::Format) {
+    SCREENSHOT_READY.reset();
+    SCREENSHOT_REQUEST.signal(format);
+    let len = SCREENSHOT_READY.wait().await;
+    let image = SCREENSHOT.lock().await;
+    let headers = format.headers();
+    if let Err(e) = write_all(socket, headers.as_bytes()).await {
+        debug!("Write error: {:?}", e);
+        return;
+    }
+    if let Err(e) = write_all(socket, &image[..len]).await {
+        debug!("Write error: {:?}", e);
+    }
+    info!("Screenshot sent ({} bytes)", len);
+}
+
+/// Write all of `data`, however many calls it takes.
+async fn write_all(
+    socket: &mut TcpSocket<'_>,
+    mut data: &[u8],
+) -> Result<(), embassy_net::tcp::Error> {
+    while !data.is_empty() {
+        match socket.write(data).await? {
+            0 => return Err(embassy_net::tcp::Error::ConnectionReset),
+            n => data = &data[n..],
+        }
+    }
+    socket.flush().await
+}
+
+/// Send one WebSocket frame right away.
+async fn write_frame(socket: &mut TcpSocket<'_>, frame: &[u8]) {
+    if let Err(e) = socket.write(frame).await {
+        debug!("Write error: {:?}", e);
+    }
+    let _ = socket.flush().await;
 }
 
 /// If the parsed response carries a new display text, update the
-/// shared `DISPLAY_TEXT` global. Logs the change.
+/// shared `DISPLAY_TEXT` global and save it to flash. Logs the change.
 async fn apply_text_update(text: Option<heapless::String<{ http_request::MAX_MESSAGE_LEN }>>) {
     if let Some(text) = text {
-        let mut display_text = DISPLAY_TEXT.lock().await;
-        *display_text = text;
+        *DISPLAY_TEXT.lock().await = text.clone();
         info!("Display text updated");
+        nvs::save_text(&text).await;
     }
 }
 
-/// Write the response body to the socket. For HTML pages we append the
-/// page body after the headers; everything else already includes its
-/// own headers.
-async fn write_response(socket: &mut TcpSocket<'_>, body: &'static [u8]) {
-    let mut response_data = [0u8; 8192];
-    let mut offset;
+/// Animate the next text change with `transition`, or switch at once
+/// for `None`.
+async fn apply_transition(transition: Option<Transition>) {
+    *TRANSITION.lock().await = transition;
+}
 
-    if body.starts_with(b"HTTP/1.1 200") {
-        response_data[..body.len()].copy_from_slice(body);
-        offset = body.len();
-        let page = http_request::html_page();
-        response_data[offset..offset + page.len()].copy_from_slice(page);
-        offset += page.len();
-    } else {
-        response_data[..body.len()].copy_from_slice(body);
-        offset = body.len();
+/// Color the next text with `gradient` while it scrolls, or in the text
+/// color for `None`.
+async fn apply_gradient(gradient: Option<GradientMode>) {
+    *SCROLL_GRADIENT.lock().await = gradient;
+}
+
+/// Color the next text segment by segment with `rich`, or in the text
+/// color for `None`.
+async fn apply_rich_text(rich: Option<RichText>) {
+    *RICH_TEXT.lock().await = rich;
+}
+
+/// If the parsed response carries a new text color, update the shared
+/// `TEXT_COLOR` global.
+async fn apply_text_color(color: Option<Pixel>) {
+    if let Some(color) = color {
+        *TEXT_COLOR.lock().await = color;
+        info!("Text color updated");
     }
+}
 
-    if let Err(e) = socket.write(&response_data[..offset]).await {
-        debug!("Write error: {:?}", e);
+/// If the parsed response carries a new brightness level, update the
+/// shared `BRIGHTNESS` global.
+async fn apply_brightness(level: Option<u8>) {
+    if let Some(level) = level {
+        *BRIGHTNESS.lock().await = level;
+        info!("Brightness set to {}", level);
+    }
+}
+
+/// If the parsed response carries a timer command, start or pause the
+/// countdown.
+async fn apply_timer(command: Option<TimerCommand>) {
+    if let Some(command) = command {
+        COUNTDOWN
+            .lock()
+            .await
+            .apply(command, Instant::now().as_millis());
+        info!("Timer: {:?}", command);
+    }
+}
+
+/// If the parsed response carries a graph sample, add it and have the
+/// refresh loop redraw the graph.
+async fn apply_graph_sample(sample: Option<f32>) {
+    if let Some(value) = sample {
+        GRAPH.lock().await.push(value);
+        GRAPH_UPDATED.signal(());
+    }
+}
+
+/// If the parsed response carries a region's text, set it and have the
+/// refresh loop redraw the regions.
+async fn apply_region(update: Option<RegionUpdate>) {
+    if let Some(update) = update {
+        REGIONS.lock().await.apply(&update);
+        REGIONS_UPDATED.signal(());
+        info!("Region {:?}: {}", update.region, update.text.as_str());
+    }
+}
+
+/// If the parsed response carries a screensaver timeout, switch to it
+/// and save it.
+async fn apply_idle_timeout(secs: Option<u32>) {
+    if let Some(secs) = secs {
+        SCREENSAVER
+            .lock()
+            .await
+            .set_timeout(secs, Instant::now().as_millis());
+        nvs::save_idle_timeout(secs).await;
+        info!("Idle timeout set to {} s", secs);
+    }
+}
+
+/// If the parsed response carries a refresh-rate target, pace the
+/// refresh loop to it.
+async fn apply_target_fps(fps: Option<u32>) {
+    if let Some(fps) = fps {
+        FRAME_PACER.lock().await.set_target_fps(fps);
+        info!("Target refresh rate set to {} fps", fps);
+    }
+}
+
+/// If the parsed response carries a notice, queue it behind any others.
+async fn apply_notice(notice: Option<TimedNotice>) {
+    if let Some(notice) = notice
+        && let Err(notice) = NOTICES.lock().await.push(notice)
+    {
+        warn!("Notice queue full, dropping {:?}", notice.notice);
+    }
+}
+
+/// If the parsed response carries a new display mode, switch to it.
+async fn apply_mode(mode: Option<DisplayMode>) {
+    if let Some(mode) = mode {
+        *DISPLAY_MODE.lock().await = mode;
+        info!("Display mode set to {:?}", mode);
+    }
+}
+
+/// Save the current mode and text color, so they come back after a
+/// reboot.
+async fn save_display_state() {
+    let state = DisplayState {
+        mode: *DISPLAY_MODE.lock().await,
+        color: *TEXT_COLOR.lock().await,
+    };
+    nvs::save_display_state(&state).await;
+}
+
+/// If the parsed response carries a new time zone, use and save it.
+async fn apply_timezone(tz: Option<heapless::String<MAX_TZ_LEN>>) {
+    if let Some(tz) = tz {
+        clock::set_timezone(&tz).await;
+    }
+}
+
+/// If the parsed response carries a new mDNS hostname, save it. The
+/// responder keeps the current name until the next boot.
+async fn apply_hostname(hostname: Option<mdns::Hostname>) {
+    if let Some(hostname) = hostname {
+        mdns::save_hostname(&hostname).await;
+        info!("Hostname set to {}.local, used after reboot", hostname);
+    }
+}
+
+/// If the parsed response carries new WiFi credentials, save them and
+/// reboot so the device comes back up in station mode on that network.
+async fn apply_credentials(credentials: Option<Credentials>) {
+    if let Some(credentials) = credentials {
+        info!("WiFi credentials updated for SSID {}", credentials.ssid);
+        wifi::save_credentials(&credentials).await;
+        info!("Rebooting to join the new network...");
+        reboot().await;
+    }
+}
+
+/// Reset the chip, after giving the TCP stack a moment to deliver the
+/// reply.
+async fn reboot() -> ! {
+    Timer::after(Duration::from_millis(REBOOT_DELAY_MS)).await;
+    esp_hal::system::software_reset()
+}
+
+/// Save the `POST /ota` token from the setup form, unless one is already
+/// saved: changing it takes erasing the `nvs` partition over USB, so a
+/// client on the network can't replace it.
+async fn apply_ota_token(token: Option<heapless::String<MAX_TOKEN_LEN>>) {
+    if let Some(token) = token {
+        if nvs::load::<MAX_TOKEN_LEN>(nvs::Slot::OtaToken)
+            .await
+            .is_some()
+        {
+            warn!("OTA token already set, not replacing it");
+        } else {
+            nvs::save(nvs::Slot::OtaToken, &token).await;
+            info!("OTA token saved");
+        }
+    }
+}
+
+/// The control password saved via `POST /setauth`, if any.
+async fn load_auth_password() -> Option<heapless::String<MAX_AUTH_PASSWORD_LEN>> {
+    nvs::load(nvs::Slot::AuthPassword)
+        .await
+        .filter(|password| !password.is_empty())
+}
+
+/// If the parsed response carries a new control password, save it. An
+/// empty one turns authentication off.
+async fn apply_auth_password(password: Option<heapless::String<MAX_AUTH_PASSWORD_LEN>>) {
+    if let Some(password) = password {
+        nvs::save(nvs::Slot::AuthPassword, &password).await;
+        if password.is_empty() {
+            info!("Control password removed");
+        } else {
+            info!("Control password set");
+        }
+    }
+}
+
+/// Hand Snake input to the refresh loop. Never blocks — if the queue
+/// is full the input is dropped and logged.
+fn queue_game_input(input: Option<GameInput>) {
+    if let Some(input) = input
+        && let Err(TrySendError::Full(input)) = GAME_INPUT.try_send(input)
+    {
+        warn!("Game input queue full, dropping {:?}", input);
+    }
+}
+
+/// Hand a drawing command to the refresh loop. Never blocks — if the
+/// queue is full the command is dropped and logged. Requests were
+/// already checked for room by [`http_request::backpressure`], so that
+/// only happens when the other server task filled it in between.
+fn queue_draw(cmd: Option<DrawCommand>) {
+    if let Some(cmd) = cmd
+        && let Err(TrySendError::Full(cmd)) = DRAW_QUEUE.try_send(cmd)
+    {
+        warn!("Draw queue full, dropping {:?}", cmd);
+    }
+}
+
+/// Write the response to the socket: `body` (headers, or a complete
+/// response) followed by `page`, which is empty when `body` already
+/// stands alone. Both go out straight from where they are, in as many
+/// writes as the socket's buffer needs, so a page of any size never has
+/// to be copied into one contiguous buffer; a `Content-Length` header
+/// for `page` goes in between.
+async fn write_response(socket: &mut TcpSocket<'_>, body: &[u8], page: &[u8]) {
+    let (head, length) = if page.is_empty() {
+        (body, heapless::String::new())
+    } else {
+        http_request::content_length_head(body, page.len())
+    };
+    for part in [head, length.as_bytes(), page] {
+        if let Err(e) = write_all(socket, part).await {
+            debug!("Write error: {:?}", e);
+            return;
+        }
     }
 }
@@ -0,0 +1,262 @@
+//! Minimal JSON reader for request bodies.
+//!
+//! A pull parser over a byte slice: the caller asks for the value it
+//! expects next ([`Reader::string`], [`Reader::integer`], ...) and gets
+//! `None` if the input has something else. Only what the API needs is
+//! supported: numbers must be integers, and strings are decoded into a
+//! fixed-capacity [`heapless::String`]. Nothing is allocated.
+
+/// How deeply [`Reader::skip_value`] follows nested arrays and objects.
+const MAX_DEPTH: usize = 8;
+
+/// Position in a JSON document.
+#[derive(Debug, Clone)]
+pub struct Reader<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Reader at the start of `input`.
+    pub fn new(input: &'a [u8]) -> Self {
+        Self { input, pos: 0 }
+    }
+
+    /// Next non-whitespace byte, without consuming it.
+    pub fn peek(&mut self) -> Option<u8> {
+        while let Some(&b) = self.input.get(self.pos) {
+            if !matches!(b, b' ' | b'\t' | b'\r' | b'\n') {
+                return Some(b);
+            }
+            self.pos += 1;
+        }
+        None
+    }
+
+    /// Consume `byte` (after whitespace) if it comes next.
+    pub fn eat(&mut self, byte: u8) -> bool {
+        let found = self.peek() == Some(byte);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    /// Consume `byte`, or `None` if something else comes next.
+    pub fn expect(&mut self, byte: u8) -> Option<()> {
+        self.eat(byte).then_some(())
+    }
+
+    /// True when only whitespace is left.
+    pub fn at_end(&mut self) -> bool {
+        self.peek().is_none()
+    }
+
+    /// Read a string, decoding escapes. `None` if the next value isn't a
+    /// string, is malformed or is longer than `N` bytes.
+    pub fn string<const N: usize>(&mut self) -> Option<heapless::String<N>> {
+        self.expect(b'"')?;
+        let mut out = heapless::String::new();
+        loop {
+            let b = *self.input.get(self.pos)?;
+            self.pos += 1;
+            let ch = match b {
+                b'"' => return Some(out),
+                b'\\' => {
+                    let escape = *self.input.get(self.pos)?;
+                    self.pos += 1;
+                    match escape {
+                        b'"' | b'\\' | b'/' => escape as char,
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.unicode_escape()?,
+                        _ => return None,
+                    }
+                }
+                0x00..=0x1F => return None,
+                0x80.. => {
+                    // Copy a multi-byte UTF-8 sequence whole.
+                    let start = self.pos - 1;
+                    let len = match b {
+                        0xC0..=0xDF => 2,
+                        0xE0..=0xEF => 3,
+                        _ => 4,
+                    };
+                    let bytes = self.input.get(start..start + len)?;
+                    out.push_str(core::str::from_utf8(bytes).ok()?).ok()?;
+                    self.pos = start + len;
+                    continue;
+                }
+                _ => b as char,
+            };
+            out.push(ch).ok()?;
+        }
+    }
+
+    /// The code point of a `\uXXXX` escape (the `\u` already read),
+    /// combining a surrogate pair.
+    fn unicode_escape(&mut self) -> Option<char> {
+        let high = self.hex4()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high);
+        }
+        if self.input.get(self.pos..self.pos + 2)? != b"\\u" {
+            return None;
+        }
+        self.pos += 2;
+        let low = self.hex4()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return None;
+        }
+        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+    }
+
+    fn hex4(&mut self) -> Option<u32> {
+        let digits = self.input.get(self.pos..self.pos + 4)?;
+        let digits = core::str::from_utf8(digits).ok()?;
+        let value = u32::from_str_radix(digits, 16).ok()?;
+        self.pos += 4;
+        Some(value)
+    }
+
+    /// Read an integer. `None` for anything else, including fractions and
+    /// exponents, or if it doesn't fit an `i64`.
+    pub fn integer(&mut self) -> Option<i64> {
+        self.peek()?;
+        let start = self.pos;
+        if self.input.get(self.pos) == Some(&b'-') {
+            self.pos += 1;
+        }
+        while self.input.get(self.pos).is_some_and(u8::is_ascii_digit) {
+            self.pos += 1;
+        }
+        if matches!(self.input.get(self.pos), Some(b'.' | b'e' | b'E')) {
+            return None;
+        }
+        let digits = core::str::from_utf8(&self.input[start..self.pos]).ok()?;
+        digits.parse().ok()
+    }
+
+    /// Read `true` or `false`.
+    pub fn boolean(&mut self) -> Option<bool> {
+        self.peek()?;
+        for (word, value) in [(&b"true"[..], true), (b"false", false)] {
+            if self.input[self.pos..].starts_with(word) {
+                self.pos += word.len();
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// Skip over the next value of any type. `None` if it is malformed.
+    pub fn skip_value(&mut self) -> Option<()> {
+        self.skip_nested(0)
+    }
+
+    fn skip_nested(&mut self, depth: usize) -> Option<()> {
+        match self.peek()? {
+            b'"' => self.skip_string(),
+            open @ (b'[' | b'{') => {
+                if depth == MAX_DEPTH {
+                    return None;
+                }
+                self.pos += 1;
+                let close = if open == b'[' { b']' } else { b'}' };
+                if self.eat(close) {
+                    return Some(());
+                }
+                loop {
+                    if open == b'{' {
+                        self.skip_string()?;
+                        self.expect(b':')?;
+                    }
+                    self.skip_nested(depth + 1)?;
+                    if self.eat(close) {
+                        return Some(());
+                    }
+                    self.expect(b',')?;
+                }
+            }
+            b't' | b'f' => self.boolean().map(|_| ()),
+            b'n' => {
+                let null = self.input[self.pos..].starts_with(b"null");
+                self.pos += 4;
+                null.then_some(())
+            }
+            _ => self.integer().map(|_| ()),
+        }
+    }
+
+    /// Skip a string of any length.
+    fn skip_string(&mut self) -> Option<()> {
+        self.expect(b'"')?;
+        loop {
+            match *self.input.get(self.pos)? {
+                b'"' => {
+                    self.pos += 1;
+                    return Some(());
+                }
+                b'\\' => self.pos += 2,
+                _ => self.pos += 1,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_strings_with_escapes() {
+        let mut r = Reader::new(br#" "a\"b\\c\/\n\u00e9\ud83d\ude00" "#);
+        let s: heapless::String<16> = r.string().unwrap();
+        assert_eq!(s.as_str(), "a\"b\\c/\n\u{e9}\u{1F600}");
+        assert!(r.at_end());
+    }
+
+    #[test]
+    fn keeps_raw_utf8() {
+        let mut r = Reader::new("\"ÄÖ\"".as_bytes());
+        assert_eq!(r.string::<8>().unwrap().as_str(), "ÄÖ");
+    }
+
+    #[test]
+    fn rejects_bad_strings() {
+        assert!(Reader::new(br#""unterminated"#).string::<16>().is_none());
+        assert!(Reader::new(br#""bad \x escape""#).string::<16>().is_none());
+        assert!(Reader::new(br#""too long""#).string::<4>().is_none());
+        assert!(Reader::new(b"42").string::<4>().is_none());
+    }
+
+    #[test]
+    fn reads_integers_only() {
+        assert_eq!(Reader::new(b" -12,").integer(), Some(-12));
+        assert_eq!(Reader::new(b"65535]").integer(), Some(65535));
+        assert_eq!(Reader::new(b"1.5").integer(), None);
+        assert_eq!(Reader::new(b"1e3").integer(), None);
+        assert_eq!(Reader::new(b"-").integer(), None);
+        assert_eq!(Reader::new(b"\"1\"").integer(), None);
+    }
+
+    #[test]
+    fn reads_booleans() {
+        assert_eq!(Reader::new(b"true").boolean(), Some(true));
+        assert_eq!(Reader::new(b" false").boolean(), Some(false));
+        assert_eq!(Reader::new(b"yes").boolean(), None);
+    }
+
+    #[test]
+    fn skips_nested_values() {
+        let mut r = Reader::new(br#"{"a":[1,{"b":null}],"long string":"xyz"} ,7"#);
+        r.skip_value().unwrap();
+        r.expect(b',').unwrap();
+        assert_eq!(r.integer(), Some(7));
+        assert!(Reader::new(b"[1,]").skip_value().is_none());
+        assert!(Reader::new(b"[[[[[[[[[1]]]]]]]]]").skip_value().is_none());
+    }
+}
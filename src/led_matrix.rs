@@ -1,117 +1,149 @@
 //! LED Matrix Driver for 88x88 RGB Display
 //!
-//! This driver implements the control protocol for the LED matrix display
+//! This driver runs the control protocol for the LED matrix display
 //! based on the [Helsinki Hacklab documentation][wiki].
 //!
 //! [wiki]: https://wiki.helsinki.hacklab.fi/Ledimatriisin_ohjaaminen
 //!
-//! ## Control Signals
-//! - GCLK: Multiplex clock (~1 MHz, 256 pulses per scanline, plus a 257th
-//!   pulse with longer high/low "dead time" before the next scanline)
-//! - DCLK: Data clock for shift register
-//! - LE: Latch Enable (combined with DCLK for commands — see "Commands" below)
-//! - A0-A3: Scanline address (0-10)
-//! - DR1,DG1,DB1: RGB data chain 1
-//! - DR2,DG2,DB2: RGB data chain 2
-//!
-//! ## Display Architecture
-//! - 88x88 pixels, RGB
-//! - 6 parallel shift register chains (R1,G1,B1,R2,G2,B2) each holding 22
-//!   driver ICs in series
-//! - 11:1 multiplexing (11 scanlines, 8 physical rows per scanline)
-//! - 16-bit PWM per color channel
-//! - Double buffering with VSYNC
-//!
-//! ## Commands (sent via LE + N DCLK pulses)
-//! Raising LE and pulsing DCLK N times issues a command. The number of
-//! pulses is the command code:
-//!
-//! | N  | Command                                |
-//! |----|----------------------------------------|
-//! | 1  | Data Latch (strobe shift register)     |
-//! | 2  | VSYNC (swap display buffers)           |
-//! | 4  | Write Configuration1 register          |
-//! | 10 | Reset                                  |
-//! | 14 | Pre-Active (enable configuration write)|
-//!
-//! When LE is low, DCLK shifts the data lines into the shift register chain.
+//! The protocol itself — signals, commands and the per-frame bit stream —
+//! lives in [`panel`](esp32_led_matrix::panel), generic over how the pins
+//! are driven, so it can run on the host against a simulated panel. This
+//! module supplies the ESP32-C3 pins and keeps the frame buffers.
 //!
 //! ## Data flow
-//! Per frame:
-//! 1. [`chain_mapper::compute_chain_data`] translates the linear 88x88
-//!    bitmap into the 44 pixels-per-cycle ordering the chain hardware expects.
-//! 2. [`bit_stream::chain_data_bits`] converts the chain data to MSB-first
-//!    bit patterns for the shift register.
-//! 3. [`LedMatrix::write_chain`] toggles GPIO pins in lockstep with DCLK.
+//! Drawing goes to a back [`FrameBuffer`]; [`LedMatrix::swap_buffers`]
+//! copies finished frames to the front buffer, which is all `refresh`
+//! reads. Batch several drawing calls between
+//! [`LedMatrix::begin_frame`] and [`LedMatrix::commit_frame`] so none of
+//! the intermediate states (e.g. the cleared screen at the start of
+//! `display_text`) is ever shown. With [`LedMatrix::start_transition`]
+//! the next frame fades or wipes in instead: `refresh` mixes the front
+//! and back buffers until it is done.
+//!
+//! [`LedMatrix::refresh`] hands the front buffer (and, mid-transition,
+//! the back buffer) to [`Panel::send_frame`]. When neither the front
+//! buffer nor the brightness or gamma changed since, it calls
+//! [`Panel::hold_frame`] instead, which only keeps GCLK running; see
+//! [`dirty`](esp32_led_matrix::dirty) for why that is all-or-nothing.
 //!
-//! Configuration is sent once at init via [`bit_stream::config_bits`].
+//! All of this is bit-banged: the ESP32-C3 has no parallel I2S/LCD,
+//! LCD_CAM or PARL_IO peripheral to clock the six data lines out by DMA
+//! (see "Why No DMA Output?" in `README.md`).
 //!
-//! The pure logic (frame buffer, chain mapping, bit stream generation) lives
-//! in its own modules so it can be unit-tested on the host. This module owns
-//! the GPIO pins and orchestrates the protocol.
+//! The pure logic (frame buffer, chain mapping, bit stream generation,
+//! the protocol) lives in its own modules so it can be unit-tested on the
+//! host. This module owns the GPIO pins and the double buffering.
 
 use esp_hal::delay::Delay;
-use esp_hal::gpio::Output;
-
-use esp32_led_matrix::bit_stream::{self, ChainBit, PWM_BITS};
-use esp32_led_matrix::chain_mapper::{self, CHAIN_LEN, SCANLINES};
-use esp32_led_matrix::frame_buffer::FrameBuffer;
-
-/// Configuration1 register value, per the wiki:
-///   - scanline count = 11
-///   - GCLK multiplier enabled
-///   - 16-bit PWM (not 13-bit)
-///   - current gain = 5 (room lighting)
-/// The wiki recommends `0x0A45`. The Teensy reference design uses `0x0A4B`
-/// (current gain 11). We use the wiki value.
-const CONFIG_REGISTER_1: u16 = 0x0A45;
-
-/// GCLK pulses per scanline (per the wiki: 256 regular pulses + 1 dead-time
-/// pulse = 257 total).
-const GCLK_PULSES_PER_SCANLINE: u32 = 256;
-
-/// Dead time on the 257th GCLK pulse (the wiki says longer delays are
-/// *required* there — MBI5252 datasheet parameters `tdth` and `tdtl` are
-/// minimums in the low-microsecond range, so 5 µs on each phase gives a
-/// 10 µs period, ~10× the normal pulse width).
-const GCLK_DEAD_TIME_US: u32 = 5;
-
-/// Commands sent via LE + DCLK pulses.
-#[repr(u8)]
-#[derive(Clone, Copy)]
-enum Command {
-    /// Swap display buffers (front <-> back). Must be issued at the
-    /// scanline 10 -> 0 transition.
-    Vsync = 2,
-    /// Reset the display.
-    Reset = 10,
-    /// Pre-Active — enables writes to Configuration1.
-    PreActive = 14,
+use esp_hal::gpio::{Flex, Output};
+use esp_hal::time::Instant;
+use log::{error, info, warn};
+
+use esp32_led_matrix::command::DrawCommand;
+use esp32_led_matrix::crash_screen::crash_pixel;
+use esp32_led_matrix::effects::Animation;
+use esp32_led_matrix::font::Font;
+use esp32_led_matrix::frame_buffer::{
+    Align, FrameBuffer, Orientation, Pixel, load_pixel, store_pixel,
+};
+use esp32_led_matrix::gradient::GradientMode;
+use esp32_led_matrix::graph::Graph;
+use esp32_led_matrix::mode::TestPattern;
+use esp32_led_matrix::panel::{
+    ConfigRegister, DCLK_PIN, Frame, LE_PIN, PIN_MAP, Panel, PinCaveat, Pins,
+};
+use esp32_led_matrix::regions::Regions;
+use esp32_led_matrix::sprite::Sprite;
+use esp32_led_matrix::stats::FrameTimer;
+use esp32_led_matrix::transition::{Transition, TransitionKind};
+use esp32_led_matrix::{MATRIX_HEIGHT, MATRIX_WIDTH};
+
+/// ESP32-C3 `GPIO_OUT_W1TS_REG`: writing 1 bits drives those pins high.
+const GPIO_OUT_W1TS: *mut u32 = 0x6000_4008 as *mut u32;
+
+/// ESP32-C3 `GPIO_OUT_W1TC_REG`: writing 1 bits drives those pins low.
+const GPIO_OUT_W1TC: *mut u32 = 0x6000_400C as *mut u32;
+
+/// CPU cycles a pin gets to settle before [`self_test`] reads it back:
+/// the input path syncs over a couple of APB clocks.
+const SELF_TEST_SETTLE_CYCLES: u32 = 160;
+
+/// The panel's 13 pins. They are only held so nothing else can claim
+/// them: every write goes straight to the output registers, by the GPIO
+/// numbers in [`panel`](esp32_led_matrix::panel).
+struct GpioPins {
+    _pins: [Flex<'static>; 13],
+}
+
+impl Pins for GpioPins {
+    #[inline(always)]
+    fn write(&mut self, high: u32, low: u32) {
+        write_gpio(high, low);
+    }
+
+    #[inline(always)]
+    fn wait_cycles(&mut self, cycles: u32) {
+        wait_cycles(cycles);
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        Delay::new().delay_micros(us);
+    }
+}
+
+/// The same pins for the crash screen: the [`LedMatrix`] still holds
+/// them, but every write goes to the output registers anyway.
+struct CrashPins;
+
+impl Pins for CrashPins {
+    #[inline(always)]
+    fn write(&mut self, high: u32, low: u32) {
+        write_gpio(high, low);
+    }
+
+    #[inline(always)]
+    fn wait_cycles(&mut self, cycles: u32) {
+        wait_cycles(cycles);
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        Delay::new().delay_micros(us);
+    }
 }
 
 /// LED Matrix Driver
 pub struct LedMatrix {
-    // GPIO pins — named individually because `Output<'static>` is not
-    // trivially array-able. Helpers below hide the repetition.
-    gclk: Output<'static>,
-    dclk: Output<'static>,
-    le: Output<'static>,
-    a0: Output<'static>,
-    a1: Output<'static>,
-    a2: Output<'static>,
-    a3: Output<'static>,
-    dr1: Output<'static>,
-    dg1: Output<'static>,
-    db1: Output<'static>,
-    dr2: Output<'static>,
-    dg2: Output<'static>,
-    db2: Output<'static>,
-
-    /// Pixel data + text rendering. Pure logic, no GPIO.
+    /// The panel protocol, on the GPIO pins.
+    panel: Panel<GpioPins>,
+
+    /// Back buffer: pixel data + text rendering. Pure logic, no GPIO.
+    /// All drawing goes here.
     buffer: FrameBuffer,
 
-    /// Initialized flag — refresh() is a no-op until init() has run.
-    initialized: bool,
+    /// Front buffer: the last committed frame, read by `refresh`.
+    front: Frame,
+
+    /// Set between `begin_frame` and `commit_frame`; while set, drawing
+    /// calls don't promote the back buffer on their own.
+    frame_open: bool,
+
+    /// Back buffer changed since the last `swap_buffers`.
+    dirty: bool,
+
+    /// The panel shows something other than the front buffer at the
+    /// current brightness and gamma, so the next `refresh` sends it.
+    stale: bool,
+
+    /// Transition from the front buffer to the back buffer, and when it
+    /// started. The start is `None` until the next frame is shown; see
+    /// [`LedMatrix::start_transition`].
+    transition: Option<(Transition, Option<Instant>)>,
+
+    /// Rolling average of the time between `refresh` completions.
+    frame_timer: FrameTimer,
+
+    /// When the last `refresh` finished, `None` before the first.
+    last_refresh: Option<Instant>,
 }
 
 impl LedMatrix {
@@ -119,7 +151,8 @@ impl LedMatrix {
     ///
     /// # Pin Order (ESP32-C3 SuperMini)
     /// The constructor takes 13 [`Output`] pins in this fixed order, which
-    /// matches the wiring diagram in `README.md`:
+    /// matches the wiring diagram in `README.md` and the GPIO numbers in
+    /// [`panel`](esp32_led_matrix::panel):
     ///
     /// | # | Argument | LED-matrix signal | ESP32-C3 GPIO | Notes              |
     /// |---|----------|-------------------|---------------|--------------------|
@@ -136,6 +169,9 @@ impl LedMatrix {
     /// |11 | `dr2`    | DR2               | GPIO10        | red   data chain 2 |
     /// |12 | `dg2`    | DG2               | GPIO20        | green data chain 2 (UART RXD) |
     /// |13 | `db2`    | DB2               | GPIO21        | blue  data chain 2 (UART TXD) |
+    ///
+    /// A self-test reads each line back to check it really is on that GPIO;
+    /// the results are in the log.
     pub fn new(
         gclk: Output<'static>,
         dclk: Output<'static>,
@@ -151,222 +187,585 @@ impl LedMatrix {
         dg2: Output<'static>,
         db2: Output<'static>,
     ) -> Self {
-        let mut matrix = Self {
-            gclk,
-            dclk,
-            le,
-            a0,
-            a1,
-            a2,
-            a3,
-            dr1,
-            dg1,
-            db1,
-            dr2,
-            dg2,
-            db2,
+        enable_cycle_counter();
+        let mut pins =
+            [gclk, dclk, le, a0, a1, a2, a3, dr1, dg1, db1, dr2, dg2, db2].map(Output::into_flex);
+        self_test(&mut pins);
+        let mut panel = Panel::new(GpioPins { _pins: pins });
+        panel.init();
+        Self {
+            panel,
             buffer: FrameBuffer::new(),
-            initialized: false,
-        };
+            front: [[[0; 3]; MATRIX_WIDTH]; MATRIX_HEIGHT],
+            frame_open: false,
+            dirty: false,
+            stale: true,
+            transition: None,
+            frame_timer: FrameTimer::new(),
+            last_refresh: None,
+        }
+    }
 
-        matrix.init();
-        matrix
+    /// Start a batch of drawing calls. Nothing drawn until
+    /// [`Self::commit_frame`] reaches the panel.
+    pub fn begin_frame(&mut self) {
+        self.frame_open = true;
+    }
+
+    /// Finish the batch started by [`Self::begin_frame`] and show it.
+    /// Skips the copy when nothing was drawn.
+    pub fn commit_frame(&mut self) {
+        self.frame_open = false;
+        if self.dirty {
+            self.present();
+        }
+    }
+
+    /// Promote the back buffer: copy it to the front buffer that
+    /// [`Self::refresh`] reads. The back buffer keeps its contents, so
+    /// later drawing builds on the frame just shown. Skips the copy when
+    /// the back buffer wasn't drawn into since.
+    pub fn swap_buffers(&mut self) {
+        if self.buffer.take_dirty().is_some() {
+            self.front = *self.buffer.as_pixels();
+            self.stale = true;
+        }
+        self.dirty = false;
+    }
+
+    /// Called after every drawing call. Outside a
+    /// [`Self::begin_frame`] batch each call is shown on its own.
+    fn drawn(&mut self) {
+        self.dirty = true;
+        if !self.frame_open {
+            self.present();
+        }
+    }
+
+    /// Show the finished back buffer. During a transition the back buffer
+    /// is what [`Self::refresh`] mixes towards, so there is nothing to
+    /// copy: the first frame shown starts the transition's clock, and
+    /// later ones (e.g. a scroll step) simply change its target.
+    fn present(&mut self) {
+        match &mut self.transition {
+            Some((_, start @ None)) => *start = Some(Instant::now()),
+            Some(_) => {}
+            None => self.swap_buffers(),
+        }
+    }
+
+    /// Animate the next frame in with `transition` instead of swapping
+    /// it in at once. Call it before drawing the frame; the transition
+    /// starts when the frame is shown and [`Self::refresh`] steps it.
+    ///
+    /// A transition still running is frozen where it is, and that
+    /// in-between picture is what the new one starts from, so a new
+    /// message mid-transition takes over without a jump.
+    pub fn start_transition(&mut self, transition: Transition) {
+        if let Some((current, progress)) = self.transition_progress() {
+            let target = self.buffer.as_pixels();
+            for (row, new_row) in self.front.iter_mut().zip(target) {
+                for (x, (px, &to)) in row.iter_mut().zip(new_row).enumerate() {
+                    let mixed = current.mix(load_pixel(*px), load_pixel(to), x, progress);
+                    *px = store_pixel(mixed);
+                }
+            }
+        }
+        self.transition = Some((transition, None));
+    }
+
+    /// Cross-fade from the current picture to `target` over
+    /// `duration_ms`.
+    pub fn fade_to(&mut self, target: &FrameBuffer, duration_ms: u32) {
+        self.transition_to(target, Transition::new(TransitionKind::Fade, duration_ms));
+    }
+
+    /// Reveal `target` left to right over `duration_ms`.
+    pub fn wipe_to(&mut self, target: &FrameBuffer, duration_ms: u32) {
+        self.transition_to(target, Transition::new(TransitionKind::Wipe, duration_ms));
+    }
+
+    fn transition_to(&mut self, target: &FrameBuffer, transition: Transition) {
+        self.start_transition(transition);
+        self.buffer.copy_from(target);
+        self.drawn();
+    }
+
+    /// The running transition and its progress, or `None` when there is
+    /// none or it hasn't started.
+    fn transition_progress(&self) -> Option<(Transition, u8)> {
+        let (transition, start) = self.transition?;
+        let elapsed_ms = start?.elapsed().as_millis();
+        Some((transition, transition.progress(elapsed_ms)))
     }
 
     /// Render `text` to the back buffer (cleared first).
     pub fn display_text(&mut self, text: &str) {
         self.buffer.display_text(text);
+        self.drawn();
     }
 
-    /// Initialize the display with configuration.
-    fn init(&mut self) {
-        self.set_all_pins_low();
+    /// Render several lines stacked and evenly spaced. See
+    /// [`FrameBuffer::display_text_lines`].
+    pub fn display_text_lines(&mut self, lines: &[&str]) {
+        self.buffer.display_text_lines(lines);
+        self.drawn();
+    }
 
-        // Wait for power stabilization.
-        Delay::new().delay_millis(100);
+    /// Render `text` word-wrapped over as many lines as fit. See
+    /// [`FrameBuffer::display_text_wrapped`].
+    pub fn display_text_wrapped(&mut self, text: &str) {
+        self.buffer.display_text_wrapped(text);
+        self.drawn();
+    }
+
+    /// Render `segments` on one line, each in its own color. See
+    /// [`FrameBuffer::display_colored_segments`].
+    pub fn display_colored_segments(&mut self, segments: &[(&str, Pixel)]) {
+        self.buffer.display_colored_segments(segments);
+        self.drawn();
+    }
 
-        self.send_config(CONFIG_REGISTER_1);
+    /// True when [`Self::display_text`] can show all of `text` without
+    /// truncating it.
+    pub fn text_fits(&self, text: &str) -> bool {
+        self.buffer.text_fits(text)
+    }
 
-        // Reset after config so the new register values take effect.
-        // (Some reference designs do it the other way around — the Hacklab
-        // panel reportedly tolerates both.)
-        self.send_command(Command::Reset);
-        Delay::new().delay_millis(10);
+    /// Scroll `text` across the panel, `speed_px` pixels per
+    /// [`Self::refresh`]. Replaced by the next [`Self::display_text`].
+    pub fn set_scroll_text(&mut self, text: &str, speed_px: usize) {
+        self.buffer.set_scroll_text(text, speed_px);
+        self.drawn();
+    }
 
-        self.initialized = true;
+    /// Scroll `lines` up the panel, `speed_px` pixels per
+    /// [`Self::refresh`]. See [`FrameBuffer::set_vscroll_lines`].
+    pub fn set_vscroll_lines(&mut self, lines: &[&str], speed_px: usize) {
+        self.buffer.set_vscroll_lines(lines, speed_px);
+        self.drawn();
     }
 
-    /// Send a command to the display via LE + DCLK.
-    fn send_command(&mut self, cmd: Command) {
-        self.le.set_high();
-        for _ in 0..cmd as u8 {
-            self.pulse_dclk();
+    /// Move the scrolling message `px` pixels, for a scroll started with
+    /// a speed of 0 and timed by the caller.
+    pub fn scroll_by(&mut self, px: usize) {
+        if self.buffer.scroll_by(px) {
+            self.drawn();
         }
-        self.le.set_low();
     }
 
-    /// Send the Configuration1 register to all driver ICs.
-    ///
-    /// Per the wiki (`Ledimatriisin ohjaaminen > Ohjainpiirin konfigurointi`):
-    /// 1. Send the Pre-Active command (N=14, LE high for 14 DCLKs)
-    /// 2. Send the 16-bit Configuration1 value
-    /// 3. Send the WriteConfig command (N=4)
-    ///
-    /// Steps 2 and 3 are combined by holding LE high during the last 4
-    /// DCLKs of the 16-bit shift. The 16-bit value is broadcast 22 times
-    /// (352 DCLKs total) so it lands in the shift register of every IC.
-    fn send_config(&mut self, config: u16) {
-        self.send_command(Command::PreActive);
-        for bit in bit_stream::config_bits(config) {
-            self.shift_one_bit(bit);
+    /// Set every pixel of the back buffer to one color. `fill(0, 0, 0)`
+    /// is the same as clearing it.
+    pub fn fill(&mut self, r: u16, g: u16, b: u16) {
+        self.buffer.fill(r, g, b);
+        self.drawn();
+    }
+
+    /// Blend a color over a rectangle of the back buffer with opacity
+    /// `alpha` (0 = unchanged, 255 = solid). See
+    /// [`FrameBuffer::blend_rect`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn blend_rect(
+        &mut self,
+        x: i32,
+        y: i32,
+        width: usize,
+        height: usize,
+        r: u16,
+        g: u16,
+        b: u16,
+        alpha: u8,
+    ) {
+        self.buffer.blend_rect(x, y, width, height, r, g, b, alpha);
+        self.drawn();
+    }
+
+    /// Set one pixel of the back buffer from an HSV color. See
+    /// [`hsv_to_pixel`](esp32_led_matrix::frame_buffer::hsv_to_pixel).
+    pub fn set_pixel_hsv(&mut self, x: i32, y: i32, h: f32, s: f32, v: f32) {
+        self.buffer.set_pixel_hsv(x, y, h, s, v);
+        self.drawn();
+    }
+
+    /// Paint a diagonal rainbow shifted by `phase` degrees. See
+    /// [`FrameBuffer::rainbow_fill`].
+    pub fn rainbow_fill(&mut self, phase: f32) {
+        self.buffer.rainbow_fill(phase);
+        self.drawn();
+    }
+
+    /// Paint the frame of `animation` at `t_ms` milliseconds in. See
+    /// [`Animation::step`].
+    pub fn animate(&mut self, animation: &mut impl Animation, t_ms: u64) {
+        animation.step(&mut self.buffer, t_ms);
+        self.drawn();
+    }
+
+    /// Paint `graph` as a line chart in the text color. See
+    /// [`Graph::render`].
+    pub fn draw_graph(&mut self, graph: &Graph) {
+        graph.render(&mut self.buffer);
+        self.drawn();
+    }
+
+    /// Paint the header and body regions. See [`Regions::render`].
+    pub fn draw_regions(&mut self, regions: &Regions) {
+        regions.render(&mut self.buffer);
+        self.drawn();
+    }
+
+    /// Paint a bring-up pattern, with the sweep pixel `step` pixels
+    /// along. See [`FrameBuffer::test_pattern`].
+    pub fn test_pattern(&mut self, pattern: TestPattern, step: usize) {
+        self.buffer.test_pattern(pattern, step);
+        self.drawn();
+    }
+
+    /// Draw one character over the back buffer with its top-left corner
+    /// at `(x, y)`, without clearing. Returns the x-advance to the next
+    /// character. See [`FrameBuffer::draw_char_at`].
+    pub fn draw_char_at(&mut self, ch: char, x: i32, y: i32, r: u16, g: u16, b: u16) -> usize {
+        let advance = self.buffer.draw_char_at(ch, x, y, r, g, b);
+        self.drawn();
+        advance
+    }
+
+    /// Set a batch of `(x, y, r, g, b)` pixels in the back buffer. See
+    /// [`FrameBuffer::set_pixels`].
+    pub fn set_pixels(&mut self, pixels: &[(i32, i32, u16, u16, u16)]) {
+        self.buffer.set_pixels(pixels);
+        self.drawn();
+    }
+
+    /// Replace the whole back buffer with `frame`, e.g. once per frame of
+    /// a host-driven animation. See [`FrameBuffer::set_frame`].
+    pub fn set_frame(&mut self, frame: &[[Pixel; MATRIX_WIDTH]; MATRIX_HEIGHT]) {
+        self.buffer.set_frame(frame);
+        self.drawn();
+    }
+
+    /// Copy a block of pixels into the back buffer, clipped to the panel.
+    /// See [`FrameBuffer::draw_bitmap`].
+    pub fn draw_bitmap(&mut self, x: i32, y: i32, width: usize, height: usize, pixels: &[Pixel]) {
+        self.buffer.draw_bitmap(x, y, width, height, pixels);
+        self.drawn();
+    }
+
+    /// Stamp a sprite into the back buffer, skipping its transparent
+    /// pixels. See [`FrameBuffer::draw_sprite`].
+    pub fn draw_sprite(&mut self, sprite: &Sprite, x: i32, y: i32) {
+        self.buffer.draw_sprite(sprite, x, y);
+        self.drawn();
+    }
+
+    /// Apply a queued drawing command to the frame buffer, once: the
+    /// next text redraw wipes it.
+    pub fn apply(&mut self, cmd: &DrawCommand) {
+        cmd.apply(&mut self.buffer);
+        self.drawn();
+    }
+
+    /// Draw `cmd` and keep it under the text. See
+    /// [`FrameBuffer::push_command`].
+    pub fn push_command(&mut self, cmd: DrawCommand) -> bool {
+        let kept = self.buffer.push_command(cmd);
+        self.drawn();
+        kept
+    }
+
+    /// Forget the retained drawing. See [`FrameBuffer::clear_scene`].
+    pub fn clear_scene(&mut self) {
+        self.buffer.clear_scene();
+    }
+
+    /// Set the color used by [`Self::display_text`]. Defaults to white.
+    pub fn set_text_color(&mut self, r: u16, g: u16, b: u16) {
+        self.buffer.set_text_color(r, g, b);
+    }
+
+    /// Color scrolling text along its length with `gradient` instead of
+    /// the text color, or go back to the text color with `None`. Text
+    /// that fits and stands still keeps the text color.
+    pub fn set_scroll_gradient(&mut self, gradient: Option<GradientMode>) {
+        self.buffer.set_scroll_gradient(gradient);
+    }
+
+    /// Draw text at `scale` times the font size (each glyph pixel becomes
+    /// a `scale` x `scale` block). Applies to text drawn afterwards.
+    pub fn set_text_scale(&mut self, scale: usize) {
+        self.buffer.set_text_scale(scale);
+    }
+
+    /// Leave `px` font pixels between characters of later text (0:
+    /// touching). See [`FrameBuffer::set_char_spacing`].
+    pub fn set_char_spacing(&mut self, px: usize) {
+        self.buffer.set_char_spacing(px);
+    }
+
+    /// Leave `px` font pixels between lines of later text, or `None` for
+    /// the default. See [`FrameBuffer::set_line_spacing`].
+    pub fn set_line_spacing(&mut self, px: Option<usize>) {
+        self.buffer.set_line_spacing(px);
+    }
+
+    /// Draw later text with `font`, e.g. one uploaded via `POST /font`.
+    pub fn set_font(&mut self, font: Font) {
+        self.buffer.set_font(font);
+    }
+
+    /// Place text at the left margin, centred, or at the right margin.
+    pub fn set_text_align(&mut self, align: Align) {
+        self.buffer.set_text_align(align);
+    }
+
+    /// Set how the panel is mounted, so drawing comes out upright. Applies
+    /// to everything drawn afterwards; see [`Orientation`].
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.buffer.set_orientation(orientation);
+    }
+
+    /// Dither later drawing into the `pixel8` buffer instead of rounding
+    /// it. See [`FrameBuffer::set_dithering`].
+    pub fn set_dithering(&mut self, on: bool) {
+        self.buffer.set_dithering(on);
+    }
+
+    /// Rebuild the output gamma curve. `1.0` disables correction. The
+    /// frame buffer is not modified. See [`Panel::set_gamma`].
+    pub fn set_gamma(&mut self, gamma: f32) {
+        self.panel.set_gamma(gamma);
+        self.stale = true;
+    }
+
+    /// Set the global brightness: 255 = full, 0 = blank. Scales every
+    /// channel on output; the frame buffer is untouched.
+    pub fn set_brightness(&mut self, level: u8) {
+        if level != self.panel.brightness() {
+            self.panel.set_brightness(level);
+            self.stale = true;
         }
-        self.le.set_low();
+    }
+
+    /// Hold DCLK high, then low, for at least `ns` nanoseconds each
+    /// phase. Defaults to the driver ICs' 10 ns minimum; raise it if long
+    /// or noisy wiring garbles the picture. The GPIO writes themselves
+    /// take a few cycles, so small values mostly don't slow the clock.
+    pub fn set_dclk_delay_ns(&mut self, ns: u32) {
+        self.panel.set_dclk_delay_ns(ns);
+    }
+
+    /// Reconfigure the driver ICs with `config`; the frame is sent again
+    /// on the next refresh. See [`Panel::write_config`].
+    pub fn write_config(&mut self, config: ConfigRegister) {
+        self.panel.write_config(config);
+        self.stale = true;
+    }
+
+    /// Set the LED drivers' output current. `false`, and nothing
+    /// changed, if `gain` is out of range. See
+    /// [`ConfigRegister::current_gain`].
+    pub fn set_current_gain(&mut self, gain: u8) -> bool {
+        let changed = self.panel.set_current_gain(gain);
+        self.stale |= changed;
+        changed
     }
 
     /// Refresh the display — must be called continuously.
     ///
-    /// Two phases, per the wiki:
-    /// 1. Shift one full frame of image data into the display's back buffer
-    ///    (the display keeps showing the previous frame while we do this).
-    /// 2. Run a complete multiplex cycle: 256 GCLK pulses per scanline,
-    ///    advancing the address every scanline. At the scanline-10 -> 0
-    ///    wrap-around, issue VSYNC so the display swaps to the back buffer
-    ///    we just filled.
+    /// An active scrolling message moves one step per call (unless it
+    /// was started with a speed of 0), a running transition steps, and
+    /// the resulting frame goes out via [`Panel::send_frame`]. If nothing
+    /// changed since the last one, [`Panel::hold_frame`] keeps it on
+    /// screen instead, which takes a fraction of the time; the frame time
+    /// counts those refreshes too.
     pub fn refresh(&mut self) {
-        if !self.initialized {
-            return;
+        // Step the scrolling message (if any) before sending the frame.
+        if self.buffer.advance_scroll() {
+            self.drawn();
         }
 
-        // Phase 1: send image data for all scanlines. Scope the
-        // immutable borrow of `self.buffer` so it ends before we start
-        // toggling GPIO in `write_chain` (which needs `&mut self`).
-        let mut data = [[0u16; 3]; CHAIN_LEN];
-        for scanline in 0..SCANLINES {
-            for led in 0..PWM_BITS {
-                {
-                    let pixels = self.buffer.as_pixels();
-                    chain_mapper::compute_chain_data(scanline, led, pixels, &mut data);
-                }
-                self.write_chain(&data);
+        // Step the transition. Once it is done the back buffer simply
+        // becomes the front buffer.
+        let mix = match self.transition_progress() {
+            Some((_, u8::MAX)) => {
+                self.transition = None;
+                // The front buffer still holds where the transition
+                // started, whatever was drawn since.
+                self.buffer.mark_dirty(0, 0, MATRIX_WIDTH, MATRIX_HEIGHT);
+                self.swap_buffers();
+                None
             }
+            mix => mix,
+        };
+        if mix.is_none() && !self.stale {
+            self.panel.hold_frame();
+        } else {
+            let target = self.buffer.as_pixels();
+            let mix = mix.map(|(transition, progress)| (target, transition, progress));
+            self.panel.send_frame(&self.front, mix);
+            // Mid-transition the last frame sent was a mix: stale again.
+            self.stale = mix.is_some();
         }
 
-        // Phase 2: multiplex one frame.
-        self.multiplex_frame();
+        let now = Instant::now();
+        if let Some(last) = self.last_refresh {
+            let frame_us = (now - last).as_micros().min(u64::from(u32::MAX));
+            self.frame_timer.record(frame_us as u32);
+        }
+        self.last_refresh = Some(now);
     }
 
-    /// Run one full multiplex cycle — 11 scanlines, each with 256 GCLK
-    /// pulses plus a 257th dead-time pulse.
-    fn multiplex_frame(&mut self) {
-        let delay = Delay::new();
-        for scanline in 0..SCANLINES {
-            self.set_scanline(scanline);
-            self.pulse_gclk_n(GCLK_PULSES_PER_SCANLINE);
-
-            // VSYNC must be issued at the scanline-10 -> 0 wrap so the
-            // display swaps buffers exactly at the frame boundary.
-            if scanline == SCANLINES - 1 {
-                self.send_command(Command::Vsync);
-            }
+    /// The frame on screen: the front buffer, before gamma and
+    /// brightness. Mid-transition, the frame being left.
+    pub fn front(&self) -> &Frame {
+        &self.front
+    }
 
-            // 257th GCLK pulse: longer high/low phase than the regular
-            // 256. MBI5252 datasheet's tdth/tdtl are microsecond minimums.
-            self.gclk.set_high();
-            delay.delay_micros(GCLK_DEAD_TIME_US);
-            self.gclk.set_low();
-            delay.delay_micros(GCLK_DEAD_TIME_US);
-        }
+    /// Average time between recent [`Self::refresh`] completions in
+    /// microseconds (the inverse of the frame rate), or `None` until two
+    /// refreshes have finished.
+    pub fn frame_time_us(&self) -> Option<u32> {
+        self.frame_timer.average_us()
     }
+}
 
-    /// Shift 22 × 16 = 352 DCLKs for one `(scanline, led)` cycle.
-    ///
-    /// Bits shift MSB-first. On the very last DCLK of the very last IC, LE
-    /// is raised to issue the Data Latch command (N=1). LE is dropped
-    /// immediately after the loops finish.
-    fn write_chain(&mut self, data: &[[u16; 3]; CHAIN_LEN]) {
-        // Drop LE before clocking — the latch at the end of the previous
-        // cycle, if any, would have left it high.
-        self.le.set_low();
-
-        for bit in bit_stream::chain_data_bits(data) {
-            self.shift_one_bit(bit);
-        }
+#[cfg(feature = "embedded-graphics")]
+impl embedded_graphics_core::geometry::OriginDimensions for LedMatrix {
+    fn size(&self) -> embedded_graphics_core::geometry::Size {
+        embedded_graphics_core::geometry::OriginDimensions::size(&self.buffer)
+    }
+}
 
-        self.le.set_low();
+/// `embedded-graphics` drawing goes into the back buffer like any other
+/// drawing call. See [`esp32_led_matrix::graphics`].
+#[cfg(feature = "embedded-graphics")]
+impl embedded_graphics_core::draw_target::DrawTarget for LedMatrix {
+    type Color = embedded_graphics_core::pixelcolor::Rgb565;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = embedded_graphics_core::Pixel<Self::Color>>,
+    {
+        embedded_graphics_core::draw_target::DrawTarget::draw_iter(&mut self.buffer, pixels)?;
+        self.drawn();
+        Ok(())
     }
+}
 
-    /// Apply one [`ChainBit`] (data lines + optional LE) and pulse DCLK.
-    #[inline]
-    fn shift_one_bit(&mut self, bit: ChainBit) {
-        Self::set_data_pin(&mut self.dr1, bit.r1);
-        Self::set_data_pin(&mut self.dg1, bit.g1);
-        Self::set_data_pin(&mut self.db1, bit.b1);
-        Self::set_data_pin(&mut self.dr2, bit.r2);
-        Self::set_data_pin(&mut self.dg2, bit.g2);
-        Self::set_data_pin(&mut self.db2, bit.b2);
-        if bit.le_high {
-            self.le.set_high();
+/// Blank the panel and show the
+/// [`crash_screen`](esp32_led_matrix::crash_screen) for `duration_ms`,
+/// for the panic handler.
+///
+/// The [`LedMatrix`] and its frame buffers may be mid-update, so this
+/// leaves them alone: a fresh [`Panel`] on the same pins resets the
+/// driver ICs and draws the pattern straight from
+/// [`crash_pixel`], with nothing allocated and no frame buffer. Before
+/// [`LedMatrix::new`] has made the pins outputs, nothing shows.
+pub fn show_crash_screen(dclk_delay_ns: u32, duration_ms: u64) {
+    enable_cycle_counter();
+    let mut panel = Panel::new(CrashPins);
+    panel.set_dclk_delay_ns(dclk_delay_ns);
+    panel.init();
+    let start = Instant::now();
+    loop {
+        let elapsed_ms = start.elapsed().as_millis();
+        if elapsed_ms >= duration_ms {
+            break;
         }
-        self.pulse_dclk();
+        panel.send_pattern(|x, y| crash_pixel(x, y, elapsed_ms));
     }
+}
 
-    /// Set a single data pin high or low.
-    #[inline]
-    fn set_data_pin(pin: &mut Output<'static>, high: bool) {
-        if high {
-            pin.set_high();
-        } else {
-            pin.set_low();
+/// Check the wiring before the panel is set up, logging what it finds.
+///
+/// Lists which GPIO carries which signal and warns about pins that are
+/// a bad place for one ([`PinCaveat`]). Then it drives each line high
+/// and low through the output registers, by the GPIO numbers in
+/// [`PIN_MAP`], and reads it back through the pin passed in its place:
+/// a line that doesn't follow is shorted, or `main` passed its pin in
+/// the wrong place. DCLK and LE are left alone, as pulsing them would
+/// shift or latch into the panel.
+///
+/// Faults are logged rather than fatal, so a board with a dead color
+/// line still comes up and can be reached over the network.
+fn self_test(pins: &mut [Flex<'static>; 13]) {
+    for (&(signal, gpio), pin) in PIN_MAP.iter().zip(pins.iter_mut()) {
+        match PinCaveat::of(gpio) {
+            Some(caveat) if !caveat.is_benign() => {
+                warn!("{} on GPIO{}: {:?} pin", signal, gpio, caveat);
+            }
+            Some(caveat) => info!("{} on GPIO{} ({:?} pin)", signal, gpio, caveat),
+            None => info!("{} on GPIO{}", signal, gpio),
+        }
+        if gpio == DCLK_PIN || gpio == LE_PIN {
+            continue;
+        }
+        pin.set_input_enable(true);
+        let mask = 1 << gpio;
+        let mut follows = true;
+        for high in [true, false] {
+            if high {
+                write_gpio(mask, 0);
+            } else {
+                write_gpio(0, mask);
+            }
+            wait_cycles(SELF_TEST_SETTLE_CYCLES);
+            follows &= pin.is_high() == high;
+        }
+        pin.set_input_enable(false);
+        if !follows {
+            error!(
+                "{} (GPIO{}) doesn't follow its output: shorted, or wired to another pin",
+                signal, gpio
+            );
         }
     }
+}
 
-    /// Generate a single DCLK pulse.
-    #[inline(always)]
-    fn pulse_dclk(&mut self) {
-        self.dclk.set_high();
-        // Minimal delay — ESP32 is fast enough.
-        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
-        self.dclk.set_low();
-        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
-    }
-
-    /// Generate GCLK pulses.
-    #[inline]
-    fn pulse_gclk_n(&mut self, count: u32) {
-        for _ in 0..count {
-            self.gclk.set_high();
-            core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
-            self.gclk.set_low();
-            core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
-        }
+/// Drive the `high` pins high and the `low` pins low (GPIO bit masks)
+/// with one write to each output register.
+#[inline(always)]
+fn write_gpio(high: u32, low: u32) {
+    // SAFETY: W1TS/W1TC only change the pins whose bits are set, and the
+    // masks only ever name pins the driver owns.
+    unsafe {
+        core::ptr::write_volatile(GPIO_OUT_W1TC, low);
+        core::ptr::write_volatile(GPIO_OUT_W1TS, high);
     }
+}
 
-    /// Set scanline address (0-10) on the four A pins.
-    fn set_scanline(&mut self, scanline: usize) {
-        let addr = scanline as u8;
-        // Iterate over the 4-bit mask. Each iteration handles one pin.
-        let mut remaining = addr;
-        for pin in [&mut self.a0, &mut self.a1, &mut self.a2, &mut self.a3] {
-            let high = remaining & 1 != 0;
-            Self::set_data_pin(pin, high);
-            remaining >>= 1;
-        }
+/// Start the ESP32-C3 performance counter counting CPU cycles
+/// (`mpcer` = cycles, `mpcmr` = enabled). [`cycle_count`] reads it.
+fn enable_cycle_counter() {
+    // SAFETY: only selects and starts the performance counter; nothing
+    // else reads or configures it.
+    unsafe {
+        core::arch::asm!("csrw 0x7e0, {0}", "csrw 0x7e1, {0}", in(reg) 1u32);
     }
+}
 
-    /// Drive all output pins low.
-    fn set_all_pins_low(&mut self) {
-        self.gclk.set_low();
-        self.dclk.set_low();
-        self.le.set_low();
-        self.a0.set_low();
-        self.a1.set_low();
-        self.a2.set_low();
-        self.a3.set_low();
-        self.dr1.set_low();
-        self.dg1.set_low();
-        self.db1.set_low();
-        self.dr2.set_low();
-        self.dg2.set_low();
-        self.db2.set_low();
+/// CPU cycles counted since [`enable_cycle_counter`], wrapping.
+#[inline(always)]
+fn cycle_count() -> u32 {
+    let cycles: u32;
+    // SAFETY: reading `mpccr` has no side effects.
+    unsafe {
+        core::arch::asm!("csrr {0}, 0x7e2", out(reg) cycles);
+    }
+    cycles
+}
+
+/// Busy-wait for at least `cycles` CPU cycles. Unlike `Delay`, which
+/// counts whole microseconds, this resolves single cycles (6.25 ns at
+/// 160 MHz).
+#[inline(always)]
+fn wait_cycles(cycles: u32) {
+    if cycles == 0 {
+        return;
+    }
+    let start = cycle_count();
+    while cycle_count().wrapping_sub(start) < cycles {
+        core::hint::spin_loop();
     }
 }
@@ -1,18 +1,88 @@
 //! LED Matrix Controller Library
 //!
 //! This library exports testable components of the LED matrix controller.
-//! `font`, `frame_buffer`, `chain_mapper`, `bit_stream`, and `http_request`
-//! are pure Rust and can be tested on any platform. `led_matrix` and
-//! `http_server` tie the pure logic to GPIO / network and are only compiled
-//! when the `esp32` feature is enabled.
+//! `auth`, `backoff`, `bmp`, `font`, `frame_buffer`, `frame_pack`,
+//! `countdown`, `scroll`, `command`, `credentials`, `dhcp`, `dirty`,
+//! `display_state`, `dns`, `mdns_packet`, `sntp`, `timezone`, `mode`,
+//! `effects`, `events`, `games`, `graph`, `gradient`, `chain_mapper`,
+//! `gamma`, `bit_stream`, `multiplex`, `transition`, `http_request`,
+//! `rate_limit`, `notice`, `regions`, `rich_text`, `scene`,
+//! `screensaver`, `sprite`, `websocket`, `json`, `draw_api`, `stats`,
+//! `ota_image`, `crc32`, `panel`, `crash_screen`, `screenshot`,
+//! `serial_command` and `nvs_record` are pure Rust and can be tested on
+//! any platform. `graphics` implements `embedded-graphics` for the frame
+//! buffer, with the `embedded-graphics` feature. `simulator` drives a
+//! `panel` without hardware; it is built for tests and with the
+//! `simulator` feature. `led_matrix`, `http_server`, `mdns`, `clock`,
+//! `nvs` and `ota` tie the pure logic to GPIO / network / flash and are
+//! only compiled when the `esp32` feature is enabled.
 
 #![no_std]
 
+pub mod auth;
+pub mod backoff;
 pub mod bit_stream;
+pub mod bmp;
 pub mod chain_mapper;
+pub mod command;
+pub mod countdown;
+pub mod crash_screen;
+pub mod crc32;
+pub mod credentials;
+pub mod dhcp;
+pub mod dirty;
+pub mod display_state;
+pub mod dns;
+pub mod draw_api;
+pub mod effects;
+pub mod events;
 pub mod font;
 pub mod frame_buffer;
+pub mod frame_pack;
+pub mod games;
+pub mod gamma;
+pub mod gradient;
+pub mod graph;
+#[cfg(feature = "embedded-graphics")]
+pub mod graphics;
 pub mod http_request;
+pub mod json;
+pub mod mdns_packet;
+pub mod mode;
+pub mod multiplex;
+pub mod notice;
+pub mod nvs_record;
+pub mod ota_image;
+pub mod panel;
+pub mod rate_limit;
+pub mod regions;
+pub mod rich_text;
+pub mod scene;
+pub mod screensaver;
+pub mod screenshot;
+pub mod scroll;
+pub mod serial_command;
+#[cfg(any(test, feature = "simulator"))]
+pub mod simulator;
+pub mod sntp;
+pub mod sprite;
+pub mod stats;
+pub mod timezone;
+pub mod transition;
+pub mod websocket;
 
-pub const MATRIX_WIDTH: usize = 88;
-pub const MATRIX_HEIGHT: usize = 88;
+/// Width of one panel, in pixels.
+pub const PANEL_WIDTH: usize = 88;
+/// Height of one panel, in pixels.
+pub const PANEL_HEIGHT: usize = 88;
+
+/// Panels chained side by side, each one's data outputs wired to the
+/// next one's inputs. Two with the `two-panels` feature.
+#[cfg(not(feature = "two-panels"))]
+pub const PANELS: usize = 1;
+#[cfg(feature = "two-panels")]
+pub const PANELS: usize = 2;
+
+/// Width of the whole display: every panel, left to right.
+pub const MATRIX_WIDTH: usize = PANEL_WIDTH * PANELS;
+pub const MATRIX_HEIGHT: usize = PANEL_HEIGHT;
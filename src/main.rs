@@ -21,25 +21,81 @@
 #![no_main]
 
 use embassy_executor::Spawner;
-use embassy_time::{Duration, Timer};
+use embassy_time::{Duration, Instant, Timer};
 use esp_backtrace as _;
 use esp_hal::clock::CpuClock;
 use esp_hal::gpio::{Level, Output, OutputConfig};
-use log::info;
+use log::{info, warn};
 
+mod clock;
+mod health;
 mod http_server;
 mod led_matrix;
+mod mdns;
+mod nvs;
+mod ota;
+mod serial;
 mod wifi;
 
 use crate::led_matrix::LedMatrix;
+use esp32_led_matrix::bit_stream::DCLK_MIN_PULSE_NS;
+use esp32_led_matrix::bmp::Image;
+use esp32_led_matrix::command::DrawCommand;
+use esp32_led_matrix::countdown::Countdown;
+use esp32_led_matrix::draw_api::MAX_DRAW_OPS;
+use esp32_led_matrix::effects::ActiveEffect;
+use esp32_led_matrix::events::{DisplayEvent, EVENT_QUEUE_LEN, MAX_EVENT_CLIENTS};
+use esp32_led_matrix::font::Font;
+use esp32_led_matrix::frame_buffer::{
+    Align, DEFAULT_TEXT_COLOR, MAX_TEXT_LINES, Pixel, rgb565_to_pixel,
+};
+use esp32_led_matrix::frame_pack::FramePack;
+use esp32_led_matrix::games::{GameInput, Snake};
+use esp32_led_matrix::gamma::MAX_BRIGHTNESS;
+use esp32_led_matrix::gradient::GradientMode;
+use esp32_led_matrix::graph::Graph;
+use esp32_led_matrix::http_request::MAX_MESSAGE_LEN;
+use esp32_led_matrix::mode::{DisplayMode, Effect};
+use esp32_led_matrix::notice::{Notice, Notices};
+use esp32_led_matrix::panel::{ConfigRegister, MAX_CURRENT_GAIN};
+use esp32_led_matrix::regions::Regions;
+use esp32_led_matrix::rich_text::RichText;
+use esp32_led_matrix::screensaver::Screensaver;
+use esp32_led_matrix::screenshot;
+use esp32_led_matrix::scroll::ScrollTimer;
+use esp32_led_matrix::stats::{DEFAULT_TARGET_FPS, FramePacer};
+use esp32_led_matrix::transition::Transition;
 
 /// LED Matrix dimensions
-pub const MATRIX_WIDTH: usize = 88;
-pub const MATRIX_HEIGHT: usize = 88;
+pub use esp32_led_matrix::{MATRIX_HEIGHT, MATRIX_WIDTH};
 
-/// WiFi credentials - MODIFY THESE FOR YOUR NETWORK
-const WIFI_SSID: &str = "YOUR_WIFI_SSID";
-const WIFI_PASSWORD: &str = "YOUR_WIFI_PASSWORD";
+/// Pixels a too-long message moves per refresh.
+const SCROLL_SPEED_PX: usize = 1;
+
+/// DCLK high and low time in nanoseconds. The default is the driver
+/// ICs' minimum; raise it if long or noisy wiring garbles the picture.
+const DCLK_DELAY_NS: u32 = DCLK_MIN_PULSE_NS;
+
+/// Output current of the LED drivers, 0..=`MAX_CURRENT_GAIN`. Lower it
+/// if the power supply browns out on bright frames; see
+/// `ConfigRegister`.
+const CURRENT_GAIN: u8 = ConfigRegister::DEFAULT.current_gain;
+const _: () = assert!(CURRENT_GAIN <= MAX_CURRENT_GAIN);
+
+/// How fast the `/test?pattern=sweep` pixel moves, in pixels per second:
+/// a row a second.
+const SWEEP_PIXELS_PER_SEC: u64 = MATRIX_WIDTH as u64;
+
+/// Text scale of the `/timer` countdown: the largest at which `MM:SS`
+/// fits the panel in the built-in font.
+const TIMER_TEXT_SCALE: usize = 2;
+
+/// Color of the countdown's flashing "DONE".
+const TIMER_DONE_COLOR: Pixel = [0xFFFF, 0, 0];
+
+/// Least time between two "can't keep up" warnings, so a loop that
+/// stays behind its target doesn't flood the log.
+const FRAME_LAG_WARNING_INTERVAL: Duration = Duration::from_secs(10);
 
 /// Global display text buffer
 static DISPLAY_TEXT: embassy_sync::mutex::Mutex<
@@ -47,6 +103,193 @@ static DISPLAY_TEXT: embassy_sync::mutex::Mutex<
     heapless::String<32>,
 > = embassy_sync::mutex::Mutex::new(heapless::String::new());
 
+/// How the next text change is animated in, set by
+/// `/text?...&transition=`. Each `/text` request replaces it.
+static TRANSITION: embassy_sync::mutex::Mutex<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    Option<Transition>,
+> = embassy_sync::mutex::Mutex::new(None);
+
+/// Colors run along scrolling text, set by `/text?...&gradient=` (and
+/// `/marquee`, `/vscroll`). Each new text replaces it.
+static SCROLL_GRADIENT: embassy_sync::mutex::Mutex<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    Option<GradientMode>,
+> = embassy_sync::mutex::Mutex::new(None);
+
+/// Segment colors for the text, set by `/rich`. Each new text replaces
+/// it.
+static RICH_TEXT: embassy_sync::mutex::Mutex<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    Option<RichText>,
+> = embassy_sync::mutex::Mutex::new(None);
+
+/// What the display shows, switched by `/mode?m=`.
+static DISPLAY_MODE: embassy_sync::mutex::Mutex<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    DisplayMode,
+> = embassy_sync::mutex::Mutex::new(DisplayMode::Text);
+
+/// Average refresh time in microseconds, copied from the driver after
+/// every refresh for `/stats`.
+static FRAME_TIME_US: embassy_sync::mutex::Mutex<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    Option<u32>,
+> = embassy_sync::mutex::Mutex::new(None);
+
+/// Global brightness (0..=255), updated by `/brightness?level=`.
+static BRIGHTNESS: embassy_sync::mutex::Mutex<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    u8,
+> = embassy_sync::mutex::Mutex::new(MAX_BRIGHTNESS);
+
+/// When the last command came in and the idle timeout, from `/idle`.
+/// The refresh loop fades [`BRIGHTNESS`] out through it.
+static SCREENSAVER: embassy_sync::mutex::Mutex<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    Screensaver,
+> = embassy_sync::mutex::Mutex::new(Screensaver::new());
+
+/// Notices from `/notify`, waiting their turn. The refresh loop shows the
+/// current one instead of the display mode.
+static NOTICES: embassy_sync::mutex::Mutex<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    Notices,
+> = embassy_sync::mutex::Mutex::new(Notices::new());
+
+/// Paces the refresh loop to its target frame rate, set by `/fps`.
+static FRAME_PACER: embassy_sync::mutex::Mutex<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    FramePacer,
+> = embassy_sync::mutex::Mutex::new(FramePacer::new(DEFAULT_TARGET_FPS));
+
+/// Snake input from `/snake`, `/input` and `/ws`, passed to the game by
+/// the refresh loop. Dropped when full: only a few moves fit between
+/// two frames anyway.
+static GAME_INPUT: embassy_sync::channel::Channel<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    GameInput,
+    GAME_INPUT_LEN,
+> = embassy_sync::channel::Channel::new();
+
+/// Drawing commands from the HTTP server, applied by the refresh loop
+/// between frames. Requests are dropped (with a log line) when full.
+static DRAW_QUEUE: embassy_sync::channel::Channel<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    DrawCommand,
+    DRAW_QUEUE_LEN,
+> = embassy_sync::channel::Channel::new();
+
+/// Capacity of [`DRAW_QUEUE`]: room for a full `/api/draw` batch and
+/// then some.
+const DRAW_QUEUE_LEN: usize = 2 * MAX_DRAW_OPS;
+
+/// Capacity of [`GAME_INPUT`].
+const GAME_INPUT_LEN: usize = 4;
+
+/// Last frame uploaded via `POST /image`, as RGB565. Filled by the HTTP
+/// task; drawn by the refresh loop once [`IMAGE_READY`] fires.
+static IMAGE: embassy_sync::mutex::Mutex<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    [u16; MATRIX_WIDTH * MATRIX_HEIGHT],
+> = embassy_sync::mutex::Mutex::new([0; MATRIX_WIDTH * MATRIX_HEIGHT]);
+
+/// Set when [`IMAGE`] holds a new frame to show.
+static IMAGE_READY: embassy_sync::signal::Signal<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    (),
+> = embassy_sync::signal::Signal::new();
+
+/// Animation uploaded via `POST /anim`. Filled by the HTTP task, which
+/// holds the lock for the whole upload; played by the refresh loop in
+/// [`DisplayMode::Animation`].
+static FRAME_PACK: embassy_sync::mutex::Mutex<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    FramePack,
+> = embassy_sync::mutex::Mutex::new(FramePack::new());
+
+/// Set when [`FRAME_PACK`] holds a new animation, to play from the start.
+static FRAME_PACK_READY: embassy_sync::signal::Signal<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    (),
+> = embassy_sync::signal::Signal::new();
+
+/// Format asked for by `GET /screenshot`. The refresh loop encodes the
+/// frame on screen into [`SCREENSHOT`] and answers via
+/// [`SCREENSHOT_READY`].
+static SCREENSHOT_REQUEST: embassy_sync::signal::Signal<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    screenshot::Format,
+> = embassy_sync::signal::Signal::new();
+
+/// The last screenshot, encoded by the refresh loop.
+static SCREENSHOT: embassy_sync::mutex::Mutex<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    [u8; screenshot::MAX_SCREENSHOT_LEN],
+> = embassy_sync::mutex::Mutex::new([0; screenshot::MAX_SCREENSHOT_LEN]);
+
+/// Set to the length of [`SCREENSHOT`] once it holds the requested image.
+static SCREENSHOT_READY: embassy_sync::signal::Signal<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    usize,
+> = embassy_sync::signal::Signal::new();
+
+/// Font uploaded via `POST /font`, picked up by the refresh loop, which
+/// then re-renders the text with it.
+static FONT: embassy_sync::signal::Signal<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    Font,
+> = embassy_sync::signal::Signal::new();
+
+/// Readings pushed with `/graph/push?v=`, drawn in
+/// [`DisplayMode::Graph`].
+static GRAPH: embassy_sync::mutex::Mutex<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    Graph,
+> = embassy_sync::mutex::Mutex::new(Graph::new());
+
+/// Set when [`GRAPH`] has a new sample to draw.
+static GRAPH_UPDATED: embassy_sync::signal::Signal<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    (),
+> = embassy_sync::signal::Signal::new();
+
+/// The header and body set by `/region`, drawn in
+/// [`DisplayMode::Regions`].
+static REGIONS: embassy_sync::mutex::Mutex<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    Regions,
+> = embassy_sync::mutex::Mutex::new(Regions::new());
+
+/// Set when [`REGIONS`] has new text to draw.
+static REGIONS_UPDATED: embassy_sync::signal::Signal<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    (),
+> = embassy_sync::signal::Signal::new();
+
+/// The `/timer` countdown, shown in [`DisplayMode::Timer`].
+static COUNTDOWN: embassy_sync::mutex::Mutex<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    Countdown,
+> = embassy_sync::mutex::Mutex::new(Countdown::new());
+
+/// Display changes for the `/events` clients, one subscriber each.
+/// Published by `http_server::apply_changes`; a client that falls
+/// [`EVENT_QUEUE_LEN`] behind skips the oldest.
+static EVENTS: embassy_sync::pubsub::PubSubChannel<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    DisplayEvent,
+    EVENT_QUEUE_LEN,
+    MAX_EVENT_CLIENTS,
+    0,
+> = embassy_sync::pubsub::PubSubChannel::new();
+
+/// Global text color, updated by `/text?...&r=&g=&b=`. Defaults to white.
+static TEXT_COLOR: embassy_sync::mutex::Mutex<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    Pixel,
+> = embassy_sync::mutex::Mutex::new(DEFAULT_TEXT_COLOR);
+
 #[esp_rtos::main]
 async fn main(spawner: Spawner) {
     // Initialize ESP32 with default clock configuration
@@ -62,6 +305,7 @@ async fn main(spawner: Spawner) {
     esp_println::logger::init_logger_from_env();
     info!("=== ESP32 LED Matrix Controller ===");
     info!("Pure Rust build with esp-hal");
+    health::log_reset_reason();
 
     // Initialize LED matrix GPIO pins.
     //
@@ -69,7 +313,11 @@ async fn main(spawner: Spawner) {
     // LED matrix signals to these specific ESP32-C3 GPIOs. GPIO8/GPIO9 are
     // boot-strapping pins (the matrix's pull-ups keep them HIGH at boot, so
     // normal boot mode is preserved) and GPIO20/GPIO21 are the UART pins
-    // (serial logging may be visible as faint noise on DG2/DB2).
+    // (serial logging may be visible as faint noise on DG2/DB2). The
+    // driver writes GCLK, DCLK, LE and the data lines through the GPIO
+    // registers, so changing one of those pins means updating the pin
+    // numbers in the library's `panel` module too, which refuses to build
+    // with a signal on a pin that can't drive it.
     let mut led_matrix = LedMatrix::new(
         Output::new(peripherals.GPIO0, Level::Low, OutputConfig::default()), // GCLK  — multiplex clock
         Output::new(peripherals.GPIO1, Level::Low, OutputConfig::default()), // DCLK  — data clock
@@ -85,41 +333,334 @@ async fn main(spawner: Spawner) {
         Output::new(peripherals.GPIO20, Level::Low, OutputConfig::default()), // DG2   — green data chain 2 (UART RXD)
         Output::new(peripherals.GPIO21, Level::Low, OutputConfig::default()), // DB2   — blue  data chain 2 (UART TXD)
     );
+    led_matrix.set_dclk_delay_ns(DCLK_DELAY_NS);
+    if CURRENT_GAIN != ConfigRegister::DEFAULT.current_gain {
+        led_matrix.set_current_gain(CURRENT_GAIN);
+    }
 
-    // Initialize WiFi and start network task
+    // Restore the last display text, mode and color before the refresh
+    // loop starts.
+    nvs::init(peripherals.FLASH).await;
+    if let Some(text) = nvs::load_text().await {
+        info!("Restored display text from flash");
+        *DISPLAY_TEXT.lock().await = text;
+    }
+    let state = nvs::load_display_state().await;
+    *DISPLAY_MODE.lock().await = state.mode;
+    *TEXT_COLOR.lock().await = state.color;
+
+    clock::init_timezone().await;
+    let idle_timeout = nvs::load_idle_timeout().await;
+    SCREENSAVER
+        .lock()
+        .await
+        .set_timeout(idle_timeout, Instant::now().as_millis());
+
+    // Line commands on the USB serial port work with or without WiFi.
+    serial::start_serial(spawner, peripherals.USB_DEVICE);
+
+    // Initialize WiFi and start network tasks. Without stored credentials
+    // (or if joining fails) the device opens its setup access point; the
+    // connection task logs the address to browse to either way.
     info!("Initializing WiFi...");
-    let wifi_stack = wifi::init_wifi_inline(spawner, peripherals.WIFI);
-
-    // Wait for WiFi connection (link up + DHCP lease)
-    info!("Waiting for WiFi connection...");
-    wifi::wait_for_connection(wifi_stack).await;
-    info!("WiFi connected!");
-
-    // Get and display IP address
-    if let Some(ip) = wifi::get_ip_address(wifi_stack) {
-        info!("IP Address: http://{}/", ip);
-    } else {
-        info!("WiFi ready, but no IP address yet");
+    let credentials = wifi::load_credentials().await;
+    if credentials.is_none() {
+        info!("No WiFi credentials stored");
+    }
+    let station = credentials.is_some();
+    let stacks = wifi::init_wifi_inline(spawner, peripherals.WIFI, credentials);
+
+    // Scroll the address across the matrix once DHCP first assigns one.
+    if station {
+        spawner.spawn(wifi::show_address_task(stacks.sta)).ok();
     }
 
-    // Spawn the HTTP server task, handing it a reference to the network stack
+    // Answer `<hostname>.local` on the station network once it's up.
+    let hostname = mdns::load_hostname().await;
+    mdns::start_mdns(spawner, stacks.sta, &hostname);
+    clock::start_clock(spawner, stacks.sta);
+
+    // Serve HTTP on both interfaces; only one has a link at a time. The
+    // station gets a server more per `/events` client, so open streams
+    // don't hold up other requests.
+    for _ in 0..=MAX_EVENT_CLIENTS {
+        spawner
+            .spawn(http_server::http_server_task(stacks.sta, false))
+            .ok();
+    }
     spawner
-        .spawn(http_server::http_server_task(wifi_stack))
+        .spawn(http_server::http_server_task(stacks.ap, true))
         .ok();
 
     info!("=== System Ready ===");
-    info!("Open http://<ESP32_IP>/ in your browser to control the display");
+    info!(
+        "Open http://{}.local/ (or http://<ESP32_IP>/) in your browser to control the display",
+        hostname
+    );
 
-    // Main display refresh loop
+    // Main display refresh loop. The frame is only re-rendered when the
+    // text, its color or the mode changes, so an active scroll keeps its
+    // position.
+    let mut shown_text: heapless::String<32> = heapless::String::new();
+    let mut shown_color = DEFAULT_TEXT_COLOR;
+    let mut shown_gradient = None;
+    let mut shown_rich = None;
+    let mut shown_mode = DisplayMode::Text;
+    let mut marquee = ScrollTimer::new(0);
+    let mut last_tick = Instant::now();
+    let mut effect_start = Instant::now();
+    let mut animation = ActiveEffect::new(Effect::Rainbow, 0);
+    let mut shown_frame = None;
+    let mut snake = Snake::new(0);
+    let mut last_lag_warning: Option<Instant> = None;
+    // From here on a hung loop reboots the device.
+    let mut health = health::HealthMonitor::start(peripherals.TIMG1);
     loop {
-        // Get current display text
-        let text = DISPLAY_TEXT.lock().await.clone();
+        // Get current display text and color. In clock and timer mode
+        // the text is the time, so it re-renders once a minute (or
+        // second). A notice stands in for the mode while it shows, and
+        // when it is dismissed the change brings the mode back.
+        let notice = NOTICES
+            .lock()
+            .await
+            .current(Instant::now().as_millis())
+            .cloned();
+        let mode = match notice {
+            Some(Notice::Text(_)) => DisplayMode::Text,
+            Some(Notice::Pattern(pattern)) => DisplayMode::Test(pattern),
+            None => *DISPLAY_MODE.lock().await,
+        };
+        let text = if let Some(Notice::Text(text)) = notice {
+            text
+        } else {
+            match mode {
+                DisplayMode::Text
+                | DisplayMode::Marquee { .. }
+                | DisplayMode::VScroll { .. }
+                | DisplayMode::Paragraph
+                | DisplayMode::Effect { .. }
+                | DisplayMode::Graph
+                | DisplayMode::Animation
+                | DisplayMode::Regions
+                | DisplayMode::Snake
+                | DisplayMode::Test(_) => DISPLAY_TEXT.lock().await.clone(),
+                DisplayMode::Clock { seconds } => {
+                    let mut time = heapless::String::new();
+                    let _ = time.push_str(&clock::clock_text(seconds).await);
+                    time
+                }
+                DisplayMode::Timer => {
+                    let mut face = heapless::String::new();
+                    let _ = face.push_str(&COUNTDOWN.lock().await.text(Instant::now().as_millis()));
+                    face
+                }
+            }
+        };
+        let mut color = *TEXT_COLOR.lock().await;
+        if mode == DisplayMode::Timer && COUNTDOWN.lock().await.is_done(Instant::now().as_millis())
+        {
+            color = TIMER_DONE_COLOR;
+        }
+        let gradient = *SCROLL_GRADIENT.lock().await;
+        // Only while the text they were sent with is still on show: a
+        // Wi-Fi notice or the clock never picks them up.
+        let rich = RICH_TEXT
+            .lock()
+            .await
+            .clone()
+            .filter(|rich| rich.text() == text.as_str());
 
-        // Update display
-        led_matrix.display_text(&text);
+        // Update display — multi-line text is stacked, and single lines
+        // too wide to fit statically scroll.
+        // Text and queued drawing land in one frame, so the cleared
+        // screen `display_text` starts from is never shown.
+        led_matrix.begin_frame();
+        let font_changed = FONT
+            .try_take()
+            .map(|font| led_matrix.set_font(font))
+            .is_some();
+        if font_changed
+            || text != shown_text
+            || color != shown_color
+            || gradient != shown_gradient
+            || rich != shown_rich
+            || mode != shown_mode
+        {
+            let [r, g, b] = color;
+            led_matrix.set_text_color(r, g, b);
+            led_matrix.set_scroll_gradient(gradient);
+            if let Some(transition) = TRANSITION.lock().await.take() {
+                led_matrix.start_transition(transition);
+            }
+            if let DisplayMode::Marquee { speed } = mode {
+                // A new strip starts off-screen, so a replaced message
+                // never lingers mid-scroll.
+                led_matrix.set_scroll_text(&text, 0);
+                marquee = ScrollTimer::new(speed.into());
+            } else if let DisplayMode::VScroll { speed } = mode {
+                // An empty message has no lines and blanks the display.
+                let lines: heapless::Vec<&str, MAX_MESSAGE_LEN> = if text.is_empty() {
+                    heapless::Vec::new()
+                } else {
+                    text.split('\n').take(MAX_MESSAGE_LEN).collect()
+                };
+                led_matrix.set_vscroll_lines(&lines, 0);
+                marquee = ScrollTimer::new(speed.into());
+            } else if mode == DisplayMode::Paragraph {
+                led_matrix.display_text_wrapped(&text);
+            } else if let DisplayMode::Effect { effect, .. } = mode {
+                // Painted every frame below, from a fresh start.
+                effect_start = Instant::now();
+                animation = ActiveEffect::new(effect, effect_start.as_ticks() as u32);
+            } else if let DisplayMode::Test(_) = mode {
+                // Painted every frame below.
+                effect_start = Instant::now();
+            } else if mode == DisplayMode::Graph {
+                // Painted below, in the new color.
+                GRAPH_UPDATED.signal(());
+            } else if mode == DisplayMode::Snake {
+                // Painted every frame below; `/snake` starts the game.
+            } else if mode == DisplayMode::Regions {
+                // Painted below, in the new font.
+                REGIONS_UPDATED.signal(());
+            } else if mode == DisplayMode::Timer {
+                // Big and centred; all other text keeps the default
+                // size and alignment.
+                led_matrix.set_text_scale(TIMER_TEXT_SCALE);
+                led_matrix.set_text_align(Align::Center);
+                led_matrix.display_text(&text);
+                led_matrix.set_text_scale(1);
+                led_matrix.set_text_align(Align::Left);
+            } else if mode == DisplayMode::Animation {
+                // Played below, from the first frame.
+                effect_start = Instant::now();
+                shown_frame = None;
+            } else if let Some(rich) = &rich {
+                // Truncated rather than scrolled when too wide.
+                led_matrix.display_colored_segments(&rich.segments());
+            } else if text.contains('\n') {
+                let lines: heapless::Vec<&str, MAX_TEXT_LINES> =
+                    text.split('\n').take(MAX_TEXT_LINES).collect();
+                led_matrix.display_text_lines(&lines);
+            } else if led_matrix.text_fits(&text) {
+                led_matrix.display_text(&text);
+            } else {
+                led_matrix.set_scroll_text(&text, SCROLL_SPEED_PX);
+            }
+            shown_text = text;
+            shown_color = color;
+            shown_gradient = gradient;
+            shown_rich = rich;
+            shown_mode = mode;
+        }
+        // The marquee and effects move by elapsed time, so their speed
+        // doesn't depend on how long a refresh takes.
+        let now = Instant::now();
+        while let Ok(input) = GAME_INPUT.try_receive() {
+            match input {
+                GameInput::NewGame => snake = Snake::new(now.as_ticks() as u32),
+                GameInput::Steer(direction) => snake.steer(direction),
+            }
+        }
+        match mode {
+            DisplayMode::Marquee { .. } | DisplayMode::VScroll { .. } => {
+                led_matrix.scroll_by(marquee.pixels((now - last_tick).as_micros()));
+            }
+            DisplayMode::Effect { speed, .. } => {
+                // `speed` is a percentage of the effect's normal pace.
+                let t_ms = (now - effect_start).as_millis() * u64::from(speed) / 100;
+                led_matrix.animate(&mut animation, t_ms);
+            }
+            DisplayMode::Snake => led_matrix.animate(&mut snake, now.as_millis()),
+            DisplayMode::Test(pattern) => {
+                let step = (now - effect_start).as_millis() * SWEEP_PIXELS_PER_SEC / 1000;
+                led_matrix.test_pattern(pattern, step as usize);
+            }
+            DisplayMode::Graph => {
+                if GRAPH_UPDATED.try_take().is_some() {
+                    led_matrix.draw_graph(&*GRAPH.lock().await);
+                }
+            }
+            DisplayMode::Regions => {
+                if REGIONS_UPDATED.try_take().is_some() {
+                    led_matrix.draw_regions(&*REGIONS.lock().await);
+                }
+            }
+            DisplayMode::Animation => {
+                if FRAME_PACK_READY.try_take().is_some() {
+                    effect_start = now;
+                    shown_frame = None;
+                }
+                // An upload in progress holds the pack: keep showing the
+                // current frame until it is done.
+                if let Ok(pack) = FRAME_PACK.try_lock() {
+                    let frame = pack.frame_at((now - effect_start).as_millis());
+                    if frame != shown_frame {
+                        if let Some(image) = frame.and_then(|i| pack.frame(i)) {
+                            draw_rgb565(&mut led_matrix, image);
+                        }
+                        shown_frame = frame;
+                    }
+                }
+            }
+            _ => {}
+        }
+        last_tick = now;
+        // Draw on top of the rendered text. The image goes with the next
+        // text change; queued commands stay in the scene under it.
+        if IMAGE_READY.try_take().is_some() {
+            draw_image(&mut led_matrix).await;
+        }
+        while let Ok(cmd) = DRAW_QUEUE.try_receive() {
+            if !led_matrix.push_command(cmd) {
+                warn!("Scene full, drawing not kept under the text");
+            }
+        }
+        led_matrix.commit_frame();
+        // Faded out, then blank, while nobody sends a command.
+        let level = SCREENSAVER
+            .lock()
+            .await
+            .brightness(*BRIGHTNESS.lock().await, now.as_millis());
+        led_matrix.set_brightness(level);
         led_matrix.refresh();
+        health.poke();
+        *FRAME_TIME_US.lock().await = led_matrix.frame_time_us();
+        if let Some(format) = SCREENSHOT_REQUEST.try_take() {
+            let len = format.encode(led_matrix.front(), &mut *SCREENSHOT.lock().await);
+            SCREENSHOT_READY.signal(len);
+        }
+
+        // Sleep out what's left of the frame period. Even with nothing
+        // left the timer yields once, so the network tasks still run.
+        let finished = Instant::now();
+        let (wait_us, target_fps) = {
+            let mut pacer = FRAME_PACER.lock().await;
+            (pacer.wait_us(finished.as_micros()), pacer.target_fps())
+        };
+        if wait_us.is_none()
+            && last_lag_warning.is_none_or(|warned| finished - warned >= FRAME_LAG_WARNING_INTERVAL)
+        {
+            warn!("Refresh loop can't keep up with {} fps", target_fps);
+            last_lag_warning = Some(finished);
+        }
+        Timer::after(Duration::from_micros(wait_us.unwrap_or(0))).await;
+    }
+}
+
+/// Blit the uploaded [`IMAGE`] over the whole panel.
+async fn draw_image(led_matrix: &mut LedMatrix) {
+    draw_rgb565(led_matrix, &IMAGE.lock().await);
+}
 
-        // Small delay to prevent watchdog
-        Timer::after(Duration::from_millis(1)).await;
+/// Blit a full-screen RGB565 `image`, one row at a time so only a row of
+/// expanded pixels is on the stack.
+fn draw_rgb565(led_matrix: &mut LedMatrix, image: &Image) {
+    for (y, row) in image.chunks_exact(MATRIX_WIDTH).enumerate() {
+        let mut line = [[0u16; 3]; MATRIX_WIDTH];
+        for (px, &color) in line.iter_mut().zip(row) {
+            *px = rgb565_to_pixel(color);
+        }
+        led_matrix.draw_bitmap(0, y as i32, MATRIX_WIDTH, 1, &line);
     }
 }
@@ -0,0 +1,118 @@
+//! mDNS responder task — makes the controller reachable as
+//! `http://<hostname>.local/` on the station network.
+//!
+//! Packet parsing and encoding live in the host-testable
+//! [`esp32_led_matrix::mdns_packet`] module; this module only owns the
+//! UDP socket. The hostname defaults to
+//! [`DEFAULT_HOSTNAME`](mdns_packet::DEFAULT_HOSTNAME) and can be
+//! changed with `/hostname?name=`, saved to flash and used from the next
+//! boot.
+
+use embassy_executor::Spawner;
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_net::{IpAddress, IpEndpoint, Ipv4Address, Stack};
+use esp32_led_matrix::mdns_packet::{self, MAX_HOSTNAME_LEN};
+use log::{error, info, warn};
+
+use crate::nvs::{self, Slot};
+use crate::wifi;
+
+/// Hostname label, without the `.local` suffix.
+pub type Hostname = heapless::String<MAX_HOSTNAME_LEN>;
+
+/// Load the hostname saved by [`save_hostname`], falling back to
+/// [`DEFAULT_HOSTNAME`](mdns_packet::DEFAULT_HOSTNAME).
+pub async fn load_hostname() -> Hostname {
+    nvs::load::<MAX_HOSTNAME_LEN>(Slot::Hostname)
+        .await
+        .filter(|name| mdns_packet::is_valid_hostname(name))
+        .unwrap_or_else(|| Hostname::try_from(mdns_packet::DEFAULT_HOSTNAME).unwrap())
+}
+
+/// Save the hostname for the next boot.
+pub async fn save_hostname(hostname: &str) {
+    nvs::save(Slot::Hostname, hostname).await;
+}
+
+/// Start answering mDNS queries for `hostname` on the station `stack`.
+/// The responder waits for a DHCP lease before announcing itself.
+pub fn start_mdns(spawner: Spawner, stack: &'static Stack<'static>, hostname: &str) {
+    let Ok(hostname) = Hostname::try_from(hostname) else {
+        error!("mDNS hostname too long: {}", hostname);
+        return;
+    };
+    info!("mDNS hostname: {}.local", hostname);
+    spawner.spawn(mdns_task(stack, hostname)).ok();
+}
+
+/// Join the mDNS group once the station has an address, announce our
+/// records, then answer queries forever.
+#[embassy_executor::task]
+async fn mdns_task(stack: &'static Stack<'static>, hostname: Hostname) {
+    wifi::wait_for_connection(stack).await;
+
+    let [a, b, c, d] = mdns_packet::MDNS_GROUP;
+    let group = Ipv4Address::new(a, b, c, d);
+    if let Err(e) = stack.join_multicast_group(group) {
+        error!("mDNS multicast join error: {:?}", e);
+        return;
+    }
+
+    let mut rx_meta = [PacketMetadata::EMPTY; 4];
+    let mut rx_buffer = [0u8; 1024];
+    let mut tx_meta = [PacketMetadata::EMPTY; 4];
+    let mut tx_buffer = [0u8; 1024];
+    let mut socket = UdpSocket::new(
+        *stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    if let Err(e) = socket.bind(mdns_packet::MDNS_PORT) {
+        error!("mDNS bind error: {:?}", e);
+        return;
+    }
+
+    let multicast = IpEndpoint::new(IpAddress::Ipv4(group), mdns_packet::MDNS_PORT);
+    let mut query = [0u8; mdns_packet::MAX_PACKET_LEN];
+    let mut reply = [0u8; mdns_packet::MAX_PACKET_LEN];
+
+    if let Some(ip) = station_ip(stack)
+        && let Some(len) = mdns_packet::announcement(&hostname, ip, &mut reply)
+        && let Err(e) = socket.send_to(&reply[..len], multicast).await
+    {
+        warn!("mDNS announcement failed: {:?}", e);
+    }
+    info!("mDNS responder ready: http://{}.local/", hostname);
+
+    loop {
+        let Ok((len, meta)) = socket.recv_from(&mut query).await else {
+            continue;
+        };
+        // The lease may have changed since the last query.
+        let Some(ip) = station_ip(stack) else {
+            continue;
+        };
+        let Some(reply_len) = mdns_packet::answer(&query[..len], &hostname, ip, &mut reply) else {
+            continue;
+        };
+        // Queries from port 5353 get the multicast reply; one-shot
+        // resolvers using another port are answered directly, with their
+        // query id echoed back.
+        let target = if meta.endpoint.port == mdns_packet::MDNS_PORT {
+            multicast
+        } else {
+            reply[..2].copy_from_slice(&query[..2]);
+            meta.endpoint
+        };
+        if let Err(e) = socket.send_to(&reply[..reply_len], target).await {
+            warn!("mDNS reply failed: {:?}", e);
+        }
+    }
+}
+
+/// Current station IPv4 address as octets, if DHCP has assigned one.
+fn station_ip(stack: &Stack<'static>) -> Option<[u8; 4]> {
+    Some(stack.config_v4()?.address.address().octets())
+}
@@ -0,0 +1,372 @@
+//! mDNS responder logic, so the device is reachable as `<host>.local`.
+//!
+//! The firmware's `mdns` module runs it on UDP 5353. Answers multicast
+//! DNS (RFC 6762) questions for exactly three names:
+//!
+//! - `<host>.local` — `A` record with the station address
+//! - `_http._tcp.local` — `PTR` to the device's HTTP service instance
+//! - `<host>._http._tcp.local` — `SRV` (port 80) and an empty `TXT`
+//!
+//! so browsers can open `http://<host>.local/` and service browsers list
+//! the controller. Everything else is ignored; other hosts on the link
+//! answer for their own names. Names in replies are written out in full
+//! (no compression) to keep the encoder trivial.
+
+/// UDP port mDNS runs on.
+pub const MDNS_PORT: u16 = 5353;
+
+/// IPv4 multicast group for mDNS.
+pub const MDNS_GROUP: [u8; 4] = [224, 0, 0, 251];
+
+/// Hostname used until one is saved via `/hostname`.
+pub const DEFAULT_HOSTNAME: &str = "ledmatrix";
+
+/// Longest hostname label accepted.
+pub const MAX_HOSTNAME_LEN: usize = 32;
+
+/// Largest packet handled, and the size of the reply buffer callers must
+/// provide.
+pub const MAX_PACKET_LEN: usize = 512;
+
+/// Port advertised for the HTTP service.
+const HTTP_PORT: u16 = 80;
+
+/// TTL for host and service records (RFC 6762 recommends 120 s for
+/// records containing a host name).
+const TTL_SECS: u32 = 120;
+
+const HEADER_LEN: usize = 12;
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+/// `CLASS_IN` with the cache-flush bit: we own these records exclusively.
+const CLASS_IN_FLUSH: u16 = 0x8001;
+
+/// Most questions looked at in one query.
+const MAX_QUESTIONS: usize = 8;
+
+/// True if `name` can be used as the `.local` host label: 1 to
+/// [`MAX_HOSTNAME_LEN`] ASCII letters, digits or `-`, not starting or
+/// ending with `-`.
+pub fn is_valid_hostname(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_HOSTNAME_LEN
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+        && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+}
+
+/// Which record groups a reply carries.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Wanted {
+    host: bool,
+    service: bool,
+    instance: bool,
+}
+
+/// Answer `query` for `hostname` at `ip`. Writes the response into
+/// `reply` and returns its length, or `None` when the query asks for
+/// nothing of ours (or isn't a well-formed query).
+pub fn answer(
+    query: &[u8],
+    hostname: &str,
+    ip: [u8; 4],
+    reply: &mut [u8; MAX_PACKET_LEN],
+) -> Option<usize> {
+    if query.len() < HEADER_LEN || query[2] & 0x80 != 0 {
+        return None;
+    }
+    let qdcount = u16::from_be_bytes([query[4], query[5]]) as usize;
+
+    let mut wanted = Wanted::default();
+    let mut pos = HEADER_LEN;
+    for _ in 0..qdcount.min(MAX_QUESTIONS) {
+        let mut name = [0u8; 256];
+        let (name_len, next) = read_name(query, pos, &mut name)?;
+        let qtype = u16::from_be_bytes([*query.get(next)?, *query.get(next + 1)?]);
+        // Ignore the unicast-response bit in the class field.
+        let qclass = u16::from_be_bytes([*query.get(next + 2)?, *query.get(next + 3)?]) & 0x7FFF;
+        pos = next + 4;
+        if qclass != CLASS_IN {
+            continue;
+        }
+        let name = &name[..name_len];
+        if name_matches(name, &[hostname, "local"]) && matches!(qtype, TYPE_A | TYPE_ANY) {
+            wanted.host = true;
+        }
+        if name_matches(name, &["_http", "_tcp", "local"]) && matches!(qtype, TYPE_PTR | TYPE_ANY) {
+            wanted.service = true;
+        }
+        if name_matches(name, &[hostname, "_http", "_tcp", "local"])
+            && matches!(qtype, TYPE_SRV | TYPE_TXT | TYPE_ANY)
+        {
+            wanted.instance = true;
+        }
+    }
+    if wanted == Wanted::default() {
+        return None;
+    }
+    build_response(hostname, ip, wanted, reply)
+}
+
+/// Unsolicited response announcing every record, sent when the device
+/// joins a network.
+pub fn announcement(
+    hostname: &str,
+    ip: [u8; 4],
+    reply: &mut [u8; MAX_PACKET_LEN],
+) -> Option<usize> {
+    let all = Wanted {
+        host: true,
+        service: true,
+        instance: true,
+    };
+    build_response(hostname, ip, all, reply)
+}
+
+fn build_response(
+    hostname: &str,
+    ip: [u8; 4],
+    wanted: Wanted,
+    reply: &mut [u8; MAX_PACKET_LEN],
+) -> Option<usize> {
+    let host = [hostname, "local"];
+    let service = ["_http", "_tcp", "local"];
+    let instance = [hostname, "_http", "_tcp", "local"];
+
+    let mut w = Writer { buf: reply, pos: 0 };
+    // id 0, QR + AA, no questions; counts patched below.
+    w.bytes(&[0, 0, 0x84, 0, 0, 0, 0, 0, 0, 0, 0, 0])?;
+    let mut answers = 0u16;
+
+    if wanted.service {
+        w.record_header(&service, TYPE_PTR, CLASS_IN)?;
+        let len_at = w.pos;
+        w.u16(0)?;
+        w.name(&instance)?;
+        w.patch_len(len_at);
+        answers += 1;
+    }
+    if wanted.service || wanted.instance {
+        w.record_header(&instance, TYPE_SRV, CLASS_IN_FLUSH)?;
+        let len_at = w.pos;
+        w.u16(0)?;
+        // Priority, weight, port, target.
+        w.u16(0)?;
+        w.u16(0)?;
+        w.u16(HTTP_PORT)?;
+        w.name(&host)?;
+        w.patch_len(len_at);
+        // An empty TXT record is a single zero-length string.
+        w.record_header(&instance, TYPE_TXT, CLASS_IN_FLUSH)?;
+        w.u16(1)?;
+        w.bytes(&[0])?;
+        answers += 2;
+    }
+    if wanted.host || wanted.service || wanted.instance {
+        w.record_header(&host, TYPE_A, CLASS_IN_FLUSH)?;
+        w.u16(4)?;
+        w.bytes(&ip)?;
+        answers += 1;
+    }
+
+    w.buf[6..8].copy_from_slice(&answers.to_be_bytes());
+    Some(w.pos)
+}
+
+/// Bounds-checked big-endian packet writer.
+struct Writer<'a> {
+    buf: &'a mut [u8; MAX_PACKET_LEN],
+    pos: usize,
+}
+
+impl Writer<'_> {
+    fn bytes(&mut self, data: &[u8]) -> Option<()> {
+        self.buf
+            .get_mut(self.pos..self.pos + data.len())?
+            .copy_from_slice(data);
+        self.pos += data.len();
+        Some(())
+    }
+
+    fn u16(&mut self, value: u16) -> Option<()> {
+        self.bytes(&value.to_be_bytes())
+    }
+
+    fn name(&mut self, labels: &[&str]) -> Option<()> {
+        for label in labels {
+            self.bytes(&[label.len() as u8])?;
+            self.bytes(label.as_bytes())?;
+        }
+        self.bytes(&[0])
+    }
+
+    fn record_header(&mut self, name: &[&str], rtype: u16, class: u16) -> Option<()> {
+        self.name(name)?;
+        self.u16(rtype)?;
+        self.u16(class)?;
+        self.bytes(&TTL_SECS.to_be_bytes())
+    }
+
+    /// Fill in the RDLENGTH field at `at` from what was written after it.
+    fn patch_len(&mut self, at: usize) {
+        let len = (self.pos - at - 2) as u16;
+        self.buf[at..at + 2].copy_from_slice(&len.to_be_bytes());
+    }
+}
+
+/// Read the (possibly compressed) name at `pos` into `out` as
+/// length-prefixed labels without the terminating zero. Returns the
+/// bytes written and the offset just past the name in `packet`.
+fn read_name(packet: &[u8], mut pos: usize, out: &mut [u8; 256]) -> Option<(usize, usize)> {
+    let mut written = 0;
+    let mut end = None;
+    // Bounds the number of pointer hops, so loops can't hang us.
+    for _ in 0..32 {
+        let len = *packet.get(pos)? as usize;
+        if len == 0 {
+            return Some((written, end.unwrap_or(pos + 1)));
+        }
+        if len & 0xC0 == 0xC0 {
+            let target = ((len & 0x3F) << 8) | *packet.get(pos + 1)? as usize;
+            end.get_or_insert(pos + 2);
+            pos = target;
+            continue;
+        }
+        let label = packet.get(pos..pos + 1 + len)?;
+        out.get_mut(written..written + label.len())?
+            .copy_from_slice(label);
+        written += label.len();
+        pos += 1 + len;
+    }
+    None
+}
+
+/// Case-insensitive match of length-prefixed `name` against `labels`.
+fn name_matches(mut name: &[u8], labels: &[&str]) -> bool {
+    for label in labels {
+        let Some((&len, rest)) = name.split_first() else {
+            return false;
+        };
+        let len = len as usize;
+        if len != label.len()
+            || rest.len() < len
+            || !rest[..len].eq_ignore_ascii_case(label.as_bytes())
+        {
+            return false;
+        }
+        name = &rest[len..];
+    }
+    name.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IP: [u8; 4] = [192, 168, 1, 42];
+
+    /// Multicast query with one question per `(name, type)`.
+    fn query(questions: &[(&str, u16)]) -> heapless::Vec<u8, MAX_PACKET_LEN> {
+        let mut packet = heapless::Vec::new();
+        packet
+            .extend_from_slice(&[0, 0, 0, 0, 0, questions.len() as u8, 0, 0, 0, 0, 0, 0])
+            .unwrap();
+        for (name, qtype) in questions {
+            for label in name.split('.') {
+                packet.push(label.len() as u8).unwrap();
+                packet.extend_from_slice(label.as_bytes()).unwrap();
+            }
+            packet.push(0).unwrap();
+            packet.extend_from_slice(&qtype.to_be_bytes()).unwrap();
+            packet.extend_from_slice(&CLASS_IN.to_be_bytes()).unwrap();
+        }
+        packet
+    }
+
+    fn answer_count(reply: &[u8]) -> u16 {
+        u16::from_be_bytes([reply[6], reply[7]])
+    }
+
+    #[test]
+    fn hostname_validation() {
+        assert!(is_valid_hostname(DEFAULT_HOSTNAME));
+        assert!(is_valid_hostname("matrix-2"));
+        assert!(!is_valid_hostname(""));
+        assert!(!is_valid_hostname("-matrix"));
+        assert!(!is_valid_hostname("matrix-"));
+        assert!(!is_valid_hostname("led matrix"));
+        assert!(!is_valid_hostname("led.matrix"));
+        assert!(!is_valid_hostname("abcdefghijklmnopqrstuvwxyz0123456"));
+    }
+
+    #[test]
+    fn a_query_for_own_name_is_answered() {
+        let q = query(&[("LedMatrix.local", TYPE_A)]);
+        let mut reply = [0u8; MAX_PACKET_LEN];
+        let len = answer(&q, "ledmatrix", IP, &mut reply).unwrap();
+        assert_eq!(reply[2], 0x84);
+        assert_eq!(answer_count(&reply), 1);
+        assert_eq!(reply[len - 4..len], IP);
+    }
+
+    #[test]
+    fn other_names_are_ignored() {
+        let q = query(&[("printer.local", TYPE_A), ("ledmatrix.lan", TYPE_A)]);
+        let mut reply = [0u8; MAX_PACKET_LEN];
+        assert!(answer(&q, "ledmatrix", IP, &mut reply).is_none());
+    }
+
+    #[test]
+    fn service_browse_gets_ptr_srv_txt_and_a() {
+        let q = query(&[("_http._tcp.local", TYPE_PTR)]);
+        let mut reply = [0u8; MAX_PACKET_LEN];
+        let len = answer(&q, "ledmatrix", IP, &mut reply).unwrap();
+        assert_eq!(answer_count(&reply), 4);
+        assert_eq!(reply[len - 4..len], IP);
+    }
+
+    #[test]
+    fn responses_are_ignored() {
+        let mut q = query(&[("ledmatrix.local", TYPE_A)]);
+        q[2] = 0x84;
+        let mut reply = [0u8; MAX_PACKET_LEN];
+        assert!(answer(&q, "ledmatrix", IP, &mut reply).is_none());
+    }
+
+    #[test]
+    fn compressed_question_names_are_followed() {
+        // Second question is `ledmatrix.local` via a pointer to the
+        // `local` label of the first (`x.local`).
+        let mut q = query(&[("x.local", TYPE_A)]);
+        q[5] = 2;
+        q.extend_from_slice(&[9]).unwrap();
+        q.extend_from_slice(b"ledmatrix").unwrap();
+        q.extend_from_slice(&[0xC0, 14]).unwrap();
+        q.extend_from_slice(&TYPE_A.to_be_bytes()).unwrap();
+        q.extend_from_slice(&CLASS_IN.to_be_bytes()).unwrap();
+        let mut reply = [0u8; MAX_PACKET_LEN];
+        assert!(answer(&q, "ledmatrix", IP, &mut reply).is_some());
+    }
+
+    #[test]
+    fn pointer_loops_are_rejected() {
+        let mut q = query(&[]);
+        q[5] = 1;
+        q.extend_from_slice(&[0xC0, 12, 0, 1, 0, 1]).unwrap();
+        let mut reply = [0u8; MAX_PACKET_LEN];
+        assert!(answer(&q, "ledmatrix", IP, &mut reply).is_none());
+    }
+
+    #[test]
+    fn announcement_carries_every_record() {
+        let mut reply = [0u8; MAX_PACKET_LEN];
+        let len = announcement("ledmatrix", IP, &mut reply).unwrap();
+        assert_eq!(answer_count(&reply), 4);
+        assert_eq!(reply[len - 4..len], IP);
+    }
+}
@@ -0,0 +1,264 @@
+//! What the display shows: the user's text, a marquee, a vertical
+//! ticker, a wrapped paragraph, a clock, a countdown, an animated effect,
+//! a graph, an uploaded animation, two text regions, a game of Snake or a
+//! test pattern.
+//!
+//! Selected with `/mode?m=` (and `/marquee`, `/vscroll`, `/paragraph`,
+//! `/timer`, `/effect`, `/graph/push`, `/anim`, `/region`, `/snake`,
+//! `/test`). The refresh loop turns the mode into the text it renders, so
+//! the clock and the countdown go through the same font, color and layout
+//! path as ordinary messages.
+
+use crate::timezone::TimeZone;
+
+/// Effect speed when `/effect` has no `speed`, in percent.
+pub const DEFAULT_EFFECT_SPEED: u16 = 100;
+
+/// Fastest accepted effect speed, in percent.
+pub const MAX_EFFECT_SPEED: u16 = 1000;
+
+/// Marquee speed when `/marquee` has no `speed`, in pixels per second.
+pub const DEFAULT_MARQUEE_SPEED: u16 = 30;
+
+/// Fastest accepted marquee speed, in pixels per second.
+pub const MAX_MARQUEE_SPEED: u16 = 500;
+
+/// Display mode, switched by `/mode?m=`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayMode {
+    /// The text set by `/text`.
+    #[default]
+    Text,
+    /// The text set by `/marquee`, always scrolling at `speed` pixels per
+    /// second, even when it would fit on the panel.
+    Marquee { speed: u16 },
+    /// The lines set by `/vscroll`, scrolling upwards at `speed` pixels
+    /// per second like a news ticker.
+    VScroll { speed: u16 },
+    /// The text set by `/paragraph`, word-wrapped over as many lines as
+    /// fit instead of scrolling.
+    Paragraph,
+    /// Local time as `HH:MM`, or `HH:MM:SS` with `seconds`.
+    Clock { seconds: bool },
+    /// The `/timer` countdown, big and centred. See
+    /// [`Countdown`](crate::countdown::Countdown).
+    Timer,
+    /// A full-screen animation, set by `/effect?name=&speed=`. `speed`
+    /// is a percentage of its normal speed.
+    Effect { effect: Effect, speed: u16 },
+    /// The readings pushed with `/graph/push?v=`, as a line chart. See
+    /// [`Graph`](crate::graph::Graph).
+    Graph,
+    /// The frame pack uploaded to `/anim`, looping. See
+    /// [`FramePack`](crate::frame_pack::FramePack).
+    Animation,
+    /// A header and a body, each set by `/region?id=`. See
+    /// [`Regions`](crate::regions::Regions).
+    Regions,
+    /// A game of Snake, started by `/snake` and steered with `/input`.
+    /// See [`Snake`](crate::games::Snake).
+    Snake,
+    /// A bring-up pattern, set by `/test?pattern=`.
+    Test(TestPattern),
+}
+
+/// Full-screen animations for [`DisplayMode::Effect`], drawn by
+/// [`effects`](crate::effects).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    /// A diagonal rainbow cycling through every hue.
+    Rainbow,
+    /// Overlapping sine waves in drifting colors.
+    Plasma,
+    /// Flames rising from the bottom edge.
+    Fire,
+    /// Stars flying towards the viewer.
+    Starfield,
+    /// Conway's Game of Life, a cell per pixel. With `wrap`, cells on
+    /// opposite edges are neighbours; with `from_screen` it starts from
+    /// whatever was shown instead of a random pattern.
+    Life { wrap: bool, from_screen: bool },
+}
+
+impl Effect {
+    /// Effect for an `/effect?name=` name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rainbow" => Some(Self::Rainbow),
+            "plasma" => Some(Self::Plasma),
+            "fire" => Some(Self::Fire),
+            "starfield" => Some(Self::Starfield),
+            "life" => Some(Self::Life {
+                wrap: true,
+                from_screen: false,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The effect's `/effect?name=` name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Rainbow => "rainbow",
+            Self::Plasma => "plasma",
+            Self::Fire => "fire",
+            Self::Starfield => "starfield",
+            Self::Life { .. } => "life",
+        }
+    }
+}
+
+/// Patterns for checking the wiring, for [`DisplayMode::Test`]. See
+/// [`FrameBuffer::test_pattern`](crate::frame_buffer::FrameBuffer::test_pattern).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestPattern {
+    /// Every pixel full red: only the DR1 and DR2 chains.
+    Red,
+    /// Every pixel full green: only DG1 and DG2.
+    Green,
+    /// Every pixel full blue: only DB1 and DB2.
+    Blue,
+    /// A one-pixel white outline around the panel's edges.
+    Border,
+    /// A single white pixel stepping along each row, top to bottom.
+    Sweep,
+    /// Vertical bars of white, yellow, cyan, green, magenta, red, blue
+    /// and black: every mix of the three chains of a row.
+    Bars,
+}
+
+impl TestPattern {
+    /// Pattern for a `/test?pattern=` name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "red" => Some(Self::Red),
+            "green" => Some(Self::Green),
+            "blue" => Some(Self::Blue),
+            "border" => Some(Self::Border),
+            "sweep" => Some(Self::Sweep),
+            "bars" => Some(Self::Bars),
+            _ => None,
+        }
+    }
+}
+
+impl DisplayMode {
+    /// Mode for a `/mode?m=` name. `seconds` only matters for the clock.
+    pub fn from_name(name: &str, seconds: bool) -> Option<Self> {
+        match name {
+            "text" => Some(Self::Text),
+            "clock" => Some(Self::Clock { seconds }),
+            "graph" => Some(Self::Graph),
+            _ => None,
+        }
+    }
+
+    /// Short name for `/status`: `text`, `marquee`, `vscroll`,
+    /// `paragraph`, `clock`, `timer`, `effect`, `graph`, `animation`,
+    /// `regions`, `snake` or `test`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Marquee { .. } => "marquee",
+            Self::VScroll { .. } => "vscroll",
+            Self::Paragraph => "paragraph",
+            Self::Clock { .. } => "clock",
+            Self::Timer => "timer",
+            Self::Effect { .. } => "effect",
+            Self::Graph => "graph",
+            Self::Animation => "animation",
+            Self::Regions => "regions",
+            Self::Snake => "snake",
+            Self::Test(_) => "test",
+        }
+    }
+}
+
+/// Clock face text: local `HH:MM` (or `HH:MM:SS`) at Unix time `unix`
+/// in `tz`, or dashes while the time is unknown (before the first SNTP
+/// reply).
+pub fn clock_text(unix: Option<i64>, tz: &TimeZone, seconds: bool) -> heapless::String<8> {
+    let mut text = heapless::String::new();
+    let Some(unix) = unix else {
+        let _ = text.push_str(if seconds { "--:--:--" } else { "--:--" });
+        return text;
+    };
+    let (h, m, s) = tz.time_of_day(unix);
+    for (i, value) in [h, m, s].into_iter().enumerate() {
+        if i == 2 && !seconds {
+            break;
+        }
+        if i > 0 {
+            let _ = text.push(':');
+        }
+        let _ = text.push((b'0' + value / 10) as char);
+        let _ = text.push((b'0' + value % 10) as char);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-01-01T09:05:07Z.
+    const UNIX: i64 = 1_704_099_907;
+
+    #[test]
+    fn mode_names() {
+        assert_eq!(
+            DisplayMode::from_name("text", true),
+            Some(DisplayMode::Text)
+        );
+        assert_eq!(
+            DisplayMode::from_name("clock", false),
+            Some(DisplayMode::Clock { seconds: false })
+        );
+        assert_eq!(
+            DisplayMode::from_name("graph", false),
+            Some(DisplayMode::Graph)
+        );
+        assert_eq!(DisplayMode::from_name("Clock", false), None);
+        for name in ["text", "clock", "graph"] {
+            assert_eq!(DisplayMode::from_name(name, false).unwrap().name(), name);
+        }
+        assert_eq!(DisplayMode::Timer.name(), "timer");
+        assert_eq!(DisplayMode::default(), DisplayMode::Text);
+        assert_eq!(Effect::from_name("rainbow"), Some(Effect::Rainbow));
+        assert_eq!(Effect::from_name("fire"), Some(Effect::Fire));
+        assert_eq!(
+            Effect::from_name("life"),
+            Some(Effect::Life {
+                wrap: true,
+                from_screen: false
+            })
+        );
+        assert_eq!(Effect::from_name("sparkle"), None);
+        for name in ["rainbow", "plasma", "fire", "starfield", "life"] {
+            assert_eq!(Effect::from_name(name).unwrap().name(), name);
+        }
+        assert_eq!(TestPattern::from_name("bars"), Some(TestPattern::Bars));
+        assert_eq!(TestPattern::from_name("sweep"), Some(TestPattern::Sweep));
+        assert_eq!(TestPattern::from_name("Bars"), None);
+    }
+
+    #[test]
+    fn unsynced_clock_shows_dashes() {
+        assert_eq!(clock_text(None, &TimeZone::UTC, false).as_str(), "--:--");
+        assert_eq!(clock_text(None, &TimeZone::UTC, true).as_str(), "--:--:--");
+    }
+
+    #[test]
+    fn clock_is_zero_padded_local_time() {
+        assert_eq!(
+            clock_text(Some(UNIX), &TimeZone::UTC, false).as_str(),
+            "09:05"
+        );
+        assert_eq!(
+            clock_text(Some(UNIX), &TimeZone::UTC, true).as_str(),
+            "09:05:07"
+        );
+        let helsinki = TimeZone::parse("EET-2EEST,M3.5.0/3,M10.5.0/4").unwrap();
+        assert_eq!(clock_text(Some(UNIX), &helsinki, false).as_str(), "11:05");
+    }
+}
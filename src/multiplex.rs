@@ -0,0 +1,132 @@
+//! GCLK multiplex bookkeeping.
+//!
+//! The panel shows its front buffer one scanline at a time while GCLK
+//! runs: [`GCLK_PULSES_PER_SCANLINE`] regular pulses, then a longer
+//! dead-time pulse, then the next scanline. Its back buffer can be
+//! written at the same time, so the driver shifts a new frame in with one
+//! GCLK per DCLK and the picture never stops. [`Multiplexer`] tracks the
+//! position in that cycle and says when a scanline ends and whether VSYNC
+//! is due; the driver does the pin toggling.
+
+use crate::chain_mapper::SCANLINES;
+
+/// Regular GCLK pulses per scanline (per the wiki: 256 regular pulses + 1
+/// dead-time pulse = 257 total).
+pub const GCLK_PULSES_PER_SCANLINE: u32 = 256;
+
+/// What the driver does after the last regular pulse of a scanline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanlineEnd {
+    /// Issue VSYNC before the dead-time pulse. Only at the scanline
+    /// 10 -> 0 wrap, so the panel swaps buffers at a frame boundary.
+    pub vsync: bool,
+    /// Scanline to address after the dead-time pulse.
+    pub next_scanline: usize,
+}
+
+/// Position in the GCLK multiplex cycle.
+#[derive(Debug, Clone)]
+pub struct Multiplexer {
+    /// Scanline currently addressed.
+    scanline: usize,
+    /// Regular GCLK pulses sent for it so far.
+    pulses: u32,
+    /// A complete frame is in the back buffer, waiting for VSYNC.
+    vsync_pending: bool,
+}
+
+impl Multiplexer {
+    /// Start of scanline 0, nothing to swap in.
+    pub const fn new() -> Self {
+        Self {
+            scanline: 0,
+            pulses: 0,
+            vsync_pending: false,
+        }
+    }
+
+    /// Scanline currently addressed.
+    pub fn scanline(&self) -> usize {
+        self.scanline
+    }
+
+    /// Swap in the back buffer at the next scanline 10 -> 0 wrap. Call
+    /// once a whole frame has been shifted and latched.
+    pub fn request_vsync(&mut self) {
+        self.vsync_pending = true;
+    }
+
+    /// True from [`Self::request_vsync`] until the VSYNC is issued.
+    pub fn vsync_pending(&self) -> bool {
+        self.vsync_pending
+    }
+
+    /// Count one regular GCLK pulse. Returns `Some` when it was the
+    /// scanline's last, and the driver must finish the scanline.
+    pub fn pulse(&mut self) -> Option<ScanlineEnd> {
+        self.pulses += 1;
+        if self.pulses < GCLK_PULSES_PER_SCANLINE {
+            return None;
+        }
+        let vsync = self.vsync_pending && self.scanline == SCANLINES - 1;
+        if vsync {
+            self.vsync_pending = false;
+        }
+        self.pulses = 0;
+        self.scanline = (self.scanline + 1) % SCANLINES;
+        Some(ScanlineEnd {
+            vsync,
+            next_scanline: self.scanline,
+        })
+    }
+}
+
+impl Default for Multiplexer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pulse through one whole scanline, returning how it ended.
+    fn finish_scanline(mux: &mut Multiplexer) -> ScanlineEnd {
+        for _ in 1..GCLK_PULSES_PER_SCANLINE {
+            assert_eq!(mux.pulse(), None);
+        }
+        mux.pulse().expect("scanline should end on its 256th pulse")
+    }
+
+    #[test]
+    fn scanlines_advance_every_256_pulses_and_wrap() {
+        let mut mux = Multiplexer::new();
+        for expected in (1..SCANLINES).chain([0, 1]) {
+            let end = finish_scanline(&mut mux);
+            assert_eq!(end.next_scanline, expected);
+            assert_eq!(mux.scanline(), expected);
+            assert!(!end.vsync);
+        }
+    }
+
+    #[test]
+    fn vsync_waits_for_the_frame_wrap() {
+        let mut mux = Multiplexer::new();
+        finish_scanline(&mut mux);
+        mux.request_vsync();
+        // Scanlines 1..=9 end without it...
+        for _ in 1..SCANLINES - 1 {
+            assert!(!finish_scanline(&mut mux).vsync);
+            assert!(mux.vsync_pending());
+        }
+        // ...and the 10 -> 0 wrap issues it exactly once.
+        let end = finish_scanline(&mut mux);
+        assert_eq!(end.next_scanline, 0);
+        assert!(end.vsync);
+        assert!(!mux.vsync_pending());
+        for _ in 0..SCANLINES {
+            assert!(!finish_scanline(&mut mux).vsync);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Notices: text or a test pattern shown for a while, then dismissed,
+//! from `/notify`.
+//!
+//! [`Notices`] queues them on a caller-supplied millisecond clock like
+//! [`Countdown`](crate::countdown::Countdown). The refresh loop asks
+//! [`Notices::current`] every frame and shows what it returns instead
+//! of the display mode. A notice's time starts when it first shows, so
+//! queued ones play one after another, in order; after the last one
+//! `current` returns `None` and whatever was there before comes back.
+
+use crate::http_request::MAX_MESSAGE_LEN;
+use crate::mode::TestPattern;
+
+/// Notices that can wait their turn, the one on show included.
+pub const MAX_NOTICES: usize = 4;
+
+/// Longest a notice can show: an hour.
+pub const MAX_NOTICE_SECONDS: u32 = 60 * 60;
+
+/// What a notice shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Notice {
+    /// Text, laid out like `/text`.
+    Text(heapless::String<MAX_MESSAGE_LEN>),
+    /// A test pattern, as with `/test`.
+    Pattern(TestPattern),
+}
+
+/// A notice and how long it shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimedNotice {
+    pub notice: Notice,
+    pub duration_ms: u64,
+}
+
+/// Notices waiting to show, in order. See the module docs.
+#[derive(Debug, Default)]
+pub struct Notices {
+    /// The first one is on show, or will be on the next
+    /// [`Notices::current`].
+    queue: heapless::Deque<TimedNotice, MAX_NOTICES>,
+    /// When the first one's time is up; `None` until it has shown.
+    until_ms: Option<u64>,
+}
+
+impl Notices {
+    /// Nothing queued.
+    pub const fn new() -> Self {
+        Self {
+            queue: heapless::Deque::new(),
+            until_ms: None,
+        }
+    }
+
+    /// Queue `notice` after the ones already waiting. Hands it back if
+    /// [`MAX_NOTICES`] are queued.
+    pub fn push(&mut self, notice: TimedNotice) -> Result<(), TimedNotice> {
+        self.queue.push_back(notice)
+    }
+
+    /// The notice to show at `now_ms`, dropping those whose time is up.
+    /// `None` once they all are.
+    pub fn current(&mut self, now_ms: u64) -> Option<&Notice> {
+        loop {
+            let duration_ms = self.queue.front()?.duration_ms;
+            let until = *self
+                .until_ms
+                .get_or_insert(now_ms.saturating_add(duration_ms));
+            if now_ms < until {
+                return self.queue.front().map(|timed| &timed.notice);
+            }
+            self.queue.pop_front();
+            self.until_ms = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(msg: &str, duration_ms: u64) -> TimedNotice {
+        TimedNotice {
+            notice: Notice::Text(msg.try_into().unwrap()),
+            duration_ms,
+        }
+    }
+
+    #[test]
+    fn notices_play_in_order_then_go() {
+        let mut notices = Notices::new();
+        assert_eq!(notices.current(0), None);
+        notices.push(text("one", 1_000)).unwrap();
+        let bars = TimedNotice {
+            notice: Notice::Pattern(TestPattern::Bars),
+            duration_ms: 500,
+        };
+        notices.push(bars.clone()).unwrap();
+
+        // The first starts when it first shows, not when it was queued.
+        assert_eq!(notices.current(5_000), Some(&text("one", 0).notice));
+        assert_eq!(notices.current(5_999), Some(&text("one", 0).notice));
+        assert_eq!(notices.current(6_000), Some(&bars.notice));
+        assert_eq!(notices.current(6_499), Some(&bars.notice));
+        assert_eq!(notices.current(6_500), None);
+    }
+
+    #[test]
+    fn late_checks_skip_what_already_expired() {
+        let mut notices = Notices::new();
+        notices.push(text("a", 100)).unwrap();
+        notices.current(0);
+        notices.push(text("b", 100)).unwrap();
+        notices.push(text("c", 100)).unwrap();
+        // "b" starts when "a" is seen to be over, so it still shows.
+        assert_eq!(notices.current(10_000), Some(&text("b", 0).notice));
+        assert_eq!(notices.current(10_100), Some(&text("c", 0).notice));
+    }
+
+    #[test]
+    fn a_full_queue_refuses_more() {
+        let mut notices = Notices::new();
+        for _ in 0..MAX_NOTICES {
+            notices.push(text("x", 1)).unwrap();
+        }
+        assert_eq!(notices.push(text("y", 1)), Err(text("y", 1)));
+    }
+}
@@ -0,0 +1,195 @@
+//! Persistent settings in the flash `nvs` partition.
+//!
+//! Thin wrapper around `esp-storage`: each [`Slot`] owns one 4 KB sector
+//! and stores a single record in the layout defined by
+//! [`esp32_led_matrix::nvs_record`]. Failures are logged and otherwise
+//! ignored — losing a saved setting must never take the display down.
+
+use core::fmt::Write;
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embedded_storage::{ReadStorage, Storage};
+use esp_hal::peripherals::FLASH;
+use esp_storage::FlashStorage;
+use esp32_led_matrix::auth::MAX_AUTH_PASSWORD_LEN;
+use esp32_led_matrix::credentials::MAX_PASSWORD_LEN;
+use esp32_led_matrix::display_state::{DISPLAY_STATE_LEN, DisplayState};
+use esp32_led_matrix::http_request::MAX_MESSAGE_LEN;
+use esp32_led_matrix::nvs_record::{self, HEADER_LEN};
+use esp32_led_matrix::ota_image::MAX_TOKEN_LEN;
+use log::{info, warn};
+
+/// Start of the `nvs` partition (same in `partitions.csv` as in
+/// espflash's default table). The one in `partitions.csv` has room for
+/// nine slots; the default table's only for six.
+const NVS_PARTITION_OFFSET: u32 = 0x9000;
+
+/// Flash sector size; one per setting.
+const SECTOR_SIZE: u32 = FlashStorage::SECTOR_SIZE;
+
+/// Largest record any setting writes. Sizes the read buffers.
+const MAX_RECORD_LEN: usize = HEADER_LEN
+    + max(
+        max(MAX_MESSAGE_LEN, MAX_PASSWORD_LEN),
+        max(max(MAX_TOKEN_LEN, MAX_AUTH_PASSWORD_LEN), DISPLAY_STATE_LEN),
+    );
+
+/// One stored setting.
+#[derive(Clone, Copy)]
+pub enum Slot {
+    /// Last display text.
+    Text,
+    /// WiFi station SSID.
+    Ssid,
+    /// WiFi station password.
+    Password,
+    /// mDNS hostname.
+    Hostname,
+    /// POSIX TZ string for the clock.
+    Timezone,
+    /// Bearer token `POST /ota` must present.
+    OtaToken,
+    /// HTTP Basic password for the control endpoints.
+    AuthPassword,
+    /// Screensaver timeout in seconds, as decimal digits.
+    IdleTimeout,
+    /// Display mode and text color, as a [`DisplayState`] record.
+    DisplayState,
+}
+
+impl Slot {
+    /// Flash offset of this setting's sector.
+    const fn offset(self) -> u32 {
+        NVS_PARTITION_OFFSET + SECTOR_SIZE * self as u32
+    }
+}
+
+/// Flash access, shared by the HTTP task (writes) and `main` (reads).
+/// `None` until [`init`] runs.
+static STORAGE: Mutex<CriticalSectionRawMutex, Option<FlashStorage<'static>>> = Mutex::new(None);
+
+/// Take ownership of the flash peripheral. Call once, before any other
+/// function in this module.
+pub async fn init(flash: FLASH<'static>) {
+    *STORAGE.lock().await = Some(FlashStorage::new(flash));
+}
+
+/// Save the display text so it survives a reboot. An empty string is
+/// stored too, so a cleared display stays cleared.
+pub async fn save_text(text: &str) {
+    save(Slot::Text, text).await;
+}
+
+/// Load the display text saved by [`save_text`]. Returns `None` when
+/// nothing (or only an empty string) was saved, or the record is corrupt.
+pub async fn load_text() -> Option<heapless::String<MAX_MESSAGE_LEN>> {
+    load(Slot::Text).await.filter(|text| !text.is_empty())
+}
+
+/// Save the screensaver timeout, in seconds (0 = off).
+pub async fn save_idle_timeout(secs: u32) {
+    let mut digits: heapless::String<10> = heapless::String::new();
+    let _ = write!(digits, "{}", secs);
+    save(Slot::IdleTimeout, &digits).await;
+}
+
+/// Load the timeout saved by [`save_idle_timeout`]; 0 (off) when
+/// nothing valid was saved.
+pub async fn load_idle_timeout() -> u32 {
+    load::<10>(Slot::IdleTimeout)
+        .await
+        .and_then(|digits| digits.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Save the display mode and text color so the display comes back up
+/// the same after a reboot.
+pub async fn save_display_state(state: &DisplayState) {
+    save(Slot::DisplayState, &state.to_record()).await;
+}
+
+/// Load the state saved by [`save_display_state`]; the first-boot
+/// default when nothing was saved or the record is corrupt.
+pub async fn load_display_state() -> DisplayState {
+    let state = load::<DISPLAY_STATE_LEN>(Slot::DisplayState)
+        .await
+        .and_then(|record| DisplayState::from_record(&record));
+    if state.is_none() {
+        info!("No valid display mode saved, starting in text mode");
+    }
+    state.unwrap_or_default()
+}
+
+/// Save `value` in `slot`. Values too long for the record buffer are
+/// logged and dropped.
+pub async fn save(slot: Slot, value: &str) {
+    let mut record = [0u8; MAX_RECORD_LEN];
+    match nvs_record::encode(value, &mut record) {
+        Ok(len) => write_record(slot.offset(), &record[..len]).await,
+        Err(e) => warn!("Not saving setting at {:#x}: {:?}", slot.offset(), e),
+    }
+}
+
+/// Load the value saved in `slot`. `None` when nothing was saved, the
+/// record is corrupt or the value is longer than `N`.
+pub async fn load<const N: usize>(slot: Slot) -> Option<heapless::String<N>> {
+    let mut record = [0u8; MAX_RECORD_LEN];
+    read_record(slot.offset(), &mut record).await?;
+    nvs_record::decode(&record)
+}
+
+/// `const` max, for sizing [`MAX_RECORD_LEN`].
+const fn max(a: usize, b: usize) -> usize {
+    if a > b { a } else { b }
+}
+
+/// Read `record.len()` bytes at `offset`. `None` (after logging) on
+/// flash errors. Also used by `ota` for raw partition access.
+pub async fn read_record(offset: u32, record: &mut [u8]) -> Option<()> {
+    let mut storage = STORAGE.lock().await;
+    let storage = storage.as_mut()?;
+    if let Err(e) = storage.read(offset, record) {
+        warn!("Flash read at {:#x} failed: {:?}", offset, e);
+        return None;
+    }
+    Some(())
+}
+
+/// Write `record` at `offset`, skipping the erase/write cycle when the
+/// sector already holds the same bytes to spare flash wear.
+async fn write_record(offset: u32, record: &[u8]) {
+    let mut storage = STORAGE.lock().await;
+    let Some(storage) = storage.as_mut() else {
+        warn!("Flash storage not initialised, setting not saved");
+        return;
+    };
+
+    let mut current = [0u8; MAX_RECORD_LEN];
+    let current = &mut current[..record.len()];
+    if storage.read(offset, current).is_ok() && current == record {
+        return;
+    }
+    match storage.write(offset, record) {
+        Ok(()) => info!("Saved setting at {:#x}", offset),
+        Err(e) => warn!("Flash write at {:#x} failed: {:?}", offset, e),
+    }
+}
+
+/// Write `data` at `offset` as is, erasing the sectors it covers. For
+/// `ota`, which writes whole partitions. Returns `false` (after logging)
+/// on flash errors.
+pub async fn write_flash(offset: u32, data: &[u8]) -> bool {
+    let mut storage = STORAGE.lock().await;
+    let Some(storage) = storage.as_mut() else {
+        warn!("Flash storage not initialised");
+        return false;
+    };
+    match storage.write(offset, data) {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Flash write at {:#x} failed: {:?}", offset, e);
+            false
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! On-flash record layout for small persistent settings.
+//!
+//! The firmware doesn't link ESP-IDF, so there is no NVS library to lean
+//! on. Instead each setting owns one flash sector inside the `nvs`
+//! partition and stores a single record there:
+//!
+//! | Bytes  | Field                                  |
+//! | ------ | -------------------------------------- |
+//! | 0..4   | Magic, [`RECORD_MAGIC`]                |
+//! | 4..6   | Value length in bytes (little-endian)  |
+//! | 6..8   | Fletcher-16 checksum of the value      |
+//! | 8..    | Value, UTF-8                           |
+//!
+//! Erased flash reads back as `0xFF`, so a sector that was never written
+//! fails the magic check and decodes as "nothing stored". The checksum
+//! catches a record torn by a power cut mid-write.
+//!
+//! Only the byte layout lives here so it can be tested on the host; the
+//! flash access itself is in the firmware's `nvs` module.
+
+/// Marks a sector as holding a record.
+pub const RECORD_MAGIC: [u8; 4] = *b"LMR1";
+
+/// Bytes in front of the value: magic, length and checksum.
+pub const HEADER_LEN: usize = 8;
+
+/// Why a value couldn't be encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordError {
+    /// The value plus header doesn't fit in the output buffer.
+    TooLong,
+}
+
+/// Encode `value` into `out` and return the record length. Empty values
+/// are valid and produce a header-only record.
+pub fn encode(value: &str, out: &mut [u8]) -> Result<usize, RecordError> {
+    let bytes = value.as_bytes();
+    let len = HEADER_LEN + bytes.len();
+    if len > out.len() || bytes.len() > u16::MAX as usize {
+        return Err(RecordError::TooLong);
+    }
+    out[0..4].copy_from_slice(&RECORD_MAGIC);
+    out[4..6].copy_from_slice(&(bytes.len() as u16).to_le_bytes());
+    out[6..8].copy_from_slice(&fletcher16(bytes).to_le_bytes());
+    out[HEADER_LEN..len].copy_from_slice(bytes);
+    Ok(len)
+}
+
+/// Decode a record read back from flash. Returns `None` for erased or
+/// corrupt sectors and for values longer than `N` bytes.
+pub fn decode<const N: usize>(record: &[u8]) -> Option<heapless::String<N>> {
+    if record.len() < HEADER_LEN || record[0..4] != RECORD_MAGIC {
+        return None;
+    }
+    let len = u16::from_le_bytes([record[4], record[5]]) as usize;
+    let checksum = u16::from_le_bytes([record[6], record[7]]);
+    let bytes = record.get(HEADER_LEN..HEADER_LEN + len)?;
+    if fletcher16(bytes) != checksum {
+        return None;
+    }
+    let text = core::str::from_utf8(bytes).ok()?;
+    heapless::String::try_from(text).ok()
+}
+
+/// Fletcher-16 checksum. Cheap, and unlike a plain sum it notices
+/// swapped bytes.
+fn fletcher16(bytes: &[u8]) -> u16 {
+    let mut sum1: u16 = 0;
+    let mut sum2: u16 = 0;
+    for &byte in bytes {
+        sum1 = (sum1 + byte as u16) % 255;
+        sum2 = (sum2 + sum1) % 255;
+    }
+    (sum2 << 8) | sum1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut buf = [0u8; 64];
+        let len = encode("HELLO", &mut buf).unwrap();
+        assert_eq!(len, HEADER_LEN + 5);
+        let text: heapless::String<32> = decode(&buf[..len]).unwrap();
+        assert_eq!(text.as_str(), "HELLO");
+    }
+
+    #[test]
+    fn empty_value_round_trips() {
+        let mut buf = [0u8; HEADER_LEN];
+        let len = encode("", &mut buf).unwrap();
+        let text: heapless::String<32> = decode(&buf[..len]).unwrap();
+        assert!(text.is_empty());
+    }
+
+    #[test]
+    fn too_long_for_buffer_is_rejected() {
+        let mut buf = [0u8; HEADER_LEN + 3];
+        assert_eq!(encode("HELLO", &mut buf), Err(RecordError::TooLong));
+    }
+
+    #[test]
+    fn erased_flash_decodes_as_none() {
+        let erased = [0xFFu8; 64];
+        assert!(decode::<32>(&erased).is_none());
+    }
+
+    #[test]
+    fn corrupt_value_decodes_as_none() {
+        let mut buf = [0u8; 64];
+        let len = encode("HELLO", &mut buf).unwrap();
+        buf[HEADER_LEN] = b'J';
+        assert!(decode::<32>(&buf[..len]).is_none());
+    }
+
+    #[test]
+    fn value_longer_than_capacity_decodes_as_none() {
+        let mut buf = [0u8; 64];
+        let len = encode("HELLO", &mut buf).unwrap();
+        assert!(decode::<4>(&buf[..len]).is_none());
+    }
+
+    #[test]
+    fn truncated_record_decodes_as_none() {
+        let mut buf = [0u8; 64];
+        let len = encode("HELLO", &mut buf).unwrap();
+        assert!(decode::<32>(&buf[..len - 1]).is_none());
+    }
+}
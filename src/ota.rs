@@ -0,0 +1,115 @@
+//! Over-the-air firmware updates.
+//!
+//! Needs the partition table in `partitions.csv` (flashed once over USB,
+//! see README): `otadata` at [`OTADATA_OFFSET`] and two app slots,
+//! `ota_0` and `ota_1`, at [`OTA_SLOT_OFFSETS`]. An [`Update`] streams an
+//! image into the slot that isn't running and, once all of it is written,
+//! points `otadata` at it; see [`esp32_led_matrix::ota_image`] for why a
+//! failed upload leaves the running firmware in place.
+
+use esp32_led_matrix::ota_image::{
+    self, ImageError, OTA_SELECT_LEN, OTA_SLOT_LEN, OtaSelect, UpdatePlan,
+};
+use log::info;
+
+use crate::nvs;
+
+/// Start of the `otadata` partition: two sectors, one entry each.
+const OTADATA_OFFSET: u32 = 0x1_0000;
+
+/// Flash sector size, the distance between the two `otadata` entries.
+const SECTOR_SIZE: u32 = 0x1000;
+
+/// Start of the `ota_0` and `ota_1` app partitions.
+const OTA_SLOT_OFFSETS: [u32; 2] = [0x2_0000, 0x20_0000];
+
+/// Log progress every this many bytes.
+const PROGRESS_STEP: usize = 64 * 1024;
+
+/// Why an update was abandoned.
+#[derive(Debug)]
+pub enum OtaError {
+    /// The image is larger than an app slot.
+    TooLarge,
+    /// The first bytes aren't an ESP32-C3 app image.
+    Image(ImageError),
+    /// More bytes were written than announced.
+    Overrun,
+    /// Fewer bytes arrived than announced.
+    Incomplete,
+    /// A flash read or write failed.
+    Flash,
+}
+
+/// An update in progress.
+pub struct Update {
+    plan: UpdatePlan,
+    /// Announced image size.
+    len: usize,
+    /// Bytes written so far.
+    written: usize,
+}
+
+impl Update {
+    /// Start writing a `len`-byte image to the idle app slot.
+    pub async fn begin(len: usize) -> Result<Self, OtaError> {
+        if len > OTA_SLOT_LEN {
+            return Err(OtaError::TooLarge);
+        }
+        let mut entries = [None; 2];
+        for (i, entry) in entries.iter_mut().enumerate() {
+            let mut bytes = [0u8; OTA_SELECT_LEN];
+            nvs::read_record(OTADATA_OFFSET + SECTOR_SIZE * i as u32, &mut bytes)
+                .await
+                .ok_or(OtaError::Flash)?;
+            *entry = OtaSelect::decode(&bytes);
+        }
+        let plan = ota_image::plan_update(&entries);
+        info!("OTA: writing {} bytes to ota_{}", len, plan.slot);
+        Ok(Self {
+            plan,
+            len,
+            written: 0,
+        })
+    }
+
+    /// Write the next `chunk` of the image. The first chunk must hold the
+    /// whole image header, which is checked before anything is written.
+    pub async fn write(&mut self, chunk: &[u8]) -> Result<(), OtaError> {
+        if self.written == 0 {
+            ota_image::check_image_header(chunk).map_err(OtaError::Image)?;
+        }
+        if self.written + chunk.len() > self.len {
+            return Err(OtaError::Overrun);
+        }
+        let offset = OTA_SLOT_OFFSETS[self.plan.slot] + self.written as u32;
+        if !nvs::write_flash(offset, chunk).await {
+            return Err(OtaError::Flash);
+        }
+        let before = self.written / PROGRESS_STEP;
+        self.written += chunk.len();
+        if self.written / PROGRESS_STEP != before || self.written == self.len {
+            info!(
+                "OTA: {}/{} bytes ({}%)",
+                self.written,
+                self.len,
+                self.written * 100 / self.len
+            );
+        }
+        Ok(())
+    }
+
+    /// Boot the new image from the next reset on. Fails, leaving the
+    /// running firmware selected, unless the whole image was written.
+    pub async fn finish(self) -> Result<(), OtaError> {
+        if self.written != self.len {
+            return Err(OtaError::Incomplete);
+        }
+        let offset = OTADATA_OFFSET + SECTOR_SIZE * self.plan.entry as u32;
+        if !nvs::write_flash(offset, &self.plan.select.encode()).await {
+            return Err(OtaError::Flash);
+        }
+        info!("OTA: ota_{} selected for the next boot", self.plan.slot);
+        Ok(())
+    }
+}
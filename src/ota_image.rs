@@ -0,0 +1,220 @@
+//! Over-the-air update bookkeeping.
+//!
+//! The flash holds two app partitions, `ota_0` and `ota_1`, and an
+//! `otadata` partition whose two sectors each hold one [`OtaSelect`]
+//! entry. The ESP-IDF second-stage bootloader boots the app slot picked
+//! by the valid entry with the highest sequence number. An update is
+//! written to the slot that isn't running, and only once it is complete
+//! does [`plan_update`]'s new entry go to `otadata`, over the older of
+//! the two. An upload that fails midway never touches `otadata`, so the
+//! running image stays the one that boots.
+//!
+//! This module checks the image header and encodes the entries; `ota`
+//! does the flash writes.
+
+use crate::crc32::crc32_update;
+
+/// First byte of every ESP app image.
+pub const IMAGE_MAGIC: u8 = 0xE9;
+
+/// `chip_id` of images built for the ESP32-C3.
+pub const ESP32C3_CHIP_ID: u16 = 0x0005;
+
+/// Bytes of the image header [`check_image_header`] needs.
+pub const IMAGE_HEADER_LEN: usize = 24;
+
+/// Most segments the bootloader loads from one image.
+const MAX_SEGMENTS: u8 = 16;
+
+/// Size of each of the `ota_0` and `ota_1` app partitions, and so the
+/// largest image an update can be. See `partitions.csv`.
+pub const OTA_SLOT_LEN: usize = 0x1E_0000;
+
+/// Size of one `otadata` entry.
+pub const OTA_SELECT_LEN: usize = 32;
+
+/// Longest accepted upload token.
+pub const MAX_TOKEN_LEN: usize = 64;
+
+/// Why an uploaded image was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageError {
+    /// Fewer than [`IMAGE_HEADER_LEN`] bytes.
+    TooShort,
+    /// The first byte isn't [`IMAGE_MAGIC`].
+    BadMagic(u8),
+    /// Built for another chip.
+    WrongChip(u16),
+    /// No segments, or more than the bootloader loads.
+    BadSegmentCount(u8),
+}
+
+/// Check that `header` (the start of an upload) is an ESP32-C3 app image.
+pub fn check_image_header(header: &[u8]) -> Result<(), ImageError> {
+    let header = header.get(..IMAGE_HEADER_LEN).ok_or(ImageError::TooShort)?;
+    if header[0] != IMAGE_MAGIC {
+        return Err(ImageError::BadMagic(header[0]));
+    }
+    let segments = header[1];
+    if !(1..=MAX_SEGMENTS).contains(&segments) {
+        return Err(ImageError::BadSegmentCount(segments));
+    }
+    let chip_id = u16::from_le_bytes([header[12], header[13]]);
+    if chip_id != ESP32C3_CHIP_ID {
+        return Err(ImageError::WrongChip(chip_id));
+    }
+    Ok(())
+}
+
+/// One `otadata` entry: boot app slot `(seq - 1) % 2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OtaSelect {
+    pub seq: u32,
+}
+
+impl OtaSelect {
+    /// The entry as stored: `seq`, a blank 20-byte label, an undefined
+    /// image state (so the bootloader doesn't wait for the app to confirm
+    /// itself) and the CRC of `seq`.
+    pub fn encode(&self) -> [u8; OTA_SELECT_LEN] {
+        let mut entry = [0xFF; OTA_SELECT_LEN];
+        entry[..4].copy_from_slice(&self.seq.to_le_bytes());
+        entry[28..].copy_from_slice(&seq_crc(self.seq).to_le_bytes());
+        entry
+    }
+
+    /// Read a stored entry. `None` for an erased sector or a CRC mismatch,
+    /// which the bootloader ignores too.
+    pub fn decode(entry: &[u8; OTA_SELECT_LEN]) -> Option<Self> {
+        let seq = u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]);
+        let crc = u32::from_le_bytes([entry[28], entry[29], entry[30], entry[31]]);
+        (seq != u32::MAX && crc == seq_crc(seq)).then_some(Self { seq })
+    }
+
+    /// App slot this entry boots.
+    pub fn slot(&self) -> usize {
+        (self.seq.wrapping_sub(1) % 2) as usize
+    }
+}
+
+/// Where an update goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpdatePlan {
+    /// App slot to write the image to (0 for `ota_0`).
+    pub slot: usize,
+    /// `otadata` sector to write [`Self::select`] to once it's done.
+    pub entry: usize,
+    /// The entry that boots [`Self::slot`].
+    pub select: OtaSelect,
+}
+
+/// Plan an update from the two current `otadata` entries. With neither
+/// valid the bootloader runs `ota_0`, so the update goes to `ota_1`.
+pub fn plan_update(entries: &[Option<OtaSelect>; 2]) -> UpdatePlan {
+    let current = entries.iter().flatten().max_by_key(|e| e.seq);
+    let running = current.map_or(0, OtaSelect::slot);
+    let slot = 1 - running;
+    let mut seq = current.map_or(0, |e| e.seq) + 1;
+    if (OtaSelect { seq }).slot() != slot {
+        seq += 1;
+    }
+    // Overwrite the invalid or older entry, keeping the running one.
+    let entry = match entries {
+        [Some(a), Some(b)] => usize::from(a.seq > b.seq),
+        [Some(_), None] => 1,
+        _ => 0,
+    };
+    UpdatePlan {
+        slot,
+        entry,
+        select: OtaSelect { seq },
+    }
+}
+
+/// The CRC the bootloader expects for `seq`: the ROM's `crc32_le` seeded
+/// with `u32::MAX`.
+fn seq_crc(seq: u32) -> u32 {
+    !crc32_update(0, &seq.to_le_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A minimal ESP32-C3 image header with three segments.
+    fn header() -> [u8; IMAGE_HEADER_LEN] {
+        let mut header = [0u8; IMAGE_HEADER_LEN];
+        header[0] = IMAGE_MAGIC;
+        header[1] = 3;
+        header[12..14].copy_from_slice(&ESP32C3_CHIP_ID.to_le_bytes());
+        header
+    }
+
+    #[test]
+    fn accepts_esp32c3_images() {
+        assert_eq!(check_image_header(&header()), Ok(()));
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let good = header();
+        assert_eq!(check_image_header(&good[..23]), Err(ImageError::TooShort));
+        let mut bad = good;
+        bad[0] = 0x7F;
+        assert_eq!(check_image_header(&bad), Err(ImageError::BadMagic(0x7F)));
+        let mut bad = good;
+        bad[12] = 0; // ESP32
+        assert_eq!(check_image_header(&bad), Err(ImageError::WrongChip(0)));
+        for segments in [0, 17] {
+            let mut bad = good;
+            bad[1] = segments;
+            assert_eq!(
+                check_image_header(&bad),
+                Err(ImageError::BadSegmentCount(segments))
+            );
+        }
+    }
+
+    #[test]
+    fn entry_matches_the_bootloader_format() {
+        let entry = OtaSelect { seq: 1 }.encode();
+        assert_eq!(entry[..4], [1, 0, 0, 0]);
+        assert!(entry[4..28].iter().all(|&b| b == 0xFF));
+        // As written by ESP-IDF's `esp_ota_set_boot_partition`.
+        assert_eq!(entry[28..], 0x4743_989Au32.to_le_bytes());
+        assert_eq!(OtaSelect::decode(&entry), Some(OtaSelect { seq: 1 }));
+    }
+
+    #[test]
+    fn erased_or_corrupt_entries_are_invalid() {
+        assert_eq!(OtaSelect::decode(&[0xFF; OTA_SELECT_LEN]), None);
+        let mut entry = OtaSelect { seq: 7 }.encode();
+        entry[0] = 8;
+        assert_eq!(OtaSelect::decode(&entry), None);
+    }
+
+    #[test]
+    fn first_update_goes_to_ota_1() {
+        let plan = plan_update(&[None, None]);
+        assert_eq!(plan.slot, 1);
+        assert_eq!(plan.select.slot(), 1);
+        assert_eq!(plan.entry, 0);
+    }
+
+    #[test]
+    fn updates_alternate_slots_and_keep_the_running_entry() {
+        let mut entries = [None, None];
+        let mut slots = [0; 4];
+        for slot in &mut slots {
+            let plan = plan_update(&entries);
+            let running = entries.iter().flatten().max_by_key(|e| e.seq).copied();
+            if running.is_some() {
+                assert_ne!(entries[plan.entry], running);
+            }
+            entries[plan.entry] = Some(plan.select);
+            *slot = plan.slot;
+        }
+        assert_eq!(slots, [1, 0, 1, 0]);
+        assert_eq!(plan_update(&entries).select.seq, 6);
+    }
+}
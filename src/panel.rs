@@ -0,0 +1,840 @@
+//! Panel control protocol, independent of how the pins are driven.
+//!
+//! Implements the protocol from the [Helsinki Hacklab documentation][wiki]
+//! on top of the [`Pins`] trait: the firmware's `led_matrix` drives the
+//! ESP32-C3 output registers, and [`simulator::MockPins`] (tests, or the
+//! `simulator` feature) decodes the same pin traffic back into the
+//! picture the panel would show, so the whole output path runs on the
+//! host.
+//!
+//! [wiki]: https://wiki.helsinki.hacklab.fi/Ledimatriisin_ohjaaminen
+//! [`simulator::MockPins`]: crate::simulator::MockPins
+//!
+//! ## Control Signals
+//! - GCLK: Multiplex clock (~1 MHz, 256 pulses per scanline, plus a 257th
+//!   pulse with longer high/low "dead time" before the next scanline)
+//! - DCLK: Data clock for shift register
+//! - LE: Latch Enable (combined with DCLK for commands — see "Commands" below)
+//! - A0-A3: Scanline address (0-10)
+//! - DR1,DG1,DB1: RGB data chain 1
+//! - DR2,DG2,DB2: RGB data chain 2
+//!
+//! ## Commands (sent via LE + N DCLK pulses)
+//! Raising LE and pulsing DCLK N times issues a command. The number of
+//! pulses is the command code:
+//!
+//! | N  | Command                                |
+//! |----|----------------------------------------|
+//! | 1  | Data Latch (strobe shift register)     |
+//! | 2  | VSYNC (swap display buffers)           |
+//! | 4  | Write Configuration1 register          |
+//! | 10 | Reset                                  |
+//! | 14 | Pre-Active (enable configuration write)|
+//!
+//! When LE is low, DCLK shifts the data lines into the shift register chain.
+//!
+//! ## Per frame ([`Panel::send_frame`])
+//! 1. The panel's [`PixelMapper`] ([`HacklabMapper`] unless built with
+//!    [`Panel::with_mapper`]) translates the linear 88x88 bitmap into the
+//!    44 pixels-per-cycle ordering the chain hardware expects.
+//! 2. [`GammaTable::correct_chain`] maps each stored channel value to its
+//!    perceptually-corrected PWM value, then [`gamma::scale_chain`]
+//!    applies the global brightness.
+//! 3. [`bit_stream::chain_data_bits`] converts the chain data to MSB-first
+//!    bit patterns for the shift register.
+//! 4. Each bit goes out on the data lines with a DCLK, interleaving GCLK
+//!    so the previous frame stays on screen.
+//! 5. VSYNC swaps the new frame in at the next scanline 10 -> 0 wrap
+//!    ([`Multiplexer`] tracks the position).
+//!
+//! Configuration is sent once by [`Panel::init`] via
+//! [`bit_stream::config_bits`].
+
+use core::sync::atomic::{Ordering, compiler_fence};
+
+use crate::bit_stream::{self, ChainBit, DCLK_MIN_PULSE_NS, PWM_BITS};
+use crate::chain_mapper::{self, CHAIN_LEN, HacklabMapper, ICS_PER_CHAIN, PixelMapper, SCANLINES};
+use crate::frame_buffer::{Pixel, StoredPixel};
+use crate::gamma::{self, DEFAULT_GAMMA, GammaTable, MAX_BRIGHTNESS};
+use crate::multiplex::{GCLK_PULSES_PER_SCANLINE, Multiplexer};
+use crate::transition::Transition;
+use crate::{MATRIX_HEIGHT, MATRIX_WIDTH};
+
+/// A whole picture as a frame buffer stores it, indexed `[y][x]`.
+pub type Frame = [[StoredPixel; MATRIX_WIDTH]; MATRIX_HEIGHT];
+
+/// GPIO number of GCLK on the ESP32-C3 SuperMini.
+pub const GCLK_PIN: u8 = 0;
+
+/// GPIO number of DCLK.
+pub const DCLK_PIN: u8 = 1;
+
+/// GPIO number of LE.
+pub const LE_PIN: u8 = 2;
+
+/// GPIO numbers of A0..A3, lowest address bit first.
+pub const ADDRESS_PINS: [u8; 4] = [3, 4, 5, 6];
+
+/// GPIO numbers of DR1, DG1, DB1, DR2, DG2 and DB2.
+pub const DATA_PINS: [u8; 6] = [7, 8, 9, 10, 20, 21];
+
+/// Every pin the panel uses, as a GPIO bit mask.
+const ALL_PINS: u32 = {
+    let mut mask = 1 << GCLK_PIN | 1 << DCLK_PIN | 1 << LE_PIN;
+    let mut i = 0;
+    while i < ADDRESS_PINS.len() {
+        mask |= 1 << ADDRESS_PINS[i];
+        i += 1;
+    }
+    let mut i = 0;
+    while i < DATA_PINS.len() {
+        mask |= 1 << DATA_PINS[i];
+        i += 1;
+    }
+    mask
+};
+
+/// Signal name and GPIO number of every pin, in the order the
+/// firmware's `LedMatrix::new` takes them.
+pub const PIN_MAP: [(&str, u8); 13] = [
+    ("GCLK", GCLK_PIN),
+    ("DCLK", DCLK_PIN),
+    ("LE", LE_PIN),
+    ("A0", ADDRESS_PINS[0]),
+    ("A1", ADDRESS_PINS[1]),
+    ("A2", ADDRESS_PINS[2]),
+    ("A3", ADDRESS_PINS[3]),
+    ("DR1", DATA_PINS[0]),
+    ("DG1", DATA_PINS[1]),
+    ("DB1", DATA_PINS[2]),
+    ("DR2", DATA_PINS[3]),
+    ("DG2", DATA_PINS[4]),
+    ("DB2", DATA_PINS[5]),
+];
+
+/// Why an ESP32-C3 GPIO is a risky place for a panel signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinCaveat {
+    /// GPIO2, GPIO8 or GPIO9: their level at reset picks the boot mode.
+    /// The panel's pull-ups hold them high, which is the normal boot.
+    Strapping,
+    /// GPIO20/GPIO21, UART0: serial output shows up on the line.
+    Uart,
+    /// GPIO18/GPIO19, the USB port's D-/D+: using them takes away USB
+    /// flashing and logging.
+    Usb,
+    /// GPIO12–GPIO17, wired to the SPI flash: driving them crashes the
+    /// chip.
+    Flash,
+    /// Past GPIO21: the ESP32-C3 has no such pin.
+    Missing,
+}
+
+impl PinCaveat {
+    /// The caveat of `gpio`, `None` for a plain GPIO.
+    pub const fn of(gpio: u8) -> Option<Self> {
+        match gpio {
+            2 | 8 | 9 => Some(Self::Strapping),
+            12..=17 => Some(Self::Flash),
+            18 | 19 => Some(Self::Usb),
+            20 | 21 => Some(Self::Uart),
+            0..=21 => None,
+            _ => Some(Self::Missing),
+        }
+    }
+
+    /// True if the panel works fine on such a pin, as on the SuperMini
+    /// wiring; the rest break the panel or the chip.
+    pub const fn is_benign(self) -> bool {
+        matches!(self, Self::Strapping | Self::Uart)
+    }
+}
+
+/// A signal on a GPIO that can't carry it, from [`check_pins`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PinError {
+    pub signal: &'static str,
+    pub gpio: u8,
+    pub caveat: PinCaveat,
+    /// Plain GPIOs no signal uses yet, as a bit mask.
+    free: u32,
+}
+
+impl PinError {
+    /// Plain GPIOs no other signal uses, lowest first: where the signal
+    /// could move instead.
+    pub fn alternatives(&self) -> impl Iterator<Item = u8> {
+        let free = self.free;
+        (0..32).filter(move |gpio| free & 1 << gpio != 0)
+    }
+}
+
+/// Check that no signal in `map` is on a pin that breaks the panel or
+/// the chip, i.e. whose [`PinCaveat`] isn't benign. `const`, so
+/// [`PIN_MAP`] is checked at compile time: moving a signal onto a flash
+/// or USB pin, or one the ESP32-C3 doesn't have, fails the build.
+pub const fn check_pins(map: &[(&'static str, u8)]) -> Result<(), PinError> {
+    let mut used = 0u32;
+    let mut i = 0;
+    while i < map.len() {
+        if map[i].1 < 32 {
+            used |= 1 << map[i].1;
+        }
+        i += 1;
+    }
+    let mut free = 0u32;
+    let mut gpio = 0;
+    while gpio < 32 {
+        if PinCaveat::of(gpio).is_none() && used & 1 << gpio == 0 {
+            free |= 1 << gpio;
+        }
+        gpio += 1;
+    }
+    let mut i = 0;
+    while i < map.len() {
+        let (signal, gpio) = map[i];
+        if let Some(caveat) = PinCaveat::of(gpio)
+            && !caveat.is_benign()
+        {
+            return Err(PinError {
+                signal,
+                gpio,
+                caveat,
+                free,
+            });
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+const _: () = assert!(
+    check_pins(&PIN_MAP).is_ok(),
+    "a panel signal is on a GPIO that can't drive it, see PinCaveat"
+);
+
+/// Highest [`ConfigRegister::current_gain`]: the field is 6 bits wide.
+pub const MAX_CURRENT_GAIN: u8 = 0x3F;
+
+/// The Configuration1 register, per the wiki. [`Self::bits`] composes
+/// it:
+///
+/// | Bits  | Field                                                    |
+/// |-------|----------------------------------------------------------|
+/// | 0-5   | current gain, [`Self::current_gain`]                     |
+/// | 6     | GCLK multiplier, [`Self::gclk_multiplier`]               |
+/// | 7     | PWM depth: 0 for 16-bit, 1 for 13-bit; always 16-bit     |
+/// | 8-12  | scanlines minus one; always [`SCANLINES`] - 1            |
+/// | 13-15 | unused, 0                                                |
+///
+/// The PWM depth and scanline count are fixed by how the driver shifts
+/// and multiplexes frames, so they aren't fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfigRegister {
+    /// Output current of every LED driver, 0..=[`MAX_CURRENT_GAIN`]:
+    /// higher is brighter at full PWM, and draws more from the supply.
+    /// Lower it if the supply browns out on bright frames; unlike
+    /// brightness, it costs no PWM levels.
+    pub current_gain: u8,
+    /// Multiply GCLK in the driver ICs, so the 256 pulses per scanline
+    /// clock all 16 PWM bits.
+    pub gclk_multiplier: bool,
+}
+
+impl ConfigRegister {
+    /// The wiki's settings, `0x0A45`: current gain 5 (room lighting),
+    /// GCLK multiplier on. The Teensy reference design uses current gain
+    /// 11 (`0x0A4B`).
+    pub const DEFAULT: Self = Self {
+        current_gain: 5,
+        gclk_multiplier: true,
+    };
+
+    /// `self` with `gain` as its current gain, or `None` if it is above
+    /// [`MAX_CURRENT_GAIN`].
+    pub const fn with_current_gain(self, gain: u8) -> Option<Self> {
+        if gain > MAX_CURRENT_GAIN {
+            return None;
+        }
+        Some(Self {
+            current_gain: gain,
+            ..self
+        })
+    }
+
+    /// The register value, laid out as in the table above. A gain above
+    /// [`MAX_CURRENT_GAIN`] is cut to its low 6 bits.
+    pub const fn bits(self) -> u16 {
+        ((SCANLINES as u16 - 1) << 8)
+            | ((self.gclk_multiplier as u16) << 6)
+            | (self.current_gain & MAX_CURRENT_GAIN) as u16
+    }
+}
+
+impl Default for ConfigRegister {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Configuration1 as [`Panel::init`] first sends it.
+pub const CONFIG_REGISTER_1: u16 = ConfigRegister::DEFAULT.bits();
+
+/// Dead time on the 257th GCLK pulse (the wiki says longer delays are
+/// *required* there — MBI5252 datasheet parameters `tdth` and `tdtl` are
+/// minimums in the low-microsecond range, so 5 µs on each phase gives a
+/// 10 µs period, ~10× the normal pulse width).
+const GCLK_DEAD_TIME_US: u32 = 5;
+
+/// CPU clock in MHz, as set by `CpuClock::max()` in `main`. Converts
+/// the DCLK pulse width into cycles.
+const CPU_MHZ: u32 = 160;
+
+/// Commands sent via LE + DCLK pulses.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Latch the shifted data. Sent as the last bit of each chain.
+    DataLatch = 1,
+    /// Swap display buffers (front <-> back). Must be issued at the
+    /// scanline 10 -> 0 transition.
+    Vsync = 2,
+    /// Store the last 16 shifted bits in Configuration1. Sent as the
+    /// last bits of the config shift.
+    WriteConfig = 4,
+    /// Reset the display.
+    Reset = 10,
+    /// Pre-Active — enables writes to Configuration1.
+    PreActive = 14,
+}
+
+impl Command {
+    /// The command `pulses` DCLKs with LE high issue, if any.
+    pub fn from_pulses(pulses: usize) -> Option<Self> {
+        match pulses {
+            1 => Some(Self::DataLatch),
+            2 => Some(Self::Vsync),
+            4 => Some(Self::WriteConfig),
+            10 => Some(Self::Reset),
+            14 => Some(Self::PreActive),
+            _ => None,
+        }
+    }
+}
+
+/// How the panel's pins are driven.
+pub trait Pins {
+    /// Drive the `high` pins high and the `low` pins low, each a bit
+    /// mask by GPIO number. Pins in neither keep their level.
+    fn write(&mut self, high: u32, low: u32);
+
+    /// Busy-wait at least `cycles` CPU cycles.
+    fn wait_cycles(&mut self, cycles: u32);
+
+    /// Wait at least `us` microseconds.
+    fn delay_us(&mut self, us: u32);
+}
+
+/// The panel, through its pins: frames in, GPIO traffic out. `M` says
+/// where each pixel sits in the chains.
+pub struct Panel<P: Pins, M: PixelMapper = HacklabMapper> {
+    pins: P,
+
+    /// Frame-buffer pixel of each chain slot.
+    mapper: M,
+
+    /// Gamma curve applied to every channel on the way out.
+    gamma: GammaTable,
+
+    /// Global brightness, 0 (blank) ..= 255 (full). Applied on output so
+    /// the frame buffer keeps full-precision values.
+    brightness: u8,
+
+    /// Busy-wait after each DCLK edge, in CPU cycles. See
+    /// [`Panel::set_dclk_delay_ns`].
+    dclk_delay_cycles: u32,
+
+    /// Where GCLK is in the multiplex cycle. It keeps running across
+    /// frames so every scanline gets its full 256 pulses.
+    mux: Multiplexer,
+
+    /// Configuration1 as last sent, or to be sent by [`Panel::init`].
+    config: ConfigRegister,
+}
+
+impl<P: Pins> Panel<P> {
+    /// A Hacklab panel on `pins`. Call [`Self::init`] before sending
+    /// frames.
+    pub fn new(pins: P) -> Self {
+        Self::with_mapper(pins, HacklabMapper)
+    }
+}
+
+impl<P: Pins, M: PixelMapper> Panel<P, M> {
+    /// A panel on `pins` wired as `mapper` says, for panels whose ICs
+    /// don't follow the Hacklab layout.
+    pub fn with_mapper(pins: P, mapper: M) -> Self {
+        Self {
+            pins,
+            mapper,
+            gamma: GammaTable::new(DEFAULT_GAMMA),
+            brightness: MAX_BRIGHTNESS,
+            dclk_delay_cycles: bit_stream::delay_cycles(DCLK_MIN_PULSE_NS, CPU_MHZ),
+            mux: Multiplexer::new(),
+            config: ConfigRegister::DEFAULT,
+        }
+    }
+
+    /// The pins, e.g. to inspect a [`MockPins`](crate::simulator::MockPins).
+    pub fn pins(&self) -> &P {
+        &self.pins
+    }
+
+    /// Rebuild the output gamma curve. `1.0` disables correction; the
+    /// default is [`DEFAULT_GAMMA`].
+    pub fn set_gamma(&mut self, gamma: f32) {
+        self.gamma = GammaTable::new(gamma);
+    }
+
+    /// Set the global brightness: 255 = full, 0 = blank. Scales every
+    /// channel on output.
+    pub fn set_brightness(&mut self, level: u8) {
+        self.brightness = level;
+    }
+
+    /// The global brightness set by [`Self::set_brightness`].
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    /// Hold DCLK high, then low, for at least `ns` nanoseconds each
+    /// phase. Defaults to the driver ICs' 10 ns minimum; raise it if long
+    /// or noisy wiring garbles the picture. The GPIO writes themselves
+    /// take a few cycles, so small values mostly don't slow the clock.
+    pub fn set_dclk_delay_ns(&mut self, ns: u32) {
+        self.dclk_delay_cycles = bit_stream::delay_cycles(ns, CPU_MHZ);
+    }
+
+    /// Initialize the display with configuration.
+    pub fn init(&mut self) {
+        self.pins.write(0, ALL_PINS);
+
+        // Wait for power stabilization.
+        self.pins.delay_us(100_000);
+
+        self.configure();
+    }
+
+    /// Send `config` to the driver ICs and reset them so it takes
+    /// effect. The panel goes dark for the reset: send the frame again
+    /// afterwards.
+    pub fn write_config(&mut self, config: ConfigRegister) {
+        self.config = config;
+        self.configure();
+    }
+
+    /// [`Self::write_config`] with the current gain changed to `gain`.
+    /// `false`, and nothing sent, if `gain` is above
+    /// [`MAX_CURRENT_GAIN`].
+    pub fn set_current_gain(&mut self, gain: u8) -> bool {
+        match self.config.with_current_gain(gain) {
+            Some(config) => {
+                self.write_config(config);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Configuration1 as last sent.
+    pub fn config(&self) -> ConfigRegister {
+        self.config
+    }
+
+    /// Send [`Self::config`], then reset.
+    fn configure(&mut self) {
+        self.send_config(self.config.bits());
+
+        // Reset after config so the new register values take effect.
+        // (Some reference designs do it the other way around — the Hacklab
+        // panel reportedly tolerates both.)
+        self.send_command(Command::Reset);
+        self.pins.delay_us(10_000);
+    }
+
+    /// Send one frame and swap it in.
+    ///
+    /// Per the wiki, the panel's double buffering lets the two halves of
+    /// the work overlap:
+    /// 1. Shift `front` into the display's back buffer — mixed towards
+    ///    the `mix` frame by its transition and progress, if given. Every
+    ///    DCLK is followed by one GCLK, so the display keeps multiplexing
+    ///    the previous frame the whole time — ~22 multiplex cycles per
+    ///    shifted frame — instead of going dark while data loads.
+    /// 2. Keep clocking GCLK until the scanline-10 -> 0 wrap, and issue
+    ///    VSYNC there, once per frame, so the display swaps to the back
+    ///    buffer exactly at a frame boundary.
+    ///
+    /// The only waits are the datasheet's dead time on each scanline's
+    /// 257th GCLK pulse and the DCLK pulse width.
+    pub fn send_frame(&mut self, front: &Frame, mix: Option<(&Frame, Transition, u8)>) {
+        let mut data = [[0u16; 3]; CHAIN_LEN];
+        let mut target = [[0u16; 3]; CHAIN_LEN];
+        for scanline in 0..SCANLINES {
+            for led in 0..PWM_BITS {
+                chain_mapper::map_chain_data(&self.mapper, scanline, led, front, &mut data);
+                if let Some((to, transition, progress)) = mix {
+                    chain_mapper::map_chain_data(&self.mapper, scanline, led, to, &mut target);
+                    for (slot, (from, &to)) in data.iter_mut().zip(&target).enumerate() {
+                        let (chain, ic) = (slot / ICS_PER_CHAIN, slot % ICS_PER_CHAIN);
+                        let (x, _) = self.mapper.pixel(scanline, led, chain, ic);
+                        *from = transition.mix(*from, to, x, progress);
+                    }
+                }
+                self.write_cycle(&mut data);
+            }
+        }
+        self.swap_frame();
+    }
+
+    /// [`Self::send_frame`] for a picture with no frame buffer behind
+    /// it: `pixel(x, y)` is asked for each pixel as it goes out. Takes no
+    /// memory beyond one cycle's chain data, for the panic handler's
+    /// [`crash_screen`](crate::crash_screen).
+    pub fn send_pattern(&mut self, pixel: impl Fn(usize, usize) -> Pixel) {
+        let mut data = [[0u16; 3]; CHAIN_LEN];
+        for scanline in 0..SCANLINES {
+            for led in 0..PWM_BITS {
+                for (slot, value) in data.iter_mut().enumerate() {
+                    let (chain, ic) = (slot / ICS_PER_CHAIN, slot % ICS_PER_CHAIN);
+                    let (x, y) = self.mapper.pixel(scanline, led, chain, ic);
+                    *value = pixel(x, y);
+                }
+                self.write_cycle(&mut data);
+            }
+        }
+        self.swap_frame();
+    }
+
+    /// Gamma-correct and dim one cycle's chain data, then shift it out.
+    fn write_cycle(&mut self, data: &mut [[u16; 3]; CHAIN_LEN]) {
+        self.gamma.correct_chain(data);
+        gamma::scale_chain(data, self.brightness);
+        self.write_chain(data);
+    }
+
+    /// Clock on to the next frame boundary and VSYNC the frame just
+    /// shifted in.
+    fn swap_frame(&mut self) {
+        self.mux.request_vsync();
+        while self.mux.vsync_pending() {
+            self.tick_gclk();
+        }
+    }
+
+    /// Keep showing the frame last sent, for about as long as one
+    /// multiplex cycle: GCLK only, no data and no VSYNC. Use it instead
+    /// of [`Self::send_frame`] when nothing changed; the panel must not
+    /// go without GCLK. See [`crate::dirty`] for why nothing smaller than
+    /// a whole frame can be skipped.
+    pub fn hold_frame(&mut self) {
+        for _ in 0..SCANLINES as u32 * GCLK_PULSES_PER_SCANLINE {
+            self.tick_gclk();
+        }
+    }
+
+    /// Send a command to the display via LE + DCLK.
+    fn send_command(&mut self, cmd: Command) {
+        self.pins.write(1 << LE_PIN, 0);
+        for _ in 0..cmd as u8 {
+            self.pulse_dclk();
+        }
+        self.pins.write(0, 1 << LE_PIN);
+    }
+
+    /// Send the Configuration1 register to all driver ICs.
+    ///
+    /// Per the wiki (`Ledimatriisin ohjaaminen > Ohjainpiirin konfigurointi`):
+    /// 1. Send the Pre-Active command (N=14, LE high for 14 DCLKs)
+    /// 2. Send the 16-bit Configuration1 value
+    /// 3. Send the WriteConfig command (N=4)
+    ///
+    /// Steps 2 and 3 are combined by holding LE high during the last 4
+    /// DCLKs of the 16-bit shift. The 16-bit value is broadcast 22 times
+    /// (352 DCLKs total) so it lands in the shift register of every IC.
+    fn send_config(&mut self, config: u16) {
+        self.send_command(Command::PreActive);
+        for bit in bit_stream::config_bits(config) {
+            self.shift_one_bit(bit);
+        }
+        self.pins.write(0, 1 << LE_PIN);
+    }
+
+    /// Send one regular GCLK pulse. After a scanline's 256th, finish it:
+    /// VSYNC if one is due at this frame boundary, the 257th dead-time
+    /// pulse, then address the next scanline.
+    #[inline]
+    fn tick_gclk(&mut self) {
+        self.pins.write(1 << GCLK_PIN, 0);
+        compiler_fence(Ordering::SeqCst);
+        self.pins.write(0, 1 << GCLK_PIN);
+        compiler_fence(Ordering::SeqCst);
+
+        let Some(end) = self.mux.pulse() else {
+            return;
+        };
+        if end.vsync {
+            self.send_command(Command::Vsync);
+        }
+        // 257th GCLK pulse: longer high/low phase than the regular
+        // 256. MBI5252 datasheet's tdth/tdtl are microsecond minimums.
+        self.pins.write(1 << GCLK_PIN, 0);
+        self.pins.delay_us(GCLK_DEAD_TIME_US);
+        self.pins.write(0, 1 << GCLK_PIN);
+        self.pins.delay_us(GCLK_DEAD_TIME_US);
+        self.set_scanline(end.next_scanline);
+    }
+
+    /// Shift 22 × 16 = 352 DCLKs for one `(scanline, led)` cycle.
+    ///
+    /// Bits shift MSB-first. On the very last DCLK of the very last IC, LE
+    /// is raised to issue the Data Latch command (N=1). LE is dropped
+    /// immediately after the loops finish. A GCLK pulse follows every
+    /// DCLK so the display keeps multiplexing meanwhile.
+    fn write_chain(&mut self, data: &[[u16; 3]; CHAIN_LEN]) {
+        // Drop LE before clocking — the latch at the end of the previous
+        // cycle, if any, would have left it high.
+        self.pins.write(0, 1 << LE_PIN);
+
+        for bit in bit_stream::chain_data_bits(data) {
+            self.shift_one_bit(bit);
+            self.tick_gclk();
+        }
+
+        self.pins.write(0, 1 << LE_PIN);
+    }
+
+    /// Apply one [`ChainBit`] (data lines + optional LE) and pulse DCLK.
+    /// All six data lines change together: one write sets the high ones
+    /// (and LE), one clears the low ones.
+    #[inline]
+    fn shift_one_bit(&mut self, bit: ChainBit) {
+        let (high, low) = bit.gpio_masks(&DATA_PINS, LE_PIN);
+        self.pins.write(high, low);
+        self.pulse_dclk();
+    }
+
+    /// Generate a single DCLK pulse, each phase at least
+    /// `dclk_delay_cycles` long.
+    #[inline(always)]
+    fn pulse_dclk(&mut self) {
+        self.pins.write(1 << DCLK_PIN, 0);
+        self.pins.wait_cycles(self.dclk_delay_cycles);
+        compiler_fence(Ordering::SeqCst);
+        self.pins.write(0, 1 << DCLK_PIN);
+        self.pins.wait_cycles(self.dclk_delay_cycles);
+        compiler_fence(Ordering::SeqCst);
+    }
+
+    /// Set scanline address (0-10) on the four A pins.
+    fn set_scanline(&mut self, scanline: usize) {
+        let (mut high, mut low) = (0, 0);
+        for (bit, &pin) in ADDRESS_PINS.iter().enumerate() {
+            if scanline >> bit & 1 != 0 {
+                high |= 1 << pin;
+            } else {
+                low |= 1 << pin;
+            }
+        }
+        self.pins.write(high, low);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame_buffer::FrameBuffer;
+    use crate::simulator::MockPins;
+    use crate::transition::TransitionKind;
+
+    /// An initialized panel on simulated pins, without gamma correction
+    /// so what goes in comes out.
+    fn panel() -> Panel<MockPins> {
+        let mut panel = Panel::new(MockPins::new());
+        panel.init();
+        panel.set_gamma(1.0);
+        panel
+    }
+
+    #[test]
+    fn pin_map_names_every_pin_once() {
+        let mask = PIN_MAP
+            .iter()
+            .fold(0u32, |mask, &(_, gpio)| mask | 1 << gpio);
+        assert_eq!(mask, ALL_PINS);
+        assert_eq!(mask.count_ones() as usize, PIN_MAP.len());
+        assert_eq!(PIN_MAP[8], ("DG1", 8));
+    }
+
+    #[test]
+    fn supermini_wiring_has_only_benign_caveats() {
+        for &(_, gpio) in &PIN_MAP {
+            assert!(PinCaveat::of(gpio).is_none_or(PinCaveat::is_benign));
+        }
+        assert_eq!(PinCaveat::of(LE_PIN), Some(PinCaveat::Strapping));
+        assert_eq!(PinCaveat::of(14), Some(PinCaveat::Flash));
+        assert_eq!(PinCaveat::of(34), Some(PinCaveat::Missing));
+    }
+
+    #[test]
+    fn bad_pins_are_named_with_free_alternatives() {
+        assert_eq!(check_pins(&PIN_MAP), Ok(()));
+        let mut map = PIN_MAP;
+        map[12] = ("DB2", 14);
+        let err = check_pins(&map).unwrap_err();
+        assert_eq!((err.signal, err.gpio), ("DB2", 14));
+        assert_eq!(err.caveat, PinCaveat::Flash);
+        assert!(err.alternatives().eq([11]));
+
+        map[12] = ("DB2", 36);
+        map[3] = ("A0", 19);
+        let err = check_pins(&map).unwrap_err();
+        assert_eq!((err.signal, err.caveat), ("A0", PinCaveat::Usb));
+        assert!(err.alternatives().eq([3, 11]));
+    }
+
+    #[test]
+    fn init_configures_and_resets() {
+        let pins = *panel().pins().counts();
+        assert_eq!(pins.config, Some(CONFIG_REGISTER_1));
+        assert_eq!(pins.resets, 1);
+        assert_eq!(pins.latches, 0);
+    }
+
+    #[test]
+    fn config_register_fields_compose() {
+        assert_eq!(CONFIG_REGISTER_1, 0x0A45);
+        let teensy = ConfigRegister::DEFAULT.with_current_gain(11).unwrap();
+        assert_eq!(teensy.bits(), 0x0A4B);
+        assert_eq!(ConfigRegister::DEFAULT.with_current_gain(64), None);
+
+        let mut panel = panel();
+        assert!(panel.set_current_gain(MAX_CURRENT_GAIN));
+        assert_eq!(panel.pins().counts().config, Some(0x0A7F));
+        assert_eq!(panel.pins().counts().resets, 2);
+        assert!(!panel.set_current_gain(MAX_CURRENT_GAIN + 1));
+        assert_eq!(panel.config().current_gain, MAX_CURRENT_GAIN);
+
+        panel.write_config(ConfigRegister {
+            current_gain: 0,
+            gclk_multiplier: false,
+        });
+        assert_eq!(panel.pins().counts().config, Some(0x0A00));
+    }
+
+    #[test]
+    fn frame_shows_up_after_vsync() {
+        let mut panel = panel();
+        let mut buffer = FrameBuffer::new();
+        buffer.fill(0, 0, 0x4000);
+        buffer.set_pixel_16(3, 80, 0xFFFF, 0x1234, 0);
+        panel.send_frame(buffer.as_pixels(), None);
+
+        let shown = panel.pins().shown();
+        for (y, row) in shown.iter().enumerate() {
+            for (x, &px) in row.iter().enumerate() {
+                assert_eq!(px, buffer.get_pixel(x, y), "({x}, {y})");
+            }
+        }
+        let counts = panel.pins().counts();
+        assert_eq!(counts.latches, SCANLINES * PWM_BITS);
+        assert_eq!(counts.vsyncs, 1);
+        assert_eq!(counts.vsync_scanline, Some(SCANLINES - 1));
+    }
+
+    #[test]
+    fn held_frame_stays_on_screen() {
+        let mut panel = panel();
+        let mut buffer = FrameBuffer::new();
+        buffer.fill(0x1000, 0, 0x2000);
+        panel.send_frame(buffer.as_pixels(), None);
+        let sent = *panel.pins().counts();
+
+        panel.hold_frame();
+        let held = panel.pins().counts();
+        assert_eq!(held.latches, sent.latches);
+        assert_eq!(held.vsyncs, sent.vsyncs);
+        let pulses = SCANLINES as u64 * (u64::from(GCLK_PULSES_PER_SCANLINE) + 1);
+        assert_eq!(held.gclk_pulses - sent.gclk_pulses, pulses);
+        assert_eq!(panel.pins().shown()[40][40], buffer.get_pixel(40, 40));
+    }
+
+    #[test]
+    fn brightness_and_gamma_scale_the_output() {
+        let mut panel = panel();
+        let mut buffer = FrameBuffer::new();
+        buffer.fill(0xFFFF, 0x8080, 0);
+        panel.set_brightness(100);
+        panel.send_frame(buffer.as_pixels(), None);
+        let dimmed = [gamma::scale(0xFFFF, 100), gamma::scale(0x8080, 100), 0];
+        assert_eq!(panel.pins().shown()[10][10], dimmed);
+
+        panel.set_brightness(MAX_BRIGHTNESS);
+        panel.set_gamma(DEFAULT_GAMMA);
+        panel.send_frame(buffer.as_pixels(), None);
+        let [r, g, _] = panel.pins().shown()[10][10];
+        assert_eq!(r, 0xFFFF);
+        assert!(g < 0x4000, "gamma darkens midtones: {g:#x}");
+    }
+
+    #[test]
+    fn transition_mixes_the_two_frames() {
+        let mut panel = panel();
+        let (mut from, mut to) = (FrameBuffer::new(), FrameBuffer::new());
+        from.fill(0xFFFF, 0, 0);
+        to.fill(0, 0, 0xFFFF);
+        let wipe = Transition::new(TransitionKind::Wipe, 500);
+        panel.send_frame(from.as_pixels(), Some((to.as_pixels(), wipe, 128)));
+        let shown = panel.pins().shown();
+        assert_eq!(shown[40][0], [0, 0, 0xFFFF]);
+        assert_eq!(shown[40][MATRIX_WIDTH - 1], [0xFFFF, 0, 0]);
+    }
+
+    #[test]
+    fn custom_mapper_places_the_pixels() {
+        // A panel wired mirror-image to the Hacklab one.
+        let mirrored = |scanline, led, chain, ic| {
+            let (x, y) = HacklabMapper.pixel(scanline, led, chain, ic);
+            (MATRIX_WIDTH - 1 - x, y)
+        };
+        let mut panel = Panel::with_mapper(MockPins::new(), mirrored);
+        panel.init();
+        panel.set_gamma(1.0);
+        let mut buffer = FrameBuffer::new();
+        buffer.set_pixel_16(3, 80, 0xFFFF, 0x1234, 0);
+        panel.send_frame(buffer.as_pixels(), None);
+
+        // The simulator decodes Hacklab wiring, so it sees the mirror.
+        let shown = panel.pins().shown();
+        assert_eq!(shown[80][MATRIX_WIDTH - 1 - 3], buffer.get_pixel(3, 80));
+        assert_eq!(shown[80][3], [0; 3]);
+    }
+
+    #[test]
+    fn pattern_shows_like_a_frame() {
+        let pattern = |x: usize, y: usize| [(x * 0x101) as u16, (y * 0x101) as u16, 0x1212];
+        let mut buffer = FrameBuffer::new();
+        for y in 0..MATRIX_HEIGHT {
+            for x in 0..MATRIX_WIDTH {
+                let [r, g, b] = pattern(x, y);
+                buffer.set_pixel_16(x as i32, y as i32, r, g, b);
+            }
+        }
+        let mut by_frame = panel();
+        by_frame.send_frame(buffer.as_pixels(), None);
+        let mut by_pattern = panel();
+        by_pattern.send_pattern(pattern);
+        assert_eq!(by_pattern.pins().shown(), by_frame.pins().shown());
+        assert_eq!(by_pattern.pins().counts().vsyncs, 1);
+    }
+}
@@ -0,0 +1,83 @@
+//! Request rate limiting for the drawing endpoints.
+//!
+//! Every display change goes through the refresh loop's queues and
+//! mutexes, so a client firing `/pixel` in a tight loop can keep the
+//! loop waiting on them. [`RateLimiter`] counts requests in fixed
+//! one-second windows and refuses the ones past the limit; the HTTP
+//! layer answers those with `429 Too Many Requests`.
+
+/// Display-changing requests allowed per second by default.
+pub const DEFAULT_REQUESTS_PER_SEC: u32 = 20;
+
+/// Length of one counting window.
+const WINDOW_MS: u64 = 1000;
+
+/// Fixed-window request counter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimiter {
+    limit: u32,
+    window_start_ms: u64,
+    count: u32,
+}
+
+impl RateLimiter {
+    /// Allow up to `limit` requests per second.
+    pub const fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            window_start_ms: 0,
+            count: 0,
+        }
+    }
+
+    /// Count a request at `now_ms` (any monotonic clock, e.g. time since
+    /// boot). `true` if it is within the limit; refused requests don't
+    /// count towards it.
+    pub fn allow(&mut self, now_ms: u64) -> bool {
+        if now_ms.saturating_sub(self.window_start_ms) >= WINDOW_MS {
+            self.window_start_ms = now_ms;
+            self.count = 0;
+        }
+        if self.count >= self.limit {
+            return false;
+        }
+        self.count += 1;
+        true
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(DEFAULT_REQUESTS_PER_SEC)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_up_to_the_limit_per_window() {
+        let mut limiter = RateLimiter::new(3);
+        let allowed: [bool; 5] = core::array::from_fn(|i| limiter.allow(100 + i as u64));
+        assert_eq!(allowed, [true, true, true, false, false]);
+    }
+
+    #[test]
+    fn a_new_window_starts_after_a_second() {
+        let mut limiter = RateLimiter::new(2);
+        assert!(limiter.allow(0));
+        assert!(limiter.allow(500));
+        assert!(!limiter.allow(999));
+        assert!(limiter.allow(1000));
+        assert!(limiter.allow(1999));
+        assert!(!limiter.allow(1999));
+    }
+
+    #[test]
+    fn zero_limit_refuses_everything() {
+        let mut limiter = RateLimiter::new(0);
+        assert!(!limiter.allow(0));
+        assert!(!limiter.allow(5000));
+    }
+}
@@ -0,0 +1,190 @@
+//! Two text regions on one screen, from `/region`.
+//!
+//! The panel is split into a header, the top third, and a body below
+//! it. Each [`Region`] keeps its own text and color, so updating one
+//! leaves the other as it was; [`Regions::render`] lays each one out in
+//! its band like [`FrameBuffer::display_text_lines`], lines that don't
+//! fit the band dropped.
+
+use crate::MATRIX_HEIGHT;
+use crate::frame_buffer::{DEFAULT_TEXT_COLOR, FrameBuffer, MAX_TEXT_LINES, Pixel};
+use crate::http_request::MAX_MESSAGE_LEN;
+
+/// Rows in the header; the body has the rest.
+pub const TOP_REGION_HEIGHT: usize = MATRIX_HEIGHT / 3;
+
+/// One part of the panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    /// The top [`TOP_REGION_HEIGHT`] rows.
+    Top,
+    /// Every row below the header.
+    Bottom,
+}
+
+impl Region {
+    /// The region for an `id` as given to `/region`: `top` or `bottom`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "top" => Some(Self::Top),
+            "bottom" => Some(Self::Bottom),
+            _ => None,
+        }
+    }
+
+    /// First row and number of rows.
+    pub fn rows(self) -> (usize, usize) {
+        match self {
+            Self::Top => (0, TOP_REGION_HEIGHT),
+            Self::Bottom => (TOP_REGION_HEIGHT, MATRIX_HEIGHT - TOP_REGION_HEIGHT),
+        }
+    }
+}
+
+/// New content for one region, from `/region`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionUpdate {
+    pub region: Region,
+    /// Lines separated by `'\n'`.
+    pub text: heapless::String<MAX_MESSAGE_LEN>,
+    /// The color to draw it in; `None` keeps the region's color.
+    pub color: Option<Pixel>,
+}
+
+/// What a region shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Content {
+    text: heapless::String<MAX_MESSAGE_LEN>,
+    color: Pixel,
+}
+
+/// The text and color of both regions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Regions {
+    top: Content,
+    bottom: Content,
+}
+
+impl Default for Regions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Regions {
+    /// Both regions empty, in [`DEFAULT_TEXT_COLOR`].
+    pub const fn new() -> Self {
+        Self {
+            top: Content {
+                text: heapless::String::new(),
+                color: DEFAULT_TEXT_COLOR,
+            },
+            bottom: Content {
+                text: heapless::String::new(),
+                color: DEFAULT_TEXT_COLOR,
+            },
+        }
+    }
+
+    /// Replace one region's text, and its color if the update has one.
+    pub fn apply(&mut self, update: &RegionUpdate) {
+        let content = match update.region {
+            Region::Top => &mut self.top,
+            Region::Bottom => &mut self.bottom,
+        };
+        content.text = update.text.clone();
+        if let Some(color) = update.color {
+            content.color = color;
+        }
+    }
+
+    /// `region`'s text.
+    pub fn text(&self, region: Region) -> &str {
+        match region {
+            Region::Top => &self.top.text,
+            Region::Bottom => &self.bottom.text,
+        }
+    }
+
+    /// Draw both regions over the scene, in the current font, scale,
+    /// alignment and spacing. Cancels any active scroll.
+    pub fn render(&self, fb: &mut FrameBuffer) {
+        fb.stop_scroll();
+        fb.clear_to_scene();
+        for (region, content) in [(Region::Top, &self.top), (Region::Bottom, &self.bottom)] {
+            let lines: heapless::Vec<&str, MAX_TEXT_LINES> =
+                content.text.split('\n').take(MAX_TEXT_LINES).collect();
+            let (top, height) = region.rows();
+            fb.draw_text_band(&lines, content.color, top, height);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame_buffer::tests::lit_pixels;
+
+    fn update(region: Region, text: &str, color: Option<Pixel>) -> RegionUpdate {
+        RegionUpdate {
+            region,
+            text: text.try_into().unwrap(),
+            color,
+        }
+    }
+
+    /// The first and last lit row among `rows`, and the color of the
+    /// last lit pixel there.
+    fn lit_rows(fb: &FrameBuffer, rows: core::ops::Range<usize>) -> Option<(usize, usize, Pixel)> {
+        let lit = lit_pixels(fb);
+        let mut band = lit.iter().filter(|(_, y)| rows.contains(y));
+        let &(_, first) = band.clone().next()?;
+        let &(x, last) = band.next_back()?;
+        Some((first, last, fb.get_pixel(x, last)))
+    }
+
+    #[test]
+    fn each_region_draws_in_its_own_band_and_color() {
+        let mut regions = Regions::new();
+        regions.apply(&update(Region::Top, "12:00", Some([0xFFFF, 0, 0])));
+        regions.apply(&update(
+            Region::Bottom,
+            "Hello\nWorld",
+            Some([0, 0, 0xFFFF]),
+        ));
+        let mut fb = FrameBuffer::new();
+        regions.render(&mut fb);
+
+        let (first, last, color) = lit_rows(&fb, 0..TOP_REGION_HEIGHT).unwrap();
+        assert_eq!(color, [0xFFFF, 0, 0]);
+        assert!(
+            first > 0 && last < TOP_REGION_HEIGHT - 1,
+            "{first}..={last}"
+        );
+        let (first, last, color) = lit_rows(&fb, TOP_REGION_HEIGHT..MATRIX_HEIGHT).unwrap();
+        assert_eq!(color, [0, 0, 0xFFFF]);
+        assert!(first > TOP_REGION_HEIGHT && last < MATRIX_HEIGHT - 1);
+    }
+
+    #[test]
+    fn updating_one_region_keeps_the_other() {
+        let mut regions = Regions::new();
+        regions.apply(&update(Region::Top, "News", Some([1, 2, 3])));
+        regions.apply(&update(Region::Bottom, "Body", None));
+        regions.apply(&update(Region::Bottom, "More", None));
+        assert_eq!(regions.text(Region::Top), "News");
+        assert_eq!(regions.text(Region::Bottom), "More");
+        assert_eq!(regions.top.color, [1, 2, 3]);
+        assert_eq!(regions.bottom.color, DEFAULT_TEXT_COLOR);
+    }
+
+    #[test]
+    fn lines_past_the_band_are_dropped() {
+        let mut regions = Regions::new();
+        regions.apply(&update(Region::Top, "A\nB\nC\nD\nE\nF", None));
+        let mut fb = FrameBuffer::new();
+        regions.render(&mut fb);
+        assert!(lit_rows(&fb, 0..TOP_REGION_HEIGHT).is_some());
+        assert!(lit_rows(&fb, TOP_REGION_HEIGHT..MATRIX_HEIGHT).is_none());
+    }
+}
@@ -0,0 +1,92 @@
+//! Messages with a color per segment, from `/rich`.
+//!
+//! A [`RichText`] keeps the whole message as plain text, so it saves,
+//! throttles and compares like any `/text` message, plus where each
+//! segment ends and its color. The refresh loop only uses the colors
+//! while the message on show is still the one they were made for.
+
+use crate::frame_buffer::Pixel;
+use crate::http_request::MAX_MESSAGE_LEN;
+
+/// Most segments in one message.
+pub const MAX_SEGMENTS: usize = 8;
+
+/// A message and the color of each of its segments.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RichText {
+    text: heapless::String<MAX_MESSAGE_LEN>,
+    /// Each segment's end, as a byte offset into `text`, and its color.
+    segments: heapless::Vec<(usize, Pixel), MAX_SEGMENTS>,
+}
+
+impl RichText {
+    pub const fn new() -> Self {
+        Self {
+            text: heapless::String::new(),
+            segments: heapless::Vec::new(),
+        }
+    }
+
+    /// Add `text` in `color` after the segments so far. `false`, leaving
+    /// the message as it was, if it would get too long or have too many
+    /// segments.
+    pub fn push(&mut self, text: &str, color: Pixel) -> bool {
+        if self.segments.is_full() || self.text.len() + text.len() > MAX_MESSAGE_LEN {
+            return false;
+        }
+        let _ = self.text.push_str(text);
+        let _ = self.segments.push((self.text.len(), color));
+        true
+    }
+
+    /// The whole message, without colors.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Each segment's text and color, in order, for
+    /// [`FrameBuffer::display_colored_segments`](crate::frame_buffer::FrameBuffer::display_colored_segments).
+    pub fn segments(&self) -> heapless::Vec<(&str, Pixel), MAX_SEGMENTS> {
+        let mut start = 0;
+        self.segments
+            .iter()
+            .map(|&(end, color)| {
+                let segment = (&self.text[start..end], color);
+                start = end;
+                segment
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments_split_the_text_back_up() {
+        let mut rich = RichText::new();
+        assert!(rich.push("Hello", [1, 1, 1]));
+        assert!(rich.push("Wörld", [2, 2, 2]));
+        assert_eq!(rich.text(), "HelloWörld");
+        assert_eq!(
+            rich.segments(),
+            [("Hello", [1, 1, 1]), ("Wörld", [2, 2, 2])]
+        );
+    }
+
+    #[test]
+    fn push_refuses_what_does_not_fit() {
+        let mut rich = RichText::new();
+        for _ in 0..MAX_SEGMENTS {
+            assert!(rich.push("a", [0, 0, 0]));
+        }
+        assert!(!rich.push("a", [0, 0, 0]));
+
+        let long = "abcdefghijklmnopqrstuvwxyz012345";
+        let mut rich = RichText::new();
+        assert!(rich.push(&long[..MAX_MESSAGE_LEN - 1], [0, 0, 0]));
+        assert!(!rich.push("yz", [0, 0, 0]));
+        assert_eq!(rich.text().len(), MAX_MESSAGE_LEN - 1);
+    }
+}
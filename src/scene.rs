@@ -0,0 +1,123 @@
+//! Retained drawing: the shapes that stay behind the text.
+//!
+//! [`DrawCommand`]s from `/pixel`, `/color`, `/api/draw` and friends are
+//! kept in a [`Scene`] as well as drawn. Whenever the
+//! [`FrameBuffer`](crate::frame_buffer::FrameBuffer) redraws its text
+//! (a new message, every scroll step) it starts from the scene instead of
+//! black, so a background survives the next `/text` and the text lands on
+//! top of it.
+//!
+//! A [`DrawCommand::Fill`] or [`DrawCommand::Gradient`] covers
+//! everything drawn before it, so pushing one drops the earlier
+//! commands; a black fill leaves the scene empty. That is how `/clear`,
+//! `/color` and `/gradient` start over.
+
+use crate::command::DrawCommand;
+use crate::frame_buffer::FrameBuffer;
+
+/// Most commands a [`Scene`] keeps. Further ones are still drawn, but
+/// gone at the next redraw.
+pub const MAX_SCENE_COMMANDS: usize = 64;
+
+/// Drawing commands replayed, oldest first, under the text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Scene {
+    commands: heapless::Vec<DrawCommand, MAX_SCENE_COMMANDS>,
+}
+
+impl Scene {
+    /// An empty scene: a black background.
+    pub const fn new() -> Self {
+        Self {
+            commands: heapless::Vec::new(),
+        }
+    }
+
+    /// Keep `cmd` on top of the scene. Returns false (and keeps nothing)
+    /// once [`MAX_SCENE_COMMANDS`] are stored.
+    pub fn push(&mut self, cmd: DrawCommand) -> bool {
+        match cmd {
+            DrawCommand::Fill { color } => {
+                self.commands.clear();
+                if color == [0, 0, 0] {
+                    return true;
+                }
+            }
+            DrawCommand::Gradient { .. } => self.commands.clear(),
+            _ => {}
+        }
+        self.commands.push(cmd).is_ok()
+    }
+
+    /// Forget every command: back to a black background.
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+
+    /// Number of commands kept.
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// True when nothing is kept.
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Replay the commands onto `fb`, oldest first.
+    pub fn render(&self, fb: &mut FrameBuffer) {
+        for cmd in &self.commands {
+            cmd.apply(fb);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(x: i32) -> DrawCommand {
+        DrawCommand::Pixel {
+            x,
+            y: 0,
+            color: [0x101; 3],
+        }
+    }
+
+    #[test]
+    fn fill_drops_what_it_covers() {
+        let mut scene = Scene::new();
+        assert!(scene.push(pixel(0)));
+        assert!(scene.push(pixel(1)));
+        assert!(scene.push(DrawCommand::Fill { color: [0, 0, 9] }));
+        assert!(scene.push(pixel(2)));
+        assert_eq!(scene.len(), 2);
+        assert!(scene.push(DrawCommand::Fill { color: [0, 0, 0] }));
+        assert!(scene.is_empty());
+    }
+
+    #[test]
+    fn render_replays_in_order() {
+        let mut scene = Scene::new();
+        scene.push(DrawCommand::Fill {
+            color: [0, 0, 0x909],
+        });
+        scene.push(pixel(3));
+        let mut fb = FrameBuffer::new();
+        scene.render(&mut fb);
+        assert_eq!(fb.get_pixel(3, 0), [0x101; 3]);
+        assert_eq!(fb.get_pixel(4, 0), [0, 0, 0x909]);
+    }
+
+    #[test]
+    fn full_scene_refuses_more() {
+        let mut scene = Scene::new();
+        for x in 0..MAX_SCENE_COMMANDS {
+            assert!(scene.push(pixel(x as i32)));
+        }
+        assert!(!scene.push(pixel(0)));
+        assert_eq!(scene.len(), MAX_SCENE_COMMANDS);
+        scene.clear();
+        assert!(scene.is_empty());
+    }
+}
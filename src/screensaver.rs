@@ -0,0 +1,97 @@
+//! Fading the panel out while nobody uses it.
+//!
+//! A [`Screensaver`] keeps when the last command came in, on a
+//! caller-supplied millisecond clock like
+//! [`Countdown`](crate::countdown::Countdown). Once the timeout passes
+//! without another one, [`Screensaver::brightness`] fades the brightness
+//! down to 0, which blanks the panel, over [`IDLE_FADE_MS`]; the next
+//! command brings it straight back.
+
+/// How long the fade to black takes once the timeout has passed.
+pub const IDLE_FADE_MS: u64 = 5_000;
+
+/// Longest accepted idle timeout: a day.
+pub const MAX_IDLE_TIMEOUT_SECS: u32 = 24 * 60 * 60;
+
+/// When the display last had a command and how long it may sit idle.
+/// See the module docs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Screensaver {
+    /// Seconds without a command before the fade starts; 0 never fades.
+    timeout_secs: u32,
+    /// When the last command came in.
+    last_command_ms: u64,
+}
+
+impl Screensaver {
+    /// A screensaver that never fades.
+    pub const fn new() -> Self {
+        Self {
+            timeout_secs: 0,
+            last_command_ms: 0,
+        }
+    }
+
+    /// Fade after `secs` without a command (0: never), counting from
+    /// `now_ms`.
+    pub fn set_timeout(&mut self, secs: u32, now_ms: u64) {
+        self.timeout_secs = secs;
+        self.last_command_ms = now_ms;
+    }
+
+    /// Seconds without a command before the fade starts; 0 never fades.
+    pub fn timeout_secs(&self) -> u32 {
+        self.timeout_secs
+    }
+
+    /// A command came in at `now_ms`: back to full brightness.
+    pub fn wake(&mut self, now_ms: u64) {
+        self.last_command_ms = now_ms;
+    }
+
+    /// The brightness to show at `now_ms` for the set `level`: `level`
+    /// until the timeout, then less and less down to 0.
+    pub fn brightness(&self, level: u8, now_ms: u64) -> u8 {
+        if self.timeout_secs == 0 {
+            return level;
+        }
+        let idle_ms = now_ms.saturating_sub(self.last_command_ms);
+        let fading_ms = idle_ms.saturating_sub(u64::from(self.timeout_secs) * 1000);
+        let left_ms = IDLE_FADE_MS.saturating_sub(fading_ms);
+        (u64::from(level) * left_ms / IDLE_FADE_MS) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fades_out_after_the_timeout() {
+        let mut saver = Screensaver::new();
+        saver.set_timeout(60, 1_000);
+        assert_eq!(saver.brightness(200, 1_000), 200);
+        assert_eq!(saver.brightness(200, 61_000), 200);
+        assert_eq!(saver.brightness(200, 61_000 + IDLE_FADE_MS / 2), 100);
+        assert_eq!(saver.brightness(200, 61_000 + IDLE_FADE_MS), 0);
+        assert_eq!(saver.brightness(200, 1_000_000), 0);
+    }
+
+    #[test]
+    fn a_command_wakes_it() {
+        let mut saver = Screensaver::new();
+        saver.set_timeout(10, 0);
+        assert_eq!(saver.brightness(255, 20_000), 0);
+        saver.wake(20_000);
+        assert_eq!(saver.brightness(255, 20_000), 255);
+        assert_eq!(saver.brightness(255, 29_999), 255);
+    }
+
+    #[test]
+    fn zero_never_fades() {
+        let mut saver = Screensaver::new();
+        assert_eq!(saver.brightness(128, u64::MAX), 128);
+        saver.set_timeout(0, 0);
+        assert_eq!(saver.brightness(128, 1_000_000), 128);
+    }
+}
@@ -0,0 +1,315 @@
+//! Encode a frame as an image file for `GET /screenshot`.
+//!
+//! Two formats, neither needing a compression library: binary PPM (P6),
+//! the simplest there is, and PNG with the pixel data in uncompressed
+//! ("stored") deflate blocks, which any browser shows. Both are 8 bits
+//! per channel: each 16-bit channel keeps its high byte. The pixels are
+//! the frame buffer's, before gamma correction and brightness, so a
+//! screenshot looks like what was drawn rather than the PWM values.
+//!
+//! Every frame encodes to the same number of bytes
+//! ([`Format::encoded_len`]), so the HTTP layer can send
+//! `Content-Length` up front.
+
+use core::fmt::Write;
+
+use crate::crc32::crc32_update;
+use crate::frame_buffer::load_pixel;
+use crate::panel::Frame;
+use crate::{MATRIX_HEIGHT, MATRIX_WIDTH};
+
+/// Bytes of 8-bit RGB pixel data in a frame.
+const RGB_LEN: usize = MATRIX_WIDTH * MATRIX_HEIGHT * 3;
+
+/// PPM header: magic, width and height, and the maximum channel value.
+#[cfg(not(feature = "two-panels"))]
+const PPM_HEADER: &[u8] = b"P6\n88 88\n255\n";
+#[cfg(feature = "two-panels")]
+const PPM_HEADER: &[u8] = b"P6\n176 88\n255\n";
+
+/// Size of a PPM screenshot.
+pub const PPM_LEN: usize = PPM_HEADER.len() + RGB_LEN;
+
+/// The eight bytes every PNG starts with.
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+/// PNG scanlines as deflated: a filter type byte (0, none) before each row.
+const PNG_RAW_LEN: usize = MATRIX_HEIGHT * (1 + MATRIX_WIDTH * 3);
+
+/// Most bytes a stored deflate block holds.
+const STORED_BLOCK_LEN: usize = 0xFFFF;
+
+/// zlib stream of [`PNG_RAW_LEN`] bytes: a 2-byte header, stored blocks
+/// with a 5-byte header each, and the Adler-32 checksum.
+const ZLIB_LEN: usize = 2 + PNG_RAW_LEN.div_ceil(STORED_BLOCK_LEN) * 5 + PNG_RAW_LEN + 4;
+
+/// Length, type and CRC around each PNG chunk's data.
+const CHUNK_OVERHEAD: usize = 12;
+
+/// Size of the PNG header chunk's data.
+const IHDR_LEN: usize = 13;
+
+/// Size of a PNG screenshot: signature, `IHDR`, one `IDAT` and `IEND`.
+pub const PNG_LEN: usize =
+    PNG_SIGNATURE.len() + CHUNK_OVERHEAD + IHDR_LEN + CHUNK_OVERHEAD + ZLIB_LEN + CHUNK_OVERHEAD;
+
+/// Size of the largest screenshot, for the buffer it is encoded into.
+pub const MAX_SCREENSHOT_LEN: usize = if PNG_LEN > PPM_LEN { PNG_LEN } else { PPM_LEN };
+
+/// Longest [`Format::headers`].
+pub const MAX_HEADERS_LEN: usize = 128;
+
+/// Image format of a screenshot, picked with `/screenshot?format=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Png,
+    Ppm,
+}
+
+impl Format {
+    /// Parse a `format` query value: `png` or `ppm`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "png" => Some(Self::Png),
+            "ppm" => Some(Self::Ppm),
+            _ => None,
+        }
+    }
+
+    /// MIME type for the `Content-Type` header.
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Ppm => "image/x-portable-pixmap",
+        }
+    }
+
+    /// Size of an encoded frame.
+    pub fn encoded_len(self) -> usize {
+        match self {
+            Self::Png => PNG_LEN,
+            Self::Ppm => PPM_LEN,
+        }
+    }
+
+    /// 200 OK headers for a screenshot in this format, with its
+    /// `Content-Type` and `Content-Length`.
+    pub fn headers(self) -> heapless::String<MAX_HEADERS_LEN> {
+        let mut headers = heapless::String::new();
+        // Always fits: the longest is under 100 bytes.
+        let _ = write!(
+            headers,
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.content_type(),
+            self.encoded_len()
+        );
+        headers
+    }
+
+    /// Encode `frame` into the start of `out`, which must hold at least
+    /// [`Self::encoded_len`] bytes. Returns the number of bytes written.
+    pub fn encode(self, frame: &Frame, out: &mut [u8]) -> usize {
+        let mut out = Output { buf: out, len: 0 };
+        match self {
+            Self::Png => encode_png(frame, &mut out),
+            Self::Ppm => encode_ppm(frame, &mut out),
+        }
+        out.len
+    }
+}
+
+/// Bytes appended to a buffer.
+struct Output<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl Output<'_> {
+    fn put(&mut self, bytes: &[u8]) {
+        self.buf[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+}
+
+/// The frame's pixels as 8-bit RGB, row by row.
+fn rgb_rows(frame: &Frame) -> impl Iterator<Item = impl Iterator<Item = u8> + '_> {
+    frame.iter().map(|row| {
+        row.iter()
+            .flat_map(|&px| load_pixel(px).map(|channel| (channel >> 8) as u8))
+    })
+}
+
+fn encode_ppm(frame: &Frame, out: &mut Output) {
+    out.put(PPM_HEADER);
+    for row in rgb_rows(frame) {
+        for byte in row {
+            out.put(&[byte]);
+        }
+    }
+}
+
+fn encode_png(frame: &Frame, out: &mut Output) {
+    out.put(&PNG_SIGNATURE);
+
+    let mut ihdr = [0u8; IHDR_LEN];
+    ihdr[..4].copy_from_slice(&(MATRIX_WIDTH as u32).to_be_bytes());
+    ihdr[4..8].copy_from_slice(&(MATRIX_HEIGHT as u32).to_be_bytes());
+    ihdr[8] = 8; // bit depth
+    ihdr[9] = 2; // color type: RGB
+    // Compression, filter and interlace methods all 0.
+    put_chunk(out, b"IHDR", |out| out.put(&ihdr));
+
+    put_chunk(out, b"IDAT", |out| {
+        // Deflate with a 32K window and no preset dictionary; the check
+        // bits make the header a multiple of 31.
+        out.put(&[0x78, 0x01]);
+        let mut raw = rgb_rows(frame).flat_map(|row| core::iter::once(0).chain(row));
+        let mut adler = Adler32::new();
+        let mut left = PNG_RAW_LEN;
+        while left > 0 {
+            let len = left.min(STORED_BLOCK_LEN);
+            left -= len;
+            let len16 = len as u16;
+            out.put(&[u8::from(left == 0)]);
+            out.put(&len16.to_le_bytes());
+            out.put(&(!len16).to_le_bytes());
+            for byte in raw.by_ref().take(len) {
+                adler.update(byte);
+                out.put(&[byte]);
+            }
+        }
+        out.put(&adler.finish().to_be_bytes());
+    });
+
+    put_chunk(out, b"IEND", |_| {});
+}
+
+/// Write a PNG chunk whose data `write_data` puts, framed by its length
+/// and CRC.
+fn put_chunk(out: &mut Output, kind: &[u8; 4], write_data: impl FnOnce(&mut Output)) {
+    let start = out.len;
+    out.put(&[0; 4]);
+    out.put(kind);
+    write_data(out);
+    let data_len = (out.len - start - 8) as u32;
+    out.buf[start..start + 4].copy_from_slice(&data_len.to_be_bytes());
+    let crc = !crc32_update(!0, &out.buf[start + 4..out.len]);
+    out.put(&crc.to_be_bytes());
+}
+
+/// zlib's Adler-32 checksum, a byte at a time.
+struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Adler32 {
+    const MOD: u32 = 65_521;
+
+    fn new() -> Self {
+        Self { a: 1, b: 0 }
+    }
+
+    fn update(&mut self, byte: u8) {
+        self.a = (self.a + u32::from(byte)) % Self::MOD;
+        self.b = (self.b + self.a) % Self::MOD;
+    }
+
+    fn finish(&self) -> u32 {
+        self.b << 16 | self.a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame_buffer::store_pixel;
+
+    /// A frame with a few distinct pixels.
+    fn frame() -> Frame {
+        let mut frame = [[[0; 3]; MATRIX_WIDTH]; MATRIX_HEIGHT];
+        frame[0][0] = store_pixel([0xFFFF, 0, 0]);
+        frame[0][1] = store_pixel([0, 0x80FF, 0]);
+        frame[MATRIX_HEIGHT - 1][MATRIX_WIDTH - 1] = store_pixel([0x0100, 0x007F, 0xFFFF]);
+        frame
+    }
+
+    fn encoded(format: Format) -> ([u8; MAX_SCREENSHOT_LEN], usize) {
+        let mut out = [0xAA; MAX_SCREENSHOT_LEN];
+        let len = format.encode(&frame(), &mut out);
+        (out, len)
+    }
+
+    #[test]
+    fn ppm_keeps_the_high_byte() {
+        let (out, len) = encoded(Format::Ppm);
+        assert_eq!(len, PPM_LEN);
+        let mut header = heapless::String::<16>::new();
+        write!(header, "P6\n{MATRIX_WIDTH} {MATRIX_HEIGHT}\n255\n").unwrap();
+        assert!(out.starts_with(header.as_bytes()));
+        let pixels = &out[PPM_HEADER.len()..len];
+        assert_eq!(pixels[..6], [0xFF, 0, 0, 0, 0x80, 0]);
+        assert_eq!(pixels[RGB_LEN - 3..], [1, 0, 0xFF]);
+    }
+
+    #[test]
+    fn png_chunks_are_well_formed() {
+        let (out, len) = encoded(Format::Png);
+        assert_eq!(len, PNG_LEN);
+        assert_eq!(out[..8], PNG_SIGNATURE);
+        let mut rest = &out[8..len];
+        let mut kinds = [[0u8; 4]; 3];
+        for kind in &mut kinds {
+            let data_len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            kind.copy_from_slice(&rest[4..8]);
+            let crc = u32::from_be_bytes(rest[8 + data_len..12 + data_len].try_into().unwrap());
+            assert_eq!(crc, !crc32_update(!0, &rest[4..8 + data_len]));
+            rest = &rest[12 + data_len..];
+        }
+        assert_eq!(kinds, [*b"IHDR", *b"IDAT", *b"IEND"]);
+        assert!(rest.is_empty());
+        // Display-sized, 8-bit RGB.
+        assert_eq!(out[16..20], (MATRIX_WIDTH as u32).to_be_bytes());
+        assert_eq!(out[20..24], (MATRIX_HEIGHT as u32).to_be_bytes());
+        assert_eq!(out[24..26], [8, 2]);
+    }
+
+    #[test]
+    fn png_stores_filtered_rows() {
+        let (out, _) = encoded(Format::Png);
+        let zlib = &out[8 + CHUNK_OVERHEAD + IHDR_LEN + 8..][..ZLIB_LEN];
+        assert_eq!(u16::from_be_bytes([zlib[0], zlib[1]]) % 31, 0);
+        // One final stored block holds every row.
+        assert_eq!(zlib[2], 1);
+        let len = u16::from_le_bytes([zlib[3], zlib[4]]);
+        assert_eq!(usize::from(len), PNG_RAW_LEN);
+        assert_eq!(u16::from_le_bytes([zlib[5], zlib[6]]), !len);
+        let raw = &zlib[7..7 + PNG_RAW_LEN];
+        assert_eq!(raw[..7], [0, 0xFF, 0, 0, 0, 0x80, 0]);
+        assert_eq!(raw[1 + MATRIX_WIDTH * 3], 0);
+        assert_eq!(raw[PNG_RAW_LEN - 3..], [1, 0, 0xFF]);
+        let mut adler = Adler32::new();
+        raw.iter().for_each(|&byte| adler.update(byte));
+        assert_eq!(zlib[ZLIB_LEN - 4..], adler.finish().to_be_bytes());
+    }
+
+    #[test]
+    fn adler32_matches_zlib() {
+        let mut adler = Adler32::new();
+        b"Wikipedia".iter().for_each(|&byte| adler.update(byte));
+        assert_eq!(adler.finish(), 0x11E6_0398);
+    }
+
+    #[test]
+    fn headers_carry_type_and_length() {
+        let headers = Format::Png.headers();
+        assert!(headers.contains("Content-Type: image/png\r\n"));
+        let mut length: heapless::String<32> = heapless::String::new();
+        write!(length, "Content-Length: {PNG_LEN}\r\n").unwrap();
+        assert!(headers.contains(length.as_str()));
+        assert!(headers.ends_with("\r\n\r\n"));
+        assert_eq!(Format::from_name("ppm"), Some(Format::Ppm));
+        assert_eq!(Format::from_name("gif"), None);
+    }
+}
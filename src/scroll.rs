@@ -0,0 +1,557 @@
+//! Scrolling text: horizontal marquees and a vertical ticker.
+//!
+//! [`ScrollText`] pre-renders a message into an off-screen strip that is
+//! exactly as wide as the rendered text, then slides a panel-wide window
+//! across it. The strip stores one bit mask per column (bit `y` = glyph
+//! row `y` lit, up to the panel height), so a full-length message costs a
+//! few kilobytes rather than a second RGB frame.
+//!
+//! The scroll cycle is `strip width + MATRIX_WIDTH` pixels long: the text
+//! enters from the right edge, leaves on the left, and the offset wraps
+//! back to zero only once the panel is blank again, so the restart never
+//! shows a partial jump.
+//!
+//! [`VerticalScroll`] does the same upwards for several lines: one
+//! [`RowBits`] per panel row, with the text entering at the bottom edge
+//! and wrapping once it has left at the top.
+//!
+//! [`ScrollTimer`] turns elapsed time into whole pixels for scrolls that
+//! should move at a fixed speed however fast the panel refreshes.
+
+use crate::font::{DEFAULT_CHAR_SPACING, FONT_WIDTH, Font};
+use crate::{MATRIX_HEIGHT, MATRIX_WIDTH};
+
+/// Longest message (in characters) that fits in the scroll strip.
+pub const MAX_SCROLL_CHARS: usize = 64;
+
+/// Strip capacity in columns: every character of the built-in font plus
+/// its trailing gap. Wider uploaded fonts, or wider gaps, fit fewer
+/// characters.
+const MAX_SCROLL_COLUMNS: usize = MAX_SCROLL_CHARS * (FONT_WIDTH + DEFAULT_CHAR_SPACING);
+
+/// Pre-rendered scrolling message plus its current position.
+pub struct ScrollText {
+    /// One entry per strip column; bit `y` set = pixel lit in glyph row `y`.
+    columns: heapless::Vec<u128, MAX_SCROLL_COLUMNS>,
+    /// Current position within the scroll cycle, in pixels.
+    offset: usize,
+    /// Pixels advanced per [`Self::advance`].
+    speed_px: usize,
+    /// Panel pixels per strip column. See [`Self::with_scale`].
+    scale: usize,
+}
+
+impl ScrollText {
+    /// Render `text` into a fresh strip, positioned just off the right
+    /// edge of the panel. Characters beyond [`MAX_SCROLL_CHARS`] are
+    /// dropped.
+    pub fn new(font: &Font, text: &str, speed_px: usize) -> Self {
+        Self::new_spaced(font, text, speed_px, DEFAULT_CHAR_SPACING)
+    }
+
+    /// [`Self::new`], with `spacing` blank columns between glyphs.
+    /// Characters that don't fit in the strip are dropped.
+    pub fn new_spaced(font: &Font, text: &str, speed_px: usize, spacing: usize) -> Self {
+        let mut columns = heapless::Vec::new();
+        for (i, ch) in text.chars().take(MAX_SCROLL_CHARS).enumerate() {
+            if i > 0 {
+                for _ in 0..spacing {
+                    // Capacity covers MAX_SCROLL_CHARS glyphs + default
+                    // gaps; past it the rest is dropped.
+                    let _ = columns.push(0);
+                }
+            }
+            let glyph = font.glyph(ch);
+            for gx in 0..font.width() {
+                let mut bits = 0u128;
+                if let Some(glyph) = glyph {
+                    for gy in 0..font.height() {
+                        if glyph.is_lit(gx, gy) {
+                            bits |= 1 << gy;
+                        }
+                    }
+                }
+                let _ = columns.push(bits);
+            }
+        }
+
+        Self {
+            columns,
+            offset: 0,
+            speed_px,
+            scale: 1,
+        }
+    }
+
+    /// Stretch the strip horizontally by `scale` (at least 1), to match
+    /// text drawn with a scaled font. The strip itself is not copied;
+    /// [`Self::column_at`] maps panel columns back onto it.
+    pub fn with_scale(mut self, scale: usize) -> Self {
+        self.scale = scale.max(1);
+        self
+    }
+
+    /// Rendered width of the message in pixels.
+    pub fn width(&self) -> usize {
+        self.columns.len() * self.scale
+    }
+
+    /// Length of one full scroll cycle in pixels.
+    pub fn period(&self) -> usize {
+        self.width() + MATRIX_WIDTH
+    }
+
+    /// Current offset within the scroll cycle.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Pixels moved per [`Self::advance`].
+    pub fn speed_px(&self) -> usize {
+        self.speed_px
+    }
+
+    /// Move the window left by `speed_px`, wrapping at the end of the cycle.
+    pub fn advance(&mut self) {
+        self.advance_by(self.speed_px);
+    }
+
+    /// Move the window left by `px`, wrapping at the end of the cycle.
+    pub fn advance_by(&mut self, px: usize) {
+        self.offset = (self.offset + px) % self.period();
+    }
+
+    /// Column bits for panel column `x` at the current offset, or 0 when
+    /// the text doesn't cover that column.
+    pub fn column_at(&self, x: usize) -> u128 {
+        // The text's left edge sits at `MATRIX_WIDTH - offset`.
+        let strip_x = (x + self.offset).checked_sub(MATRIX_WIDTH);
+        strip_x
+            .and_then(|i| self.columns.get(i / self.scale).copied())
+            .unwrap_or(0)
+    }
+}
+
+/// `u128`s in a [`RowBits`]: one bit for every column of the display.
+const ROW_WORDS: usize = MATRIX_WIDTH.div_ceil(u128::BITS as usize);
+
+/// One bit per display column, set where the column is lit. A chained
+/// display is wider than one `u128`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RowBits([u128; ROW_WORDS]);
+
+impl RowBits {
+    /// No column lit.
+    pub const EMPTY: Self = Self([0; ROW_WORDS]);
+
+    /// Light column `x`. Columns past the display are ignored.
+    pub fn set(&mut self, x: usize) {
+        if let Some(word) = self.0.get_mut(x / u128::BITS as usize) {
+            *word |= 1 << (x % u128::BITS as usize);
+        }
+    }
+
+    /// True if column `x` is lit.
+    pub fn is_lit(&self, x: usize) -> bool {
+        self.0
+            .get(x / u128::BITS as usize)
+            .is_some_and(|word| word & 1 << (x % u128::BITS as usize) != 0)
+    }
+
+    /// True if no column is lit.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::EMPTY
+    }
+}
+
+/// Rows the vertical strip holds: as many as fit in the marquee
+/// strip's memory. That is 27 lines of the built-in font with a line's
+/// gap between them, or 14 across two panels.
+pub const MAX_VSCROLL_ROWS: usize = MAX_SCROLL_COLUMNS / ROW_WORDS;
+
+/// Lines of text stacked into a tall strip that scrolls up the panel.
+pub struct VerticalScroll {
+    /// One entry per strip row.
+    rows: heapless::Vec<RowBits, MAX_VSCROLL_ROWS>,
+    /// Current position within the scroll cycle, in pixels.
+    offset: usize,
+    /// Pixels advanced per [`Self::advance`].
+    speed_px: usize,
+    /// Font pixels between glyphs. See [`Self::with_spacing`].
+    char_spacing: usize,
+    /// Font pixels between lines, or `None` for a line's height.
+    line_spacing: Option<usize>,
+}
+
+impl VerticalScroll {
+    /// An empty strip, positioned just below the bottom edge of the
+    /// panel. Scrolls a blank screen until lines are pushed.
+    pub fn new(speed_px: usize) -> Self {
+        Self {
+            rows: heapless::Vec::new(),
+            offset: 0,
+            speed_px,
+            char_spacing: DEFAULT_CHAR_SPACING,
+            line_spacing: None,
+        }
+    }
+
+    /// Space the lines pushed from now on `char_spacing` font pixels
+    /// between glyphs and `line_spacing` between lines (`None`: a line's
+    /// height), both scaled like the glyphs.
+    pub fn with_spacing(mut self, char_spacing: usize, line_spacing: Option<usize>) -> Self {
+        self.char_spacing = char_spacing;
+        self.line_spacing = line_spacing;
+        self
+    }
+
+    /// Append `text` below the previous line, with a gap as tall as a
+    /// line between them. Glyphs start at column `x`, are drawn `scale`
+    /// times their size with a `scale`-pixel gap, and are dropped when
+    /// they would run past the right edge. [`Self::with_spacing`] changes
+    /// both gaps. Returns false, adding nothing, when the line doesn't
+    /// fit in the strip.
+    pub fn push_line(&mut self, font: &Font, text: &str, x: usize, scale: usize) -> bool {
+        let scale = scale.max(1);
+        let height = font.height() * scale;
+        let gap = match self.line_spacing {
+            _ if self.rows.is_empty() => 0,
+            Some(spacing) => spacing * scale,
+            None => height,
+        };
+        if self.rows.len() + gap + height > MAX_VSCROLL_ROWS {
+            return false;
+        }
+        for _ in 0..gap {
+            // Room was checked above.
+            let _ = self.rows.push(RowBits::EMPTY);
+        }
+        let glyph_width = font.width() * scale;
+        let max_x = MATRIX_WIDTH.saturating_sub(glyph_width);
+        for gy in 0..font.height() {
+            let mut bits = RowBits::EMPTY;
+            let mut cx = x;
+            for ch in text.chars() {
+                if cx >= max_x {
+                    break;
+                }
+                if let Some(glyph) = font.glyph(ch) {
+                    for gx in 0..font.width() {
+                        if glyph.is_lit(gx, gy) {
+                            for dx in 0..scale {
+                                bits.set(cx + gx * scale + dx);
+                            }
+                        }
+                    }
+                }
+                cx += glyph_width + self.char_spacing * scale;
+            }
+            for _ in 0..scale {
+                let _ = self.rows.push(bits);
+            }
+        }
+        true
+    }
+
+    /// Height of the stacked lines in pixels.
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Length of one full scroll cycle in pixels.
+    pub fn period(&self) -> usize {
+        self.height() + MATRIX_HEIGHT
+    }
+
+    /// Current offset within the scroll cycle.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Pixels moved per [`Self::advance`].
+    pub fn speed_px(&self) -> usize {
+        self.speed_px
+    }
+
+    /// Move the window up by `speed_px`, wrapping at the end of the cycle.
+    pub fn advance(&mut self) {
+        self.advance_by(self.speed_px);
+    }
+
+    /// Move the window up by `px`, wrapping at the end of the cycle.
+    pub fn advance_by(&mut self, px: usize) {
+        self.offset = (self.offset + px) % self.period();
+    }
+
+    /// Row bits for panel row `y` at the current offset, or
+    /// [`RowBits::EMPTY`] when the text doesn't cover that row.
+    pub fn row_at(&self, y: usize) -> RowBits {
+        // The top line's top edge sits at `MATRIX_HEIGHT - offset`.
+        let strip_y = (y + self.offset).checked_sub(MATRIX_HEIGHT);
+        strip_y
+            .and_then(|i| self.rows.get(i).copied())
+            .unwrap_or(RowBits::EMPTY)
+    }
+}
+
+/// Converts elapsed time into scroll pixels at a fixed speed. The
+/// fraction of a pixel left over each time carries to the next call, so
+/// slow speeds still move and the average speed is exact.
+#[derive(Debug, Clone, Copy)]
+pub struct ScrollTimer {
+    px_per_sec: u32,
+    /// Leftover progress, in pixel-microseconds (1_000_000 = one pixel).
+    carry: u64,
+}
+
+impl ScrollTimer {
+    /// Timer for `px_per_sec` pixels per second.
+    pub const fn new(px_per_sec: u32) -> Self {
+        Self {
+            px_per_sec,
+            carry: 0,
+        }
+    }
+
+    /// Whole pixels to scroll for `elapsed_us` microseconds since the
+    /// previous call.
+    pub fn pixels(&mut self, elapsed_us: u64) -> usize {
+        let total = self.carry + elapsed_us * u64::from(self.px_per_sec);
+        self.carry = total % 1_000_000;
+        (total / 1_000_000) as usize
+    }
+
+    /// Milliseconds it takes to scroll `px` pixels, rounded up. A stopped
+    /// timer (0 px/s) never gets there: `u64::MAX`.
+    pub fn duration_ms(&self, px: usize) -> u64 {
+        if self.px_per_sec == 0 {
+            return u64::MAX;
+        }
+        (px as u64 * 1000).div_ceil(u64::from(self.px_per_sec))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(columns: impl IntoIterator<Item = usize>) -> RowBits {
+        let mut bits = RowBits::EMPTY;
+        columns.into_iter().for_each(|x| bits.set(x));
+        bits
+    }
+
+    #[test]
+    fn width_matches_font_text_width() {
+        let font = Font::new();
+        let scroll = ScrollText::new(&font, "HELLO WORLD", 1);
+        assert_eq!(
+            scroll.width(),
+            font.text_width("HELLO WORLD", DEFAULT_CHAR_SPACING)
+        );
+    }
+
+    #[test]
+    fn starts_blank_just_off_the_right_edge() {
+        let font = Font::new();
+        let scroll = ScrollText::new(&font, "I", 1);
+        for x in 0..MATRIX_WIDTH {
+            assert_eq!(scroll.column_at(x), 0);
+        }
+    }
+
+    #[test]
+    fn text_enters_from_the_right() {
+        let font = Font::new();
+        let mut scroll = ScrollText::new(&font, "I", 1);
+        // 'I' is a centred vertical bar; its middle column is strip x=2.
+        for _ in 0..3 {
+            scroll.advance();
+        }
+        assert_ne!(scroll.column_at(MATRIX_WIDTH - 1), 0);
+    }
+
+    #[test]
+    fn offset_wraps_at_period() {
+        let font = Font::new();
+        let mut scroll = ScrollText::new(&font, "AB", 4);
+        let period = scroll.period();
+        for _ in 0..period {
+            scroll.advance();
+        }
+        // 4 * period is a multiple of period, so we are back at the start.
+        assert_eq!(scroll.offset(), 0);
+    }
+
+    #[test]
+    fn advance_by_matches_repeated_advance() {
+        let font = Font::new();
+        let mut stepped = ScrollText::new(&font, "AB", 1);
+        let mut jumped = ScrollText::new(&font, "AB", 1);
+        for _ in 0..stepped.period() + 7 {
+            stepped.advance();
+        }
+        jumped.advance_by(jumped.period() + 7);
+        assert_eq!(jumped.offset(), stepped.offset());
+    }
+
+    #[test]
+    fn timer_speed_is_independent_of_call_rate() {
+        // 30 px/s for one second, in one call or many uneven ones.
+        assert_eq!(ScrollTimer::new(30).pixels(1_000_000), 30);
+        let mut timer = ScrollTimer::new(30);
+        let moved: usize = (0..500)
+            .map(|i| timer.pixels(if i % 2 == 0 { 1_500 } else { 2_500 }))
+            .sum();
+        assert_eq!(moved, 30);
+    }
+
+    #[test]
+    fn timer_carries_fractions_of_a_pixel() {
+        // 3 px/s: 0.3 s is 0.9 px, which moves nothing...
+        let mut timer = ScrollTimer::new(3);
+        assert_eq!(timer.pixels(300_000), 0);
+        // ...until the remainder adds up.
+        assert_eq!(timer.pixels(40_000), 1);
+        assert_eq!(ScrollTimer::new(0).pixels(u32::MAX as u64), 0);
+    }
+
+    #[test]
+    fn timer_duration_covers_the_distance() {
+        let timer = ScrollTimer::new(30);
+        assert_eq!(timer.duration_ms(0), 0);
+        assert_eq!(timer.duration_ms(30), 1000);
+        assert_eq!(timer.duration_ms(31), 1034);
+        assert_eq!(ScrollTimer::new(0).duration_ms(1), u64::MAX);
+    }
+
+    #[test]
+    fn long_text_is_capped() {
+        let font = Font::new();
+        let bytes = [b'A'; MAX_SCROLL_CHARS + 10];
+        let long = core::str::from_utf8(&bytes).unwrap();
+        let scroll = ScrollText::new(&font, long, 1);
+        assert_eq!(
+            scroll.width(),
+            font.text_width(&long[..MAX_SCROLL_CHARS], 1)
+        );
+    }
+
+    #[test]
+    fn scaled_strip_repeats_each_column() {
+        let font = Font::new();
+        let plain = ScrollText::new(&font, "AB", 1);
+        let mut scaled = ScrollText::new(&font, "AB", 1).with_scale(3);
+        assert_eq!(scaled.width(), plain.width() * 3);
+        for _ in 0..MATRIX_WIDTH {
+            scaled.advance();
+        }
+        // The text's left edge is now at panel column 0.
+        for x in 0..scaled.width() {
+            let mut unscaled = ScrollText::new(&font, "AB", 1);
+            for _ in 0..MATRIX_WIDTH + x / 3 {
+                unscaled.advance();
+            }
+            assert_eq!(scaled.column_at(x), unscaled.column_at(0));
+        }
+    }
+
+    #[test]
+    fn spacing_widens_or_closes_the_gaps() {
+        let font = Font::new();
+        for spacing in [0, 3] {
+            let scroll = ScrollText::new_spaced(&font, "HELLO", 1, spacing);
+            assert_eq!(scroll.width(), font.text_width("HELLO", spacing));
+        }
+        let mut scroll = VerticalScroll::new(1).with_spacing(0, Some(2));
+        assert!(scroll.push_line(&font, "A", 0, 1));
+        assert!(scroll.push_line(&font, "B", 0, 2));
+        assert_eq!(scroll.height(), 7 + 2 * 2 + 14);
+        let mut scroll = VerticalScroll::new(1).with_spacing(0, None);
+        scroll.push_line(&font, "II", 0, 1);
+        // "I" is three columns wide in the middle of its cell.
+        assert_eq!(scroll.rows[0], row([1, 2, 3, 6, 7, 8]));
+    }
+
+    #[test]
+    fn lines_stack_with_a_line_gap() {
+        let font = Font::new();
+        let mut scroll = VerticalScroll::new(1);
+        assert!(scroll.push_line(&font, "A", 0, 1));
+        assert!(scroll.push_line(&font, "B", 0, 2));
+        assert_eq!(scroll.height(), 7 + 14 + 14);
+        scroll.advance_by(MATRIX_HEIGHT);
+        // The top row of 'A' is now at the top edge, then the gap.
+        assert!(!scroll.row_at(0).is_empty());
+        assert!((7..21).all(|y| scroll.row_at(y).is_empty()));
+        assert!(!scroll.row_at(21).is_empty());
+        assert!(scroll.row_at(35).is_empty());
+    }
+
+    #[test]
+    fn single_line_enters_at_the_bottom_and_wraps() {
+        let font = Font::new();
+        let mut scroll = VerticalScroll::new(1);
+        scroll.push_line(&font, "I", 4, 1);
+        assert!((0..MATRIX_HEIGHT).all(|y| scroll.row_at(y).is_empty()));
+        scroll.advance();
+        // 'I' lights columns 5..=7 of its top row.
+        assert_eq!(scroll.row_at(MATRIX_HEIGHT - 1), row(5..=7));
+        for _ in 1..scroll.period() {
+            scroll.advance();
+        }
+        assert_eq!(scroll.offset(), 0);
+    }
+
+    #[cfg(feature = "two-panels")]
+    #[test]
+    fn rows_span_the_whole_chained_display() {
+        let font = Font::new();
+        let mut scroll = VerticalScroll::new(1);
+        // Glyphs past column 127, and one running off the right edge.
+        scroll.push_line(&font, "III", MATRIX_WIDTH - 14, 1);
+        scroll.advance();
+        let x = MATRIX_WIDTH - 14;
+        assert_eq!(
+            scroll.row_at(MATRIX_HEIGHT - 1),
+            row((1..=3).chain(7..=9).map(|dx| x + dx))
+        );
+    }
+
+    #[test]
+    fn empty_ticker_stays_blank() {
+        let mut scroll = VerticalScroll::new(3);
+        for _ in 0..2 * MATRIX_HEIGHT {
+            scroll.advance();
+            assert!((0..MATRIX_HEIGHT).all(|y| scroll.row_at(y).is_empty()));
+        }
+    }
+
+    #[test]
+    fn full_strip_refuses_more_lines() {
+        let font = Font::new();
+        let mut scroll = VerticalScroll::new(1);
+        let mut lines = 0;
+        while scroll.push_line(&font, "X", 0, 1) {
+            lines += 1;
+        }
+        assert_eq!(lines, (MAX_VSCROLL_ROWS + 7) / 14);
+        assert!(scroll.height() <= MAX_VSCROLL_ROWS);
+    }
+
+    #[test]
+    fn strip_holds_glyphs_taller_than_a_byte() {
+        // A 1x40 font whose ' ' lights its top and bottom rows.
+        let mut data = [0u8; 4 + 40];
+        data[..4].copy_from_slice(&[1, 40, 1, 0]);
+        data[4] = 0x80;
+        data[4 + 39] = 0x80;
+        let font = Font::from_bytes(&data).unwrap();
+        let mut scroll = ScrollText::new(&font, " ", 1);
+        assert_eq!(scroll.width(), 1);
+        for _ in 0..MATRIX_WIDTH {
+            scroll.advance();
+        }
+        assert_eq!(scroll.column_at(0), 1 | 1 << 39);
+    }
+}
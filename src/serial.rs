@@ -0,0 +1,77 @@
+//! Serial control task — the line commands of
+//! [`esp32_led_matrix::serial_command`] (`TEXT hello`, `CLEAR`, ...),
+//! read from the ESP32-C3's built-in USB serial port, for hosts that
+//! can't or shouldn't reach the display over WiFi.
+//!
+//! Each line becomes the HTTP request that does the same, goes through
+//! [`http_request::dispatch`] and is applied by
+//! [`http_server::apply_changes`], so `TEXT hello` is exactly
+//! `/text?msg=hello`. The answer, `OK` or `ERR` and a reason, is printed
+//! back on the same port, between the log lines. Whoever is at the cable
+//! is trusted: the control password isn't asked for.
+
+use embassy_executor::Spawner;
+use embassy_time::Instant;
+use embedded_io_async::Read;
+use esp_hal::peripherals::USB_DEVICE;
+use esp_hal::usb_serial_jtag::UsbSerialJtag;
+use esp_println::println;
+use esp32_led_matrix::http_request;
+use esp32_led_matrix::serial_command::{self, LineReader, MAX_LINE_LEN, SerialError};
+use log::{debug, info};
+
+use crate::http_server;
+use crate::{DRAW_QUEUE, SCREENSAVER};
+
+/// Bytes read from the port at a time.
+const SERIAL_CHUNK_LEN: usize = 64;
+
+/// Start taking commands on the USB serial port.
+pub fn start_serial(spawner: Spawner, usb: USB_DEVICE<'static>) {
+    spawner.spawn(serial_task(usb)).ok();
+}
+
+/// Read lines from the port forever, running each as it ends.
+#[embassy_executor::task]
+async fn serial_task(usb: USB_DEVICE<'static>) {
+    info!("Serial commands enabled on the USB port");
+    // Output stays with `esp_println`, which logs to the same port.
+    let (mut rx, _tx) = UsbSerialJtag::new(usb).into_async().split();
+    let mut reader = LineReader::new();
+    let mut chunk = [0u8; SERIAL_CHUNK_LEN];
+    loop {
+        let len = match rx.read(&mut chunk).await {
+            Ok(n) => n,
+            Err(e) => {
+                debug!("Serial read error: {:?}", e);
+                continue;
+            }
+        };
+        for &byte in &chunk[..len] {
+            if let Some(line) = reader.push(byte) {
+                run_line(line).await;
+            }
+        }
+    }
+}
+
+/// Run one line as its HTTP request would run, and print how it went.
+async fn run_line(line: Result<heapless::String<MAX_LINE_LEN>, SerialError>) {
+    let request = match line.and_then(|line| serial_command::to_request(&line)) {
+        Ok(request) => request,
+        Err(e) => {
+            println!("ERR {}", e.message());
+            return;
+        }
+    };
+    let response = http_request::dispatch(request.as_bytes());
+    let mut response = http_request::backpressure(response, DRAW_QUEUE.free_capacity());
+    if http_request::is_command(&response) {
+        SCREENSAVER.lock().await.wake(Instant::now().as_millis());
+    }
+    http_server::apply_changes(&mut response).await;
+    match serial_command::reply(response.body) {
+        "OK" => println!("OK"),
+        status => println!("ERR {}", status),
+    }
+}
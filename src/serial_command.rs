@@ -0,0 +1,244 @@
+//! Control over a serial line, for when WiFi isn't there.
+//!
+//! One command per line, the command word in any case:
+//!
+//! | Line                 | Does the same as            |
+//! |----------------------|-----------------------------|
+//! | `TEXT hello`         | `/text?msg=hello`           |
+//! | `CLEAR`              | `/clear`                    |
+//! | `PIXEL x y rrggbb`   | `/pixel?x=&y=&r=&g=&b=`     |
+//! | `FILL rrggbb`        | `/color?hex=rrggbb`         |
+//! | `BRIGHT n`           | `/brightness?level=n`       |
+//!
+//! [`to_request`] turns a line into that HTTP request, so the network
+//! layer can run it through [`dispatch`](crate::http_request::dispatch)
+//! and apply it like any other: a serial command behaves exactly like
+//! its HTTP counterpart. [`LineReader`] cuts the incoming bytes into
+//! lines, and [`reply`] says how it went.
+
+use core::fmt::Write;
+
+use crate::http_request::parse_hex_color;
+
+/// Longest accepted line, without its line ending.
+pub const MAX_LINE_LEN: usize = 256;
+
+/// Capacity of [`to_request`]'s output: a line of text with every byte
+/// percent-escaped, plus the request line around it.
+pub const SERIAL_REQUEST_LEN: usize = 3 * MAX_LINE_LEN + 32;
+
+/// Why a line was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerialError {
+    /// Not one of the commands in the module docs.
+    Unknown,
+    /// A known command with missing or malformed arguments; holds its
+    /// usage.
+    Usage(&'static str),
+    /// Longer than [`MAX_LINE_LEN`].
+    TooLong,
+    /// Not valid UTF-8.
+    NotUtf8,
+}
+
+impl SerialError {
+    /// What to print back.
+    pub fn message(self) -> &'static str {
+        match self {
+            Self::Unknown => "unknown command, try TEXT, CLEAR, PIXEL, FILL or BRIGHT",
+            Self::Usage(usage) => usage,
+            Self::TooLong => "line too long",
+            Self::NotUtf8 => "line is not UTF-8",
+        }
+    }
+}
+
+/// The HTTP request that does what `line` asks. See the module docs.
+pub fn to_request(line: &str) -> Result<heapless::String<SERIAL_REQUEST_LEN>, SerialError> {
+    let line = line.trim();
+    let (word, args) = line.split_once(' ').unwrap_or((line, ""));
+    let mut request = heapless::String::new();
+    // SERIAL_REQUEST_LEN covers the longest line, fully escaped.
+    let _ = if word.eq_ignore_ascii_case("TEXT") {
+        if args.is_empty() {
+            return Err(SerialError::Usage("usage: TEXT message"));
+        }
+        let _ = request.push_str("GET /text?msg=");
+        args.bytes().try_for_each(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                request.push(char::from(b)).map_err(|_| core::fmt::Error)
+            }
+            _ => write!(request, "%{b:02X}"),
+        })
+    } else if word.eq_ignore_ascii_case("CLEAR") && args.is_empty() {
+        request.push_str("GET /clear").map_err(|_| core::fmt::Error)
+    } else if word.eq_ignore_ascii_case("PIXEL") {
+        let mut args = args.split_whitespace();
+        let (Some(x), Some(y), Some(color), None) = (
+            args.next().and_then(|x| x.parse::<usize>().ok()),
+            args.next().and_then(|y| y.parse::<usize>().ok()),
+            args.next().and_then(parse_hex_color),
+            args.next(),
+        ) else {
+            return Err(SerialError::Usage("usage: PIXEL x y rrggbb"));
+        };
+        let [r, g, b] = color;
+        write!(request, "GET /pixel?x={x}&y={y}&r={r}&g={g}&b={b}")
+    } else if word.eq_ignore_ascii_case("FILL") {
+        let hex = args.trim();
+        if parse_hex_color(hex).is_none() {
+            return Err(SerialError::Usage("usage: FILL rrggbb"));
+        }
+        write!(request, "GET /color?hex={}", hex.trim_start_matches('#'))
+    } else if word.eq_ignore_ascii_case("BRIGHT") {
+        let Ok(level) = args.trim().parse::<u8>() else {
+            return Err(SerialError::Usage("usage: BRIGHT 0-255"));
+        };
+        write!(request, "GET /brightness?level={level}")
+    } else {
+        return Err(SerialError::Unknown);
+    };
+    let _ = request.push_str(" HTTP/1.1");
+    Ok(request)
+}
+
+/// How a command that went through `dispatch` and got `body` went:
+/// `OK`, or the HTTP status (`400 Bad Request`) to report as an error.
+pub fn reply(body: &[u8]) -> &str {
+    let status_line = body.split(|&b| b == b'\r').next().unwrap_or_default();
+    let status = core::str::from_utf8(status_line)
+        .unwrap_or_default()
+        .trim_start_matches("HTTP/1.1 ");
+    if status.starts_with("200") {
+        "OK"
+    } else {
+        status
+    }
+}
+
+/// Collects bytes from the serial port into lines.
+#[derive(Debug, Default)]
+pub struct LineReader {
+    line: heapless::Vec<u8, MAX_LINE_LEN>,
+    /// The current line outgrew `line`; it is dropped at its end.
+    overflow: bool,
+}
+
+impl LineReader {
+    /// No bytes read yet.
+    pub const fn new() -> Self {
+        Self {
+            line: heapless::Vec::new(),
+            overflow: false,
+        }
+    }
+
+    /// Add the next byte. At a `\n` this returns the finished line,
+    /// without `\r\n`; blank lines are skipped. A line longer than
+    /// [`MAX_LINE_LEN`] comes out whole as [`SerialError::TooLong`].
+    pub fn push(
+        &mut self,
+        byte: u8,
+    ) -> Option<Result<heapless::String<MAX_LINE_LEN>, SerialError>> {
+        if byte != b'\n' {
+            if self.line.push(byte).is_err() {
+                self.overflow = true;
+            }
+            return None;
+        }
+        let line = core::mem::take(&mut self.line);
+        if core::mem::take(&mut self.overflow) {
+            return Some(Err(SerialError::TooLong));
+        }
+        match heapless::String::from_utf8(line) {
+            Ok(line) if line.trim().is_empty() => None,
+            Ok(mut line) => {
+                if line.ends_with('\r') {
+                    line.pop();
+                }
+                Some(Ok(line))
+            }
+            Err(_) => Some(Err(SerialError::NotUtf8)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::DrawCommand;
+    use crate::http_request::dispatch;
+
+    #[test]
+    fn commands_become_the_matching_requests() {
+        let request = |line| to_request(line).unwrap();
+        assert_eq!(request("CLEAR").as_str(), "GET /clear HTTP/1.1");
+        assert_eq!(
+            request("bright 64").as_str(),
+            "GET /brightness?level=64 HTTP/1.1"
+        );
+        assert_eq!(
+            request("Fill #00ff80").as_str(),
+            "GET /color?hex=00ff80 HTTP/1.1"
+        );
+
+        let text = dispatch(request("TEXT  Hi & bye, 50%").as_bytes());
+        assert_eq!(text.display_text.unwrap().as_str(), " Hi & bye, 50%");
+        let pixel = dispatch(request("PIXEL 3 4 FF0001").as_bytes());
+        assert_eq!(
+            pixel.draw,
+            Some(DrawCommand::Pixel {
+                x: 3,
+                y: 4,
+                color: [0xFFFF, 0, 0x0101],
+            })
+        );
+        assert_eq!(reply(pixel.body), "OK");
+        // Range checks are left to dispatch, as for HTTP.
+        let outside = dispatch(request("PIXEL 300 4 FF0001").as_bytes());
+        assert_eq!(reply(outside.body), "400 Bad Request");
+    }
+
+    #[test]
+    fn bad_lines_are_refused_with_a_reason() {
+        for (line, error) in [
+            ("HELLO", SerialError::Unknown),
+            ("CLEAR now", SerialError::Unknown),
+            ("TEXT", SerialError::Usage("usage: TEXT message")),
+            ("PIXEL 1 2", SerialError::Usage("usage: PIXEL x y rrggbb")),
+            (
+                "PIXEL 1 2 FFFFFF 3",
+                SerialError::Usage("usage: PIXEL x y rrggbb"),
+            ),
+            (
+                "PIXEL -1 2 FFFFFF",
+                SerialError::Usage("usage: PIXEL x y rrggbb"),
+            ),
+            ("FILL red", SerialError::Usage("usage: FILL rrggbb")),
+            ("BRIGHT 256", SerialError::Usage("usage: BRIGHT 0-255")),
+        ] {
+            assert_eq!(to_request(line), Err(error), "{line}");
+        }
+    }
+
+    #[test]
+    fn bytes_are_cut_into_lines() {
+        let mut reader = LineReader::new();
+        let mut lines = heapless::Vec::<_, 5>::new();
+        let mut input = heapless::Vec::<u8, 512>::new();
+        input.extend_from_slice(b"CLEAR\r\n\r\nTEXT a\n").unwrap();
+        input.extend_from_slice(&[b'x'; MAX_LINE_LEN + 1]).unwrap();
+        input.extend_from_slice(b"\n\xFF\nBRIGHT 1\n").unwrap();
+        for &byte in &input {
+            if let Some(line) = reader.push(byte) {
+                lines.push(line).unwrap();
+            }
+        }
+        assert_eq!(lines[0].as_deref(), Ok("CLEAR"));
+        assert_eq!(lines[1].as_deref(), Ok("TEXT a"));
+        assert_eq!(lines[2], Err(SerialError::TooLong));
+        assert_eq!(lines[3], Err(SerialError::NotUtf8));
+        assert_eq!(lines[4].as_deref(), Ok("BRIGHT 1"));
+        assert_eq!(reader.push(b'\n'), None);
+    }
+}
@@ -0,0 +1,285 @@
+//! Simulated panel for host-side testing.
+//!
+//! [`MockPins`] implements [`Pins`] without any GPIO: it watches the pin
+//! levels a [`Panel`](crate::panel::Panel) drives, shifts the six data
+//! lines into model shift registers on each DCLK, decodes LE + DCLK
+//! commands, and rebuilds the picture each latched chain belongs to. A
+//! VSYNC swaps that picture in as [`MockPins::shown`], just as the real
+//! panel swaps its buffers, so a test can draw into a
+//! [`FrameBuffer`](crate::frame_buffer::FrameBuffer), send it through
+//! the whole output path and compare what would light up.
+//!
+//! Built for tests, and with the `simulator` feature for other crates.
+
+use crate::chain_mapper::{self, CHAIN_LEN, ICS_PER_CHAIN, LEDS_PER_IC, SCANLINES};
+use crate::frame_buffer::{Pixel, store_pixel};
+use crate::panel::{ADDRESS_PINS, Command, DATA_PINS, DCLK_PIN, Frame, GCLK_PIN, LE_PIN, Pins};
+use crate::{MATRIX_HEIGHT, MATRIX_WIDTH};
+
+/// PWM values latched for every pixel, indexed `[y][x]`.
+pub type Latched = [[Pixel; MATRIX_WIDTH]; MATRIX_HEIGHT];
+
+/// Shift register contents of each data line, in [`DATA_PINS`] order.
+/// Word `i` is what IC `i` of the chain would latch.
+type Chains = [[u16; ICS_PER_CHAIN]; DATA_PINS.len()];
+
+/// What the simulated panel has been told so far.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Counts {
+    /// Data Latch commands: `(scanline, led)` cycles received.
+    pub latches: usize,
+    /// VSYNC commands.
+    pub vsyncs: usize,
+    /// Scanline addressed when the last VSYNC came.
+    pub vsync_scanline: Option<usize>,
+    /// Reset commands.
+    pub resets: usize,
+    /// Last value written to Configuration1.
+    pub config: Option<u16>,
+    /// GCLK pulses, including the dead-time ones.
+    pub gclk_pulses: u64,
+}
+
+/// Pins that drive a simulated panel instead of GPIO.
+#[derive(Clone)]
+pub struct MockPins {
+    /// Current level of every pin, as a GPIO bit mask.
+    level: u32,
+    /// DCLKs since LE went high.
+    le_pulses: usize,
+    chains: Chains,
+    /// `(scanline, led)` cycles latched since the last VSYNC.
+    cycle: usize,
+    /// The panel's back buffer, as latched so far.
+    back: Latched,
+    /// The panel's front buffer, on screen.
+    shown: Latched,
+    counts: Counts,
+}
+
+impl MockPins {
+    /// All pins low and a black screen.
+    pub const fn new() -> Self {
+        Self {
+            level: 0,
+            le_pulses: 0,
+            chains: [[0; ICS_PER_CHAIN]; DATA_PINS.len()],
+            cycle: 0,
+            back: [[[0; 3]; MATRIX_WIDTH]; MATRIX_HEIGHT],
+            shown: [[[0; 3]; MATRIX_WIDTH]; MATRIX_HEIGHT],
+            counts: Counts {
+                latches: 0,
+                vsyncs: 0,
+                vsync_scanline: None,
+                resets: 0,
+                config: None,
+                gclk_pulses: 0,
+            },
+        }
+    }
+
+    /// The picture on screen: the PWM values latched before the last
+    /// VSYNC, after gamma and brightness.
+    pub fn shown(&self) -> &Latched {
+        &self.shown
+    }
+
+    /// The panel's back buffer: what the next VSYNC will show.
+    pub fn back(&self) -> &Latched {
+        &self.back
+    }
+
+    /// Commands and pulses seen so far.
+    pub fn counts(&self) -> &Counts {
+        &self.counts
+    }
+
+    /// Scanline currently addressed on A0..A3.
+    pub fn scanline(&self) -> usize {
+        ADDRESS_PINS
+            .iter()
+            .enumerate()
+            .map(|(bit, &pin)| usize::from(self.is_high(pin)) << bit)
+            .sum()
+    }
+
+    fn is_high(&self, pin: u8) -> bool {
+        self.level & 1 << pin != 0
+    }
+
+    /// Shift the data lines one bit into every chain.
+    fn shift(&mut self) {
+        for (chain, &pin) in self.chains.iter_mut().zip(&DATA_PINS) {
+            for i in 0..ICS_PER_CHAIN - 1 {
+                chain[i] = chain[i] << 1 | chain[i + 1] >> 15;
+            }
+            let last = &mut chain[ICS_PER_CHAIN - 1];
+            *last = *last << 1 | u16::from(self.level & 1 << pin != 0);
+        }
+    }
+
+    fn command(&mut self, command: Command) {
+        match command {
+            Command::DataLatch => self.latch(),
+            Command::Vsync => {
+                self.shown = self.back;
+                self.cycle = 0;
+                self.counts.vsyncs += 1;
+                self.counts.vsync_scanline = Some(self.scanline());
+            }
+            Command::WriteConfig => {
+                self.counts.config = Some(self.chains[0][ICS_PER_CHAIN - 1]);
+            }
+            Command::Reset => self.counts.resets += 1,
+            Command::PreActive => {}
+        }
+    }
+
+    /// Store the latched chains in the back buffer, at the pixels the
+    /// driver took them from for this cycle.
+    fn latch(&mut self) {
+        self.counts.latches += 1;
+        let cycle = self.cycle % (SCANLINES * LEDS_PER_IC);
+        self.cycle += 1;
+        // Map each chain slot back to its pixel.
+        let mut slots = [[0; 3]; CHAIN_LEN];
+        let (scanline, led) = (cycle / LEDS_PER_IC, cycle % LEDS_PER_IC);
+        chain_mapper::compute_chain_data(scanline, led, &coordinates(), &mut slots);
+        for (slot, &loaded) in slots.iter().enumerate() {
+            let [x, y, _] = store_pixel(loaded);
+            let (first, ic) = if slot < ICS_PER_CHAIN {
+                (0, slot)
+            } else {
+                (3, slot - ICS_PER_CHAIN)
+            };
+            let rgb = core::array::from_fn(|c| self.chains[first + c][ic]);
+            self.back[usize::from(y)][usize::from(x)] = rgb;
+        }
+    }
+}
+
+/// A picture whose pixels hold their own coordinates, to map each chain
+/// slot back to its pixel. Stored values pass through the chain mapper
+/// unchanged once reduced again with [`store_pixel`].
+fn coordinates() -> Frame {
+    let mut coordinates = [[[0; 3]; MATRIX_WIDTH]; MATRIX_HEIGHT];
+    for (y, row) in coordinates.iter_mut().enumerate() {
+        for (x, px) in row.iter_mut().enumerate() {
+            *px = [x as _, y as _, 0];
+        }
+    }
+    coordinates
+}
+
+impl Default for MockPins {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pins for MockPins {
+    fn write(&mut self, high: u32, low: u32) {
+        let before = self.level;
+        self.level = (self.level & !low) | high;
+        let rising = self.level & !before;
+        let falling = before & !self.level;
+
+        if rising & 1 << GCLK_PIN != 0 {
+            self.counts.gclk_pulses += 1;
+        }
+        if rising & 1 << DCLK_PIN != 0 {
+            self.shift();
+            if self.is_high(LE_PIN) {
+                self.le_pulses += 1;
+            }
+        }
+        if falling & 1 << LE_PIN != 0 {
+            if let Some(command) = Command::from_pulses(self.le_pulses) {
+                self.command(command);
+            }
+            self.le_pulses = 0;
+        }
+    }
+
+    fn wait_cycles(&mut self, _cycles: u32) {}
+
+    fn delay_us(&mut self, _us: u32) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bit_stream::chain_data_bits;
+
+    /// Shift and latch one `(scanline, led)` cycle of `data`, as the
+    /// driver does.
+    fn send_chain(pins: &mut MockPins, data: &[[u16; 3]; CHAIN_LEN]) {
+        for bit in chain_data_bits(data) {
+            let (high, low) = bit.gpio_masks(&DATA_PINS, LE_PIN);
+            pins.write(high, low);
+            pins.write(1 << DCLK_PIN, 0);
+            pins.write(0, 1 << DCLK_PIN);
+        }
+        pins.write(0, 1 << LE_PIN);
+    }
+
+    fn send_command(pins: &mut MockPins, command: Command) {
+        pins.write(1 << LE_PIN, 0);
+        for _ in 0..command as u8 {
+            pins.write(1 << DCLK_PIN, 0);
+            pins.write(0, 1 << DCLK_PIN);
+        }
+        pins.write(0, 1 << LE_PIN);
+    }
+
+    #[test]
+    fn latched_chain_lands_on_its_pixels() {
+        let mut pins = MockPins::new();
+        let mut data = [[0; 3]; CHAIN_LEN];
+        data[0] = [1, 2, 3];
+        data[CHAIN_LEN - 1] = [4, 5, 6];
+        send_chain(&mut pins, &data);
+        assert_eq!(pins.counts().latches, 1);
+
+        // Where the driver's first cycle (scanline 0, led 0) reads them.
+        let mut expected = [[0; 3]; CHAIN_LEN];
+        chain_mapper::compute_chain_data(0, 0, &coordinates(), &mut expected);
+        let [x, y, _] = store_pixel(expected[0]).map(usize::from);
+        assert_eq!(pins.back()[y][x], [1, 2, 3]);
+        let [x, y, _] = store_pixel(expected[CHAIN_LEN - 1]).map(usize::from);
+        assert_eq!(pins.back()[y][x], [4, 5, 6]);
+    }
+
+    #[test]
+    fn vsync_shows_the_back_buffer() {
+        let mut pins = MockPins::new();
+        send_chain(&mut pins, &[[7; 3]; CHAIN_LEN]);
+        assert!(pins.shown().iter().flatten().all(|&px| px == [0; 3]));
+
+        pins.write(1 << ADDRESS_PINS[1] | 1 << ADDRESS_PINS[3], 0);
+        send_command(&mut pins, Command::Vsync);
+        let lit = pins.shown().iter().flatten().filter(|&&px| px == [7; 3]);
+        assert_eq!(lit.count(), CHAIN_LEN);
+        assert_eq!(pins.counts().vsyncs, 1);
+        assert_eq!(pins.counts().vsync_scanline, Some(10));
+    }
+
+    #[test]
+    fn other_pulse_counts_are_ignored() {
+        let mut pins = MockPins::new();
+        pins.write(1 << LE_PIN, 0);
+        for _ in 0..3 {
+            pins.write(1 << DCLK_PIN, 0);
+            pins.write(0, 1 << DCLK_PIN);
+        }
+        pins.write(0, 1 << LE_PIN);
+        send_command(&mut pins, Command::Reset);
+        assert_eq!(
+            *pins.counts(),
+            Counts {
+                resets: 1,
+                ..Counts::default()
+            }
+        );
+    }
+}
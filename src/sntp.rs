@@ -0,0 +1,117 @@
+//! SNTP (RFC 4330) client packets, for setting the clock.
+//!
+//! Only what a one-shot query needs: build a client request and pull the
+//! server's transmit time out of the reply. Sub-second precision and
+//! round-trip compensation are skipped — the clock shows minutes.
+
+/// UDP port NTP servers listen on.
+pub const NTP_PORT: u16 = 123;
+
+/// Length of an NTP packet without extensions.
+pub const PACKET_LEN: usize = 48;
+
+/// Seconds from the NTP epoch (1900) to the Unix epoch (1970).
+const NTP_TO_UNIX_SECS: i64 = 2_208_988_800;
+
+/// Version 4, mode 3 (client), no leap warning.
+const CLIENT_HEADER: u8 = (4 << 3) | 3;
+const MODE_SERVER: u8 = 4;
+
+/// Build a client request. `nonce` goes in the transmit timestamp; the
+/// server echoes it back as the originate timestamp, which ties the reply
+/// to this request.
+pub fn request(nonce: u64) -> [u8; PACKET_LEN] {
+    let mut packet = [0u8; PACKET_LEN];
+    packet[0] = CLIENT_HEADER;
+    packet[40..48].copy_from_slice(&nonce.to_be_bytes());
+    packet
+}
+
+/// Unix time (seconds) from a server reply to [`request`]`(nonce)`.
+/// `None` for anything else: wrong mode or length, a mismatched nonce,
+/// or a kiss-o'-death (stratum 0) or unsynchronised (leap = 3) server.
+pub fn parse_reply(packet: &[u8], nonce: u64) -> Option<i64> {
+    if packet.len() < PACKET_LEN {
+        return None;
+    }
+    let leap = packet[0] >> 6;
+    let mode = packet[0] & 0x7;
+    let stratum = packet[1];
+    if mode != MODE_SERVER || leap == 3 || stratum == 0 {
+        return None;
+    }
+    let originate = u64::from_be_bytes(packet[24..32].try_into().ok()?);
+    if originate != nonce {
+        return None;
+    }
+    let seconds = u32::from_be_bytes(packet[40..44].try_into().ok()?) as i64;
+    if seconds == 0 {
+        return None;
+    }
+    // Era 1 starts in 2036; small values belong to it, not to 1900.
+    let seconds = if seconds < 1 << 31 {
+        seconds + (1 << 32)
+    } else {
+        seconds
+    };
+    Some(seconds - NTP_TO_UNIX_SECS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NONCE: u64 = 0x0123_4567_89AB_CDEF;
+
+    /// Server reply echoing `nonce`, transmitting at NTP `seconds`.
+    fn reply(nonce: u64, seconds: u32) -> [u8; PACKET_LEN] {
+        let mut packet = [0u8; PACKET_LEN];
+        packet[0] = (4 << 3) | MODE_SERVER;
+        packet[1] = 2;
+        packet[24..32].copy_from_slice(&nonce.to_be_bytes());
+        packet[40..44].copy_from_slice(&seconds.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn request_is_v4_client_with_nonce() {
+        let packet = request(NONCE);
+        assert_eq!(packet[0], 0x23);
+        assert_eq!(packet[40..48], NONCE.to_be_bytes());
+        assert!(packet[1..40].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn reply_converts_to_unix_time() {
+        // 2024-01-01T00:00:00Z.
+        let unix = 1_704_067_200;
+        let packet = reply(NONCE, (unix + NTP_TO_UNIX_SECS) as u32);
+        assert_eq!(parse_reply(&packet, NONCE), Some(unix));
+    }
+
+    #[test]
+    fn era_one_timestamps_are_after_2036() {
+        let packet = reply(NONCE, 10);
+        assert_eq!(
+            parse_reply(&packet, NONCE),
+            Some((1 << 32) + 10 - NTP_TO_UNIX_SECS)
+        );
+    }
+
+    #[test]
+    fn mismatched_or_bad_replies_are_rejected() {
+        let good = reply(NONCE, 3_913_056_000);
+        assert!(parse_reply(&good, NONCE + 1).is_none());
+        assert!(parse_reply(&good[..40], NONCE).is_none());
+
+        let mut kiss = good;
+        kiss[1] = 0;
+        assert!(parse_reply(&kiss, NONCE).is_none());
+        let mut unsynced = good;
+        unsynced[0] |= 0xC0;
+        assert!(parse_reply(&unsynced, NONCE).is_none());
+        let mut client = good;
+        client[0] = CLIENT_HEADER;
+        assert!(parse_reply(&client, NONCE).is_none());
+    }
+}
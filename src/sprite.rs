@@ -0,0 +1,84 @@
+//! Small bitmaps with a transparent color, for icons and animations.
+//!
+//! A [`Sprite`] borrows its pixels, so an icon set can live in flash as
+//! `const` arrays. [`FrameBuffer::draw_sprite`] stamps one over whatever
+//! is already drawn, leaving the pixels that match its transparent key
+//! alone. Draw each frame of an animation between the firmware's
+//! `LedMatrix::begin_frame` and `commit_frame` and it never tears.
+//!
+//! [`FrameBuffer::draw_sprite`]: crate::frame_buffer::FrameBuffer::draw_sprite
+
+use crate::frame_buffer::Pixel;
+
+/// A `width` x `height` bitmap, row-major like
+/// [`FrameBuffer::draw_bitmap`](crate::frame_buffer::FrameBuffer::draw_bitmap).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sprite<'a> {
+    pub width: usize,
+    pub height: usize,
+    /// `height` rows of `width` pixels. If shorter, the missing rows are
+    /// left out.
+    pub pixels: &'a [Pixel],
+    /// Pixels of exactly this color are not drawn.
+    pub transparent: Option<Pixel>,
+}
+
+impl<'a> Sprite<'a> {
+    /// A sprite without transparency.
+    pub const fn new(width: usize, height: usize, pixels: &'a [Pixel]) -> Self {
+        Self {
+            width,
+            height,
+            pixels,
+            transparent: None,
+        }
+    }
+
+    /// The same sprite, with pixels of `color` left undrawn.
+    pub const fn with_transparent(self, color: Pixel) -> Self {
+        Self {
+            transparent: Some(color),
+            ..self
+        }
+    }
+
+    /// Every pixel to draw, as `(dx, dy, color)` from the top-left
+    /// corner: all of them but the transparent ones.
+    pub fn opaque_pixels(&self) -> impl Iterator<Item = (usize, usize, Pixel)> + '_ {
+        let height = if self.width == 0 { 0 } else { self.height };
+        let rows = self.pixels.chunks(self.width.max(1)).take(height);
+        rows.enumerate().flat_map(move |(dy, row)| {
+            row.iter()
+                .enumerate()
+                .filter(move |&(_, &px)| Some(px) != self.transparent)
+                .map(move |(dx, &px)| (dx, dy, px))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: Pixel = [0xFFFF, 0, 0xFFFF];
+
+    #[test]
+    fn transparent_pixels_are_skipped() {
+        let pixels = [[1, 1, 1], KEY, [2, 2, 2], [3, 3, 3]];
+        let sprite = Sprite::new(2, 2, &pixels).with_transparent(KEY);
+        let opaque: heapless::Vec<_, 4> = sprite.opaque_pixels().collect();
+        assert_eq!(
+            opaque,
+            [(0, 0, [1, 1, 1]), (0, 1, [2, 2, 2]), (1, 1, [3, 3, 3])]
+        );
+        assert_eq!(Sprite::new(2, 2, &pixels).opaque_pixels().count(), 4);
+    }
+
+    #[test]
+    fn empty_sprites_draw_nothing() {
+        let pixels = [[1, 1, 1]; 4];
+        assert_eq!(Sprite::new(0, 2, &pixels).opaque_pixels().count(), 0);
+        assert_eq!(Sprite::new(2, 0, &pixels).opaque_pixels().count(), 0);
+        assert_eq!(Sprite::new(2, 5, &pixels).opaque_pixels().count(), 4);
+    }
+}
@@ -0,0 +1,423 @@
+//! Refresh-rate and memory statistics for `/stats`, and device health
+//! for `/status`.
+//!
+//! [`FrameTimer`] keeps a rolling average of the time between refreshes,
+//! and [`FramePacer`] spaces the refreshes out to a target rate.
+//! [`Stats`] is the snapshot `/stats` reports and [`Status`] the one
+//! `/status` does, both serialised by hand (see [`Stats::to_json`]) so no
+//! JSON crate is needed.
+
+use core::fmt::Write;
+
+use crate::credentials::MAX_SSID_LEN;
+use crate::{MATRIX_HEIGHT, MATRIX_WIDTH};
+
+/// Frames in the rolling average.
+pub const FRAME_WINDOW: usize = 16;
+
+/// Refresh rate the loop paces itself to until `/fps` sets another.
+pub const DEFAULT_TARGET_FPS: u32 = 30;
+
+/// Highest accepted target refresh rate.
+pub const MAX_TARGET_FPS: u32 = 200;
+
+/// Capacity of [`Stats::to_json`]'s output; fits every field at its
+/// maximum value.
+pub const STATS_JSON_LEN: usize = 160;
+
+/// Capacity of [`Status::to_json`]'s output; fits every field at its
+/// maximum, with every SSID character escaped as `\u00XX`.
+pub const STATUS_JSON_LEN: usize = 160 + MAX_SSID_LEN * 6;
+
+/// Rolling average of the last [`FRAME_WINDOW`] frame times.
+#[derive(Debug, Clone)]
+pub struct FrameTimer {
+    /// Frame times in microseconds, oldest overwritten first.
+    frame_us: [u32; FRAME_WINDOW],
+    /// Slot the next frame time goes into.
+    next: usize,
+    /// Slots filled so far, up to [`FRAME_WINDOW`].
+    len: usize,
+}
+
+impl FrameTimer {
+    /// Timer with no frames recorded.
+    pub const fn new() -> Self {
+        Self {
+            frame_us: [0; FRAME_WINDOW],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    /// Record one frame that took `frame_us` microseconds, replacing the
+    /// oldest once the window is full.
+    pub fn record(&mut self, frame_us: u32) {
+        self.frame_us[self.next] = frame_us;
+        self.next = (self.next + 1) % FRAME_WINDOW;
+        self.len = (self.len + 1).min(FRAME_WINDOW);
+    }
+
+    /// Average frame time in microseconds, or `None` before any frame.
+    pub fn average_us(&self) -> Option<u32> {
+        if self.len == 0 {
+            return None;
+        }
+        let sum: u64 = self.frame_us[..self.len]
+            .iter()
+            .map(|&us| u64::from(us))
+            .sum();
+        Some((sum / self.len as u64) as u32)
+    }
+}
+
+impl Default for FrameTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Spaces refreshes a target frame period apart, on a caller-supplied
+/// microsecond clock: after each refresh [`FramePacer::wait_us`] says
+/// how long to sleep until the next frame is due. A loop that falls
+/// behind isn't made to catch up with a burst of frames; the next one
+/// is due a full period after it finally gets there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FramePacer {
+    period_us: u64,
+    /// When the next frame is due, `None` before the first one.
+    next_due_us: Option<u64>,
+}
+
+impl FramePacer {
+    /// Pacer for `fps` frames per second, clamped to
+    /// 1..=[`MAX_TARGET_FPS`].
+    pub const fn new(fps: u32) -> Self {
+        Self {
+            period_us: Self::period_us(fps),
+            next_due_us: None,
+        }
+    }
+
+    /// Switch to `fps` frames per second, clamped like [`FramePacer::new`],
+    /// from the next frame on.
+    pub fn set_target_fps(&mut self, fps: u32) {
+        self.period_us = Self::period_us(fps);
+    }
+
+    /// The target rate, rounded to whole frames per second.
+    pub fn target_fps(&self) -> u32 {
+        ((1_000_000 + self.period_us / 2) / self.period_us) as u32
+    }
+
+    /// A frame finished at `now_us`: how long to sleep until the next
+    /// one is due, or `None` if it is overdue already and the loop is
+    /// not keeping up with the target.
+    pub fn wait_us(&mut self, now_us: u64) -> Option<u64> {
+        let due_us = *self.next_due_us.get_or_insert(now_us);
+        if now_us > due_us {
+            self.next_due_us = Some(now_us + self.period_us);
+            return None;
+        }
+        self.next_due_us = Some(due_us + self.period_us);
+        Some(due_us - now_us)
+    }
+
+    const fn period_us(fps: u32) -> u64 {
+        let fps = if fps == 0 {
+            1
+        } else if fps > MAX_TARGET_FPS {
+            MAX_TARGET_FPS
+        } else {
+            fps
+        };
+        1_000_000 / fps as u64
+    }
+}
+
+impl Default for FramePacer {
+    fn default() -> Self {
+        Self::new(DEFAULT_TARGET_FPS)
+    }
+}
+
+/// Snapshot reported by `/stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    /// Average refresh time in microseconds, `None` until the panel has
+    /// refreshed.
+    pub frame_us: Option<u32>,
+    /// Free heap in bytes.
+    pub free_heap: usize,
+    /// Seconds since boot.
+    pub uptime_secs: u64,
+    /// Failed WiFi connection attempts in a row, 0 while connected.
+    pub wifi_failures: u32,
+}
+
+impl Stats {
+    /// Frames per second (to two decimals) from the average frame time,
+    /// as `(whole, hundredths)`. `None` until the panel has refreshed.
+    pub fn fps(&self) -> Option<(u32, u32)> {
+        let frame_us = self.frame_us.filter(|&us| us > 0)?;
+        let centi_fps = (100_000_000 + u64::from(frame_us) / 2) / u64::from(frame_us);
+        Some(((centi_fps / 100) as u32, (centi_fps % 100) as u32))
+    }
+
+    /// The snapshot as a JSON object, e.g.
+    /// `{"fps":12.50,"frame_us":80000,"free_heap":40960,"uptime_s":42,"wifi_failures":0}`.
+    /// `fps` and `frame_us` are `null` until the panel has refreshed.
+    pub fn to_json(&self) -> heapless::String<STATS_JSON_LEN> {
+        let mut json = heapless::String::new();
+        // STATS_JSON_LEN covers the longest possible output.
+        let _ = json.push_str("{\"fps\":");
+        let _ = match self.fps() {
+            Some((whole, hundredths)) => write!(json, "{whole}.{hundredths:02}"),
+            None => write!(json, "null"),
+        };
+        let _ = json.push_str(",\"frame_us\":");
+        let _ = match self.frame_us {
+            Some(us) => write!(json, "{us}"),
+            None => write!(json, "null"),
+        };
+        let _ = write!(
+            json,
+            ",\"free_heap\":{},\"uptime_s\":{},\"wifi_failures\":{}}}",
+            self.free_heap, self.uptime_secs, self.wifi_failures
+        );
+        json
+    }
+}
+
+/// Snapshot reported by `/status`. Values the device doesn't know yet,
+/// e.g. before WiFi has joined, are `None` and reported as `null`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status<'a> {
+    /// Seconds since boot.
+    pub uptime_secs: u64,
+    /// Signal strength of the joined access point, in dBm.
+    pub rssi: Option<i8>,
+    /// Free heap in bytes.
+    pub free_heap: usize,
+    /// Network the station has joined.
+    pub ssid: Option<&'a str>,
+    /// The station's IPv4 address, dotted.
+    pub ip: Option<&'a str>,
+    /// [`DisplayMode::name`](crate::mode::DisplayMode::name) of what the
+    /// display shows.
+    pub mode: &'static str,
+}
+
+impl Status<'_> {
+    /// The snapshot as a JSON object, with the display's size, e.g.
+    /// `{"uptime_s":42,"rssi":-61,"free_heap":40960,"ssid":"home","ip":"192.168.1.20","mode":"text","width":88,"height":88}`.
+    pub fn to_json(&self) -> heapless::String<STATUS_JSON_LEN> {
+        let mut json = heapless::String::new();
+        // STATUS_JSON_LEN covers the longest possible output.
+        let _ = write!(json, "{{\"uptime_s\":{},\"rssi\":", self.uptime_secs);
+        let _ = match self.rssi {
+            Some(rssi) => write!(json, "{rssi}"),
+            None => write!(json, "null"),
+        };
+        let _ = write!(json, ",\"free_heap\":{},\"ssid\":", self.free_heap);
+        push_json_string(&mut json, self.ssid);
+        let _ = json.push_str(",\"ip\":");
+        push_json_string(&mut json, self.ip);
+        let _ = json.push_str(",\"mode\":");
+        push_json_string(&mut json, Some(self.mode));
+        let _ = write!(
+            json,
+            ",\"width\":{MATRIX_WIDTH},\"height\":{MATRIX_HEIGHT}}}"
+        );
+        json
+    }
+}
+
+/// Append `value` as a quoted, escaped JSON string, or `null`. SSIDs (and
+/// display text, for [`crate::events`]) may hold any character.
+pub(crate) fn push_json_string<const N: usize>(
+    json: &mut heapless::String<N>,
+    value: Option<&str>,
+) {
+    let Some(value) = value else {
+        let _ = json.push_str("null");
+        return;
+    };
+    let _ = json.push('"');
+    for ch in value.chars() {
+        let _ = match ch {
+            '"' | '\\' => write!(json, "\\{ch}"),
+            ch if ch.is_control() => write!(json, "\\u{:04x}", ch as u32),
+            ch => json.push(ch).map_err(|_| core::fmt::Error),
+        };
+    }
+    let _ = json.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `json` closed off with the display size [`Status::to_json`] ends
+    /// with.
+    fn with_size(json: &str) -> heapless::String<STATUS_JSON_LEN> {
+        let mut full = heapless::String::new();
+        write!(
+            full,
+            "{json},\"width\":{MATRIX_WIDTH},\"height\":{MATRIX_HEIGHT}}}"
+        )
+        .unwrap();
+        full
+    }
+
+    #[test]
+    fn empty_timer_has_no_average() {
+        assert_eq!(FrameTimer::new().average_us(), None);
+    }
+
+    #[test]
+    fn average_covers_only_the_last_window() {
+        let mut timer = FrameTimer::new();
+        timer.record(100);
+        timer.record(300);
+        assert_eq!(timer.average_us(), Some(200));
+        // A full window of 1000 µs frames pushes the early ones out.
+        for _ in 0..FRAME_WINDOW {
+            timer.record(1000);
+        }
+        assert_eq!(timer.average_us(), Some(1000));
+    }
+
+    #[test]
+    fn pacer_sleeps_only_what_is_left_of_the_period() {
+        let mut pacer = FramePacer::new(100);
+        assert_eq!(pacer.target_fps(), 100);
+        // The first frame is due straight away, the next 10 ms later.
+        assert_eq!(pacer.wait_us(1_000), Some(0));
+        assert_eq!(pacer.wait_us(5_000), Some(6_000));
+        assert_eq!(pacer.wait_us(11_000), Some(10_000));
+        assert_eq!(pacer.wait_us(31_000), Some(0));
+    }
+
+    #[test]
+    fn pacer_reports_falling_behind_without_catching_up() {
+        let mut pacer = FramePacer::new(100);
+        assert_eq!(pacer.wait_us(0), Some(0));
+        assert_eq!(pacer.wait_us(25_000), None);
+        // Due a full period after the late frame, not in a burst.
+        assert_eq!(pacer.wait_us(30_000), Some(5_000));
+    }
+
+    #[test]
+    fn pacer_target_is_clamped() {
+        let mut pacer = FramePacer::new(0);
+        assert_eq!(pacer.target_fps(), 1);
+        pacer.set_target_fps(10_000);
+        assert_eq!(pacer.target_fps(), MAX_TARGET_FPS);
+        pacer.set_target_fps(30);
+        assert_eq!(pacer.target_fps(), 30);
+    }
+
+    #[test]
+    fn fps_is_rounded_to_hundredths() {
+        let stats = Stats {
+            frame_us: Some(80_000),
+            free_heap: 0,
+            uptime_secs: 0,
+            wifi_failures: 0,
+        };
+        assert_eq!(stats.fps(), Some((12, 50)));
+        let stats = Stats {
+            frame_us: Some(3_000),
+            ..stats
+        };
+        assert_eq!(stats.fps(), Some((333, 33)));
+    }
+
+    #[test]
+    fn json_reports_every_field() {
+        let stats = Stats {
+            frame_us: Some(80_000),
+            free_heap: 40_960,
+            uptime_secs: 42,
+            wifi_failures: 3,
+        };
+        assert_eq!(
+            stats.to_json().as_str(),
+            r#"{"fps":12.50,"frame_us":80000,"free_heap":40960,"uptime_s":42,"wifi_failures":3}"#
+        );
+    }
+
+    #[test]
+    fn json_uses_null_before_first_frame() {
+        let stats = Stats {
+            frame_us: None,
+            free_heap: 1,
+            uptime_secs: 0,
+            wifi_failures: 0,
+        };
+        assert_eq!(
+            stats.to_json().as_str(),
+            r#"{"fps":null,"frame_us":null,"free_heap":1,"uptime_s":0,"wifi_failures":0}"#
+        );
+    }
+
+    #[test]
+    fn status_json_escapes_the_ssid_and_nulls_unknowns() {
+        let status = Status {
+            uptime_secs: 42,
+            rssi: Some(-61),
+            free_heap: 40_960,
+            ssid: Some("Cafe \"Wifi\"\\\n"),
+            ip: Some("192.168.1.20"),
+            mode: "clock",
+        };
+        assert_eq!(
+            status.to_json().as_str(),
+            with_size(
+                r#"{"uptime_s":42,"rssi":-61,"free_heap":40960,"ssid":"Cafe \"Wifi\"\\\u000a","ip":"192.168.1.20","mode":"clock""#
+            )
+        );
+        let status = Status {
+            rssi: None,
+            ssid: None,
+            ip: None,
+            ..status
+        };
+        assert_eq!(
+            status.to_json().as_str(),
+            with_size(
+                r#"{"uptime_s":42,"rssi":null,"free_heap":40960,"ssid":null,"ip":null,"mode":"clock""#
+            )
+        );
+    }
+
+    #[test]
+    fn status_json_fits_the_largest_values() {
+        let ssid = [b'\n'; MAX_SSID_LEN];
+        let status = Status {
+            uptime_secs: u64::MAX,
+            rssi: Some(i8::MIN),
+            free_heap: usize::MAX,
+            ssid: Some(core::str::from_utf8(&ssid).unwrap()),
+            ip: Some("255.255.255.255"),
+            mode: "animation",
+        };
+        assert!(
+            status
+                .to_json()
+                .ends_with(with_size(r#""animation""#).as_str())
+        );
+    }
+
+    #[test]
+    fn json_fits_the_largest_values() {
+        let stats = Stats {
+            frame_us: Some(1),
+            free_heap: usize::MAX,
+            uptime_secs: u64::MAX,
+            wifi_failures: u32::MAX,
+        };
+        assert!(stats.to_json().ends_with("}"));
+    }
+}
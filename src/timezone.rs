@@ -0,0 +1,427 @@
+//! POSIX `TZ` strings, for showing local time on the clock.
+//!
+//! Supports the format used by glibc and ESP-IDF:
+//! `std offset [dst [offset] [,start[/time],end[/time]]]`, e.g.
+//! `EET-2EEST,M3.5.0/3,M10.5.0/4` for Helsinki. Names may be quoted
+//! (`<+03>-3`). Rule dates can be `Mm.w.d`, `Jn` or `n`; a zone with a
+//! DST name but no rules uses the US rules, like glibc.
+//!
+//! POSIX offsets count hours *west* of UTC (`EET-2` is UTC+2). Everything
+//! here stores seconds *east*, so local time is `utc + offset`.
+
+/// Longest TZ string accepted.
+pub const MAX_TZ_LEN: usize = 48;
+
+/// TZ string used until one is saved.
+pub const DEFAULT_TZ: &str = "UTC0";
+
+const SECS_PER_DAY: i64 = 86_400;
+
+/// Rule time when a rule doesn't give one: 02:00 local.
+const DEFAULT_RULE_TIME: i32 = 2 * 3600;
+
+/// A parsed time zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeZone {
+    /// Standard time offset, seconds east of UTC.
+    std_offset: i32,
+    dst: Option<Dst>,
+}
+
+/// Daylight saving time: its offset and when it starts and ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Dst {
+    /// Seconds east of UTC.
+    offset: i32,
+    /// Given in standard local time.
+    start: Rule,
+    /// Given in daylight local time.
+    end: Rule,
+}
+
+/// One transition: a day of the year and a local time on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rule {
+    date: RuleDate,
+    /// Seconds after local midnight. May be negative or past 24 h.
+    time: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RuleDate {
+    /// `Mm.w.d`: weekday `d` (0 = Sunday) of week `w` (1..=5, 5 = last)
+    /// of month `m`.
+    MonthWeekDay { month: u8, week: u8, weekday: u8 },
+    /// `Jn`: day 1..=365, never counting February 29.
+    Julian(u16),
+    /// `n`: zero-based day 0..=365, counting February 29.
+    DayOfYear(u16),
+}
+
+/// US rules (second Sunday in March to first Sunday in November), the
+/// glibc default when a DST name has no rules.
+const US_DST_START: Rule = Rule {
+    date: RuleDate::MonthWeekDay {
+        month: 3,
+        week: 2,
+        weekday: 0,
+    },
+    time: DEFAULT_RULE_TIME,
+};
+const US_DST_END: Rule = Rule {
+    date: RuleDate::MonthWeekDay {
+        month: 11,
+        week: 1,
+        weekday: 0,
+    },
+    time: DEFAULT_RULE_TIME,
+};
+
+impl TimeZone {
+    /// Coordinated Universal Time.
+    pub const UTC: Self = Self {
+        std_offset: 0,
+        dst: None,
+    };
+
+    /// Parse a POSIX TZ string. `None` if it is malformed or longer than
+    /// [`MAX_TZ_LEN`].
+    pub fn parse(tz: &str) -> Option<Self> {
+        if tz.len() > MAX_TZ_LEN {
+            return None;
+        }
+        let mut p = Parser {
+            rest: tz.as_bytes(),
+        };
+        p.name()?;
+        let std_offset = -p.offset()?;
+        if p.rest.is_empty() {
+            return Some(Self {
+                std_offset,
+                dst: None,
+            });
+        }
+
+        p.name()?;
+        let offset = match p.rest.first() {
+            None | Some(b',') => std_offset + 3600,
+            Some(_) => -p.offset()?,
+        };
+        let (start, end) = if p.eat(b',') {
+            let start = p.rule()?;
+            if !p.eat(b',') {
+                return None;
+            }
+            (start, p.rule()?)
+        } else {
+            (US_DST_START, US_DST_END)
+        };
+        if !p.rest.is_empty() {
+            return None;
+        }
+        Some(Self {
+            std_offset,
+            dst: Some(Dst { offset, start, end }),
+        })
+    }
+
+    /// Offset from UTC in seconds (east positive) at Unix time `unix`.
+    pub fn utc_offset(&self, unix: i64) -> i32 {
+        let Some(dst) = self.dst else {
+            return self.std_offset;
+        };
+        let local_days = (unix + self.std_offset as i64).div_euclid(SECS_PER_DAY);
+        let year = civil_from_days(local_days).0;
+        let start = dst.start.local_secs(year) - self.std_offset as i64;
+        let end = dst.end.local_secs(year) - dst.offset as i64;
+        let in_dst = if start < end {
+            start <= unix && unix < end
+        } else {
+            // Southern hemisphere: DST spans the new year.
+            !(end <= unix && unix < start)
+        };
+        if in_dst { dst.offset } else { self.std_offset }
+    }
+
+    /// Local `(hour, minute, second)` at Unix time `unix`.
+    pub fn time_of_day(&self, unix: i64) -> (u8, u8, u8) {
+        let secs = (unix + self.utc_offset(unix) as i64).rem_euclid(SECS_PER_DAY);
+        (
+            (secs / 3600) as u8,
+            (secs / 60 % 60) as u8,
+            (secs % 60) as u8,
+        )
+    }
+}
+
+impl Rule {
+    /// The transition in `year`, as seconds since the epoch in local
+    /// time.
+    fn local_secs(&self, year: i64) -> i64 {
+        let jan1 = days_from_civil(year, 1, 1);
+        let day = match self.date {
+            RuleDate::MonthWeekDay {
+                month,
+                week,
+                weekday,
+            } => {
+                let first = days_from_civil(year, month as u32, 1);
+                let next = if month == 12 {
+                    days_from_civil(year + 1, 1, 1)
+                } else {
+                    days_from_civil(year, month as u32 + 1, 1)
+                };
+                let mut day = first
+                    + (weekday as i64 - weekday_of(first)).rem_euclid(7)
+                    + (week as i64 - 1) * 7;
+                // Week 5 means "last", which may be the fourth.
+                while day >= next {
+                    day -= 7;
+                }
+                day
+            }
+            RuleDate::Julian(n) => {
+                let n = n as i64;
+                jan1 + n - 1 + i64::from(is_leap(year) && n >= 60)
+            }
+            RuleDate::DayOfYear(n) => jan1 + n as i64,
+        };
+        day * SECS_PER_DAY + self.time as i64
+    }
+}
+
+/// Byte cursor over a TZ string.
+struct Parser<'a> {
+    rest: &'a [u8],
+}
+
+impl Parser<'_> {
+    fn eat(&mut self, byte: u8) -> bool {
+        if self.rest.first() == Some(&byte) {
+            self.rest = &self.rest[1..];
+            true
+        } else {
+            false
+        }
+    }
+
+    /// A zone name: three or more letters, or anything in `<...>`.
+    fn name(&mut self) -> Option<()> {
+        if self.eat(b'<') {
+            let end = self.rest.iter().position(|&b| b == b'>')?;
+            self.rest = &self.rest[end + 1..];
+            return (end >= 3).then_some(());
+        }
+        let len = self
+            .rest
+            .iter()
+            .take_while(|b| b.is_ascii_alphabetic())
+            .count();
+        self.rest = &self.rest[len..];
+        (len >= 3).then_some(())
+    }
+
+    /// An unsigned run of digits.
+    fn number(&mut self) -> Option<i32> {
+        let len = self.rest.iter().take_while(|b| b.is_ascii_digit()).count();
+        if len == 0 || len > 3 {
+            return None;
+        }
+        let digits = core::str::from_utf8(&self.rest[..len]).ok()?;
+        self.rest = &self.rest[len..];
+        digits.parse().ok()
+    }
+
+    /// `[+-]hh[:mm[:ss]]` in seconds, POSIX sign (west positive).
+    fn offset(&mut self) -> Option<i32> {
+        let sign = if self.eat(b'-') {
+            -1
+        } else {
+            self.eat(b'+');
+            1
+        };
+        let hours = self.number()?;
+        let mut secs = hours * 3600;
+        if self.eat(b':') {
+            let minutes = self.number().filter(|m| *m < 60)?;
+            secs += minutes * 60;
+            if self.eat(b':') {
+                secs += self.number().filter(|s| *s < 60)?;
+            }
+        }
+        // Offsets are within ±24 h; rule times (shared syntax) within ±167 h.
+        (hours <= 167).then_some(sign * secs)
+    }
+
+    /// `date[/time]`.
+    fn rule(&mut self) -> Option<Rule> {
+        let date = if self.eat(b'M') {
+            let month = self.number().filter(|m| (1..=12).contains(m))?;
+            if !self.eat(b'.') {
+                return None;
+            }
+            let week = self.number().filter(|w| (1..=5).contains(w))?;
+            if !self.eat(b'.') {
+                return None;
+            }
+            let weekday = self.number().filter(|d| (0..=6).contains(d))?;
+            RuleDate::MonthWeekDay {
+                month: month as u8,
+                week: week as u8,
+                weekday: weekday as u8,
+            }
+        } else if self.eat(b'J') {
+            RuleDate::Julian(self.number().filter(|n| (1..=365).contains(n))? as u16)
+        } else {
+            RuleDate::DayOfYear(self.number().filter(|n| (0..=365).contains(n))? as u16)
+        };
+        let time = if self.eat(b'/') {
+            self.offset()?
+        } else {
+            DEFAULT_RULE_TIME
+        };
+        Some(Rule { date, time })
+    }
+}
+
+fn is_leap(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+/// Day of the week of `days` since the epoch, 0 = Sunday.
+fn weekday_of(days: i64) -> i64 {
+    // 1970-01-01 was a Thursday.
+    (days + 4).rem_euclid(7)
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date (Howard Hinnant's
+/// `days_from_civil`).
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// `(year, month, day)` of `days` since 1970-01-01; inverse of
+/// [`days_from_civil`].
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELSINKI: &str = "EET-2EEST,M3.5.0/3,M10.5.0/4";
+
+    /// Unix time of a UTC date and time.
+    fn utc(year: i64, month: u32, day: u32, hour: i64, minute: i64) -> i64 {
+        days_from_civil(year, month, day) * SECS_PER_DAY + hour * 3600 + minute * 60
+    }
+
+    #[test]
+    fn civil_date_round_trips() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        for days in [-1, 0, 59, 11_016, 19_782, 50_000] {
+            let (y, m, d) = civil_from_days(days);
+            assert_eq!(days_from_civil(y, m, d), days);
+        }
+    }
+
+    #[test]
+    fn plain_offsets() {
+        assert_eq!(TimeZone::parse("UTC0"), Some(TimeZone::UTC));
+        let tz = TimeZone::parse("<+0530>-5:30").unwrap();
+        assert_eq!(tz.utc_offset(0), 5 * 3600 + 30 * 60);
+        let tz = TimeZone::parse("HST10").unwrap();
+        assert_eq!(tz.time_of_day(utc(2024, 6, 1, 3, 15)), (17, 15, 0));
+    }
+
+    #[test]
+    fn helsinki_switches_at_eu_transitions() {
+        let tz = TimeZone::parse(HELSINKI).unwrap();
+        // 2024: DST from 31 March 01:00 UTC to 27 October 01:00 UTC.
+        assert_eq!(tz.utc_offset(utc(2024, 3, 31, 0, 59)), 2 * 3600);
+        assert_eq!(tz.utc_offset(utc(2024, 3, 31, 1, 0)), 3 * 3600);
+        assert_eq!(tz.utc_offset(utc(2024, 10, 27, 0, 59)), 3 * 3600);
+        assert_eq!(tz.utc_offset(utc(2024, 10, 27, 1, 0)), 2 * 3600);
+        assert_eq!(tz.time_of_day(utc(2024, 7, 1, 21, 30)), (0, 30, 0));
+    }
+
+    #[test]
+    fn dst_without_rules_uses_us_rules() {
+        let tz = TimeZone::parse("EST5EDT").unwrap();
+        // 2024: 10 March 07:00 UTC to 3 November 06:00 UTC.
+        assert_eq!(tz.utc_offset(utc(2024, 3, 10, 6, 59)), -5 * 3600);
+        assert_eq!(tz.utc_offset(utc(2024, 3, 10, 7, 0)), -4 * 3600);
+        assert_eq!(tz.utc_offset(utc(2024, 11, 3, 6, 0)), -5 * 3600);
+    }
+
+    #[test]
+    fn southern_hemisphere_dst_spans_new_year() {
+        let tz = TimeZone::parse("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert_eq!(tz.utc_offset(utc(2024, 1, 15, 0, 0)), 11 * 3600);
+        assert_eq!(tz.utc_offset(utc(2024, 6, 15, 0, 0)), 10 * 3600);
+        assert_eq!(tz.utc_offset(utc(2024, 12, 15, 0, 0)), 11 * 3600);
+    }
+
+    #[test]
+    fn julian_rule_dates() {
+        // J60 is always 1 March; 59 is 1 March only in leap years.
+        let j = Rule {
+            date: RuleDate::Julian(60),
+            time: 0,
+        };
+        let n = Rule {
+            date: RuleDate::DayOfYear(59),
+            time: 0,
+        };
+        assert_eq!(
+            j.local_secs(2024),
+            days_from_civil(2024, 3, 1) * SECS_PER_DAY
+        );
+        assert_eq!(
+            j.local_secs(2023),
+            days_from_civil(2023, 3, 1) * SECS_PER_DAY
+        );
+        assert_eq!(
+            n.local_secs(2024),
+            days_from_civil(2024, 2, 29) * SECS_PER_DAY
+        );
+        assert_eq!(
+            n.local_secs(2023),
+            days_from_civil(2023, 3, 1) * SECS_PER_DAY
+        );
+    }
+
+    #[test]
+    fn malformed_strings_are_rejected() {
+        for tz in [
+            "",
+            "UT0",
+            "UTC",
+            "EET-2EEST,M3.5.0",
+            "EET-2EEST,M13.5.0,M10.5.0",
+            "EET-2EEST,M3.6.0,M10.5.0",
+            "EET-2EEST,M3.5.0/3,M10.5.0/4x",
+            "<+03-3",
+            "CET-1:60",
+        ] {
+            assert!(TimeZone::parse(tz).is_none(), "{tz}");
+        }
+    }
+}
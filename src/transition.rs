@@ -0,0 +1,135 @@
+//! Animated transitions between two frames.
+//!
+//! A [`Transition`] turns the time since it started into a progress value
+//! (0 = old frame, 255 = new frame), and [`Transition::mix`] picks each
+//! pixel's color at that point: a cross-fade, or a left-to-right wipe.
+//! The driver mixes the front (old) and back (new) buffers this way on
+//! every refresh until the transition is done, so it needs no third frame
+//! buffer.
+
+use crate::MATRIX_WIDTH;
+use crate::frame_buffer::{Pixel, blend};
+
+/// Transition length when `/text` has no `ms`, in milliseconds.
+pub const DEFAULT_TRANSITION_MS: u32 = 500;
+
+/// Longest accepted transition, in milliseconds.
+pub const MAX_TRANSITION_MS: u32 = 10_000;
+
+/// How the new frame replaces the old one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransitionKind {
+    /// Cross-fade every pixel from the old color to the new one.
+    Fade,
+    /// Reveal the new frame column by column, left to right.
+    Wipe,
+}
+
+impl TransitionKind {
+    /// Kind for a `/text?transition=` name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fade" => Some(Self::Fade),
+            "wipe" => Some(Self::Wipe),
+            _ => None,
+        }
+    }
+}
+
+/// A transition and how long it takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transition {
+    pub kind: TransitionKind,
+    /// Time from the old frame to the new one, in milliseconds.
+    pub duration_ms: u32,
+}
+
+impl Transition {
+    /// A `kind` transition lasting `duration_ms` milliseconds.
+    pub const fn new(kind: TransitionKind, duration_ms: u32) -> Self {
+        Self { kind, duration_ms }
+    }
+
+    /// Progress `elapsed_ms` after the start: 0 at the start, 255 once
+    /// `duration_ms` has passed (immediately for a zero duration).
+    pub fn progress(&self, elapsed_ms: u64) -> u8 {
+        let duration = u64::from(self.duration_ms);
+        if elapsed_ms >= duration {
+            return u8::MAX;
+        }
+        (elapsed_ms * 255 / duration) as u8
+    }
+
+    /// Color of the pixel in column `x` at `progress`, between its color
+    /// `from` in the old frame and `to` in the new one.
+    pub fn mix(&self, from: Pixel, to: Pixel, x: usize, progress: u8) -> Pixel {
+        match self.kind {
+            TransitionKind::Fade => blend(from, to, progress),
+            TransitionKind::Wipe => {
+                if x < usize::from(progress) * MATRIX_WIDTH / 255 {
+                    to
+                } else {
+                    from
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FROM: Pixel = [0, 0, 0];
+    const TO: Pixel = [0xFFFF, 0x8000, 0];
+
+    #[test]
+    fn names_map_to_kinds() {
+        assert_eq!(
+            TransitionKind::from_name("fade"),
+            Some(TransitionKind::Fade)
+        );
+        assert_eq!(
+            TransitionKind::from_name("wipe"),
+            Some(TransitionKind::Wipe)
+        );
+        assert_eq!(TransitionKind::from_name("dissolve"), None);
+    }
+
+    #[test]
+    fn progress_runs_from_zero_to_full() {
+        let t = Transition::new(TransitionKind::Fade, 1000);
+        assert_eq!(t.progress(0), 0);
+        assert_eq!(t.progress(500), 127);
+        assert_eq!(t.progress(999), 254);
+        assert_eq!(t.progress(1000), 255);
+        assert_eq!(t.progress(u64::MAX), 255);
+    }
+
+    #[test]
+    fn zero_duration_is_instant() {
+        let t = Transition::new(TransitionKind::Wipe, 0);
+        assert_eq!(t.progress(0), 255);
+    }
+
+    #[test]
+    fn fade_blends_every_column_alike() {
+        let t = Transition::new(TransitionKind::Fade, 1000);
+        for x in [0, MATRIX_WIDTH - 1] {
+            assert_eq!(t.mix(FROM, TO, x, 0), FROM);
+            assert_eq!(t.mix(FROM, TO, x, 255), TO);
+            assert_eq!(t.mix(FROM, TO, x, 51), [0x3333, 0x199A, 0]);
+        }
+    }
+
+    #[test]
+    fn wipe_reveals_columns_left_to_right() {
+        let t = Transition::new(TransitionKind::Wipe, 1000);
+        assert_eq!(t.mix(FROM, TO, 0, 0), FROM);
+        // Halfway: the left half is new, the rest still old.
+        assert_eq!(t.mix(FROM, TO, MATRIX_WIDTH / 2 - 1, 128), TO);
+        assert_eq!(t.mix(FROM, TO, MATRIX_WIDTH / 2, 128), FROM);
+        assert_eq!(t.mix(FROM, TO, MATRIX_WIDTH - 1, 254), FROM);
+        assert_eq!(t.mix(FROM, TO, MATRIX_WIDTH - 1, 255), TO);
+    }
+}
@@ -0,0 +1,602 @@
+//! WebSocket (RFC 6455) server side for `/ws` live frames.
+//!
+//! The handshake answer ([`accept_key`]) and a streaming frame
+//! [`Decoder`] that reassembles fragmented binary messages into one
+//! [`IMAGE_LEN`]-byte RGB565 frame, passes short text messages on as
+//! commands (e.g. `left` to steer the Snake game), answers pings and
+//! reports anything else as [`Malformed`] so the caller can log it and
+//! carry on. SHA-1
+//! and base64 are implemented here for the handshake alone.
+
+use crate::http_request::IMAGE_LEN;
+
+/// Appended to the client's key before hashing, per RFC 6455.
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Length of a `Sec-WebSocket-Accept` value (base64 of 20 bytes).
+pub const ACCEPT_KEY_LEN: usize = 28;
+
+/// Longest `Sec-WebSocket-Key` accepted. Real keys are 24 characters.
+pub const MAX_KEY_LEN: usize = 64;
+
+/// Longest text message passed on; longer ones are dropped.
+pub const MAX_TEXT_LEN: usize = 16;
+
+/// Longest control frame payload allowed by the protocol.
+pub const MAX_CONTROL_LEN: usize = 125;
+
+/// Longest frame header: 2 bytes, a 64-bit length and a mask.
+const MAX_HEADER_LEN: usize = 14;
+
+/// Frame opcodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Continuation = 0x0,
+    Text = 0x1,
+    Binary = 0x2,
+    Close = 0x8,
+    Ping = 0x9,
+    Pong = 0xA,
+}
+
+impl Opcode {
+    fn from_bits(bits: u8) -> Option<Self> {
+        match bits {
+            0x0 => Some(Self::Continuation),
+            0x1 => Some(Self::Text),
+            0x2 => Some(Self::Binary),
+            0x8 => Some(Self::Close),
+            0x9 => Some(Self::Ping),
+            0xA => Some(Self::Pong),
+            _ => None,
+        }
+    }
+
+    fn is_control(self) -> bool {
+        self as u8 & 0x8 != 0
+    }
+}
+
+/// Why a message was dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Malformed {
+    /// A binary message of this many bytes instead of [`IMAGE_LEN`].
+    WrongLength(u64),
+    /// A text message of this many bytes, over [`MAX_TEXT_LEN`].
+    TextTooLong(u64),
+    /// A text message that isn't valid UTF-8.
+    NotUtf8,
+    /// A reserved opcode.
+    UnknownOpcode(u8),
+    /// A continuation frame with no message to continue.
+    UnexpectedContinuation,
+    /// A new message started before the previous one's final fragment.
+    Interrupted,
+    /// A fragmented control frame, or one over [`MAX_CONTROL_LEN`].
+    BadControl,
+}
+
+/// What a [`Decoder::feed`] call found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A complete image is in the frame buffer.
+    Frame,
+    /// A text message.
+    Text(heapless::String<MAX_TEXT_LEN>),
+    /// Answer with a pong carrying this payload.
+    Ping(heapless::Vec<u8, MAX_CONTROL_LEN>),
+    /// The client is closing the connection.
+    Close,
+    /// A message was dropped; the connection stays open.
+    Dropped(Malformed),
+}
+
+/// `Sec-WebSocket-Accept` value for the client's `Sec-WebSocket-Key`.
+pub fn accept_key(key: &str) -> heapless::String<ACCEPT_KEY_LEN> {
+    let mut input = heapless::Vec::<u8, { MAX_KEY_LEN + GUID.len() }>::new();
+    let _ = input.extend_from_slice(key.as_bytes());
+    let _ = input.extend_from_slice(GUID.as_bytes());
+    base64(&sha1(&input))
+}
+
+/// End of the `101 Switching Protocols` headers: the accept value and
+/// the blank line.
+pub fn handshake_end(accept: &str) -> heapless::String<{ ACCEPT_KEY_LEN + 4 }> {
+    let mut end = heapless::String::new();
+    let _ = end.push_str(accept);
+    let _ = end.push_str("\r\n\r\n");
+    end
+}
+
+/// An unmasked, unfragmented server control frame, e.g. a pong.
+/// `payload` is cut to [`MAX_CONTROL_LEN`].
+pub fn control_frame(opcode: Opcode, payload: &[u8]) -> heapless::Vec<u8, { MAX_CONTROL_LEN + 2 }> {
+    let payload = &payload[..payload.len().min(MAX_CONTROL_LEN)];
+    let mut frame = heapless::Vec::new();
+    let _ = frame.push(0x80 | opcode as u8);
+    let _ = frame.push(payload.len() as u8);
+    let _ = frame.extend_from_slice(payload);
+    frame
+}
+
+/// Header of the frame being read.
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    opcode: Option<Opcode>,
+    fin: bool,
+    /// All zero for an unmasked frame, so unmasking is a no-op.
+    mask: [u8; 4],
+    len: u64,
+    /// Payload bytes read so far.
+    read: u64,
+}
+
+/// Data message being reassembled from fragments.
+#[derive(Debug, Clone)]
+enum Message {
+    /// A binary message with this many payload bytes so far; the first
+    /// [`IMAGE_LEN`] of them are in the frame buffer.
+    Image(u64),
+    /// A text message with this many payload bytes so far, the first
+    /// [`MAX_TEXT_LEN`] of them kept.
+    Text(heapless::Vec<u8, MAX_TEXT_LEN>, u64),
+}
+
+/// Streaming decoder for client frames.
+///
+/// Clients must mask their frames; unmasked ones are read as they are
+/// rather than closing the connection.
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    /// Header bytes of the next frame, until it is complete.
+    header: heapless::Vec<u8, MAX_HEADER_LEN>,
+    frame: Option<Frame>,
+    message: Option<Message>,
+    /// Payload of the control frame being read.
+    control: heapless::Vec<u8, MAX_CONTROL_LEN>,
+    /// Set when the frame being read is skipped for this reason.
+    skip: Option<Malformed>,
+}
+
+impl Decoder {
+    /// Decoder waiting for the first frame.
+    pub const fn new() -> Self {
+        Self {
+            header: heapless::Vec::new(),
+            frame: None,
+            message: None,
+            control: heapless::Vec::new(),
+            skip: None,
+        }
+    }
+
+    /// Decode from the start of `input` (unmasked in place) up to the
+    /// first event, copying image bytes into `image`. Returns how many
+    /// bytes were used and the event, if any; call again with the rest.
+    /// Uses all of `input` when there is no event.
+    pub fn feed(
+        &mut self,
+        input: &mut [u8],
+        image: &mut [u8; IMAGE_LEN],
+    ) -> (usize, Option<Event>) {
+        let mut pos = 0;
+        loop {
+            let Some(mut frame) = self.frame else {
+                while pos < input.len() && !self.header_complete() {
+                    let _ = self.header.push(input[pos]);
+                    pos += 1;
+                }
+                if !self.header_complete() {
+                    return (pos, None);
+                }
+                if let Some(event) = self.start_frame() {
+                    return (pos, Some(event));
+                }
+                continue;
+            };
+
+            let n = (frame.len - frame.read).min((input.len() - pos) as u64) as usize;
+            let payload = &mut input[pos..pos + n];
+            for (i, byte) in payload.iter_mut().enumerate() {
+                *byte ^= frame.mask[(frame.read as usize + i) % 4];
+            }
+            self.take_payload(&frame, payload, image);
+            frame.read += n as u64;
+            pos += n;
+            if frame.read < frame.len {
+                self.frame = Some(frame);
+                return (pos, None);
+            }
+            self.frame = None;
+            if let Some(event) = self.finish_frame(&frame) {
+                return (pos, Some(event));
+            }
+        }
+    }
+
+    /// True once `header` holds a whole frame header.
+    fn header_complete(&self) -> bool {
+        let h = &self.header;
+        if h.len() < 2 {
+            return false;
+        }
+        let ext = match h[1] & 0x7F {
+            126 => 2,
+            127 => 8,
+            _ => 0,
+        };
+        let mask = if h[1] & 0x80 != 0 { 4 } else { 0 };
+        h.len() >= 2 + ext + mask
+    }
+
+    /// Parse the complete header and decide what to do with the payload.
+    /// Returns an event when the frame cuts an unfinished message short.
+    fn start_frame(&mut self) -> Option<Event> {
+        let h = &self.header;
+        let (len, rest) = match h[1] & 0x7F {
+            126 => (u64::from(u16::from_be_bytes([h[2], h[3]])), &h[4..]),
+            127 => (
+                u64::from_be_bytes(h[2..10].try_into().unwrap_or([0xFF; 8])),
+                &h[10..],
+            ),
+            len => (u64::from(len), &h[2..]),
+        };
+        let mask = rest.try_into().unwrap_or([0; 4]);
+        let opcode_bits = h[0] & 0x0F;
+        let frame = Frame {
+            opcode: Opcode::from_bits(opcode_bits),
+            fin: h[0] & 0x80 != 0,
+            mask,
+            len,
+            read: 0,
+        };
+        self.header.clear();
+        self.control.clear();
+        self.skip = None;
+
+        match frame.opcode {
+            None => self.skip = Some(Malformed::UnknownOpcode(opcode_bits)),
+            Some(op) if op.is_control() => {
+                if !frame.fin || len > MAX_CONTROL_LEN as u64 {
+                    self.skip = Some(Malformed::BadControl);
+                }
+            }
+            Some(Opcode::Continuation) => {
+                if self.message.is_none() {
+                    self.skip = Some(Malformed::UnexpectedContinuation);
+                }
+            }
+            Some(op) => {
+                let interrupted = self.message.is_some();
+                self.message = Some(if op == Opcode::Binary {
+                    Message::Image(0)
+                } else {
+                    Message::Text(heapless::Vec::new(), 0)
+                });
+                self.frame = Some(frame);
+                return interrupted.then_some(Event::Dropped(Malformed::Interrupted));
+            }
+        }
+        self.frame = Some(frame);
+        None
+    }
+
+    /// Route (unmasked) payload bytes to the image, text or control
+    /// buffer.
+    fn take_payload(&mut self, frame: &Frame, payload: &[u8], image: &mut [u8; IMAGE_LEN]) {
+        if self.skip.is_some() {
+            return;
+        }
+        if frame.opcode.is_some_and(Opcode::is_control) {
+            let _ = self.control.extend_from_slice(payload);
+        } else if let Some(Message::Image(len)) = &mut self.message {
+            let start = (*len).min(IMAGE_LEN as u64) as usize;
+            let end = (start + payload.len()).min(IMAGE_LEN);
+            image[start..end].copy_from_slice(&payload[..end - start]);
+            *len += payload.len() as u64;
+        } else if let Some(Message::Text(text, len)) = &mut self.message {
+            let room = MAX_TEXT_LEN - text.len();
+            let _ = text.extend_from_slice(&payload[..payload.len().min(room)]);
+            *len += payload.len() as u64;
+        }
+    }
+
+    /// Event for a fully read frame.
+    fn finish_frame(&mut self, frame: &Frame) -> Option<Event> {
+        if let Some(reason) = self.skip.take() {
+            return Some(Event::Dropped(reason));
+        }
+        match frame.opcode? {
+            Opcode::Ping => Some(Event::Ping(self.control.clone())),
+            Opcode::Close => Some(Event::Close),
+            Opcode::Pong => None,
+            _ if !frame.fin => None,
+            _ => match self.message.take()? {
+                Message::Image(len) if len == IMAGE_LEN as u64 => Some(Event::Frame),
+                Message::Image(len) => Some(Event::Dropped(Malformed::WrongLength(len))),
+                Message::Text(text, len) if len <= MAX_TEXT_LEN as u64 => {
+                    Some(match heapless::String::from_utf8(text) {
+                        Ok(text) => Event::Text(text),
+                        Err(_) => Event::Dropped(Malformed::NotUtf8),
+                    })
+                }
+                Message::Text(_, len) => Some(Event::Dropped(Malformed::TextTooLong(len))),
+            },
+        }
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// SHA-1 digest of `data`.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [
+        0x6745_2301,
+        0xEFCD_AB89,
+        0x98BA_DCFE,
+        0x1032_5476,
+        0xC3D2_E1F0,
+    ];
+    let bit_len = (data.len() as u64) * 8;
+    // Message, 0x80, zero padding, then the 64-bit length, in 64-byte
+    // blocks.
+    let blocks = (data.len() + 9).div_ceil(64);
+    for block in 0..blocks {
+        let mut w = [0u32; 80];
+        for (i, word) in w.iter_mut().take(16).enumerate() {
+            let mut bytes = [0u8; 4];
+            for (j, byte) in bytes.iter_mut().enumerate() {
+                let at = block * 64 + i * 4 + j;
+                *byte = if at < data.len() {
+                    data[at]
+                } else if at == data.len() {
+                    0x80
+                } else if at >= blocks * 64 - 8 {
+                    (bit_len >> ((blocks * 64 - 1 - at) * 8)) as u8
+                } else {
+                    0
+                };
+            }
+            *word = u32::from_be_bytes(bytes);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..20 => ((b & c) | (!b & d), 0x5A82_7999),
+                20..40 => (b ^ c ^ d, 0x6ED9_EBA1),
+                40..60 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+                _ => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+            let t = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+    let mut digest = [0u8; 20];
+    for (chunk, word) in digest.as_chunks_mut::<4>().0.iter_mut().zip(h) {
+        *chunk = word.to_be_bytes();
+    }
+    digest
+}
+
+/// Standard base64 (with padding) of a 20-byte digest.
+fn base64(data: &[u8; 20]) -> heapless::String<ACCEPT_KEY_LEN> {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = heapless::String::new();
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            let c = if i <= chunk.len() {
+                ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char
+            } else {
+                '='
+            };
+            let _ = out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A masked client frame, with room to append more.
+    fn client_frame(first: u8, payload: &[u8]) -> heapless::Vec<u8, { 2 * IMAGE_LEN }> {
+        let mask = [0x12, 0x34, 0x56, 0x78];
+        let mut frame = heapless::Vec::new();
+        frame.push(first).unwrap();
+        match payload.len() {
+            0..=125 => frame.push(0x80 | payload.len() as u8).unwrap(),
+            126..=0xFFFF => {
+                frame.push(0x80 | 126).unwrap();
+                frame
+                    .extend_from_slice(&(payload.len() as u16).to_be_bytes())
+                    .unwrap();
+            }
+            _ => {
+                frame.push(0x80 | 127).unwrap();
+                frame
+                    .extend_from_slice(&(payload.len() as u64).to_be_bytes())
+                    .unwrap();
+            }
+        }
+        frame.extend_from_slice(&mask).unwrap();
+        for (i, &b) in payload.iter().enumerate() {
+            frame.push(b ^ mask[i % 4]).unwrap();
+        }
+        frame
+    }
+
+    /// Feed `input` in `chunk`-byte reads, collecting every event.
+    fn feed_all(
+        decoder: &mut Decoder,
+        input: &mut [u8],
+        chunk: usize,
+        image: &mut [u8; IMAGE_LEN],
+    ) -> heapless::Vec<Event, 8> {
+        let mut events = heapless::Vec::new();
+        for read in input.chunks_mut(chunk) {
+            let mut pos = 0;
+            while pos < read.len() {
+                let (used, event) = decoder.feed(&mut read[pos..], image);
+                pos += used;
+                if let Some(event) = event {
+                    events.push(event).unwrap();
+                }
+            }
+        }
+        events
+    }
+
+    fn test_image() -> [u8; IMAGE_LEN] {
+        core::array::from_fn(|i| (i * 7) as u8)
+    }
+
+    #[test]
+    fn accept_key_matches_rfc_example() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ==").as_str(),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn sha1_pads_across_blocks() {
+        // 56 bytes: the length no longer fits in the first block.
+        let digest = sha1(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq");
+        assert_eq!(&digest[..4], &[0x84, 0x98, 0x3E, 0x44]);
+        assert_eq!(&digest[16..], &[0xE5, 0x46, 0x70, 0xF1]);
+    }
+
+    #[test]
+    fn control_frames_are_unmasked() {
+        assert_eq!(control_frame(Opcode::Pong, b"hi").as_slice(), b"\x8A\x02hi");
+        assert_eq!(control_frame(Opcode::Close, b"").as_slice(), b"\x88\x00");
+    }
+
+    #[test]
+    fn whole_binary_frame_is_an_image() {
+        let expected = test_image();
+        let mut input = client_frame(0x82, &expected);
+        let mut image = [0u8; IMAGE_LEN];
+        let events = feed_all(&mut Decoder::new(), &mut input, 1500, &mut image);
+        assert_eq!(events.as_slice(), [Event::Frame]);
+        assert_eq!(image, expected);
+    }
+
+    #[test]
+    fn fragments_are_reassembled_around_a_ping() {
+        let expected = test_image();
+        let (first, second) = expected.split_at(5000);
+        let mut input = client_frame(0x02, first);
+        input
+            .extend_from_slice(&client_frame(0x89, b"hey"))
+            .unwrap();
+        input
+            .extend_from_slice(&client_frame(0x80, second))
+            .unwrap();
+        let mut image = [0u8; IMAGE_LEN];
+        // Odd read sizes split headers and masks across reads.
+        let events = feed_all(&mut Decoder::new(), &mut input, 7, &mut image);
+        let ping = Event::Ping(heapless::Vec::from_slice(b"hey").unwrap());
+        assert_eq!(events.as_slice(), [ping, Event::Frame]);
+        assert_eq!(image, expected);
+    }
+
+    #[test]
+    fn malformed_messages_are_dropped_and_decoding_continues() {
+        let mut input = client_frame(0x82, &[1, 2, 3]);
+        input
+            .extend_from_slice(&client_frame(0x81, b"seventeen letters"))
+            .unwrap();
+        input
+            .extend_from_slice(&client_frame(0x81, b"\xFF"))
+            .unwrap();
+        input
+            .extend_from_slice(&client_frame(0x80, b"orphan"))
+            .unwrap();
+        input.extend_from_slice(&client_frame(0x83, b"")).unwrap();
+        input.extend_from_slice(&client_frame(0x09, b"")).unwrap();
+        input.extend_from_slice(&client_frame(0x88, b"")).unwrap();
+        let mut image = [0u8; IMAGE_LEN];
+        let events = feed_all(&mut Decoder::new(), &mut input, 64, &mut image);
+        assert_eq!(
+            events.as_slice(),
+            [
+                Event::Dropped(Malformed::WrongLength(3)),
+                Event::Dropped(Malformed::TextTooLong(17)),
+                Event::Dropped(Malformed::NotUtf8),
+                Event::Dropped(Malformed::UnexpectedContinuation),
+                Event::Dropped(Malformed::UnknownOpcode(3)),
+                Event::Dropped(Malformed::BadControl),
+                Event::Close,
+            ]
+        );
+    }
+
+    #[test]
+    fn text_messages_are_passed_on() {
+        let mut input = client_frame(0x01, b"le");
+        input.extend_from_slice(&client_frame(0x80, b"ft")).unwrap();
+        input.extend_from_slice(&client_frame(0x81, b"up")).unwrap();
+        let mut image = [0u8; IMAGE_LEN];
+        let events = feed_all(&mut Decoder::new(), &mut input, 3, &mut image);
+        assert_eq!(
+            events.as_slice(),
+            [
+                Event::Text("left".try_into().unwrap()),
+                Event::Text("up".try_into().unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn oversized_image_is_dropped() {
+        let mut payload = [0u8; IMAGE_LEN + 1];
+        payload[..IMAGE_LEN].copy_from_slice(&test_image());
+        let mut input = client_frame(0x82, &payload);
+        let mut image = [0u8; IMAGE_LEN];
+        let events = feed_all(&mut Decoder::new(), &mut input, 1500, &mut image);
+        let len = IMAGE_LEN as u64 + 1;
+        assert_eq!(
+            events.as_slice(),
+            [Event::Dropped(Malformed::WrongLength(len))]
+        );
+    }
+
+    #[test]
+    fn interrupted_message_is_replaced() {
+        let expected = test_image();
+        let mut input = client_frame(0x02, &[9; 100]);
+        input
+            .extend_from_slice(&client_frame(0x82, &expected))
+            .unwrap();
+        let mut image = [0u8; IMAGE_LEN];
+        let events = feed_all(&mut Decoder::new(), &mut input, 1500, &mut image);
+        let interrupted = Event::Dropped(Malformed::Interrupted);
+        assert_eq!(events.as_slice(), [interrupted, Event::Frame]);
+        assert_eq!(image, expected);
+    }
+}
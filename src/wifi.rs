@@ -1,64 +1,146 @@
 //! WiFi connectivity module using esp-radio 0.17.0
 //!
 //! Handles WiFi connection using the pure Rust esp-radio crate with embassy-net.
+//!
+//! Station credentials come from flash (see [`load_credentials`]). When
+//! none are stored, or the station can't join after
+//! [`STA_CONNECT_RETRIES`] attempts, the radio switches to an open setup
+//! access point ([`SETUP_AP_SSID`]) running a captive portal: DHCP hands
+//! out addresses, DNS resolves every name to the device, and the HTTP
+//! server redirects everything else to `/setup`. Submitting credentials
+//! there saves them and reboots into station mode.
+//!
+//! Once the station first has an address it scrolls across the matrix
+//! [`ADDRESS_SCROLL_PASSES`] times, so users can find the web UI without
+//! looking in their router. Join attempts back off exponentially. Once joined, a dropped
+//! connection shows [`WIFI_LOST_TEXT`] on the matrix and is retried the
+//! same way; the previous text and mode come back when the station
+//! reconnects. [`connect_failures`] reports how many attempts in a row
+//! have failed, and [`joined_ssid`] and [`rssi`] which network is joined
+//! and how strong its signal is.
 
 extern crate alloc;
 
 use core::fmt::Write;
 
 use embassy_executor::Spawner;
-use embassy_net::{Config, Stack, StackResources};
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_net::{
+    Config, IpAddress, IpEndpoint, Ipv4Address, Ipv4Cidr, Stack, StackResources, StaticConfigV4,
+};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
 use esp_radio::Controller;
-use esp_radio::wifi::{ClientConfig, ModeConfig, WifiController, WifiDevice, WifiEvent};
-use log::{error, info};
+use esp_radio::wifi::{
+    AccessPointConfig, ClientConfig, ModeConfig, WifiController, WifiDevice, WifiEvent,
+};
+use esp32_led_matrix::backoff::Backoff;
+use esp32_led_matrix::credentials::{Credentials, MAX_PASSWORD_LEN, MAX_SSID_LEN};
+use esp32_led_matrix::dhcp::{self, DhcpServer};
+use esp32_led_matrix::dns;
+use esp32_led_matrix::events::MAX_EVENT_CLIENTS;
+use esp32_led_matrix::font::Font;
+use esp32_led_matrix::mode::{DEFAULT_MARQUEE_SPEED, DisplayMode};
+use esp32_led_matrix::scroll::ScrollTimer;
+use log::{error, info, warn};
 use static_cell::StaticCell;
 
-use crate::WIFI_PASSWORD;
-use crate::WIFI_SSID;
+use crate::nvs::{self, Slot};
+use crate::{DISPLAY_MODE, DISPLAY_TEXT, MATRIX_WIDTH};
+
+/// SSID of the open access point served while no network is configured.
+pub const SETUP_AP_SSID: &str = "LedMatrix-Setup";
+
+/// Address of the device on the setup access point.
+pub const SETUP_AP_IP: [u8; 4] = [192, 168, 4, 1];
+
+/// Station join attempts before falling back to the setup access point.
+const STA_CONNECT_RETRIES: u32 = 3;
+
+/// Shown on the matrix while the captive portal is up.
+const SETUP_MODE_TEXT: &str = "SETUP MODE";
+
+/// Shown on the matrix while the station is reconnecting.
+const WIFI_LOST_TEXT: &str = "WIFI LOST";
+
+/// Times the station's IP address scrolls across the matrix after the
+/// first join.
+const ADDRESS_SCROLL_PASSES: usize = 2;
+
+/// First wait between reconnection attempts; doubles after each failure.
+const RECONNECT_INITIAL_MS: u32 = 1000;
+
+/// Longest wait between reconnection attempts.
+const RECONNECT_MAX_MS: u32 = 60_000;
+
+/// How often the signal strength of the joined network is read while
+/// connected.
+const RSSI_POLL_SECS: u64 = 10;
+
+/// Failed connection attempts in a row; see [`connect_failures`].
+static CONNECT_FAILURES: Mutex<CriticalSectionRawMutex, u32> = Mutex::new(0);
+
+/// Network the station joined; see [`joined_ssid`].
+static JOINED_SSID: Mutex<CriticalSectionRawMutex, Option<heapless::String<MAX_SSID_LEN>>> =
+    Mutex::new(None);
+
+/// Last signal strength read, in dBm; see [`rssi`].
+static RSSI: Mutex<CriticalSectionRawMutex, Option<i8>> = Mutex::new(None);
 
 /// Global radio controller — must outlive `WifiController` and `WifiDevice`.
 static RADIO_CONTROLLER: StaticCell<Controller<'static>> = StaticCell::new();
 
-/// Global WiFi stack resources (sockets, etc.).
-static WIFI_RESOURCES: StaticCell<StackResources<3>> = StaticCell::new();
+/// Global WiFi stack resources (HTTP, one more HTTP per `/events`
+/// client, DHCP client, mDNS, DNS client and SNTP sockets).
+static WIFI_RESOURCES: StaticCell<StackResources<{ 5 + MAX_EVENT_CLIENTS }>> = StaticCell::new();
+
+/// Setup access point stack resources (HTTP, DHCP and DNS sockets).
+static AP_RESOURCES: StaticCell<StackResources<3>> = StaticCell::new();
 
 /// Global network stack — stored here so the stack value is never dropped.
 /// A `'static` reference is returned to callers (HTTP server, etc.).
 static STACK: StaticCell<Stack<'static>> = StaticCell::new();
 
-/// Initialize WiFi (radio + driver) and the embassy-net stack.
+/// Network stack of the setup access point.
+static AP_STACK: StaticCell<Stack<'static>> = StaticCell::new();
+
+/// Network stacks for both radio interfaces. Only one is up at a time.
+pub struct WifiStacks {
+    /// Station interface, configured by DHCP from the router.
+    pub sta: &'static Stack<'static>,
+    /// Setup access point interface, fixed at [`SETUP_AP_IP`].
+    pub ap: &'static Stack<'static>,
+}
+
+/// Initialize WiFi (radio + driver) and the embassy-net stacks.
 ///
-/// The radio controller and the network stack are stored in `'static` cells so
-/// they live for the entire program lifetime. A reference to the stack is
-/// returned so the HTTP server (and any other user) can accept connections
-/// on it.
+/// The radio controller and the network stacks are stored in `'static`
+/// cells so they live for the entire program lifetime. References to the
+/// stacks are returned so the HTTP server (and any other user) can accept
+/// connections on them.
+///
+/// `credentials` are the stored station credentials, if any; without
+/// them the device starts straight in setup mode.
 ///
 /// Requires `esp_rtos::start()` to have been called before this function so
 /// the embassy executor is running and can host the spawned tasks.
 pub fn init_wifi_inline(
     spawner: Spawner,
     wifi: esp_hal::peripherals::WIFI<'static>,
-) -> &'static Stack<'static> {
+    credentials: Option<Credentials>,
+) -> WifiStacks {
     // Initialize the radio controller (requires RTOS scheduler to be running).
     let controller: Controller<'static> = esp_radio::init().unwrap();
     let controller = RADIO_CONTROLLER.init(controller);
 
     // Create WiFi interface with default hardware config (buffer sizes etc.).
-    let (mut wifi_controller, interfaces) =
+    // The mode (station or setup AP) is chosen by the connection task.
+    let (wifi_controller, interfaces) =
         esp_radio::wifi::new(controller, wifi, esp_radio::wifi::Config::default()).unwrap();
 
-    // Configure station (client) mode with SSID and password.
-    wifi_controller
-        .set_config(&ModeConfig::Client(
-            ClientConfig::default()
-                .with_ssid(alloc::string::String::from(WIFI_SSID))
-                .with_password(alloc::string::String::from(WIFI_PASSWORD)),
-        ))
-        .unwrap();
-
-    // Build the network stack with DHCP (IP address assigned by router).
+    // Build the station stack with DHCP (IP address assigned by router).
     let stack_config = Config::dhcpv4(Default::default());
-    let stack_resources = WIFI_RESOURCES.init(StackResources::<3>::new());
+    let stack_resources = WIFI_RESOURCES.init(StackResources::new());
 
     let (stack, runner) = embassy_net::new(
         interfaces.sta,
@@ -67,21 +149,148 @@ pub fn init_wifi_inline(
         1234, // Random seed
     );
 
-    // Spawn the network runner task (drives the stack + DHCP socket)
-    // and the WiFi connection task (associates and reconnects as needed).
+    // The setup AP stack has a fixed address; clients get theirs from
+    // `dhcp_server_task`.
+    let [a, b, c, d] = SETUP_AP_IP;
+    let ap_config = Config::ipv4_static(StaticConfigV4 {
+        address: Ipv4Cidr::new(Ipv4Address::new(a, b, c, d), 24),
+        gateway: None,
+        dns_servers: Default::default(),
+    });
+    let ap_resources = AP_RESOURCES.init(StackResources::<3>::new());
+    let (ap_stack, ap_runner) = embassy_net::new(interfaces.ap, ap_config, ap_resources, 4321);
+
+    let stack = STACK.init(stack);
+    let ap_stack = AP_STACK.init(ap_stack);
+
+    // Spawn the network runner tasks (drive the stacks + DHCP), the setup
+    // portal's DHCP and DNS servers, and the WiFi connection task (picks
+    // the mode, associates and reconnects as needed).
     spawner.spawn(net_task(runner)).ok();
-    spawner.spawn(wifi_connection_task(wifi_controller)).ok();
+    spawner.spawn(net_task(ap_runner)).ok();
+    spawner.spawn(dhcp_server_task(ap_stack)).ok();
+    spawner.spawn(dns_server_task(ap_stack)).ok();
+    spawner
+        .spawn(wifi_connection_task(wifi_controller, credentials))
+        .ok();
 
-    // Park the stack in a `'static` cell and hand out a reference.
-    STACK.init(stack)
+    WifiStacks {
+        sta: stack,
+        ap: ap_stack,
+    }
 }
 
-/// Network runner task — drives the embassy-net stack (DHCP, ARP, etc.).
-#[embassy_executor::task]
+/// Save station credentials to flash so the next boot uses them.
+pub async fn save_credentials(credentials: &Credentials) {
+    nvs::save(Slot::Ssid, &credentials.ssid).await;
+    nvs::save(Slot::Password, &credentials.password).await;
+}
+
+/// Load the station credentials saved by [`save_credentials`]. `None`
+/// if nothing valid is stored.
+pub async fn load_credentials() -> Option<Credentials> {
+    let ssid: heapless::String<MAX_SSID_LEN> = nvs::load(Slot::Ssid).await?;
+    let password: heapless::String<MAX_PASSWORD_LEN> = nvs::load(Slot::Password).await?;
+    Credentials::new(&ssid, &password)
+}
+
+/// Station connection attempts that have failed in a row, 0 while
+/// connected (or before the first attempt).
+pub async fn connect_failures() -> u32 {
+    *CONNECT_FAILURES.lock().await
+}
+
+/// The network the station joined, `None` before it first has (and in
+/// setup mode).
+pub async fn joined_ssid() -> Option<heapless::String<MAX_SSID_LEN>> {
+    JOINED_SSID.lock().await.clone()
+}
+
+/// Signal strength of the joined network in dBm, read every
+/// [`RSSI_POLL_SECS`]. `None` while disconnected or if the radio can't
+/// tell.
+pub async fn rssi() -> Option<i8> {
+    *RSSI.lock().await
+}
+
+/// Network runner task — drives an embassy-net stack (DHCP, ARP, etc.).
+/// One instance per interface.
+#[embassy_executor::task(pool_size = 2)]
 async fn net_task(mut runner: embassy_net::Runner<'static, WifiDevice<'static>>) {
     runner.run().await
 }
 
+/// Answer DHCP requests on the setup access point so joining clients get
+/// an address. Idle while the AP is down.
+#[embassy_executor::task]
+async fn dhcp_server_task(stack: &'static Stack<'static>) {
+    let mut rx_meta = [PacketMetadata::EMPTY; 4];
+    let mut rx_buffer = [0u8; 1024];
+    let mut tx_meta = [PacketMetadata::EMPTY; 4];
+    let mut tx_buffer = [0u8; 1024];
+    let mut socket = UdpSocket::new(
+        *stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    if let Err(e) = socket.bind(dhcp::SERVER_PORT) {
+        error!("DHCP server bind error: {:?}", e);
+        return;
+    }
+
+    let mut server = DhcpServer::new(SETUP_AP_IP);
+    let mut request = [0u8; 576];
+    let mut reply = [0u8; dhcp::REPLY_LEN];
+    // Clients have no address yet, so replies are broadcast.
+    let broadcast = IpEndpoint::new(IpAddress::Ipv4(Ipv4Address::BROADCAST), dhcp::CLIENT_PORT);
+    loop {
+        let Ok((len, _)) = socket.recv_from(&mut request).await else {
+            continue;
+        };
+        if let Some(reply_len) = server.handle(&request[..len], &mut reply)
+            && let Err(e) = socket.send_to(&reply[..reply_len], broadcast).await
+        {
+            warn!("DHCP reply failed: {:?}", e);
+        }
+    }
+}
+
+/// Answer every DNS query on the setup access point with
+/// [`SETUP_AP_IP`], so OS connectivity probes land on the HTTP server.
+#[embassy_executor::task]
+async fn dns_server_task(stack: &'static Stack<'static>) {
+    let mut rx_meta = [PacketMetadata::EMPTY; 4];
+    let mut rx_buffer = [0u8; 1024];
+    let mut tx_meta = [PacketMetadata::EMPTY; 4];
+    let mut tx_buffer = [0u8; 1024];
+    let mut socket = UdpSocket::new(
+        *stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    if let Err(e) = socket.bind(dns::DNS_PORT) {
+        error!("DNS server bind error: {:?}", e);
+        return;
+    }
+
+    let mut query = [0u8; dns::MAX_PACKET_LEN];
+    let mut reply = [0u8; dns::MAX_PACKET_LEN];
+    loop {
+        let Ok((len, meta)) = socket.recv_from(&mut query).await else {
+            continue;
+        };
+        if let Some(reply_len) = dns::answer(&query[..len], SETUP_AP_IP, &mut reply)
+            && let Err(e) = socket.send_to(&reply[..reply_len], meta.endpoint).await
+        {
+            warn!("DNS reply failed: {:?}", e);
+        }
+    }
+}
+
 /// Wait until WiFi is associated and a DHCP lease has been acquired.
 ///
 /// Polls the embassy-net stack for link-up state and an IPv4 configuration.
@@ -101,11 +310,15 @@ pub async fn wait_for_connection(stack: &Stack<'static>) {
 
 /// Get the current IPv4 address as a printable `"x.x.x.x"` string.
 ///
-/// Returns `None` if the stack doesn't yet have an IPv4 address
-/// (no DHCP lease yet, or the link is down).
+/// Returns `None` if the stack doesn't yet have a usable IPv4 address:
+/// no DHCP lease yet, the link is down, or only an unspecified
+/// (`0.0.0.0`) or link-local (`169.254.x.x`) address is configured.
 pub fn get_ip_address(stack: &Stack<'static>) -> Option<heapless::String<16>> {
     let config = stack.config_v4()?;
     let addr = config.address.address(); // embassy_net::Ipv4Address (smoltcp) — `address()` returns the address from a CIDR
+    if addr.is_unspecified() || addr.is_link_local() {
+        return None;
+    }
     let mut s: heapless::String<16> = heapless::String::new();
     // `smoltcp::wire::Ipv4Address` implements `core::fmt::Display` as dotted
     // decimal. `"255.255.255.255"` is the longest possible form (15 chars +
@@ -114,29 +327,196 @@ pub fn get_ip_address(stack: &Stack<'static>) -> Option<heapless::String<16>> {
     Some(s)
 }
 
-/// WiFi connection task — starts WiFi, then connects and reconnects as needed.
+/// WiFi connection task — picks station or setup mode, then connects and
+/// reconnects as needed.
 ///
-/// `WifiController::connect_async` resolves once the station is associated to
-/// the AP. We then wait for a `StaDisconnected` event and try again.
+/// With credentials, tries to join up to [`STA_CONNECT_RETRIES`] times.
+/// Once joined it reconnects forever, as before. If it never joins (or
+/// there were no credentials) it serves the setup access point until the
+/// `/setup` handler reboots the device.
 #[embassy_executor::task]
-async fn wifi_connection_task(mut controller: WifiController<'static>) {
+async fn wifi_connection_task(
+    mut controller: WifiController<'static>,
+    credentials: Option<Credentials>,
+) {
     info!("WiFi connection task started");
 
-    controller.start_async().await.unwrap();
+    if let Some(creds) = &credentials
+        && join_station(&mut controller, creds).await
+    {
+        *JOINED_SSID.lock().await = Some(creds.ssid.clone());
+        stay_connected(&mut controller).await;
+    }
 
+    run_setup_ap(&mut controller).await;
+    // Keep the controller (and with it the AP) alive until reboot.
+    core::future::pending::<()>().await;
+}
+
+/// Switch to station mode and try to join `creds`' network, backing off
+/// between attempts. Returns `true` once associated, `false` after
+/// [`STA_CONNECT_RETRIES`] failures.
+async fn join_station(controller: &mut WifiController<'static>, creds: &Credentials) -> bool {
+    restart_with(
+        controller,
+        ModeConfig::Client(
+            ClientConfig::default()
+                .with_ssid(alloc::string::String::from(creds.ssid.as_str()))
+                .with_password(alloc::string::String::from(creds.password.as_str())),
+        ),
+    )
+    .await;
+
+    let mut backoff = Backoff::new(RECONNECT_INITIAL_MS, RECONNECT_MAX_MS);
+    while backoff.failures() < STA_CONNECT_RETRIES {
+        info!(
+            "Connecting to SSID: {} (attempt {})",
+            creds.ssid,
+            backoff.failures() + 1
+        );
+        if try_connect(controller, &mut backoff).await {
+            return true;
+        }
+    }
+    warn!("Could not join {}, starting setup access point", creds.ssid);
+    false
+}
+
+/// Keep the station connected forever, reconnecting after drops with
+/// exponential backoff. [`WIFI_LOST_TEXT`] is shown while disconnected.
+async fn stay_connected(controller: &mut WifiController<'static>) -> ! {
+    let mut backoff = Backoff::new(RECONNECT_INITIAL_MS, RECONNECT_MAX_MS);
     loop {
-        info!("Connecting to SSID: {}", WIFI_SSID);
-        match controller.connect_async().await {
-            Ok(()) => {
-                info!("WiFi connected!");
-                // Wait until disconnected before attempting reconnect.
-                controller.wait_for_event(WifiEvent::StaDisconnected).await;
-                info!("WiFi disconnected, reconnecting...");
-            }
-            Err(e) => {
-                error!("WiFi connect error: {:?}", e);
-                embassy_time::Timer::after(embassy_time::Duration::from_secs(2)).await;
+        // Wait until disconnected before attempting reconnect, reading
+        // the signal strength now and then meanwhile.
+        loop {
+            record_rssi(controller).await;
+            let disconnected = embassy_time::with_timeout(
+                embassy_time::Duration::from_secs(RSSI_POLL_SECS),
+                controller.wait_for_event(WifiEvent::StaDisconnected),
+            )
+            .await;
+            if disconnected.is_ok() {
+                break;
             }
         }
+        *RSSI.lock().await = None;
+        warn!("WiFi disconnected, reconnecting...");
+        let shown = show_wifi_lost().await;
+        while !try_connect(controller, &mut backoff).await {}
+        restore_display(shown).await;
+    }
+}
+
+/// Read the joined network's signal strength into [`RSSI`].
+async fn record_rssi(controller: &WifiController<'static>) {
+    let rssi = controller
+        .rssi()
+        .ok()
+        .and_then(|dbm| i8::try_from(dbm).ok());
+    *RSSI.lock().await = rssi;
+}
+
+/// One connection attempt. On failure, logs the error, records it in
+/// [`CONNECT_FAILURES`] and waits out `backoff`'s next delay; on success
+/// resets both.
+async fn try_connect(controller: &mut WifiController<'static>, backoff: &mut Backoff) -> bool {
+    match controller.connect_async().await {
+        Ok(()) => {
+            backoff.reset();
+            *CONNECT_FAILURES.lock().await = 0;
+            info!("WiFi connected!");
+            true
+        }
+        Err(e) => {
+            let delay = backoff.next_delay_ms();
+            *CONNECT_FAILURES.lock().await = backoff.failures();
+            error!("WiFi connect error: {:?}, retrying in {} ms", e, delay);
+            embassy_time::Timer::after_millis(delay.into()).await;
+            false
+        }
+    }
+}
+
+/// Replace the display with [`WIFI_LOST_TEXT`], returning the text and
+/// mode it showed so [`restore_display`] can put them back.
+async fn show_wifi_lost() -> (heapless::String<32>, DisplayMode) {
+    show_notice(WIFI_LOST_TEXT, DisplayMode::Text).await
+}
+
+/// Show `notice` in `mode`, returning the text and mode it replaced.
+async fn show_notice(notice: &str, mode: DisplayMode) -> (heapless::String<32>, DisplayMode) {
+    let mode = core::mem::replace(&mut *DISPLAY_MODE.lock().await, mode);
+    let mut display_text = DISPLAY_TEXT.lock().await;
+    let text = display_text.clone();
+    display_text.clear();
+    let _ = display_text.push_str(notice);
+    (text, mode)
+}
+
+/// Bring back what [`show_wifi_lost`] replaced.
+async fn restore_display((text, mode): (heapless::String<32>, DisplayMode)) {
+    *DISPLAY_TEXT.lock().await = text;
+    *DISPLAY_MODE.lock().await = mode;
+}
+
+/// Bring up the open setup access point and show [`SETUP_MODE_TEXT`].
+/// The text isn't saved to flash, so the old message returns after the
+/// reboot into station mode.
+async fn run_setup_ap(controller: &mut WifiController<'static>) {
+    restart_with(
+        controller,
+        ModeConfig::AccessPoint(
+            AccessPointConfig::default().with_ssid(alloc::string::String::from(SETUP_AP_SSID)),
+        ),
+    )
+    .await;
+    let [a, b, c, d] = SETUP_AP_IP;
+    info!(
+        "Setup mode: join WiFi \"{}\" and open http://{}.{}.{}.{}/setup",
+        SETUP_AP_SSID, a, b, c, d
+    );
+    let mut display_text = DISPLAY_TEXT.lock().await;
+    display_text.clear();
+    let _ = display_text.push_str(SETUP_MODE_TEXT);
+}
+
+/// Stop the radio (if running), apply `config` and start it again.
+async fn restart_with(controller: &mut WifiController<'static>, config: ModeConfig) {
+    if controller.is_started().unwrap_or(false)
+        && let Err(e) = controller.stop_async().await
+    {
+        warn!("WiFi stop error: {:?}", e);
+    }
+    controller.set_config(&config).unwrap();
+    controller.start_async().await.unwrap();
+}
+
+/// Log the station address once DHCP has first assigned one and scroll
+/// it across the matrix [`ADDRESS_SCROLL_PASSES`] times. The previous
+/// text and mode come back afterwards unless something else replaced
+/// the address in the meantime (a `/text` request, say). Reconnects
+/// don't show it again.
+#[embassy_executor::task]
+pub async fn show_address_task(stack: &'static Stack<'static>) {
+    wait_for_connection(stack).await;
+    let Some(ip) = get_ip_address(stack) else {
+        warn!("WiFi ready, but no usable IP address");
+        return;
+    };
+    info!("IP Address: http://{}/", ip);
+    let marquee = DisplayMode::Marquee {
+        speed: DEFAULT_MARQUEE_SPEED,
+    };
+    let shown = show_notice(&ip, marquee).await;
+    // One pass: the text enters on the right and fully leaves on the left.
+    let pass = Font::new().text_width(&ip, 1) + MATRIX_WIDTH;
+    let duration_ms =
+        ScrollTimer::new(DEFAULT_MARQUEE_SPEED.into()).duration_ms(pass * ADDRESS_SCROLL_PASSES);
+    embassy_time::Timer::after_millis(duration_ms).await;
+    let still_shown =
+        *DISPLAY_TEXT.lock().await == ip.as_str() && *DISPLAY_MODE.lock().await == marquee;
+    if still_shown {
+        restore_display(shown).await;
     }
 }
@@ -11,17 +11,18 @@ use esp32_led_matrix::chain_mapper::{
     CHAIN_LEN, ICS_PER_CHAIN, LEDS_PER_IC, SCANLINES, compute_chain_data, compute_full_frame,
 };
 use esp32_led_matrix::font::Font;
-use esp32_led_matrix::frame_buffer::FrameBuffer;
+use esp32_led_matrix::frame_buffer::{FrameBuffer, load_pixel};
 use esp32_led_matrix::http_request::{MAX_MESSAGE_LEN, NOT_FOUND_RESPONSE, dispatch, html_page};
-use esp32_led_matrix::{MATRIX_HEIGHT, MATRIX_WIDTH};
+use esp32_led_matrix::{MATRIX_HEIGHT, MATRIX_WIDTH, PANEL_WIDTH, PANELS};
 
 #[test]
 fn public_constants_hold_invariants() {
-    assert_eq!(MATRIX_WIDTH, 88);
+    assert_eq!(PANEL_WIDTH, 88);
+    assert_eq!(MATRIX_WIDTH, PANEL_WIDTH * PANELS);
     assert_eq!(MATRIX_HEIGHT, 88);
     assert!(MATRIX_WIDTH > 0 && MATRIX_HEIGHT > 0);
     assert!(SCANLINES > 0 && LEDS_PER_IC > 0 && ICS_PER_CHAIN > 0);
-    assert_eq!(CHAIN_LEN, 44);
+    assert_eq!(CHAIN_LEN, 44 * PANELS);
     // CHAIN_LEN must equal 2 * ICS_PER_CHAIN because each chain holds two
     // pixel groups per cycle (top/bottom row groups).
     assert_eq!(CHAIN_LEN, 2 * ICS_PER_CHAIN);
@@ -32,8 +33,8 @@ fn frame_buffer_round_trip_matches_constant_pixel_value() {
     let mut fb = FrameBuffer::new();
     fb.set_pixel(0, 0, 11, 22, 33);
     fb.set_pixel(87, 87, 44, 55, 66);
-    assert_eq!(fb.get_pixel(0, 0), [11, 22, 33]);
-    assert_eq!(fb.get_pixel(87, 87), [44, 55, 66]);
+    assert_eq!(fb.get_pixel(0, 0), load_pixel([11, 22, 33]));
+    assert_eq!(fb.get_pixel(87, 87), load_pixel([44, 55, 66]));
     // Unknown coordinates are black.
     assert_eq!(fb.get_pixel(MATRIX_WIDTH, 0), [0, 0, 0]);
     assert_eq!(fb.get_pixel(0, MATRIX_HEIGHT), [0, 0, 0]);
@@ -147,7 +148,7 @@ fn frame_buffer_then_chain_mapper_round_trip_uniform_color() {
     // Paint a non-black uniform color.
     for y in 0..MATRIX_HEIGHT {
         for x in 0..MATRIX_WIDTH {
-            fb.set_pixel(x, y, 0x1111, 0x2222, 0x3333);
+            fb.set_pixel_16(x as i32, y as i32, 0x1111, 0x2222, 0x3333);
         }
     }
     let mut data = [[0u16; 3]; CHAIN_LEN];